@@ -0,0 +1,187 @@
+# Response items shared by several route modules
+
+from datetime import datetime
+from typing import List, Optional
+
+from fastapi import HTTPException
+
+from textbook.chapter_quiz import ChapterQuizProgress
+from textbook.cross_reference import entity_anchor
+from textbook.database import BookInfo, CardStateInfo, ChapterInfo, EntityInfo, ExerciseInfo, FlashcardInfo, ProblemInfo, ReadingItemInfo
+from textbook.problems import problem_figures
+from textbook.pipeline import PipelineStatus
+from textbook.problem_quality import problem_issues
+from textbook.reader import EXERCISE_TYPE_TEXT
+from textbook.review import GRADE_LABELS, card_schedule, preview_intervals
+from textbook.status import STATUS_ALL, STATUS_FILTERS, item_status, matches_status
+
+from api.models import ChapterQuizItem, EntityItem, ExerciseItem, FlashcardItem, GradeIntervalItem, PipelineNodeItem, PipelineResponse, ProblemItem, ReadingItem, ReviewCardItem, StatusResponse
+from api.state import state
+
+
+def entity_item(entity: EntityInfo) -> EntityItem:
+    return EntityItem(
+        entity_id=entity.entity_id,
+        kind=entity.entity_kind,
+        label=entity.entity_label,
+        name=entity.entity_name,
+        statement=entity.statement,
+        page_number=entity.page_number,
+        chapter_id=entity.chapter_id,
+        section_id=entity.section_id,
+        source=entity.entity_source,
+        anchor=entity_anchor(entity.entity_id),
+    )
+
+
+def exercise_item(exercise: ExerciseInfo) -> ExerciseItem:
+    return ExerciseItem(
+        exercise_id=exercise.exercise_id,
+        book_id=exercise.book_id,
+        page_number=exercise.page_number,
+        exercise_type=exercise.exercise_type or EXERCISE_TYPE_TEXT,
+        description=exercise.exercise_description,
+        starter_code=exercise.starter_code,
+        solution_code=exercise.solution_code,
+        code_language=exercise.code_language,
+        difficulty_level=exercise.details.difficulty_level if exercise.details else None,
+    )
+
+
+def problem_item(problem: ProblemInfo) -> ProblemItem:
+    return ProblemItem(
+        problem_id=problem.problem_id,
+        number=problem.problem_number,
+        statement=problem.statement,
+        difficulty_hint=problem.difficulty_hint,
+        figures=problem_figures(problem),
+        page_number=problem.page_number,
+        section_index=problem.section_index,
+        quality_score=problem.quality_score,
+        quality_status=problem.quality_status,
+        quality_issues=problem_issues(problem),
+        chemical_formula=problem.chemical_formula,
+        smiles=problem.smiles,
+        model_name=problem.model_name,
+        temperature=problem.temperature,
+        seed=problem.seed,
+        prompt_version=problem.prompt_version,
+        source_sha256=problem.source_sha256,
+        created_at=problem.created_at,
+    )
+
+
+def review_card_items(batch: List[tuple[FlashcardInfo, Optional[CardStateInfo]]], now: datetime) -> List[ReviewCardItem]:
+    # Everything a client needs to show the cards, so a review session takes a single request
+    if not state.database:
+        raise HTTPException(status_code=500, detail="Context not initialized")
+    cards = [card for card, _ in batch]
+    with state.database.new_session() as session:
+        books = {book.book_id: book.book_name for book in session.query(BookInfo).filter(BookInfo.book_id.in_({card.book_id for card in cards}))}
+        chapters = {chapter.chapter_id: chapter.title for chapter in session.query(ChapterInfo).filter(ChapterInfo.chapter_id.in_({card.chapter_id for card in cards if card.chapter_id is not None}))}
+        entities = {entity.entity_id: entity_item(entity) for entity in session.query(EntityInfo).filter(EntityInfo.entity_id.in_({card.entity_id for card in cards if card.entity_id is not None}))}
+
+    items = []
+    for card, card_state in batch:
+        intervals = preview_intervals(card_schedule(card_state), now)
+        items.append(ReviewCardItem(
+            card_id=card.card_id,
+            book_id=card.book_id,
+            book_name=books.get(card.book_id),
+            front=card.front,
+            back=card.back,
+            entity=entities.get(card.entity_id) if card.entity_id is not None else None,
+            chapter_id=card.chapter_id,
+            chapter_title=chapters.get(card.chapter_id) if card.chapter_id is not None else None,
+            page_number=card.page_number,
+            chemical_formula=card.chemical_formula,
+            smiles=card.smiles,
+            is_new=card_state is None,
+            due_at=card_state.due_at if card_state else None,
+            interval_days=card_state.interval_days if card_state else None,
+            repetitions=card_state.repetitions if card_state else 0,
+            lapses=card_state.lapses if card_state else 0,
+            grade_intervals=[GradeIntervalItem(grade=grade, label=label, interval_days=intervals[grade]) for grade, label in GRADE_LABELS.items()],
+        ))
+    return items
+
+
+def reading_item(item: ReadingItemInfo, chapter_quiz: Optional[ChapterQuizItem] = None) -> ReadingItem:
+    return ReadingItem(
+        reading_id=item.reading_id,
+        user_id=item.user_id,
+        book_id=item.book_id,
+        section_id=item.section_id,
+        page_number=item.page_number,
+        title=item.title,
+        text=item.text,
+        interval_days=item.interval_days,
+        read_count=item.read_count,
+        due_at=item.due_at,
+        last_read_at=item.last_read_at,
+        done_at=item.done_at,
+        chapter_quiz=chapter_quiz,
+    )
+
+
+def chapter_quiz_item(progress: ChapterQuizProgress) -> ChapterQuizItem:
+    return ChapterQuizItem(
+        quiz_id=progress.quiz.quiz_id,
+        user_id=progress.quiz.user_id,
+        book_id=progress.quiz.book_id,
+        chapter_id=progress.quiz.chapter_id,
+        exercise_ids=progress.exercise_ids,
+        answered=len(progress.scores),
+        score=progress.score,
+        created_at=progress.quiz.created_at,
+        completed_at=progress.quiz.completed_at,
+    )
+
+
+def pipeline_response(status: PipelineStatus, message: Optional[str] = None) -> PipelineResponse:
+    return PipelineResponse(
+        pipeline_id=status.pipeline_id,
+        status=status.status,
+        nodes=[PipelineNodeItem(name=node.name, kind=node.kind, status=node.status, depends_on=node.depends_on, job_id=node.job_id) for node in status.nodes],
+        message=message,
+    )
+
+
+def flashcard_items(book_id: int, cards: List[FlashcardInfo], status_filter: Optional[str] = STATUS_ALL) -> List[FlashcardItem]:
+    if not state.database:
+        raise HTTPException(status_code=500, detail="Context not initialized")
+    book = state.database.get_book_by_id(book_id)
+    chapters = {chapter.chapter_id: chapter for chapter in state.database.get_chapters_by_book_id(book_id)}
+    items = []
+    for card in cards:
+        status = item_status(card, chapters.get(card.chapter_id), book)
+        if not matches_status(status, status_filter):
+            continue
+        items.append(FlashcardItem(
+            card_id=card.card_id,
+            front=card.front,
+            back=card.back,
+            entity_id=card.entity_id,
+            entity_kind=card.entity_kind,
+            chapter_id=card.chapter_id,
+            page_number=card.page_number,
+            chemical_formula=card.chemical_formula,
+            smiles=card.smiles,
+            status=status,
+        ))
+    return items
+
+
+def check_status_filter(status: Optional[str]) -> None:
+    if status is not None and status not in STATUS_FILTERS:
+        raise HTTPException(status_code=400, detail=f"Invalid status '{status}', expected one of {', '.join(STATUS_FILTERS)}")
+
+
+def status_response(item_type: str, item_id: int, item, *parents) -> StatusResponse:
+    return StatusResponse(
+        item_type=item_type,
+        item_id=item_id,
+        archived_at=item.archived_at,
+        suspended_at=item.suspended_at,
+        status=item_status(item, *parents),
+    )
@@ -1,16 +1,286 @@
+from datetime import date, datetime
 from pydantic import BaseModel, Field
-from typing import Optional, List
+from typing import Any, Dict, Optional, List
 
 class UploadBookResponse(BaseModel):
     book_id: int
     message: str
 
 
+class DocumentFromUrlRequest(BaseModel):
+    url: str = Field(..., description="URL of the article to ingest")
+    generate_summaries: bool = Field(default=False, description="Whether to generate page summaries right away")
+
+
 class DeleteBookResponse(BaseModel):
     book_id: int
     message: str
     deleted: bool
 
+
+class DocumentItem(BaseModel):
+    document_id: int
+    title: str
+    author: Optional[str] = None
+    source_path: Optional[str] = None  # relative to the uploads directory, or the URL of the source
+    page_count: Optional[int] = None
+    ocr_status: str  # pending, running, done, failed or not_needed
+    detected_type: str  # textbook, problem_set, article, transcript, notebook or unknown
+    tags: List[str] = []
+    job_id: Optional[str] = None
+    book_id: Optional[int] = None
+    created_at: datetime
+    updated_at: datetime
+
+
+class DocumentsResponse(BaseModel):
+    documents: List[DocumentItem]
+
+
+class RenameDocumentRequest(BaseModel):
+    title: str = Field(..., description="New title of the document and its book")
+    author: Optional[str] = Field(default=None, description="New author, unchanged when omitted")
+
+
+class TagDocumentRequest(BaseModel):
+    add: List[str] = Field(default=[], description="Tags to add")
+    remove: List[str] = Field(default=[], description="Tags to remove")
+
+
+class IngestionSettingsItem(BaseModel):
+    ocr_language: str
+    parse_method: str  # auto, txt or ocr
+    problems_per_section: Optional[int] = None
+    target_difficulty: Optional[int] = None
+
+
+class UpdateDocumentSettingsRequest(BaseModel):
+    ocr_language: Optional[str] = Field(default=None, description="MinerU language code of the document")
+    parse_method: Optional[str] = Field(default=None, description="auto, txt or ocr")
+    problems_per_section: Optional[int] = Field(default=None, description="Keep at most this many problems per chapter")
+    target_difficulty: Optional[int] = Field(default=None, description="Difficulty from 1 to 5 the problems kept are picked around")
+
+
+class DocumentSettingsResponse(BaseModel):
+    document_id: int
+    settings: IngestionSettingsItem
+    overridden: List[str]  # The settings that do not follow config.toml
+
+
+class DeleteDocumentResponse(BaseModel):
+    document_id: int
+    book_id: Optional[int] = None
+    message: str
+    deleted: bool
+
+
+class ContentChunkItem(BaseModel):
+    kind: str  # page or section
+    index: int  # page number of pages, position among the sections of sections
+    title: Optional[str] = None
+    level: Optional[int] = None
+    page_start: Optional[int] = None
+    page_end: Optional[int] = None
+    char_start: int  # offsets in the document's markdown file
+    char_end: int
+    text: Optional[str] = None  # left out of listings
+
+
+class DocumentSectionsResponse(BaseModel):
+    document_id: int
+    sections: List[ContentChunkItem]
+
+
+class DocumentSummaryResponse(BaseModel):
+    document_id: int
+    summary: str
+
+
+class GlossaryTermItem(BaseModel):
+    term: str
+    definition: str
+    page_number: Optional[int] = None
+
+
+class GlossaryResponse(BaseModel):
+    document_id: int
+    terms: List[GlossaryTermItem]
+
+
+class ExplainPassageRequest(BaseModel):
+    char_start: int = Field(..., ge=0, description="Where the selected passage starts in the document's markdown, as the offsets of its pages and sections")
+    char_end: int = Field(..., ge=0, description="Where the selected passage ends, exclusive")
+    question: Optional[str] = Field(default=None, description="What the learner asks about the passage, by default what it means")
+    session_id: Optional[str] = Field(default=None, min_length=1, description="Tutoring session to build on and to save the exchange into")
+    style: Optional[str] = Field(default=None, description="Explanation style: analogy, eli5, rigorous or visual-description, by default a plain explanation")
+
+
+class ExplanationSourceItem(BaseModel):
+    kind: str  # page or section
+    index: int
+    title: Optional[str] = None
+    page_start: Optional[int] = None
+    score: float
+
+
+class ExplanationSourcesEvent(BaseModel):
+    document_id: int
+    selection: str
+    sources: List[ExplanationSourceItem]
+
+
+class ExplanationDoneEvent(BaseModel):
+    session_id: Optional[str] = None
+    message_ids: List[int] = []  # of the question and the explanation, when saved into a session
+
+
+class TutoringMessageItem(BaseModel):
+    message_id: int
+    role: str  # learner or tutor
+    content: str
+    char_start: Optional[int] = None
+    char_end: Optional[int] = None
+    document_id: int
+    created_at: datetime
+
+
+class TutoringSessionResponse(BaseModel):
+    session_id: str
+    messages: List[TutoringMessageItem]
+
+
+class TocEntryItem(BaseModel):
+    title: str
+    page_number: int
+    level: int = Field(description="1 for chapters, deeper for numbered sections like 2.3")
+
+
+class DocumentTocResponse(BaseModel):
+    document_id: int
+    has_toc: Optional[bool] = Field(default=None, description="None for documents without OCR output yet")
+    entries: List[TocEntryItem]
+
+
+class SegmentDocumentRequest(BaseModel):
+    use_llm: bool = Field(default=True, description="Have the LLM review the headings found, by default; without it the headings are only inferred from numbering and the table of contents")
+
+
+class OutlineNodeItem(BaseModel):
+    section_index: int
+    title: str
+    level: int
+    page_start: Optional[int] = None
+    page_end: Optional[int] = None
+    children: List["OutlineNodeItem"] = []
+
+
+class DocumentOutlineResponse(BaseModel):
+    document_id: int
+    chapters: List[OutlineNodeItem]
+
+
+class ExtractProblemsRequest(BaseModel):
+    section_index: Optional[int] = Field(default=None, description="Only extract the problems of this section, by default those of every chapter")
+
+
+class ProblemItem(BaseModel):
+    problem_id: int
+    number: Optional[str] = None  # as printed, e.g. 3.2 or 7b
+    statement: str
+    difficulty_hint: Optional[str] = None  # as marked in the text, e.g. * or hard
+    figures: List[str]  # labels of the figures and tables referred to
+    page_number: Optional[int] = None
+    section_index: Optional[int] = None  # the chapter it was extracted from
+    quality_score: Optional[float] = None  # between 0 and 1, None until checked
+    quality_status: Optional[str] = None  # passed, flagged, approved or rejected
+    quality_issues: List[str] = []
+    chemical_formula: Optional[str] = None  # of the compound asked for, in Hill order, e.g. C2H6O
+    smiles: Optional[str] = None  # canonical, for clients to render the structure
+    model_name: Optional[str] = None  # what it was extracted with, to extract it again identically
+    temperature: Optional[float] = None
+    seed: Optional[int] = None
+    prompt_version: Optional[int] = None
+    source_sha256: Optional[str] = None  # of the chapter text it was extracted from
+    created_at: datetime
+
+
+class ProblemsResponse(BaseModel):
+    document_id: int
+    problems: List[ProblemItem]
+
+
+class QualityReviewResponse(BaseModel):
+    document_id: Optional[int] = None
+    problems: List[ProblemItem]  # flagged by the quality checks, lowest score first
+
+
+class QualityReviewRequest(BaseModel):
+    decision: str  # approved or rejected
+
+
+class SolutionItem(BaseModel):
+    solution_id: int
+    problem_id: int
+    steps: List[str]  # in order, LaTeX for mathematics
+    final_answer: str
+    hints: List[str]  # from the most general to the most specific
+    model_name: Optional[str] = None
+    created_at: datetime
+
+
+class HintItem(BaseModel):
+    level: int  # 1 gives the least away
+    tier: str  # nudge, approach or partial_solution
+    text: str
+    created_at: datetime
+
+
+class HintsResponse(BaseModel):
+    problem_id: int
+    level: int  # the hints up to this level are revealed
+    max_level: int
+    hints: List[HintItem]
+
+
+class NumericAnswerRequest(BaseModel):
+    value: float = Field(..., description="The expected value, in unit")
+    unit: str = Field(default="", max_length=100, description="Its unit with SI prefixes and integer powers, e.g. \"m/s^2\", \"kJ/mol\", empty for a pure number")
+    abs_tolerance: Optional[float] = Field(default=None, ge=0, description="How far from the value an answer may be, in unit")
+    rel_tolerance: Optional[float] = Field(default=None, ge=0, lt=1, description="How far from the value an answer may be relative to it, 0.01 when neither tolerance is set")
+
+
+class NumericAnswerItem(BaseModel):
+    problem_id: int
+    value: float
+    unit: str
+    abs_tolerance: Optional[float] = None
+    rel_tolerance: Optional[float] = None
+    si_value: float  # The value in SI base units
+    si_unit: str  # e.g. "m s^-2", empty for a pure number
+    created_at: datetime
+
+
+class DeleteNumericAnswerResponse(BaseModel):
+    message: str
+    problem_id: int
+
+
+class StructureRequest(BaseModel):
+    chemical_formula: Optional[str] = Field(default=None, max_length=200, description="Chemical formula, e.g. \"CuSO4·5H2O\" or \"SO4^2-\", null or empty for none")
+    smiles: Optional[str] = Field(default=None, max_length=2000, description="SMILES of the structure, e.g. \"CC(=O)O\", null or empty for none")
+
+
+class CheckAnswerRequest(BaseModel):
+    answer: str = Field(..., min_length=1, max_length=20000, description="The learner's answer, LaTeX for mathematics")
+
+
+class AnswerCheckResponse(BaseModel):
+    problem_id: int
+    job_id: Optional[str] = None  # None when a numeric answer or a chemical structure decided it without a job
+    verdict: str  # correct, partially_correct or wrong
+    feedback: str
+
+
 class BookIdRequest(BaseModel):
     book_id: int = Field(..., description="ID of the book")
 
@@ -24,6 +294,7 @@ class PageImageRequest(BaseModel):
     book_id: int = Field(..., description="ID of the book")
     page_number: int = Field(..., ge=0, description="Page number (0-indexed)")
     dpi: int = Field(default=150, ge=72, le=300, description="DPI for image rendering")
+    preprocess: bool = Field(default=False, description="Apply scanned-page preprocessing (rotate, deskew, contrast)")
 
 
 class UpdateBookInfoRequest(BaseModel):
@@ -103,7 +374,11 @@ class BookListItem(BaseModel):
     book_file_name: Optional[str] = None
     book_toc_end_page: Optional[int] = None
     alignment_offset: Optional[int] = None  # from book_alignment_offset
+    ingestion_mode: Optional[str] = None  # from book_ingestion_mode
+    source_type: Optional[str] = None  # from book_source_type
+    source_url: Optional[str] = None  # from book_source_url
     toc_exists: bool = False  # computed field
+    status: str = "active"  # computed field, active, suspended or archived
 
 
 class BooksListResponse(BaseModel):
@@ -118,6 +393,10 @@ class ChapterItem(BaseModel):
     end_page_number: Optional[int] = None
     book_index_string: Optional[str] = None
     summary: Optional[str] = None
+    start_seconds: Optional[float] = None  # position in the source video, for transcripts
+    end_seconds: Optional[float] = None
+    deep_link: Optional[str] = None  # computed field, video URL at start_seconds
+    status: str = "active"  # computed field, including the book's state
 
 
 class ChaptersResponse(BaseModel):
@@ -195,6 +474,12 @@ class PageItem(BaseModel):
     page_id: int
     page_number: int
     summary: Optional[str] = None
+    transcription: Optional[str] = None
+    transcription_confidence: Optional[float] = None
+    transcription_source: Optional[str] = None
+    start_seconds: Optional[float] = None  # position in the source video, for transcripts
+    end_seconds: Optional[float] = None
+    deep_link: Optional[str] = None  # computed field, video URL at start_seconds
     book_id: int
     # Note: embedding, related_chapters, related_section_id are BLOB fields and not exposed via API
 
@@ -210,4 +495,1239 @@ class PageResponse(BaseModel):
 
 class PageMessageResponse(BaseModel):
     page_id: int
-    message: str
\ No newline at end of file
+    message: str
+
+
+# Handwriting transcription request/response models
+class TranscribeBookRequest(BaseModel):
+    start_page: Optional[int] = Field(default=None, ge=0, description="First page to transcribe (defaults to the first page)")
+    end_page: Optional[int] = Field(default=None, ge=0, description="Last page to transcribe, inclusive (defaults to the last page)")
+    overwrite: bool = Field(default=False, description="Whether to re-transcribe pages that already have a transcription, including corrected ones")
+
+
+class CorrectTranscriptionRequest(BaseModel):
+    transcription: str = Field(..., description="Corrected transcription of the page")
+
+
+class TranscriptionItem(BaseModel):
+    page_id: int
+    page_number: int
+    transcription: Optional[str] = None
+    confidence: Optional[float] = None
+    source: Optional[str] = None
+    needs_review: bool = False  # computed field, confidence below the review threshold
+
+
+class TranscriptionsResponse(BaseModel):
+    book_id: int
+    transcriptions: List[TranscriptionItem]
+
+
+# Exercise request/response models
+class GenerateCodeExercisesRequest(BaseModel):
+    start_page: Optional[int] = Field(default=None, ge=0, description="First page to generate exercises from (defaults to the first page)")
+    end_page: Optional[int] = Field(default=None, ge=0, description="Last page to generate exercises from, inclusive (defaults to the last page)")
+    exercises_per_page: int = Field(default=2, ge=1, le=5, description="Number of exercises to generate per page")
+    language: Optional[str] = Field(default=None, description="Programming language of the exercises (defaults to the notebook language)")
+    user_id: str = Field(default="default", description="User whose preferred difficulty and output language to generate for")
+
+
+class ExerciseItem(BaseModel):
+    exercise_id: int
+    book_id: int
+    page_number: int
+    exercise_type: str
+    description: str
+    starter_code: Optional[str] = None
+    solution_code: Optional[str] = None
+    code_language: Optional[str] = None
+    difficulty_level: Optional[int] = None
+
+
+class ExercisesResponse(BaseModel):
+    book_id: int
+    exercises: List[ExerciseItem]
+
+
+# Attempt request/response models
+class SubmitAttemptRequest(BaseModel):
+    answer: str = Field(..., min_length=1, description="The learner's answer to the exercise")
+    token_budget: Optional[int] = Field(default=None, ge=500, le=32000, description="Token budget of the grading context (defaults to 3000)")
+    study_mode: str = Field(default="practice", description="practice, or self_explanation to be asked to explain the solution afterwards")
+    think_seconds: Optional[float] = Field(default=None, ge=0, description="Seconds from showing the question to submitting the answer, recorded for quiz timing analytics")
+    answer_changes: int = Field(default=0, ge=0, description="How many times the answer was changed before it was submitted")
+
+
+class SkipQuestionRequest(BaseModel):
+    think_seconds: float = Field(..., ge=0, description="Seconds from showing the question to skipping it")
+    answer_changes: int = Field(default=0, ge=0, description="How many times an answer was started or changed before skipping")
+
+
+class QuestionTimingItem(BaseModel):
+    timing_id: int
+    exercise_id: int
+    attempt_id: Optional[int] = None  # None for skips
+    think_seconds: float
+    answer_changes: int
+    skipped: bool
+    created_at: datetime
+
+
+class TimingStatsItem(BaseModel):
+    key: str  # question type (text or code), or chapter ID
+    title: Optional[str] = None  # of the chapter
+    answered: int
+    skipped: int
+    skip_rate: float
+    median_think_seconds: Optional[float] = None
+    mean_answer_changes: float
+    accuracy: Optional[float] = None  # mean score of the timed answers
+    accurate_but_slow: bool  # scores well but takes much longer than the learner's median answer
+
+
+class QuizTimingResponse(BaseModel):
+    book_id: int
+    median_think_seconds: Optional[float] = None
+    by_question_type: List[TimingStatsItem]
+    by_chapter: List[TimingStatsItem]
+
+
+class GradingJudgmentItem(BaseModel):
+    judgment_id: int
+    model_name: Optional[str] = None
+    reason: str
+    score: Optional[float] = None
+    is_correct: Optional[bool] = None
+    confidence: Optional[float] = None
+    feedback: Optional[str] = None
+    created_at: datetime
+
+
+class AttemptItem(BaseModel):
+    attempt_id: int
+    exercise_id: int
+    book_id: int
+    answer: str
+    study_mode: str
+    self_explanation_prompt: Optional[str] = None  # computed field, the question to answer with POST /attempts/{id}/self-explanation
+    score: Optional[float] = None
+    is_correct: Optional[bool] = None
+    confidence: Optional[float] = None
+    feedback: Optional[str] = None
+    escalated: bool  # computed field, whether a stronger model regraded the attempt
+    judgments: List[GradingJudgmentItem]
+    misconceptions: List[str]
+    grading_context: Optional[str] = None
+    created_at: datetime
+    voided_at: Optional[datetime] = None
+
+
+class AttemptsResponse(BaseModel):
+    exercise_id: int
+    attempts: List[AttemptItem]
+
+
+class SelfExplanationRequest(BaseModel):
+    explanation: str = Field(..., min_length=1, description="The learner's explanation of how they solved the exercise")
+
+
+class ReflectionItem(BaseModel):
+    reflection_id: int
+    attempt_id: int
+    exercise_id: int
+    explanation: str
+    completeness: Optional[float] = None
+    solution_steps: List[str]
+    missing_steps: List[str]
+    feedback: Optional[str] = None
+    chapter_id: Optional[int] = None
+    created_at: datetime
+
+
+class ChapterReflectionsItem(BaseModel):
+    chapter_id: Optional[int] = None
+    chapter_title: Optional[str] = None  # None for exercises outside of any chapter
+    average_completeness: Optional[float] = None  # computed field
+    reflections: List[ReflectionItem]
+
+
+class ReflectionJournalResponse(BaseModel):
+    book_id: int
+    chapters: List[ChapterReflectionsItem]
+
+
+class DisputeAttemptRequest(BaseModel):
+    comment: str = Field(..., min_length=1, description="Why the grade is unfair")
+
+
+class ResolveDisputeRequest(BaseModel):
+    score: float = Field(..., ge=0, le=1, description="The score the reviewer settles on")
+    is_correct: bool = Field(..., description="Whether the reviewer judges the answer correct")
+    note: Optional[str] = Field(default=None, description="Note of the reviewer, shown to the learner as feedback")
+
+
+class DisputeItem(BaseModel):
+    dispute_id: int
+    attempt_id: int
+    comment: Optional[str] = None
+    status: str
+    original_score: Optional[float] = None
+    original_is_correct: Optional[bool] = None
+    escalated_score: Optional[float] = None
+    escalated_is_correct: Optional[bool] = None
+    resolved_score: Optional[float] = None
+    resolved_is_correct: Optional[bool] = None
+    reviewer_note: Optional[str] = None
+    created_at: datetime
+    resolved_at: Optional[datetime] = None
+    attempt: AttemptItem
+
+
+class DisputesResponse(BaseModel):
+    disputes: List[DisputeItem]
+
+
+class GradingPeriodMetricsItem(BaseModel):
+    period_start: date
+    attempts: int
+    escalations: int
+    disputes: int
+    resolved_disputes: int
+    overturned: int
+    dispute_rate: float
+    accuracy: float
+    escalation_agreement: Optional[float] = None
+
+
+class GradingMetricsResponse(BaseModel):
+    period_days: int
+    periods: List[GradingPeriodMetricsItem]
+
+
+# Quiz request/response models
+class QuizRequest(BaseModel):
+    chapter_id: int = Field(..., description="The chapter being studied")
+    size: int = Field(default=10, ge=1, le=50, description="Number of problems in the quiz")
+    current_proportion: Optional[float] = Field(default=None, ge=0, le=1, description="Share of problems from the current chapter (defaults to 0.5, or 1 with the blocked scheduler)")
+    weak_proportion: Optional[float] = Field(default=None, ge=0, le=1, description="Share of problems from earlier chapters with the lowest mastery (defaults to 0.3)")
+    review_proportion: Optional[float] = Field(default=None, ge=0, le=1, description="Share of problems from earlier chapters due for review (defaults to 0.2)")
+    seed: Optional[int] = Field(default=None, description="Seed for a reproducible quiz")
+    user_id: str = Field(default="default", description="User whose scheduler, daily new item limit and difficulty to respect")
+    filter_id: Optional[int] = Field(default=None, description="Smart filter of the user, only the exercises it matches are picked")
+
+
+class ChapterMasteryItem(BaseModel):
+    chapter_id: int
+    title: str
+    mastery: float
+    attempts: int
+    last_attempt_at: Optional[datetime] = None
+    review_interval_days: Optional[float] = None
+    due_for_review: bool
+    accurate_but_slow: bool = False  # answered well but slowly in quizzes, the mastery is scaled down
+
+
+class QuizItemResponse(BaseModel):
+    exercise: ExerciseItem
+    chapter_id: Optional[int] = None
+    slot: str
+    mastery: float
+
+
+class QuizResponse(BaseModel):
+    book_id: int
+    chapter_id: int
+    items: List[QuizItemResponse]
+    chapters: List[ChapterMasteryItem]
+
+
+class MasteryResponse(BaseModel):
+    book_id: int
+    chapters: List[ChapterMasteryItem]
+
+
+# Cross reference request/response models
+class EntityItem(BaseModel):
+    entity_id: int
+    kind: str
+    label: Optional[str] = None
+    name: Optional[str] = None
+    statement: Optional[str] = None
+    page_number: int
+    chapter_id: Optional[int] = None
+    section_id: Optional[int] = None
+    source: Optional[str] = None
+    anchor: str  # computed field, link target in the reader view
+
+
+class CrossReferenceItem(BaseModel):
+    reference_id: int
+    kind: str
+    label: str
+    text: str
+    page_number: int
+    char_start: int
+    char_end: int
+    target_page_number: Optional[int] = None
+    anchor: Optional[str] = None  # computed field, None when the reference could not be resolved
+
+
+class CrossReferencesResponse(BaseModel):
+    book_id: int
+    entities: List[EntityItem]
+    references: List[CrossReferenceItem]
+
+
+class LinkReferencesRequest(BaseModel):
+    text: str = Field(..., description="Text to link, e.g. a generated problem statement")
+
+
+class LinkReferencesResponse(BaseModel):
+    book_id: int
+    text: str  # markdown with the resolved references turned into links to their anchors
+
+
+class ExtractEntitiesRequest(BaseModel):
+    chapter_ids: Optional[List[int]] = Field(default=None, description="Chapters to extract entities from, all chapters if not given")
+
+
+class ChapterEntitiesItem(BaseModel):
+    chapter_id: Optional[int] = None
+    chapter_title: Optional[str] = None  # None for entities outside of any chapter
+    entities: List[EntityItem]
+
+
+class EntityIndexResponse(BaseModel):
+    book_id: int
+    chapters: List[ChapterEntitiesItem]
+
+
+class GenerateFlashcardsRequest(BaseModel):
+    entity_kinds: Optional[List[str]] = Field(default=None, description="Only generate cards for these entity kinds, e.g. ['definition', 'theorem']")
+    chapter_id: Optional[int] = Field(default=None, description="Only generate cards for the entities of this chapter")
+    cards_per_entity: int = Field(default=1, ge=1, le=3, description="Number of flashcards per entity")
+    user_id: str = Field(default="default", description="User whose preferred output language to write the cards in")
+
+
+class FlashcardItem(BaseModel):
+    card_id: int
+    front: str
+    back: str
+    entity_id: Optional[int] = None
+    entity_kind: Optional[str] = None
+    chapter_id: Optional[int] = None
+    page_number: Optional[int] = None
+    chemical_formula: Optional[str] = None  # in Hill order, e.g. C2H6O
+    smiles: Optional[str] = None  # canonical, for clients to render the structure
+    status: str = "active"  # computed field, including the chapter's and book's state
+
+
+class FlashcardsResponse(BaseModel):
+    book_id: int
+    flashcards: List[FlashcardItem]
+
+
+# Review request/response models
+class GradeIntervalItem(BaseModel):
+    grade: int
+    label: str
+    interval_days: float
+
+
+class ReviewCardItem(BaseModel):
+    card_id: int
+    book_id: int
+    book_name: Optional[str] = None
+    front: str
+    back: str
+    entity: Optional[EntityItem] = None
+    chapter_id: Optional[int] = None
+    chapter_title: Optional[str] = None
+    page_number: Optional[int] = None
+    chemical_formula: Optional[str] = None
+    smiles: Optional[str] = None
+    is_new: bool
+    due_at: Optional[datetime] = None
+    interval_days: Optional[float] = None
+    repetitions: int
+    lapses: int
+    grade_intervals: List[GradeIntervalItem]
+
+
+class ReviewBatchResponse(BaseModel):
+    user_id: str
+    due_count: int
+    new_remaining: int
+    cards: List[ReviewCardItem]
+
+
+class ReviewGradeItem(BaseModel):
+    card_id: int
+    grade: int = Field(..., ge=1, le=4, description="1 (again), 2 (hard), 3 (good) or 4 (easy)")
+    duration_ms: Optional[int] = Field(default=None, ge=0, description="How long the answer took")
+    reviewed_at: Optional[datetime] = Field(default=None, description="When the card was reviewed (defaults to now), for reviews queued offline")
+
+
+class SubmitReviewBatchRequest(BaseModel):
+    user_id: str = Field(default="default", description="User the reviews are for")
+    reviews: List[ReviewGradeItem] = Field(..., min_length=1, max_length=100, description="The grades to record, all or none are stored")
+
+
+class ReviewResultItem(BaseModel):
+    review_id: int
+    card_id: int
+    grade: int
+    interval_days: float
+    due_at: datetime
+
+
+class SubmitReviewBatchResponse(BaseModel):
+    user_id: str
+    reviews: List[ReviewResultItem]
+
+
+class ProblemScheduleItem(BaseModel):
+    interval_days: float
+    ease: float
+    repetitions: int
+    lapses: int
+    due_at: datetime
+    last_reviewed_at: datetime
+
+
+class ProblemDueItem(BaseModel):
+    problem: ProblemItem
+    schedule: Optional[ProblemScheduleItem] = None  # None for a problem never reviewed
+    intervals: List[GradeIntervalItem]  # the interval each grade would give
+
+
+class ProblemsDueResponse(BaseModel):
+    user_id: str
+    problems: List[ProblemDueItem]
+
+
+class GradeProblemRequest(BaseModel):
+    grade: int = Field(..., ge=1, le=4, description="1 (again), 2 (hard), 3 (good) or 4 (easy)")
+    user_id: str = Field(default="default", description="User who reviewed the problem")
+
+
+class GradeProblemResponse(BaseModel):
+    problem_id: int
+    user_id: str
+    grade: int
+    schedule: ProblemScheduleItem
+
+
+class DayLoadItem(BaseModel):
+    day: date
+    reviews: int
+    new_cards: int
+    projected_retention: Optional[float] = None  # mean chance of recalling a learned card at the end of the day
+
+
+class ReviewForecastResponse(BaseModel):
+    user_id: str
+    daily_new_card_limit: int
+    load_balancing: bool
+    retention: float
+    days: List[DayLoadItem]
+
+
+class SimulateReviewRequest(BaseModel):
+    user_id: str = Field(default="default", description="User whose cards and review history are simulated")
+    days: int = Field(default=90, ge=1, le=365, description="Number of days to simulate")
+    book_id: Optional[int] = Field(default=None, description="Only simulate the cards of this book")
+    daily_new_card_limit: Optional[int] = Field(default=None, ge=0, description="Proposed daily new card limit, null keeps the current one")
+    load_balancing: Optional[bool] = Field(default=None, description="Proposed load balancing, null keeps the current setting")
+
+
+class SimulationRunItem(BaseModel):
+    daily_new_card_limit: int
+    load_balancing: bool
+    total_reviews: int
+    peak_reviews: int
+    mean_retention: Optional[float] = None
+    days: List[DayLoadItem]
+
+
+class ReviewSimulationResponse(BaseModel):
+    user_id: str
+    retention: float  # pass rate of the user's reviews, or the default with too little history
+    review_count: int
+    current: SimulationRunItem
+    proposed: SimulationRunItem
+
+
+class UndoReviewResponse(BaseModel):
+    card_id: int
+    user_id: str
+    undone_review_id: int
+    undone_grade: int
+    is_new: bool
+    due_at: Optional[datetime] = None
+    interval_days: Optional[float] = None
+    repetitions: int
+    lapses: int
+
+
+# Preferences request/response models
+class UpdatePreferencesRequest(BaseModel):
+    default_difficulty: Optional[int] = Field(default=None, ge=1, le=5, description="Difficulty generated exercises and quizzes aim for")
+    daily_new_card_limit: Optional[int] = Field(default=None, ge=0, le=1000, description="Never practiced items introduced per day")
+    output_language: Optional[str] = Field(default=None, description="Language of generated content, null to keep the language of the source")
+    notifications_enabled: Optional[bool] = Field(default=None, description="Whether to remind of due reviews")
+    notification_hour: Optional[int] = Field(default=None, ge=0, le=23, description="Hour of the day (UTC) to send reminders at")
+    scheduler: Optional[str] = Field(default=None, description="interleaved, or blocked to only practice the current chapter")
+    load_balancing: Optional[bool] = Field(default=None, description="Move review due dates by a few days to the least loaded day")
+
+
+class PreferencesResponse(BaseModel):
+    user_id: str
+    default_difficulty: int
+    daily_new_card_limit: int
+    output_language: Optional[str] = None
+    notifications_enabled: bool
+    notification_hour: Optional[int] = None
+    scheduler: str
+    load_balancing: bool
+
+
+# API token request/response models
+class CreateApiTokenRequest(BaseModel):
+    name: str = Field(..., min_length=1, description="What the token is for, e.g. status bar")
+    scopes: List[str] = Field(..., min_length=1, description="Scopes to grant: read:stats, write:review and/or admin")
+
+
+class ApiTokenItem(BaseModel):
+    token_id: int
+    user_id: str
+    name: str
+    scopes: List[str]
+    created_at: datetime
+    last_used_at: Optional[datetime] = None
+    revoked_at: Optional[datetime] = None
+
+
+class CreateApiTokenResponse(BaseModel):
+    token: str  # only shown once, send it as "Authorization: Bearer <token>"
+    info: ApiTokenItem
+
+
+class ApiTokensResponse(BaseModel):
+    user_id: str
+    tokens: List[ApiTokenItem]
+
+
+# Reading view request/response models
+class CreateHighlightRequest(BaseModel):
+    user_id: str = Field(default="default", description="ID of the user, chosen by the client")
+    page_number: int = Field(..., ge=0, description="PDF page the passage is on")
+    char_start: int = Field(..., ge=0, description="Where the passage starts in the page text")
+    char_end: int = Field(..., ge=1, description="Where the passage ends in the page text")
+    text: str = Field(..., min_length=1, description="The highlighted passage")
+    note: Optional[str] = Field(default=None, description="A note on the passage")
+
+
+class HighlightItem(BaseModel):
+    highlight_id: int
+    user_id: str
+    page_number: int
+    char_start: int
+    char_end: int
+    text: str
+    note: Optional[str] = None
+    created_at: datetime
+    anchor: str  # computed field, link target in the reader view
+
+
+class HighlightsResponse(BaseModel):
+    book_id: int
+    highlights: List[HighlightItem]
+
+
+class HighlightMessageResponse(BaseModel):
+    highlight_id: int
+    message: str
+
+
+class ReadingPageItem(BaseModel):
+    page_number: int  # PDF page
+    printed_page_number: int
+    anchor: str
+    markdown: str  # page text with references linked, anchors for sections and entities, and highlights marked
+
+
+class SectionPracticeItem(BaseModel):
+    section_id: int
+    title: str
+    book_index_string: Optional[str] = None
+    start_page_number: int
+    end_page_number: Optional[int] = None
+    anchor: str
+    problem_count: int  # exercises on the section's pages
+    attempted_count: int
+
+
+class ChapterViewResponse(BaseModel):
+    book_id: int
+    chapter_number: int
+    chapter: ChapterItem
+    pages: List[ReadingPageItem]
+    sections: List[SectionPracticeItem]
+    entities: List[EntityItem]
+    figures: List[EntityItem]
+    highlights: List[HighlightItem]
+
+
+# Reading queue request/response models
+class EnqueueReadingRequest(BaseModel):
+    user_id: str = Field(default="default", description="User whose queue to add to")
+    section_id: Optional[int] = Field(default=None, description="Section to read; leave out to enqueue an excerpt")
+    page_number: Optional[int] = Field(default=None, ge=0, description="PDF page the excerpt is on")
+    text: Optional[str] = Field(default=None, min_length=1, description="The excerpt to read")
+
+
+class ChapterQuizItem(BaseModel):
+    quiz_id: int
+    user_id: str
+    book_id: int
+    chapter_id: int
+    exercise_ids: List[int]
+    answered: int  # exercises answered since the quiz was enqueued
+    score: Optional[float] = None  # mean score of the answers
+    created_at: datetime
+    completed_at: Optional[datetime] = None  # null while the quiz is pending
+
+
+class ChapterQuizzesResponse(BaseModel):
+    user_id: str
+    quizzes: List[ChapterQuizItem]
+
+
+class ChapterQuizResponse(BaseModel):
+    quiz: ChapterQuizItem
+    exercises: List[ExerciseItem]
+    mastery: Optional[ChapterMasteryItem] = None  # of the chapter, seeded by the answers
+
+
+class RefreshSuggestionItem(BaseModel):
+    book_id: int
+    chapter_id: int
+    title: str
+    mastery: float  # as estimated from the attempts
+    projected_mastery: float  # decayed since the last attempt
+    forget_on: date  # when the projected mastery drops below the threshold
+    refresh_on: date
+    message: str
+
+
+class SyllabusTopicItem(BaseModel):
+    topic_id: int
+    user_id: str
+    book_id: int
+    week: int
+    week_starts_at: datetime
+    title: str
+    description: Optional[str] = None
+    chapter_id: Optional[int] = None  # the chapter covering the topic, none if no chapter does
+    mastery: Optional[float] = None  # of the chapter, in upcoming topics
+
+
+class SyllabusResponse(BaseModel):
+    user_id: str
+    topics: List[SyllabusTopicItem]  # by week
+
+
+class StudyPlanDayItem(BaseModel):
+    day: date
+    reviews: int
+    new_cards: int
+    refresh: List[RefreshSuggestionItem]
+    syllabus: List[SyllabusTopicItem]  # topics of the course week starting that day, weakest chapter first
+
+
+class StudyPlanResponse(BaseModel):
+    user_id: str
+    threshold: float
+    days: List[StudyPlanDayItem]
+
+
+class StudyDigestResponse(BaseModel):
+    user_id: str
+    due_reviews: int
+    chapter_quizzes: List[ChapterQuizItem]
+    refresh: List[RefreshSuggestionItem]
+    coming_up: List[SyllabusTopicItem]  # topics of the course this week and next, weakest chapter first
+
+
+class ReadingItem(BaseModel):
+    reading_id: int
+    user_id: str
+    book_id: int
+    section_id: Optional[int] = None
+    page_number: Optional[int] = None
+    title: str
+    text: Optional[str] = None  # the excerpt, null for sections
+    interval_days: float
+    read_count: int
+    due_at: datetime
+    last_read_at: Optional[datetime] = None
+    done_at: Optional[datetime] = None
+    chapter_quiz: Optional[ChapterQuizItem] = None  # the diagnostic quiz enqueued when this reading completed a chapter
+
+
+class ReadingQueueResponse(BaseModel):
+    user_id: str
+    items: List[ReadingItem]
+
+
+class RecordReadingRequest(BaseModel):
+    done: bool = Field(default=False, description="Whether the user is done with the item, which takes it out of the queue")
+
+
+class StudyQueueEntry(BaseModel):
+    kind: str  # card or reading
+    card: Optional[ReviewCardItem] = None
+    reading: Optional[ReadingItem] = None
+
+
+class StudyQueueResponse(BaseModel):
+    user_id: str
+    due_count: int
+    new_remaining: int
+    entries: List[StudyQueueEntry]
+
+
+class ExtractClozeRequest(BaseModel):
+    user_id: str = Field(default="default", description="User extracting the passage")
+    page_number: int = Field(..., ge=0, description="PDF page the passage is on")
+    char_start: int = Field(..., ge=0, description="Where the passage starts in the page text")
+    char_end: int = Field(..., ge=1, description="Where the passage ends in the page text")
+    text: str = Field(..., min_length=1, description="The passage")
+    cloze_terms: List[str] = Field(..., min_length=1, max_length=20, description="Terms of the passage to blank out, one card each")
+    enqueue: bool = Field(default=False, description="Also add the passage to the reading queue")
+
+
+class ExtractClozeResponse(BaseModel):
+    book_id: int
+    highlight: HighlightItem
+    flashcards: List[FlashcardItem]
+    reading_item: Optional[ReadingItem] = None
+
+
+# Workspace request/response models
+class CreateWorkspaceRequest(BaseModel):
+    name: str
+
+
+class WorkspaceItem(BaseModel):
+    workspace_id: int
+    name: str
+    role: str  # of the user asking, instructor or student
+    created_at: datetime
+
+
+class WorkspacesResponse(BaseModel):
+    user_id: str
+    workspaces: List[WorkspaceItem]
+
+
+class AddMemberRequest(BaseModel):
+    member_id: str  # the user added
+    role: str = "student"  # instructor or student
+
+
+class UpdateSharingRequest(BaseModel):
+    share_attempts: bool
+
+
+class WorkspaceMemberItem(BaseModel):
+    user_id: str
+    role: str
+    share_attempts: bool
+    joined_at: datetime
+
+
+class RosterResponse(BaseModel):
+    workspace_id: int
+    members: List[WorkspaceMemberItem]
+
+
+class CreateAssignmentRequest(BaseModel):
+    kind: str  # reading or quiz
+    book_id: int
+    chapter_id: Optional[int] = None  # none to assign the reading of the whole book
+    due_at: Optional[datetime] = None
+    quiz_size: int = Field(default=5, ge=1, le=20)
+
+
+class AssignmentItem(BaseModel):
+    assignment_id: int
+    workspace_id: int
+    kind: str
+    book_id: int
+    chapter_id: Optional[int] = None
+    due_at: Optional[datetime] = None
+    created_at: datetime
+
+
+class AssignmentsResponse(BaseModel):
+    workspace_id: int
+    assignments: List[AssignmentItem]
+
+
+class TopicAnalyticsItem(BaseModel):
+    book_id: int
+    chapter_id: int
+    title: str
+    students: int
+    read: int  # students done with every section of the chapter
+    quizzes_completed: int
+    quiz_score: Optional[float] = None  # withheld under min_group_size completed quizzes
+    reviewers: int
+    retention: Optional[float] = None  # share of flashcard reviews recalled, withheld under min_group_size reviewers
+
+
+class WorkspaceAnalyticsResponse(BaseModel):
+    workspace_id: int
+    min_group_size: int
+    topics: List[TopicAnalyticsItem]
+
+
+class ChapterReviewGradesItem(BaseModel):
+    chapter_id: int
+    grades: List[int]
+
+
+class StudentActivityResponse(BaseModel):
+    workspace_id: int
+    user_id: str
+    readings: List[ReadingItem]
+    quizzes: List[ChapterQuizItem]
+    reviews: List[ChapterReviewGradesItem]
+
+
+# Archive and suspend request/response models
+class UpdateStatusRequest(BaseModel):
+    archived: Optional[bool] = Field(default=None, description="Archive (hide from quizzes, reviews and listings) or restore, null leaves it unchanged")
+    suspended: Optional[bool] = Field(default=None, description="Suspend (leave out of review queues) or resume, null leaves it unchanged")
+
+
+class StatusResponse(BaseModel):
+    item_type: str  # book, chapter or flashcard
+    item_id: int
+    archived_at: Optional[datetime] = None
+    suspended_at: Optional[datetime] = None
+    status: str  # effective status, including the states of the chapter and book it belongs to
+
+
+
+# Job request/response models
+class SubmitJobResponse(BaseModel):
+    job_id: str
+    kind: str
+    status: str  # pending, running, succeeded, failed, interrupted, cancelled or deferred
+    message: str
+    document_id: Optional[int] = None  # the library document of an uploaded PDF
+    pipeline_id: Optional[str] = None  # the pipeline the job starts, poll it with GET /jobs/pipelines/{pipeline_id}
+    retry_of: Optional[str] = None  # the failed job a retry runs again
+
+
+class JobEventItem(BaseModel):
+    status: str
+    created_at: datetime
+
+
+class JobItem(BaseModel):
+    job_id: str
+    kind: str
+    status: str  # pending, running, succeeded, failed, interrupted, cancelled or deferred
+    progress: float
+    percent: float  # computed field, the progress in percent
+    stage: Optional[str] = None  # step the job last reported progress in, e.g. render
+    detail: Optional[str] = None  # what the job last reported doing, e.g. page 12/80 rendered
+    params: Dict[str, Any]
+    created_at: datetime
+    started_at: Optional[datetime] = None
+    finished_at: Optional[datetime] = None
+    error: Optional[str] = None
+    error_category: Optional[str] = None  # auth, quota, content_filter, timeout, server or unavailable when an LLM provider call failed
+    queue_position: Optional[int] = None  # place in the queue of its category while pending, 1 runs next
+    group_id: Optional[str] = None  # the group the job was submitted in, if any
+    retry_of: Optional[str] = None  # the failed job this one retries, if any
+    retried_by: Optional[str] = None  # the job retrying this one, only when a single job is requested
+    events: List[JobEventItem] = []  # status transitions, only when a single job is requested
+
+
+class JobsResponse(BaseModel):
+    jobs: List[JobItem]
+    queue_depths: Dict[str, int]  # pending jobs waiting for a worker, per category
+    running: Dict[str, int] = {}  # jobs running, per category
+    workers: Dict[str, int] = {}  # worker limit of each category, from the [jobs] section of config.toml
+    paused: List[str] = []  # categories whose jobs do not start, OCR while the disk or memory runs low
+
+
+class DeadLetterResponse(BaseModel):
+    jobs: List[JobItem]  # failed and interrupted jobs not retried yet, newest first, with the params to retry them with
+
+
+class PurgeJobsRequest(BaseModel):
+    older_than_hours: Optional[float] = Field(default=None, ge=0, description="Only the jobs finished longer ago than this, all finished jobs by default")
+    history: bool = Field(default=False, description="Delete them from the stored history as well, the dead-letter queue included")
+
+
+class PurgeJobsResponse(BaseModel):
+    evicted: int  # finished jobs dropped from memory
+    deleted: int  # finished jobs deleted from the stored history
+    tracked: int  # jobs still kept in memory
+
+
+class JobResultResponse(BaseModel):
+    job_id: str
+    kind: str
+    result: Dict[str, Any]
+
+
+class SubmitJobGroupResponse(BaseModel):
+    group_id: str
+    job_ids: List[str]
+    message: str
+    document_id: Optional[int] = None
+
+
+class PipelineNodeItem(BaseModel):
+    name: str
+    kind: str
+    status: str  # waiting, blocked, or the status of its job once submitted
+    depends_on: List[str]
+    job_id: Optional[str] = None
+
+
+class PipelineResponse(BaseModel):
+    pipeline_id: str
+    status: str  # running, succeeded or failed
+    nodes: List[PipelineNodeItem]
+    message: Optional[str] = None
+
+
+class JobGroupResponse(BaseModel):
+    group_id: str
+    status: str  # pending until a job started, running until all finished, then succeeded or the status of the first failure
+    total: int
+    completed: int  # jobs that succeeded
+    counts: Dict[str, int]  # jobs per status
+    progress: float
+    percent: float  # computed field, the progress in percent
+    first_failure: Optional[JobItem] = None  # the job that failed, was interrupted or cancelled first
+    jobs: List[JobItem]
+
+
+# Admin request/response models
+class VerifyIntegrityRequest(BaseModel):
+    repair: bool = Field(default=False, description="Whether to regenerate recoverable artifacts")
+
+
+class IntegrityIssueItem(BaseModel):
+    kind: str
+    detail: str
+    book_id: Optional[int] = None
+    artifact_id: Optional[int] = None
+    path: Optional[str] = None
+    recoverable: bool
+
+
+class ExportAnalyticsRequest(BaseModel):
+    format: str = Field(default="parquet", description="parquet (one file per table) or duckdb (a single database file)")
+
+
+class VerifyIntegrityResponse(BaseModel):
+    issues: List[IntegrityIssueItem]
+    repaired: List[IntegrityIssueItem]
+
+class JsonRepairStatsItem(BaseModel):
+    model_name: str
+    responses: int  # answers validated against a schema
+    repaired: int  # answers valid only after a repair
+    failed: int  # answers invalid even after a repair
+    repair_rate: float  # computed field, share of answers that needed a repair
+    fixes: Dict[str, int]  # answers per fix, code_fence, surrounding_text, trailing_comma or unquoted_key
+
+
+class JsonRepairMetricsResponse(BaseModel):
+    models: List[JsonRepairStatsItem]
+
+
+class CircuitBreakerItem(BaseModel):
+    service: str  # mineru, or llm: followed by the provider
+    state: str  # closed, open or half_open
+    consecutive_failures: int
+    trips: int  # times it opened since the server started
+    rejected: int  # calls failed fast while it was open
+    retry_in_seconds: Optional[float] = None  # until the next probe, while open
+
+
+class CircuitBreakersResponse(BaseModel):
+    failure_threshold: int
+    reset_seconds: float
+    breakers: List[CircuitBreakerItem]
+
+
+class CapabilityItem(BaseModel):
+    name: str  # llm, embeddings, grading, mineru or tts
+    enabled: bool
+    guidance: Optional[str] = None  # how to enable it, None when enabled
+
+
+class CapabilitiesResponse(BaseModel):
+    capabilities: List[CapabilityItem]
+
+
+class PrimingStepItem(BaseModel):
+    name: str  # schemas, database or models
+    primed: int  # items primed: schemas generated, rows read, models created
+    seconds: float
+    error: Optional[str] = None  # why the step failed, the server became ready without it
+
+
+class ReadinessResponse(BaseModel):
+    ready: bool
+    steps: List[PrimingStepItem]  # finished so far
+
+
+class LlmProfileItem(BaseModel):
+    name: str
+    model_name: str
+    tasks: List[str]  # routed to the profile, the default profile gets every task not routed elsewhere
+
+
+class LlmProfilesResponse(BaseModel):
+    profiles: List[LlmProfileItem]
+
+
+class ResourcesResponse(BaseModel):
+    free_disk_mb: int  # on the disk of the uploads directory
+    rss_mb: int  # memory of the server process
+    min_free_disk_mb: int  # uploads are refused below it, 0 if not checked
+    max_rss_mb: int  # OCR jobs are paused above it, 0 if not checked
+    disk_low: bool
+    memory_high: bool
+    warnings: Dict[str, int]  # thresholds crossed since the server started, per disk_low or memory_high
+    paused_categories: List[str]  # job categories paused by the pressure
+
+
+class PromptLogItem(BaseModel):
+    log_id: int
+    job_id: Optional[str] = None
+    model_name: str
+    schema_name: Optional[str] = None
+    prompt: str  # redacted, and cut in truncated mode
+    response: Optional[str] = None
+    error: Optional[str] = None
+    parsed: Optional[str] = None  # validated output as JSON
+    attempt: int = 0  # 0 for the first answer, then the repair number
+    prompt_chars: int  # length before truncation
+    response_chars: int
+    truncated: bool
+    created_at: datetime
+
+
+class PromptLogsResponse(BaseModel):
+    mode: str  # full, truncated or disabled
+    retention_days: int
+    logs: List[PromptLogItem]
+
+
+# Usage request/response models
+class UsageTotalsItem(BaseModel):
+    calls: int
+    input_tokens: int
+    output_tokens: int
+    cost: float  # estimated USD, of the calls of models with a price
+    unpriced_calls: int  # of models without a price in the [pricing] section of config.toml
+    estimated_calls: int  # whose tokens the provider did not report, estimated from the text
+
+
+class UsageDayItem(BaseModel):
+    day: date  # UTC
+    usage: UsageTotalsItem
+
+
+class UsageDocumentItem(BaseModel):
+    document_id: int
+    title: Optional[str] = None  # None once the document is deleted
+    usage: UsageTotalsItem
+
+
+class UsageModelItem(BaseModel):
+    model_name: str
+    usage: UsageTotalsItem
+
+
+class UsageJobItem(BaseModel):
+    job_id: str
+    job_kind: Optional[str] = None
+    document_id: Optional[int] = None
+    usage: UsageTotalsItem
+
+
+class UsageResponse(BaseModel):
+    since: datetime
+    totals: UsageTotalsItem
+    days: List[UsageDayItem]
+    documents: List[UsageDocumentItem]  # most expensive first
+    models: List[UsageModelItem]
+    jobs: List[UsageJobItem]  # most expensive first
+
+
+# Estimate request/response models
+class EstimateRequest(BaseModel):
+    document_id: int
+    section_index: Optional[int] = None  # only this section for extraction, solutions and hints
+    tasks: Optional[List[str]] = None  # by default every generation task of a document
+    profile: Optional[str] = None  # LLM profile of config.toml, by default the profile each task is routed to
+
+
+class TaskEstimateItem(BaseModel):
+    task: str
+    model_name: str
+    prompts: int
+    input_tokens: int
+    output_tokens: int
+    cost: Optional[float] = None  # estimated USD, None for a model without a price
+    calibrated: bool  # whether the output tokens use the ratio measured on the task's recorded calls
+    assumed_problems: Optional[int] = None  # problems expected of an extraction not run yet
+
+
+class EstimateResponse(BaseModel):
+    document_id: int
+    section_index: Optional[int] = None
+    tasks: List[TaskEstimateItem]
+    prompts: int
+    input_tokens: int
+    output_tokens: int
+    cost: float  # of the tasks with a price
+    unpriced_tasks: List[str]
+
+
+# Watermark request/response models
+class TraceExportRequest(BaseModel):
+    text: str  # a leaked copy, or the manifest.json of a bundle
+
+
+class ExportFingerprintItem(BaseModel):
+    fingerprint: str
+    instance_id: str
+    user_id: str
+    export_kind: str  # book_bundle or problem_set
+    subject_id: Optional[int] = None  # the book or document exported
+    created_at: datetime
+
+
+class TraceExportResponse(BaseModel):
+    fingerprints: List[str]  # found in the text, recorded on this instance or not
+    exports: List[ExportFingerprintItem]
+
+
+class SeedDemoResponse(BaseModel):
+    document_id: int
+    title: str
+    created: bool  # false when the demo was seeded before
+    problem_count: int
+
+
+# Search suggestion response models
+class SuggestionItem(BaseModel):
+    text: str
+    kind: str  # title, heading or entity
+    book_id: Optional[int] = None
+    document_id: Optional[int] = None
+    chapter_id: Optional[int] = None  # of chapter headings
+    section_index: Optional[int] = None  # of section headings of a document's OCR output
+    entity_id: Optional[int] = None
+    entity_kind: Optional[str] = None  # e.g. theorem or definition
+    page_number: Optional[int] = None
+
+
+class SuggestResponse(BaseModel):
+    query: str
+    suggestions: List[SuggestionItem]  # best first
+
+
+# Smart filter request/response models
+class SmartFilterCriteriaItem(BaseModel):
+    chapter_from: Optional[int] = Field(default=None, ge=1, description="First chapter, numbered from 1 in the order of the book")
+    chapter_to: Optional[int] = Field(default=None, ge=1, description="Last chapter, inclusive")
+    chapter_ids: Optional[List[int]] = Field(default=None, description="Only exercises of these chapters")
+    progress: Optional[str] = Field(default=None, description="unsolved, solved, unattempted or attempted")
+    min_difficulty: Optional[int] = Field(default=None, ge=1, le=5)
+    max_difficulty: Optional[int] = Field(default=None, ge=1, le=5)
+    exercise_type: Optional[str] = Field(default=None, description="text or code")
+    text: Optional[str] = Field(default=None, description="Words the description contains")
+
+
+class SaveSmartFilterRequest(BaseModel):
+    name: str = Field(..., min_length=1, max_length=100, description="Name of the filter, unique per user")
+    book_id: int = Field(..., description="The book whose exercises the filter searches")
+    criteria: SmartFilterCriteriaItem = Field(default_factory=SmartFilterCriteriaItem)
+
+
+class SmartFilterItem(BaseModel):
+    filter_id: int
+    user_id: str
+    name: str
+    book_id: int
+    criteria: SmartFilterCriteriaItem
+    created_at: datetime
+    updated_at: datetime
+
+
+class SmartFiltersResponse(BaseModel):
+    user_id: str
+    filters: List[SmartFilterItem]  # by name
+
+
+class SmartFilterExercisesResponse(BaseModel):
+    filter: SmartFilterItem
+    exercises: List[ExerciseItem]  # in the order of the book
+
+
+class DeleteSmartFilterResponse(BaseModel):
+    message: str
+    filter_id: int
+
+
+# Study event models
+class StudyEventItem(BaseModel):
+    event_id: int
+    kind: str  # attempted, graded, voided, reviewed, review_undone or read
+    user_id: Optional[str] = None  # None for attempts, which are not made per user
+    subject_kind: str  # exercise, card, problem or reading
+    subject_id: int
+    payload: Dict[str, Any]
+    occurred_at: datetime
+    recorded_at: datetime
+    book_id: Optional[int] = None
+    document_id: Optional[int] = None
+
+
+class StudyEventsResponse(BaseModel):
+    events: List[StudyEventItem]  # in the order they were appended
+    next_after_id: Optional[int] = None  # after_id of the next page, None on the last page
+
+
+class ActivityDayItem(BaseModel):
+    day: date  # UTC
+    attempted: int
+    reviewed: int
+    read: int
+
+
+class ActivityResponse(BaseModel):
+    user_id: str
+    book_id: Optional[int] = None
+    current_streak: int  # days in a row with activity, up to today or yesterday
+    longest_streak: int
+    attempted: int
+    reviewed: int
+    read: int
+    days: List[ActivityDayItem]  # days with activity, oldest first
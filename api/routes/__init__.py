@@ -0,0 +1 @@
+# Route modules of the subsystems, each exposing a `router` nested under its prefix and included by api/app.py
@@ -0,0 +1,242 @@
+# Admin routes
+# Integrity verification of the stored artifacts, the analytics export, the JSON repair metrics of the LLMs, the
+# circuit breakers of MinerU and the LLM provider, the disk and memory of the server, the prompt log, tracing leaked
+# exports by their fingerprint and seeding the demo document.
+
+from pathlib import Path
+from typing import Optional
+import uuid
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.integrity import verify_integrity, repair_issues
+from textbook.jobs import JobHandle, JOB_KIND_ANALYTICS_EXPORT
+from textbook.analytics_export import export_analytics, EXPORT_FORMATS
+from textbook.json_repair import repair_metrics
+from textbook.circuit_breaker import circuit_breakers
+from textbook.demo import seed_demo
+from textbook.watermark import find_fingerprints, trace_export
+
+from api.models import VerifyIntegrityRequest, VerifyIntegrityResponse, ExportAnalyticsRequest, IntegrityIssueItem, SubmitJobResponse, JsonRepairStatsItem, JsonRepairMetricsResponse, CircuitBreakerItem, CircuitBreakersResponse, ResourcesResponse, PromptLogItem, PromptLogsResponse, SeedDemoResponse, TraceExportRequest, ExportFingerprintItem, TraceExportResponse
+from api.state import state
+
+router = APIRouter(prefix="/admin", tags=["admin"])
+
+
+@router.post("/verify", response_model=VerifyIntegrityResponse)
+async def verify_artifacts(request: VerifyIntegrityRequest):
+    """Verify stored artifacts against their recorded digests, optionally repairing recoverable issues"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        issues = verify_integrity(state.database, state.uploads_dir)
+        repaired = repair_issues(state.database, state.uploads_dir, issues) if request.repair else []
+
+        return VerifyIntegrityResponse(
+            issues=[IntegrityIssueItem(**issue.to_dict()) for issue in issues],
+            repaired=[IntegrityIssueItem(**issue.to_dict()) for issue in repaired],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /admin/verify endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def export_analytics_job(params: dict, handle: JobHandle) -> dict:
+    """Analytics export job, the files are written to a directory under the uploads directory"""
+    if not state.database:
+        raise RuntimeError("Context not initialized")
+    file_names = export_analytics(state.database, Path(state.uploads_dir) / params["export_dir"], params["format"], handle.cancellation_token, handle.report_progress)
+    return {"export_dir": params["export_dir"], "file_names": file_names}
+
+
+@router.post("/export", response_model=SubmitJobResponse)
+async def export_analytics_data(request: ExportAnalyticsRequest):
+    """Start a job exporting attempts, reviews, problems and study sessions as Parquet files or a DuckDB database"""
+    try:
+        if not state.database or not state.job_pool:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if request.format not in EXPORT_FORMATS:
+            raise HTTPException(status_code=400, detail=f"Unsupported export format: {request.format}, expected one of {', '.join(EXPORT_FORMATS)}")
+
+        export_dir = Path("exports") / str(uuid.uuid4())
+        job = state.job_pool.submit(JOB_KIND_ANALYTICS_EXPORT, {"export_dir": str(export_dir), "format": request.format})
+        return SubmitJobResponse(job_id=job.job_id, kind=job.kind, status=job.status, message=f"Export to {export_dir} submitted")
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /admin/export endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/json-repairs", response_model=JsonRepairMetricsResponse)
+async def get_json_repair_metrics():
+    """How often the answers of each model needed a JSON repair before schema validation, since the server started"""
+    try:
+        return JsonRepairMetricsResponse(models=[
+            JsonRepairStatsItem(
+                model_name=model_name,
+                responses=stats.responses,
+                repaired=stats.repaired,
+                failed=stats.failed,
+                repair_rate=stats.repaired / stats.responses if stats.responses else 0.0,
+                fixes=stats.fixes,
+            )
+            for model_name, stats in sorted(repair_metrics.snapshot().items())
+        ])
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /admin/json-repairs endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/circuit-breakers", response_model=CircuitBreakersResponse)
+async def get_circuit_breakers():
+    """The state of the circuit breakers of MinerU and the LLM provider, which fail calls fast while a service is down"""
+    try:
+        return CircuitBreakersResponse(
+            failure_threshold=circuit_breakers.settings.failure_threshold,
+            reset_seconds=circuit_breakers.settings.reset_seconds,
+            breakers=[
+                CircuitBreakerItem(
+                    service=status.service,
+                    state=status.state,
+                    consecutive_failures=status.consecutive_failures,
+                    trips=status.trips,
+                    rejected=status.rejected,
+                    retry_in_seconds=status.retry_in_seconds,
+                )
+                for status in circuit_breakers.snapshot()
+            ],
+        )
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /admin/circuit-breakers endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/resources", response_model=ResourcesResponse)
+async def get_resources():
+    """Free disk space and memory of the server, the thresholds crossed and the job categories paused by them"""
+    try:
+        if not state.resource_monitor or not state.job_pool:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+        status = state.resource_monitor.check()
+        return ResourcesResponse(
+            free_disk_mb=status.free_disk_mb,
+            rss_mb=status.rss_mb,
+            min_free_disk_mb=state.resource_monitor.limits.min_free_disk_mb,
+            max_rss_mb=state.resource_monitor.limits.max_rss_mb,
+            disk_low=status.disk_low,
+            memory_high=status.memory_high,
+            warnings=dict(state.resource_monitor.warnings),
+            paused_categories=state.job_pool.paused(),
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /admin/resources endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/prompt-logs", response_model=PromptLogsResponse)
+async def get_prompt_logs(
+    job_id: Optional[str] = Query(default=None, description="Only return the prompts sent for this job"),
+    limit: int = Query(default=50, ge=1, le=500, description="Maximum number of entries, the latest first"),
+):
+    """Get the logged LLM prompts and responses within the retention window, for debugging generation quality"""
+    try:
+        if not state.prompt_log:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        entries = state.prompt_log.entries(job_id, limit)
+        return PromptLogsResponse(
+            mode=state.prompt_log.settings.mode,
+            retention_days=state.prompt_log.settings.retention_days,
+            logs=[
+                PromptLogItem(
+                    log_id=entry.log_id,
+                    job_id=entry.job_id,
+                    model_name=entry.model_name,
+                    schema_name=entry.schema_name,
+                    prompt=entry.prompt,
+                    response=entry.response,
+                    error=entry.error,
+                    parsed=entry.parsed,
+                    attempt=entry.attempt,
+                    prompt_chars=entry.prompt_chars,
+                    response_chars=entry.response_chars,
+                    truncated=entry.truncated,
+                    created_at=entry.created_at,
+                )
+                for entry in entries
+            ],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /admin/prompt-logs endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/watermark/trace", response_model=TraceExportResponse)
+async def post_trace_export(request: TraceExportRequest):
+    """Find the fingerprints of watermarked exports in a text and who they were exported for"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        exports = trace_export(state.database, request.text)
+        return TraceExportResponse(
+            fingerprints=find_fingerprints(request.text),
+            exports=[
+                ExportFingerprintItem(
+                    fingerprint=export.fingerprint,
+                    instance_id=export.instance_id,
+                    user_id=export.user_id,
+                    export_kind=export.export_kind,
+                    subject_id=export.subject_id,
+                    created_at=export.created_at,
+                )
+                for export in exports
+            ],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /admin/watermark/trace endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/seed-demo", response_model=SeedDemoResponse)
+async def post_seed_demo():
+    """Add the demo textbook with its pre-generated summary, glossary, problems, solutions and hints, no LLM needed"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        document, created = seed_demo(state.database)
+        return SeedDemoResponse(
+            document_id=document.document_id,
+            title=document.title,
+            created=created,
+            problem_count=len(state.database.get_problems(document.document_id)),
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /admin/seed-demo endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
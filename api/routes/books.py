@@ -0,0 +1,661 @@
+# Book routes
+# Uploading, listing, exporting and deleting books, their PDF pages and TOC, handwriting transcriptions, and archiving.
+
+import io
+import os
+from pathlib import Path
+from typing import Optional
+import base64
+import uuid
+
+from fastapi import APIRouter, HTTPException, Query, UploadFile, File, Form
+from fastapi.responses import Response, FileResponse
+
+from textbook.database import ChapterInfo, BookInfo, PageInfo
+from textbook.bundle import export_book_bundle, import_book_bundle, BundleError
+from textbook.watermark import EXPORT_BOOK_BUNDLE, issue_watermark
+from textbook.integrity import register_file_artifact, SOURCE_PDF_ARTIFACT
+from textbook.pdf_validation import prepare_pdf, convert_image_to_pdf, PDFValidationError
+from textbook.reader import INGESTION_MODES, INGESTION_MODE_PRINTED, INGESTION_MODE_HANDWRITTEN, LOW_CONFIDENCE_THRESHOLD, TRANSCRIPTION_SOURCE_MANUAL, BOOK_SOURCE_PDF, BOOK_SOURCE_IMAGE
+from textbook.status import item_status, matches_status, STATUS_FILTERS
+from textbook.provider_errors import ModelError
+from textbook.capabilities import FEATURE_LLM
+from textbook.images import SIZE_FULL
+
+from api.models import BookIdRequest, PageNumberRequest, PageImageRequest, UpdateBookInfoRequest, UpdateTocRequest, UpdateAlignmentOffsetRequest, UpdateBookFieldsRequest, CheckAlignmentOffsetRequest, TotalPagesResponse, PageTextResponse, PageImageResponse, BookInfoResponse, TocExistsResponse, TocResponse, AlignmentOffsetResponse, AlignmentCheckResponse, BooksListResponse, UploadBookResponse, DeleteBookResponse, BookListItem, TranscribeBookRequest, CorrectTranscriptionRequest, TranscriptionItem, TranscriptionsResponse, UpdateStatusRequest, StatusResponse
+from api.state import state, require_disk_space, require_feature, get_pdf_path_from_book_id, get_reader
+from api.items import check_status_filter, status_response
+
+router = APIRouter(tags=["books"])
+
+
+@router.post("/total-pages", response_model=TotalPagesResponse)
+async def get_total_pages(request: BookIdRequest):
+    """Get the total number of pages in a PDF"""
+    try:
+        pdf_path = get_pdf_path_from_book_id(request.book_id)
+        with get_reader(pdf_path) as reader:
+            total_pages = reader.get_total_pages()
+            return TotalPagesResponse(
+                book_id=request.book_id,
+                total_pages=total_pages
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post("/page-text", response_model=PageTextResponse)
+async def get_page_text(request: PageNumberRequest):
+    """Get text content from a specific page"""
+    try:
+        pdf_path = get_pdf_path_from_book_id(request.book_id)
+        with get_reader(pdf_path) as reader:
+            text = reader.get_page_content(request.page_number)
+            return PageTextResponse(
+                book_id=request.book_id,
+                page_number=request.page_number,
+                text=text
+            )
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post("/page-image", response_model=PageImageResponse)
+async def get_page_image(request: PageImageRequest):
+    """Get image representation of a specific page"""
+    try:
+        pdf_path = get_pdf_path_from_book_id(request.book_id)
+        with get_reader(pdf_path) as reader:
+            img = reader.get_page_as_image(request.page_number, request.dpi, preprocess=request.preprocess)
+            
+            # Convert PIL Image to base64
+            img_buffer = io.BytesIO()
+            img.save(img_buffer, format="PNG")
+            img_base64 = base64.b64encode(img_buffer.getvalue()).decode("utf-8")
+            
+            return PageImageResponse(
+                book_id=request.book_id,
+                page_number=request.page_number,
+                image_base64=img_base64,
+                format="PNG"
+            )
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/page-image-binary")
+async def get_page_image_binary(
+    book_id: int = Query(..., description="ID of the book"),
+    page_number: int = Query(..., ge=0, description="Page number (0-indexed)"),
+    dpi: int = Query(default=150, ge=72, le=300, description="DPI for image rendering"),
+    preprocess: bool = Query(default=False, description="Apply scanned-page preprocessing (rotate, deskew, contrast)"),
+    size: str = Query(default=SIZE_FULL, description="Size variant: thumb, small, medium or full"),
+    image_format: Optional[str] = Query(default=None, alias="format", description="png, webp or avif, the configured format by default"),
+):
+    """Get image representation of a specific page as binary image, rendered once per size and format and cached"""
+    try:
+        if not state.image_cache:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        pdf_path = get_pdf_path_from_book_id(book_id)
+
+        def render():
+            with get_reader(pdf_path) as reader:
+                return reader.get_page_as_image(page_number, dpi, preprocess=preprocess)
+
+        key = f"page {page_number} at {dpi} dpi{', preprocessed' if preprocess else ''}"
+        content, media_type = state.image_cache.variant(pdf_path, key, size, image_format, render)
+        return Response(content=content, media_type=media_type)
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/view-pdf")
+async def view_pdf(book_id: int = Query(..., description="ID of the book")):
+    """View/serve the PDF file for a book"""
+    try:
+        pdf_path = get_pdf_path_from_book_id(book_id)
+        
+        print(pdf_path)
+        if not os.path.exists(pdf_path):
+            raise HTTPException(status_code=404, detail=f"PDF file not found: {pdf_path}")
+        
+        return FileResponse(
+            path=str(pdf_path),
+            media_type="application/pdf",
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post("/update-book-info", response_model=BookInfoResponse)
+async def update_book_info(request: UpdateBookInfoRequest):
+    if state.struct_logger:
+        state.struct_logger.info(f"Updating book info for book {request.book_id}", request=request)
+
+    """Extract and update book information from the PDF"""
+    try:
+        pdf_path = get_pdf_path_from_book_id(request.book_id)
+        with get_reader(pdf_path) as reader:
+            reader.update_book_info()
+            return BookInfoResponse(
+                book_id=request.book_id,
+                message="Book info updated successfully"
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/check-toc-exists", response_model=TocExistsResponse)
+async def check_toc_exists(book_id: int = Query(..., description="ID of the book")):
+    """Check if table of contents exists for the PDF"""
+    try:
+        pdf_path = get_pdf_path_from_book_id(book_id)
+        with get_reader(pdf_path) as reader:
+            exists = reader.check_if_toc_exists()
+            return TocExistsResponse(
+                book_id=book_id,
+                toc_exists=exists
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post("/update-toc", response_model=TocResponse)
+async def update_toc(request: UpdateTocRequest):
+    """Extract and update table of contents from the PDF"""
+    if state.struct_logger:
+        state.struct_logger.info(f"Updating table of contents for book {request.book_id}", request=request)
+    try:
+        pdf_path = get_pdf_path_from_book_id(request.book_id)
+        with get_reader(pdf_path) as reader:
+            if not reader.check_if_book_exists_and_load():
+                raise HTTPException(status_code=404, detail="Book not found")
+            reader.update_toc(caching=request.caching, overwrite=request.overwrite)
+            return TocResponse(
+                book_id=request.book_id,
+                message="Table of contents updated successfully"
+            )
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.post("/update-alignment-offset", response_model=AlignmentOffsetResponse)
+async def update_alignment_offset(request: UpdateAlignmentOffsetRequest):
+    """Update the alignment offset for page numbers"""
+    try:
+        pdf_path = get_pdf_path_from_book_id(request.book_id)
+        with get_reader(pdf_path) as reader:
+            reader.update_alignment_offset(request.page_number)
+            return AlignmentOffsetResponse(
+                book_id=request.book_id,
+                message="Alignment offset updated successfully",
+                offset=request.page_number
+            )
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.put("/books/{book_id}", response_model=BookInfoResponse)
+async def update_book_fields(book_id: int, request: UpdateBookFieldsRequest):
+    """Update book fields directly (title, author, keywords, alignment offset)"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            book = session.query(BookInfo).filter(BookInfo.book_id == book_id).first()
+            if not book:
+                raise HTTPException(status_code=404, detail=f"Book not found: {book_id}")
+            
+            # Update fields if provided
+            if request.book_name is not None:
+                book.book_name = request.book_name
+            if request.book_author is not None:
+                book.book_author = request.book_author
+            if request.book_keywords is not None:
+                book.book_keywords = request.book_keywords
+            if request.alignment_offset is not None:
+                book.book_alignment_offset = request.alignment_offset
+            
+            session.commit()
+            
+            return BookInfoResponse(
+                book_id=book_id,
+                message="Book fields updated successfully"
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id} PUT endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/check-alignment-offset", response_model=AlignmentCheckResponse)
+async def check_alignment_offset(request: CheckAlignmentOffsetRequest):
+    """Check alignment offset by returning sample pages"""
+    try:
+        pdf_path = get_pdf_path_from_book_id(request.book_id)
+        with get_reader(pdf_path) as reader:
+            results = reader.check_alignment_offset()
+            return AlignmentCheckResponse(
+                book_id=request.book_id,
+                results=results
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        raise HTTPException(status_code=500, detail=str(e))
+
+
+@router.get("/books", response_model=BooksListResponse)
+async def get_books(status: Optional[str] = Query(default=None, description=f"Only return books with this status, one of {', '.join(STATUS_FILTERS)}, by default all but archived ones")):
+    """Get all books from the database"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        check_status_filter(status)
+        
+        book_items = []
+        
+        with state.database.new_session() as session:
+            # Get all books in the same session
+            all_books = session.query(BookInfo).all()
+            
+            for book in all_books:
+                book_status = item_status(book)
+                if not matches_status(book_status, status):
+                    continue
+                try:
+                    # Check if TOC exists by checking if there are chapters
+                    toc_exists = False
+                    if book.book_id:
+                        chapters = session.query(ChapterInfo).filter(
+                            ChapterInfo.book_id == book.book_id
+                        ).all()
+                        toc_exists = len(chapters) > 0
+                    
+                    book_items.append(BookListItem(
+                        book_id=book.book_id,
+                        book_name=book.book_name,
+                        book_author=book.book_author,
+                        total_pages=book.book_pages,
+                        book_keywords=book.book_keywords,
+                        book_summary=book.book_summary,
+                        book_file_name=book.book_file_name,
+                        book_toc_end_page=book.book_toc_end_page,
+                        alignment_offset=book.book_alignment_offset,
+                        ingestion_mode=book.book_ingestion_mode or INGESTION_MODE_PRINTED,
+                        source_type=book.book_source_type,
+                        source_url=book.book_source_url,
+                        toc_exists=toc_exists,
+                        status=book_status
+                    ))
+                except Exception as book_error:
+                    # Log error for individual book but continue processing others
+                    print(f"Error processing book {book.book_id}: {book_error}")
+                    continue
+        
+        return BooksListResponse(books=book_items)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+HANDWRITTEN_IMAGE_EXTENSIONS = (".png", ".jpg", ".jpeg")
+
+
+@router.post("/upload-book", response_model=UploadBookResponse)
+async def upload_book(
+    file: UploadFile = File(..., description="PDF file to upload, or a photo of a page in handwritten mode"),
+    password: Optional[str] = Form(default=None, description="Password for encrypted PDFs"),
+    ingestion_mode: str = Form(default=INGESTION_MODE_PRINTED, description="How pages are read: printed (text layer and OCR) or handwritten (vision LLM transcription)"),
+):
+    """Upload a PDF file and create a book entry in the database"""
+    try:
+        require_feature(FEATURE_LLM)
+        if not state.llm or not state.database:
+            raise HTTPException(status_code=500, detail="LLM or Context not initialized")
+        require_disk_space()
+
+        if ingestion_mode not in INGESTION_MODES:
+            raise HTTPException(status_code=400, detail=f"Unsupported ingestion mode: {ingestion_mode}, expected one of {', '.join(INGESTION_MODES)}")
+        
+        # Validate file type, photos of notes are accepted in handwritten mode
+        file_extension = Path(file.filename).suffix.lower() if file.filename else ""
+        is_image = ingestion_mode == INGESTION_MODE_HANDWRITTEN and file_extension in HANDWRITTEN_IMAGE_EXTENSIONS
+        if file_extension != ".pdf" and not is_image:
+            raise HTTPException(status_code=400, detail="Only PDF files are allowed" if ingestion_mode == INGESTION_MODE_PRINTED else "Only PDF, PNG and JPEG files are allowed")
+        
+        # Generate UUID for filename
+        file_stem = str(uuid.uuid4())
+        unique_filename = f"{file_stem}.pdf"
+        file_path = os.path.join(state.uploads_dir, unique_filename)
+        
+        # Save uploaded file
+        content = await file.read()
+        if is_image:
+            image_path = os.path.join(state.uploads_dir, f"{file_stem}{file_extension}")
+            with open(image_path, "wb") as f:
+                f.write(content)
+            try:
+                convert_image_to_pdf(Path(image_path), Path(file_path))
+            except PDFValidationError as e:
+                raise HTTPException(status_code=400, detail={"code": e.code, "message": e.message})
+            finally:
+                os.remove(image_path)
+        else:
+            with open(file_path, "wb") as f:
+                f.write(content)
+
+        # Reject, decrypt or repair the PDF before anything renders it
+        try:
+            prepare_pdf(Path(file_path), password=password)
+        except PDFValidationError as e:
+            os.remove(file_path)
+            raise HTTPException(status_code=400, detail={"code": e.code, "message": e.message})
+        
+        # Use LazyTextbookReader to create book entry
+        try:
+            with get_reader(Path(file_path), ingestion_mode=ingestion_mode) as reader:
+                reader.update_book_info()
+                
+                # Get the created book info
+                book_info = reader.book_info
+                if not book_info or not book_info.book_id:
+                    raise HTTPException(status_code=500, detail="Failed to create book entry")
+
+                register_file_artifact(state.database, state.uploads_dir, book_info.book_id, SOURCE_PDF_ARTIFACT, unique_filename)
+                state.database.update_book_source(book_info.book_id, BOOK_SOURCE_IMAGE if is_image else BOOK_SOURCE_PDF)
+                
+                return UploadBookResponse(
+                    book_id=book_info.book_id,
+                    message="Book uploaded and created successfully"
+                )
+        except Exception as reader_error:
+            # If book creation fails, clean up the uploaded file
+            if os.path.exists(file_path):
+                os.remove(file_path)
+            raise HTTPException(status_code=500, detail=f"Failed to create book entry: {str(reader_error)}")
+            
+    except HTTPException:
+        raise
+    except ModelError as e:
+        raise HTTPException(status_code=502, detail={"code": e.category, "message": e.user_message})
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /upload-book endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.delete("/delete-book", response_model=DeleteBookResponse)
+async def delete_book(book_id: int = Query(..., description="ID of the book to delete")):
+    """Delete a book from both the database and the file system"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        # Get pdf_path before deleting
+        pdf_path = get_pdf_path_from_book_id(book_id)
+        
+        # Delete from database
+        with state.database.new_session() as session:
+            book = session.query(BookInfo).filter(BookInfo.book_id == book_id).first()
+            if not book:
+                raise HTTPException(status_code=404, detail=f"Book not found: {book_id}")
+
+            # Files derived from the source PDF (e.g. article markdown) go with the book
+            artifact_paths = [Path(state.uploads_dir) / artifact.artifact_path for artifact in book.artifacts]
+            
+            session.delete(book)
+            session.commit()
+
+        for artifact_path in artifact_paths:
+            if artifact_path != pdf_path and os.path.exists(artifact_path):
+                try:
+                    os.remove(artifact_path)
+                except OSError as e:
+                    print(f"Warning: Failed to delete file {artifact_path}: {e}")
+        
+        # Delete file from file system if it exists
+        file_deleted = False
+        if os.path.exists(pdf_path):
+            try:
+                os.remove(pdf_path)
+                file_deleted = True
+            except OSError as e:
+                # Log error but don't fail if file deletion fails
+                print(f"Warning: Failed to delete file {pdf_path}: {e}")
+        else:
+            print(f"File not found: {pdf_path}")
+        
+        return DeleteBookResponse(
+            book_id=book_id,
+            message="Book deleted successfully" + (f" (file {'deleted' if file_deleted else 'not found'})" if not file_deleted else ""),
+            deleted=True
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /delete-book endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/books/{book_id}/export")
+async def export_book(
+    book_id: int,
+    include_embeddings: bool = Query(default=False, description="Whether to include embedding blobs in the bundle"),
+    user_id: str = Query(default="default", description="User the bundle is exported for, recorded with its fingerprint"),
+):
+    """Export a book with its PDF and derived data as a portable zip bundle, watermarked when enabled in config.toml"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        pdf_path = get_pdf_path_from_book_id(book_id)
+        # The fingerprint is recorded before the bundle is built, an export that fails leaves an unused one
+        watermark = issue_watermark(state.database, state.watermark_settings, user_id, EXPORT_BOOK_BUNDLE, book_id)
+        bundle = export_book_bundle(state.database, book_id, pdf_path, include_embeddings=include_embeddings, watermark=watermark)
+
+        return Response(
+            content=bundle,
+            media_type="application/zip",
+            headers={"Content-Disposition": f'attachment; filename="book_{book_id}.zip"'},
+        )
+    except HTTPException:
+        raise
+    except BundleError as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/export endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/books/import", response_model=UploadBookResponse)
+async def import_book(file: UploadFile = File(..., description="Bundle zip file to import")):
+    """Import a book from a bundle produced by /books/{book_id}/export"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        if not file.filename or not file.filename.lower().endswith('.zip'):
+            raise HTTPException(status_code=400, detail="Only zip bundles are allowed")
+        require_disk_space()
+
+        content = await file.read()
+        book_id = import_book_bundle(state.database, content, state.uploads_dir)
+
+        return UploadBookResponse(
+            book_id=book_id,
+            message="Book imported successfully"
+        )
+    except HTTPException:
+        raise
+    except BundleError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/import endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _transcription_item(page: PageInfo) -> TranscriptionItem:
+    return TranscriptionItem(
+        page_id=page.page_id,
+        page_number=page.page_number,
+        transcription=page.transcription,
+        confidence=page.transcription_confidence,
+        source=page.transcription_source,
+        needs_review=page.transcription_source != TRANSCRIPTION_SOURCE_MANUAL and (page.transcription_confidence or 0.0) < LOW_CONFIDENCE_THRESHOLD,
+    )
+
+
+# Handwriting transcription endpoints
+@router.post("/books/{book_id}/transcribe", response_model=TranscriptionsResponse)
+async def transcribe_book(book_id: int, request: TranscribeBookRequest):
+    """Transcribe the pages of a handwritten book with the vision LLM"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        pdf_path = get_pdf_path_from_book_id(book_id)
+        with get_reader(pdf_path) as reader:
+            if not reader.check_if_book_exists_and_load():
+                raise HTTPException(status_code=404, detail="Book not found")
+            if reader.ingestion_mode != INGESTION_MODE_HANDWRITTEN:
+                raise HTTPException(status_code=400, detail=f"Book {book_id} is not in handwritten ingestion mode")
+
+            start_page = request.start_page if request.start_page is not None else 0
+            end_page = request.end_page if request.end_page is not None else reader.get_total_pages() - 1
+            if start_page > end_page:
+                raise HTTPException(status_code=400, detail="start_page must not be after end_page")
+
+            for page_number in range(start_page, end_page + 1):
+                reader.update_page_transcription(page_number, overwrite=request.overwrite)
+
+        pages = [page for page in state.database.get_pages_by_book_id(book_id) if start_page <= page.page_number <= end_page]
+        return TranscriptionsResponse(
+            book_id=book_id,
+            transcriptions=[_transcription_item(page) for page in pages if page.transcription is not None]
+        )
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/transcribe endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/books/{book_id}/transcriptions", response_model=TranscriptionsResponse)
+async def get_transcriptions(
+    book_id: int,
+    needs_review: Optional[bool] = Query(default=None, description="Only return pages that need (true) or do not need (false) manual review"),
+):
+    """Get the page transcriptions of a handwritten book"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        items = [_transcription_item(page) for page in state.database.get_pages_by_book_id(book_id) if page.transcription is not None]
+        if needs_review is not None:
+            items = [item for item in items if item.needs_review == needs_review]
+
+        return TranscriptionsResponse(book_id=book_id, transcriptions=items)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/transcriptions endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/pages/{page_id}/transcription", response_model=TranscriptionItem)
+async def correct_transcription(page_id: int, request: CorrectTranscriptionRequest):
+    """Replace the transcription of a page with a manual correction"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        with state.database.new_session() as session:
+            page = session.query(PageInfo).filter(PageInfo.page_id == page_id).first()
+            if not page:
+                raise HTTPException(status_code=404, detail=f"Page not found: {page_id}")
+
+            page.transcription = request.transcription
+            page.transcription_confidence = 1.0
+            page.transcription_source = TRANSCRIPTION_SOURCE_MANUAL
+            # The summary was generated from the old transcription
+            page.summary = None
+            session.commit()
+
+            return _transcription_item(page)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /pages/{page_id}/transcription PUT endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+# Archive and suspend endpoints
+@router.put("/books/{book_id}/status", response_model=StatusResponse)
+async def put_book_status(book_id: int, request: UpdateStatusRequest):
+    """Archive or suspend a book with all its decks and cards, or restore it"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        book = state.database.set_book_status(book_id, request.archived, request.suspended)
+        if book is None:
+            raise HTTPException(status_code=404, detail=f"Book not found: {book_id}")
+        return status_response("book", book_id, book)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/status endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
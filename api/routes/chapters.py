@@ -0,0 +1,507 @@
+# Chapter routes
+# Chapters, sections and pages of books, and archiving the deck of a chapter.
+
+from typing import Optional
+
+from fastapi import APIRouter, HTTPException, Query, Path as FastAPIPath
+
+from textbook.database import ChapterInfo, BookInfo, SectionInfo, PageInfo
+from textbook.transcript import video_deep_link
+from textbook.status import item_status, matches_status, STATUS_FILTERS
+
+from api.models import ChaptersResponse, ChapterResponse, SectionMessageResponse, UpdateSectionRequest, SectionItem, SectionsResponse, SectionResponse, PageMessageResponse, CreatePageRequest, UpdatePageRequest, PageItem, PagesResponse, PageResponse, ChapterItem, UpdateStatusRequest, StatusResponse
+from api.state import state, get_video_url
+from api.items import check_status_filter, status_response
+
+router = APIRouter(tags=["chapters"])
+
+
+@router.get("/chapters", response_model=ChaptersResponse)
+async def get_chapters(
+    book_id: int = Query(..., description="ID of the book"),
+    status: Optional[str] = Query(default=None, description=f"Only return chapters with this status, one of {', '.join(STATUS_FILTERS)}, by default all but archived ones"),
+):
+    """Get all chapters for a book by book_id"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        check_status_filter(status)
+
+        with state.database.new_session() as session:
+            # Get chapters for this book
+            chapters = session.query(ChapterInfo).filter(
+                ChapterInfo.book_id == book_id
+            ).order_by(ChapterInfo.start_page_number).all()
+            video_url = get_video_url(session, book_id)
+            book = session.query(BookInfo).filter(BookInfo.book_id == book_id).first()
+            
+            chapter_items = [
+                ChapterItem(
+                    chapter_id=ch.chapter_id,
+                    title=ch.title,
+                    start_page_number=ch.start_page_number,
+                    end_page_number=ch.end_page_number,
+                    book_index_string=ch.book_index_string,
+                    start_seconds=ch.start_seconds,
+                    end_seconds=ch.end_seconds,
+                    deep_link=video_deep_link(video_url, ch.start_seconds),
+                    status=item_status(ch, book)
+                )
+                for ch in chapters
+                if matches_status(item_status(ch, book), status)
+            ]
+            
+            return ChaptersResponse(
+                book_id=book_id,
+                chapters=chapter_items
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /chapters endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/chapters/{chapter_id}", response_model=ChapterResponse)
+async def get_chapter(chapter_id: int = FastAPIPath(..., ge=0, description="ID of the chapter")):
+    """Get a specific chapter by ID"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            chapter = session.query(ChapterInfo).filter(ChapterInfo.chapter_id == chapter_id).first()
+            if not chapter:
+                raise HTTPException(status_code=404, detail=f"Chapter not found: {chapter_id}")
+            
+            return ChapterResponse(
+                chapter=ChapterItem(
+                    chapter_id=chapter.chapter_id,
+                    title=chapter.title,
+                    start_page_number=chapter.start_page_number,
+                    end_page_number=chapter.end_page_number,
+                    book_index_string=chapter.book_index_string,
+                    start_seconds=chapter.start_seconds,
+                    end_seconds=chapter.end_seconds,
+                    deep_link=video_deep_link(get_video_url(session, chapter.book_id), chapter.start_seconds),
+                    status=item_status(chapter, chapter.book)
+                )
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /chapters/{chapter_id} GET endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+# # Section CRUD endpoints
+# @router.post("/sections", response_model=SectionMessageResponse)
+# async def extract_sections(request: ExtractSectionsRequest):
+#     """Create a new section"""
+#     try:
+#         if not database:
+#             raise HTTPException(status_code=500, detail="Context not initialized")
+        
+#         with database.new_session() as session:
+#             # Verify book exists
+#             book = session.query(BookInfo).filter(BookInfo.book_id == request.book_id).first()
+#             if not book:
+#                 raise HTTPException(status_code=404, detail=f"Book not found: {request.book_id}")
+            
+#             # Verify chapter exists if provided
+#             if request.chapter_id is not None:
+#                 chapter = session.query(ChapterInfo).filter(ChapterInfo.chapter_id == request.chapter_id).first()
+#                 if not chapter:
+#                     raise HTTPException(status_code=404, detail=f"Chapter not found: {request.chapter_id}")
+            
+#             return SectionMessageResponse(
+#                 section_id=section.section_id,
+#                 message="Section created successfully"
+#             )
+#     except HTTPException:
+#         raise
+#     except Exception as e:
+#         import traceback
+#         error_trace = traceback.format_exc()
+#         print(f"Error in /sections POST endpoint: {error_trace}")
+#         raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/sections", response_model=SectionsResponse)
+async def get_sections(
+    book_id: int = Query(..., description="ID of the book"),
+    chapter_id: Optional[int] = Query(default=None, description="Optional chapter ID to filter sections"),
+):
+    """Get all sections for a book, optionally filtered by chapter"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            query = session.query(SectionInfo).filter(SectionInfo.book_id == book_id)
+            
+            if chapter_id is not None:
+                query = query.filter(SectionInfo.chapter_id == chapter_id)
+            
+            sections = query.order_by(SectionInfo.start_page_number).all()
+            
+            section_items = [
+                SectionItem(
+                    section_id=sec.section_id,
+                    title=sec.title,
+                    start_page_number=sec.start_page_number,
+                    end_page_number=sec.end_page_number,
+                    summary=sec.summary,
+                    chapter_id=sec.chapter_id,
+                    book_index_string=sec.book_index_string,
+                    book_id=sec.book_id
+                )
+                for sec in sections
+            ]
+            
+            return SectionsResponse(
+                book_id=book_id,
+                sections=section_items
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /sections GET endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/sections/{section_id}", response_model=SectionResponse)
+async def get_section(section_id: int = FastAPIPath(..., ge=0, description="ID of the section")):
+    """Get a specific section by ID"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            section = session.query(SectionInfo).filter(SectionInfo.section_id == section_id).first()
+            if not section:
+                raise HTTPException(status_code=404, detail=f"Section not found: {section_id}")
+            
+            return SectionResponse(
+                section=SectionItem(
+                    section_id=section.section_id,
+                    title=section.title,
+                    start_page_number=section.start_page_number,
+                    end_page_number=section.end_page_number,
+                    summary=section.summary,
+                    chapter_id=section.chapter_id,
+                    book_index_string=section.book_index_string,
+                    book_id=section.book_id
+                )
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /sections/{section_id} GET endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/sections/{section_id}", response_model=SectionMessageResponse)
+async def update_section(request: UpdateSectionRequest, section_id: int = FastAPIPath(..., ge=0, description="ID of the section")):
+    """Update a section"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            section = session.query(SectionInfo).filter(SectionInfo.section_id == section_id).first()
+            if not section:
+                raise HTTPException(status_code=404, detail=f"Section not found: {section_id}")
+            
+            # Verify chapter exists if provided
+            if request.chapter_id is not None:
+                chapter = session.query(ChapterInfo).filter(ChapterInfo.chapter_id == request.chapter_id).first()
+                if not chapter:
+                    raise HTTPException(status_code=404, detail=f"Chapter not found: {request.chapter_id}")
+            
+            # Check for duplicate title if title is being updated
+            if request.title is not None and request.title != section.title:
+                existing = session.query(SectionInfo).filter(
+                    SectionInfo.book_id == section.book_id,
+                    SectionInfo.title == request.title,
+                    SectionInfo.section_id != section_id
+                ).first()
+                if existing:
+                    raise HTTPException(status_code=400, detail=f"Section with title '{request.title}' already exists for this book")
+            
+            # Update fields
+            if request.title is not None:
+                section.title = request.title
+            if request.start_page_number is not None:
+                section.start_page_number = request.start_page_number
+            if request.end_page_number is not None:
+                section.end_page_number = request.end_page_number
+            if request.summary is not None:
+                section.summary = request.summary
+            if request.chapter_id is not None:
+                section.chapter_id = request.chapter_id
+            if request.book_index_string is not None:
+                section.book_index_string = request.book_index_string
+            
+            session.commit()
+            
+            return SectionMessageResponse(
+                section_id=section_id,
+                message="Section updated successfully"
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /sections/{section_id} PUT endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.delete("/sections/{section_id}", response_model=SectionMessageResponse)
+async def delete_section(section_id: int = FastAPIPath(..., ge=0, description="ID of the section")):
+    """Delete a section"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            section = session.query(SectionInfo).filter(SectionInfo.section_id == section_id).first()
+            if not section:
+                raise HTTPException(status_code=404, detail=f"Section not found: {section_id}")
+            
+            session.delete(section)
+            session.commit()
+            
+            return SectionMessageResponse(
+                section_id=section_id,
+                message="Section deleted successfully"
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /sections/{section_id} DELETE endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+# Page CRUD endpoints
+@router.post("/pages", response_model=PageMessageResponse)
+async def create_page(request: CreatePageRequest):
+    """Create a new page info entry"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            # Verify book exists
+            book = session.query(BookInfo).filter(BookInfo.book_id == request.book_id).first()
+            if not book:
+                raise HTTPException(status_code=404, detail=f"Book not found: {request.book_id}")
+            
+            # Check for duplicate page_number within the same book
+            existing = session.query(PageInfo).filter(
+                PageInfo.book_id == request.book_id,
+                PageInfo.page_number == request.page_number
+            ).first()
+            if existing:
+                raise HTTPException(status_code=400, detail=f"Page with page_number {request.page_number} already exists for this book")
+            
+            # Create new page
+            page = PageInfo(
+                book_id=request.book_id,
+                page_number=request.page_number,
+                summary=request.summary
+            )
+            
+            session.add(page)
+            session.commit()
+            session.refresh(page)
+            
+            return PageMessageResponse(
+                page_id=page.page_id,
+                message="Page created successfully"
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /pages POST endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/pages", response_model=PagesResponse)
+async def get_pages(book_id: int = Query(..., description="ID of the book")):
+    """Get all pages for a book"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            pages = session.query(PageInfo).filter(
+                PageInfo.book_id == book_id
+            ).order_by(PageInfo.page_number).all()
+            video_url = get_video_url(session, book_id)
+            
+            page_items = [
+                PageItem(
+                    page_id=page.page_id,
+                    page_number=page.page_number,
+                    summary=page.summary,
+                    transcription=page.transcription,
+                    transcription_confidence=page.transcription_confidence,
+                    transcription_source=page.transcription_source,
+                    start_seconds=page.start_seconds,
+                    end_seconds=page.end_seconds,
+                    deep_link=video_deep_link(video_url, page.start_seconds),
+                    book_id=page.book_id
+                )
+                for page in pages
+            ]
+            
+            return PagesResponse(
+                book_id=book_id,
+                pages=page_items
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /pages GET endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/pages/{page_id}", response_model=PageResponse)
+async def get_page(page_id: int):
+    """Get a specific page by ID"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            page = session.query(PageInfo).filter(PageInfo.page_id == page_id).first()
+            if not page:
+                raise HTTPException(status_code=404, detail=f"Page not found: {page_id}")
+            
+            return PageResponse(
+                page=PageItem(
+                    page_id=page.page_id,
+                    page_number=page.page_number,
+                    summary=page.summary,
+                    transcription=page.transcription,
+                    transcription_confidence=page.transcription_confidence,
+                    transcription_source=page.transcription_source,
+                    start_seconds=page.start_seconds,
+                    end_seconds=page.end_seconds,
+                    deep_link=video_deep_link(get_video_url(session, page.book_id), page.start_seconds),
+                    book_id=page.book_id
+                )
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /pages/{page_id} GET endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/pages/{page_id}", response_model=PageMessageResponse)
+async def update_page(page_id: int, request: UpdatePageRequest):
+    """Update a page"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            page = session.query(PageInfo).filter(PageInfo.page_id == page_id).first()
+            if not page:
+                raise HTTPException(status_code=404, detail=f"Page not found: {page_id}")
+            
+            # Check for duplicate page_number if page_number is being updated
+            if request.page_number is not None and request.page_number != page.page_number:
+                existing = session.query(PageInfo).filter(
+                    PageInfo.book_id == page.book_id,
+                    PageInfo.page_number == request.page_number,
+                    PageInfo.page_id != page_id
+                ).first()
+                if existing:
+                    raise HTTPException(status_code=400, detail=f"Page with page_number {request.page_number} already exists for this book")
+            
+            # Update fields
+            if request.page_number is not None:
+                page.page_number = request.page_number
+            if request.summary is not None:
+                page.summary = request.summary
+            
+            session.commit()
+            
+            return PageMessageResponse(
+                page_id=page_id,
+                message="Page updated successfully"
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /pages/{page_id} PUT endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.delete("/pages/{page_id}", response_model=PageMessageResponse)
+async def delete_page(page_id: int):
+    """Delete a page"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        
+        with state.database.new_session() as session:
+            page = session.query(PageInfo).filter(PageInfo.page_id == page_id).first()
+            if not page:
+                raise HTTPException(status_code=404, detail=f"Page not found: {page_id}")
+            
+            session.delete(page)
+            session.commit()
+            
+            return PageMessageResponse(
+                page_id=page_id,
+                message="Page deleted successfully"
+            )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /pages/{page_id} DELETE endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/chapters/{chapter_id}/status", response_model=StatusResponse)
+async def put_chapter_status(chapter_id: int, request: UpdateStatusRequest):
+    """Archive or suspend the deck of a chapter, its flashcards and exercises, or restore it"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        chapter = state.database.set_chapter_status(chapter_id, request.archived, request.suspended)
+        if chapter is None:
+            raise HTTPException(status_code=404, detail=f"Chapter not found: {chapter_id}")
+        return status_response("chapter", chapter_id, chapter, state.database.get_book_by_id(chapter.book_id))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /chapters/{chapter_id}/status endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
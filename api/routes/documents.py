@@ -0,0 +1,1156 @@
+# Document routes
+# Ingesting documents that are not uploaded as books: OCR jobs of uploaded PDFs, web articles, lecture transcripts and
+# course notebooks. The library of all documents, books included, can be listed, renamed, tagged and deleted, and the
+# OCR output of uploaded PDFs read by page or section, or through the table of contents detected in it, and the figures
+# OCR extracted served in a size and image format. Problems, a summary and a glossary are generated from the OCR output
+# by LLM jobs.
+
+import json
+import os
+import shutil
+from dataclasses import asdict
+from pathlib import Path
+from typing import Iterator, List, Optional
+import uuid
+
+from fastapi import APIRouter, HTTPException, UploadFile, File, Form, Query
+from PIL import Image
+from fastapi.responses import Response, StreamingResponse
+
+from textbook.integrity import register_file_artifact, SOURCE_PDF_ARTIFACT, ARTICLE_MARKDOWN_ARTIFACT, TRANSCRIPT_MARKDOWN_ARTIFACT, NOTEBOOK_MARKDOWN_ARTIFACT
+from textbook.pdf_validation import prepare_pdf, PDFValidationError
+from textbook.reader import INGESTION_MODE_PRINTED, BOOK_SOURCE_WEB_ARTICLE, BOOK_SOURCE_TRANSCRIPT, BOOK_SOURCE_NOTEBOOK
+from textbook.web_article import ingest_article, WebArticleError
+from textbook.transcript import parse_transcript, fetch_youtube_captions, ingest_transcript, TranscriptError, TRANSCRIPT_EXTENSIONS
+from textbook.notebook import ingest_notebook, NotebookError, NOTEBOOK_EXTENSIONS
+from textbook.jobs import JobHandle, JOB_KIND_OCR, JOB_KIND_PROBLEM_EXTRACTION, JOB_KIND_PROBLEM_QUALITY, JOB_KIND_DOCUMENT_SUMMARY, JOB_KIND_GLOSSARY, JOB_KIND_SEGMENTATION, JOB_KIND_SOLUTION
+from textbook.pipeline import PipelineNode
+from textbook.provider_errors import ModelError
+from textbook.circuit_breaker import ServiceUnavailable
+from textbook.capabilities import FEATURE_LLM, FEATURE_MINERU
+from textbook.mineru import images_dir_name, store_images
+from textbook.chunked_ocr import OCR_CHUNK_DIR, ChunkedOcr
+from textbook.images import SIZE_FULL
+from textbook.compression import write_markdown
+from textbook.content import chunk_content, source_markdown, store_chunks, get_page_markdown, get_section, get_sections
+from textbook.library import add_upload, delete_document, detect_type, document_tags, list_documents, rename_document, set_ocr_status, tag_document, OCR_DONE, OCR_FAILED, OCR_PENDING, OCR_RUNNING
+from textbook.problems import document_chapters, extract_problems, problem_set_markdown
+from textbook.generation import deterministic
+from textbook.problem_quality import QUALITY_FLAGGED, QUALITY_REJECTED, score_problems
+from textbook.watermark import EXPORT_PROBLEM_SET, issue_watermark
+from textbook.digest import build_glossary, summarize_document
+from textbook.explain import HISTORY_MESSAGES, Passage, Selection, document_passages, explanation_prompt, retrieve_passages, save_exchange, select_range
+from textbook.model import LLM
+from textbook.profiles import TASK_EXPLAIN
+from textbook.prompt_templates import KIND_EXPLANATION_STYLE, check_explanation_style, registry
+from textbook.toc import analyze_toc, document_toc
+from textbook.text_layer import extract_with_text_layer, read_text_layer
+from textbook.segmentation import document_outline, segment_document
+from textbook.ingestion_settings import apply_overrides, document_ingestion_settings, document_overrides, store_overrides
+from textbook.database import ContentChunkInfo, DocumentInfo
+
+from api.models import UploadBookResponse, DocumentFromUrlRequest, SubmitJobResponse, DocumentItem, DocumentsResponse, RenameDocumentRequest, TagDocumentRequest, IngestionSettingsItem, UpdateDocumentSettingsRequest, DocumentSettingsResponse, DeleteDocumentResponse, ContentChunkItem, DocumentSectionsResponse, ExtractProblemsRequest, ProblemsResponse, DocumentSummaryResponse, GlossaryTermItem, GlossaryResponse, TocEntryItem, DocumentTocResponse, SegmentDocumentRequest, OutlineNodeItem, DocumentOutlineResponse, ExplainPassageRequest, ExplanationSourceItem, ExplanationSourcesEvent, ExplanationDoneEvent, SubmitJobGroupResponse, PipelineResponse
+from api.items import pipeline_response, problem_item
+from api.state import FORCE_REFRESH_DESCRIPTION, PROFILE_DESCRIPTION, state, get_llm, get_reader, require_disk_space, require_feature, require_profile
+
+router = APIRouter(prefix="/documents", tags=["documents"])
+
+STYLE_DESCRIPTION = f"Explanation style of the summary: {', '.join(registry.names(KIND_EXPLANATION_STYLE))}, by default the stored summary is generated"
+
+
+def _document_item(document: DocumentInfo) -> DocumentItem:
+    return DocumentItem(
+        document_id=document.document_id,
+        title=document.title,
+        author=document.author,
+        source_path=document.source_path,
+        page_count=document.page_count,
+        ocr_status=document.ocr_status,
+        detected_type=document.detected_type,
+        tags=document_tags(document),
+        job_id=document.job_id,
+        book_id=document.book_id,
+        created_at=document.created_at,
+        updated_at=document.updated_at,
+    )
+
+
+def _chunk_item(chunk: ContentChunkInfo, include_text: bool = True) -> ContentChunkItem:
+    return ContentChunkItem(
+        kind=chunk.chunk_kind,
+        index=chunk.chunk_index,
+        title=chunk.title,
+        level=chunk.level,
+        page_start=chunk.page_start,
+        page_end=chunk.page_end,
+        char_start=chunk.char_start,
+        char_end=chunk.char_end,
+        text=chunk.text if include_text else None,
+    )
+
+
+def ocr_document(params: dict, handle: JobHandle) -> dict:
+    """OCR job of an uploaded PDF, the markdown is stored next to it and the library document follows its status"""
+    pdf_path = Path(state.uploads_dir) / params["pdf_file_name"]
+    markdown_path = pdf_path.with_suffix(".md")
+    document_id = params.get("document_id")
+    try:
+        handle.cancellation_token.raise_if_cancelled()
+        if document_id is not None:
+            set_ocr_status(state.database, document_id, OCR_RUNNING)
+        settings = document_ingestion_settings(state.database, document_id, state.ingestion_settings, params.get("settings")) if document_id is not None else state.ingestion_settings
+        # Chunks of pages read are kept, so the job resumes after the last one when it runs again
+        backend = ChunkedOcr(state.ocr_backend, str(Path(state.uploads_dir) / OCR_CHUNK_DIR), state.ocr_pages_per_chunk, handle.cancellation_token, handle.report_progress)
+        if state.ocr_text_layer and settings.parse_method != "ocr":
+            layer = read_text_layer(str(pdf_path))
+            handle.report_progress("ocr", 0, f"Read {len(layer.pages)} pages of {pdf_path.name} from their text layer, OCRing {len(layer.scanned)} with {state.ocr_backend.name}")
+            result = extract_with_text_layer(backend, str(pdf_path), settings.ocr_language, settings.parse_method, job_id=handle.job_id, layer=layer)
+        else:
+            handle.report_progress("ocr", 0, f"Reading {pdf_path.name} with {state.ocr_backend.name}")
+            result = backend.ocr_pdf(str(pdf_path), settings.ocr_language, settings.parse_method, job_id=handle.job_id)
+        handle.cancellation_token.raise_if_cancelled()
+        handle.report_progress("write", 90, f"Writing {len(result.markdown)} characters of markdown and {len(result.images)} images")
+        if state.resource_monitor is not None:
+            # Fails the job with OutOfSpaceError rather than leaving truncated markdown on a full disk
+            state.resource_monitor.require_space()
+        markdown, chunks = chunk_content(store_images(state.uploads_dir, pdf_path.name, result), result.content_list)
+        markdown_path = write_markdown(markdown_path, markdown)
+        backend.discard()
+        if document_id is not None:
+            store_chunks(state.database, document_id, chunks)
+            handle.report_progress("toc", 95, "Looking for a table of contents")
+            analyze_toc(state.database, document_id)
+            # The chapters are found without the LLM here, POST /documents/{id}/segmentation has the LLM review them
+            segment_document(state.database, document_id, markdown)
+    except ServiceUnavailable:
+        # The job is deferred until the OCR service is back
+        if document_id is not None:
+            set_ocr_status(state.database, document_id, OCR_PENDING)
+        raise
+    except Exception:
+        if document_id is not None:
+            set_ocr_status(state.database, document_id, OCR_FAILED)
+        raise
+    if document_id is not None:
+        set_ocr_status(state.database, document_id, OCR_DONE, detect_type(markdown))
+    return {"pdf_file_name": pdf_path.name, "markdown_file_name": markdown_path.name}
+
+
+def extract_problems_job(params: dict, handle: JobHandle) -> dict:
+    """Problem extraction job of a document's OCR output, one or more prompts per chapter"""
+    # Prefetched jobs carry no settings, they follow those stored with the document
+    settings = document_ingestion_settings(state.database, params["document_id"], state.ingestion_settings, params.get("settings"))
+    llm = get_llm(JOB_KIND_PROBLEM_EXTRACTION, params.get("profile"))
+    if params.get("seed") is not None:
+        llm = llm.with_generation(deterministic(llm.generation, params["seed"]))
+    problem_ids = extract_problems(
+        state.database,
+        llm,
+        params["document_id"],
+        params.get("section_index"),
+        cancellation_token=handle.cancellation_token,
+        report_progress=handle.report_progress,
+        problems_per_section=settings.problems_per_section,
+        target_difficulty=settings.target_difficulty,
+        generation=llm.generation_record(),
+    )
+    if not state.problem_quality_settings.enabled or not problem_ids:
+        return {"document_id": params["document_id"], "problem_ids": problem_ids}
+    # Problems scoring low are extracted again by the model that extracted them
+    reports = score_problems(
+        state.database,
+        get_llm(JOB_KIND_PROBLEM_QUALITY, params.get("profile")),
+        params["document_id"],
+        problem_ids,
+        state.problem_quality_settings,
+        regenerate_llm=llm,
+        cancellation_token=handle.cancellation_token,
+        report_progress=handle.report_progress,
+    )
+    flagged = [report.problem_id for report in reports if report.score < state.problem_quality_settings.threshold]
+    return {"document_id": params["document_id"], "problem_ids": problem_ids, "flagged_problem_ids": flagged}
+
+
+def problem_quality_job(params: dict, handle: JobHandle) -> dict:
+    """Quality check job of the problems extracted from a document, a prompt per problem and one more per problem extracted again"""
+    reports = score_problems(
+        state.database,
+        get_llm(JOB_KIND_PROBLEM_QUALITY, params.get("profile")),
+        params["document_id"],
+        settings=state.problem_quality_settings,
+        regenerate_llm=get_llm(JOB_KIND_PROBLEM_EXTRACTION, params.get("profile")),
+        cancellation_token=handle.cancellation_token,
+        report_progress=handle.report_progress,
+    )
+    flagged = [problem.problem_id for problem in state.database.get_problems_by_quality(QUALITY_FLAGGED, params["document_id"])]
+    return {"document_id": params["document_id"], "checked": len(reports), "regenerated": sum(1 for report in reports if report.regenerated), "flagged_problem_ids": flagged}
+
+
+def summarize_document_job(params: dict, handle: JobHandle) -> dict:
+    """Summary job of a document's OCR output, a prompt per batch of pages and one combining them"""
+    style = params.get("style")
+    summary = summarize_document(state.database, get_llm(JOB_KIND_DOCUMENT_SUMMARY, params.get("profile")), params["document_id"], handle.cancellation_token, handle.report_progress, style)
+    if style is not None:
+        # Summaries in a style are not stored on the document, the job result is where they are read
+        return {"document_id": params["document_id"], "style": style, "summary": summary.result, **summary.summary()}
+    return {"document_id": params["document_id"], **summary.summary()}
+
+
+def segment_document_job(params: dict, handle: JobHandle) -> dict:
+    """Segmentation job of a document's OCR output, with a prompt reviewing the headings found unless use_llm is false"""
+    document_id = params["document_id"]
+    markdown = source_markdown(state.database, document_id, state.uploads_dir)
+    if markdown is None:
+        raise ValueError(f"Document {document_id} has no OCR output, run its OCR first")
+    llm = get_llm(JOB_KIND_SEGMENTATION, params.get("profile")) if params.get("use_llm", True) else None
+    outline = segment_document(state.database, document_id, markdown, llm, handle.cancellation_token, handle.report_progress)
+    return {"document_id": document_id, "chapters": len(outline)}
+
+
+def build_glossary_job(params: dict, handle: JobHandle) -> dict:
+    """Glossary job of a document's OCR output, a prompt per batch of pages whose terms are merged"""
+    glossary = build_glossary(state.database, get_llm(JOB_KIND_GLOSSARY, params.get("profile")), params["document_id"], handle.cancellation_token, handle.report_progress)
+    return {"document_id": params["document_id"], "term_ids": glossary.result, **glossary.summary()}
+
+
+def _document_pipeline(
+    document_id: int,
+    settings: dict,
+    profile: Optional[str] = None,
+    force_refresh: bool = False,
+    solutions: bool = True,
+    ocr_params: Optional[dict] = None,
+) -> List[PipelineNode]:
+    # OCR (of an upload) -> segmentation reviewed by the LLM -> problem extraction -> solutions of the problems extracted
+    common = {"profile": profile, "force_refresh": force_refresh}
+    nodes = [PipelineNode("ocr", JOB_KIND_OCR, ocr_params)] if ocr_params is not None else []
+    nodes.append(PipelineNode("segmentation", JOB_KIND_SEGMENTATION, {"document_id": document_id, **common}, [node.name for node in nodes]))
+    nodes.append(PipelineNode("extraction", JOB_KIND_PROBLEM_EXTRACTION, {"document_id": document_id, "section_index": None, "settings": settings, **common}, ["segmentation"]))
+    if solutions:
+        nodes.append(PipelineNode("solutions", JOB_KIND_SOLUTION, common, ["extraction"]))
+    return nodes
+
+
+@router.post("", response_model=SubmitJobResponse)
+async def create_document(
+    file: UploadFile = File(..., description="PDF file to OCR"),
+    password: Optional[str] = Form(default=None, description="Password for encrypted PDFs"),
+    ocr_language: Optional[str] = Form(default=None, description="MinerU language code of the document, overrides config.toml"),
+    parse_method: Optional[str] = Form(default=None, description="auto, txt or ocr, overrides config.toml"),
+    problems_per_section: Optional[int] = Form(default=None, description="Keep at most this many problems per chapter, overrides config.toml"),
+    target_difficulty: Optional[int] = Form(default=None, description="Difficulty from 1 to 5 the problems kept are picked around, overrides config.toml"),
+    pipeline: bool = Form(default=False, description="Run segmentation, problem extraction and solutions after the OCR, as a pipeline"),
+):
+    """Upload a PDF to the uploads directory, add it to the library and start an OCR job on it, poll the job for its progress"""
+    try:
+        if not state.job_pool or not state.pipeline_runner or not state.database:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+        require_feature(FEATURE_MINERU)
+        if pipeline:
+            require_feature(FEATURE_LLM)
+        require_disk_space()
+
+        overrides = {
+            name: value for name, value in (
+                ("ocr_language", ocr_language),
+                ("parse_method", parse_method),
+                ("problems_per_section", problems_per_section),
+                ("target_difficulty", target_difficulty),
+            ) if value is not None
+        }
+        try:
+            apply_overrides(state.ingestion_settings, overrides)
+        except ValueError as e:
+            raise HTTPException(status_code=400, detail=str(e))
+
+        file_extension = Path(file.filename).suffix.lower() if file.filename else ""
+        if file_extension != ".pdf":
+            raise HTTPException(status_code=400, detail="Only PDF files are allowed")
+
+        pdf_path = Path(state.uploads_dir) / f"{uuid.uuid4()}.pdf"
+        pdf_path.write_bytes(await file.read())
+
+        # Reject, decrypt or repair the PDF before MinerU gets it
+        try:
+            validation = prepare_pdf(pdf_path, password=password)
+        except PDFValidationError as e:
+            os.remove(pdf_path)
+            raise HTTPException(status_code=400, detail={"code": e.code, "message": e.message})
+
+        document = add_upload(state.database, Path(file.filename).stem, pdf_path.name, validation.page_count)
+        store_overrides(state.database, document.document_id, state.ingestion_settings, overrides)
+        ocr_params = {"pdf_file_name": pdf_path.name, "document_id": document.document_id, "settings": overrides}
+        pipeline_id = None
+        if pipeline:
+            started = state.pipeline_runner.submit(_document_pipeline(document.document_id, overrides, ocr_params=ocr_params))
+            pipeline_id = started.pipeline_id
+            job = state.job_pool.get_job_status(started.job_ids["ocr"])
+        else:
+            job = state.job_pool.submit(JOB_KIND_OCR, ocr_params)
+        state.database.update_document(document.document_id, job_id=job.job_id)
+        return SubmitJobResponse(job_id=job.job_id, kind=job.kind, status=job.status, message="OCR job submitted", document_id=document.document_id, pipeline_id=pipeline_id)
+
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/from-url", response_model=UploadBookResponse)
+async def create_document_from_url(request: DocumentFromUrlRequest):
+    """Fetch a web article, store it as markdown and PDF, and segment it by its headings"""
+    try:
+        require_feature(FEATURE_LLM)
+        if not state.llm or not state.database:
+            raise HTTPException(status_code=500, detail="LLM or Context not initialized")
+        require_disk_space()
+
+        file_stem = str(uuid.uuid4())
+        pdf_file_name = f"{file_stem}.pdf"
+        markdown_file_name = f"{file_stem}.md"
+        pdf_path = Path(state.uploads_dir) / pdf_file_name
+        markdown_path = Path(state.uploads_dir) / markdown_file_name
+
+        try:
+            article, toc = ingest_article(request.url, pdf_path, markdown_path)
+        except WebArticleError as e:
+            for path in (pdf_path, markdown_path):
+                if path.exists():
+                    os.remove(path)
+            raise HTTPException(status_code=400, detail=str(e))
+
+        try:
+            with get_reader(pdf_path) as reader:
+                book_info = state.database.create_book(article.title, article.byline or "", article.keywords or "", file_stem, reader.get_total_pages(), INGESTION_MODE_PRINTED)
+                state.database.update_book_source(book_info.book_id, BOOK_SOURCE_WEB_ARTICLE, request.url)
+                register_file_artifact(state.database, state.uploads_dir, book_info.book_id, SOURCE_PDF_ARTIFACT, pdf_file_name)
+                register_file_artifact(state.database, state.uploads_dir, book_info.book_id, ARTICLE_MARKDOWN_ARTIFACT, markdown_file_name)
+
+                # Headings replace the LLM TOC extraction, articles have no TOC page
+                reader.check_if_book_exists_and_load()
+                reader.save_toc(toc)
+
+                if request.generate_summaries:
+                    for page_number in range(reader.get_total_pages()):
+                        reader.create_or_update_page_info(page_number)
+
+                return UploadBookResponse(
+                    book_id=book_info.book_id,
+                    message="Article ingested successfully"
+                )
+        except Exception as reader_error:
+            for path in (pdf_path, markdown_path):
+                if path.exists():
+                    os.remove(path)
+            raise HTTPException(status_code=500, detail=f"Failed to create book entry: {str(reader_error)}")
+    except HTTPException:
+        raise
+    except ModelError as e:
+        raise HTTPException(status_code=502, detail={"code": e.category, "message": e.user_message})
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/from-url endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/from-transcript", response_model=UploadBookResponse)
+async def create_document_from_transcript(
+    file: Optional[UploadFile] = File(default=None, description="Subtitle or transcript file (.srt, .vtt or timestamped .txt)"),
+    video_url: Optional[str] = Form(default=None, description="URL of the video, captions are fetched from YouTube when no file is given"),
+    title: Optional[str] = Form(default=None, description="Title of the lecture, defaults to the file name or video URL"),
+    language: str = Form(default="en", description="Caption language to fetch from YouTube"),
+    generate_summaries: bool = Form(default=False, description="Whether to generate page summaries right away"),
+):
+    """Ingest a lecture transcript as timestamped sections that deep-link into the video"""
+    try:
+        require_feature(FEATURE_LLM)
+        if not state.llm or not state.database:
+            raise HTTPException(status_code=500, detail="LLM or Context not initialized")
+        require_disk_space()
+
+        if file is None and not video_url:
+            raise HTTPException(status_code=400, detail="Provide a transcript file, a video URL, or both")
+
+        try:
+            if file is not None:
+                if not file.filename or not file.filename.lower().endswith(TRANSCRIPT_EXTENSIONS):
+                    raise HTTPException(status_code=400, detail=f"Only {', '.join(TRANSCRIPT_EXTENSIONS)} transcript files are allowed")
+                content = (await file.read()).decode("utf-8-sig", errors="replace")
+                cues = parse_transcript(content, file.filename)
+            else:
+                assert video_url is not None
+                cues = fetch_youtube_captions(video_url, language)
+        except TranscriptError as e:
+            raise HTTPException(status_code=400, detail=str(e))
+
+        document_title = title or (Path(file.filename).stem if file is not None and file.filename else video_url) or "Lecture transcript"
+
+        file_stem = str(uuid.uuid4())
+        pdf_file_name = f"{file_stem}.pdf"
+        markdown_file_name = f"{file_stem}.md"
+        pdf_path = Path(state.uploads_dir) / pdf_file_name
+        markdown_path = Path(state.uploads_dir) / markdown_file_name
+
+        try:
+            transcript = ingest_transcript(document_title, cues, pdf_path, markdown_path, video_url)
+            with get_reader(pdf_path) as reader:
+                book_info = state.database.create_book(document_title, "", "", file_stem, reader.get_total_pages(), INGESTION_MODE_PRINTED)
+                state.database.update_book_source(book_info.book_id, BOOK_SOURCE_TRANSCRIPT, video_url)
+                register_file_artifact(state.database, state.uploads_dir, book_info.book_id, SOURCE_PDF_ARTIFACT, pdf_file_name)
+                register_file_artifact(state.database, state.uploads_dir, book_info.book_id, TRANSCRIPT_MARKDOWN_ARTIFACT, markdown_file_name)
+
+                reader.check_if_book_exists_and_load()
+                reader.save_toc(transcript.toc)
+                for chapter in state.database.get_chapters_by_book_id(book_info.book_id):
+                    start_seconds, end_seconds = transcript.chapter_ranges.get(chapter.title, (None, None))
+                    state.database.update_chapter_time_range(chapter.chapter_id, start_seconds, end_seconds)
+                for page_number, (start_seconds, end_seconds) in enumerate(transcript.page_ranges):
+                    state.database.save_page_time_range(book_info.book_id, page_number, start_seconds, end_seconds)
+
+                if generate_summaries:
+                    for page_number in range(reader.get_total_pages()):
+                        reader.create_or_update_page_info(page_number)
+
+                return UploadBookResponse(
+                    book_id=book_info.book_id,
+                    message="Transcript ingested successfully"
+                )
+        except Exception as reader_error:
+            for path in (pdf_path, markdown_path):
+                if path.exists():
+                    os.remove(path)
+            raise HTTPException(status_code=500, detail=f"Failed to create book entry: {str(reader_error)}")
+    except HTTPException:
+        raise
+    except ModelError as e:
+        raise HTTPException(status_code=502, detail={"code": e.category, "message": e.user_message})
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/from-transcript endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/from-notebook", response_model=UploadBookResponse)
+async def create_document_from_notebook(
+    file: UploadFile = File(..., description="Jupyter notebook (.ipynb) to ingest"),
+    title: Optional[str] = Form(default=None, description="Title of the notebook, defaults to its metadata title or file name"),
+    generate_summaries: bool = Form(default=False, description="Whether to generate page summaries right away"),
+):
+    """Ingest a course notebook, markdown cells as text and code cells as code, segmented by its headings"""
+    try:
+        require_feature(FEATURE_LLM)
+        if not state.llm or not state.database:
+            raise HTTPException(status_code=500, detail="LLM or Context not initialized")
+        require_disk_space()
+
+        if not file.filename or not file.filename.lower().endswith(NOTEBOOK_EXTENSIONS):
+            raise HTTPException(status_code=400, detail="Only .ipynb notebook files are allowed")
+
+        raw = await file.read()
+        file_stem = str(uuid.uuid4())
+        pdf_file_name = f"{file_stem}.pdf"
+        markdown_file_name = f"{file_stem}.md"
+        pdf_path = Path(state.uploads_dir) / pdf_file_name
+        markdown_path = Path(state.uploads_dir) / markdown_file_name
+
+        try:
+            ingested = ingest_notebook(raw, pdf_path, markdown_path, title=title, fallback_title=Path(file.filename).stem)
+        except NotebookError as e:
+            for path in (pdf_path, markdown_path):
+                if path.exists():
+                    os.remove(path)
+            raise HTTPException(status_code=400, detail=str(e))
+
+        try:
+            with get_reader(pdf_path) as reader:
+                book_info = state.database.create_book(ingested.title, "", ingested.notebook.language, file_stem, reader.get_total_pages(), INGESTION_MODE_PRINTED)
+                state.database.update_book_source(book_info.book_id, BOOK_SOURCE_NOTEBOOK)
+                register_file_artifact(state.database, state.uploads_dir, book_info.book_id, SOURCE_PDF_ARTIFACT, pdf_file_name)
+                register_file_artifact(state.database, state.uploads_dir, book_info.book_id, NOTEBOOK_MARKDOWN_ARTIFACT, markdown_file_name)
+
+                # Markdown headings replace the LLM TOC extraction, notebooks have no TOC page
+                reader.check_if_book_exists_and_load()
+                reader.save_toc(ingested.toc)
+
+                if generate_summaries:
+                    for page_number in range(reader.get_total_pages()):
+                        reader.create_or_update_page_info(page_number)
+
+                return UploadBookResponse(
+                    book_id=book_info.book_id,
+                    message="Notebook ingested successfully"
+                )
+        except Exception as reader_error:
+            for path in (pdf_path, markdown_path):
+                if path.exists():
+                    os.remove(path)
+            raise HTTPException(status_code=500, detail=f"Failed to create book entry: {str(reader_error)}")
+    except HTTPException:
+        raise
+    except ModelError as e:
+        raise HTTPException(status_code=502, detail={"code": e.category, "message": e.user_message})
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/from-notebook endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+# Library endpoints
+
+@router.get("", response_model=DocumentsResponse)
+async def get_documents(
+    tag: Optional[str] = Query(default=None, description="Only return documents with this tag"),
+    detected_type: Optional[str] = Query(default=None, description="Only return documents of this type, e.g. problem_set"),
+    ocr_status: Optional[str] = Query(default=None, description="Only return documents with this OCR status, e.g. failed"),
+):
+    """List the library, every ingested book and uploaded PDF with its metadata"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        documents = list_documents(state.database, tag, detected_type, ocr_status)
+        return DocumentsResponse(documents=[_document_item(document) for document in documents])
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/{document_id}", response_model=DocumentItem)
+async def put_document(document_id: int, request: RenameDocumentRequest):
+    """Rename a document, and the book ingested from it"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        document = rename_document(state.database, document_id, request.title, request.author)
+        if document is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        return _document_item(document)
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/{document_id}/tags", response_model=DocumentItem)
+async def put_document_tags(document_id: int, request: TagDocumentRequest):
+    """Add and remove tags of a document, tags are lowercase with dashes between words"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        document = tag_document(state.database, document_id, request.add, request.remove)
+        if document is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        return _document_item(document)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/tags endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _settings_response(document_id: int, overrides: dict) -> DocumentSettingsResponse:
+    settings = apply_overrides(state.ingestion_settings, overrides)
+    return DocumentSettingsResponse(document_id=document_id, settings=IngestionSettingsItem(**asdict(settings)), overridden=sorted(overrides))
+
+
+@router.get("/{document_id}/settings", response_model=DocumentSettingsResponse)
+async def get_document_settings(document_id: int):
+    """Get the ingestion settings of a document, config.toml with the document's overrides"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_document(document_id) is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        return _settings_response(document_id, document_overrides(state.database, document_id))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/settings endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/{document_id}/settings", response_model=DocumentSettingsResponse)
+async def put_document_settings(document_id: int, request: UpdateDocumentSettingsRequest):
+    """Replace the ingestion settings overridden for a document, those left out follow config.toml again. Jobs submitted from now on use them"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_document(document_id) is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        overrides = request.model_dump(exclude_none=True)
+        store_overrides(state.database, document_id, state.ingestion_settings, overrides)
+        return _settings_response(document_id, overrides)
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/settings endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.delete("/{document_id}", response_model=DeleteDocumentResponse)
+async def remove_document(document_id: int):
+    """Delete a document from the library with its book and files"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        document = state.database.get_document(document_id)
+        file_names = delete_document(state.database, document_id)
+        if document is None or file_names is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+
+        for file_name in file_names:
+            file_path = Path(state.uploads_dir) / file_name
+            if file_path.exists():
+                try:
+                    if file_path.is_dir():
+                        shutil.rmtree(file_path)
+                    else:
+                        os.remove(file_path)
+                except OSError as e:
+                    print(f"Warning: Failed to delete file {file_path}: {e}")
+
+        return DeleteDocumentResponse(document_id=document_id, book_id=document.book_id, message="Document deleted successfully", deleted=True)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{document_id}/pages/{page_number}/markdown", response_model=ContentChunkItem)
+async def get_document_page_markdown(document_id: int, page_number: int):
+    """Get the OCR markdown of one page of an uploaded document"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        page = get_page_markdown(state.database, document_id, page_number)
+        if page is None:
+            raise HTTPException(status_code=404, detail=f"Page {page_number} not found in the OCR output of document {document_id}")
+        return _chunk_item(page)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/pages/{page_number}/markdown endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{document_id}/sections", response_model=DocumentSectionsResponse)
+async def get_document_sections(document_id: int):
+    """List the sections of the OCR output of an uploaded document, without their text"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_document(document_id) is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        sections = get_sections(state.database, document_id)
+        return DocumentSectionsResponse(document_id=document_id, sections=[_chunk_item(section, include_text=False) for section in sections])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/sections endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{document_id}/sections/{section_index}", response_model=ContentChunkItem)
+async def get_document_section(document_id: int, section_index: int):
+    """Get a section of the OCR output of an uploaded document with its markdown, and prefetch what comes after it"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        section = get_section(state.database, document_id, section_index)
+        if section is None:
+            raise HTTPException(status_code=404, detail=f"Section {section_index} not found in the OCR output of document {document_id}")
+        if state.prefetcher:
+            # Reading goes on when prefetching fails
+            try:
+                state.prefetcher.chapter_read(document_id, section_index)
+            except Exception as e:
+                print(f"Error prefetching after section {section_index} of document {document_id}: {e}")
+        return _chunk_item(section)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/sections/{section_index} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{document_id}/figures/{name}")
+async def get_document_figure(
+    document_id: int,
+    name: str,
+    size: str = Query(default=SIZE_FULL, description="Size variant: thumb, small, medium or full"),
+    image_format: Optional[str] = Query(default=None, alias="format", description="png, webp or avif, the configured format by default"),
+):
+    """Get a figure OCR extracted from an uploaded document, by the name its markdown links to, in a size and format"""
+    try:
+        if not state.database or not state.image_cache:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        document = state.database.get_document(document_id)
+        if document is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        # Only the file name, a path must not leave the document's images directory
+        figure_path = Path(state.uploads_dir) / images_dir_name(document.source_path) / Path(name).name if document.source_path else None
+        if figure_path is None or not figure_path.is_file():
+            raise HTTPException(status_code=404, detail=f"Figure {name} not found in document {document_id}")
+
+        def render():
+            with Image.open(figure_path) as image:
+                image.load()
+                return image
+
+        content, media_type = state.image_cache.variant(figure_path, "figure", size, image_format, render)
+        return Response(content=content, media_type=media_type)
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/figures/{name} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{document_id}/problems/extract", response_model=SubmitJobResponse)
+async def post_extract_problems(
+    document_id: int,
+    request: Optional[ExtractProblemsRequest] = None,
+    profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION),
+    force_refresh: bool = Query(default=False, description=FORCE_REFRESH_DESCRIPTION),
+    seed: Optional[int] = Query(default=None, ge=0, description="Extract at temperature 0 with this sampling seed, to extract the same problems again later"),
+):
+    """Start a job extracting the problems of a document's OCR output, of one section or of every chapter"""
+    try:
+        require_feature(FEATURE_LLM)
+        require_profile(profile)
+        if not state.job_pool or not state.database:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+        if state.database.get_document(document_id) is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        section_index = request.section_index if request else None
+        if section_index is not None and get_section(state.database, document_id, section_index) is None:
+            raise HTTPException(status_code=404, detail=f"Section {section_index} not found in the OCR output of document {document_id}")
+
+        params = {"document_id": document_id, "section_index": section_index, "settings": document_overrides(state.database, document_id), "profile": profile, "force_refresh": force_refresh, "seed": seed}
+        job = state.job_pool.submit(JOB_KIND_PROBLEM_EXTRACTION, params)
+        return SubmitJobResponse(job_id=job.job_id, kind=job.kind, status=job.status, message="Problem extraction job submitted", document_id=document_id)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/problems/extract endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{document_id}/pipeline", response_model=PipelineResponse)
+async def post_document_pipeline(
+    document_id: int,
+    solutions: bool = Query(default=True, description="Generate the solutions of the problems extracted"),
+    profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION),
+    force_refresh: bool = Query(default=False, description=FORCE_REFRESH_DESCRIPTION),
+):
+    """Start a pipeline on a document's OCR output: segmentation, then problem extraction, then the solutions of the problems extracted"""
+    try:
+        require_feature(FEATURE_LLM)
+        require_profile(profile)
+        if not state.pipeline_runner or not state.database:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+        if state.database.get_document(document_id) is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+
+        nodes = _document_pipeline(document_id, document_overrides(state.database, document_id), profile, force_refresh, solutions)
+        pipeline = state.pipeline_runner.submit(nodes)
+        status = state.pipeline_runner.status(pipeline.pipeline_id)
+        assert status is not None
+        return pipeline_response(status, f"Pipeline of {len(nodes)} jobs submitted")
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/pipeline endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{document_id}/problems/extract-chapters", response_model=SubmitJobGroupResponse)
+async def post_extract_chapter_problems(
+    document_id: int,
+    profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION),
+    force_refresh: bool = Query(default=False, description=FORCE_REFRESH_DESCRIPTION),
+    seed: Optional[int] = Query(default=None, ge=0, description="Extract at temperature 0 with this sampling seed, to extract the same problems again later"),
+):
+    """Start a group of jobs extracting the problems of a whole document, a job per chapter, tracked with GET /jobs/groups/{group_id}"""
+    try:
+        require_feature(FEATURE_LLM)
+        require_profile(profile)
+        if not state.job_pool or not state.database:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+        if state.database.get_document(document_id) is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+
+        settings = document_overrides(state.database, document_id)
+        group = state.job_pool.submit_group([
+            (JOB_KIND_PROBLEM_EXTRACTION, {"document_id": document_id, "section_index": chapter.section_index, "settings": settings, "profile": profile, "force_refresh": force_refresh, "seed": seed})
+            for chapter in document_chapters(state.database, document_id)
+        ])
+        return SubmitJobGroupResponse(
+            group_id=group.group_id,
+            job_ids=[job.job_id for job in group.jobs],
+            message=f"Problem extraction jobs submitted for {group.total} chapters",
+            document_id=document_id,
+        )
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/problems/extract-chapters endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{document_id}/problems/quality", response_model=SubmitJobResponse)
+async def post_problem_quality(
+    document_id: int,
+    profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION),
+    force_refresh: bool = Query(default=False, description=FORCE_REFRESH_DESCRIPTION),
+):
+    """Start a job checking the quality of a document's problems again, extracting low scoring ones again and flagging those still low"""
+    try:
+        require_feature(FEATURE_LLM)
+        return _submit_document_job(document_id, JOB_KIND_PROBLEM_QUALITY, "Problem quality job submitted", profile=profile, force_refresh=force_refresh)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/problems/quality endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{document_id}/problems", response_model=ProblemsResponse)
+async def get_document_problems(
+    document_id: int,
+    section_index: Optional[int] = Query(default=None, description="Only the problems of this section"),
+):
+    """List the problems extracted from a document"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_document(document_id) is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        problems = state.database.get_problems(document_id, section_index)
+        return ProblemsResponse(document_id=document_id, problems=[problem_item(problem) for problem in problems])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/problems endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{document_id}/problems/export")
+async def export_document_problems(
+    document_id: int,
+    user_id: str = Query(default="default", description="User the problem set is exported for, recorded with its fingerprint"),
+    section_index: Optional[int] = Query(default=None, description="Only the problems of this section"),
+):
+    """Export the problems of a document as a markdown problem set to share, watermarked when enabled in config.toml"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        document = state.database.get_document(document_id)
+        if document is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        # Problems held back by the quality checks are not shared
+        problems = [
+            problem for problem in state.database.get_problems(document_id, section_index)
+            if problem.quality_status not in (QUALITY_FLAGGED, QUALITY_REJECTED)
+        ]
+        watermark = issue_watermark(state.database, state.watermark_settings, user_id, EXPORT_PROBLEM_SET, document_id)
+        return Response(
+            content=problem_set_markdown(document.title, problems, watermark),
+            media_type="text/markdown",
+            headers={"Content-Disposition": f'attachment; filename="problems_{document_id}.md"'},
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/problems/export endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _submit_document_job(
+    document_id: int,
+    kind: str,
+    message: str,
+    params: Optional[dict] = None,
+    profile: Optional[str] = None,
+    force_refresh: bool = False,
+) -> SubmitJobResponse:
+    # The digest and segmentation jobs all run on a document's OCR output
+    require_profile(profile)
+    if not state.job_pool or not state.database:
+        raise HTTPException(status_code=500, detail="Job pool not initialized")
+    if state.database.get_document(document_id) is None:
+        raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+    job = state.job_pool.submit(kind, {"document_id": document_id, "profile": profile, "force_refresh": force_refresh, **(params or {})})
+    return SubmitJobResponse(job_id=job.job_id, kind=job.kind, status=job.status, message=message, document_id=document_id)
+
+
+@router.post("/{document_id}/summary", response_model=SubmitJobResponse)
+async def post_document_summary(
+    document_id: int,
+    style: Optional[str] = Query(default=None, description=STYLE_DESCRIPTION),
+    profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION),
+    force_refresh: bool = Query(default=False, description=FORCE_REFRESH_DESCRIPTION),
+):
+    """
+    Start a job summarizing a document from its OCR output
+
+    With a style the summary is an alternative read from the job's result, the stored summary is kept.
+    """
+    try:
+        require_feature(FEATURE_LLM)
+        check_explanation_style(style)
+        params = {"style": style} if style is not None else None
+        return _submit_document_job(document_id, JOB_KIND_DOCUMENT_SUMMARY, "Summary job submitted", params, profile, force_refresh)
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/summary endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{document_id}/summary", response_model=DocumentSummaryResponse)
+async def get_document_summary(document_id: int):
+    """Get the summary generated for a document"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        document = state.database.get_document(document_id)
+        if document is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        if not document.summary:
+            raise HTTPException(status_code=404, detail=f"No summary generated for document {document_id}")
+        return DocumentSummaryResponse(document_id=document_id, summary=document.summary)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/summary endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{document_id}/glossary", response_model=SubmitJobResponse)
+async def post_document_glossary(
+    document_id: int,
+    profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION),
+    force_refresh: bool = Query(default=False, description=FORCE_REFRESH_DESCRIPTION),
+):
+    """Start a job building the glossary of a document from its OCR output"""
+    try:
+        require_feature(FEATURE_LLM)
+        return _submit_document_job(document_id, JOB_KIND_GLOSSARY, "Glossary job submitted", profile=profile, force_refresh=force_refresh)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/glossary endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{document_id}/glossary", response_model=GlossaryResponse)
+async def get_document_glossary(document_id: int):
+    """Get the glossary of a document in alphabetical order"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_document(document_id) is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        terms = state.database.get_glossary(document_id)
+        return GlossaryResponse(document_id=document_id, terms=[GlossaryTermItem(term=term.term, definition=term.definition, page_number=term.page_number) for term in terms])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/glossary endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{document_id}/toc", response_model=DocumentTocResponse)
+async def get_document_toc(document_id: int):
+    """Get the table of contents detected in the first pages of a document, detecting it first for documents OCRed before"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        document = state.database.get_document(document_id)
+        if document is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        has_toc = document.has_toc
+        if has_toc is None:
+            analysis = analyze_toc(state.database, document_id)
+            has_toc = analysis.has_toc if analysis is not None else None
+        entries = [TocEntryItem(**entry) for entry in document_toc(state.database, document_id)]
+        return DocumentTocResponse(document_id=document_id, has_toc=has_toc, entries=entries)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/toc endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{document_id}/segmentation", response_model=SubmitJobResponse)
+async def post_document_segmentation(
+    document_id: int,
+    request: Optional[SegmentDocumentRequest] = None,
+    profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION),
+    force_refresh: bool = Query(default=False, description=FORCE_REFRESH_DESCRIPTION),
+):
+    """Start a job segmenting a document's OCR output into chapters and sections again, which replaces its sections"""
+    try:
+        use_llm = request.use_llm if request else True
+        if use_llm:
+            require_feature(FEATURE_LLM)
+        return _submit_document_job(document_id, JOB_KIND_SEGMENTATION, "Segmentation job submitted", {"use_llm": use_llm}, profile, force_refresh)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/segmentation endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{document_id}/outline", response_model=DocumentOutlineResponse)
+async def get_document_outline(document_id: int):
+    """Get the tree of chapters and sections found by segmenting a document"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_document(document_id) is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        outline = document_outline(state.database, document_id)
+        if outline is None:
+            raise HTTPException(status_code=404, detail=f"Document {document_id} was not segmented, run its OCR or POST /documents/{document_id}/segmentation")
+        return DocumentOutlineResponse(document_id=document_id, chapters=[OutlineNodeItem(**node) for node in outline])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/outline endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _explanation_stream(llm: LLM, prompt: str, document_id: int, request: ExplainPassageRequest, selection: Selection, passages: List[Passage]) -> Iterator[str]:
+    # The sources go first, so the client can show what the explanation is grounded in while it is generated
+    sources = ExplanationSourcesEvent(
+        document_id=document_id,
+        selection=selection.text,
+        sources=[ExplanationSourceItem(kind=passage.chunk_kind, index=passage.chunk_index, title=passage.title, page_start=passage.page_start, score=round(passage.score, 4)) for passage in passages],
+    )
+    yield f"event: sources\ndata: {sources.model_dump_json()}\n\n"
+    pieces = []
+    try:
+        for piece in llm.stream(prompt):
+            pieces.append(piece)
+            yield f"event: chunk\ndata: {json.dumps({'text': piece})}\n\n"
+    except ModelError as e:
+        # The response already started, the error can only be sent as an event; an incomplete exchange is not saved
+        yield f"event: error\ndata: {json.dumps({'code': e.category, 'message': e.user_message})}\n\n"
+        return
+    message_ids = []
+    if request.session_id:
+        message_ids = save_exchange(state.database, request.session_id, document_id, request.char_start, request.char_end, selection.text, request.question, "".join(pieces))
+    yield f"event: done\ndata: {ExplanationDoneEvent(session_id=request.session_id, message_ids=message_ids).model_dump_json()}\n\n"
+
+
+@router.post("/{document_id}/explain")
+async def post_explain_passage(document_id: int, request: ExplainPassageRequest, profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION)):
+    """
+    Explain a passage selected in a document, grounded in the text around it and the related passages of the document
+
+    The answer is a stream of server-sent events: `sources` with the passages retrieved, `chunk` events with the
+    explanation as it is generated, then `done`, or `error` when the model fails. With a `session_id` the last messages
+    of the tutoring session go into the prompt and the exchange is saved into it.
+    """
+    try:
+        require_feature(FEATURE_LLM)
+        require_profile(profile)
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        document = state.database.get_document(document_id)
+        if document is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {document_id}")
+        markdown = source_markdown(state.database, document_id, state.uploads_dir)
+        if markdown is None:
+            raise HTTPException(status_code=404, detail=f"Document {document_id} has no OCR output, run its OCR first")
+
+        selection = select_range(markdown, request.char_start, request.char_end)
+        query = f"{selection.text}\n{request.question or ''}"
+        passages = retrieve_passages(document_passages(state.database, document_id), query, selection.context_start, selection.context_end)
+        history = state.database.get_tutoring_messages(request.session_id, HISTORY_MESSAGES) if request.session_id else []
+        prompt = explanation_prompt(document.title, selection, passages, history, request.question, request.style)
+        return StreamingResponse(
+            _explanation_stream(get_llm(TASK_EXPLAIN, profile), prompt, document_id, request, selection, passages),
+            media_type="text/event-stream",
+            headers={"Cache-Control": "no-cache"},
+        )
+    except HTTPException:
+        raise
+    except ModelError as e:
+        raise HTTPException(status_code=502, detail={"code": e.category, "message": e.user_message})
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{document_id}/explain endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
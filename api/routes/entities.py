@@ -0,0 +1,187 @@
+# Entity routes
+# Numbered entities of books (theorems, definitions, equations) and the cross references linking the text to them.
+
+from typing import Optional
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.database import BookInfo, CrossReferenceInfo
+from textbook.cross_reference import link_references, load_cross_reference_index, entity_anchor, section_anchor, chapter_anchor, ENTITY_KINDS, ENTITY_SOURCE_LLM
+
+from api.models import CrossReferenceItem, CrossReferencesResponse, LinkReferencesRequest, LinkReferencesResponse, ExtractEntitiesRequest, ChapterEntitiesItem, EntityIndexResponse
+from api.state import state, get_reader_by_book_id
+from api.items import entity_item
+
+router = APIRouter(tags=["entities"])
+
+
+def _cross_reference_item(reference: CrossReferenceInfo) -> CrossReferenceItem:
+    if reference.target_entity_id is not None:
+        anchor = entity_anchor(reference.target_entity_id)
+    elif reference.target_section_id is not None:
+        anchor = section_anchor(reference.target_section_id)
+    elif reference.target_chapter_id is not None:
+        anchor = chapter_anchor(reference.target_chapter_id)
+    else:
+        anchor = None
+    return CrossReferenceItem(
+        reference_id=reference.reference_id,
+        kind=reference.reference_kind,
+        label=reference.reference_label,
+        text=reference.reference_text,
+        page_number=reference.page_number,
+        char_start=reference.char_start,
+        char_end=reference.char_end,
+        target_page_number=reference.target_page_number,
+        anchor=anchor,
+    )
+
+
+# Cross reference endpoints
+@router.post("/books/{book_id}/cross-references", response_model=CrossReferencesResponse)
+async def update_cross_references(book_id: int):
+    """Rebuild the index of numbered entities of a book and resolve the references between its pages"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        with get_reader_by_book_id(book_id) as reader:
+            if not reader.check_if_book_exists_and_load():
+                raise HTTPException(status_code=404, detail="Book not found")
+            reader.update_cross_references()
+
+        return CrossReferencesResponse(
+            book_id=book_id,
+            entities=[entity_item(entity) for entity in state.database.get_entities_by_book_id(book_id)],
+            references=[_cross_reference_item(reference) for reference in state.database.get_cross_references_by_book_id(book_id)],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/cross-references endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/books/{book_id}/cross-references", response_model=CrossReferencesResponse)
+async def get_cross_references(
+    book_id: int,
+    page_number: Optional[int] = Query(default=None, ge=0, description="Only return the references on this page"),
+):
+    """Get the entity index of a book and its resolved references, with anchors for the reader view"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        return CrossReferencesResponse(
+            book_id=book_id,
+            entities=[entity_item(entity) for entity in state.database.get_entities_by_book_id(book_id)],
+            references=[_cross_reference_item(reference) for reference in state.database.get_cross_references_by_book_id(book_id, page_number)],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/cross-references endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/books/{book_id}/link-references", response_model=LinkReferencesResponse)
+async def link_text_references(book_id: int, request: LinkReferencesRequest):
+    """Turn the references in a text (e.g. a generated problem) into markdown links to the book's anchors"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        index = load_cross_reference_index(state.database, book_id)
+        return LinkReferencesResponse(book_id=book_id, text=link_references(request.text, index))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/link-references endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _entity_index(book_id: int, entity_kind: Optional[str] = None, chapter_id: Optional[int] = None) -> EntityIndexResponse:
+    entities = state.database.get_entities_by_book_id(book_id, [entity_kind] if entity_kind else None)
+    # Pattern matches the LLM extraction also found are dropped, the extracted ones carry the full statement
+    extracted = {(entity.entity_kind, entity.entity_label) for entity in entities if entity.entity_source == ENTITY_SOURCE_LLM and entity.entity_label}
+    entities = [
+        entity for entity in entities
+        if (entity.entity_source == ENTITY_SOURCE_LLM or (entity.entity_kind, entity.entity_label) not in extracted)
+        and (chapter_id is None or entity.chapter_id == chapter_id)
+    ]
+
+    chapters = state.database.get_chapters_by_book_id(book_id)
+    grouped = [
+        ChapterEntitiesItem(chapter_id=chapter.chapter_id, chapter_title=chapter.title, entities=[entity_item(entity) for entity in entities if entity.chapter_id == chapter.chapter_id])
+        for chapter in chapters
+        if chapter_id is None or chapter.chapter_id == chapter_id
+    ]
+    chapter_ids = {chapter.chapter_id for chapter in chapters}
+    unassigned = [entity_item(entity) for entity in entities if entity.chapter_id not in chapter_ids]
+    if unassigned:
+        grouped.append(ChapterEntitiesItem(chapter_id=None, chapter_title=None, entities=unassigned))
+    return EntityIndexResponse(book_id=book_id, chapters=grouped)
+
+
+# Entity index endpoints
+@router.post("/documents/{book_id}/entities/extract", response_model=EntityIndexResponse)
+async def extract_entities(book_id: int, request: ExtractEntitiesRequest):
+    """Extract the definitions, theorems, lemmas, equations, ... of each chapter with the LLM"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        with get_reader_by_book_id(book_id) as reader:
+            if not reader.check_if_book_exists_and_load():
+                raise HTTPException(status_code=404, detail="Book not found")
+            chapter_ids = [chapter.chapter_id for chapter in state.database.get_chapters_by_book_id(book_id)]
+            if not chapter_ids:
+                raise HTTPException(status_code=400, detail=f"Book {book_id} has no chapters, extract the TOC first")
+            if request.chapter_ids is not None:
+                unknown = [chapter_id for chapter_id in request.chapter_ids if chapter_id not in chapter_ids]
+                if unknown:
+                    raise HTTPException(status_code=404, detail=f"Chapters {unknown} not found in book {book_id}")
+                chapter_ids = request.chapter_ids
+            for chapter_id in chapter_ids:
+                reader.extract_chapter_entities(chapter_id)
+
+        return _entity_index(book_id)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{book_id}/entities/extract endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/documents/{book_id}/entities", response_model=EntityIndexResponse)
+async def get_entities(
+    book_id: int,
+    kind: Optional[str] = Query(default=None, description=f"Only return entities of this kind, one of {', '.join(ENTITY_KINDS)}"),
+    chapter_id: Optional[int] = Query(default=None, description="Only return the entities of this chapter"),
+):
+    """Get the entity index of a document, grouped per chapter"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if kind is not None and kind not in ENTITY_KINDS:
+            raise HTTPException(status_code=400, detail=f"Invalid entity kind '{kind}', expected one of {', '.join(ENTITY_KINDS)}")
+        with state.database.new_session() as session:
+            if not session.query(BookInfo).filter(BookInfo.book_id == book_id).first():
+                raise HTTPException(status_code=404, detail=f"Book not found: {book_id}")
+
+        return _entity_index(book_id, kind, chapter_id)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{book_id}/entities endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,59 @@
+# Estimate routes
+# The prompts, tokens and cost generating the content of a document would take, before its jobs are submitted.
+
+from fastapi import APIRouter, HTTPException
+
+from textbook.capabilities import FEATURE_LLM
+from textbook.estimate import Estimator
+
+from api.models import EstimateRequest, EstimateResponse, TaskEstimateItem
+from api.state import state, get_llm, require_feature, require_profile
+
+router = APIRouter(prefix="/estimate", tags=["estimate"])
+
+
+@router.post("", response_model=EstimateResponse)
+async def estimate(request: EstimateRequest):
+    """Estimate the tokens and cost of generation tasks on a document, or a section of it, from its OCR output and the [pricing] section"""
+    try:
+        require_feature(FEATURE_LLM)
+        if not state.llm or not state.database:
+            raise HTTPException(status_code=500, detail="LLM or Context not initialized")
+        require_profile(request.profile)
+        if state.database.get_document(request.document_id) is None:
+            raise HTTPException(status_code=404, detail=f"Document not found: {request.document_id}")
+        prices = state.usage_tracker.prices if state.usage_tracker else {}
+        estimator = Estimator(state.database, lambda task: get_llm(task, request.profile).model_name, prices)
+        try:
+            estimate = estimator.document(request.document_id, request.tasks, request.section_index)
+        except ValueError as e:
+            raise HTTPException(status_code=400, detail=str(e))
+        return EstimateResponse(
+            document_id=estimate.document_id,
+            section_index=estimate.section_index,
+            tasks=[
+                TaskEstimateItem(
+                    task=task.task,
+                    model_name=task.model_name,
+                    prompts=task.prompts,
+                    input_tokens=task.input_tokens,
+                    output_tokens=task.output_tokens,
+                    cost=round(task.cost, 6) if task.cost is not None else None,
+                    calibrated=task.calibrated,
+                    assumed_problems=task.assumed_problems,
+                )
+                for task in estimate.tasks
+            ],
+            prompts=estimate.prompts,
+            input_tokens=estimate.input_tokens,
+            output_tokens=estimate.output_tokens,
+            cost=round(estimate.cost, 6),
+            unpriced_tasks=estimate.unpriced_tasks,
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /estimate endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,84 @@
+# Study event routes
+# The append-only stream of study activity (attempts, gradings, reviews, reads and their corrections), paged by
+# event ID for analytics consumers, and the daily activity and streaks of a user rebuilt from it by replaying the
+# stream. GET /books/{id}/mastery?from_events=true rebuilds mastery from the stream the same way.
+
+from datetime import datetime, timezone
+from typing import List, Optional
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.preferences import DEFAULT_USER_ID
+from textbook.projections import user_activity
+from textbook.study_events import EVENT_ATTEMPTED, EVENT_KINDS, EVENT_READ, EVENT_REVIEWED, decode_payload
+
+from api.models import ActivityDayItem, ActivityResponse, StudyEventItem, StudyEventsResponse
+from api.state import state
+
+router = APIRouter(prefix="/events", tags=["events"])
+
+
+@router.get("", response_model=StudyEventsResponse)
+async def get_events(
+    after_id: int = Query(default=0, ge=0, description="Only the events appended after this event ID"),
+    limit: int = Query(default=500, ge=1, le=5000),
+    user_id: Optional[str] = Query(default=None, description="Only the events of this user, the attempts included for the default user"),
+    book_id: Optional[int] = Query(default=None),
+    kind: Optional[List[str]] = Query(default=None, description="Only events of these kinds"),
+):
+    """Get a page of the study event stream, in the order the events were appended"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        unknown = [value for value in kind or [] if value not in EVENT_KINDS]
+        if unknown:
+            raise HTTPException(status_code=400, detail=f"Unknown event kinds: {', '.join(unknown)}, expected some of {', '.join(EVENT_KINDS)}")
+        events = state.database.get_study_events(after_id, limit, user_id, user_id == DEFAULT_USER_ID, book_id, kind)
+        return StudyEventsResponse(
+            events=[StudyEventItem(
+                event_id=event.event_id,
+                kind=event.event_kind,
+                user_id=event.user_id,
+                subject_kind=event.subject_kind,
+                subject_id=event.subject_id,
+                payload=decode_payload(event.payload),
+                occurred_at=event.occurred_at,
+                recorded_at=event.recorded_at,
+                book_id=event.book_id,
+                document_id=event.document_id,
+            ) for event in events],
+            next_after_id=events[-1].event_id if len(events) == limit else None,
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /events endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/activity", response_model=ActivityResponse)
+async def get_activity(user_id: str = Query(default=DEFAULT_USER_ID), book_id: Optional[int] = Query(default=None, description="Only the activity on this book")):
+    """Get the days a user studied and their streaks, replayed from the study events"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        activity = user_activity(state.database, user_id, book_id)
+        return ActivityResponse(
+            user_id=user_id,
+            book_id=book_id,
+            current_streak=activity.current_streak(datetime.now(timezone.utc).date()),
+            longest_streak=activity.longest_streak(),
+            attempted=activity.totals[EVENT_ATTEMPTED],
+            reviewed=activity.totals[EVENT_REVIEWED],
+            read=activity.totals[EVENT_READ],
+            days=[ActivityDayItem(day=day, attempted=counts[EVENT_ATTEMPTED], reviewed=counts[EVENT_REVIEWED], read=counts[EVENT_READ]) for day, counts in sorted(activity.days.items())],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /events/activity endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
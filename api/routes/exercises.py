@@ -0,0 +1,458 @@
+# Exercise routes
+# Exercises of books, graded attempts, the reflection journal of self-explanations and grading disputes.
+
+from pathlib import Path
+from datetime import datetime, timedelta, timezone
+from typing import Optional, List
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.database import ExerciseInfo, AttemptInfo, GradingJudgmentInfo, DisputeInfo, ReflectionInfo
+from textbook.integrity import NOTEBOOK_MARKDOWN_ARTIFACT
+from textbook.reader import EXERCISE_TYPES
+from textbook.notebook import markdown_language
+from textbook.grading import grading_metrics, DEFAULT_CONTEXT_TOKEN_BUDGET, DISPUTE_PENDING_REVIEW, DISPUTE_RESOLVED, JUDGMENT_EXTERNAL, JUDGMENT_MANUAL, STUDY_MODES, STUDY_MODE_PRACTICE, STUDY_MODE_SELF_EXPLANATION, SELF_EXPLANATION_QUESTION, ATTEMPT_VOID_WINDOW_HOURS
+from textbook.quiz_timing import record_answer_timing
+from textbook.preferences import load_preferences
+from textbook.provider_errors import ModelError
+from textbook.capabilities import FEATURE_GRADING, FEATURE_LLM
+from textbook.cross_reference import link_references, load_cross_reference_index
+
+from api.models import GenerateCodeExercisesRequest, ExercisesResponse, SubmitAttemptRequest, AttemptItem, AttemptsResponse, GradingJudgmentItem, SelfExplanationRequest, ReflectionItem, ChapterReflectionsItem, ReflectionJournalResponse, DisputeAttemptRequest, ResolveDisputeRequest, DisputeItem, DisputesResponse, GradingPeriodMetricsItem, GradingMetricsResponse
+from api.state import state, require_feature, get_pdf_path_from_book_id, get_reader, get_reader_by_book_id
+from api.items import exercise_item
+
+router = APIRouter(tags=["exercises"])
+
+
+def get_notebook_language(book_id: int) -> str:
+    """Read the notebook language back from the stored notebook markdown"""
+    if not state.database:
+        raise HTTPException(status_code=500, detail="Context not initialized")
+    for artifact in state.database.get_artifacts_by_book_id(book_id):
+        markdown_path = Path(state.uploads_dir) / artifact.artifact_path
+        if artifact.artifact_kind == NOTEBOOK_MARKDOWN_ARTIFACT and markdown_path.exists():
+            return markdown_language(markdown_path.read_text(encoding="utf-8"))
+    raise HTTPException(status_code=400, detail=f"Book {book_id} has no notebook source, pass the exercise language explicitly")
+
+
+# Exercise endpoints
+@router.post("/books/{book_id}/code-exercises", response_model=ExercisesResponse)
+async def generate_code_exercises(book_id: int, request: GenerateCodeExercisesRequest):
+    """Generate coding exercises with starter and solution code from the pages of a book"""
+    try:
+        require_feature(FEATURE_LLM)
+        if not state.llm or not state.database:
+            raise HTTPException(status_code=500, detail="LLM or Context not initialized")
+
+        pdf_path = get_pdf_path_from_book_id(book_id)
+        language = request.language or get_notebook_language(book_id)
+        preferences = load_preferences(state.database, request.user_id)
+        with get_reader(pdf_path) as reader:
+            if not reader.check_if_book_exists_and_load():
+                raise HTTPException(status_code=404, detail="Book not found")
+
+            start_page = request.start_page if request.start_page is not None else 0
+            end_page = request.end_page if request.end_page is not None else reader.get_total_pages() - 1
+            if start_page > end_page:
+                raise HTTPException(status_code=400, detail="start_page must not be after end_page")
+
+            exercise_ids = []
+            for page_number in range(start_page, end_page + 1):
+                exercise_ids.extend(reader.generate_code_exercises(page_number, language, request.exercises_per_page, preferences.default_difficulty, preferences.output_language))
+
+        with state.database.new_session() as session:
+            exercises = session.query(ExerciseInfo).filter(ExerciseInfo.exercise_id.in_(exercise_ids)).order_by(ExerciseInfo.page_number, ExerciseInfo.exercise_id).all()
+            return ExercisesResponse(book_id=book_id, exercises=[exercise_item(exercise) for exercise in exercises])
+    except HTTPException:
+        raise
+    except ModelError as e:
+        raise HTTPException(status_code=502, detail={"code": e.category, "message": e.user_message})
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/code-exercises endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/books/{book_id}/exercises", response_model=ExercisesResponse)
+async def get_exercises(
+    book_id: int,
+    exercise_type: Optional[str] = Query(default=None, description="Only return exercises of this type (text or code)"),
+    link: bool = Query(default=False, description="Whether to turn cross references in the descriptions into markdown links"),
+):
+    """Get the exercises of a book"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if exercise_type is not None and exercise_type not in EXERCISE_TYPES:
+            raise HTTPException(status_code=400, detail=f"Invalid exercise_type: {exercise_type}, expected one of {', '.join(EXERCISE_TYPES)}")
+
+        with state.database.new_session() as session:
+            exercises = session.query(ExerciseInfo).filter(ExerciseInfo.book_id == book_id).order_by(ExerciseInfo.page_number, ExerciseInfo.exercise_id).all()
+            items = [exercise_item(exercise) for exercise in exercises]
+        if exercise_type is not None:
+            items = [item for item in items if item.exercise_type == exercise_type]
+        if link and items:
+            index = load_cross_reference_index(state.database, book_id)
+            for item in items:
+                item.description = link_references(item.description, index)
+
+        return ExercisesResponse(book_id=book_id, exercises=items)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/exercises endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _judgment_item(judgment: GradingJudgmentInfo) -> GradingJudgmentItem:
+    return GradingJudgmentItem(
+        judgment_id=judgment.judgment_id,
+        model_name=judgment.model_name,
+        reason=judgment.reason,
+        score=judgment.score,
+        is_correct=judgment.is_correct,
+        confidence=judgment.confidence,
+        feedback=judgment.feedback,
+        created_at=judgment.created_at,
+    )
+
+
+def _attempt_item(attempt: AttemptInfo) -> AttemptItem:
+    return AttemptItem(
+        attempt_id=attempt.attempt_id,
+        exercise_id=attempt.exercise_id,
+        book_id=attempt.book_id,
+        answer=attempt.answer,
+        study_mode=attempt.study_mode or STUDY_MODE_PRACTICE,
+        self_explanation_prompt=SELF_EXPLANATION_QUESTION if attempt.study_mode == STUDY_MODE_SELF_EXPLANATION else None,
+        score=attempt.score,
+        is_correct=attempt.is_correct,
+        confidence=attempt.confidence,
+        feedback=attempt.feedback,
+        escalated=sum(judgment.reason != JUDGMENT_EXTERNAL for judgment in attempt.judgments) > 1,
+        judgments=[_judgment_item(judgment) for judgment in attempt.judgments],
+        misconceptions=[misconception.description for misconception in attempt.misconceptions],
+        grading_context=attempt.grading_context,
+        created_at=attempt.created_at,
+        voided_at=attempt.voided_at,
+    )
+
+
+# Attempt endpoints
+@router.post("/exercises/{exercise_id}/attempts", response_model=AttemptItem)
+async def submit_attempt(exercise_id: int, request: SubmitAttemptRequest):
+    """
+    Grade an answer to an exercise, with the problem, its source, related entities and recent misconceptions as context
+
+    Low confidence gradings are redone by the escalation model if one is configured.
+    """
+    try:
+        require_feature(FEATURE_GRADING)
+        if not state.llm or not state.database:
+            raise HTTPException(status_code=500, detail="LLM or Context not initialized")
+        if request.study_mode not in STUDY_MODES:
+            raise HTTPException(status_code=400, detail=f"Invalid study_mode: {request.study_mode}, expected one of {', '.join(STUDY_MODES)}")
+
+        exercise = state.database.get_exercise_info(exercise_id)
+        if exercise is None:
+            raise HTTPException(status_code=404, detail=f"Exercise not found: {exercise_id}")
+
+        with get_reader_by_book_id(exercise.book_id) as reader:
+            if not reader.check_if_book_exists_and_load():
+                raise HTTPException(status_code=404, detail="Book not found")
+            attempt_id = reader.grade_attempt(exercise_id, request.answer, request.token_budget or DEFAULT_CONTEXT_TOKEN_BUDGET, state.escalation_llm, request.study_mode, state.grading_services)
+        if request.think_seconds is not None:
+            record_answer_timing(state.database, attempt_id, request.think_seconds, request.answer_changes)
+
+        return _attempt_item(state.database.get_attempt_info(attempt_id))
+    except HTTPException:
+        raise
+    except ModelError as e:
+        raise HTTPException(status_code=502, detail={"code": e.category, "message": e.user_message})
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /exercises/{exercise_id}/attempts endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/exercises/{exercise_id}/attempts", response_model=AttemptsResponse)
+async def get_attempts(exercise_id: int):
+    """Get the graded attempts at an exercise"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        return AttemptsResponse(exercise_id=exercise_id, attempts=[_attempt_item(attempt) for attempt in state.database.get_attempts_by_exercise_id(exercise_id)])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /exercises/{exercise_id}/attempts endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/attempts/{attempt_id}", response_model=AttemptItem)
+async def get_attempt(attempt_id: int):
+    """Get a graded attempt with the context it was graded with"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        attempt = state.database.get_attempt_info(attempt_id)
+        if attempt is None:
+            raise HTTPException(status_code=404, detail=f"Attempt not found: {attempt_id}")
+        return _attempt_item(attempt)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /attempts/{attempt_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/attempts/{attempt_id}/void", response_model=AttemptItem)
+async def void_attempt(attempt_id: int):
+    """
+    Void an attempt, e.g. one submitted by mistake, within 24 hours of grading
+
+    The attempt is kept but no longer counts towards mastery, recent misconceptions or grading metrics.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        attempt = state.database.get_attempt_info(attempt_id)
+        if attempt is None:
+            raise HTTPException(status_code=404, detail=f"Attempt not found: {attempt_id}")
+        if attempt.voided_at is not None:
+            raise HTTPException(status_code=409, detail=f"Attempt {attempt_id} is already voided")
+        created_at = attempt.created_at if attempt.created_at.tzinfo is not None else attempt.created_at.replace(tzinfo=timezone.utc)
+        if created_at < datetime.now(timezone.utc) - timedelta(hours=ATTEMPT_VOID_WINDOW_HOURS):
+            raise HTTPException(status_code=409, detail=f"Attempt {attempt_id} is older than {ATTEMPT_VOID_WINDOW_HOURS} hours and can no longer be voided")
+
+        return _attempt_item(state.database.void_attempt(attempt_id))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /attempts/{attempt_id}/void endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _reflection_item(reflection: ReflectionInfo) -> ReflectionItem:
+    return ReflectionItem(
+        reflection_id=reflection.reflection_id,
+        attempt_id=reflection.attempt_id,
+        exercise_id=reflection.exercise_id,
+        explanation=reflection.explanation,
+        completeness=reflection.completeness,
+        solution_steps=reflection.solution_steps.split("\n") if reflection.solution_steps else [],
+        missing_steps=reflection.missing_steps.split("\n") if reflection.missing_steps else [],
+        feedback=reflection.feedback,
+        chapter_id=reflection.chapter_id,
+        created_at=reflection.created_at,
+    )
+
+
+def _chapter_reflections_item(chapter_id: Optional[int], chapter_title: Optional[str], reflections: List[ReflectionInfo]) -> ChapterReflectionsItem:
+    scores = [reflection.completeness for reflection in reflections if reflection.completeness is not None]
+    return ChapterReflectionsItem(
+        chapter_id=chapter_id,
+        chapter_title=chapter_title,
+        average_completeness=sum(scores) / len(scores) if scores else None,
+        reflections=[_reflection_item(reflection) for reflection in reflections],
+    )
+
+
+# Reflection journal endpoints
+@router.post("/attempts/{attempt_id}/self-explanation", response_model=ReflectionItem)
+async def submit_self_explanation(attempt_id: int, request: SelfExplanationRequest):
+    """Evaluate the learner's explanation of a solved exercise against the solution steps and add it to the reflection journal"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        require_feature(FEATURE_GRADING)
+
+        attempt = state.database.get_attempt_info(attempt_id)
+        if attempt is None:
+            raise HTTPException(status_code=404, detail=f"Attempt not found: {attempt_id}")
+        if attempt.voided_at is not None:
+            raise HTTPException(status_code=409, detail=f"Attempt {attempt_id} was voided")
+
+        with get_reader_by_book_id(attempt.book_id) as reader:
+            if not reader.check_if_book_exists_and_load():
+                raise HTTPException(status_code=404, detail="Book not found")
+            reflection_id = reader.evaluate_self_explanation(attempt_id, request.explanation)
+
+        return _reflection_item(state.database.get_reflection_info(reflection_id))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /attempts/{attempt_id}/self-explanation endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/books/{book_id}/reflections", response_model=ReflectionJournalResponse)
+async def get_reflection_journal(
+    book_id: int,
+    chapter_id: Optional[int] = Query(default=None, description="Only return the reflections of this chapter"),
+):
+    """Get the reflection journal of a book, grouped per chapter"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        reflections = state.database.get_reflections_by_book_id(book_id, chapter_id)
+        chapters = state.database.get_chapters_by_book_id(book_id)
+        grouped = [
+            _chapter_reflections_item(chapter.chapter_id, chapter.title, [reflection for reflection in reflections if reflection.chapter_id == chapter.chapter_id])
+            for chapter in chapters
+            if chapter_id is None or chapter.chapter_id == chapter_id
+        ]
+        chapter_ids = {chapter.chapter_id for chapter in chapters}
+        unassigned = [reflection for reflection in reflections if reflection.chapter_id not in chapter_ids]
+        if unassigned:
+            grouped.append(_chapter_reflections_item(None, None, unassigned))
+        return ReflectionJournalResponse(book_id=book_id, chapters=grouped)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/reflections endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _dispute_item(dispute: DisputeInfo) -> DisputeItem:
+    attempt = state.database.get_attempt_info(dispute.attempt_id)
+    return DisputeItem(
+        dispute_id=dispute.dispute_id,
+        attempt_id=dispute.attempt_id,
+        comment=dispute.comment,
+        status=dispute.status,
+        original_score=dispute.original_score,
+        original_is_correct=dispute.original_is_correct,
+        escalated_score=dispute.escalated_score,
+        escalated_is_correct=dispute.escalated_is_correct,
+        resolved_score=dispute.resolved_score,
+        resolved_is_correct=dispute.resolved_is_correct,
+        reviewer_note=dispute.reviewer_note,
+        created_at=dispute.created_at,
+        resolved_at=dispute.resolved_at,
+        attempt=_attempt_item(attempt),
+    )
+
+
+# Dispute endpoints
+@router.post("/attempts/{attempt_id}/dispute", response_model=DisputeItem)
+async def dispute_attempt(attempt_id: int, request: DisputeAttemptRequest):
+    """Flag an unfair grade; it is regraded by the escalation model if configured and queued for manual review"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        attempt = state.database.get_attempt_info(attempt_id)
+        if attempt is None:
+            raise HTTPException(status_code=404, detail=f"Attempt not found: {attempt_id}")
+        if attempt.voided_at is not None:
+            raise HTTPException(status_code=409, detail=f"Attempt {attempt_id} was voided")
+        if state.database.get_disputes(DISPUTE_PENDING_REVIEW, attempt_id):
+            raise HTTPException(status_code=409, detail=f"Attempt {attempt_id} already has a dispute pending review")
+
+        with get_reader_by_book_id(attempt.book_id) as reader:
+            dispute_id = reader.dispute_attempt(attempt_id, request.comment, state.escalation_llm)
+
+        return _dispute_item(state.database.get_dispute_info(dispute_id))
+    except HTTPException:
+        raise
+    except ModelError as e:
+        raise HTTPException(status_code=502, detail={"code": e.category, "message": e.user_message})
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /attempts/{attempt_id}/dispute endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/disputes", response_model=DisputesResponse)
+async def get_disputes(status: Optional[str] = Query(default=None, description=f"Only return disputes with this status ({DISPUTE_PENDING_REVIEW} for the review queue or {DISPUTE_RESOLVED})")):
+    """Get the disputes, oldest first"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if status is not None and status not in (DISPUTE_PENDING_REVIEW, DISPUTE_RESOLVED):
+            raise HTTPException(status_code=400, detail=f"Invalid status: {status}, expected {DISPUTE_PENDING_REVIEW} or {DISPUTE_RESOLVED}")
+
+        return DisputesResponse(disputes=[_dispute_item(dispute) for dispute in state.database.get_disputes(status)])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /disputes endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/disputes/{dispute_id}/resolve", response_model=DisputeItem)
+async def resolve_dispute(dispute_id: int, request: ResolveDisputeRequest):
+    """Settle a dispute from the review queue; the reviewer's grade becomes the grade of the attempt"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        dispute = state.database.get_dispute_info(dispute_id)
+        if dispute is None:
+            raise HTTPException(status_code=404, detail=f"Dispute not found: {dispute_id}")
+        if dispute.status == DISPUTE_RESOLVED:
+            raise HTTPException(status_code=409, detail=f"Dispute {dispute_id} is already resolved")
+
+        state.database.resolve_dispute(dispute_id, DISPUTE_RESOLVED, request.score, request.is_correct, request.note)
+        state.database.add_grading_judgment(dispute.attempt_id, GradingJudgmentInfo(
+            reason=JUDGMENT_MANUAL,
+            score=request.score,
+            is_correct=request.is_correct,
+            confidence=1.0,
+            feedback=request.note,
+        ))
+        return _dispute_item(state.database.get_dispute_info(dispute_id))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /disputes/{dispute_id}/resolve endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/grading/metrics", response_model=GradingMetricsResponse)
+async def get_grading_metrics(
+    period_days: int = Query(default=7, ge=1, le=365, description="Length of the periods in days"),
+    days: Optional[int] = Query(default=None, ge=1, description="Only include attempts of the last days"),
+):
+    """Get grading accuracy over time: dispute rate, overturned grades and agreement of the escalation model with reviewers"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        since = datetime.now(timezone.utc) - timedelta(days=days) if days is not None else None
+        metrics = grading_metrics(state.database.get_attempts(since), state.database.get_disputes(), period_days)
+        return GradingMetricsResponse(period_days=period_days, periods=[GradingPeriodMetricsItem(**vars(period)) for period in metrics])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /grading/metrics endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,142 @@
+# Smart filter routes
+# Saved searches of a book's exercises per user, like "unsolved hard problems in chapters 5 to 7", created, renamed,
+# changed and deleted by name, and evaluated against the attempts made so far. Quizzes (POST /books/{id}/quiz) and
+# the study plan (GET /users/{id}/study-plan) take a filter_id to focus on what a filter matches.
+
+from dataclasses import asdict
+from typing import Optional
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.database import SmartFilterInfo
+from textbook.smart_filters import FilterCriteria, evaluate_filter, filter_criteria, require_filter, save_filter, update_filter
+
+from api.models import DeleteSmartFilterResponse, SaveSmartFilterRequest, SmartFilterCriteriaItem, SmartFilterExercisesResponse, SmartFilterItem, SmartFiltersResponse
+from api.items import exercise_item
+from api.state import state
+
+router = APIRouter(prefix="/users/{user_id}/filters", tags=["filters"])
+
+
+def _filter_item(smart_filter: SmartFilterInfo) -> SmartFilterItem:
+    return SmartFilterItem(
+        filter_id=smart_filter.filter_id,
+        user_id=smart_filter.user_id,
+        name=smart_filter.name,
+        book_id=smart_filter.book_id,
+        criteria=SmartFilterCriteriaItem(**asdict(filter_criteria(smart_filter))),
+        created_at=smart_filter.created_at,
+        updated_at=smart_filter.updated_at,
+    )
+
+
+def _require(filter_id: int, user_id: str) -> SmartFilterInfo:
+    if not state.database:
+        raise HTTPException(status_code=500, detail="Context not initialized")
+    try:
+        return require_filter(state.database, filter_id, user_id)
+    except LookupError as e:
+        raise HTTPException(status_code=404, detail=str(e))
+
+
+@router.get("", response_model=SmartFiltersResponse)
+async def get_filters(user_id: str, book_id: Optional[int] = Query(default=None, description="Only the filters of this book")):
+    """Get the smart filters a user saved, by name"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        return SmartFiltersResponse(user_id=user_id, filters=[_filter_item(smart_filter) for smart_filter in state.database.get_smart_filters(user_id, book_id)])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/filters endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("", response_model=SmartFilterItem)
+async def post_filter(user_id: str, request: SaveSmartFilterRequest):
+    """Save a smart filter under a name the user has no other filter by"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        smart_filter = save_filter(state.database, user_id, request.name, request.book_id, FilterCriteria(**request.criteria.model_dump()))
+        return _filter_item(smart_filter)
+    except HTTPException:
+        raise
+    except LookupError as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/filters endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{filter_id}", response_model=SmartFilterItem)
+async def get_filter(user_id: str, filter_id: int):
+    """Get a smart filter of the user"""
+    try:
+        return _filter_item(_require(filter_id, user_id))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/filters/{filter_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/{filter_id}", response_model=SmartFilterItem)
+async def put_filter(user_id: str, filter_id: int, request: SaveSmartFilterRequest):
+    """Replace the name, book and criteria of a smart filter"""
+    try:
+        smart_filter = _require(filter_id, user_id)
+        return _filter_item(update_filter(state.database, smart_filter, request.name, request.book_id, FilterCriteria(**request.criteria.model_dump())))
+    except HTTPException:
+        raise
+    except LookupError as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/filters/{filter_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.delete("/{filter_id}", response_model=DeleteSmartFilterResponse)
+async def delete_filter(user_id: str, filter_id: int):
+    """Delete a smart filter"""
+    try:
+        _require(filter_id, user_id)
+        state.database.delete_smart_filter(filter_id)
+        return DeleteSmartFilterResponse(message=f"Filter {filter_id} deleted", filter_id=filter_id)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/filters/{filter_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{filter_id}/exercises", response_model=SmartFilterExercisesResponse)
+async def get_filter_exercises(user_id: str, filter_id: int):
+    """Evaluate a smart filter: the exercises it matches now, in the order of the book"""
+    try:
+        smart_filter = _require(filter_id, user_id)
+        matched = {candidate.exercise_id for candidate in evaluate_filter(state.database, smart_filter)}
+        exercises = [exercise for exercise in state.database.get_exercises_by_book_id(smart_filter.book_id) if exercise.exercise_id in matched]
+        return SmartFilterExercisesResponse(filter=_filter_item(smart_filter), exercises=[exercise_item(exercise) for exercise in exercises])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/filters/{filter_id}/exercises endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
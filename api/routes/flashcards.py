@@ -0,0 +1,118 @@
+# Flashcard routes
+# Flashcards generated from the entities of a book, their chemical structures and archiving.
+
+from typing import Optional
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.preferences import load_preferences
+from textbook.status import STATUS_FILTERS
+from textbook.chemistry import canonical_structure
+from textbook.cross_reference import ENTITY_KINDS
+
+from api.models import GenerateFlashcardsRequest, FlashcardItem, FlashcardsResponse, UpdateStatusRequest, StatusResponse, StructureRequest
+from api.state import state, get_reader_by_book_id
+from api.items import check_status_filter, flashcard_items, status_response
+
+router = APIRouter(tags=["flashcards"])
+
+
+# Flashcard endpoints
+@router.post("/documents/{book_id}/flashcards", response_model=FlashcardsResponse)
+async def generate_flashcards(book_id: int, request: GenerateFlashcardsRequest):
+    """Generate flashcards from the entity index, optionally only for some entity kinds or one chapter"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        invalid = [kind for kind in request.entity_kinds or [] if kind not in ENTITY_KINDS]
+        if invalid:
+            raise HTTPException(status_code=400, detail=f"Invalid entity kinds {invalid}, expected any of {', '.join(ENTITY_KINDS)}")
+
+        preferences = load_preferences(state.database, request.user_id)
+        with get_reader_by_book_id(book_id) as reader:
+            if not reader.check_if_book_exists_and_load():
+                raise HTTPException(status_code=404, detail="Book not found")
+            card_ids = set(reader.generate_entity_flashcards(request.entity_kinds, request.chapter_id, request.cards_per_entity, preferences.output_language))
+
+        return FlashcardsResponse(
+            book_id=book_id,
+            flashcards=flashcard_items(book_id, [card for card in state.database.get_flashcards_by_book_id(book_id) if card.card_id in card_ids]),
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{book_id}/flashcards endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/documents/{book_id}/flashcards", response_model=FlashcardsResponse)
+async def get_flashcards(
+    book_id: int,
+    entity_kind: Optional[str] = Query(default=None, description="Only return cards about entities of this kind"),
+    chapter_id: Optional[int] = Query(default=None, description="Only return the cards of this chapter"),
+    status: Optional[str] = Query(default=None, description=f"Only return cards with this status, one of {', '.join(STATUS_FILTERS)}, by default all but archived ones"),
+):
+    """Get the flashcards of a document"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        check_status_filter(status)
+
+        cards = state.database.get_flashcards_by_book_id(book_id, chapter_id, [entity_kind] if entity_kind else None)
+        return FlashcardsResponse(book_id=book_id, flashcards=flashcard_items(book_id, cards, status))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{book_id}/flashcards endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/flashcards/{card_id}/structure", response_model=FlashcardItem)
+async def put_flashcard_structure(card_id: int, request: StructureRequest):
+    """
+    Set the chemical formula and SMILES of a flashcard, for clients to render its structure
+
+    Both are validated and stored in canonical form, the formula in Hill order; invalid ones are a 400.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        try:
+            chemical_formula, smiles = canonical_structure(request.chemical_formula, request.smiles)
+        except ValueError as e:
+            raise HTTPException(status_code=400, detail=str(e))
+        card = state.database.set_flashcard_structure(card_id, chemical_formula, smiles)
+        if card is None:
+            raise HTTPException(status_code=404, detail=f"Flashcard not found: {card_id}")
+        return flashcard_items(card.book_id, [card])[0]
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /flashcards/{card_id}/structure endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/flashcards/{card_id}/status", response_model=StatusResponse)
+async def put_flashcard_status(card_id: int, request: UpdateStatusRequest):
+    """Archive or suspend a flashcard, or restore it"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        card = state.database.set_flashcard_status(card_id, request.archived, request.suspended)
+        if card is None:
+            raise HTTPException(status_code=404, detail=f"Flashcard not found: {card_id}")
+        chapter = next((chapter for chapter in state.database.get_chapters_by_book_id(card.book_id) if chapter.chapter_id == card.chapter_id), None)
+        return status_response("flashcard", card_id, card, chapter, state.database.get_book_by_id(card.book_id))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /flashcards/{card_id}/status endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,317 @@
+# Job routes
+# Polling, listing, resuming and cancelling the background jobs of the job pool. Instead of polling, clients can
+# follow a job with GET /jobs/{job_id}/events, a stream of server-sent events carrying the job after every change.
+# Jobs submitted together, like the problem extraction of every chapter of a document, are tracked as a group with
+# GET /jobs/groups/{group_id}. Pipelines, jobs starting as the jobs they depend on succeed, are followed node by node
+# with GET /jobs/pipelines/{pipeline_id}. Failed jobs wait in the dead-letter queue, GET /jobs/dead-letter, until they
+# are resumed or retried as a new job with POST /jobs/{job_id}/retry. Finished jobs are dropped from memory after a
+# while by a sweeper, or right away with POST /jobs/purge.
+
+import asyncio
+from datetime import timedelta
+from typing import AsyncIterator, Optional, List
+
+from fastapi import APIRouter, HTTPException, Query, Path as FastAPIPath
+from fastapi.responses import StreamingResponse
+
+from textbook.jobs import Job, JOB_CANCELLED, JOB_DEFERRED, JOB_FAILED, JOB_STATUSES
+
+from api.models import SubmitJobResponse, JobEventItem, JobItem, JobsResponse, JobResultResponse, JobGroupResponse, PipelineResponse, DeadLetterResponse, PurgeJobsRequest, PurgeJobsResponse
+from api.items import pipeline_response
+from api.state import state
+
+router = APIRouter(prefix="/jobs", tags=["jobs"])
+
+KEEPALIVE_SECONDS = 15  # A comment is sent when a job did not change for this long, so proxies keep the stream open
+
+
+def _job_item(job: Job) -> JobItem:
+    return JobItem(
+        job_id=job.job_id,
+        kind=job.kind,
+        status=job.status,
+        progress=job.progress,
+        percent=round(job.progress * 100, 1),
+        stage=job.stage,
+        detail=job.detail,
+        params=job.params,
+        created_at=job.created_at,
+        started_at=job.started_at,
+        finished_at=job.finished_at,
+        error=job.error,
+        error_category=job.error_category,
+        queue_position=job.queue_position,
+        group_id=job.group_id,
+        retry_of=job.retry_of,
+    )
+
+
+def _job_events(job_id: str) -> List[JobEventItem]:
+    if not state.database:
+        return []
+    return [JobEventItem(status=event.status, created_at=event.created_at) for event in state.database.get_job_events(job_id)]
+
+
+def _get_job(job_id: str) -> Job:
+    if not state.job_pool:
+        raise HTTPException(status_code=500, detail="Job pool not initialized")
+    job = state.job_pool.get_job_status(job_id)
+    if job is None:
+        raise HTTPException(status_code=404, detail=f"Job not found: {job_id}")
+    return job
+
+
+async def _job_event_stream(job_id: str) -> AsyncIterator[str]:
+    # The job as it is, then after every change until it finished
+    version = -1
+    while state.job_pool:
+        changed_version, job = await asyncio.to_thread(state.job_pool.watch, job_id, version, KEEPALIVE_SECONDS)
+        if job is None:
+            return
+        if changed_version == version:
+            yield ": keepalive\n\n"
+            continue
+        version = changed_version
+        yield f"event: job\ndata: {_job_item(job).model_dump_json()}\n\n"
+        if job.finished:
+            return
+
+
+# Job endpoints
+@router.get("", response_model=JobsResponse)
+async def get_jobs(
+    status: Optional[str] = Query(default=None, description="Only jobs in this status: pending, running, succeeded, failed, interrupted, cancelled or deferred"),
+    kind: Optional[str] = Query(default=None, description="Only jobs of this kind"),
+    limit: int = Query(default=50, ge=1, le=500, description="Number of jobs to return"),
+):
+    """List the background jobs newest first, including those that ran before the server restarted"""
+    try:
+        if not state.job_pool:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+        if status is not None and status not in JOB_STATUSES:
+            raise HTTPException(status_code=400, detail=f"Invalid status: {status}, expected one of {', '.join(JOB_STATUSES)}")
+
+        return JobsResponse(
+            jobs=[_job_item(job) for job in state.job_pool.list_jobs(status, kind, limit)],
+            queue_depths=state.job_pool.queue_depths(),
+            running=state.job_pool.running_jobs(),
+            workers=dict(state.job_pool.concurrency),
+            paused=state.job_pool.paused(),
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/groups/{group_id}", response_model=JobGroupResponse)
+async def get_job_group(group_id: str = FastAPIPath(..., description="ID of the job group")):
+    """Poll the aggregate status of a group of jobs: how many completed out of the total and the first failure"""
+    try:
+        if not state.job_pool:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+
+        group = state.job_pool.get_group(group_id)
+        if group is None:
+            raise HTTPException(status_code=404, detail=f"Job group not found: {group_id}")
+        failure = group.first_failure
+        return JobGroupResponse(
+            group_id=group.group_id,
+            status=group.status,
+            total=group.total,
+            completed=group.completed,
+            counts=group.counts,
+            progress=group.progress,
+            percent=round(group.progress * 100, 1),
+            first_failure=_job_item(failure) if failure is not None else None,
+            jobs=[_job_item(job) for job in group.jobs],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs/groups/{group_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/dead-letter", response_model=DeadLetterResponse)
+async def get_dead_letter(
+    kind: Optional[str] = Query(default=None, description="Only jobs of this kind"),
+    limit: int = Query(default=50, ge=1, le=500, description="Number of jobs to return"),
+):
+    """List the failed and interrupted jobs nobody retried yet, newest first, with the parameters they ran with"""
+    try:
+        if not state.job_pool:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+
+        return DeadLetterResponse(jobs=[_job_item(job) for job in state.job_pool.dead_letter(kind, limit)])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs/dead-letter endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/purge", response_model=PurgeJobsResponse)
+async def purge_jobs(request: Optional[PurgeJobsRequest] = None):
+    """Drop finished jobs from memory without waiting for the sweeper, and from the stored history if asked"""
+    try:
+        if not state.job_pool:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+
+        request = request or PurgeJobsRequest()
+        older_than = timedelta(hours=request.older_than_hours) if request.older_than_hours is not None else None
+        result = state.job_pool.purge(older_than, history=request.history)
+        return PurgeJobsResponse(evicted=result.evicted, deleted=result.deleted, tracked=state.job_pool.tracked())
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs/purge endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/pipelines/{pipeline_id}", response_model=PipelineResponse)
+async def get_pipeline(pipeline_id: str = FastAPIPath(..., description="ID of the pipeline")):
+    """Poll a pipeline: the status of each node, waiting for its dependencies, blocked by a failed one, or of its job"""
+    try:
+        if not state.pipeline_runner:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+
+        status = state.pipeline_runner.status(pipeline_id)
+        if status is None:
+            raise HTTPException(status_code=404, detail=f"Pipeline not found: {pipeline_id}")
+        return pipeline_response(status)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs/pipelines/{pipeline_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{job_id}", response_model=JobItem)
+async def get_job(job_id: str = FastAPIPath(..., description="ID of the job")):
+    """Poll the status and progress of a background job, with every status it went through"""
+    try:
+        job = _job_item(_get_job(job_id))
+        job.events = _job_events(job_id)
+        assert state.job_pool is not None
+        retries = state.job_pool.retries(job_id)
+        job.retried_by = retries[-1].job_id if retries else None
+        return job
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs/{job_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{job_id}/events")
+async def stream_job_events(job_id: str = FastAPIPath(..., description="ID of the job")):
+    """Follow a job as server-sent `job` events carrying its status and progress after every change, until it finished"""
+    try:
+        _get_job(job_id)
+        return StreamingResponse(_job_event_stream(job_id), media_type="text/event-stream", headers={"Cache-Control": "no-cache"})
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs/{job_id}/events endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{job_id}/result", response_model=JobResultResponse)
+async def get_job_result(job_id: str = FastAPIPath(..., description="ID of the job")):
+    """Get the result of a job that succeeded, 409 while it runs or when it failed"""
+    try:
+        job = _get_job(job_id)
+        if job.status in (JOB_FAILED, JOB_CANCELLED, JOB_DEFERRED):
+            raise HTTPException(status_code=409, detail=f"Job {job.status}: {job.error}")
+        if not job.finished:
+            raise HTTPException(status_code=409, detail=f"Job is {job.status}, poll /jobs/{job_id} until it finished")
+        return JobResultResponse(job_id=job.job_id, kind=job.kind, result=job.result or {})
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs/{job_id}/result endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{job_id}/resume", response_model=SubmitJobResponse)
+async def resume_job(job_id: str = FastAPIPath(..., description="ID of the job")):
+    """Run a failed, interrupted, cancelled or deferred job again with the same parameters, keeping its ID"""
+    try:
+        if not state.job_pool:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+
+        job = state.job_pool.resume(job_id)
+        if job is None:
+            raise HTTPException(status_code=404, detail=f"Job not found: {job_id}")
+        return SubmitJobResponse(job_id=job.job_id, kind=job.kind, status=job.status, message="Job resumed")
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=409, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs/{job_id}/resume endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{job_id}/retry", response_model=SubmitJobResponse)
+async def retry_job(job_id: str = FastAPIPath(..., description="ID of the failed job")):
+    """Submit the parameters of a failed or interrupted job as a new job, which records the job it retries"""
+    try:
+        if not state.job_pool:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+
+        job = state.job_pool.retry(job_id)
+        if job is None:
+            raise HTTPException(status_code=404, detail=f"Job not found: {job_id}")
+        return SubmitJobResponse(job_id=job.job_id, kind=job.kind, status=job.status, message="Job retried", retry_of=job.retry_of)
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=409, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs/{job_id}/retry endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.delete("/{job_id}", response_model=JobItem)
+async def cancel_job(job_id: str = FastAPIPath(..., description="ID of the job")):
+    """Cancel a pending or running job, its handler stops at its next check and the job can be resumed later"""
+    try:
+        if not state.job_pool:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+
+        job = state.job_pool.cancel_job(job_id)
+        if job is None:
+            raise HTTPException(status_code=404, detail=f"Job not found: {job_id}")
+        return _job_item(job)
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=409, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /jobs/{job_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,87 @@
+# Problem review routes
+# Spaced repetition of extracted problems: the problems due for a user and grading how a review went.
+
+from datetime import datetime, timezone
+from typing import Optional
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.database import ProblemStateInfo
+from textbook.review import GRADE_LABELS, preview_intervals
+from textbook.srs import grade_problem, problem_review_queue, problem_schedule
+
+from api.models import GradeIntervalItem, ProblemScheduleItem, ProblemDueItem, ProblemsDueResponse, GradeProblemRequest, GradeProblemResponse
+from api.items import problem_item
+from api.state import state
+
+router = APIRouter(prefix="/reviews", tags=["reviews"])
+
+
+def _problem_schedule_item(problem_state: ProblemStateInfo) -> ProblemScheduleItem:
+    return ProblemScheduleItem(
+        interval_days=problem_state.interval_days,
+        ease=problem_state.ease,
+        repetitions=problem_state.repetitions,
+        lapses=problem_state.lapses,
+        due_at=problem_state.due_at,
+        last_reviewed_at=problem_state.last_reviewed_at,
+    )
+
+
+# Problem review endpoints
+@router.get("/due", response_model=ProblemsDueResponse)
+async def get_due_problems(
+    limit: int = Query(default=20, ge=1, le=100, description="Number of problems to return"),
+    new_limit: int = Query(default=5, ge=0, le=100, description="Most problems never reviewed to add after the due ones"),
+    user_id: str = Query(default="default", description="User to review for"),
+    document_id: Optional[int] = Query(default=None, description="Only review the problems of this document"),
+):
+    """
+    Get the problems due for review
+
+    Due problems come first, most overdue first, then problems never reviewed in extraction order. Each problem
+    carries the interval every grade would give.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        now = datetime.now(timezone.utc)
+        problems = []
+        for problem, problem_state in problem_review_queue(state.database, user_id, limit, new_limit, document_id, now):
+            intervals = preview_intervals(problem_schedule(problem_state), now)
+            problems.append(ProblemDueItem(
+                problem=problem_item(problem),
+                schedule=_problem_schedule_item(problem_state) if problem_state else None,
+                intervals=[GradeIntervalItem(grade=grade, label=label, interval_days=intervals[grade]) for grade, label in GRADE_LABELS.items()],
+            ))
+        return ProblemsDueResponse(user_id=user_id, problems=problems)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /reviews/due endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{problem_id}/grade", response_model=GradeProblemResponse)
+async def post_problem_grade(problem_id: int, request: GradeProblemRequest):
+    """Record how a review of a problem went and schedule its next review"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        if state.database.get_problem(problem_id) is None:
+            raise HTTPException(status_code=404, detail=f"Problem not found: {problem_id}")
+        problem_state = grade_problem(state.database, request.user_id, problem_id, request.grade)
+        return GradeProblemResponse(problem_id=problem_id, user_id=request.user_id, grade=request.grade, schedule=_problem_schedule_item(problem_state))
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /reviews/{problem_id}/grade endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,390 @@
+# Problem routes
+# Problems extracted from documents and what is generated for them: stepwise solutions and hint ladders, generated
+# by LLM jobs and read once they finished. Hints are read up to a level, so learners reveal them one at a time.
+# Answers are checked against the stored solution in a job too, whose verdict the request waits for. Problems given a
+# numeric answer, a value with a unit and a tolerance, have answers that are a number with a unit checked right away,
+# and so do problems given a chemical structure, with answers that are a formula or SMILES.
+# Problems the quality checks flagged wait in a review queue until a reviewer approves or rejects them.
+
+import asyncio
+from typing import Optional
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.answer_check import check_answer, deterministic_verdict, numeric_answer
+from textbook.capabilities import FEATURE_GRADING, FEATURE_LLM
+from textbook.chemistry import canonical_structure
+from textbook.database import HintInfo, NumericAnswerInfo, SolutionInfo
+from textbook.hints import generate_hint_ladder, revealed_hints, HINT_TIERS
+from textbook.jobs import JobHandle, JOB_KIND_ANSWER_CHECK, JOB_KIND_HINT_LADDER, JOB_KIND_SOLUTION, JOB_SUCCEEDED
+from textbook.problem_quality import QUALITY_FLAGGED, review_problem
+from textbook.solutions import generate_solution, solution_hints, solution_steps
+from textbook.units import dimension_text, validate_numeric_answer
+
+from api.models import SubmitJobResponse, SolutionItem, HintItem, HintsResponse, CheckAnswerRequest, AnswerCheckResponse, DeleteNumericAnswerResponse, NumericAnswerItem, NumericAnswerRequest, ProblemItem, QualityReviewRequest, QualityReviewResponse, StructureRequest
+from api.items import problem_item
+from api.state import FORCE_REFRESH_DESCRIPTION, PROFILE_DESCRIPTION, state, get_llm, require_feature, require_profile
+
+router = APIRouter(prefix="/problems", tags=["problems"])
+
+ANSWER_CHECK_TIMEOUT_SECONDS = 60  # Longer checks go on in their job, which clients can poll
+
+
+def _solution_item(solution: SolutionInfo) -> SolutionItem:
+    return SolutionItem(
+        solution_id=solution.solution_id,
+        problem_id=solution.problem_id,
+        steps=solution_steps(solution),
+        final_answer=solution.final_answer,
+        hints=solution_hints(solution),
+        model_name=solution.model_name,
+        created_at=solution.created_at,
+    )
+
+
+def _numeric_answer_item(stored: NumericAnswerInfo) -> NumericAnswerItem:
+    expected = validate_numeric_answer(numeric_answer(stored))
+    return NumericAnswerItem(
+        problem_id=stored.problem_id,
+        value=stored.value,
+        unit=stored.unit,
+        abs_tolerance=stored.abs_tolerance,
+        rel_tolerance=stored.rel_tolerance,
+        si_value=expected.value,
+        si_unit=dimension_text(expected.dimensions),
+        created_at=stored.created_at,
+    )
+
+
+def _hint_item(hint: HintInfo) -> HintItem:
+    return HintItem(level=hint.level, tier=hint.tier, text=hint.text, created_at=hint.created_at)
+
+
+def generate_solution_job(params: dict, handle: JobHandle) -> dict:
+    """Solution job of a problem, a prompt whose answer replaces its solution; in a pipeline, of every problem extracted and not flagged before"""
+    llm = get_llm(JOB_KIND_SOLUTION, params.get("profile"))
+    if "problem_id" in params:
+        solution = generate_solution(
+            state.database,
+            llm,
+            params["problem_id"],
+            cancellation_token=handle.cancellation_token,
+            report_progress=handle.report_progress,
+            uploads_dir=state.uploads_dir,
+        )
+        return {"problem_id": solution.problem_id, "solution_id": solution.solution_id}
+    problem_ids = [
+        problem_id
+        for result in params.get("inputs", {}).values()
+        for problem_id in result.get("problem_ids", [])
+        if problem_id not in result.get("flagged_problem_ids", [])
+    ]
+    solution_ids = []
+    for position, problem_id in enumerate(problem_ids):
+        handle.cancellation_token.raise_if_cancelled()
+        handle.report_progress("solve", 100 * position / len(problem_ids), f"problem {position + 1}/{len(problem_ids)}")
+        solution_ids.append(generate_solution(state.database, llm, problem_id, cancellation_token=handle.cancellation_token, uploads_dir=state.uploads_dir).solution_id)
+    return {"problem_ids": problem_ids, "solution_ids": solution_ids}
+
+
+def generate_hint_ladder_job(params: dict, handle: JobHandle) -> dict:
+    """Hint ladder job of a problem, a single prompt for all tiers"""
+    hints = generate_hint_ladder(
+        state.database,
+        get_llm(JOB_KIND_HINT_LADDER, params.get("profile")),
+        params["problem_id"],
+        cancellation_token=handle.cancellation_token,
+        report_progress=handle.report_progress,
+        uploads_dir=state.uploads_dir,
+    )
+    return {"problem_id": params["problem_id"], "hint_ids": [hint.hint_id for hint in hints]}
+
+
+def check_answer_job(params: dict, handle: JobHandle) -> dict:
+    """Answer check job of a problem, a single prompt comparing the answer with the stored solution"""
+    verdict = check_answer(
+        state.database,
+        get_llm(JOB_KIND_ANSWER_CHECK, params.get("profile")),
+        params["problem_id"],
+        params["answer"],
+        cancellation_token=handle.cancellation_token,
+        report_progress=handle.report_progress,
+    )
+    return {"problem_id": params["problem_id"], "verdict": verdict.verdict, "feedback": verdict.feedback}
+
+
+@router.get("/quality-review", response_model=QualityReviewResponse)
+async def get_quality_review_queue(document_id: Optional[int] = Query(default=None, description="Only the problems of this document")):
+    """List the problems the quality checks flagged, lowest score first, with the issues found"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        problems = state.database.get_problems_by_quality(QUALITY_FLAGGED, document_id)
+        return QualityReviewResponse(document_id=document_id, problems=[problem_item(problem) for problem in problems])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/quality-review endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{problem_id}/quality-review", response_model=ProblemItem)
+async def post_quality_review(problem_id: int, request: QualityReviewRequest):
+    """Approve a flagged problem, so it is reviewed like the others, or reject it for good"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_problem(problem_id) is None:
+            raise HTTPException(status_code=404, detail=f"Problem not found: {problem_id}")
+        try:
+            problem = review_problem(state.database, problem_id, request.decision)
+        except ValueError as e:
+            raise HTTPException(status_code=400, detail=str(e))
+        return problem_item(problem)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/{problem_id}/quality-review endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/{problem_id}/structure", response_model=ProblemItem)
+async def put_problem_structure(problem_id: int, request: StructureRequest):
+    """
+    Set the chemical formula and SMILES of the compound a problem asks for
+
+    Both are validated and stored in canonical form, the formula in Hill order; invalid ones are a 400. Answers that
+    are a formula, or a SMILES string with RDKit installed, are then checked against them without the LLM.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        try:
+            chemical_formula, smiles = canonical_structure(request.chemical_formula, request.smiles)
+        except ValueError as e:
+            raise HTTPException(status_code=400, detail=str(e))
+        problem = state.database.update_problem(problem_id, chemical_formula=chemical_formula, smiles=smiles)
+        if problem is None:
+            raise HTTPException(status_code=404, detail=f"Problem not found: {problem_id}")
+        return problem_item(problem)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/{problem_id}/structure endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{problem_id}/solution", response_model=SubmitJobResponse)
+async def post_problem_solution(
+    problem_id: int,
+    profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION),
+    force_refresh: bool = Query(default=False, description=FORCE_REFRESH_DESCRIPTION),
+):
+    """Start a job generating a stepwise solution of a problem, poll the job and then get the solution"""
+    try:
+        require_feature(FEATURE_LLM)
+        require_profile(profile)
+        if not state.job_pool or not state.database:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+        problem = state.database.get_problem(problem_id)
+        if problem is None:
+            raise HTTPException(status_code=404, detail=f"Problem not found: {problem_id}")
+
+        job = state.job_pool.submit(JOB_KIND_SOLUTION, {"problem_id": problem_id, "profile": profile, "force_refresh": force_refresh})
+        return SubmitJobResponse(job_id=job.job_id, kind=job.kind, status=job.status, message="Solution job submitted", document_id=problem.document_id)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/{problem_id}/solution endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{problem_id}/solution", response_model=SolutionItem)
+async def get_problem_solution(problem_id: int):
+    """Get the stepwise solution generated for a problem"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        solution = state.database.get_solution(problem_id)
+        if solution is None:
+            raise HTTPException(status_code=404, detail=f"No solution generated for problem {problem_id}")
+        return _solution_item(solution)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/{problem_id}/solution endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{problem_id}/hints", response_model=SubmitJobResponse)
+async def post_problem_hints(
+    problem_id: int,
+    profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION),
+    force_refresh: bool = Query(default=False, description=FORCE_REFRESH_DESCRIPTION),
+):
+    """Start a job generating the hint ladder of a problem: a nudge, the approach and a partial solution"""
+    try:
+        require_feature(FEATURE_LLM)
+        require_profile(profile)
+        if not state.job_pool or not state.database:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+        problem = state.database.get_problem(problem_id)
+        if problem is None:
+            raise HTTPException(status_code=404, detail=f"Problem not found: {problem_id}")
+
+        job = state.job_pool.submit(JOB_KIND_HINT_LADDER, {"problem_id": problem_id, "profile": profile, "force_refresh": force_refresh})
+        return SubmitJobResponse(job_id=job.job_id, kind=job.kind, status=job.status, message="Hint ladder job submitted", document_id=problem.document_id)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/{problem_id}/hints endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{problem_id}/hints", response_model=HintsResponse)
+async def get_problem_hints(
+    problem_id: int,
+    level: int = Query(default=1, ge=1, le=len(HINT_TIERS), description="Reveal the hints up to this level, 1 gives the least away"),
+):
+    """Get the hints of a problem up to a level"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        hints = revealed_hints(state.database, problem_id, level)
+        if not hints:
+            raise HTTPException(status_code=404, detail=f"No hints generated for problem {problem_id}")
+        return HintsResponse(problem_id=problem_id, level=level, max_level=len(HINT_TIERS), hints=[_hint_item(hint) for hint in hints])
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/{problem_id}/hints endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/{problem_id}/numeric-answer", response_model=NumericAnswerItem)
+async def put_problem_numeric_answer(problem_id: int, request: NumericAnswerRequest):
+    """
+    Set the numeric answer of a problem, replacing the one set before
+
+    Answers that are a number with a unit are then checked against it in SI within the tolerance, without the LLM.
+    The unit is validated, unknown units are a 400.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_problem(problem_id) is None:
+            raise HTTPException(status_code=404, detail=f"Problem not found: {problem_id}")
+        stored = NumericAnswerInfo(
+            problem_id=problem_id,
+            value=request.value,
+            unit=request.unit.strip(),
+            abs_tolerance=request.abs_tolerance,
+            rel_tolerance=request.rel_tolerance,
+        )
+        try:
+            validate_numeric_answer(numeric_answer(stored))
+        except ValueError as e:
+            raise HTTPException(status_code=400, detail=str(e))
+        return _numeric_answer_item(state.database.save_numeric_answer(stored))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/{problem_id}/numeric-answer endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{problem_id}/numeric-answer", response_model=NumericAnswerItem)
+async def get_problem_numeric_answer(problem_id: int):
+    """Get the numeric answer of a problem, with its value in SI"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        stored = state.database.get_numeric_answer(problem_id)
+        if stored is None:
+            raise HTTPException(status_code=404, detail=f"No numeric answer set for problem {problem_id}")
+        return _numeric_answer_item(stored)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/{problem_id}/numeric-answer endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.delete("/{problem_id}/numeric-answer", response_model=DeleteNumericAnswerResponse)
+async def delete_problem_numeric_answer(problem_id: int):
+    """Remove the numeric answer of a problem, its answers are checked against the solution again"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if not state.database.delete_numeric_answer(problem_id):
+            raise HTTPException(status_code=404, detail=f"No numeric answer set for problem {problem_id}")
+        return DeleteNumericAnswerResponse(message=f"Numeric answer of problem {problem_id} removed", problem_id=problem_id)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/{problem_id}/numeric-answer endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{problem_id}/check", response_model=AnswerCheckResponse)
+async def post_problem_check(problem_id: int, request: CheckAnswerRequest, profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION)):
+    """
+    Check an answer to a problem against its stored solution
+
+    The verdict is correct, partially_correct or wrong, with feedback on the first mistake or missing step. The
+    problem needs a generated solution. The check runs in a job; if it takes longer than a minute the response is
+    a 504 naming the job, whose result holds the verdict once it finished. An answer that is a number with a unit, to
+    a problem with a numeric answer, or a formula to a problem with a structure, is checked right away without a job
+    or a solution.
+    """
+    try:
+        require_feature(FEATURE_GRADING)
+        require_profile(profile)
+        if not state.job_pool or not state.database:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+        if state.database.get_problem(problem_id) is None:
+            raise HTTPException(status_code=404, detail=f"Problem not found: {problem_id}")
+        deterministic = deterministic_verdict(state.database, problem_id, request.answer)
+        if deterministic is not None:
+            return AnswerCheckResponse(problem_id=problem_id, verdict=deterministic.verdict, feedback=deterministic.feedback)
+        if state.database.get_solution(problem_id) is None:
+            raise HTTPException(status_code=409, detail=f"No solution generated for problem {problem_id}, generate one to check answers against")
+
+        job = state.job_pool.submit(JOB_KIND_ANSWER_CHECK, {"problem_id": problem_id, "answer": request.answer, "profile": profile})
+        finished = await asyncio.to_thread(state.job_pool.wait, job.job_id, ANSWER_CHECK_TIMEOUT_SECONDS)
+        if finished.status == JOB_SUCCEEDED:
+            return AnswerCheckResponse(problem_id=problem_id, job_id=job.job_id, verdict=finished.result["verdict"], feedback=finished.result["feedback"])
+        if not finished.finished:
+            raise HTTPException(status_code=504, detail=f"The answer is still being checked, get the verdict from job {job.job_id}")
+        if finished.error_category:
+            raise HTTPException(status_code=502, detail={"code": finished.error_category, "message": finished.error})
+        raise HTTPException(status_code=500, detail=f"Answer check failed: {finished.error}")
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /problems/{problem_id}/check endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,250 @@
+# Quiz routes
+# Quizzes assembled from the exercises of a book, answer timing, mastery, and the diagnostic quizzes given after a chapter.
+
+from dataclasses import asdict
+from typing import Optional, List
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.database import QuestionTimingInfo, ChapterInfo, ExerciseInfo
+from textbook.quiz import assemble_quiz, book_mastery, load_exercise_candidates, QuizMix, TopicMastery
+from textbook.projections import projected_mastery
+from textbook.quiz_timing import TimingStats, record_skip, timing_analytics
+from textbook.preferences import load_preferences
+from textbook.chapter_quiz import chapter_quiz_progress
+from textbook.smart_filters import evaluate_filter, require_filter
+
+from api.models import SkipQuestionRequest, QuestionTimingItem, TimingStatsItem, QuizTimingResponse, QuizRequest, ChapterMasteryItem, QuizItemResponse, QuizResponse, MasteryResponse, ChapterQuizzesResponse, ChapterQuizResponse
+from api.state import state
+from api.items import chapter_quiz_item, exercise_item
+
+router = APIRouter(tags=["quizzes"])
+
+
+def _chapter_mastery_items(chapters: List[ChapterInfo], masteries: dict[int, TopicMastery]) -> List[ChapterMasteryItem]:
+    items = []
+    for chapter in chapters:
+        mastery = masteries.get(chapter.chapter_id)
+        if mastery is None:
+            continue
+        items.append(ChapterMasteryItem(
+            chapter_id=chapter.chapter_id,
+            title=chapter.title,
+            mastery=mastery.mastery,
+            attempts=mastery.attempts,
+            last_attempt_at=mastery.last_attempt_at,
+            review_interval_days=mastery.review_interval_days,
+            due_for_review=mastery.attempts > 0 and mastery.overdue >= 1,
+            accurate_but_slow=mastery.accurate_but_slow,
+        ))
+    return items
+
+
+# Quiz endpoints
+@router.post("/books/{book_id}/quiz", response_model=QuizResponse)
+async def create_quiz(book_id: int, request: QuizRequest):
+    """
+    Assemble a quiz on a chapter, interleaved with earlier chapters the learner is weak at or due to review
+
+    The proportions of the three slots are normalized; slots without enough problems are filled from the others.
+    Without proportions the user's scheduler decides, and their daily new item limit and difficulty are respected.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        proportions = (request.current_proportion, request.weak_proportion, request.review_proportion)
+        mix = None
+        if any(proportion is not None for proportion in proportions):
+            default = QuizMix()
+            mix = QuizMix(
+                request.current_proportion if request.current_proportion is not None else default.current,
+                request.weak_proportion if request.weak_proportion is not None else default.weak,
+                request.review_proportion if request.review_proportion is not None else default.review,
+            )
+            if mix.current + mix.weak + mix.review <= 0:
+                raise HTTPException(status_code=400, detail="The quiz proportions must not all be zero")
+
+        chapters = state.database.get_chapters_by_book_id(book_id)
+        if not any(chapter.chapter_id == request.chapter_id for chapter in chapters):
+            raise HTTPException(status_code=404, detail=f"Chapter {request.chapter_id} not found in book {book_id}")
+
+        exercise_ids = None
+        if request.filter_id is not None:
+            smart_filter = require_filter(state.database, request.filter_id, request.user_id)
+            if smart_filter.book_id != book_id:
+                raise HTTPException(status_code=400, detail=f"Filter {request.filter_id} searches book {smart_filter.book_id}, not book {book_id}")
+            exercise_ids = {candidate.exercise_id for candidate in evaluate_filter(state.database, smart_filter)}
+
+        quiz, masteries = assemble_quiz(state.database, book_id, request.chapter_id, request.size, mix, request.seed, load_preferences(state.database, request.user_id), exercise_ids)
+
+        with state.database.new_session() as session:
+            exercises = session.query(ExerciseInfo).filter(ExerciseInfo.exercise_id.in_([item.exercise_id for item in quiz])).all()
+            exercise_items = {exercise.exercise_id: exercise_item(exercise) for exercise in exercises}
+
+        return QuizResponse(
+            book_id=book_id,
+            chapter_id=request.chapter_id,
+            items=[QuizItemResponse(exercise=exercise_items[item.exercise_id], chapter_id=item.chapter_id, slot=item.slot, mastery=item.mastery) for item in quiz],
+            chapters=_chapter_mastery_items(chapters, masteries),
+        )
+    except HTTPException:
+        raise
+    except LookupError as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except ValueError as e:
+        raise HTTPException(status_code=409, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/quiz endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _question_timing_item(timing: QuestionTimingInfo) -> QuestionTimingItem:
+    return QuestionTimingItem(
+        timing_id=timing.timing_id,
+        exercise_id=timing.exercise_id,
+        attempt_id=timing.attempt_id,
+        think_seconds=timing.think_seconds,
+        answer_changes=timing.answer_changes,
+        skipped=timing.skipped,
+        created_at=timing.created_at,
+    )
+
+
+def _timing_stats_item(stats: TimingStats, title: Optional[str] = None) -> TimingStatsItem:
+    return TimingStatsItem(title=title, **asdict(stats))
+
+
+@router.post("/exercises/{exercise_id}/skip", response_model=QuestionTimingItem)
+async def skip_question(exercise_id: int, request: SkipQuestionRequest):
+    """Record a quiz question skipped without an answer, with how long it was looked at"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_exercise_info(exercise_id) is None:
+            raise HTTPException(status_code=404, detail=f"Exercise not found: {exercise_id}")
+        return _question_timing_item(record_skip(state.database, exercise_id, request.think_seconds, request.answer_changes))
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /exercises/{exercise_id}/skip endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/books/{book_id}/quiz-timing", response_model=QuizTimingResponse)
+async def get_quiz_timing(book_id: int):
+    """
+    Get the think time, answer changes, skips and accuracy of a book's quiz questions per question type and chapter
+
+    Chapters answered accurately but slowly are flagged; their mastery is scaled down so quizzes keep practicing them.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_book_by_id(book_id) is None:
+            raise HTTPException(status_code=404, detail=f"Book not found: {book_id}")
+
+        chapter_of = {candidate.exercise_id: candidate.chapter_id for candidate in load_exercise_candidates(state.database, book_id)}
+        analytics = timing_analytics(state.database, book_id, chapter_of)
+        titles = {str(chapter.chapter_id): chapter.title for chapter in state.database.get_chapters_by_book_id(book_id)}
+        return QuizTimingResponse(
+            book_id=book_id,
+            median_think_seconds=analytics.median_think_seconds,
+            by_question_type=[_timing_stats_item(stats) for stats in analytics.by_question_type],
+            by_chapter=[_timing_stats_item(stats, titles.get(stats.key)) for stats in analytics.by_chapter],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/quiz-timing endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/books/{book_id}/mastery", response_model=MasteryResponse)
+async def get_mastery(book_id: int, from_events: bool = Query(default=False, description="Rebuild the mastery by replaying the study events")):
+    """Get the estimated mastery of every chapter of a book and whether it is due for review"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        chapters = state.database.get_chapters_by_book_id(book_id)
+        masteries = projected_mastery(state.database, book_id) if from_events else book_mastery(state.database, book_id)
+        return MasteryResponse(book_id=book_id, chapters=_chapter_mastery_items(chapters, masteries))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/mastery endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+# Chapter quiz endpoints
+@router.get("/users/{user_id}/chapter-quizzes", response_model=ChapterQuizzesResponse)
+async def get_chapter_quizzes(
+    user_id: str,
+    include_completed: bool = Query(default=False, description="Also return the quizzes every exercise of which was answered"),
+):
+    """Get the diagnostic quizzes enqueued after a user read a chapter, newest first; pending ones are the user's notifications"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        quizzes = [chapter_quiz_item(chapter_quiz_progress(state.database, quiz)) for quiz in state.database.get_chapter_quizzes(user_id, include_completed)]
+        if not include_completed:
+            # Answering the last question completes a quiz, which then no longer notifies
+            quizzes = [quiz for quiz in quizzes if quiz.completed_at is None]
+        return ChapterQuizzesResponse(user_id=user_id, quizzes=quizzes)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/chapter-quizzes endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/chapter-quizzes/{quiz_id}", response_model=ChapterQuizResponse)
+async def get_chapter_quiz(
+    quiz_id: int,
+    user_id: Optional[str] = Query(default=None, description="Only return the quiz if it is this user's, set for API tokens acting for a user"),
+):
+    """
+    Get a chapter quiz with its exercises, the answers so far and the chapter's mastery they seeded
+
+    Answers are submitted as attempts of the exercises; once every exercise is answered the quiz is completed.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        quiz = state.database.get_chapter_quiz(quiz_id)
+        # Another user's quiz is not found, rather than forbidden, so quiz IDs cannot be probed
+        if quiz is None or user_id not in (None, quiz.user_id):
+            raise HTTPException(status_code=404, detail=f"Chapter quiz not found: {quiz_id}")
+        progress = chapter_quiz_progress(state.database, quiz)
+
+        with state.database.new_session() as session:
+            exercises = session.query(ExerciseInfo).filter(ExerciseInfo.exercise_id.in_(progress.exercise_ids)).all()
+            exercise_items = {exercise.exercise_id: exercise_item(exercise) for exercise in exercises}
+
+        chapters = [chapter for chapter in state.database.get_chapters_by_book_id(quiz.book_id) if chapter.chapter_id == quiz.chapter_id]
+        mastery_items = _chapter_mastery_items(chapters, {quiz.chapter_id: progress.mastery} if progress.mastery is not None else {})
+        return ChapterQuizResponse(
+            quiz=chapter_quiz_item(progress),
+            # Exercises deleted since the quiz was enqueued are left out
+            exercises=[exercise_items[exercise_id] for exercise_id in progress.exercise_ids if exercise_id in exercise_items],
+            mastery=mastery_items[0] if mastery_items else None,
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /chapter-quizzes/{quiz_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
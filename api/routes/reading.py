@@ -0,0 +1,339 @@
+# Reading routes
+# The incremental reading queue and extracts, the reading view of chapters with highlights, and tutoring sessions.
+
+from datetime import datetime, timezone
+from typing import Optional
+
+from fastapi import APIRouter, HTTPException, Query, Path as FastAPIPath
+
+from textbook.database import HighlightInfo
+from textbook.transcript import video_deep_link
+from textbook.reading_queue import enqueue_section, excerpt_item, record_reading, extract_to_cloze
+from textbook.reading_view import render_page, section_practice, page_anchor, highlight_anchor
+from textbook.status import item_status
+from textbook.chapter_quiz import chapter_quiz_progress, reading_done
+from textbook.cross_reference import load_cross_reference_index, section_anchor, ENTITY_FIGURE
+
+from api.models import TutoringMessageItem, TutoringSessionResponse, ChapterItem, CreateHighlightRequest, HighlightItem, HighlightsResponse, HighlightMessageResponse, ReadingPageItem, SectionPracticeItem, ChapterViewResponse, EnqueueReadingRequest, ReadingItem, ReadingQueueResponse, RecordReadingRequest, ExtractClozeRequest, ExtractClozeResponse
+from api.state import state, get_reader_by_book_id, get_video_url
+from api.items import chapter_quiz_item, entity_item, reading_item, flashcard_items
+
+router = APIRouter(tags=["reading"])
+
+
+# Reading queue endpoints
+@router.post("/documents/{book_id}/reading-queue", response_model=ReadingItem)
+async def enqueue_reading(book_id: int, request: EnqueueReadingRequest):
+    """Add a section or an excerpt of a document to the user's incremental reading queue, due right away"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_book_by_id(book_id) is None:
+            raise HTTPException(status_code=404, detail=f"Book not found: {book_id}")
+
+        if request.section_id is not None:
+            item = enqueue_section(state.database, request.user_id, book_id, request.section_id)
+        elif request.text is not None and request.page_number is not None:
+            item = state.database.create_reading_item(excerpt_item(request.user_id, book_id, request.page_number, request.text, datetime.now(timezone.utc)))
+        else:
+            raise HTTPException(status_code=400, detail="Either section_id, or page_number and text of an excerpt, are required")
+        return reading_item(item)
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{book_id}/reading-queue endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/reading/queue", response_model=ReadingQueueResponse)
+async def get_reading_queue(
+    user_id: str = Query(default="default", description="User whose queue to return"),
+    book_id: Optional[int] = Query(default=None, description="Only return the items of this book"),
+    include_done: bool = Query(default=False, description="Also return the items the user is done with"),
+):
+    """Get the reading queue of a user, next due first"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        items = state.database.get_reading_items(user_id, book_id, include_done)
+        return ReadingQueueResponse(user_id=user_id, items=[reading_item(item) for item in items])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /reading/queue endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/reading/{reading_id}/read", response_model=ReadingItem)
+async def read_reading_item(reading_id: int, request: RecordReadingRequest):
+    """
+    Record a reading of an item, which is due again after a growing interval unless the user is done with it
+
+    Being done with the last section of a chapter enqueues a diagnostic quiz on the chapter, returned with the item.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        item = record_reading(state.database, reading_id, request.done)
+        if item is None:
+            raise HTTPException(status_code=404, detail=f"Reading item not found: {reading_id}")
+        quiz = reading_done(state.database, item, state.chapter_quiz_settings) if request.done else None
+        return reading_item(item, chapter_quiz_item(chapter_quiz_progress(state.database, quiz)) if quiz is not None else None)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /reading/{reading_id}/read endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/documents/{book_id}/extracts", response_model=ExtractClozeResponse)
+async def extract_cloze(book_id: int, request: ExtractClozeRequest):
+    """Extract a passage: keep it as a highlight, turn each cloze term into a card and optionally enqueue it for reading"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if state.database.get_book_by_id(book_id) is None:
+            raise HTTPException(status_code=404, detail=f"Book not found: {book_id}")
+
+        extract = extract_to_cloze(
+            state.database,
+            request.user_id,
+            book_id,
+            request.page_number,
+            request.char_start,
+            request.char_end,
+            request.text,
+            request.cloze_terms,
+            request.enqueue,
+        )
+        return ExtractClozeResponse(
+            book_id=book_id,
+            highlight=_highlight_item(extract.highlight),
+            flashcards=flashcard_items(book_id, state.database.get_flashcards_by_ids(extract.card_ids)),
+            reading_item=reading_item(extract.reading_item) if extract.reading_item is not None else None,
+        )
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{book_id}/extracts endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _highlight_item(highlight: HighlightInfo) -> HighlightItem:
+    return HighlightItem(
+        highlight_id=highlight.highlight_id,
+        user_id=highlight.user_id,
+        page_number=highlight.page_number,
+        char_start=highlight.char_start,
+        char_end=highlight.char_end,
+        text=highlight.text,
+        note=highlight.note,
+        created_at=highlight.created_at,
+        anchor=highlight_anchor(highlight.highlight_id),
+    )
+
+
+# Reading view endpoints
+@router.get("/documents/{book_id}/chapters/{chapter_number}/view", response_model=ChapterViewResponse)
+async def get_chapter_view(
+    book_id: int,
+    chapter_number: int = FastAPIPath(..., ge=1, description="Position of the chapter in the TOC, starting at 1"),
+    user_id: str = Query(default="default", description="User whose highlights to show"),
+):
+    """
+    Get a chapter for the reader UI in one payload: the markdown of its pages with references linked, anchors for
+    sections, figures and other entities, the user's highlights marked, and the problems to practice per section
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        with get_reader_by_book_id(book_id) as reader:
+            if not reader.check_if_book_exists_and_load():
+                raise HTTPException(status_code=404, detail="Book not found")
+            chapters = state.database.get_chapters_by_book_id(book_id)
+            if chapter_number > len(chapters):
+                raise HTTPException(status_code=404, detail=f"Chapter {chapter_number} not found in book {book_id}, it has {len(chapters)} chapters")
+            chapter = chapters[chapter_number - 1]
+            book = state.database.get_book_by_id(book_id)
+            offset = book.book_alignment_offset or 0
+
+            # The TOC is in printed page numbers, the pages of the PDF start at the alignment offset
+            end_page_number = chapter.end_page_number if chapter.end_page_number is not None else chapter.start_page_number
+            total_pages = reader.get_total_pages()
+            page_numbers = [page_number for page_number in range(chapter.start_page_number + offset, end_page_number + offset + 1) if 0 <= page_number < total_pages]
+            sections = [section for section in state.database.get_sections_by_book_id(book_id) if section.chapter_id == chapter.chapter_id]
+            entities = [entity for entity in state.database.get_entities_by_book_id(book_id) if entity.page_number in page_numbers]
+            highlights = state.database.get_highlights_by_book_id(book_id, user_id, page_numbers[0], page_numbers[-1]) if page_numbers else []
+            index = load_cross_reference_index(state.database, book_id)
+
+            pages = []
+            for page_number in page_numbers:
+                markdown = render_page(
+                    reader.get_page_content(page_number),
+                    page_number,
+                    index,
+                    [section.section_id for section in sections if section.start_page_number + offset == page_number],
+                    [(entity.entity_id, entity.statement) for entity in entities if entity.page_number == page_number],
+                    [(highlight.highlight_id, highlight.char_start, highlight.char_end, highlight.text) for highlight in highlights if highlight.page_number == page_number],
+                )
+                pages.append(ReadingPageItem(page_number=page_number, printed_page_number=page_number - offset, anchor=page_anchor(page_number), markdown=markdown))
+
+        attempted = {attempt.exercise_id for attempt in state.database.get_attempts_by_book_id(book_id)}
+        practice = section_practice(sections, [(exercise.exercise_id, exercise.page_number) for exercise in state.database.get_exercises_by_book_id(book_id)], attempted, offset)
+        with state.database.new_session() as session:
+            video_url = get_video_url(session, book_id)
+
+        return ChapterViewResponse(
+            book_id=book_id,
+            chapter_number=chapter_number,
+            chapter=ChapterItem(
+                chapter_id=chapter.chapter_id,
+                title=chapter.title,
+                start_page_number=chapter.start_page_number,
+                end_page_number=chapter.end_page_number,
+                book_index_string=chapter.book_index_string,
+                start_seconds=chapter.start_seconds,
+                end_seconds=chapter.end_seconds,
+                deep_link=video_deep_link(video_url, chapter.start_seconds),
+                status=item_status(chapter, book),
+            ),
+            pages=pages,
+            sections=[
+                SectionPracticeItem(
+                    section_id=section.section_id,
+                    title=section.title,
+                    book_index_string=section.book_index_string,
+                    start_page_number=section.start_page_number,
+                    end_page_number=section.end_page_number,
+                    anchor=section_anchor(section.section_id),
+                    problem_count=practice[section.section_id].problem_count,
+                    attempted_count=practice[section.section_id].attempted_count,
+                )
+                for section in sections
+            ],
+            entities=[entity_item(entity) for entity in entities if entity.entity_kind != ENTITY_FIGURE],
+            figures=[entity_item(entity) for entity in entities if entity.entity_kind == ENTITY_FIGURE],
+            highlights=[_highlight_item(highlight) for highlight in highlights],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{book_id}/chapters/{chapter_number}/view endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/documents/{book_id}/highlights", response_model=HighlightItem)
+async def create_highlight(book_id: int, request: CreateHighlightRequest):
+    """Highlight a passage of a page, optionally with a note"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if request.char_end <= request.char_start:
+            raise HTTPException(status_code=400, detail="char_end must be greater than char_start")
+        if state.database.get_book_by_id(book_id) is None:
+            raise HTTPException(status_code=404, detail=f"Book not found: {book_id}")
+
+        highlight = state.database.create_highlight(HighlightInfo(
+            user_id=request.user_id,
+            page_number=request.page_number,
+            char_start=request.char_start,
+            char_end=request.char_end,
+            text=request.text,
+            note=request.note,
+            book_id=book_id,
+        ))
+        return _highlight_item(highlight)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{book_id}/highlights POST endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/documents/{book_id}/highlights", response_model=HighlightsResponse)
+async def get_highlights(
+    book_id: int,
+    user_id: str = Query(default="default", description="User whose highlights to return"),
+    page_number: Optional[int] = Query(default=None, description="Only return the highlights of this PDF page"),
+):
+    """Get the highlights of a user in a document, in reading order"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        highlights = state.database.get_highlights_by_book_id(book_id, user_id, page_number, page_number)
+        return HighlightsResponse(book_id=book_id, highlights=[_highlight_item(highlight) for highlight in highlights])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /documents/{book_id}/highlights endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.delete("/highlights/{highlight_id}", response_model=HighlightMessageResponse)
+async def delete_highlight(highlight_id: int):
+    """Delete a highlight"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if not state.database.delete_highlight(highlight_id):
+            raise HTTPException(status_code=404, detail=f"Highlight not found: {highlight_id}")
+        return HighlightMessageResponse(highlight_id=highlight_id, message="Highlight deleted successfully")
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /highlights/{highlight_id} DELETE endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/tutoring/sessions/{session_id}", response_model=TutoringSessionResponse)
+async def get_tutoring_session(session_id: str):
+    """Get the history of a tutoring session, the passages asked about and their explanations, oldest first"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        messages = state.database.get_tutoring_messages(session_id)
+        if not messages:
+            raise HTTPException(status_code=404, detail=f"Tutoring session not found: {session_id}")
+        return TutoringSessionResponse(
+            session_id=session_id,
+            messages=[
+                TutoringMessageItem(
+                    message_id=message.message_id,
+                    role=message.role,
+                    content=message.content,
+                    char_start=message.char_start,
+                    char_end=message.char_end,
+                    document_id=message.document_id,
+                    created_at=message.created_at,
+                )
+                for message in messages
+            ],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /tutoring/sessions/{session_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,188 @@
+# Review routes
+# Flashcard review batches, undo, and the load forecast and what-if simulation of the review settings.
+
+from datetime import datetime, timezone
+from typing import Optional, List
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.preferences import load_preferences, Preferences
+from textbook.forecast import forecast_load, simulate_settings, DayLoad, DEFAULT_RETENTION, MAX_FORECAST_DAYS
+from textbook.review import review_batch, submit_reviews, undo_last_review, new_card_allowance, ReviewGrade
+
+from api.models import ReviewBatchResponse, SubmitReviewBatchRequest, ReviewResultItem, SubmitReviewBatchResponse, DayLoadItem, ReviewForecastResponse, SimulateReviewRequest, SimulationRunItem, ReviewSimulationResponse, UndoReviewResponse
+from api.items import review_card_items
+from api.state import state
+
+router = APIRouter(prefix="/review", tags=["review"])
+
+
+def _day_load_item(load: DayLoad) -> DayLoadItem:
+    return DayLoadItem(day=load.day, reviews=load.reviews, new_cards=load.new_cards, projected_retention=load.projected_retention)
+
+
+def _simulation_run_item(preferences: Preferences, loads: List[DayLoad]) -> SimulationRunItem:
+    retentions = [load.projected_retention for load in loads if load.projected_retention is not None]
+    return SimulationRunItem(
+        daily_new_card_limit=preferences.daily_new_card_limit,
+        load_balancing=preferences.load_balancing,
+        total_reviews=sum(load.reviews for load in loads),
+        peak_reviews=max(load.reviews for load in loads),
+        mean_retention=sum(retentions) / len(retentions) if retentions else None,
+        days=[_day_load_item(load) for load in loads],
+    )
+
+
+# Review endpoints
+@router.get("/batch", response_model=ReviewBatchResponse)
+async def get_review_batch(
+    limit: int = Query(default=20, ge=1, le=100, description="Number of cards to return"),
+    user_id: str = Query(default="default", description="User to review for"),
+    book_id: Optional[int] = Query(default=None, description="Only review the cards of this book"),
+):
+    """
+    Get the next cards to review with all their render data
+
+    Due cards come first, most overdue first, then new cards up to the user's daily new card limit. Each card
+    carries the interval every grade would give, to label the review buttons.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        now = datetime.now(timezone.utc)
+        preferences = load_preferences(state.database, user_id)
+        batch = review_batch(state.database, user_id, limit, preferences.daily_new_card_limit, book_id, now)
+        return ReviewBatchResponse(
+            user_id=user_id,
+            due_count=state.database.count_due_cards(user_id, now, book_id),
+            new_remaining=new_card_allowance(state.database, user_id, preferences.daily_new_card_limit, now),
+            cards=review_card_items(batch, now),
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /review/batch endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/batch", response_model=SubmitReviewBatchResponse)
+async def post_review_batch(request: SubmitReviewBatchRequest):
+    """Record several review grades at once; if any of them is invalid none is recorded"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        grades = [ReviewGrade(review.card_id, review.grade, review.reviewed_at, review.duration_ms) for review in request.reviews]
+        reviews = submit_reviews(state.database, request.user_id, grades, load_balancing=load_preferences(state.database, request.user_id).load_balancing)
+        return SubmitReviewBatchResponse(
+            user_id=request.user_id,
+            reviews=[ReviewResultItem(review_id=review.review_id, card_id=review.card_id, grade=review.grade, interval_days=review.interval_days, due_at=review.due_at) for review in reviews],
+        )
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /review/batch endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/forecast", response_model=ReviewForecastResponse)
+async def get_review_forecast(
+    user_id: str = Query(default="default", description="User to forecast for"),
+    days: int = Query(default=30, ge=1, le=MAX_FORECAST_DAYS, description="Number of days to project"),
+    retention: float = Query(default=DEFAULT_RETENTION, ge=0, le=1, description="Share of reviews expected to pass"),
+    book_id: Optional[int] = Query(default=None, description="Only forecast the cards of this book"),
+):
+    """Project the reviews and new cards of each coming day under the user's daily new card limit and load balancing"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        preferences = load_preferences(state.database, user_id)
+        loads = forecast_load(state.database, preferences, days, retention, book_id)
+        return ReviewForecastResponse(
+            user_id=user_id,
+            daily_new_card_limit=preferences.daily_new_card_limit,
+            load_balancing=preferences.load_balancing,
+            retention=retention,
+            days=[_day_load_item(load) for load in loads],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /review/forecast endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/simulate", response_model=ReviewSimulationResponse)
+async def post_review_simulate(request: SimulateReviewRequest):
+    """
+    Simulate the coming days of reviews under proposed scheduler settings, next to the current ones
+
+    Both runs use the pass rate of the user's past reviews, so the due load and projected retention of the two can be
+    compared before changing any preference.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        preferences = load_preferences(state.database, request.user_id)
+        changes = request.model_dump(include={"daily_new_card_limit", "load_balancing"}, exclude_none=True)
+        what_if = simulate_settings(state.database, preferences, request.days, request.book_id, **changes)
+        return ReviewSimulationResponse(
+            user_id=request.user_id,
+            retention=what_if.retention,
+            review_count=what_if.review_count,
+            current=_simulation_run_item(preferences, what_if.current),
+            proposed=_simulation_run_item(what_if.proposed_preferences, what_if.proposed),
+        )
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /review/simulate endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{card_id}/undo", response_model=UndoReviewResponse)
+async def undo_review(card_id: int, user_id: str = Query(default="default", description="User whose review to undo")):
+    """Undo the last grade of a card within 30 minutes, restoring its schedule from before the review"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        undone = undo_last_review(state.database, user_id, card_id)
+        if undone is None:
+            raise HTTPException(status_code=404, detail=f"No review of card {card_id} to undo")
+        review, card_state = undone
+        return UndoReviewResponse(
+            card_id=card_id,
+            user_id=user_id,
+            undone_review_id=review.review_id,
+            undone_grade=review.grade,
+            is_new=card_state is None,
+            due_at=card_state.due_at if card_state else None,
+            interval_days=card_state.interval_days if card_state else None,
+            repetitions=card_state.repetitions if card_state else 0,
+            lapses=card_state.lapses if card_state else 0,
+        )
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=409, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /review/{card_id}/undo endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
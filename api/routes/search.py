@@ -0,0 +1,42 @@
+# Search routes
+# Search-as-you-type suggestions: titles, headings and entity names completing what was typed, from the prefix index of
+# textbook/suggest, fast enough to be asked on every keystroke. Full text and semantic search stay with the documents.
+
+from dataclasses import asdict
+from typing import Optional
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.suggest import MAX_SUGGESTIONS, SUGGESTION_KINDS
+
+from api.models import SuggestionItem, SuggestResponse
+from api.state import state
+
+router = APIRouter(prefix="/search", tags=["search"])
+
+
+@router.get("/suggest", response_model=SuggestResponse)
+async def suggest(
+    q: str = Query(..., max_length=200, description="What was typed so far, matched from the start of any word of a term"),
+    limit: int = Query(default=10, ge=1, le=MAX_SUGGESTIONS, description="Maximum number of suggestions"),
+    kinds: Optional[str] = Query(default=None, description="Comma separated kinds to suggest, of title, heading and entity, all by default"),
+):
+    """Get completions of a search query from the titles, headings and entity names of the library"""
+    try:
+        if not state.suggestion_index:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        kind_list = None
+        if kinds:
+            kind_list = [kind.strip() for kind in kinds.split(",") if kind.strip()]
+            unknown = [kind for kind in kind_list if kind not in SUGGESTION_KINDS]
+            if unknown:
+                raise HTTPException(status_code=400, detail=f"Unknown suggestion kinds: {', '.join(unknown)}, expected {', '.join(SUGGESTION_KINDS)}")
+        suggestions = state.suggestion_index.suggest(q, limit, kind_list)
+        return SuggestResponse(query=q, suggestions=[SuggestionItem(**asdict(suggestion)) for suggestion in suggestions])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /search/suggest endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
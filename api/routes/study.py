@@ -0,0 +1,247 @@
+# Study routes
+# The study plan and digest of a user, course syllabi and the interleaved study queue.
+
+from datetime import date, datetime, timezone
+from typing import Optional, List
+
+from fastapi import APIRouter, HTTPException, Query, UploadFile, File, Form
+
+from textbook.database import SyllabusTopicInfo
+from textbook.preferences import load_preferences
+from textbook.reading_queue import study_queue, QUEUE_CARD
+from textbook.review import new_card_allowance
+from textbook.decay import FORGET_THRESHOLD, RefreshSuggestion
+from textbook.forecast import MAX_FORECAST_DAYS
+from textbook.planner import study_digest, study_plan
+from textbook.smart_filters import evaluate_filter, require_filter
+from textbook.syllabus import SyllabusFocus, import_syllabus, syllabus_text, upcoming_topics
+from textbook.capabilities import FEATURE_LLM
+from textbook.jobs import JOB_KIND_SYLLABUS_IMPORT, JobHandle
+
+from api.models import RefreshSuggestionItem, SyllabusTopicItem, SyllabusResponse, StudyPlanDayItem, StudyPlanResponse, StudyDigestResponse, SubmitJobResponse, StudyQueueEntry, StudyQueueResponse
+from api.state import PROFILE_DESCRIPTION, state, get_llm, require_feature, require_profile
+from api.items import chapter_quiz_item, reading_item, review_card_items
+
+router = APIRouter(tags=["study"])
+
+
+def _refresh_suggestion_item(suggestion: RefreshSuggestion) -> RefreshSuggestionItem:
+    return RefreshSuggestionItem(
+        book_id=suggestion.book_id,
+        chapter_id=suggestion.chapter_id,
+        title=suggestion.title,
+        mastery=suggestion.mastery,
+        projected_mastery=suggestion.projected_mastery,
+        forget_on=suggestion.forget_on,
+        refresh_on=suggestion.refresh_on,
+        message=suggestion.message,
+    )
+
+
+# Study planner endpoints
+@router.get("/users/{user_id}/study-plan", response_model=StudyPlanResponse)
+async def get_study_plan(
+    user_id: str,
+    days: int = Query(default=14, ge=1, le=MAX_FORECAST_DAYS, description="Number of days to plan"),
+    book_id: Optional[int] = Query(default=None, description="Only plan the cards and chapters of this book"),
+    threshold: float = Query(default=FORGET_THRESHOLD, gt=0, lt=1, description="Projected mastery below which a chapter is forgotten"),
+    filter_id: Optional[int] = Query(default=None, description="Smart filter of the user to focus on: only its book, and refreshing the chapters it matches exercises of"),
+):
+    """
+    Plan the coming days: the forecast reviews and new cards, and refresh sessions of chapters about to be forgotten
+
+    A chapter's mastery decays since its last attempt, slower the longer its review interval; a refresh is planned
+    the day before its projected mastery drops below the threshold.
+    """
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        chapter_ids = None
+        if filter_id is not None:
+            try:
+                smart_filter = require_filter(state.database, filter_id, user_id)
+            except LookupError as e:
+                raise HTTPException(status_code=404, detail=str(e))
+            if book_id is not None and book_id != smart_filter.book_id:
+                raise HTTPException(status_code=400, detail=f"Filter {filter_id} searches book {smart_filter.book_id}, not book {book_id}")
+            book_id = smart_filter.book_id
+            chapter_ids = {candidate.chapter_id for candidate in evaluate_filter(state.database, smart_filter) if candidate.chapter_id is not None}
+        plan = study_plan(state.database, load_preferences(state.database, user_id), days, book_id, threshold, chapter_ids=chapter_ids)
+        return StudyPlanResponse(
+            user_id=user_id,
+            threshold=threshold,
+            days=[
+                StudyPlanDayItem(
+                    day=day.day,
+                    reviews=day.reviews,
+                    new_cards=day.new_cards,
+                    refresh=[_refresh_suggestion_item(suggestion) for suggestion in day.refresh],
+                    syllabus=[_syllabus_topic_item(focus.topic, focus.mastery) for focus in day.syllabus],
+                )
+                for day in plan
+            ],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/study-plan endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/users/{user_id}/digest", response_model=StudyDigestResponse)
+async def get_study_digest(
+    user_id: str,
+    book_id: Optional[int] = Query(default=None, description="Only sum up this book"),
+):
+    """Sum up today: the reviews due, the chapter quizzes waiting and the chapters about to be forgotten this week"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        digest = study_digest(state.database, user_id, book_id)
+        return StudyDigestResponse(
+            user_id=user_id,
+            due_reviews=digest.due_reviews,
+            chapter_quizzes=[chapter_quiz_item(progress) for progress in digest.chapter_quizzes],
+            refresh=[_refresh_suggestion_item(suggestion) for suggestion in digest.refresh],
+            coming_up=[_syllabus_topic_item(focus.topic, focus.mastery) for focus in digest.coming_up],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/digest endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+# Syllabus endpoints
+def _syllabus_topic_item(topic: SyllabusTopicInfo, mastery: Optional[float] = None) -> SyllabusTopicItem:
+    return SyllabusTopicItem(
+        topic_id=topic.topic_id,
+        user_id=topic.user_id,
+        book_id=topic.book_id,
+        week=topic.week,
+        week_starts_at=topic.week_starts_at,
+        title=topic.title,
+        description=topic.description,
+        chapter_id=topic.chapter_id,
+        mastery=mastery,
+    )
+
+
+def import_syllabus_job(params: dict, handle: JobHandle) -> dict:
+    """Syllabus import job of a book, a single prompt parsing the syllabus into topics aligned to the chapters"""
+    topics = import_syllabus(
+        state.database,
+        get_llm(JOB_KIND_SYLLABUS_IMPORT, params.get("profile")),
+        params["user_id"],
+        params["book_id"],
+        params["text"],
+        date.fromisoformat(params["starts_on"]),
+        cancellation_token=handle.cancellation_token,
+        report_progress=handle.report_progress,
+    )
+    return {"book_id": params["book_id"], "topic_ids": [topic.topic_id for topic in topics]}
+
+
+@router.post("/books/{book_id}/syllabus", response_model=SubmitJobResponse)
+async def post_book_syllabus(
+    book_id: int,
+    user_id: str = Form(..., description="The user following the course"),
+    text: Optional[str] = Form(default=None, description="The syllabus, pasted"),
+    file: Optional[UploadFile] = File(default=None, description="The syllabus as a PDF, text or Markdown file, instead of text"),
+    starts_on: Optional[date] = Form(default=None, description="The first day of week 1, by default today"),
+    profile: Optional[str] = Query(default=None, description=PROFILE_DESCRIPTION),
+):
+    """
+    Start a job importing a course syllabus: its topics by week, aligned to the chapters of the book
+
+    The study plan and digest then put first what the course covers this week and next. Importing again replaces the
+    user's syllabus of the book.
+    """
+    try:
+        require_feature(FEATURE_LLM)
+        require_profile(profile)
+        if not state.job_pool or not state.database:
+            raise HTTPException(status_code=500, detail="Job pool not initialized")
+        if state.database.get_book_by_id(book_id) is None:
+            raise HTTPException(status_code=404, detail=f"Book not found: {book_id}")
+        if (text is None) == (file is None):
+            raise HTTPException(status_code=400, detail="Send the syllabus either as text or as a file")
+        if file is not None:
+            try:
+                text = syllabus_text(file.filename or "", await file.read())
+            except ValueError as e:
+                raise HTTPException(status_code=400, detail=str(e))
+        if not text or not text.strip():
+            raise HTTPException(status_code=400, detail="The syllabus is empty")
+
+        starts_on = starts_on or datetime.now(timezone.utc).date()
+        job = state.job_pool.submit(JOB_KIND_SYLLABUS_IMPORT, {"user_id": user_id, "book_id": book_id, "text": text, "starts_on": starts_on.isoformat(), "profile": profile})
+        return SubmitJobResponse(job_id=job.job_id, kind=job.kind, status=job.status, message="Syllabus import job submitted")
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /books/{book_id}/syllabus endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/users/{user_id}/syllabus", response_model=SyllabusResponse)
+async def get_user_syllabus(
+    user_id: str,
+    book_id: Optional[int] = Query(default=None, description="Only the syllabus of this book"),
+    upcoming: bool = Query(default=False, description="Only the topics of this week and next, weakest chapter first, with their mastery"),
+):
+    """List the syllabus topics of a user by week, with the chapter each is aligned to"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        if upcoming:
+            focus: List[SyllabusFocus] = upcoming_topics(state.database, user_id, book_id)
+            return SyllabusResponse(user_id=user_id, topics=[_syllabus_topic_item(item.topic, item.mastery) for item in focus])
+        return SyllabusResponse(user_id=user_id, topics=[_syllabus_topic_item(topic) for topic in state.database.get_syllabus_topics(user_id, book_id)])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/syllabus endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/study/queue", response_model=StudyQueueResponse)
+async def get_study_queue(
+    limit: int = Query(default=20, ge=1, le=100, description="Number of entries to return"),
+    user_id: str = Query(default="default", description="User to study for"),
+    book_id: Optional[int] = Query(default=None, description="Only study the cards and reading items of this book"),
+):
+    """Get the next review cards and due reading items, intermixed with a reading item after every few cards"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+
+        now = datetime.now(timezone.utc)
+        preferences = load_preferences(state.database, user_id)
+        queue = study_queue(state.database, user_id, limit, preferences.daily_new_card_limit, book_id, now)
+        cards = iter(review_card_items([entry for kind, entry in queue if kind == QUEUE_CARD], now))
+        entries = [
+            StudyQueueEntry(kind=kind, card=next(cards)) if kind == QUEUE_CARD else StudyQueueEntry(kind=kind, reading=reading_item(entry))
+            for kind, entry in queue
+        ]
+        return StudyQueueResponse(
+            user_id=user_id,
+            due_count=state.database.count_due_cards(user_id, now, book_id),
+            new_remaining=new_card_allowance(state.database, user_id, preferences.daily_new_card_limit, now),
+            entries=entries,
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /study/queue endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
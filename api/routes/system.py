@@ -0,0 +1,77 @@
+# System routes
+# Health, readiness after the startup warmup, the optional subsystems enabled and the LLM profiles of config.toml.
+
+import asyncio
+from dataclasses import asdict
+
+from fastapi import APIRouter, HTTPException
+from fastapi.responses import JSONResponse
+
+from textbook.profiles import TASKS
+from textbook.capabilities import FEATURE_LLM
+from textbook.health import server_status
+
+from api.models import LlmProfileItem, LlmProfilesResponse, CapabilityItem, CapabilitiesResponse, PrimingStepItem, ReadinessResponse
+from api.state import state, require_feature
+
+router = APIRouter(tags=["system"])
+
+
+@router.get("/health")
+async def health():
+    """Health check endpoint, with the status of the OCR backend (MinerU by default) and the LLM provider"""
+    if state.struct_logger:
+        state.struct_logger.info("Health check endpoint called")
+
+    # Pinging MinerU blocks for up to its timeout
+    dependencies = await asyncio.to_thread(state.health_probe.check, state.llm) if state.health_probe else []
+    return {
+        "status": server_status(dependencies),
+        "llm_initialized": state.llm is not None,
+        "context_initialized": state.database is not None,
+        "ready": state.warmup is not None and state.warmup.ready,
+        "dependencies": [asdict(dependency) for dependency in dependencies],
+    }
+
+
+@router.get("/ready", response_model=ReadinessResponse)
+async def ready():
+    """Readiness check: 503 until the caches and models were primed on startup, then 200"""
+    reports = state.warmup.reports() if state.warmup else []
+    response = ReadinessResponse(
+        ready=state.warmup is not None and state.warmup.ready,
+        steps=[PrimingStepItem(name=report.name, primed=report.primed, seconds=report.seconds, error=report.error) for report in reports],
+    )
+    if not response.ready:
+        return JSONResponse(status_code=503, content=response.model_dump())
+    return response
+
+
+@router.get("/capabilities", response_model=CapabilitiesResponse)
+async def get_capabilities():
+    """Which optional subsystems are enabled, and how to enable the others; their endpoints answer 501 while disabled"""
+    return CapabilitiesResponse(capabilities=[
+        CapabilityItem(name=capability.name, enabled=capability.enabled, guidance=capability.guidance)
+        for capability in state.capabilities.values()
+    ])
+
+
+@router.get("/llm/profiles", response_model=LlmProfilesResponse)
+async def get_llm_profiles():
+    """List the LLM profiles of config.toml with their model and the tasks routed to them; requests starting a task can pick one with `profile`"""
+    try:
+        require_feature(FEATURE_LLM)
+        if not state.model_router:
+            raise HTTPException(status_code=500, detail="LLM not initialized")
+        router = state.model_router
+        return LlmProfilesResponse(profiles=[
+            LlmProfileItem(name=name, model_name=model.model_name, tasks=[task for task in TASKS if router.profile_for(task) == name])
+            for name, model in router.models.items()
+        ])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /llm/profiles endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,64 @@
+# Usage routes
+# The tokens the LLM calls used and their estimated cost, per day, document, model and job.
+
+from datetime import datetime, timedelta, timezone
+from typing import Optional
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.usage import UsageTotals, usage_summary
+
+from api.models import UsageDayItem, UsageDocumentItem, UsageJobItem, UsageModelItem, UsageResponse, UsageTotalsItem
+from api.state import state
+
+router = APIRouter(prefix="/usage", tags=["usage"])
+
+MAX_USAGE_DAYS = 365
+
+
+def _totals_item(totals: UsageTotals) -> UsageTotalsItem:
+    return UsageTotalsItem(
+        calls=totals.calls,
+        input_tokens=totals.input_tokens,
+        output_tokens=totals.output_tokens,
+        cost=round(totals.cost, 6),
+        unpriced_calls=totals.unpriced_calls,
+        estimated_calls=totals.estimated_calls,
+    )
+
+
+@router.get("", response_model=UsageResponse)
+async def get_usage(
+    days: int = Query(default=30, ge=1, le=MAX_USAGE_DAYS, description="Days back to sum up, today included"),
+    document_id: Optional[int] = Query(default=None, description="Only the calls of jobs working on this document"),
+    job_id: Optional[str] = Query(default=None, description="Only the calls of this job"),
+    job_limit: int = Query(default=50, ge=1, le=500, description="Maximum number of jobs listed, the most expensive first"),
+):
+    """Get the tokens and estimated cost of the LLM calls, in total and per day, document, model and job"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        today = datetime.now(timezone.utc).replace(hour=0, minute=0, second=0, microsecond=0)
+        summary = usage_summary(state.database, today - timedelta(days=days - 1), document_id, job_id)
+        documents = []
+        for usage_document_id, totals in sorted(summary.documents.items(), key=lambda item: -item[1].cost):
+            document = state.database.get_document(usage_document_id)
+            documents.append(UsageDocumentItem(document_id=usage_document_id, title=document.title if document else None, usage=_totals_item(totals)))
+        return UsageResponse(
+            since=summary.since,
+            totals=_totals_item(summary.totals),
+            days=[UsageDayItem(day=day, usage=_totals_item(totals)) for day, totals in summary.days.items()],
+            documents=documents,
+            models=[UsageModelItem(model_name=model_name, usage=_totals_item(totals)) for model_name, totals in summary.models.items()],
+            jobs=[
+                UsageJobItem(job_id=job.job_id, job_kind=job.job_kind, document_id=job.document_id, usage=_totals_item(job.totals))
+                for job in summary.jobs[:job_limit]
+            ],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /usage endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,124 @@
+# User routes
+# Study preferences and scoped API tokens of users.
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.database import ApiTokenInfo
+from textbook.preferences import load_preferences, update_preferences, Preferences
+from textbook.api_tokens import create_token, token_scopes
+
+from api.models import UpdatePreferencesRequest, PreferencesResponse, CreateApiTokenRequest, ApiTokenItem, CreateApiTokenResponse, ApiTokensResponse
+from api.state import state
+
+router = APIRouter(tags=["users"])
+
+
+def _preferences_response(preferences: Preferences) -> PreferencesResponse:
+    return PreferencesResponse(**vars(preferences))
+
+
+# Preferences endpoints
+@router.get("/users/{user_id}/preferences", response_model=PreferencesResponse)
+async def get_preferences(user_id: str):
+    """Get the study preferences of a user, the defaults if none were saved"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        return _preferences_response(load_preferences(state.database, user_id))
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/preferences endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/users/{user_id}/preferences", response_model=PreferencesResponse)
+async def put_preferences(user_id: str, request: UpdatePreferencesRequest):
+    """Change the study preferences of a user, fields left out keep their value"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        # Only the output language and notification hour can be reset to null
+        changes = {name: value for name, value in request.model_dump(exclude_unset=True).items() if value is not None or name in ("output_language", "notification_hour")}
+        return _preferences_response(update_preferences(state.database, user_id, **changes))
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/preferences endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+def _api_token_item(token: ApiTokenInfo) -> ApiTokenItem:
+    return ApiTokenItem(
+        token_id=token.token_id,
+        user_id=token.user_id,
+        name=token.name,
+        scopes=token_scopes(token),
+        created_at=token.created_at,
+        last_used_at=token.last_used_at,
+        revoked_at=token.revoked_at,
+    )
+
+
+# API token endpoints
+@router.post("/users/{user_id}/api-tokens", response_model=CreateApiTokenResponse)
+async def create_api_token(user_id: str, request: CreateApiTokenRequest):
+    """Create a scoped API token acting for a user, the token is only returned this once"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        info, token = create_token(state.database, user_id, request.name, request.scopes)
+        return CreateApiTokenResponse(token=token, info=_api_token_item(info))
+    except HTTPException:
+        raise
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/api-tokens endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/users/{user_id}/api-tokens", response_model=ApiTokensResponse)
+async def get_api_tokens(
+    user_id: str,
+    include_revoked: bool = Query(default=False, description="Whether to list revoked tokens too"),
+):
+    """List the API tokens of a user, without the tokens themselves"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        return ApiTokensResponse(user_id=user_id, tokens=[_api_token_item(token) for token in state.database.get_api_tokens(user_id, include_revoked)])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /users/{user_id}/api-tokens endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.delete("/api-tokens/{token_id}", response_model=ApiTokenItem)
+async def revoke_api_token(token_id: int):
+    """Revoke an API token, requests using it are rejected from then on"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        token = state.database.revoke_api_token(token_id)
+        if token is None:
+            raise HTTPException(status_code=404, detail=f"API token not found: {token_id}")
+        return _api_token_item(token)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /api-tokens/{token_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,264 @@
+# Workspace routes
+# Instructor mode: workspaces with a roster of instructors and students, readings and quizzes assigned to the
+# students, and how the class does per topic. Requests name the user making them in user_id, which API tokens without
+# the admin scope fill in with their own user; only instructors manage the roster and assignments and see the
+# analytics, and a student's own activity only when the student shares it.
+
+from typing import Optional
+
+from fastapi import APIRouter, HTTPException, Query
+
+from textbook.database import AssignmentInfo, WorkspaceMemberInfo
+from textbook.workspaces import MIN_GROUP_SIZE, ROLE_INSTRUCTOR, add_member, assign, create_workspace, require_member, student_activity, topic_analytics
+
+from api.models import (
+    AddMemberRequest,
+    AssignmentItem,
+    AssignmentsResponse,
+    ChapterReviewGradesItem,
+    CreateAssignmentRequest,
+    CreateWorkspaceRequest,
+    RosterResponse,
+    StudentActivityResponse,
+    TopicAnalyticsItem,
+    UpdateSharingRequest,
+    WorkspaceAnalyticsResponse,
+    WorkspaceItem,
+    WorkspaceMemberItem,
+    WorkspacesResponse,
+)
+from api.items import chapter_quiz_item, reading_item
+from api.state import state
+
+router = APIRouter(prefix="/workspaces", tags=["workspaces"])
+
+USER_DESCRIPTION = "User making the request"
+
+
+def _member_item(member: WorkspaceMemberInfo) -> WorkspaceMemberItem:
+    return WorkspaceMemberItem(user_id=member.user_id, role=member.role, share_attempts=member.share_attempts, joined_at=member.joined_at)
+
+
+def _assignment_item(assignment: AssignmentInfo) -> AssignmentItem:
+    return AssignmentItem(
+        assignment_id=assignment.assignment_id,
+        workspace_id=assignment.workspace_id,
+        kind=assignment.assignment_kind,
+        book_id=assignment.book_id,
+        chapter_id=assignment.chapter_id,
+        due_at=assignment.due_at,
+        created_at=assignment.created_at,
+    )
+
+
+def _require(workspace_id: int, user_id: str, role: Optional[str] = None) -> WorkspaceMemberInfo:
+    """The membership of the user making a request, 404 for unknown workspaces and 403 for users without the role"""
+    if not state.database:
+        raise HTTPException(status_code=500, detail="Context not initialized")
+    try:
+        return require_member(state.database, workspace_id, user_id, role)
+    except ValueError as e:
+        raise HTTPException(status_code=404, detail=str(e))
+    except PermissionError as e:
+        raise HTTPException(status_code=403, detail=str(e))
+
+
+@router.post("", response_model=WorkspaceItem)
+async def post_workspace(request: CreateWorkspaceRequest, user_id: str = Query(..., description="The instructor creating the workspace")):
+    """Create a workspace with the user as its instructor"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        try:
+            workspace = create_workspace(state.database, request.name, user_id)
+        except ValueError as e:
+            raise HTTPException(status_code=400, detail=str(e))
+        return WorkspaceItem(workspace_id=workspace.workspace_id, name=workspace.name, role=ROLE_INSTRUCTOR, created_at=workspace.created_at)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /workspaces endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("", response_model=WorkspacesResponse)
+async def get_workspaces(user_id: str = Query(..., description=USER_DESCRIPTION)):
+    """List the workspaces the user is on the roster of, with their role"""
+    try:
+        if not state.database:
+            raise HTTPException(status_code=500, detail="Context not initialized")
+        workspaces = []
+        for workspace in state.database.get_workspaces(user_id):
+            member = state.database.get_workspace_member(workspace.workspace_id, user_id)
+            workspaces.append(WorkspaceItem(workspace_id=workspace.workspace_id, name=workspace.name, role=member.role, created_at=workspace.created_at))
+        return WorkspacesResponse(user_id=user_id, workspaces=workspaces)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /workspaces endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{workspace_id}/members", response_model=RosterResponse)
+async def get_workspace_members(workspace_id: int, user_id: str = Query(..., description=USER_DESCRIPTION)):
+    """List the roster of a workspace, for its instructors"""
+    try:
+        _require(workspace_id, user_id, ROLE_INSTRUCTOR)
+        return RosterResponse(workspace_id=workspace_id, members=[_member_item(member) for member in state.database.get_workspace_members(workspace_id)])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /workspaces/{workspace_id}/members endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{workspace_id}/members", response_model=WorkspaceMemberItem)
+async def post_workspace_member(workspace_id: int, request: AddMemberRequest, user_id: str = Query(..., description=USER_DESCRIPTION)):
+    """Add a user to the roster; students get the readings and quizzes assigned so far"""
+    try:
+        _require(workspace_id, user_id, ROLE_INSTRUCTOR)
+        try:
+            member = add_member(state.database, workspace_id, request.member_id, request.role)
+        except ValueError as e:
+            raise HTTPException(status_code=400, detail=str(e))
+        return _member_item(member)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /workspaces/{workspace_id}/members endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.delete("/{workspace_id}/members/{member_id}", response_model=RosterResponse)
+async def delete_workspace_member(workspace_id: int, member_id: str, user_id: str = Query(..., description=USER_DESCRIPTION)):
+    """Remove a user from the roster, what was delivered to them stays in their queues"""
+    try:
+        _require(workspace_id, user_id, ROLE_INSTRUCTOR)
+        if not state.database.remove_workspace_member(workspace_id, member_id):
+            raise HTTPException(status_code=404, detail=f"{member_id} is not on the roster of workspace {workspace_id}")
+        return RosterResponse(workspace_id=workspace_id, members=[_member_item(member) for member in state.database.get_workspace_members(workspace_id)])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /workspaces/{workspace_id}/members/{member_id} endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.put("/{workspace_id}/sharing", response_model=WorkspaceMemberItem)
+async def put_workspace_sharing(workspace_id: int, request: UpdateSharingRequest, user_id: str = Query(..., description="The student sharing their attempts or not")):
+    """Let the instructors see the user's own readings, quizzes and reviews, or stop letting them"""
+    try:
+        _require(workspace_id, user_id)
+        member = state.database.update_workspace_member(workspace_id, user_id, share_attempts=request.share_attempts)
+        return _member_item(member)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /workspaces/{workspace_id}/sharing endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.post("/{workspace_id}/assignments", response_model=AssignmentItem)
+async def post_workspace_assignment(workspace_id: int, request: CreateAssignmentRequest, user_id: str = Query(..., description=USER_DESCRIPTION)):
+    """Assign a reading (a chapter or a whole book) or a chapter quiz, delivered to every student of the roster"""
+    try:
+        _require(workspace_id, user_id, ROLE_INSTRUCTOR)
+        try:
+            assignment = assign(state.database, workspace_id, request.kind, request.book_id, request.chapter_id, request.due_at, request.quiz_size)
+        except ValueError as e:
+            raise HTTPException(status_code=400, detail=str(e))
+        return _assignment_item(assignment)
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /workspaces/{workspace_id}/assignments endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{workspace_id}/assignments", response_model=AssignmentsResponse)
+async def get_workspace_assignments(workspace_id: int, user_id: str = Query(..., description=USER_DESCRIPTION)):
+    """List the assignments of a workspace, newest first, for its members"""
+    try:
+        _require(workspace_id, user_id)
+        return AssignmentsResponse(workspace_id=workspace_id, assignments=[_assignment_item(assignment) for assignment in state.database.get_assignments(workspace_id)])
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /workspaces/{workspace_id}/assignments endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{workspace_id}/analytics", response_model=WorkspaceAnalyticsResponse)
+async def get_workspace_analytics(workspace_id: int, user_id: str = Query(..., description=USER_DESCRIPTION)):
+    """
+    How the students do per chapter assigned, anonymized: counts of students who read it and took its quiz, and
+    the mean quiz score and flashcard retention, withheld when fewer than min_group_size students contribute
+    """
+    try:
+        _require(workspace_id, user_id, ROLE_INSTRUCTOR)
+        return WorkspaceAnalyticsResponse(
+            workspace_id=workspace_id,
+            min_group_size=MIN_GROUP_SIZE,
+            topics=[
+                TopicAnalyticsItem(
+                    book_id=topic.book_id,
+                    chapter_id=topic.chapter_id,
+                    title=topic.title,
+                    students=topic.students,
+                    read=topic.read,
+                    quizzes_completed=topic.quizzes_completed,
+                    quiz_score=topic.quiz_score,
+                    reviewers=topic.reviewers,
+                    retention=topic.retention,
+                )
+                for topic in topic_analytics(state.database, workspace_id)
+            ],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /workspaces/{workspace_id}/analytics endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
+
+
+@router.get("/{workspace_id}/students/{student_id}/activity", response_model=StudentActivityResponse)
+async def get_student_activity(workspace_id: int, student_id: str, user_id: str = Query(..., description=USER_DESCRIPTION)):
+    """A student's readings, quizzes and review grades of the books assigned, for instructors when the student shares them"""
+    try:
+        _require(workspace_id, user_id, ROLE_INSTRUCTOR)
+        try:
+            activity = student_activity(state.database, workspace_id, student_id)
+        except PermissionError as e:
+            raise HTTPException(status_code=403, detail=str(e))
+        return StudentActivityResponse(
+            workspace_id=workspace_id,
+            user_id=student_id,
+            readings=[reading_item(item) for item in activity.readings],
+            quizzes=[chapter_quiz_item(progress) for progress in activity.quizzes],
+            reviews=[ChapterReviewGradesItem(chapter_id=chapter_id, grades=grades) for chapter_id, grades in activity.review_grades.items()],
+        )
+    except HTTPException:
+        raise
+    except Exception as e:
+        import traceback
+        error_trace = traceback.format_exc()
+        print(f"Error in /workspaces/{workspace_id}/students/{student_id}/activity endpoint: {error_trace}")
+        raise HTTPException(status_code=500, detail=f"Internal server error: {str(e)}")
@@ -0,0 +1,155 @@
+# Application state
+# Everything the routes share: the database, the models, the job pool and the configuration. The lifespan of the
+# app in api/app.py fills in the state on startup, the route modules in api/routes read it from the `state` instance.
+# The helpers opening a book's reader from the state live here too.
+
+import os
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import Dict, Optional
+
+import structlog
+from fastapi import HTTPException
+
+from textbook import LazyTextbookReader, LLM, TextBookDatabase
+from textbook.capabilities import FEATURE_LLM, Capability
+from textbook.chapter_quiz import ChapterQuizSettings
+from textbook.database import BookInfo
+from textbook.external_grading import GradingServices
+from textbook.ingestion_settings import IngestionSettings
+from textbook.images import ImageCache
+from textbook.jobs import JobPool
+from textbook.ocr import DEFAULT_PAGES_PER_CHUNK, MinerUBackend, OcrBackend
+from textbook.health import HealthProbe
+from textbook.pipeline import PipelineRunner
+from textbook.prefetch import Prefetcher
+from textbook.problem_quality import ProblemQualitySettings
+from textbook.profiles import ModelRouter
+from textbook.provider_errors import ERROR_AUTH, ModelError
+from textbook.resources import OutOfSpaceError, ResourceMonitor
+from textbook.prompt_log import PromptLog
+from textbook.reader import BOOK_SOURCE_TRANSCRIPT
+from textbook.response_cache import ResponseCache
+from textbook.suggest import SuggestionIndex
+from textbook.warmup import Warmup
+from textbook.watermark import WatermarkSettings
+from textbook.usage import UsageTracker
+
+
+@dataclass
+class AppState:
+    struct_logger: Optional[structlog.BoundLogger] = None
+    llm: Optional[LLM] = None
+    escalation_llm: Optional[LLM] = None # Stronger model for low confidence and disputed gradings, if configured
+    model_router: Optional[ModelRouter] = None # The models of the profiles in config.toml and the tasks routed to them
+    database: Optional[TextBookDatabase] = None
+    db_path: str = "textbook_context.db"
+    uploads_dir: str = "uploads"
+    preprocess_scanned_pages: bool = True
+    require_api_tokens: bool = False # Reject requests without an API token, except the health checks
+    job_pool: Optional[JobPool] = None
+    pipeline_runner: Optional[PipelineRunner] = None # Starts the jobs of pipelines as the jobs they depend on succeed
+    prompt_log: Optional[PromptLog] = None # Prompts and responses of the LLMs, recorded when enabled in config.toml
+    usage_tracker: Optional[UsageTracker] = None # Tokens and estimated cost of every LLM call
+    response_cache: Optional[ResponseCache] = None # Validated LLM responses, answering identical requests without the provider
+    prefetcher: Optional[Prefetcher] = None # Pre-generates what readers will likely want next, in low priority jobs
+    ingestion_settings: IngestionSettings = field(default_factory=IngestionSettings) # Of config.toml, uploads can override them
+    chapter_quiz_settings: ChapterQuizSettings = field(default_factory=ChapterQuizSettings) # The quizzes given after reading a chapter
+    problem_quality_settings: ProblemQualitySettings = field(default_factory=ProblemQualitySettings) # The checks of extracted problems
+    watermark_settings: WatermarkSettings = field(default_factory=WatermarkSettings) # Fingerprints of exports, off by default
+    resource_monitor: Optional[ResourceMonitor] = None # Watches free disk space and memory, pausing OCR jobs under pressure
+    capabilities: Dict[str, Capability] = field(default_factory=dict) # Optional subsystems enabled, by feature name
+    warmup: Optional[Warmup] = None # Primes caches and models on startup, the server is ready once it finished
+    image_cache: Optional[ImageCache] = None # Size and format variants of page and figure images, rendered once
+    ocr_backend: OcrBackend = field(default_factory=MinerUBackend) # OCR of uploads and pages without a text layer, from [ocr] in config.toml
+    ocr_text_layer: bool = True # Read the pages of uploads with a text layer from it, OCRing only the others
+    ocr_pages_per_chunk: int = DEFAULT_PAGES_PER_CHUNK # OCR jobs send the pages in chunks, resuming after the last one read
+    suggestion_index: Optional[SuggestionIndex] = None # Completions of search queries, from the titles, headings and entity names
+    health_probe: Optional[HealthProbe] = None # Pings the OCR backend and judges the LLM provider for GET /health
+    grading_services: Optional[GradingServices] = None # External graders of attempts by exercise type, from [grading_services] in config.toml
+
+
+# Initialized on startup
+state = AppState()
+
+
+def get_pdf_path_from_book_id(book_id: int) -> Path:
+    """Helper function to get pdf_path from book_id"""
+    if not state.database:
+        raise HTTPException(status_code=500, detail="Context not initialized")
+    
+    with state.database.new_session() as session:
+        book = session.query(BookInfo).filter(BookInfo.book_id == book_id).first()
+        if not book or not book.book_file_name:
+            raise HTTPException(status_code=404, detail=f"PDF file not found for book, consider uploading the book first: {book_id}")
+        return Path(state.uploads_dir) / Path(book.book_file_name + ".pdf") 
+
+
+def require_disk_space():
+    """Fail an upload with 507 while the uploads disk is below its free space threshold, before anything is written"""
+    if not state.resource_monitor:
+        return
+    try:
+        state.resource_monitor.require_space()
+    except OutOfSpaceError as e:
+        raise HTTPException(status_code=507, detail={"code": "out_of_space", "message": str(e)})
+
+
+def require_feature(name: str):
+    """Answer 501 with how to enable it when an optional subsystem is disabled"""
+    capability = state.capabilities.get(name)
+    if capability is not None and not capability.enabled:
+        raise HTTPException(status_code=501, detail={"code": "feature_disabled", "feature": name, "message": capability.guidance})
+
+
+def get_llm(task: Optional[str] = None, profile: Optional[str] = None) -> LLM:
+    """
+    The LLM of a task: of the profile asked for, or of the one the task is routed to in config.toml
+
+    Without an API key jobs fail as an auth error telling to set one, with an unknown profile they fail with ValueError.
+    """
+    if not state.llm:
+        raise ModelError(ERROR_AUTH, "No API key is set for the LLM provider")
+    if not state.model_router:
+        return state.llm
+    return state.model_router.model_for(task, profile)
+
+
+PROFILE_DESCRIPTION = "LLM profile of config.toml to run the task with, by default the profile the task is routed to"
+FORCE_REFRESH_DESCRIPTION = "Prompt the LLM again instead of answering identical prompts from the response cache"
+
+
+def require_profile(profile: Optional[str]):
+    """Fail a request picking a profile that is not configured with 400, before its job is submitted"""
+    try:
+        if state.model_router:
+            state.model_router.check(profile)
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail={"code": "unknown_profile", "message": str(e)})
+
+
+def get_reader(pdf_path: Path, ingestion_mode: Optional[str] = None) -> LazyTextbookReader:
+    """Helper function to create and enter a LazyTextbookReader context"""
+    require_feature(FEATURE_LLM)
+    if not state.llm or not state.database:
+        raise HTTPException(status_code=500, detail="LLM or Context not initialized")
+    
+    if not os.path.exists(pdf_path):
+        raise HTTPException(status_code=404, detail=f"PDF file not found: {pdf_path}")
+    
+    reader = LazyTextbookReader(pdf_path, state.llm, state.database, preprocess_scanned_pages=state.preprocess_scanned_pages, ingestion_mode=ingestion_mode, ocr_backend=state.ocr_backend)
+    return reader.__enter__()
+
+
+def get_reader_by_book_id(book_id: int) -> LazyTextbookReader:
+    """Helper function to create and enter a LazyTextbookReader context using book_id"""
+    pdf_path = get_pdf_path_from_book_id(book_id)
+    return get_reader(pdf_path)
+
+
+def get_video_url(session, book_id: int) -> Optional[str]:
+    """Helper function to get the video URL of a transcript book, used for timestamp deep links"""
+    book = session.query(BookInfo).filter(BookInfo.book_id == book_id).first()
+    if not book or book.book_source_type != BOOK_SOURCE_TRANSCRIPT:
+        return None
+    return book.book_source_url
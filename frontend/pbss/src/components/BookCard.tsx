@@ -1,6 +1,7 @@
 import type { Book } from '../types/api';
 import { Card } from './Card';
 import { Button } from './Button';
+import { bookApi } from '../services/api';
 
 interface BookCardProps {
   book: Book;
@@ -15,6 +16,14 @@ export function BookCard({ book, onView, onEdit, onDelete }: BookCardProps) {
   return (
     <Card className="flex flex-col h-full">
       <div className="flex flex-col gap-4 flex-1">
+        {/* Cover, the first page as a small WebP thumbnail */}
+        <img
+          src={bookApi.getPageImageUrl(book.book_id, 0, 72, 'thumb', 'webp')}
+          alt={displayName}
+          loading="lazy"
+          className="w-full h-40 object-contain bg-background-subtle rounded"
+        />
+
         {/* Book Title */}
         <div>
           <h4 className="m-0 text-text-primary text-lg font-semibold break-words">{displayName}</h4>
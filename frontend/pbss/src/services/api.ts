@@ -4,6 +4,8 @@
 import type {
   PageNumberRequest,
   PageImageRequest,
+  ImageSize,
+  ImageFormat,
   UpdateBookInfoRequest,
   UpdateTocRequest,
   UpdateAlignmentOffsetRequest,
@@ -148,12 +150,16 @@ export const bookApi = {
   },
 
   // Get image of a page (as binary URL)
-  getPageImageUrl: (book_id: number, page_number: number, dpi: number = 150): string => {
+  getPageImageUrl: (book_id: number, page_number: number, dpi: number = 150, size: ImageSize = 'full', format?: ImageFormat): string => {
     const params = new URLSearchParams({
       book_id: book_id.toString(),
       page_number: page_number.toString(),
       dpi: dpi.toString(),
+      size,
     });
+    if (format) {
+      params.set('format', format);
+    }
     const baseUrl = API_BASE_URL || '';
     return `${baseUrl}/page-image-binary?${params.toString()}`;
   },
@@ -13,6 +13,10 @@ export interface PageImageRequest extends PageNumberRequest {
   dpi?: number;
 }
 
+// Size variants and formats of /page-image-binary, thumbnails are 160 pixels wide
+export type ImageSize = 'thumb' | 'small' | 'medium' | 'full';
+export type ImageFormat = 'png' | 'webp' | 'avif';
+
 export interface UpdateBookInfoRequest {
   book_id: number;
   overwrite?: boolean;
@@ -0,0 +1,3 @@
+from .client import ApiClient, ApiError
+
+__all__ = ["ApiClient", "ApiError"]
@@ -0,0 +1,135 @@
+# Terminal study client, run with `uv run python -m lazyreader <command>` against a running server (see run.sh)
+#
+#   review                        review the due flashcards
+#   quiz --book 1 --chapter 3     answer a quiz on a chapter, graded by the server
+#   ingest book.pdf               upload a textbook
+#   cancel <job_id>               cancel a background job, e.g. an OCR or export job
+#
+# The server URL, API token and user default to the LAZYREADER_URL, LAZYREADER_TOKEN and LAZYREADER_USER environment
+# variables. With --local (or LAZYREADER_LOCAL=1) no server is needed: the client works on a database in the user's
+# data directory, see lazyreader/local.py.
+
+import argparse
+import os
+import sys
+from pathlib import Path
+
+from lazyreader.client import DEFAULT_BASE_URL, ApiClient, ApiError
+from lazyreader.review import run_review
+
+
+def review(client, limit: int, book_id) -> int:
+    batch = client.review_batch(limit, book_id)
+    if not batch["cards"]:
+        print("No cards due")
+        return 0
+    run_review(batch, lambda review: client.submit_reviews([review]))
+    return 0
+
+
+def chapter_id(client, book_id: int, chapter_number: int) -> int:
+    # Chapters are numbered by their position in the book, the server knows them by their ID
+    chapters = client.chapters(book_id)["chapters"]
+    if not 1 <= chapter_number <= len(chapters):
+        raise ApiError(404, f"Chapter {chapter_number} not found in book {book_id}, it has {len(chapters)} chapters")
+    return chapters[chapter_number - 1]["chapter_id"]
+
+
+def quiz(client, book_id: int, chapter_number: int, size: int) -> int:
+    items = client.quiz(book_id, chapter_id(client, book_id, chapter_number), size)["items"]
+    if not items:
+        print("No problems to quiz on in this chapter")
+        return 0
+    scores = []
+    for number, item in enumerate(items, start=1):
+        exercise = item["exercise"]
+        print(f"\nProblem {number} of {len(items)} ({item['slot']})")
+        print(exercise["description"])
+        if exercise.get("starter_code"):
+            print(exercise["starter_code"])
+        answer = input("Answer (empty to skip): ").strip()
+        if not answer:
+            continue
+        attempt = client.submit_attempt(exercise["exercise_id"], answer)
+        if attempt.get("score") is not None:
+            scores.append(attempt["score"])
+        verdict = "correct" if attempt.get("is_correct") else "incorrect"
+        print(f"{verdict} ({attempt.get('score')}): {attempt.get('feedback') or ''}")
+    if scores:
+        print(f"\nMean score {sum(scores) / len(scores):.2f} over {len(scores)} answers")
+    return 0
+
+
+def ingest(client, pdf_path: Path, password, ingestion_mode) -> int:
+    if not pdf_path.is_file():
+        print(f"No such file: {pdf_path}")
+        return 1
+    response = client.ingest(pdf_path, password, ingestion_mode)
+    print(f"{response['message']} (book {response['book_id']})")
+    return 0
+
+
+def cancel(client, job_id: str) -> int:
+    job = client.cancel_job(job_id)
+    print(f"Job {job['job_id']} ({job['kind']}) is {job['status']}")
+    return 0
+
+
+def main(argv=None) -> int:
+    parser = argparse.ArgumentParser(prog="lazyreader", description="Terminal study client")
+    parser.add_argument("--url", default=os.environ.get("LAZYREADER_URL", DEFAULT_BASE_URL), help="Base URL of the server")
+    parser.add_argument("--token", default=os.environ.get("LAZYREADER_TOKEN"), help="API token to authenticate with")
+    parser.add_argument("--user", default=os.environ.get("LAZYREADER_USER", "default"), help="User to study as")
+    parser.add_argument("--local", action="store_true", default=os.environ.get("LAZYREADER_LOCAL") == "1", help="Work on a local database instead of a server")
+    parser.add_argument("--data-dir", type=Path, default=None, help="Directory of the local database, defaults to the user's data directory")
+    subparsers = parser.add_subparsers(dest="command", required=True)
+
+    review_parser = subparsers.add_parser("review", help="Review the due flashcards")
+    review_parser.add_argument("--limit", type=int, default=20, help="Number of cards to review")
+    review_parser.add_argument("--book", type=int, default=None, help="Only review the cards of this book")
+
+    quiz_parser = subparsers.add_parser("quiz", help="Answer a quiz on a chapter")
+    quiz_parser.add_argument("--book", type=int, required=True, help="Book the chapter belongs to")
+    quiz_parser.add_argument("--chapter", type=int, required=True, help="Number of the chapter to quiz on, starting at 1")
+    quiz_parser.add_argument("--size", type=int, default=10, help="Number of problems")
+
+    ingest_parser = subparsers.add_parser("ingest", help="Upload a textbook")
+    ingest_parser.add_argument("pdf_path", type=Path, help="PDF file to upload")
+    ingest_parser.add_argument("--password", default=None, help="Password for encrypted PDFs")
+    ingest_parser.add_argument("--mode", default=None, choices=("printed", "handwritten"), help="How pages are read")
+
+    cancel_parser = subparsers.add_parser("cancel", help="Cancel a background job")
+    cancel_parser.add_argument("job_id", help="ID of the job")
+
+    args = parser.parse_args(argv)
+    if args.local:
+        # Imported here so the server mode does not load the textbook package
+        from lazyreader.local import LocalClient, default_data_dir
+
+        client = LocalClient(args.data_dir or default_data_dir(), args.user)
+    else:
+        client = ApiClient(args.url, args.token, args.user)
+
+    try:
+        if args.command == "review":
+            return review(client, args.limit, args.book)
+        if args.command == "quiz":
+            return quiz(client, args.book, args.chapter, args.size)
+        if args.command == "ingest":
+            return ingest(client, args.pdf_path, args.password, args.mode)
+        if args.command == "cancel":
+            return cancel(client, args.job_id)
+    except ApiError as e:
+        print(f"Error: {e.detail}")
+        return 1
+    except KeyboardInterrupt:
+        return 130
+    finally:
+        if args.local:
+            client.close()
+
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,72 @@
+# HTTP client of the lazyreader terminal client
+# Thin wrapper over the routes of the backend API the terminal client uses. Errors come back as ApiError with the
+# detail the server gave, so the commands can print them as they are.
+
+from pathlib import Path
+from typing import Any, Dict, List, Optional
+
+import requests
+
+DEFAULT_BASE_URL = "http://localhost:8765"
+DEFAULT_TIMEOUT = 30
+INGEST_TIMEOUT = 600  # Uploading runs the table of contents extraction before the server answers
+
+
+class ApiError(Exception):
+    def __init__(self, status_code: int, detail: str):
+        super().__init__(f"{status_code}: {detail}")
+        self.status_code = status_code
+        self.detail = detail
+
+
+class ApiClient:
+    def __init__(self, base_url: str = DEFAULT_BASE_URL, token: Optional[str] = None, user_id: str = "default"):
+        self.base_url = base_url.rstrip("/")
+        self.user_id = user_id
+        self.session = requests.Session()
+        if token:
+            self.session.headers["Authorization"] = f"Bearer {token}"
+
+    def _request(self, method: str, path: str, timeout: float = DEFAULT_TIMEOUT, **kwargs) -> Dict[str, Any]:
+        try:
+            response = self.session.request(method, f"{self.base_url}{path}", timeout=timeout, **kwargs)
+        except requests.RequestException as e:
+            raise ApiError(0, f"Could not reach {self.base_url}: {e}") from e
+        if response.status_code >= 400:
+            try:
+                detail = response.json().get("detail", response.text)
+            except ValueError:
+                detail = response.text
+            raise ApiError(response.status_code, str(detail))
+        return response.json()
+
+    def review_batch(self, limit: int = 20, book_id: Optional[int] = None) -> Dict[str, Any]:
+        params: Dict[str, Any] = {"limit": limit, "user_id": self.user_id}
+        if book_id is not None:
+            params["book_id"] = book_id
+        return self._request("GET", "/review/batch", params=params)
+
+    def submit_reviews(self, reviews: List[Dict[str, Any]]) -> Dict[str, Any]:
+        return self._request("POST", "/review/batch", json={"user_id": self.user_id, "reviews": reviews})
+
+    def chapters(self, book_id: int) -> Dict[str, Any]:
+        # Archived chapters too, so chapter numbers match the book's TOC
+        return self._request("GET", "/chapters", params={"book_id": book_id, "status": "all"})
+
+    def quiz(self, book_id: int, chapter_id: int, size: int = 10) -> Dict[str, Any]:
+        return self._request("POST", f"/books/{book_id}/quiz", json={"chapter_id": chapter_id, "size": size, "user_id": self.user_id})
+
+    def submit_attempt(self, exercise_id: int, answer: str) -> Dict[str, Any]:
+        return self._request("POST", f"/exercises/{exercise_id}/attempts", json={"answer": answer}, timeout=INGEST_TIMEOUT)
+
+    def cancel_job(self, job_id: str) -> Dict[str, Any]:
+        return self._request("DELETE", f"/jobs/{job_id}")
+
+    def ingest(self, pdf_path: Path, password: Optional[str] = None, ingestion_mode: Optional[str] = None) -> Dict[str, Any]:
+        data = {}
+        if password:
+            data["password"] = password
+        if ingestion_mode:
+            data["ingestion_mode"] = ingestion_mode
+        with open(pdf_path, "rb") as f:
+            return self._request("POST", "/upload-book", files={"file": (pdf_path.name, f, "application/pdf")}, data=data, timeout=INGEST_TIMEOUT)
@@ -0,0 +1,165 @@
+# Embedded single-user mode of the lazyreader terminal client
+# For users who do not want to run a server: the client calls the textbook package directly, with the database and
+# uploaded books in the user's data directory. LocalClient answers with the same JSON shapes as the routes ApiClient
+# calls, so the commands work the same in both modes. There are no background jobs in this mode, uploads are
+# processed before the command returns.
+
+import os
+import shutil
+import sys
+import uuid
+from datetime import datetime, timezone
+from pathlib import Path
+from typing import Any, Dict, List, Optional
+
+from lazyreader.client import ApiError
+from textbook import LLM, LazyTextbookReader, TextBookDatabase
+from textbook.database import BookInfo
+from textbook.grading import DEFAULT_CONTEXT_TOKEN_BUDGET
+from textbook.integrity import SOURCE_PDF_ARTIFACT, register_file_artifact
+from textbook.pdf_validation import PDFValidationError, prepare_pdf
+from textbook.preferences import load_preferences
+from textbook.quiz import assemble_quiz
+from textbook.reader import BOOK_SOURCE_PDF, INGESTION_MODE_PRINTED, INGESTION_MODES
+from textbook.review import ReviewGrade, new_card_allowance, review_batch, submit_reviews
+
+DB_FILE_NAME = "textbook_context.db"
+UPLOADS_DIR_NAME = "uploads"
+
+
+def default_data_dir() -> Path:
+    """LAZYREADER_DATA_DIR, or the lazyreader directory in the platform's per-user data directory"""
+    if os.environ.get("LAZYREADER_DATA_DIR"):
+        return Path(os.environ["LAZYREADER_DATA_DIR"])
+    if sys.platform == "win32":
+        return Path(os.environ.get("APPDATA", Path.home())) / "lazyreader"
+    if sys.platform == "darwin":
+        return Path.home() / "Library" / "Application Support" / "lazyreader"
+    return Path(os.environ.get("XDG_DATA_HOME", Path.home() / ".local" / "share")) / "lazyreader"
+
+
+class LocalClient:
+    def __init__(self, data_dir: Path, user_id: str = "default"):
+        self.data_dir = data_dir
+        self.uploads_dir = data_dir / UPLOADS_DIR_NAME
+        self.uploads_dir.mkdir(parents=True, exist_ok=True)
+        self.user_id = user_id
+        self.database = TextBookDatabase(db_path=str(data_dir / DB_FILE_NAME))
+        self.database.__enter__()
+        self._llm: Optional[LLM] = None
+
+    @property
+    def llm(self) -> LLM:
+        # Created on first use, reviewing needs no model
+        if self._llm is None:
+            self._llm = LLM()
+        return self._llm
+
+    def close(self):
+        self.database.__exit__(None, None, None)
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        self.close()
+
+    def _book_pdf_path(self, book_id: int) -> Path:
+        book = self.database.get_book_by_id(book_id)
+        if book is None or not book.book_file_name:
+            raise ApiError(404, f"Book not found: {book_id}")
+        return self.uploads_dir / f"{book.book_file_name}.pdf"
+
+    def review_batch(self, limit: int = 20, book_id: Optional[int] = None) -> Dict[str, Any]:
+        now = datetime.now(timezone.utc)
+        preferences = load_preferences(self.database, self.user_id)
+        batch = review_batch(self.database, self.user_id, limit, preferences.daily_new_card_limit, book_id, now)
+        with self.database.new_session() as session:
+            books = {book.book_id: book.book_name for book in session.query(BookInfo).filter(BookInfo.book_id.in_({card.book_id for card, _ in batch}))}
+        chapters = {chapter.chapter_id: chapter.title for book_id in books for chapter in self.database.get_chapters_by_book_id(book_id)}
+        return {
+            "user_id": self.user_id,
+            "due_count": self.database.count_due_cards(self.user_id, now, book_id),
+            "new_remaining": new_card_allowance(self.database, self.user_id, preferences.daily_new_card_limit, now),
+            "cards": [
+                {
+                    "card_id": card.card_id,
+                    "book_id": card.book_id,
+                    "book_name": books.get(card.book_id),
+                    "front": card.front,
+                    "back": card.back,
+                    "chapter_id": card.chapter_id,
+                    "chapter_title": chapters.get(card.chapter_id),
+                    "is_new": state is None,
+                }
+                for card, state in batch
+            ],
+        }
+
+    def submit_reviews(self, reviews: List[Dict[str, Any]]) -> Dict[str, Any]:
+        grades = [ReviewGrade(review["card_id"], review["grade"], duration_ms=review.get("duration_ms")) for review in reviews]
+        try:
+            stored = submit_reviews(self.database, self.user_id, grades, load_balancing=load_preferences(self.database, self.user_id).load_balancing)
+        except ValueError as e:
+            raise ApiError(400, str(e)) from e
+        return {"user_id": self.user_id, "reviews": [{"review_id": review.review_id, "card_id": review.card_id, "grade": review.grade} for review in stored]}
+
+    def chapters(self, book_id: int) -> Dict[str, Any]:
+        chapters = self.database.get_chapters_by_book_id(book_id)
+        return {"book_id": book_id, "chapters": [{"chapter_id": chapter.chapter_id, "title": chapter.title, "start_page_number": chapter.start_page_number} for chapter in chapters]}
+
+    def quiz(self, book_id: int, chapter_id: int, size: int = 10) -> Dict[str, Any]:
+        if not any(chapter.chapter_id == chapter_id for chapter in self.database.get_chapters_by_book_id(book_id)):
+            raise ApiError(404, f"Chapter {chapter_id} not found in book {book_id}")
+        try:
+            quiz, _ = assemble_quiz(self.database, book_id, chapter_id, size, preferences=load_preferences(self.database, self.user_id))
+        except ValueError as e:
+            raise ApiError(400, str(e)) from e
+        items = []
+        for item in quiz:
+            exercise = self.database.get_exercise_info(item.exercise_id)
+            items.append({
+                "exercise": {"exercise_id": exercise.exercise_id, "description": exercise.description, "starter_code": exercise.starter_code},
+                "chapter_id": item.chapter_id,
+                "slot": item.slot,
+                "mastery": item.mastery,
+            })
+        return {"book_id": book_id, "chapter_id": chapter_id, "items": items}
+
+    def submit_attempt(self, exercise_id: int, answer: str) -> Dict[str, Any]:
+        exercise = self.database.get_exercise_info(exercise_id)
+        if exercise is None:
+            raise ApiError(404, f"Exercise not found: {exercise_id}")
+        with LazyTextbookReader(self._book_pdf_path(exercise.book_id), self.llm, self.database) as reader:
+            if not reader.check_if_book_exists_and_load():
+                raise ApiError(404, "Book not found")
+            attempt_id = reader.grade_attempt(exercise_id, answer, DEFAULT_CONTEXT_TOKEN_BUDGET)
+        attempt = self.database.get_attempt_info(attempt_id)
+        return {"attempt_id": attempt.attempt_id, "score": attempt.score, "is_correct": attempt.is_correct, "feedback": attempt.feedback}
+
+    def ingest(self, pdf_path: Path, password: Optional[str] = None, ingestion_mode: Optional[str] = None) -> Dict[str, Any]:
+        ingestion_mode = ingestion_mode or INGESTION_MODE_PRINTED
+        if ingestion_mode not in INGESTION_MODES:
+            raise ApiError(400, f"Unsupported ingestion mode: {ingestion_mode}, expected one of {', '.join(INGESTION_MODES)}")
+        file_name = f"{uuid.uuid4()}.pdf"
+        stored_path = self.uploads_dir / file_name
+        shutil.copyfile(pdf_path, stored_path)
+        try:
+            prepare_pdf(stored_path, password=password)
+            with LazyTextbookReader(stored_path, self.llm, self.database, ingestion_mode=ingestion_mode) as reader:
+                reader.update_book_info()
+                book_info = reader.book_info
+                if not book_info or not book_info.book_id:
+                    raise ApiError(500, "Failed to create book entry")
+                register_file_artifact(self.database, str(self.uploads_dir), book_info.book_id, SOURCE_PDF_ARTIFACT, file_name)
+                self.database.update_book_source(book_info.book_id, BOOK_SOURCE_PDF)
+                return {"book_id": book_info.book_id, "message": "Book uploaded and created successfully"}
+        except PDFValidationError as e:
+            stored_path.unlink(missing_ok=True)
+            raise ApiError(400, e.message) from e
+        except Exception:
+            stored_path.unlink(missing_ok=True)
+            raise
+
+    def cancel_job(self, job_id: str) -> Dict[str, Any]:
+        raise ApiError(400, "There are no background jobs in local mode")
@@ -0,0 +1,88 @@
+# Terminal review loop
+# Shows the due cards one at a time: the front first, space or enter reveals the back, then 1 (again), 2 (hard),
+# 3 (good) or 4 (easy) grades the card and q quits. Each grade is sent right away with how long the answer took, so
+# quitting halfway loses nothing. The screen is redrawn with ANSI escapes and keys are read with readchar.
+
+import time
+from dataclasses import dataclass, field
+from typing import Any, Callable, Dict, List, Optional
+
+GRADE_KEYS = {"1": 1, "2": 2, "3": 3, "4": 4}
+GRADE_LABELS = {1: "again", 2: "hard", 3: "good", 4: "easy"}
+REVEAL_KEYS = (" ", "\r", "\n")
+QUIT_KEYS = ("q", "Q", "\x1b")
+CLEAR_SCREEN = "\x1b[2J\x1b[H"
+
+
+@dataclass
+class ReviewSession:
+    cards: List[Dict[str, Any]]
+    index: int = 0
+    revealed: bool = False
+    quit: bool = False
+    shown_at: float = 0.0
+    graded: List[Dict[str, Any]] = field(default_factory=list)
+
+    @property
+    def done(self) -> bool:
+        return self.quit or self.index >= len(self.cards)
+
+    @property
+    def card(self) -> Optional[Dict[str, Any]]:
+        return None if self.done else self.cards[self.index]
+
+    def press(self, key: str, now: float) -> Optional[Dict[str, Any]]:
+        """Handle a key, returns the review to record when it graded the card"""
+        if self.done:
+            return None
+        if key in QUIT_KEYS:
+            self.quit = True
+            return None
+        if not self.revealed:
+            if key in REVEAL_KEYS:
+                self.revealed = True
+            return None
+        if key not in GRADE_KEYS:
+            return None
+        review = {"card_id": self.cards[self.index]["card_id"], "grade": GRADE_KEYS[key], "duration_ms": max(0, int((now - self.shown_at) * 1000))}
+        self.graded.append(review)
+        self.index += 1
+        self.revealed = False
+        self.shown_at = now
+        return review
+
+
+def render(session: ReviewSession, due_count: int) -> str:
+    card = session.card
+    if card is None:
+        return f"{CLEAR_SCREEN}Reviewed {len(session.graded)} cards\n"
+    location = " / ".join(str(part) for part in (card.get("book_name"), card.get("chapter_title")) if part)
+    lines = [
+        CLEAR_SCREEN,
+        f"Card {session.index + 1} of {len(session.cards)} ({due_count} due){'  new' if card.get('is_new') else ''}",
+        location,
+        "",
+        card["front"],
+        "",
+    ]
+    if session.revealed:
+        lines += ["-" * 40, card["back"], "", "  ".join(f"[{key}] {GRADE_LABELS[grade]}" for key, grade in GRADE_KEYS.items()) + "  [q] quit"]
+    else:
+        lines.append("[space] show answer  [q] quit")
+    return "\n".join(lines) + "\n"
+
+
+def run_review(batch: Dict[str, Any], submit: Callable[[Dict[str, Any]], None], read_key: Optional[Callable[[], str]] = None, out=print) -> ReviewSession:
+    """Review a batch from GET /review/batch, submit records each grade"""
+    if read_key is None:
+        import readchar
+
+        read_key = readchar.readkey
+    session = ReviewSession(batch["cards"], shown_at=time.monotonic())
+    while not session.done:
+        out(render(session, batch["due_count"]), end="")
+        review = session.press(read_key(), time.monotonic())
+        if review is not None:
+            submit(review)
+    out(render(session, batch["due_count"]), end="")
+    return session
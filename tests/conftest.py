@@ -0,0 +1,66 @@
+"""
+Fixtures and fakes shared by the test modules
+"""
+import tempfile
+from pathlib import Path
+from typing import Any, Callable, List, Optional, Union
+
+import pytest
+
+from textbook.database import TextBookDatabase
+from textbook.generation import GenerationParams
+
+
+@pytest.fixture
+def tmpdir():
+    with tempfile.TemporaryDirectory() as tmpdir:
+        yield Path(tmpdir)
+
+
+@pytest.fixture
+def database(tmpdir):
+    database = TextBookDatabase(db_path=str(tmpdir / "test.db"))
+    yield database
+    database.close()
+
+
+class FakeLLM:
+    """
+    Stands in for textbook.model.LLM, answering structured prompts without a provider and remembering the prompts
+
+    Args:
+        answer: What every prompt is answered with, or a function of the prompt and schema returning the answer
+        schema: The schema every prompt must ask for, any if None
+        model_name: Name of the model, as routing and gradings record it
+        generation: Generation parameters, as set by with_generation
+        supports_images: Whether prompts may attach page images
+    """
+
+    def __init__(
+        self,
+        answer: Union[Any, Callable[[str, Any], Any]] = None,
+        schema: Optional[type] = None,
+        model_name: str = "fake",
+        generation: Optional[GenerationParams] = None,
+        supports_images: bool = False,
+    ):
+        self.answer = answer
+        self.schema = schema
+        self.model_name = model_name
+        self.generation = generation or GenerationParams()
+        self.supports_images = supports_images
+        self.prompts: List[str] = []
+        self.images: List[list] = []
+
+    def prompt_with_schema(self, prompt, schema):
+        if self.schema is not None:
+            assert schema is self.schema
+        self.prompts.append(prompt)
+        return self.answer(prompt, schema) if callable(self.answer) else self.answer
+
+    def prompt_with_schema_and_images(self, prompt, schema, images):
+        self.images.append(images)
+        return self.prompt_with_schema(prompt, schema)
+
+    def with_generation(self, generation: GenerationParams) -> "FakeLLM":
+        return FakeLLM(self.answer, self.schema, self.model_name, generation, self.supports_images)
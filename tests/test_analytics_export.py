@@ -0,0 +1,60 @@
+"""
+Test cases for the analytics export
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.analytics_export import ACTIVITY_ATTEMPT, ACTIVITY_REVIEW, DUCKDB_FILE_NAME, EXPORT_FORMAT_DUCKDB, EXPORT_FORMAT_PARQUET, export_analytics, study_sessions
+from textbook.database import FlashcardInfo
+from textbook.review import GRADE_GOOD, ReviewGrade, submit_reviews
+
+NOW = datetime(2024, 3, 1, 12, tzinfo=timezone.utc)
+
+
+class TestStudySessions:
+    """Test suite for deriving study sessions from activity"""
+
+    def test_pauses_split_sessions(self):
+        """Test that a long pause or another user starts a new session"""
+        activity = [
+            ("alice", NOW, ACTIVITY_REVIEW),
+            ("alice", NOW + timedelta(minutes=20), ACTIVITY_ATTEMPT),
+            ("alice", NOW + timedelta(hours=2), ACTIVITY_REVIEW),
+            ("bob", NOW + timedelta(minutes=5), ACTIVITY_REVIEW),
+        ]
+        sessions = study_sessions(activity)
+
+        assert [(session[1], session[4], session[5]) for session in sessions] == [("alice", 1, 1), ("alice", 1, 0), ("bob", 1, 0)]
+        assert sessions[0][2:4] == (NOW, NOW + timedelta(minutes=20))
+        assert study_sessions([]) == []
+
+
+class TestExport:
+    """Test suite for writing the export files"""
+
+    @pytest.fixture
+    def database(self, database):
+        book = database.create_book("analysis", "me", "sequences", "analysis", 3)
+        card_ids = database.create_flashcards(book.book_id, [FlashcardInfo(front="front", back="back")])
+        submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_GOOD)], now=NOW)
+        return database
+
+    def test_parquet_files_per_table(self, database, tmpdir):
+        """Test that every table is written to its own Parquet file"""
+        import duckdb
+
+        file_names = export_analytics(database, tmpdir / "export", EXPORT_FORMAT_PARQUET)
+        assert file_names == ["attempts.parquet", "reviews.parquet", "problems.parquet", "events.parquet", "sessions.parquet"]
+        assert duckdb.sql(f"SELECT user_id, grade FROM '{tmpdir / 'export' / 'reviews.parquet'}'").fetchall() == [("alice", GRADE_GOOD)]
+
+    def test_duckdb_database(self, database, tmpdir):
+        """Test that the DuckDB export holds the sessions derived from the reviews"""
+        import duckdb
+
+        assert export_analytics(database, tmpdir / "export", EXPORT_FORMAT_DUCKDB) == [DUCKDB_FILE_NAME]
+        with duckdb.connect(str(tmpdir / "export" / DUCKDB_FILE_NAME)) as connection:
+            assert connection.execute("SELECT user_id, review_count, attempt_count FROM sessions").fetchall() == [("alice", 1, 0)]
+
+        with pytest.raises(ValueError):
+            export_analytics(database, tmpdir / "export", "csv")
@@ -0,0 +1,80 @@
+"""
+Test cases for checking answers against the reference solution
+"""
+import json
+
+import pytest
+from pydantic import ValidationError
+
+from tests.conftest import FakeLLM
+from textbook.answer_check import AnswerCheckSchema, VERDICT_CORRECT, VERDICT_PARTIALLY_CORRECT, VERDICT_WRONG, check_answer
+from textbook.database import NumericAnswerInfo, ProblemInfo, SolutionInfo
+from textbook.library import add_upload
+
+
+class TestAnswerCheck:
+    """Test suite for checking answers"""
+
+    @pytest.fixture
+    def problem_id(self, database):
+        document = add_upload(database, "algebra", "a.pdf", 1)
+        return database.replace_problems(document.document_id, [
+            ProblemInfo(problem_number="1", statement="Show that the identity of a group is unique."),
+        ])[0]
+
+    def test_answer_is_checked_against_solution(self, database, problem_id):
+        """Test that the prompt has the reference solution and the answer, and the verdict is returned"""
+        database.save_solution(SolutionInfo(problem_id=problem_id, steps=json.dumps(["Let e and f be identities.", "Then e = ef = f."]), final_answer="The identity is unique.", hints="[]"))
+        llm = FakeLLM(AnswerCheckSchema(verdict="partially_correct", feedback=" Right idea, but f is never used. "), AnswerCheckSchema)
+
+        verdict = check_answer(database, llm, problem_id, "  Take e = ef.  ")
+        assert (verdict.verdict, verdict.feedback) == (VERDICT_PARTIALLY_CORRECT, "Right idea, but f is never used.")
+        assert "2. Then e = ef = f." in llm.prompts[0] and "Take e = ef." in llm.prompts[0]
+
+    def test_equivalent_expressions_skip_the_llm(self, database, problem_id):
+        """Test that an answer equivalent to the final answer is correct without prompting, others are prompted"""
+        pytest.importorskip("sympy")
+        database.save_solution(SolutionInfo(problem_id=problem_id, steps=json.dumps(["Expand."]), final_answer="2x+2", hints="[]"))
+        llm = FakeLLM(AnswerCheckSchema(verdict="wrong", feedback="Off by one."), AnswerCheckSchema)
+        assert check_answer(database, llm, problem_id, "2(x+1)").verdict == VERDICT_CORRECT
+        assert llm.prompts == []
+        assert check_answer(database, llm, problem_id, "2x+1").verdict == VERDICT_WRONG
+        assert len(llm.prompts) == 1
+
+    def test_numeric_answers_skip_the_llm(self, database, problem_id):
+        """Test that a number with a unit is checked in SI against the numeric answer, without a solution or prompting"""
+        database.save_numeric_answer(NumericAnswerInfo(problem_id=problem_id, value=2.4, unit="kJ", rel_tolerance=0.01))
+        llm = FakeLLM(AnswerCheckSchema(verdict="partially_correct", feedback="Show your work."), AnswerCheckSchema)
+        assert check_answer(database, llm, problem_id, "E = 2400 J").verdict == VERDICT_CORRECT
+        assert check_answer(database, llm, problem_id, "2.6 kJ").verdict == VERDICT_WRONG
+        assert "does not measure the same" in check_answer(database, llm, problem_id, "2.4 kN").feedback
+        assert llm.prompts == []
+        database.save_solution(SolutionInfo(problem_id=problem_id, steps=json.dumps(["Multiply."]), final_answer="2.4 kJ", hints="[]"))
+        assert check_answer(database, llm, problem_id, "the work done by gravity").verdict == VERDICT_PARTIALLY_CORRECT
+        assert len(llm.prompts) == 1
+
+    def test_formula_answers_skip_the_llm(self, database, problem_id):
+        """Test that a formula is compared in Hill order with the problem's, other answers are prompted"""
+        database.update_problem(problem_id, chemical_formula="C2H6O")
+        database.save_solution(SolutionInfo(problem_id=problem_id, steps=json.dumps(["Count the atoms."]), final_answer="C2H6O", hints="[]"))
+        llm = FakeLLM(AnswerCheckSchema(verdict="partially_correct", feedback="Name the compound too."), AnswerCheckSchema)
+        assert check_answer(database, llm, problem_id, "CH3CH2OH").verdict == VERDICT_CORRECT
+        assert check_answer(database, llm, problem_id, "C2H4O2").verdict == VERDICT_WRONG
+        assert llm.prompts == []
+        assert check_answer(database, llm, problem_id, "ethanol").verdict == VERDICT_PARTIALLY_CORRECT
+        assert len(llm.prompts) == 1
+
+    def test_unanswerable_checks_are_rejected(self, database, problem_id):
+        """Test that problems without a solution, unknown problems and empty answers are not checked"""
+        llm = FakeLLM(AnswerCheckSchema(verdict="wrong", feedback="No."), AnswerCheckSchema)
+        for unknown_problem_id, answer in ((problem_id, "e = f"), (999, "e = f"), (problem_id, " ")):
+            with pytest.raises(ValueError):
+                check_answer(database, llm, unknown_problem_id, answer)
+        assert llm.prompts == []
+
+    def test_verdict_is_validated(self):
+        """Test that spelled-out verdicts are normalized and unknown ones rejected"""
+        assert AnswerCheckSchema.model_validate({"verdict": "Partially correct", "feedback": ""}).verdict == VERDICT_PARTIALLY_CORRECT
+        assert AnswerCheckSchema.model_validate({"verdict": "WRONG", "feedback": ""}).verdict == VERDICT_WRONG
+        with pytest.raises(ValidationError):
+            AnswerCheckSchema.model_validate({"verdict": "almost", "feedback": ""})
@@ -8,9 +8,6 @@ import shutil
 from pathlib import Path
 from io import BytesIO
 
-# Set dummy API key to avoid import errors
-os.environ.setdefault("LLM_GEMINI_KEY", "dummy_key_for_testing")
-
 from fastapi.testclient import TestClient
 
 
@@ -57,23 +54,23 @@ class TestAPIEndpoints:
         import api.app as api
         
         # Initialize context
-        api.database = TextBookDatabase(db_path=temp_db)
-        api.database.__enter__()
+        api.state.database = TextBookDatabase(db_path=temp_db)
+        api.state.database.__enter__()
         
-        # Initialize LLM (will use dummy key from env)
-        api.llm = LLM()
+        # Initialize LLM (needs the key of the provider in the environment)
+        api.state.llm = LLM()
         
         # Set paths
-        api.db_path = temp_db
-        api.uploads_dir = temp_uploads_dir
+        api.state.db_path = temp_db
+        api.state.uploads_dir = temp_uploads_dir
         
         # Create client
         client = TestClient(api.app)
         yield client
         
         # Cleanup
-        if api.database:
-            api.database.__exit__(None, None, None)
+        if api.state.database:
+            api.state.database.__exit__(None, None, None)
     
     def test_root(self, client):
         """Test GET / endpoint"""
@@ -92,6 +89,15 @@ class TestAPIEndpoints:
         assert "status" in data
         assert "llm_initialized" in data
         assert "context_initialized" in data
+        assert "dependencies" in data
+
+    def test_capabilities(self, client):
+        """Test GET /capabilities endpoint"""
+        response = client.get("/capabilities")
+        assert response.status_code == 200
+        capabilities = {capability["name"]: capability for capability in response.json()["capabilities"]}
+        assert set(capabilities) == {"llm", "embeddings", "grading", "mineru", "tts"}
+        assert not capabilities["tts"]["enabled"] and capabilities["tts"]["guidance"]
     
     def test_get_total_pages(self, client, test_pdf_path):
         """Test POST /total-pages endpoint"""
@@ -99,8 +105,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -113,11 +119,11 @@ class TestAPIEndpoints:
             book_id = book.book_id
         
         # Copy test PDF to uploads directory
-        upload_path = Path(api.uploads_dir) / os.path.basename(test_pdf_path)
+        upload_path = Path(api.state.uploads_dir) / os.path.basename(test_pdf_path)
         shutil.copy(test_pdf_path, upload_path)
         
         # Update book_file_name to match the upload path
-        with api.database.new_session() as session:
+        with api.state.database.new_session() as session:
             book = session.query(BookInfo).filter(BookInfo.book_id == book_id).first()
             assert book is not None
             book.book_file_name = os.path.basename(test_pdf_path)
@@ -137,8 +143,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -151,7 +157,7 @@ class TestAPIEndpoints:
             book_id = book.book_id
         
         # Copy test PDF to uploads directory
-        upload_path = Path(api.uploads_dir) / os.path.basename(test_pdf_path)
+        upload_path = Path(api.state.uploads_dir) / os.path.basename(test_pdf_path)
         shutil.copy(test_pdf_path, upload_path)
         
         response = client.post("/page-text", json={"book_id": book_id, "page_number": 0})
@@ -169,8 +175,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -183,7 +189,7 @@ class TestAPIEndpoints:
             book_id = book.book_id
         
         # Copy test PDF to uploads directory
-        upload_path = Path(api.uploads_dir) / os.path.basename(test_pdf_path)
+        upload_path = Path(api.state.uploads_dir) / os.path.basename(test_pdf_path)
         shutil.copy(test_pdf_path, upload_path)
         
         response = client.post("/page-image", json={"book_id": book_id, "page_number": 0, "dpi": 150})
@@ -202,8 +208,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -216,7 +222,7 @@ class TestAPIEndpoints:
             book_id = book.book_id
         
         # Copy test PDF to uploads directory
-        upload_path = Path(api.uploads_dir) / os.path.basename(test_pdf_path)
+        upload_path = Path(api.state.uploads_dir) / os.path.basename(test_pdf_path)
         shutil.copy(test_pdf_path, upload_path)
         
         response = client.post("/page-image-binary", params={"book_id": book_id, "page_number": 0, "dpi": 150})
@@ -230,8 +236,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -244,7 +250,7 @@ class TestAPIEndpoints:
             book_id = book.book_id
         
         # Copy test PDF to uploads directory
-        upload_path = Path(api.uploads_dir) / os.path.basename(test_pdf_path)
+        upload_path = Path(api.state.uploads_dir) / os.path.basename(test_pdf_path)
         shutil.copy(test_pdf_path, upload_path)
         
         response = client.post("/update-book-info", json={"book_id": book_id, "overwrite": True})
@@ -262,8 +268,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -276,7 +282,7 @@ class TestAPIEndpoints:
             book_id = book.book_id
         
         # Copy test PDF to uploads directory
-        upload_path = Path(api.uploads_dir) / os.path.basename(test_pdf_path)
+        upload_path = Path(api.state.uploads_dir) / os.path.basename(test_pdf_path)
         shutil.copy(test_pdf_path, upload_path)
         
         response = client.get("/check-toc-exists", params={"book_id": book_id})
@@ -293,8 +299,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -307,7 +313,7 @@ class TestAPIEndpoints:
             book_id = book.book_id
         
         # Copy test PDF to uploads directory
-        upload_path = Path(api.uploads_dir) / os.path.basename(test_pdf_path)
+        upload_path = Path(api.state.uploads_dir) / os.path.basename(test_pdf_path)
         shutil.copy(test_pdf_path, upload_path)
         
         response = client.post("/update-toc", json={"book_id": book_id, "caching": True, "overwrite": True})
@@ -324,8 +330,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -338,7 +344,7 @@ class TestAPIEndpoints:
             book_id = book.book_id
         
         # Copy test PDF to uploads directory
-        upload_path = Path(api.uploads_dir) / os.path.basename(test_pdf_path)
+        upload_path = Path(api.state.uploads_dir) / os.path.basename(test_pdf_path)
         shutil.copy(test_pdf_path, upload_path)
         
         response = client.post("/update-alignment-offset", json={"book_id": book_id, "page_number": 0})
@@ -356,8 +362,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -370,7 +376,7 @@ class TestAPIEndpoints:
             book_id = book.book_id
         
         # Copy test PDF to uploads directory
-        upload_path = Path(api.uploads_dir) / os.path.basename(test_pdf_path)
+        upload_path = Path(api.state.uploads_dir) / os.path.basename(test_pdf_path)
         shutil.copy(test_pdf_path, upload_path)
         
         response = client.post("/check-alignment-offset", json={"book_id": book_id})
@@ -396,8 +402,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -445,8 +451,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -459,7 +465,7 @@ class TestAPIEndpoints:
             book_id = book.book_id
         
         # Copy test PDF to uploads directory
-        upload_path = Path(api.uploads_dir) / os.path.basename(test_pdf_path)
+        upload_path = Path(api.state.uploads_dir) / os.path.basename(test_pdf_path)
         shutil.copy(test_pdf_path, upload_path)
         
         response = client.delete("/delete-book", params={"book_id": book_id})
@@ -477,8 +483,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -510,8 +516,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo, SectionInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -555,8 +561,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo, SectionInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -594,8 +600,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo, SectionInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -636,8 +642,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo, SectionInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -673,8 +679,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -703,8 +709,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo, PageInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -740,8 +746,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo, PageInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -776,8 +782,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo, PageInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
@@ -815,8 +821,8 @@ class TestAPIEndpoints:
         from textbook.database import BookInfo, PageInfo
         import api.app as api
         
-        assert api.database is not None
-        with api.database.new_session() as session:
+        assert api.state.database is not None
+        with api.state.database.new_session() as session:
             book = BookInfo(
                 book_name="Test Book",
                 book_author="Test Author",
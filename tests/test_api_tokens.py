@@ -0,0 +1,101 @@
+"""
+Test cases for scoped API tokens
+"""
+import json
+import tempfile
+from pathlib import Path
+
+import pytest
+
+from textbook.api_tokens import SCOPE_ADMIN, SCOPE_READ_STATS, SCOPE_WRITE_REVIEW, authenticate, create_token, has_scope, parse_scopes, required_scope
+from textbook.database import ChapterQuizInfo, TextBookDatabase
+
+
+class TestScopes:
+    """Test suite for matching routes to scopes"""
+
+    def test_required_scope(self):
+        """Test that statistics and reviews need narrow scopes and every other route needs admin"""
+        assert required_scope("GET", "/review/batch") == SCOPE_READ_STATS
+        assert required_scope("GET", "/books/3/mastery/") == SCOPE_READ_STATS
+        assert required_scope("POST", "/review/batch") == SCOPE_WRITE_REVIEW
+        assert required_scope("POST", "/review/12/undo") == SCOPE_WRITE_REVIEW
+        assert required_scope("DELETE", "/delete-book") == SCOPE_ADMIN
+        assert required_scope("GET", "/health") is None
+        assert required_scope("GET", "/ready") is None
+        assert required_scope("GET", "/capabilities") is None
+
+    def test_parse_scopes(self):
+        """Test that scopes are deduplicated and unknown ones rejected"""
+        assert parse_scopes(["read:stats", " read:stats", "write:review"]) == [SCOPE_READ_STATS, SCOPE_WRITE_REVIEW]
+        with pytest.raises(ValueError):
+            parse_scopes(["read:everything"])
+        with pytest.raises(ValueError):
+            parse_scopes([])
+
+
+class TestTokens:
+    """Test suite for creating, using and revoking tokens"""
+
+    def test_token_lifecycle(self, database):
+        """Test that only the digest is stored, the token authenticates until it is revoked"""
+        info, token = create_token(database, "alice", "status bar", [SCOPE_READ_STATS])
+        assert info.token_sha256 != token
+
+        authenticated = authenticate(database, token)
+        assert authenticated.token_id == info.token_id
+        assert has_scope(authenticated, SCOPE_READ_STATS) and not has_scope(authenticated, SCOPE_WRITE_REVIEW)
+        assert database.get_api_tokens("alice")[0].last_used_at is not None
+        assert authenticate(database, token + "x") is None
+
+        database.revoke_api_token(info.token_id)
+        assert authenticate(database, token) is None
+        assert database.get_api_tokens("alice") == []
+        assert len(database.get_api_tokens("alice", include_revoked=True)) == 1
+
+    def test_admin_grants_every_scope(self, database):
+        """Test that an admin token passes every scope check"""
+        info, _ = create_token(database, "alice", "backup script", [SCOPE_ADMIN])
+        assert has_scope(info, SCOPE_WRITE_REVIEW) and has_scope(info, SCOPE_READ_STATS)
+
+
+class TestTokenUsers:
+    """Test suite for the users a token may act for"""
+
+    @pytest.fixture
+    def client(self):
+        from fastapi.testclient import TestClient
+        import api.app as api
+
+        previous = api.state.database
+        with tempfile.TemporaryDirectory() as tmpdir:
+            api.state.database = TextBookDatabase(db_path=str(Path(tmpdir) / "test.db"))
+            yield TestClient(api.app)
+            api.state.database.close()
+        api.state.database = previous
+
+    def test_token_cannot_name_another_user(self, client):
+        """Test that a token without admin is refused for another user in the path, query or body"""
+        import api.app as api
+
+        _, token = create_token(api.state.database, "alice", "widget", [SCOPE_READ_STATS])
+        _, admin_token = create_token(api.state.database, "root", "backup script", [SCOPE_ADMIN])
+        headers = {"Authorization": f"Bearer {token}"}
+        for path in ("/users/bob/digest", "/users/bob/study-plan", "/users/bob/syllabus", "/users/bob/filters", "/users/bob/chapter-quizzes", "/review/batch?user_id=bob"):
+            assert client.get(path, headers=headers).status_code == 403, path
+        assert client.get("/users/alice/chapter-quizzes", headers=headers).status_code == 200
+        assert client.get("/users/bob/chapter-quizzes", headers={"Authorization": f"Bearer {admin_token}"}).status_code == 200
+
+    def test_token_cannot_read_another_users_chapter_quiz(self, client):
+        """Test that a chapter quiz of another user is not found for a token"""
+        import api.app as api
+
+        database = api.state.database
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        chapter_id = database.try_create_chapter_info(book.book_id, "Sequences", "1", 1, 10)
+        exercise_id = database.create_exercise(book.book_id, 4, "Sequence exercise")
+        quiz = database.create_chapter_quiz(ChapterQuizInfo(user_id="bob", exercise_ids=json.dumps([exercise_id]), chapter_id=chapter_id, book_id=book.book_id))
+        _, alice_token = create_token(database, "alice", "widget", [SCOPE_READ_STATS])
+        _, bob_token = create_token(database, "bob", "widget", [SCOPE_READ_STATS])
+        assert client.get(f"/chapter-quizzes/{quiz.quiz_id}", headers={"Authorization": f"Bearer {alice_token}"}).status_code == 404
+        assert client.get(f"/chapter-quizzes/{quiz.quiz_id}", headers={"Authorization": f"Bearer {bob_token}"}).status_code == 200
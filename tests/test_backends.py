@@ -0,0 +1,74 @@
+"""
+Test cases for choosing the LLM backend in config.toml
+"""
+import pytest
+
+from textbook.backends import AZURE_OPENAI, BACKENDS, GEMINI, BackendSettings, backend_settings_from_config, is_local
+from textbook.credentials import resolve_api_key
+
+
+def no_keyring(service, name):
+    return None
+
+
+class TestBackends:
+    """Test suite for the [llm] section of config.toml and the keys of the backends"""
+
+    def test_default_backend(self):
+        """Test that without an [llm] section the backend given, or gemini, is used"""
+        assert backend_settings_from_config({}) == BackendSettings(GEMINI)
+        assert backend_settings_from_config({}, "anthropic") == BackendSettings("anthropic")
+        assert backend_settings_from_config({"llm": {"backend": "openai"}}, "anthropic").backend == "openai"
+
+    def test_openai_compatible_backends(self):
+        """Test that OpenAI-compatible backends take a base URL and plugin backends do not"""
+        settings = backend_settings_from_config({"llm": {"backend": "ollama", "model": "llama3.1", "base_url": "http://gpu:11434/v1"}})
+        assert settings == BackendSettings("ollama", "llama3.1", "http://gpu:11434/v1")
+        assert settings.spec.openai_compatible and not settings.spec.requires_key
+        assert BACKENDS["deepseek"].openai_compatible and BACKENDS["deepseek"].base_url
+        assert not BACKENDS[GEMINI].openai_compatible and not BACKENDS["openai"].openai_compatible
+        with pytest.raises(ValueError):
+            backend_settings_from_config({"llm": {"backend": "anthropic", "base_url": "http://proxy"}})
+
+    def test_local_server_without_key(self):
+        """Test that a self-hosted server at a base URL can be used without a key, a cloud backend cannot"""
+        settings = backend_settings_from_config({"llm": {"backend": "openai", "base_url": "http://127.0.0.1:8000/v1", "require_api_key": False}})
+        assert not settings.requires_key and is_local(settings)
+        assert BackendSettings("openai").requires_key and not is_local(BackendSettings("openai"))
+        with pytest.raises(ValueError):
+            backend_settings_from_config({"llm": {"backend": "anthropic", "require_api_key": False}})
+        with pytest.raises(ValueError):
+            backend_settings_from_config({"llm": {"backend": "ollama", "require_api_key": "no"}})
+
+    def test_local_and_remote_urls(self):
+        """Test that loopback and private addresses are local and the default or environment base URL applies"""
+        assert is_local(BackendSettings("ollama"))
+        assert is_local(backend_settings_from_config({"llm": {"backend": "ollama", "base_url": "http://192.168.1.20:11434/v1"}}))
+        assert is_local(backend_settings_from_config({"llm": {"backend": "ollama"}}, default_base_url="http://gpu.local:11434/v1"))
+        assert not is_local(BackendSettings("ollama", base_url="https://ollama.example.com/v1"))
+        assert not is_local(BackendSettings("deepseek")) and not is_local(BackendSettings(GEMINI))
+        config = {"llm": {"backend": "ollama", "base_url": "http://localhost:11434/v1"}}
+        assert backend_settings_from_config(config, default_base_url="http://elsewhere/v1").url == "http://localhost:11434/v1"
+
+    def test_azure_needs_resource_and_version(self):
+        """Test that Azure OpenAI needs its base URL and API version, which no other backend takes"""
+        config = {"llm": {"backend": AZURE_OPENAI, "model": "gpt-4o", "base_url": "https://me.openai.azure.com", "api_version": "2024-10-21"}}
+        assert backend_settings_from_config(config).api_version == "2024-10-21"
+        with pytest.raises(ValueError):
+            backend_settings_from_config({"llm": {"backend": AZURE_OPENAI, "base_url": "https://me.openai.azure.com"}})
+        with pytest.raises(ValueError):
+            backend_settings_from_config({"llm": {"backend": "ollama", "api_version": "2024-10-21"}})
+
+    def test_invalid_settings(self):
+        """Test that unknown backends and settings are rejected"""
+        for section in ({"backend": "watson"}, {"backend": " "}, {"model": 3}, {"temperature": "0.2"}):
+            with pytest.raises(ValueError):
+                backend_settings_from_config({"llm": section})
+
+    def test_keys_of_every_backend(self):
+        """Test that each backend's key is read from its usual variables"""
+        assert resolve_api_key("openai", {}, {"OPENAI_API_KEY": "sk-1"}, no_keyring).location == "OPENAI_API_KEY"
+        assert resolve_api_key("mistral", {}, {"LLM_MISTRAL_KEY": "m-1"}, no_keyring).location == "LLM_MISTRAL_KEY"
+        assert resolve_api_key("groq", {}, {"OPENAI_API_KEY": "sk-1"}, no_keyring) is None
+        assert resolve_api_key("ollama", {}, {}, no_keyring) is None
+        assert len(BACKENDS) == 15 and all(backend.env_vars or not backend.requires_key for backend in BACKENDS.values())
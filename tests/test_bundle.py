@@ -0,0 +1,123 @@
+"""
+Test cases for document bundle export/import
+"""
+import io
+import json
+import os
+import tempfile
+import zipfile
+
+import pymupdf
+import pytest
+
+from textbook.bundle import (
+    BUNDLE_FORMAT_VERSION,
+    MANIFEST_FILE_NAME,
+    BundleError,
+    export_book_bundle,
+    import_book_bundle,
+    load_manifest,
+)
+from textbook.database import TextBookDatabase, BookInfo, ChapterInfo, SectionInfo, PageInfo
+from textbook.watermark import EXPORT_BOOK_BUNDLE, WATERMARK_METADATA, WatermarkSettings, issue_watermark, trace_export
+
+
+class TestBundle:
+    """Test suite for bundle export and import"""
+
+    @pytest.fixture
+    def temp_db(self):
+        """Create a temporary database file for testing"""
+        with tempfile.NamedTemporaryFile(delete=False, suffix='.db') as f:
+            db_path = f.name
+        yield db_path
+        if os.path.exists(db_path):
+            os.unlink(db_path)
+
+    @pytest.fixture
+    def database(self, temp_db):
+        """Create a TextBookDatabase instance with temporary database"""
+        database = TextBookDatabase(db_path=temp_db)
+        yield database
+        database.close()
+
+    @pytest.fixture
+    def uploads_dir(self):
+        """Create a temporary uploads directory"""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            yield tmpdir
+
+    @pytest.fixture
+    def book_id(self, database, uploads_dir):
+        """Create a small book with a TOC and one page summary"""
+        document = pymupdf.open()
+        for page_number in range(3):
+            page = document.new_page()
+            page.insert_text((72, 72), f"Page {page_number}")
+        document.save(os.path.join(uploads_dir, "source.pdf"))
+        document.close()
+
+        with database.new_session() as session:
+            book = BookInfo(book_name="topology", book_author="munkres", book_file_name="source", book_pages=3)
+            session.add(book)
+            session.flush()
+            chapter = ChapterInfo(title="sets", start_page_number=0, end_page_number=2, book_id=book.book_id)
+            session.add(chapter)
+            session.flush()
+            session.add(SectionInfo(title="functions", start_page_number=1, end_page_number=2, chapter_id=chapter.chapter_id, book_id=book.book_id))
+            session.add(PageInfo(page_number=1, summary="a function is a rule", embedding=b"\x00\x01", book_id=book.book_id))
+            session.commit()
+            return book.book_id
+
+    def test_roundtrip(self, database, uploads_dir, book_id):
+        """Test that an exported bundle imports as an identical new book"""
+        bundle = export_book_bundle(database, book_id, os.path.join(uploads_dir, "source.pdf"))
+        new_book_id = import_book_bundle(database, bundle, uploads_dir)
+
+        assert new_book_id != book_id
+        books = {book.book_id: book for book in database.get_all_books()}
+        assert books[new_book_id].book_name == "topology"
+        assert os.path.exists(os.path.join(uploads_dir, f"{books[new_book_id].book_file_name}.pdf"))
+
+        chapters = database.get_chapters_by_book_id(new_book_id)
+        sections = database.get_sections_by_book_id(new_book_id)
+        assert [chapter.title for chapter in chapters] == ["sets"]
+        assert [section.title for section in sections] == ["functions"]
+        assert sections[0].chapter_id == chapters[0].chapter_id
+
+    def test_embeddings_are_optional(self, database, uploads_dir, book_id):
+        """Test that embeddings are only exported when requested"""
+        pdf_path = os.path.join(uploads_dir, "source.pdf")
+        without = zipfile.ZipFile(io.BytesIO(export_book_bundle(database, book_id, pdf_path)))
+        with_embeddings = zipfile.ZipFile(io.BytesIO(export_book_bundle(database, book_id, pdf_path, include_embeddings=True)))
+
+        assert json.loads(without.read(MANIFEST_FILE_NAME))["pages"][0]["embedding"] is None
+        assert json.loads(with_embeddings.read(MANIFEST_FILE_NAME))["pages"][0]["embedding"] is not None
+
+    def test_watermarked_export_is_traced(self, database, uploads_dir, book_id):
+        """Test that a watermarked manifest names its instance and traces back to the export"""
+        watermark = issue_watermark(database, WatermarkSettings(WATERMARK_METADATA, "physics-101"), "alice", EXPORT_BOOK_BUNDLE, book_id)
+        bundle = zipfile.ZipFile(io.BytesIO(export_book_bundle(database, book_id, os.path.join(uploads_dir, "source.pdf"), watermark=watermark)))
+        manifest = bundle.read(MANIFEST_FILE_NAME).decode("utf-8")
+
+        assert json.loads(manifest)["instance_id"] == "physics-101"
+        [export] = trace_export(database, manifest)
+        assert (export.user_id, export.export_kind, export.subject_id) == ("alice", EXPORT_BOOK_BUNDLE, book_id)
+
+    def test_unsupported_version_rejected(self):
+        """Test that manifests from a newer format are rejected"""
+        with pytest.raises(BundleError, match="Unsupported bundle format version"):
+            load_manifest(json.dumps({"format_version": BUNDLE_FORMAT_VERSION + 1}))
+
+    def test_checksum_mismatch_rejected(self, database, uploads_dir, book_id):
+        """Test that a tampered source PDF is rejected"""
+        bundle = export_book_bundle(database, book_id, os.path.join(uploads_dir, "source.pdf"))
+        source = zipfile.ZipFile(io.BytesIO(bundle))
+
+        tampered = io.BytesIO()
+        with zipfile.ZipFile(tampered, "w") as archive:
+            archive.writestr(MANIFEST_FILE_NAME, source.read(MANIFEST_FILE_NAME))
+            archive.writestr("source.pdf", b"not the original pdf")
+
+        with pytest.raises(BundleError, match="checksum"):
+            import_book_bundle(database, tampered.getvalue(), uploads_dir)
@@ -0,0 +1,31 @@
+"""
+Test cases for reporting the optional subsystems enabled
+"""
+import pytest
+
+from textbook.capabilities import FEATURE_EMBEDDINGS, FEATURE_GRADING, FEATURE_LLM, FEATURE_MINERU, FEATURE_TTS, LLM_GUIDANCE, feature_flags_from_config, resolve_capabilities
+
+
+class TestCapabilities:
+    """Test suite for the feature flags and the capabilities they resolve to"""
+
+    def test_flags_from_config(self):
+        """Test that every feature is on unless turned off, and invalid flags are rejected"""
+        assert feature_flags_from_config({}) == {FEATURE_GRADING: True, FEATURE_EMBEDDINGS: True, FEATURE_MINERU: True}
+        assert feature_flags_from_config({"features": {"grading": False}})[FEATURE_GRADING] is False
+        for section in ({"tts": True}, {"llm": False}, {"grading": "no"}):
+            with pytest.raises(ValueError):
+                feature_flags_from_config({"features": section})
+
+    def test_capabilities(self):
+        """Test that a feature is disabled by its flag or what it needs, with guidance to enable it"""
+        flags = feature_flags_from_config({"features": {"mineru": False}})
+        capabilities = resolve_capabilities(flags, True, "gemini-embedding-001", None)
+        assert [name for name, capability in capabilities.items() if capability.enabled] == [FEATURE_LLM, FEATURE_EMBEDDINGS, FEATURE_GRADING]
+        assert "mineru = true" in capabilities[FEATURE_MINERU].guidance
+        assert capabilities[FEATURE_LLM].guidance is None
+        assert not capabilities[FEATURE_TTS].enabled
+
+        capabilities = resolve_capabilities(feature_flags_from_config({}), False, "", None)
+        assert [name for name, capability in capabilities.items() if capability.enabled] == [FEATURE_MINERU]
+        assert capabilities[FEATURE_GRADING].guidance == capabilities[FEATURE_EMBEDDINGS].guidance == LLM_GUIDANCE
@@ -0,0 +1,86 @@
+"""
+Test cases for the diagnostic quizzes enqueued after reading a chapter
+"""
+
+import pytest
+
+from textbook.chapter_quiz import (
+    DEFAULT_QUIZ_SIZE,
+    ChapterQuizSettings,
+    chapter_quiz_progress,
+    chapter_quiz_settings_from_config,
+    reading_done,
+)
+from textbook.database import GradingJudgmentInfo
+from textbook.grading import JUDGMENT_INITIAL
+from textbook.reading_queue import enqueue_section, record_reading
+
+
+class TestChapterQuizSettings:
+    """Test suite for the chapter quiz settings of config.toml"""
+
+    def test_settings_from_config(self):
+        """Test the defaults, a configured size and invalid sizes"""
+        assert chapter_quiz_settings_from_config({}) == ChapterQuizSettings(True, DEFAULT_QUIZ_SIZE)
+        assert chapter_quiz_settings_from_config({"chapter_quiz": False, "chapter_quiz_size": 3}) == ChapterQuizSettings(False, 3)
+        for size in (0, 21, "5"):
+            with pytest.raises(ValueError):
+                chapter_quiz_settings_from_config({"chapter_quiz_size": size})
+
+
+class TestChapterQuiz:
+    """Test suite for enqueueing chapter quizzes and following their answers"""
+
+    @pytest.fixture
+    def book_id(self, database):
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        chapter = database.try_create_chapter_info(book.book_id, "Sequences", "1", 1, 10)
+        database.try_create_section_info(book.book_id, chapter, "Limits", "1.1", 1, 5)
+        database.try_create_section_info(book.book_id, chapter, "Cauchy sequences", "1.2", 6, 10)
+        for index in range(3):
+            database.create_exercise(book.book_id, 4 + index, f"Sequence exercise {index}")
+        return book.book_id
+
+    def _read_sections(self, database, book_id):
+        items = [enqueue_section(database, "alice", book_id, section.section_id) for section in database.get_sections_by_book_id(book_id)]
+        return [record_reading(database, item.reading_id, done=True) for item in items]
+
+    def test_quiz_enqueued_once_chapter_is_read(self, database, book_id):
+        """Test that only being done with every section enqueues a quiz, once per chapter"""
+        first, second = self._read_sections(database, book_id)
+        chapter_id = database.get_sections_by_book_id(book_id)[0].chapter_id
+        assert reading_done(database, record_reading(database, first.reading_id), ChapterQuizSettings(size=2)) is None
+
+        record_reading(database, first.reading_id, done=True)
+        quiz = reading_done(database, second, ChapterQuizSettings(size=2))
+        assert quiz is not None and quiz.chapter_id == chapter_id and quiz.user_id == "alice"
+        assert reading_done(database, second, ChapterQuizSettings(size=2)) is None
+        assert [pending.quiz_id for pending in database.get_chapter_quizzes("alice")] == [quiz.quiz_id]
+        assert database.get_chapter_quizzes("bob") == []
+
+    def test_disabled_or_without_exercises(self, database, book_id):
+        """Test that no quiz is enqueued when disabled or for a chapter without exercises"""
+        _, second = self._read_sections(database, book_id)
+        assert reading_done(database, second, ChapterQuizSettings(enabled=False)) is None
+
+        chapter = database.try_create_chapter_info(book_id, "Series", "2", 11, 20)
+        database.try_create_section_info(book_id, chapter, "Convergence tests", "2.1", 11, 20)
+        section = next(section for section in database.get_sections_by_book_id(book_id) if section.chapter_id == chapter)
+        item = enqueue_section(database, "alice", book_id, section.section_id)
+        assert reading_done(database, record_reading(database, item.reading_id, done=True)) is None
+
+    def test_answers_seed_mastery_and_complete_quiz(self, database, book_id):
+        """Test that the quiz completes once every exercise is answered and the answers give the chapter a mastery"""
+        _, second = self._read_sections(database, book_id)
+        quiz = reading_done(database, second, ChapterQuizSettings(size=2))
+        progress = chapter_quiz_progress(database, quiz)
+        assert len(progress.exercise_ids) == 2 and progress.scores == {} and progress.mastery is None
+
+        for exercise_id in progress.exercise_ids:
+            database.create_attempt(exercise_id, "Converges", [GradingJudgmentInfo(model_name="test", reason=JUDGMENT_INITIAL, score=1.0, is_correct=True, confidence=0.9, feedback="")])
+        progress = chapter_quiz_progress(database, quiz)
+        assert progress.score == 1.0 and progress.quiz.completed_at is not None
+        assert progress.mastery.attempts == 2 and progress.mastery.mastery > 0
+        assert progress.mastery.review_interval_days is not None
+        assert database.get_chapter_quizzes("alice") == []
+        assert len(database.get_chapter_quizzes("alice", include_completed=True)) == 1
@@ -0,0 +1,70 @@
+"""
+Test cases for chemical formulas and SMILES of problems and flashcards
+"""
+import pytest
+
+from textbook.chemistry import canonical_formula, canonical_smiles, canonical_structure, check_structure, parse_formula, validate_smiles_syntax
+
+
+class TestFormulas:
+    """Test suite for parsing formulas and writing them in Hill order"""
+
+    def test_hill_order(self):
+        """Test that carbon and hydrogen come first with carbon, every element is alphabetical without"""
+        assert canonical_formula("CH3CH2OH") == canonical_formula("C2H6O") == "C2H6O"
+        assert canonical_formula("H2SO4") == "H2O4S"
+        assert canonical_formula("NaCl") == "ClNa"
+
+    def test_groups_hydrates_and_charges(self):
+        """Test that groups and hydrates are expanded, and charges, subscripts and LaTeX are read"""
+        assert canonical_formula("Ca(OH)2") == "CaH2O2"
+        assert canonical_formula("K4[Fe(CN)6]") == "C6FeK4N6"
+        assert canonical_formula("CuSO4·5H2O") == canonical_formula("CuSO4*5H2O") == "CuH10O9S"
+        assert parse_formula("SO4^{2-}") == ({"S": 1, "O": 4}, -2)
+        assert canonical_formula("NH4+") == "H4N^+"
+        assert canonical_formula("$\\ce{H_2O}$") == canonical_formula("H₂O") == "H2O"
+
+    def test_invalid_formulas_are_rejected(self):
+        """Test that unknown elements, unbalanced groups and words raise ValueError"""
+        for text in ("Xx2", "H2(O", "water", "H0", "()", "Na+-", ""):
+            with pytest.raises(ValueError):
+                canonical_formula(text)
+
+
+class TestSmiles:
+    """Test suite for validating SMILES"""
+
+    def test_syntax_without_rdkit(self):
+        """Test that atoms, branches, brackets and ring closures are checked"""
+        for smiles in ("CC(=O)O", "c1ccccc1", "[Na+].[Cl-]", "C[C@@H](N)C(=O)O", "C%10CC%10"):
+            validate_smiles_syntax(smiles)
+        for smiles in ("C1CC", "CC(C", "CHO", "[Xx]", "C C"):
+            with pytest.raises(ValueError):
+                validate_smiles_syntax(smiles)
+
+    def test_rdkit_canonicalizes(self):
+        """Test that equivalent SMILES get one canonical form, and a formula has to be the structure's"""
+        pytest.importorskip("rdkit")
+        assert canonical_smiles("OCC") == canonical_smiles("C(O)C")
+        assert canonical_structure("C2H4O2", "CC(=O)O")[0] == "C2H4O2"
+        with pytest.raises(ValueError):
+            canonical_structure("C2H6O", "CC(=O)O")
+        assert check_structure("C(C)O", None, "CCO")[0] is True
+        assert check_structure("CC=O", None, "CCO")[0] is False
+
+
+class TestCheckStructure:
+    """Test suite for grading formula answers"""
+
+    def test_formula_answers(self):
+        """Test that formulas are compared in Hill order, with feedback on what differs"""
+        assert check_structure("CH3CH2OH", "C2H6O", None)[0] is True
+        assert check_structure("C2H5O", "C2H6O", None) == (False, "The elements are right but not how many of each, check the counts.")
+        assert check_structure("NaCl", "C2H6O", None) == (False, "Your formula does not have the expected elements.")
+        assert check_structure("SO4^-", "SO4^2-", None) == (False, "The elements are right but the charge is not.")
+
+    def test_other_answers_are_left_to_the_llm(self):
+        """Test that answers which are not formulas, and SMILES without RDKit, are not decided"""
+        assert check_structure("ethanol", "C2H6O", None) is None
+        assert check_structure("CCO", None, "CCO")[0] is True
+        assert canonical_structure("  ", None) == (None, None)
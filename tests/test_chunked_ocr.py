@@ -0,0 +1,80 @@
+"""
+Test cases for OCR jobs reading PDFs in chunks of pages, resuming after the last chunk read
+"""
+from pathlib import Path
+
+import pymupdf
+import pytest
+
+from textbook.chunked_ocr import ChunkedOcr, page_chunks
+from textbook.ocr import OcrBackend, OcrResult
+
+
+class FlakyBackend(OcrBackend):
+    """Answers a block per page of the PDFs it is given, failing once it read fail_after of them"""
+
+    name = "flaky"
+
+    def __init__(self, fail_after=None):
+        self.fail_after = fail_after
+        self.page_counts = []
+
+    def unavailable(self):
+        return None
+
+    def ocr_pdf(self, pdf_path, language, parse_method, job_id=None):
+        if self.fail_after is not None and len(self.page_counts) >= self.fail_after:
+            raise ConnectionError("MinerU went away")
+        with pymupdf.open(pdf_path) as document:
+            self.page_counts.append(len(document))
+            blocks = [{"type": "text", "text": document[index].get_text().strip(), "page_idx": index} for index in range(len(document))]
+        return OcrResult("\n\n".join(block["text"] for block in blocks), blocks, {f"figure{len(self.page_counts)}.jpg": b"jpeg"})
+
+    def ocr_image(self, image):
+        return ""
+
+
+class TestChunkedOcr:
+    """Test suite for chunked and resumable OCR"""
+
+    @pytest.fixture
+    def pdf_path(self, tmpdir):
+        path = tmpdir / "book.pdf"
+        with pymupdf.open() as document:
+            for index in range(5):
+                document.new_page().insert_text((72, 72), f"Page {index + 1}")
+            document.save(path)
+        return str(path)
+
+    def test_pages_are_chunked(self):
+        """Test that pages are split in chunks of pages_per_chunk, all at once with 0"""
+        assert page_chunks([0, 1, 2, 3, 4], 2) == [[0, 1], [2, 3], [4]]
+        assert page_chunks([3, 7, 9], 0) == [[3, 7, 9]]
+        assert page_chunks([], 2) == []
+
+    def test_chunks_are_read_in_order_with_progress(self, tmpdir, pdf_path):
+        """Test that every chunk goes to the backend, its blocks back on their pages, with progress after each"""
+        backend, progress = FlakyBackend(), []
+        chunked = ChunkedOcr(backend, str(tmpdir / "chunks"), 2, report_progress=lambda stage, percent, message: progress.append(percent))
+        result = chunked.ocr_pdf(pdf_path, "en", "auto")
+        assert backend.page_counts == [2, 2, 1]
+        assert [(block["text"], block["page_idx"]) for block in result.content_list] == [(f"Page {index + 1}", index) for index in range(5)]
+        assert len(result.images) == 3
+        assert progress == pytest.approx([30, 60, 90])
+
+    def test_failed_jobs_resume_after_the_last_chunk(self, tmpdir, pdf_path):
+        """Test that a job running again reads only the chunks not read before, and the chunks go once written"""
+        cache_root = str(tmpdir / "chunks")
+        with pytest.raises(ConnectionError):
+            ChunkedOcr(FlakyBackend(fail_after=2), cache_root, 2).ocr_pdf(pdf_path, "en", "auto")
+
+        backend = FlakyBackend()
+        chunked = ChunkedOcr(backend, cache_root, 2)
+        result = chunked.ocr_pdf(pdf_path, "en", "auto")
+        assert backend.page_counts == [1]
+        assert [block["page_idx"] for block in result.content_list] == [0, 1, 2, 3, 4]
+        # Another language is another document to the backend
+        chunked.ocr_pdf(pdf_path, "ch", "auto")
+        assert backend.page_counts == [1, 2, 2, 1]
+        chunked.discard()
+        assert list(Path(cache_root).iterdir()) == []
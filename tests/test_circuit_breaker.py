@@ -0,0 +1,144 @@
+"""
+Test cases for the circuit breakers failing calls to a service fast while it is down
+"""
+
+import pytest
+import structlog
+
+from textbook.circuit_breaker import (
+    BREAKER_CLOSED,
+    BREAKER_HALF_OPEN,
+    BREAKER_OPEN,
+    CircuitBreakers,
+    CircuitBreakerSettings,
+    ServiceUnavailable,
+    circuit_breaker_settings_from_config,
+)
+from textbook.jobs import JOB_DEFERRED, JOB_SUCCEEDED, JobPool
+from textbook.model import LLM, SCHEMA_MODE_PROMPTED
+from textbook.provider_errors import ERROR_UNAVAILABLE, ModelError
+
+
+class FakeClock:
+    def __init__(self):
+        self.now = 0.0
+
+    def __call__(self):
+        return self.now
+
+
+class FakeResponse:
+    def __init__(self, text):
+        self._text = text
+
+    def text(self):
+        return self._text
+
+
+class FakeModel:
+    """A text model failing with the queued errors, then answering"""
+
+    def __init__(self, errors):
+        self.errors = list(errors)
+        self.calls = 0
+
+    def prompt(self, prompt, attachments=None, **kwargs):
+        self.calls += 1
+        if self.errors:
+            raise self.errors.pop(0)
+        return FakeResponse("Fine")
+
+
+def breaker_llm(breakers: CircuitBreakers, errors) -> LLM:
+    model = LLM.__new__(LLM)
+    model.logger = structlog.get_logger("LLM")
+    model.model_name = "fake"
+    model.text_model = FakeModel(errors)
+    model.schema_mode = SCHEMA_MODE_PROMPTED
+    model.circuit_breaker = breakers.get("llm:fake")
+    return model
+
+
+class TestCircuitBreaker:
+    """Test suite for opening, probing and closing the breakers"""
+
+    @pytest.fixture
+    def clock(self):
+        return FakeClock()
+
+    @pytest.fixture
+    def breakers(self, clock):
+        return CircuitBreakers(CircuitBreakerSettings(failure_threshold=2, reset_seconds=30), clock)
+
+    def test_settings_from_config(self):
+        """Test that the defaults apply and invalid settings are rejected"""
+        assert circuit_breaker_settings_from_config({}) == CircuitBreakerSettings()
+        settings = circuit_breaker_settings_from_config({"circuit_breaker": {"failure_threshold": 3, "reset_seconds": 10}})
+        assert (settings.failure_threshold, settings.reset_seconds) == (3, 10)
+        for section in ({"failure_threshold": 0}, {"failure_threshold": True}, {"reset_seconds": 0}, {"timeout": 5}):
+            with pytest.raises(ValueError):
+                circuit_breaker_settings_from_config({"circuit_breaker": section})
+
+    def test_opens_and_probes(self, breakers, clock):
+        """Test that the breaker opens after consecutive failures and lets a single probe through once it is due"""
+        closed = []
+        breakers.on_closed(closed.append)
+        breaker = breakers.get("mineru")
+        breaker.record_failure()
+        breaker.record_success()
+        breaker.record_failure()
+        breaker.check()
+        breaker.record_failure()
+        assert breaker.status().state == BREAKER_OPEN
+
+        with pytest.raises(ServiceUnavailable) as error:
+            breaker.check()
+        assert error.value.category == ERROR_UNAVAILABLE and error.value.retry_in_seconds == 30
+
+        clock.now = 31
+        breaker.check()
+        assert breaker.status().state == BREAKER_HALF_OPEN
+        with pytest.raises(ServiceUnavailable):
+            breaker.check()  # Another probe is under way
+        breaker.record_failure()
+        assert (breaker.status().state, breaker.status().retry_in_seconds) == (BREAKER_OPEN, 30)
+
+        clock.now = 62
+        breaker.check()
+        breaker.record_success()
+        status = breakers.snapshot()[0]
+        assert (status.service, status.state, status.trips, status.rejected) == ("mineru", BREAKER_CLOSED, 1, 2)
+        assert closed == ["mineru"]
+
+    def test_only_provider_outages_count(self, breakers):
+        """Test that server errors and timeouts open the LLM's breaker while rejected requests do not"""
+        model = breaker_llm(breakers, [Exception("400 API key not valid"), Exception("400 API key not valid"), Exception("503 Service Unavailable"), Exception("Deadline exceeded: timed out")])
+        for _ in range(2):
+            with pytest.raises(ModelError):
+                model._complete("Explain")
+        assert model.circuit_breaker.status().state == BREAKER_CLOSED
+        for _ in range(2):
+            with pytest.raises(ModelError):
+                model._complete("Explain")
+        assert model.circuit_breaker.status().state == BREAKER_OPEN
+
+        with pytest.raises(ServiceUnavailable):
+            model._complete("Explain")
+        assert model.text_model.calls == 4
+
+    def test_jobs_are_deferred_and_resumed(self, breakers, clock):
+        """Test that a job failing fast is deferred, and resumed once the breaker closes"""
+        model = breaker_llm(breakers, [Exception("503 Service Unavailable")] * 2)
+        pool = JobPool()
+        pool.register("document_summary", lambda params, handle: {"summary": model._complete("Summarize")})
+        breakers.on_closed(lambda service: pool.resume_deferred())
+        for _ in range(2):
+            pool.wait(pool.submit("document_summary").job_id, timeout=5)
+
+        deferred = pool.wait(pool.submit("document_summary").job_id, timeout=5)
+        assert (deferred.status, deferred.error_category) == (JOB_DEFERRED, ERROR_UNAVAILABLE)
+
+        clock.now = 31
+        assert model._complete("Probe") == "Fine"
+        pool.shutdown()
+        assert pool.get_job_status(deferred.job_id).status == JOB_SUCCEEDED
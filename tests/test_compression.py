@@ -0,0 +1,94 @@
+"""
+Test cases for storing large text zstd-compressed with transparent decompression
+"""
+import sqlite3
+
+import pytest
+
+from textbook.compression import (
+    ZSTD_MAGIC,
+    CompressionSettings,
+    compress_text,
+    compression_settings_from_config,
+    configure_compression,
+    decompress_text,
+    read_markdown,
+    write_markdown,
+)
+from textbook.content import CHUNK_PAGE, ContentChunk, source_markdown, store_chunks
+from textbook.library import add_upload
+
+MARKDOWN = "# Groups\n\n" + "A group is a set with an associative operation, an identity and inverses.\n" * 100
+
+
+def stored_type(db_path: str, document_id: int) -> str:
+    # How SQLite stores the text of a document's first chunk, text or blob
+    with sqlite3.connect(db_path) as connection:
+        return connection.execute("SELECT typeof(text) FROM content_chunk_info WHERE document_id = ?", (document_id,)).fetchone()[0]
+
+
+class TestCompression:
+    """Test suite for the compressed columns and markdown files"""
+
+    @pytest.fixture
+    def database(self, database):
+        configure_compression(CompressionSettings())
+        return database
+
+    def test_settings_from_config(self):
+        """Test that compression is on by default and invalid settings are rejected"""
+        assert compression_settings_from_config({}) == CompressionSettings()
+        settings = compression_settings_from_config({"compression": {"level": 19, "min_bytes": 0}})
+        assert (settings.enabled, settings.level, settings.min_bytes) == (True, 19, 0)
+        for section in ({"enabled": "yes"}, {"level": 0}, {"level": 23}, {"min_bytes": -1}, {"algorithm": "gzip"}):
+            with pytest.raises(ValueError):
+                compression_settings_from_config({"compression": section})
+
+    def test_only_large_text_is_compressed(self):
+        """Test that long text is compressed, short text and disabled compression keep it as UTF-8"""
+        compressed = compress_text(MARKDOWN)
+        assert compressed.startswith(ZSTD_MAGIC) and len(compressed) < len(MARKDOWN) // 10
+        assert decompress_text(compressed) == MARKDOWN
+        assert compress_text("Groups") == b"Groups"
+        assert compress_text(MARKDOWN, CompressionSettings(enabled=False)) == MARKDOWN.encode("utf-8")
+        assert decompress_text("Gruppen über Körpern".encode("utf-8")) == "Gruppen über Körpern"
+
+    def test_chunks_are_stored_compressed(self, database):
+        """Test that chunk text is compressed in the database and read back as text"""
+        document = add_upload(database, "Algebra", "algebra.pdf")
+        store_chunks(database, document.document_id, [ContentChunk(CHUNK_PAGE, 0, 0, len(MARKDOWN), MARKDOWN)])
+        assert stored_type(database.db_path, document.document_id) == "blob"
+        assert database.get_content_chunks(document.document_id, CHUNK_PAGE)[0].text == MARKDOWN
+
+    def test_text_stored_before_is_compressed_later(self, database):
+        """Test that rows stored as plain text are read as they are and compressed by compress_stored_text"""
+        document = add_upload(database, "Algebra", "algebra.pdf")
+        with sqlite3.connect(database.db_path) as connection:
+            connection.execute(
+                "INSERT INTO content_chunk_info (chunk_kind, chunk_index, char_start, char_end, text, document_id) VALUES (?, 0, 0, ?, ?, ?)",
+                (CHUNK_PAGE, len(MARKDOWN), MARKDOWN, document.document_id),
+            )
+        assert database.get_content_chunks(document.document_id, CHUNK_PAGE)[0].text == MARKDOWN
+
+        assert database.compress_stored_text()["content_chunk_info"] == 1
+        assert stored_type(database.db_path, document.document_id) == "blob"
+        assert database.get_content_chunks(document.document_id, CHUNK_PAGE)[0].text == MARKDOWN
+        assert database.compress_stored_text()["content_chunk_info"] == 0
+        database.vacuum()
+
+    def test_markdown_files(self, database, tmpdir):
+        """Test that the OCR markdown is kept compressed next to the PDF and found by the readers"""
+        markdown_path = tmpdir / "algebra.md"
+        markdown_path.write_text(MARKDOWN, encoding="utf-8")
+        assert read_markdown(markdown_path) == MARKDOWN
+
+        written = write_markdown(markdown_path, MARKDOWN)
+        assert written == tmpdir / "algebra.md.zst" and not markdown_path.exists()
+        assert read_markdown(markdown_path) == MARKDOWN
+
+        document = add_upload(database, "Algebra", "algebra.pdf")
+        assert source_markdown(database, document.document_id, str(tmpdir)) == MARKDOWN
+
+        assert write_markdown(markdown_path, "# Short") == markdown_path
+        assert not written.exists() and read_markdown(markdown_path) == "# Short"
+        assert read_markdown(tmpdir / "missing.md") is None
@@ -0,0 +1,71 @@
+"""
+Test cases for splitting OCR output into page and section chunks
+"""
+
+import pytest
+
+from textbook.content import CHUNK_PAGE, CHUNK_SECTION, chunk_content, get_page_markdown, get_pages_markdown, get_section, get_sections, store_chunks
+from textbook.library import add_upload
+
+CONTENT_LIST = [
+    {"type": "text", "text": "Groups", "text_level": 1, "page_idx": 0},
+    {"type": "text", "text": "A group is a set with an operation.", "page_idx": 0},
+    {"type": "text", "text": "Subgroups", "text_level": 2, "page_idx": 1},
+    {"type": "equation", "text": "$$H \\leq G$$", "page_idx": 1},
+    {"type": "image", "img_path": "images/a.jpg", "image_caption": [], "page_idx": 1},
+    {"type": "text", "text": "Rings", "text_level": 1, "page_idx": 2},
+    {"type": "table", "table_caption": ["Table 1"], "table_body": "<table></table>", "page_idx": 2},
+]
+
+
+class TestChunking:
+    """Test suite for splitting markdown by page and heading"""
+
+    def test_pages_and_sections(self):
+        """Test that every page and heading becomes a chunk whose offsets point into the stored markdown"""
+        markdown, chunks = chunk_content("ignored", CONTENT_LIST)
+        pages = [chunk for chunk in chunks if chunk.kind == CHUNK_PAGE]
+        sections = [chunk for chunk in chunks if chunk.kind == CHUNK_SECTION]
+
+        assert [page.text for page in pages] == [
+            "# Groups\n\nA group is a set with an operation.",
+            "## Subgroups\n\n$$H \\leq G$$",
+            "# Rings\n\nTable 1\n\n<table></table>",
+        ]
+        assert [(section.title, section.level, section.page_start, section.page_end) for section in sections] == [
+            ("Groups", 1, 0, 1),
+            ("Subgroups", 2, 1, 1),
+            ("Rings", 1, 2, 2),
+        ]
+        for chunk in chunks:
+            assert markdown[chunk.char_start:chunk.char_end] == chunk.text
+        assert sections[0].text.endswith("$$H \\leq G$$")
+
+    def test_markdown_without_pages(self):
+        """Test that without a content list the markdown is kept and only split into sections"""
+        markdown, chunks = chunk_content("Preface\n\n# One\ntext\n## One.a\nmore\n# Two\nend\n")
+        assert markdown == "Preface\n\n# One\ntext\n## One.a\nmore\n# Two\nend\n"
+        assert [(chunk.kind, chunk.title, chunk.page_start) for chunk in chunks] == [(CHUNK_SECTION, "One", None), (CHUNK_SECTION, "One.a", None), (CHUNK_SECTION, "Two", None)]
+        assert chunks[0].text == "# One\ntext\n## One.a\nmore"
+
+
+class TestChunkStore:
+    """Test suite for storing and reading chunks of a document"""
+
+    def test_retrieval(self, database):
+        """Test that pages, page ranges and sections can be read back, and storing again replaces them"""
+        document = add_upload(database, "algebra", "a.pdf", 3)
+        _, chunks = chunk_content("", CONTENT_LIST)
+        assert store_chunks(database, document.document_id, chunks) == 6
+
+        assert get_page_markdown(database, document.document_id, 1).text == "## Subgroups\n\n$$H \\leq G$$"
+        assert get_page_markdown(database, document.document_id, 3) is None
+        assert get_pages_markdown(database, document.document_id, 1, 2).startswith("## Subgroups")
+        assert [section.title for section in get_sections(database, document.document_id)] == ["Groups", "Subgroups", "Rings"]
+        assert get_section(database, document.document_id, 2).text.startswith("# Rings")
+        with pytest.raises(ValueError):
+            get_pages_markdown(database, document.document_id, 2, 1)
+
+        store_chunks(database, document.document_id, chunk_content("# Only\ntext")[1])
+        assert [section.title for section in get_sections(database, document.document_id)] == ["Only"]
+        assert get_page_markdown(database, document.document_id, 0) is None
@@ -0,0 +1,76 @@
+"""
+Test cases for looking up the API key of the LLM provider
+"""
+import tempfile
+from pathlib import Path
+
+import pytest
+
+from textbook.credentials import (
+    KEYRING_SERVICE,
+    SOURCE_CONFIG,
+    SOURCE_ENV,
+    SOURCE_FILE,
+    SOURCE_KEYRING,
+    SecretString,
+    credential_settings_from_config,
+    resolve_api_key,
+)
+
+
+def no_keyring(service, name):
+    return None
+
+
+class TestCredentials:
+    """Test suite for the precedence of the key sources and masking keys"""
+
+    def test_secret_is_masked(self):
+        """Test that a secret does not show itself when printed"""
+        secret = SecretString("sk-123")
+        assert "sk-123" not in str(secret) and "sk-123" not in repr(secret) and "sk-123" not in f"{secret!r} {[secret]}"
+        assert secret.reveal() == "sk-123"
+        assert secret == SecretString("sk-123")
+
+    def test_environment_comes_first(self):
+        """Test that the configured variable wins over the usual ones, and those over the config file"""
+        config = {"credentials": {"gemini": {"env": "MY_KEY", "api_key": "from-config"}}}
+        credential = resolve_api_key("gemini", config, {"MY_KEY": "mine", "LLM_GEMINI_KEY": "usual"}, no_keyring)
+        assert (credential.secret.reveal(), credential.source, credential.location) == ("mine", SOURCE_ENV, "MY_KEY")
+        credential = resolve_api_key("gemini", config, {"GOOGLE_API_KEY": "google"}, no_keyring)
+        assert (credential.secret.reveal(), credential.location) == ("google", "GOOGLE_API_KEY")
+        credential = resolve_api_key("gemini", config, {"LLM_GEMINI_KEY": "  "}, no_keyring)
+        assert (credential.secret.reveal(), credential.source) == ("from-config", SOURCE_CONFIG)
+
+    def test_key_file_and_keyring(self):
+        """Test that a key file wins over the config entry and the keyring is asked last"""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            key_file = Path(tmpdir) / "gemini.key"
+            key_file.write_text("from-file\n", encoding="utf-8")
+            config = {"credentials": {"gemini": {"api_key_file": str(key_file), "api_key": "from-config"}}}
+            credential = resolve_api_key("gemini", config, {}, no_keyring)
+            assert (credential.secret.reveal(), credential.source) == ("from-file", SOURCE_FILE)
+
+            key_file.write_text("", encoding="utf-8")
+            with pytest.raises(ValueError):
+                resolve_api_key("gemini", config, {}, no_keyring)
+            with pytest.raises(ValueError):
+                resolve_api_key("gemini", {"credentials": {"gemini": {"api_key_file": str(Path(tmpdir) / "missing.key")}}}, {}, no_keyring)
+
+        asked = []
+
+        def keyring(service, name):
+            asked.append((service, name))
+            return "from-keyring"
+
+        credential = resolve_api_key("gemini", {}, {}, keyring)
+        assert (credential.secret.reveal(), credential.source, asked) == ("from-keyring", SOURCE_KEYRING, [(KEYRING_SERVICE, "gemini")])
+        assert resolve_api_key("gemini", {"credentials": {"gemini": {"keyring": False}}}, {}, keyring) is None
+        assert resolve_api_key("openai", {}, {"LLM_GEMINI_KEY": "gemini"}, no_keyring) is None
+
+    def test_invalid_settings(self):
+        """Test that unknown or mistyped settings are rejected"""
+        for section in ({"token": "x"}, {"api_key": ""}, {"env": 3}, {"keyring": "yes"}):
+            with pytest.raises(ValueError):
+                credential_settings_from_config({"credentials": {"gemini": section}}, "gemini")
+        assert credential_settings_from_config({}, "gemini").keyring
@@ -0,0 +1,107 @@
+"""
+Test cases for cross reference detection and resolution
+"""
+
+import pymupdf
+import pytest
+
+from textbook.cross_reference import (
+    CrossReferenceIndex,
+    find_labeled_entities,
+    find_references,
+    link_references,
+)
+from textbook.reader import LazyTextbookReader
+
+PAGE = """Theorem 3.2 (Bolzano-Weierstrass). Every bounded sequence has a convergent subsequence.
+Proof. By Lemma 3.1 and Definition 2.4 we have
+    f(x) = x^2 + 1      (3.5)
+which together with (3.5) and Eq. (2.1) shows the claim, see Section 2.3 and §3.1.
+Corollary 3.3. The same holds in R^n (see Theorem 3.2).
+It costs (0.5) dollars."""
+
+
+class TestCrossReferenceDetection:
+    """Test suite for finding numbered entities and references in page text"""
+
+    def test_find_labeled_entities(self):
+        """Test that numbered blocks and displayed equations are indexed where they are stated"""
+        entities = find_labeled_entities(PAGE, 4)
+
+        assert [(entity.kind, entity.label) for entity in entities] == [("theorem", "3.2"), ("corollary", "3.3"), ("equation", "3.5")]
+        assert entities[0].name == "Bolzano-Weierstrass"
+        assert entities[2].statement == "f(x) = x^2 + 1"
+
+    def test_find_references(self):
+        """Test that references are found with their kind and label, but entity headers are not"""
+        references = find_references(PAGE, 4)
+
+        assert [(reference.kind, reference.label, reference.text) for reference in references] == [
+            ("lemma", "3.1", "Lemma 3.1"),
+            ("definition", "2.4", "Definition 2.4"),
+            ("equation", "3.5", "(3.5)"),
+            ("equation", "2.1", "Eq. (2.1)"),
+            ("section", "2.3", "Section 2.3"),
+            ("section", "3.1", "§3.1"),
+            ("theorem", "3.2", "Theorem 3.2"),
+            ("equation", "0.5", "(0.5)"),
+        ]
+        assert PAGE[references[0].start:references[0].end] == "Lemma 3.1"
+        assert references[2].bare and not references[3].bare
+
+
+class TestCrossReferenceResolution:
+    """Test suite for resolving references and linking text"""
+
+    @pytest.fixture
+    def index(self):
+        return CrossReferenceIndex(
+            entities=[(1, "theorem", "3.2", 4), (2, "lemma", "3.1", 3), (3, "equation", "3.5", 4), (4, "theorem", "3.2", 9)],
+            sections=[(7, "2.3", 10)],
+            chapters=[(9, "2", 8)],
+        )
+
+    def test_resolve(self, index):
+        """Test resolution to entities, sections and chapters"""
+        assert index.resolve("theorem", "3.2").anchor == "entity-1"
+        assert index.resolve("theorem", "3.2").page_number == 4
+        assert index.resolve("section", "2.3").anchor == "section-7"
+        assert index.resolve("chapter", "2").anchor == "chapter-9"
+        assert index.resolve("figure", "1.1") is None
+
+    def test_shared_counter_and_section_fallback(self, index):
+        """Test that theorem-like kinds share numbers and a missing section falls back to the chapter"""
+        assert index.resolve("proposition", "3.1").anchor == "entity-2"
+        assert index.resolve("section", "2").anchor == "chapter-9"
+
+    def test_link_references(self, index):
+        """Test that only resolvable references become links"""
+        linked = link_references("Use Theorem 3.2 and (3.5) as in Section 2.3, not Table 9.9.", index)
+        assert linked == "Use [Theorem 3.2](#entity-1) and [(3.5)](#entity-3) as in [Section 2.3](#section-7), not Table 9.9."
+
+
+class TestCrossReferenceIndexing:
+    """Test suite for building the index of a book"""
+
+    @pytest.fixture
+    def pdf_path(self, tmpdir):
+        path = tmpdir / "analysis.pdf"
+        document = pymupdf.open()
+        document.new_page().insert_text((72, 72), "Theorem 1.1. Every convergent sequence is bounded, which is a long enough page.")
+        document.new_page().insert_text((72, 72), "By Theorem 1.1 the sequence is bounded, and Lemma 7.7 does not exist here.")
+        document.save(path)
+        document.close()
+        return path
+
+    def test_update_cross_references(self, database, pdf_path):
+        """Test that entities are stored and references resolved to the page they point to"""
+        book = database.create_book("analysis", "me", "sequences", pdf_path.stem, 2)
+
+        with LazyTextbookReader(pdf_path, None, database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            assert reader.update_cross_references() == (1, 2)
+
+        entity = database.get_entities_by_book_id(book.book_id)[0]
+        references = database.get_cross_references_by_book_id(book.book_id, page_number=1)
+        assert [(reference.reference_text, reference.target_entity_id) for reference in references] == [("Theorem 1.1", entity.entity_id), ("Lemma 7.7", None)]
+        assert references[0].target_page_number == 0
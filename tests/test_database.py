@@ -2,8 +2,6 @@
 Unit tests for TextBookContext class
 """
 import os
-# Set dummy API key to avoid import errors (tests don't use LLM)
-os.environ.setdefault("LLM_GEMINI_KEY", "dummy_key_for_testing")
 
 import pytest
 import tempfile
@@ -368,4 +366,45 @@ class TestTextBookDatabase:
         
         retrieved = context.session.get(ChapterInfo, 1)
         assert retrieved.end_page_number is None
-        assert retrieved.summary is None
\ No newline at end of file
+        assert retrieved.summary is None
+
+class TestSchemaUpgrade:
+    """Test suite for opening databases created by an earlier version"""
+
+    # book_info and page_info as the first version created them
+    BASELINE_SCHEMA = """
+    CREATE TABLE book_info (
+        book_id INTEGER PRIMARY KEY AUTOINCREMENT, book_name VARCHAR, book_author VARCHAR, book_pages INTEGER,
+        book_keywords VARCHAR, book_summary TEXT, book_embedding BLOB, book_file_name VARCHAR,
+        book_toc_end_page INTEGER, book_alignment_offset INTEGER
+    );
+    CREATE TABLE page_info (
+        page_id INTEGER PRIMARY KEY AUTOINCREMENT, page_number INTEGER NOT NULL, summary TEXT, embedding BLOB,
+        related_chapters BLOB, related_sections BLOB, book_id INTEGER NOT NULL REFERENCES book_info (book_id) ON DELETE CASCADE
+    );
+    INSERT INTO book_info (book_name, book_author, book_pages, book_file_name) VALUES ('Topology', 'Munkres', 3, 'topology.pdf');
+    INSERT INTO page_info (page_number, summary, book_id) VALUES (1, 'Open sets', 1);
+    """
+
+    def test_baseline_database_is_upgraded(self):
+        """Test that the columns added since are created, the rows kept, and opening again changes nothing"""
+        import sqlite3
+        from textbook.database import add_missing_columns
+
+        with tempfile.TemporaryDirectory() as tmpdir:
+            db_path = os.path.join(tmpdir, "old.db")
+            with sqlite3.connect(db_path) as connection:
+                connection.executescript(self.BASELINE_SCHEMA)
+
+            database = TextBookDatabase(db_path=db_path)
+            book = database.get_book_by_id(1)
+            assert book.book_name == "Topology" and book.book_ingestion_mode is None and book.archived_at is None
+            page = database.get_page_by_book_id_and_page_number(1, 1)
+            assert page.summary == "Open sets" and page.transcription is None
+            database.save_page_transcription(1, 1, "Open sets are unions of balls", 0.9, "manual")
+            assert add_missing_columns(database.engine) == []
+            database.close()
+
+            database = TextBookDatabase(db_path=db_path)
+            assert database.get_page_by_book_id_and_page_number(1, 1).transcription == "Open sets are unions of balls"
+            database.close()
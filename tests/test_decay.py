@@ -0,0 +1,76 @@
+"""
+Test cases for mastery decay and the refresh sessions of the study planner
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.database import GradingJudgmentInfo
+from textbook.decay import DECAY_HALF_LIFE_DAYS, days_until_forgotten, projected_mastery, refresh_suggestions
+from textbook.grading import JUDGMENT_INITIAL
+from textbook.planner import study_digest, study_plan
+from textbook.preferences import Preferences
+from textbook.quiz import TopicMastery, book_mastery
+
+NOW = datetime(2026, 3, 1, 12, tzinfo=timezone.utc)
+
+
+class TestDecay:
+    """Test suite for projecting the mastery of chapters left alone"""
+
+    def test_mastery_halves_over_half_life(self):
+        """Test that the mastery halves over the half-life, which longer review intervals stretch"""
+        mastery = TopicMastery(1, 0.8, 3, NOW - timedelta(days=DECAY_HALF_LIFE_DAYS), None, 0.0)
+        assert projected_mastery(mastery, NOW) == pytest.approx(0.4)
+        spaced = TopicMastery(1, 0.8, 3, NOW - timedelta(days=DECAY_HALF_LIFE_DAYS), 4.0, 0.0)
+        assert projected_mastery(spaced, NOW) > projected_mastery(mastery, NOW)
+        assert projected_mastery(TopicMastery(1, 0.0, 0, None, None, 0.0), NOW) == 0.0
+
+    def test_days_until_forgotten(self):
+        """Test when a chapter drops below the threshold, and that weak or unattempted chapters never do"""
+        mastery = TopicMastery(1, 1.0, 3, NOW, None, 0.0)
+        assert days_until_forgotten(mastery, NOW) == pytest.approx(DECAY_HALF_LIFE_DAYS)
+        assert days_until_forgotten(mastery, NOW + timedelta(days=30)) == 0.0
+        assert days_until_forgotten(TopicMastery(1, 0.4, 3, NOW, None, 0.0), NOW) is None
+        assert days_until_forgotten(TopicMastery(1, 0.0, 0, None, None, 0.0), NOW) is None
+
+
+class TestRefreshPlanning:
+    """Test suite for suggesting and planning the refresh of chapters about to be forgotten"""
+
+    @pytest.fixture
+    def book_id(self, database):
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        database.try_create_chapter_info(book.book_id, "Sequences", "1", 1, 10)
+        database.try_create_chapter_info(book.book_id, "Series", "2", 11, 20)
+        exercise_id = database.create_exercise(book.book_id, 4, "Show that 1/n converges")
+        for _ in range(3):
+            database.create_attempt(exercise_id, "Converges", [GradingJudgmentInfo(model_name="test", reason=JUDGMENT_INITIAL, score=1.0, is_correct=True, confidence=0.9, feedback="")])
+        return book.book_id
+
+    def _forgotten_at(self, database, book_id):
+        now = datetime.now(timezone.utc)
+        mastery = next(mastery for mastery in book_mastery(database, book_id).values() if mastery.attempts)
+        return now + timedelta(days=days_until_forgotten(mastery, now) + 0.5)
+
+    def test_suggested_before_forgotten(self, database, book_id):
+        """Test that a practiced chapter is suggested for a refresh only once it is about to be forgotten"""
+        assert refresh_suggestions(database, datetime.now(timezone.utc), book_id) == []
+
+        later = self._forgotten_at(database, book_id)
+        suggestions = refresh_suggestions(database, later, book_id)
+        assert [suggestion.title for suggestion in suggestions] == ["Sequences"]
+        assert suggestions[0].message == "You're about to forget Sequences"
+        assert suggestions[0].projected_mastery < suggestions[0].mastery
+        assert refresh_suggestions(database, later, book_id, threshold=0.01) == []
+
+    def test_plan_and_digest(self, database, book_id):
+        """Test that the plan puts the refresh on its day and the digest lists it"""
+        later = self._forgotten_at(database, book_id)
+        plan = study_plan(database, Preferences("alice"), 3, book_id, now=later)
+        assert len(plan) == 3 and [suggestion.title for suggestion in plan[0].refresh] == ["Sequences"]
+        assert all(day.refresh == [] for day in plan[1:])
+
+        digest = study_digest(database, "alice", book_id, now=later)
+        assert [suggestion.chapter_id for suggestion in digest.refresh] == [plan[0].refresh[0].chapter_id]
+        assert digest.chapter_quizzes == []
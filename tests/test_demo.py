@@ -0,0 +1,37 @@
+"""
+Test cases for seeding the demo document
+"""
+
+from textbook.demo import find_demo, is_first_run, seed_demo
+from textbook.digest import document_batches
+from textbook.hints import revealed_hints
+from textbook.solutions import solution_steps
+
+
+class TestDemo:
+    """Test suite for the demo document and its pre-generated content"""
+
+    def test_demo_is_seeded_with_its_content(self, database):
+        """Test that the demo has its chapters, summary, glossary and every problem its solution and hint ladder"""
+        assert is_first_run(database)
+        document, created = seed_demo(database)
+        assert created and not is_first_run(database)
+        assert document.summary and database.get_glossary(document.document_id)
+        assert len(document_batches(database, document.document_id)) == 3
+
+        problems = database.get_problems(document.document_id)
+        assert len(problems) == 6 and all(problem.section_index is not None for problem in problems)
+        for problem in problems:
+            assert solution_steps(database.get_solution(problem.problem_id))
+            assert len(revealed_hints(database, problem.problem_id, 3)) == 3
+
+    def test_demo_is_seeded_once(self, database):
+        """Test that seeding again returns the demo seeded before, and seeds anew once it was deleted"""
+        document, _ = seed_demo(database)
+        again, created = seed_demo(database)
+        assert (again.document_id, created) == (document.document_id, False)
+        assert len(database.get_documents()) == 1
+
+        database.delete_document(document.document_id)
+        assert find_demo(database) is None
+        assert seed_demo(database)[1]
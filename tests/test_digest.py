@@ -0,0 +1,65 @@
+"""
+Test cases for summaries and glossaries of whole documents
+"""
+
+import pytest
+
+from tests.conftest import FakeLLM
+from textbook.content import chunk_content, store_chunks
+from textbook.digest import ChunkSummarySchema, GlossarySchema, GlossaryTermSchema, build_glossary, document_batches, summarize_document
+from textbook.library import add_upload
+from textbook.map_reduce import MapReduceError
+from textbook.provider_errors import ERROR_CONTENT_FILTER, ModelError
+
+CONTENT_LIST = [{"type": "text", "text": f"Page {page} on groups.", "page_idx": page} for page in range(40)]
+
+
+def summarize_batches(blocked=()):
+    """Summarizes each batch by its first page marker and lists a term per batch, refusing the pages in `blocked`"""
+
+    def answer(prompt, schema):
+        if any(f"[Page {page}]" in prompt for page in blocked):
+            raise ModelError(ERROR_CONTENT_FILTER, "blocked")
+        first_page = next((line.strip() for line in prompt.splitlines() if line.strip().startswith("[Page ")), None)
+        if schema is GlossarySchema:
+            return GlossarySchema(terms=[
+                GlossaryTermSchema(term="Group ", definition="A set with an associative operation.", page_number=0),
+                GlossaryTermSchema(term=f"term {first_page}", definition="Defined here.", page_number=8),
+            ])
+        assert schema is ChunkSummarySchema
+        return ChunkSummarySchema(summary=f"summary of {first_page}" if first_page else "combined")
+
+    return answer
+
+
+class TestDigest:
+    """Test suite for map-reduce summaries and glossaries"""
+
+    @pytest.fixture
+    def document_id(self, database):
+        document = add_upload(database, "groups", "a.pdf", 40)
+        store_chunks(database, document.document_id, chunk_content("", CONTENT_LIST)[1])
+        return document.document_id
+
+    def test_summary_combines_batches(self, database, document_id):
+        """Test that every batch of pages is summarized and the summaries are combined into the document's"""
+        assert len(document_batches(database, document_id)) == 5
+        llm = FakeLLM(summarize_batches())
+        summary = summarize_document(database, llm, document_id)
+
+        assert (summary.result, summary.failures) == ("combined", [])
+        assert len(llm.prompts) == 6
+        assert "summary of [Page 0]" in llm.prompts[-1] and "summary of [Page 32]" in llm.prompts[-1]
+        assert database.get_document(document_id).summary == "combined"
+
+    def test_glossary_skips_failed_batch(self, database, document_id):
+        """Test that terms are merged across batches and a refused batch is left out when the rest succeed"""
+        glossary = build_glossary(database, FakeLLM(summarize_batches(blocked=[8])), document_id)
+        assert [(failure.index, failure.error_category) for failure in glossary.failures] == [(1, ERROR_CONTENT_FILTER)]
+        terms = database.get_glossary(document_id)
+        assert [term.term for term in terms] == ["Group", "term [Page 0]", "term [Page 16]", "term [Page 24]", "term [Page 32]"]
+        assert terms[0].page_number == 0
+
+        with pytest.raises(MapReduceError):
+            build_glossary(database, FakeLLM(summarize_batches(blocked=[0, 8])), document_id)
+        assert len(database.get_glossary(document_id)) == 5
@@ -0,0 +1,103 @@
+"""
+Test cases for the per chapter entity index and flashcards generated from it
+"""
+import re
+
+import pymupdf
+import pytest
+
+from textbook.cross_reference import ENTITY_SOURCE_LLM, ENTITY_SOURCE_PATTERN
+from textbook.database import EntityInfo
+from textbook.reader import (
+    EntitiesSchema,
+    EntitySchema,
+    FlashcardSchema,
+    FlashcardsSchema,
+    LazyTextbookReader,
+)
+
+
+class FakeEntityLLM:
+    """Returns fixed entities and one card per listed entity, recording the prompts"""
+
+    def __init__(self):
+        self.prompts = []
+
+    def prompt_with_schema(self, prompt, schema):
+        self.prompts.append(prompt)
+        if schema is EntitiesSchema:
+            return EntitiesSchema(entities=[
+                EntitySchema(kind="Definition", label="1.1", name="bounded sequence", statement="A sequence is bounded if $|a_n| \\le M$.", page_number=1),
+                EntitySchema(kind="theorem", label="1.2", name="", statement="Every convergent sequence is bounded.", page_number=2),
+                EntitySchema(kind="proof", label="", name="", statement="Trivial.", page_number=2),
+            ])
+        assert schema is FlashcardsSchema
+        count = len(re.findall(r"^\s*\d+\. ", prompt, re.MULTILINE))
+        return FlashcardsSchema(cards=[FlashcardSchema(entity_number=number, front=f"front {number}", back=f"back {number}") for number in range(1, count + 1)] + [
+            FlashcardSchema(entity_number=99, front="out of range", back=""),
+        ])
+
+
+class TestEntityIndex:
+    """Test suite for the LLM entity extraction and entity flashcards"""
+
+    @pytest.fixture
+    def book(self, tmpdir, database):
+        # A cover page, so printed page 0 is PDF page 1
+        path = tmpdir / "analysis.pdf"
+        document = pymupdf.open()
+        for text in ["Cover", "Definition 1.1. A sequence is bounded if |a_n| <= M for all n.", "Theorem 1.2. Every convergent sequence is bounded."]:
+            document.new_page().insert_text((72, 72), text)
+        document.save(path)
+        document.close()
+
+        book = database.create_book("analysis", "me", "sequences", path.stem, 3)
+        database.update_book_alignment_offset(book.book_id, 1)
+        chapter_id = database.try_create_chapter_info(book.book_id, "Sequences", "1", 0, 1)
+        database.try_create_section_info(book.book_id, chapter_id, "Convergence", "1.1", 1, 1)
+        return book, chapter_id, path
+
+    def test_extract_chapter_entities(self, database, book):
+        """Test that entities keep their PDF page, get their section and unknown kinds are skipped"""
+        book, chapter_id, path = book
+        llm = FakeEntityLLM()
+        with LazyTextbookReader(path, llm, database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            entity_ids = reader.extract_chapter_entities(chapter_id)
+
+        assert len(entity_ids) == 2
+        assert "[Page 1]" in llm.prompts[0] and "[Page 2]" in llm.prompts[0]
+        entities = database.get_entities_by_book_id(book.book_id)
+        assert [(entity.entity_kind, entity.entity_label, entity.page_number, entity.entity_source) for entity in entities] == [
+            ("definition", "1.1", 1, ENTITY_SOURCE_LLM),
+            ("theorem", "1.2", 2, ENTITY_SOURCE_LLM),
+        ]
+        assert all(entity.chapter_id == chapter_id for entity in entities)
+        assert entities[0].section_id is None and entities[1].section_id is not None
+
+    def test_extraction_replaces_only_its_chapter_and_source(self, database, book):
+        """Test that re-extracting a chapter keeps the pattern entities"""
+        book, chapter_id, path = book
+        database.replace_entities(book.book_id, ENTITY_SOURCE_PATTERN, [EntityInfo(entity_kind="theorem", entity_label="1.2", page_number=2, chapter_id=chapter_id)])
+
+        with LazyTextbookReader(path, FakeEntityLLM(), database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            reader.extract_chapter_entities(chapter_id)
+            reader.extract_chapter_entities(chapter_id)
+
+        sources = [entity.entity_source for entity in database.get_entities_by_book_id(book.book_id)]
+        assert sorted(sources) == [ENTITY_SOURCE_LLM, ENTITY_SOURCE_LLM, ENTITY_SOURCE_PATTERN]
+
+    def test_generate_entity_flashcards(self, database, book):
+        """Test that cards are only generated for the requested kinds and point back to their entity"""
+        book, chapter_id, path = book
+        with LazyTextbookReader(path, FakeEntityLLM(), database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            reader.extract_chapter_entities(chapter_id)
+            card_ids = reader.generate_entity_flashcards(entity_kinds=["definition"])
+
+        assert len(card_ids) == 1
+        card = database.get_flashcards_by_book_id(book.book_id)[0]
+        definition = database.get_entities_by_book_id(book.book_id, ["definition"])[0]
+        assert (card.front, card.entity_id, card.entity_kind, card.chapter_id, card.page_number) == ("front 1", definition.entity_id, "definition", chapter_id, 1)
+        assert database.get_flashcards_by_book_id(book.book_id, entity_kinds=["theorem"]) == []
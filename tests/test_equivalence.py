@@ -0,0 +1,37 @@
+"""
+Test cases for checking symbolic answers against the final answer without the LLM
+"""
+import pytest
+
+from textbook.equivalence import answers_equivalent, is_expression, parse_expression
+
+sympy = pytest.importorskip("sympy")
+
+
+class TestEquivalence:
+    """Test suite for deciding whether two answers are the same expression"""
+
+    def test_equivalent_forms(self):
+        """Test that rewritten, factored and typeset forms of the same answer are equivalent"""
+        for answer, reference in (("2(x+1)", "2x+2"), ("x^2 - 1", "(x-1)(x+1)"), ("sin(x)^2 + cos(x)^2", "1"), ("y = 0.5", "1/2"), ("$2 \\cdot \\pi$", "2pi"), ("ln(e^3)", "3")):
+            assert answers_equivalent(answer, reference) is True, answer
+
+    def test_different_answers(self):
+        """Test that answers differing at some point are not equivalent"""
+        assert answers_equivalent("2x+1", "2x+2") is False
+        assert answers_equivalent("x^2", "x^3") is False
+
+    def test_only_expressions_are_parsed(self):
+        """Test that prose, code and huge powers never reach SymPy's parser, and are left to the LLM"""
+        for text in ("__import__('os').system('ls')", "lambda: 1", "The identity is unique", "x, y", "9^9^9^9", "10**1000"):
+            assert parse_expression(text) is None, text
+        assert not is_expression("exec(x)") and is_expression("sqrt(2) * sin(x)")
+        assert answers_equivalent("Take e = ef", "e") is None
+        assert parse_expression("E + I + N") == sympy.Symbol("E") + sympy.Symbol("I") + sympy.Symbol("N")
+
+    def test_nested_powers_are_not_expanded(self):
+        """Test that powers whose expansion would hang, nested or of long sums, are left to the LLM"""
+        for text in ("(((x+y)^99)^99)^99", "((x+y)^99)^99", "(a+b+c+d+f)^40"):
+            assert parse_expression(text) is None, text
+        assert answers_equivalent("(((x+y)^99)^99)^99", "x") is None
+        assert answers_equivalent("((x+1)^2)^3", "(x+1)^6") is True
@@ -0,0 +1,79 @@
+"""
+Test cases for estimating the tokens and cost of generation tasks
+"""
+
+import pytest
+
+from textbook.content import CHUNK_PAGE, ContentChunk, store_chunks
+from textbook.database import ProblemInfo, UsageInfo
+from textbook.estimate import DEFAULT_OUTPUT_RATIOS, MIN_CALIBRATION_CALLS, Estimator, output_ratios
+from textbook.library import add_upload
+from textbook.usage import ModelPrice
+
+PAGE_TEXT = "A group is a set with an associative operation, an identity and inverses. " * 20
+
+
+class TestEstimate:
+    """Test suite for estimating generation tasks before their jobs are submitted"""
+
+    @pytest.fixture
+    def document_id(self, database):
+        document = add_upload(database, "algebra", "a.pdf", 20)
+        pages = [ContentChunk(CHUNK_PAGE, index, index * len(PAGE_TEXT), (index + 1) * len(PAGE_TEXT), PAGE_TEXT) for index in range(20)]
+        store_chunks(database, document.document_id, pages)
+        return document.document_id
+
+    def estimator(self, database, ratios=None):
+        models = {"solution": "pro"}
+        return Estimator(database, lambda task: models.get(task, "flash"), {"flash": ModelPrice(1.0, 4.0)}, ratios)
+
+    def test_document_tasks_are_estimated_per_prompt(self, database, document_id):
+        """Test that every prompt of a task is counted, priced by its model and summed up for the document"""
+        estimate = self.estimator(database).document(document_id, ["problem_extraction", "document_summary", "glossary"])
+        extraction, summary, glossary = estimate.tasks
+        assert (extraction.prompts, summary.prompts, glossary.prompts) == (5, 4, 3)  # 4 and 8 pages a prompt, one combining the summaries
+        assert extraction.input_tokens > 20 * len(PAGE_TEXT) // 4
+        assert extraction.output_tokens == pytest.approx(extraction.input_tokens * DEFAULT_OUTPUT_RATIOS["problem_extraction"], abs=1)
+        assert summary.input_tokens > glossary.input_tokens
+        assert extraction.cost == pytest.approx(ModelPrice(1.0, 4.0).cost(extraction.input_tokens, extraction.output_tokens))
+        assert estimate.input_tokens == extraction.input_tokens + summary.input_tokens + glossary.input_tokens
+        assert estimate.cost == pytest.approx(extraction.cost + summary.cost + glossary.cost)
+        assert estimate.unpriced_tasks == []
+
+    def test_problems_are_assumed_before_extraction(self, database, document_id):
+        """Test that solutions are estimated per problem, for the problems expected until they are extracted"""
+        expected = self.estimator(database).document(document_id, ["solution"]).tasks[0]
+        assert expected.assumed_problems is not None and expected.assumed_problems > 0
+        assert expected.prompts == expected.assumed_problems
+        assert expected.model_name == "pro" and expected.cost is None
+
+        database.replace_problems(document_id, [
+            ProblemInfo(problem_number="1", statement="Show that the identity of a group is unique."),
+            ProblemInfo(problem_number="2", statement="Show that inverses are unique."),
+        ])
+        estimate = self.estimator(database).document(document_id)
+        solution = next(task for task in estimate.tasks if task.task == "solution")
+        assert solution.prompts == 2 and solution.assumed_problems is None
+        assert estimate.unpriced_tasks == ["solution"]
+        assert len(estimate.tasks) == 5
+
+    def test_output_ratio_is_calibrated_on_recorded_calls(self, database, document_id):
+        """Test that tasks with enough recorded calls use their measured ratio of output to input tokens"""
+        for _ in range(MIN_CALIBRATION_CALLS):
+            database.create_usage(UsageInfo(job_kind="glossary", model_name="flash", input_tokens=1000, output_tokens=2000, estimated=False))
+        database.create_usage(UsageInfo(job_kind="solution", model_name="pro", input_tokens=1000, output_tokens=100, estimated=False))
+        assert output_ratios(database) == {"glossary": 2.0}
+
+        glossary, extraction = self.estimator(database).document(document_id, ["glossary", "problem_extraction"]).tasks
+        assert glossary.calibrated and glossary.output_tokens == 2 * glossary.input_tokens
+        assert not extraction.calibrated
+
+    def test_invalid_requests_are_rejected(self, database, document_id):
+        """Test that unknown tasks, documents and documents without OCR output are refused"""
+        with pytest.raises(ValueError, match="Cannot estimate"):
+            self.estimator(database).document(document_id, ["answer_check"])
+        with pytest.raises(ValueError, match="not found"):
+            self.estimator(database).document(document_id + 1)
+        other = add_upload(database, "analysis", "b.pdf", 3)
+        with pytest.raises(ValueError, match="no OCR output"):
+            self.estimator(database).document(other.document_id, ["glossary"])
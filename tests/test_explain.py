@@ -0,0 +1,119 @@
+"""
+Test cases for explaining selected passages of a document
+"""
+
+import pytest
+import structlog
+
+from textbook.content import CHUNK_PAGE, CHUNK_SECTION
+from textbook.database import ContentChunkInfo
+from textbook.explain import (
+    MAX_SELECTION_CHARS,
+    ROLE_LEARNER,
+    ROLE_TUTOR,
+    document_passages,
+    explanation_prompt,
+    retrieve_passages,
+    save_exchange,
+    select_range,
+)
+from textbook.library import add_upload
+from textbook.model import LLM
+from textbook.provider_errors import ModelError
+
+
+def _chunk(index: int, char_start: int, text: str, kind: str = CHUNK_PAGE) -> ContentChunkInfo:
+    return ContentChunkInfo(chunk_kind=kind, chunk_index=index, char_start=char_start, char_end=char_start + len(text), text=text, page_start=index, page_end=index)
+
+
+class FakeStreamingModel:
+    """A text model streaming canned pieces, failing after them when given an error"""
+
+    def __init__(self, pieces, error=None):
+        self.pieces = pieces
+        self.error = error
+
+    def prompt(self, prompt, stream=False, **kwargs):
+        assert stream
+        yield from self.pieces
+        if self.error:
+            raise self.error
+
+
+def streaming_llm(pieces, error=None) -> LLM:
+    model = LLM.__new__(LLM)
+    model.logger = structlog.get_logger("LLM")
+    model.model_name = "fake"
+    model.text_model = FakeStreamingModel(pieces, error)
+    return model
+
+
+class TestExplain:
+    """Test suite for the selection, retrieval and prompt of passage explanations"""
+
+    def test_select_range(self):
+        """Test that the selection comes with the text around it, cut at the context size"""
+        markdown = "a" * 20 + "The limit exists." + "b" * 20
+        selection = select_range(markdown, 20, 37, context_chars=5)
+        assert selection.text == "The limit exists."
+        assert (selection.before, selection.after) == ("aaaaa", "bbbbb")
+        assert (selection.context_start, selection.context_end) == (15, 42)
+
+        for start, end in ((10, 10), (30, 20), (-1, 5), (0, len(markdown) + 1)):
+            with pytest.raises(ValueError):
+                select_range(markdown, start, end)
+        with pytest.raises(ValueError):
+            select_range("x" * (MAX_SELECTION_CHARS + 1), 0, MAX_SELECTION_CHARS + 1)
+        with pytest.raises(ValueError):
+            select_range("a   b", 1, 4)
+
+    def test_retrieve_passages(self):
+        """Test that the chunks sharing rare words rank first and the chunks around the selection are left out"""
+        chunks = [
+            _chunk(0, 0, "A sequence converges when its terms approach a limit."),
+            _chunk(1, 100, "The Cauchy criterion: a sequence is Cauchy when its terms get close to each other."),
+            _chunk(2, 200, "Series are sums of the terms of a sequence."),
+            _chunk(3, 300, "Every Cauchy sequence of real numbers converges, the reals are complete."),
+        ]
+        passages = retrieve_passages(chunks, "Why is every Cauchy sequence bounded?", 90, 190)
+        assert [passage.chunk_index for passage in passages][0] == 3
+        assert 1 not in [passage.chunk_index for passage in passages]
+        assert retrieve_passages(chunks, "topology", 0, 0) == []
+
+    def test_explanation_prompt(self):
+        """Test that the prompt carries the selection, its surroundings, the sources and the question"""
+        selection = select_range("Before. Every bounded sequence has a convergent subsequence. After.", 8, 60)
+        passages = retrieve_passages([_chunk(4, 500, "Bolzano and Weierstrass proved subsequence results.", CHUNK_SECTION)], selection.text, 0, 70)
+        prompt = explanation_prompt("Analysis", selection, passages, [], "Why bounded?")
+        assert "Why bounded?" in prompt and "Every bounded sequence" in prompt
+        assert "Before." in prompt and "After." in prompt
+        assert "[Section 4]" in prompt and "Earlier conversation:\n     None" in prompt
+
+    def test_stream(self):
+        """Test that the model's answer is streamed in pieces and a failure becomes a model error"""
+        assert list(streaming_llm(["The ", "limit"]).stream("Explain")) == ["The ", "limit"]
+        received = []
+        with pytest.raises(ModelError):
+            for piece in streaming_llm(["The "], RuntimeError("quota exceeded")).stream("Explain"):
+                received.append(piece)
+        assert received == ["The "]
+
+
+class TestTutoringSession:
+    """Test suite for saving explanations into tutoring sessions"""
+
+    def test_exchanges_are_saved_in_order(self, database):
+        """Test that the question and explanation are saved together and the history keeps the last messages"""
+        document = add_upload(database, "analysis", "a.pdf", 1)
+        database.replace_content_chunks(document.document_id, [_chunk(0, 0, "Every Cauchy sequence converges.")])
+        assert [chunk.chunk_index for chunk in document_passages(database, document.document_id)] == [0]
+
+        first = save_exchange(database, "s1", document.document_id, 0, 6, "Every\nCauchy", None, "It means ...")
+        second = save_exchange(database, "s1", document.document_id, 6, 12, "Cauchy", "Why?", "Because ...")
+        messages = database.get_tutoring_messages("s1")
+        assert [message.message_id for message in messages] == first + second
+        assert [message.role for message in messages] == [ROLE_LEARNER, ROLE_TUTOR, ROLE_LEARNER, ROLE_TUTOR]
+        assert messages[0].content == "> Every\n> Cauchy"
+        assert messages[2].content == "> Cauchy\n\nWhy?"
+        assert [message.message_id for message in database.get_tutoring_messages("s1", limit=2)] == second
+        assert database.get_tutoring_messages("other") == []
@@ -0,0 +1,107 @@
+"""
+Test cases for the external grading services merged with the model's grade
+"""
+import hashlib
+import hmac
+import json
+import types
+
+import pytest
+import requests
+
+from textbook.circuit_breaker import CircuitBreakers, CircuitBreakerSettings
+from textbook.database import ExerciseInfo, GradingJudgmentInfo
+from textbook.external_grading import (
+    SIGNATURE_HEADER,
+    TIMESTAMP_HEADER,
+    GradingServices,
+    grading_services_from_config,
+)
+from textbook.grading import JUDGMENT_EXTERNAL, JUDGMENT_INITIAL
+
+CONFIG = {"grading_services": {"code": {"url": "https://grader.test/grade", "secret_env": "GRADER_SECRET", "weight": 0.75}}}
+ENVIRON = {"GRADER_SECRET": "s3cret"}
+
+
+def _grade(score: float = 0.4, is_correct: bool = False) -> GradingJudgmentInfo:
+    return GradingJudgmentInfo(model_name="gemini", reason=JUDGMENT_INITIAL, score=score, is_correct=is_correct, confidence=0.6, feedback="The loop is off by one.")
+
+
+def _exercise(exercise_type: str = "code") -> ExerciseInfo:
+    return ExerciseInfo(exercise_id=7, exercise_description="Sum the first n integers", page_number=3, exercise_type=exercise_type, solution_code="sum(range(n + 1))", book_id=1)
+
+
+class FakeService:
+    """Answers the posted requests with a verdict, an HTTP status or an error, recording them"""
+
+    def __init__(self, verdict=None, status_code: int = 200, error: Exception = None):
+        self.verdict = verdict if verdict is not None else {"score": 1.0, "is_correct": True, "feedback": "All tests pass."}
+        self.status_code = status_code
+        self.error = error
+        self.requests = []
+
+    def __call__(self, url, body, headers, timeout):
+        self.requests.append((url, body, headers, timeout))
+        if self.error is not None:
+            raise self.error
+        response = types.SimpleNamespace(status_code=self.status_code, json=lambda: self.verdict)
+
+        def raise_for_status():
+            if self.status_code >= 400:
+                raise requests.exceptions.HTTPError(f"HTTP {self.status_code}", response=response)
+        response.raise_for_status = raise_for_status
+        return response
+
+
+class TestExternalGrading:
+    """Test suite for asking grading services and merging their verdicts"""
+
+    def _services(self, service: FakeService, failure_threshold: int = 2) -> GradingServices:
+        breakers = CircuitBreakers(CircuitBreakerSettings(failure_threshold=failure_threshold, reset_seconds=60))
+        return GradingServices(grading_services_from_config(CONFIG, ENVIRON), breakers, service, clock=lambda: 1700000000)
+
+    def test_settings_from_config(self):
+        """Test that services are read per exercise type and invalid settings are rejected"""
+        services = grading_services_from_config(CONFIG, ENVIRON)
+        assert list(services) == ["code"] and services["code"].weight == 0.75 and services["code"].timeout_seconds == 10
+        assert "s3cret" not in repr(services["code"])
+        assert grading_services_from_config({}, ENVIRON) == {}
+        for section in ({"url": "grader.test", "secret_env": "GRADER_SECRET"}, {"url": "https://grader.test", "secret_env": "MISSING"}, {"url": "https://grader.test", "secret_env": "GRADER_SECRET", "weight": 0}, {"url": "https://grader.test", "secret_env": "GRADER_SECRET", "retries": 2}):
+            with pytest.raises(ValueError):
+                grading_services_from_config({"grading_services": {"code": section}}, ENVIRON)
+
+    def test_requests_are_signed(self):
+        """Test that the service gets the answer with a signature it can verify from the timestamp and body"""
+        service = FakeService()
+        self._services(service).grade(_exercise(), "sum(range(n))", _grade())
+        url, body, headers, _ = service.requests[0]
+        assert url == "https://grader.test/grade" and headers[TIMESTAMP_HEADER] == "1700000000"
+        expected = hmac.new(b"s3cret", b"1700000000." + body, hashlib.sha256).hexdigest()
+        assert hmac.compare_digest(headers[SIGNATURE_HEADER], expected)
+        payload = json.loads(body)
+        assert (payload["answer"], payload["reference_solution"], payload["model_grade"]) == ("sum(range(n))", "sum(range(n + 1))", {"score": 0.4, "is_correct": False})
+
+    def test_verdict_is_merged_with_the_grade(self):
+        """Test that the score is weighted, the heavier verdict decides correctness and both feedbacks are kept"""
+        judgment = self._services(FakeService()).grade(_exercise(), "sum(range(n + 1))", _grade())
+        assert (judgment.reason, judgment.model_name) == (JUDGMENT_EXTERNAL, "grading:code")
+        assert judgment.score == pytest.approx(0.25 * 0.4 + 0.75 * 1.0)
+        assert judgment.is_correct is True
+        assert judgment.feedback == "The loop is off by one.\n\nAll tests pass."
+
+    def test_other_types_are_not_posted(self):
+        """Test that exercises of a type without a service keep the model's grade"""
+        service = FakeService()
+        assert self._services(service).grade(_exercise("text"), "n(n+1)/2", _grade()) is None
+        assert service.requests == []
+
+    def test_failing_service_keeps_the_model_grade(self):
+        """Test that errors and invalid verdicts fall back to the model's grade, and a service down is skipped"""
+        assert self._services(FakeService(verdict={"score": "high"})).grade(_exercise(), "answer", _grade()) is None
+        assert self._services(FakeService(status_code=422)).grade(_exercise(), "answer", _grade()) is None
+
+        service = FakeService(error=requests.exceptions.ConnectTimeout("timed out"))
+        services = self._services(service, failure_threshold=2)
+        for _ in range(3):
+            assert services.grade(_exercise(), "answer", _grade()) is None
+        assert len(service.requests) == 2
@@ -0,0 +1,89 @@
+"""
+Test cases for the review load forecast
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.database import FlashcardInfo
+from textbook.forecast import DEFAULT_RETENTION, MIN_REVIEWS_FOR_RETENTION, forecast_load, observed_retention, simulate_load, simulate_settings
+from textbook.preferences import Preferences
+from textbook.review import GRADE_AGAIN, GRADE_GOOD, CardSchedule, ReviewGrade, submit_reviews
+
+NOW = datetime(2024, 3, 1, 12, tzinfo=timezone.utc)
+
+
+class TestSimulation:
+    """Test suite for simulating the daily review load"""
+
+    def test_new_cards_respect_daily_limit(self):
+        """Test that new cards are introduced up to the limit until none are left"""
+        loads = simulate_load([], 25, 4, 10, retention=1.0, start=NOW)
+        assert [load.new_cards for load in loads] == [10, 10, 5, 0]
+        # Cards learned on the first day come back the day after
+        assert loads[1].reviews == 10
+
+    def test_overdue_cards_are_due_on_the_first_day(self):
+        """Test that cards due before today count towards today"""
+        overdue = [CardSchedule(6.0, 2.5, 2, 0, NOW - timedelta(days=3)) for _ in range(3)]
+        loads = simulate_load(overdue, 0, 2, 10, retention=1.0, start=NOW)
+        assert [load.reviews for load in loads] == [3, 0]
+
+    def test_load_balancing_spreads_pileups(self):
+        """Test that balancing lowers the busiest day of cards learned together"""
+        cards = [CardSchedule(6.0, 2.5, 2, 0, NOW) for _ in range(30)]
+        plain = simulate_load(cards, 0, 30, 0, retention=1.0, start=NOW)
+        balanced = simulate_load(cards, 0, 30, 0, load_balancing=True, retention=1.0, start=NOW)
+        assert max(load.reviews for load in balanced[1:]) < max(load.reviews for load in plain[1:])
+        assert sum(load.reviews for load in balanced) <= sum(load.reviews for load in plain)
+
+    def test_projected_retention_decays_between_reviews(self):
+        """Test that recall is full right after a review and drops towards the pass rate until the card is due"""
+        cards = [CardSchedule(4.0, 2.5, 2, 0, NOW + timedelta(days=4))]
+        loads = simulate_load(cards, 0, 4, 0, retention=0.8, start=NOW)
+        retentions = [load.projected_retention for load in loads]
+        assert all(later < earlier for earlier, later in zip(retentions, retentions[1:]))
+        assert retentions[-1] >= 0.8
+        assert simulate_load([], 0, 1, 0, start=NOW)[0].projected_retention is None
+
+    def test_observed_retention_needs_enough_reviews(self):
+        """Test that the pass rate counts every grade but again, and only with enough reviews"""
+        assert observed_retention([GRADE_GOOD] * (MIN_REVIEWS_FOR_RETENTION - 1)) is None
+        assert observed_retention([GRADE_GOOD] * 15 + [GRADE_AGAIN] * 5) == 0.75
+
+    def test_invalid_settings(self):
+        """Test that retention and days are checked"""
+        with pytest.raises(ValueError):
+            simulate_load([], 0, 10, 10, retention=1.5)
+        with pytest.raises(ValueError):
+            simulate_load([], 0, 0, 10)
+
+
+class TestForecastFromDatabase:
+    """Test suite for forecasting the load of stored cards"""
+
+    def test_forecast_counts_reviewed_and_new_cards(self, database):
+        """Test that reviewed cards come due after their interval and the rest are introduced as new cards"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 3)
+        card_ids = database.create_flashcards(book.book_id, [FlashcardInfo(front=f"front {index}", back="back") for index in range(5)])
+        now = datetime.now(timezone.utc)
+        submit_reviews(database, "alice", [ReviewGrade(card_id, GRADE_GOOD) for card_id in card_ids[:2]], now=now)
+
+        loads = forecast_load(database, Preferences("alice", daily_new_card_limit=2), 3, retention=1.0, now=now)
+        assert [load.new_cards for load in loads] == [2, 1, 0]
+        assert loads[1].reviews == 4
+
+    def test_simulation_compares_proposed_settings(self, database):
+        """Test that the proposed settings change only the proposed run, with the default pass rate without history"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 3)
+        database.create_flashcards(book.book_id, [FlashcardInfo(front=f"front {index}", back="back") for index in range(30)])
+        preferences = Preferences("alice", daily_new_card_limit=5)
+
+        what_if = simulate_settings(database, preferences, 10, daily_new_card_limit=10, now=NOW)
+        assert (what_if.retention, what_if.review_count) == (DEFAULT_RETENTION, 0)
+        assert what_if.proposed_preferences.daily_new_card_limit == 10
+        assert [load.new_cards for load in what_if.current[:2]] == [5, 5]
+        assert [load.new_cards for load in what_if.proposed[:2]] == [10, 10]
+
+        with pytest.raises(ValueError):
+            simulate_settings(database, preferences, 10, daily_new_card_limit=-1)
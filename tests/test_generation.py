@@ -0,0 +1,70 @@
+"""
+Test cases for the generation parameters of config.toml
+"""
+import pytest
+
+from tests.conftest import FakeLLM
+from textbook.generation import GenerationParams, GenerationSettings, deterministic, generation_settings_from_config
+from textbook.jobs import JOB_KIND_ANSWER_CHECK, JOB_KIND_HINT_LADDER, JOB_KIND_SOLUTION
+from textbook.profiles import DEFAULT_PROFILE, TASKS, ModelRouter
+
+CONFIG = {
+    "generation": {
+        "temperature": 0.7,
+        "max_tokens": 8192,
+        JOB_KIND_ANSWER_CHECK: {"temperature": 0},
+        JOB_KIND_HINT_LADDER: {"temperature": 1.2, "max_tokens": 2048},
+    },
+}
+
+
+class TestGeneration:
+    """Test suite for the [generation] section of config.toml"""
+
+    def test_settings_from_config(self):
+        """Test that task tables override the defaults and invalid settings are rejected"""
+        settings = generation_settings_from_config(CONFIG, TASKS)
+        assert settings.default == GenerationParams(0.7, 8192)
+        assert settings.for_task(JOB_KIND_ANSWER_CHECK) == GenerationParams(0.0, 8192)
+        assert settings.for_task(JOB_KIND_HINT_LADDER) == GenerationParams(1.2, 2048)
+        assert settings.for_task(JOB_KIND_SOLUTION) == settings.default
+        assert generation_settings_from_config({}, TASKS) == GenerationSettings()
+
+        for invalid in (
+            {"temperature": 3},
+            {"temperature": "0.2"},
+            {"max_tokens": 0},
+            {"max_tokens": True},
+            {"seed": -1},
+            {"seed": 1.5},
+            {"translation": {"temperature": 0}},
+            {JOB_KIND_ANSWER_CHECK: 0},
+            {JOB_KIND_ANSWER_CHECK: {"top_p": 0.9}},
+        ):
+            with pytest.raises(ValueError):
+                generation_settings_from_config({"generation": invalid}, TASKS)
+
+    def test_prompt_options(self):
+        """Test that only the parameters set are passed, max_tokens by the name the model knows"""
+        assert GenerationParams().prompt_options({"temperature", "max_tokens"}) == {}
+        assert GenerationParams(0.0, 100).prompt_options({"temperature", "max_tokens"}) == {"temperature": 0.0, "max_tokens": 100}
+        assert GenerationParams(max_tokens=100).prompt_options({"temperature", "max_output_tokens"}) == {"max_output_tokens": 100}
+        assert GenerationParams(seed=42).prompt_options({"seed"}) == {"seed": 42}
+
+    def test_seeded_generation(self):
+        """Test that seeds are read per task and deterministic parameters keep the answer length"""
+        settings = generation_settings_from_config({"generation": {"seed": 7, JOB_KIND_SOLUTION: {"seed": 42}}}, TASKS)
+        assert settings.default.seed == 7 and settings.for_task(JOB_KIND_SOLUTION).seed == 42
+        assert deterministic(GenerationParams(0.7, 8192), 3) == GenerationParams(0.0, 8192, 3)
+
+    def test_router_applies_task_parameters(self):
+        """Test that tasks with their own parameters get a model prompting with them, once per profile"""
+        settings = generation_settings_from_config(CONFIG, TASKS)
+        default = FakeLLM(model_name="flash", generation=settings.default)
+        router = ModelRouter(default, {}, {}, lambda profile: FakeLLM(model_name=profile.model_name), settings)
+
+        assert router.model_for(JOB_KIND_SOLUTION) is default
+        checker = router.model_for(JOB_KIND_ANSWER_CHECK)
+        assert checker.model_name == "flash" and checker.generation.temperature == 0.0
+        assert router.model_for(JOB_KIND_ANSWER_CHECK, DEFAULT_PROFILE) is checker
+        assert router.model_for(JOB_KIND_HINT_LADDER).generation == GenerationParams(1.2, 2048)
@@ -0,0 +1,251 @@
+"""
+Test cases for grading context assembly and attempt grading
+"""
+from datetime import date, datetime
+from types import SimpleNamespace
+
+import pymupdf
+import pytest
+
+from textbook.cross_reference import ENTITY_SOURCE_LLM
+from textbook.database import EntityInfo
+from textbook.grading import (
+    DISPUTE_PENDING_REVIEW,
+    DISPUTE_RESOLVED,
+    STUDY_MODE_SELF_EXPLANATION,
+    JUDGMENT_DISPUTE,
+    JUDGMENT_INITIAL,
+    JUDGMENT_LOW_CONFIDENCE,
+    PRIORITY_CHAPTER_ENTITY,
+    PRIORITY_PROBLEM,
+    PRIORITY_SOURCE_EXCERPT,
+    TRUNCATION_MARKER,
+    ContextPart,
+    estimate_tokens,
+    grading_metrics,
+    prioritize_context,
+    render_context,
+)
+from textbook.reader import GradingSchema, LazyTextbookReader, SelfExplanationSchema
+
+
+class TestContextPrioritizer:
+    """Test suite for fitting context parts into a token budget"""
+
+    def test_everything_fits(self):
+        """Test that parts are kept whole and in their given order when the budget allows"""
+        parts = [ContextPart("Problem", "Show x", PRIORITY_PROBLEM), ContextPart("Lemma", "y", PRIORITY_CHAPTER_ENTITY), ContextPart("Source", "z", PRIORITY_SOURCE_EXCERPT)]
+        assert prioritize_context(parts, 1000) == parts
+
+    def test_low_priority_parts_are_dropped_first(self):
+        """Test that the least important parts give way, keeping the problem beyond the budget"""
+        parts = [
+            ContextPart("Problem", "p" * 400, PRIORITY_PROBLEM),
+            ContextPart("Lemma", "l" * 400, PRIORITY_CHAPTER_ENTITY, truncatable=False),
+            ContextPart("Source", "s" * 400, PRIORITY_SOURCE_EXCERPT, truncatable=False),
+        ]
+        kept = prioritize_context(parts, 220)
+        assert [part.title for part in kept] == ["Problem", "Source"]
+        assert [part.title for part in prioritize_context(parts, 10)] == ["Problem"]
+
+    def test_truncation(self):
+        """Test that a truncatable part is cut to the remaining budget, and dropped if too little remains"""
+        parts = [ContextPart("Problem", "short", PRIORITY_PROBLEM), ContextPart("Source", "s" * 4000, PRIORITY_SOURCE_EXCERPT)]
+        kept = prioritize_context(parts, 300)
+        assert kept[1].text.endswith(TRUNCATION_MARKER)
+        assert sum(estimate_tokens(part.title) + estimate_tokens(part.text) + 2 for part in kept) <= 300
+        assert len(prioritize_context(parts, 40)) == 1
+
+    def test_render_context(self):
+        """Test the rendered sections of the prompt"""
+        assert render_context([ContextPart("Problem", "Show x", PRIORITY_PROBLEM)]) == "## Problem\nShow x"
+
+
+class TestGradingMetrics:
+    """Test suite for grading accuracy over time"""
+
+    @staticmethod
+    def attempt(attempt_id, day, reasons=("initial",)):
+        return SimpleNamespace(attempt_id=attempt_id, created_at=datetime(2026, 3, day, 12), judgments=[SimpleNamespace(reason=reason) for reason in reasons])
+
+    @staticmethod
+    def dispute(attempt_id, status=DISPUTE_RESOLVED, resolved=(1.0, True), escalated=(None, None)):
+        return SimpleNamespace(
+            attempt_id=attempt_id, status=status,
+            original_score=0.2, original_is_correct=False,
+            escalated_score=escalated[0], escalated_is_correct=escalated[1],
+            resolved_score=resolved[0], resolved_is_correct=resolved[1],
+        )
+
+    def test_weekly_periods(self):
+        """Test that attempts are grouped into weeks starting on Monday and disputes count in their attempt's week"""
+        # March 2nd 2026 is a Monday
+        attempts = [self.attempt(1, 2), self.attempt(2, 8, ("initial", "low_confidence")), self.attempt(3, 9), self.attempt(4, 10)]
+        disputes = [
+            self.dispute(1, escalated=(0.9, True)),
+            self.dispute(2, resolved=(0.3, False), escalated=(0.9, True)),
+            self.dispute(3, status=DISPUTE_PENDING_REVIEW, resolved=(None, None)),
+            self.dispute(99),
+        ]
+        first, second = grading_metrics(attempts, disputes)
+
+        assert (first.period_start, first.attempts, first.escalations, first.disputes, first.resolved_disputes, first.overturned) == (date(2026, 3, 2), 2, 1, 2, 2, 1)
+        assert first.accuracy == 0.5 and first.dispute_rate == 1.0
+        assert first.escalation_agreement == 0.5
+        assert (second.period_start, second.attempts, second.disputes, second.resolved_disputes, second.accuracy) == (date(2026, 3, 9), 2, 1, 0, 1.0)
+        assert second.escalation_agreement is None
+
+    def test_daily_periods(self):
+        """Test shorter periods"""
+        assert [period.period_start.day for period in grading_metrics([self.attempt(1, 2), self.attempt(2, 3)], [], period_days=1)] == [2, 3]
+
+
+class FakeGradingLLM:
+    """Returns a fixed grade, recording the prompts"""
+
+    def __init__(self, misconceptions, score=1.4, confidence=0.9, model_name="flash"):
+        self.prompts = []
+        self.misconceptions = misconceptions
+        self.score = score
+        self.confidence = confidence
+        self.model_name = model_name
+
+    def prompt_with_schema(self, prompt, schema):
+        self.prompts.append(prompt)
+        if schema is SelfExplanationSchema:
+            return SelfExplanationSchema(solution_steps=["Bound the first terms", " Bound the tail by the limit plus one"], missing_steps=["Bound the first terms"], completeness=0.5, feedback="Justify the first terms.")
+        assert schema is GradingSchema
+        return GradingSchema(score=self.score, is_correct=False, confidence=self.confidence, feedback=f"Graded by {self.model_name}.", misconceptions=self.misconceptions)
+
+
+class TestAttemptGrading:
+    """Test suite for assembling the grading context from the book and grading attempts"""
+
+    @pytest.fixture
+    def book(self, tmpdir, database):
+        path = tmpdir / "analysis.pdf"
+        document = pymupdf.open()
+        document.new_page().insert_text((72, 72), "Definition 1.1. A sequence converges if its terms get arbitrarily close to a limit.")
+        document.new_page().insert_text((72, 72), "Exercise. Show that every convergent sequence is bounded.")
+        document.save(path)
+        document.close()
+
+        book = database.create_book("analysis", "me", "sequences", path.stem, 2)
+        chapter_id = database.try_create_chapter_info(book.book_id, "Sequences", "1", 0, 1)
+        database.replace_entities(book.book_id, ENTITY_SOURCE_LLM, [
+            EntityInfo(entity_kind="definition", entity_label="1.1", statement="A sequence converges if ...", page_number=0, chapter_id=chapter_id),
+            EntityInfo(entity_kind="theorem", entity_label="4.1", statement="Unrelated theorem of another chapter.", page_number=0),
+        ])
+        exercise_id = database.create_exercise(book.book_id, 1, "Using Definition 1.1, show that every convergent sequence is bounded.")
+        return book, exercise_id, path
+
+    def test_assemble_grading_context(self, database, book):
+        """Test that the problem, its page and related entities are assembled, unrelated entities left out"""
+        book, exercise_id, path = book
+        with LazyTextbookReader(path, None, database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            parts = reader.assemble_grading_context(database.get_exercise_info(exercise_id))
+
+        assert [part.title for part in parts] == ["Problem", "Source (page 1)", "Definition 1.1"]
+        assert "every convergent sequence is bounded" in parts[1].text
+
+    def test_grade_attempt_records_misconceptions(self, database, book):
+        """Test that attempts are stored with a clamped score, and their misconceptions feed later gradings"""
+        book, exercise_id, path = book
+        llm = FakeGradingLLM(["Confuses bounded with convergent.", " "])
+        with LazyTextbookReader(path, llm, database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            attempt_id = reader.grade_attempt(exercise_id, "Every bounded sequence converges.")
+            reader.grade_attempt(exercise_id, "Take M as the maximum.")
+
+        attempt = database.get_attempt_info(attempt_id)
+        assert attempt.score == 1.0
+        assert [misconception.description for misconception in attempt.misconceptions] == ["Confuses bounded with convergent."]
+        assert "Recent mistakes" not in llm.prompts[0]
+        assert "- Confuses bounded with convergent." in llm.prompts[1]
+        assert len(database.get_attempts_by_exercise_id(exercise_id)) == 2
+
+    def test_grade_attempt_of_other_book(self, database, book):
+        """Test that an exercise of another book is rejected"""
+        book, exercise_id, path = book
+        other = database.create_book("algebra", "me", "groups", "algebra", 1)
+        other_exercise_id = database.create_exercise(other.book_id, 0, "Show that the identity is unique.")
+        with LazyTextbookReader(path, FakeGradingLLM([]), database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            with pytest.raises(ValueError):
+                reader.grade_attempt(other_exercise_id, "Suppose e and f are identities.")
+
+    def test_confident_grades_are_not_escalated(self, database, book):
+        """Test that the escalation model is only asked when the grader is unsure"""
+        book, exercise_id, path = book
+        strong = FakeGradingLLM([], model_name="pro")
+        with LazyTextbookReader(path, FakeGradingLLM([], confidence=0.95), database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            attempt = database.get_attempt_info(reader.grade_attempt(exercise_id, "By definition.", escalation_llm=strong))  # type: ignore[arg-type]
+
+        assert strong.prompts == []
+        assert [(judgment.model_name, judgment.reason) for judgment in attempt.judgments] == [("flash", JUDGMENT_INITIAL)]
+
+    def test_low_confidence_grades_are_escalated(self, database, book):
+        """Test that both judgments are recorded and the escalated one counts"""
+        book, exercise_id, path = book
+        weak = FakeGradingLLM(["Assumes the limit is zero."], score=0.9, confidence=0.3)
+        strong = FakeGradingLLM([], score=0.2, confidence=0.8, model_name="pro")
+        with LazyTextbookReader(path, weak, database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            attempt = database.get_attempt_info(reader.grade_attempt(exercise_id, "By definition.", escalation_llm=strong))  # type: ignore[arg-type]
+
+        assert strong.prompts == weak.prompts
+        assert [(judgment.model_name, judgment.reason, judgment.score) for judgment in attempt.judgments] == [("flash", JUDGMENT_INITIAL, 0.9), ("pro", JUDGMENT_LOW_CONFIDENCE, 0.2)]
+        assert (attempt.score, attempt.confidence, attempt.feedback) == (0.2, 0.8, "Graded by pro.")
+        assert attempt.misconceptions == []
+
+    def test_escalate_disputed_attempt(self, database, book):
+        """Test that a disputed attempt is regraded on its stored context with the objection"""
+        book, exercise_id, path = book
+        strong = FakeGradingLLM(["Forgets the first terms."], score=0.6, model_name="pro")
+        with LazyTextbookReader(path, FakeGradingLLM([], score=0.0), database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            attempt_id = reader.grade_attempt(exercise_id, "Take M as the maximum of the first terms and the limit plus one.")
+            attempt = reader.escalate_attempt(attempt_id, strong, comment="The bound covers all terms.")  # type: ignore[arg-type]
+
+        assert "The bound covers all terms." in strong.prompts[0]
+        assert attempt.grading_context in strong.prompts[0]
+        assert [judgment.reason for judgment in attempt.judgments] == [JUDGMENT_INITIAL, JUDGMENT_DISPUTE]
+        assert attempt.score == 0.6
+        assert [misconception.description for misconception in attempt.misconceptions] == ["Forgets the first terms."]
+
+    def test_dispute_attempt(self, database, book):
+        """Test that a dispute records the disputed and escalated grades and waits for review"""
+        book, exercise_id, path = book
+        with LazyTextbookReader(path, FakeGradingLLM([], score=0.1), database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            attempt_id = reader.grade_attempt(exercise_id, "Take M as the maximum.")
+            dispute_id = reader.dispute_attempt(attempt_id, "The maximum exists for finitely many terms.", FakeGradingLLM([], score=0.7, model_name="pro"))  # type: ignore[arg-type]
+            unescalated_id = reader.dispute_attempt(attempt_id, "Still unfair.")
+
+        dispute = database.get_dispute_info(dispute_id)
+        assert (dispute.status, dispute.original_score, dispute.escalated_score) == (DISPUTE_PENDING_REVIEW, 0.1, 0.7)
+        assert database.get_dispute_info(unescalated_id).escalated_score is None
+        assert [item.dispute_id for item in database.get_disputes(DISPUTE_PENDING_REVIEW)] == [dispute_id, unescalated_id]
+
+        database.resolve_dispute(dispute_id, DISPUTE_RESOLVED, 0.8, True, "Correct up to the first terms.")
+        assert [item.dispute_id for item in database.get_disputes(DISPUTE_PENDING_REVIEW, attempt_id)] == [unescalated_id]
+        assert database.get_dispute_info(dispute_id).resolved_at is not None
+
+    def test_self_explanation_is_journaled(self, database, book):
+        """Test that an explanation is evaluated against the solution steps and filed under the exercise's chapter"""
+        book, exercise_id, path = book
+        llm = FakeGradingLLM([])
+        with LazyTextbookReader(path, llm, database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            attempt_id = reader.grade_attempt(exercise_id, "Take M as the maximum.", study_mode=STUDY_MODE_SELF_EXPLANATION)
+            reflection_id = reader.evaluate_self_explanation(attempt_id, "After N the terms are close to the limit.")
+
+        assert database.get_attempt_info(attempt_id).study_mode == STUDY_MODE_SELF_EXPLANATION
+        assert "After N the terms are close to the limit." in llm.prompts[-1] and "Take M as the maximum." in llm.prompts[-1]
+        reflection = database.get_reflection_info(reflection_id)
+        assert (reflection.completeness, reflection.missing_steps) == (0.5, "Bound the first terms")
+        assert reflection.solution_steps == "Bound the first terms\nBound the tail by the limit plus one"
+        chapter_id = database.get_chapters_by_book_id(book.book_id)[0].chapter_id
+        assert [item.reflection_id for item in database.get_reflections_by_book_id(book.book_id, chapter_id)] == [reflection_id]
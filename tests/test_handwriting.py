@@ -0,0 +1,102 @@
+"""
+Test cases for the handwritten ingestion mode
+"""
+
+import pymupdf
+import pytest
+
+from textbook.reader import (
+    INGESTION_MODE_HANDWRITTEN,
+    INGESTION_MODE_PRINTED,
+    TRANSCRIPTION_SOURCE_LLM,
+    TRANSCRIPTION_SOURCE_MANUAL,
+    LazyTextbookReader,
+    TranscriptionSchema,
+)
+
+
+class FakeVisionLLM:
+    """Returns a fixed transcription and counts the calls"""
+
+    def __init__(self, text: str = "$f(x) = x^2$ is convex", confidence: float = 0.4):
+        self.text = text
+        self.confidence = confidence
+        self.calls = 0
+
+    def prompt_with_schema_and_attachments(self, prompt, schema, attachments):
+        self.calls += 1
+        assert schema is TranscriptionSchema
+        assert len(attachments) == 1
+        return TranscriptionSchema(text=self.text, confidence=self.confidence, unclear_passages=["x^2"])
+
+
+class TestHandwritingIngestion:
+    """Test suite for handwritten page transcription"""
+
+    @pytest.fixture
+    def pdf_path(self, tmpdir):
+        path = tmpdir / "notes.pdf"
+        document = pymupdf.open()
+        document.new_page()
+        document.new_page()
+        document.save(path)
+        document.close()
+        return path
+
+    @pytest.fixture
+    def book_id(self, database, pdf_path):
+        book = database.create_book("notes", "me", "convexity", pdf_path.stem, 2, INGESTION_MODE_HANDWRITTEN)
+        return book.book_id
+
+    def test_ingestion_mode_is_read_from_book(self, database, pdf_path, book_id):
+        """Test that the reader picks up the mode stored on the book"""
+        with LazyTextbookReader(pdf_path, FakeVisionLLM(), database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            assert reader.ingestion_mode == INGESTION_MODE_HANDWRITTEN
+
+    def test_unknown_book_defaults_to_printed(self, database, pdf_path):
+        """Test that books without a stored mode use the printed pipeline"""
+        with LazyTextbookReader(pdf_path, FakeVisionLLM(), database) as reader:  # type: ignore[arg-type]
+            assert reader.ingestion_mode == INGESTION_MODE_PRINTED
+
+    def test_invalid_ingestion_mode(self, database, pdf_path):
+        """Test that an unknown mode is rejected"""
+        with pytest.raises(ValueError):
+            LazyTextbookReader(pdf_path, FakeVisionLLM(), database, ingestion_mode="typed")  # type: ignore[arg-type]
+
+    def test_page_content_is_transcribed_once(self, database, pdf_path, book_id):
+        """Test that handwritten pages go through the vision LLM and the result is stored"""
+        llm = FakeVisionLLM()
+        with LazyTextbookReader(pdf_path, llm, database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            assert reader.get_page_content(1) == llm.text
+            assert reader.get_page_content(1) == llm.text
+
+        assert llm.calls == 1
+        page = database.get_page_by_book_id_and_page_number(book_id, 1)
+        assert page is not None
+        assert page.transcription == llm.text
+        assert page.transcription_confidence == pytest.approx(0.4)
+        assert page.transcription_source == TRANSCRIPTION_SOURCE_LLM
+
+    def test_manual_correction_is_kept(self, database, pdf_path, book_id):
+        """Test that re-transcribing does not overwrite a correction unless asked to"""
+        database.save_page_transcription(book_id, 0, "corrected", 1.0, TRANSCRIPTION_SOURCE_MANUAL)
+
+        llm = FakeVisionLLM()
+        with LazyTextbookReader(pdf_path, llm, database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            reader.update_page_transcription(0)
+            assert database.get_page_by_book_id_and_page_number(book_id, 0).transcription == "corrected"
+
+            reader.update_page_transcription(0, overwrite=True)
+            assert database.get_page_by_book_id_and_page_number(book_id, 0).transcription == llm.text
+
+    def test_confidence_is_clamped(self, database, pdf_path, book_id):
+        """Test that out of range confidences from the model are clamped to [0, 1]"""
+        with LazyTextbookReader(pdf_path, FakeVisionLLM(confidence=1.7), database, preprocess_scanned_pages=False) as reader:  # type: ignore[arg-type]
+            assert reader.transcribe_handwritten_page(0).confidence == 1.0
+
+    def test_summary_is_filled_after_transcription(self, database, book_id):
+        """Test that a page row created by a transcription still receives its summary"""
+        page_id = database.save_page_transcription(book_id, 0, "text", 0.9, TRANSCRIPTION_SOURCE_LLM)
+        assert database.try_create_page_info(book_id, 0, "summary") == page_id
+        assert database.get_page_info(page_id).summary == "summary"
@@ -0,0 +1,119 @@
+"""
+Test cases for the health of MinerU and the LLM provider reported by GET /health
+"""
+import types
+from typing import Optional
+
+import pytest
+import requests
+
+import textbook.mineru as mineru
+from textbook.circuit_breaker import CircuitBreakers, CircuitBreakerSettings
+from textbook.health import (
+    DEPENDENCY_DISABLED,
+    DEPENDENCY_DOWN,
+    DEPENDENCY_LLM,
+    DEPENDENCY_OK,
+    SERVER_DEGRADED,
+    SERVER_HEALTHY,
+    HealthProbe,
+    llm_health,
+    server_status,
+)
+from textbook.ocr import MinerUBackend, OcrBackend
+from textbook.warmup import prime_ocr
+
+
+class FakeClock:
+    def __init__(self):
+        self.now = 0.0
+
+    def __call__(self):
+        return self.now
+
+
+class FakeBackend(OcrBackend):
+    """Answers pings with a reason it is down, or None, counting them"""
+
+    name = "fake"
+
+    def __init__(self, reason: Optional[str] = None):
+        self.reason = reason
+        self.pings = 0
+
+    def unavailable(self) -> Optional[str]:
+        return None
+
+    def ping(self) -> Optional[str]:
+        self.pings += 1
+        return self.reason
+
+    def ocr_pdf(self, pdf_path, language, parse_method, job_id=None):
+        raise NotImplementedError
+
+    def ocr_image(self, image):
+        raise NotImplementedError
+
+
+class TestMinerUPing:
+    """Test suite for pinging MinerU's health endpoint"""
+
+    def _answer(self, monkeypatch, status_code: Optional[int] = None, error: Optional[Exception] = None):
+        def get(url, timeout):
+            if error is not None:
+                raise error
+            return types.SimpleNamespace(status_code=status_code)
+        monkeypatch.setattr(mineru.requests, "get", get)
+
+    def test_answers_below_server_errors_are_up(self, monkeypatch):
+        """Test that MinerU is up when its health endpoint answers, or is missing in its version"""
+        for status_code in (200, 404):
+            self._answer(monkeypatch, status_code)
+            assert MinerUBackend().ping() is None
+
+    def test_reasons_it_is_down(self, monkeypatch):
+        """Test that server errors, refused connections and timeouts say what went wrong and where"""
+        self._answer(monkeypatch, 503)
+        assert "HTTP 503" in MinerUBackend().ping()
+        self._answer(monkeypatch, error=requests.exceptions.ConnectionError("Connection refused"))
+        assert mineru.API_BASE_URL in MinerUBackend().ping() and "unreachable" in MinerUBackend().ping()
+        self._answer(monkeypatch, error=requests.exceptions.Timeout())
+        assert "did not answer" in MinerUBackend().ping()
+
+
+class TestHealthProbe:
+    """Test suite for the dependencies reported by GET /health"""
+
+    def test_pings_are_cached(self):
+        """Test that the OCR backend is pinged again only once the last ping is old enough"""
+        clock, backend = FakeClock(), FakeBackend("MinerU is unreachable")
+        probe = HealthProbe(backend, cache_seconds=15, clock=clock)
+        assert (probe.ocr().status, probe.ocr().detail) == (DEPENDENCY_DOWN, "MinerU is unreachable")
+        clock.now = 10
+        backend.reason = None
+        assert probe.ocr().status == DEPENDENCY_DOWN and backend.pings == 1
+        clock.now = 15
+        assert probe.ocr().status == DEPENDENCY_OK and backend.pings == 2
+
+    def test_llm_health_follows_its_breaker(self):
+        """Test that the provider is down while its breaker is open, and disabled without a model"""
+        clock = FakeClock()
+        breaker = CircuitBreakers(CircuitBreakerSettings(failure_threshold=2, reset_seconds=60), clock).get("llm:gemini")
+        model = types.SimpleNamespace(circuit_breaker=breaker)
+        assert llm_health(model).status == DEPENDENCY_OK
+        breaker.record_failure()
+        assert llm_health(model).status == DEPENDENCY_OK and "1 of the last calls failed" in llm_health(model).detail
+        breaker.record_failure()
+        health = llm_health(model)
+        assert health.status == DEPENDENCY_DOWN and "llm:gemini failed 2 calls in a row" in health.detail
+        assert llm_health(None).status == DEPENDENCY_DISABLED
+        assert llm_health(types.SimpleNamespace(circuit_breaker=None)).name == DEPENDENCY_LLM
+
+    def test_server_is_degraded_while_a_dependency_is_down(self):
+        """Test that a dependency down degrades the server and fails the OCR priming step with its reason"""
+        assert server_status(HealthProbe(FakeBackend()).check(None)) == SERVER_HEALTHY
+        probe = HealthProbe(FakeBackend("MinerU answered with HTTP 502"))
+        assert server_status(probe.check(None)) == SERVER_DEGRADED
+        with pytest.raises(RuntimeError, match="HTTP 502"):
+            prime_ocr(probe)
+        assert prime_ocr(HealthProbe(FakeBackend())) == 1
@@ -0,0 +1,39 @@
+"""
+Test cases for generating and revealing hint ladders
+"""
+
+import pytest
+
+from tests.conftest import FakeLLM
+from textbook.database import ProblemInfo
+from textbook.hints import HINT_TIERS, HintLadderSchema, generate_hint_ladder, revealed_hints
+from textbook.library import add_upload
+
+
+class TestHintLadder:
+    """Test suite for storing hint ladders and revealing them level by level"""
+
+    @pytest.fixture
+    def problem_id(self, database):
+        document = add_upload(database, "algebra", "a.pdf", 1)
+        return database.replace_problems(document.document_id, [ProblemInfo(statement="Show that the identity is unique.")])[0]
+
+    def test_hints_are_revealed_by_level(self, database, problem_id):
+        """Test that the ladder is stored as three tiers and read up to the level asked for"""
+        ladder = HintLadderSchema(nudge=" Take two identities. ", approach="Multiply them.", partial_solution="e = ef")
+        generate_hint_ladder(database, FakeLLM(ladder, HintLadderSchema), problem_id)
+
+        assert [(hint.level, hint.tier, hint.text) for hint in revealed_hints(database, problem_id, 1)] == [(1, HINT_TIERS[0], "Take two identities.")]
+        assert [hint.tier for hint in revealed_hints(database, problem_id, 3)] == list(HINT_TIERS)
+        with pytest.raises(ValueError):
+            revealed_hints(database, problem_id, 4)
+
+        generate_hint_ladder(database, FakeLLM(HintLadderSchema(nudge="Look at ef.", approach="a", partial_solution="b"), HintLadderSchema), problem_id)
+        assert [hint.text for hint in revealed_hints(database, problem_id, 1)] == ["Look at ef."]
+        assert len(database.get_hints(problem_id)) == 3
+
+    def test_empty_tier_is_rejected(self, database, problem_id):
+        """Test that a ladder with an empty tier is not stored"""
+        with pytest.raises(ValueError):
+            generate_hint_ladder(database, FakeLLM(HintLadderSchema(nudge="Look at ef.", approach=" ", partial_solution="b"), HintLadderSchema), problem_id)
+        assert revealed_hints(database, problem_id, 3) == []
@@ -0,0 +1,75 @@
+"""
+Test cases for scanned-page preprocessing
+"""
+
+import numpy as np
+import pytest
+from PIL import Image, ImageDraw
+
+from textbook.utils.image_preprocessing import (
+    binarize,
+    estimate_skew_angle,
+    is_sideways,
+    otsu_threshold,
+    preprocess_scanned_page,
+)
+
+
+class TestImagePreprocessing:
+    """Test suite for image_preprocessing"""
+
+    @pytest.fixture
+    def page(self):
+        """A synthetic page with dark text-like lines on a light background"""
+        image = Image.new("L", (600, 800), color=230)
+        draw = ImageDraw.Draw(image)
+        for top in range(60, 740, 40):
+            draw.rectangle([60, top, 540, top + 12], fill=40)
+        return image
+
+    def test_otsu_threshold_separates_classes(self):
+        """Test that the threshold falls between the two intensity clusters"""
+        gray = np.array([20] * 50 + [220] * 50, dtype=np.uint8)
+        threshold = otsu_threshold(gray)
+        assert 20 <= threshold < 220
+
+    def test_upright_page_has_no_skew(self, page):
+        """Test that level text lines are left alone"""
+        assert estimate_skew_angle(page) == 0.0
+
+    def test_skew_is_detected(self, page):
+        """Test that a tilted page is levelled by the opposite rotation"""
+        tilted = page.rotate(3, resample=Image.Resampling.BICUBIC, expand=True, fillcolor=230)
+        assert estimate_skew_angle(tilted) == pytest.approx(-3.0, abs=0.5)
+
+    def test_sideways_page_is_detected(self, page):
+        """Test that only pages with vertical text lines are flagged as sideways"""
+        assert not is_sideways(page)
+        assert is_sideways(page.transpose(Image.Transpose.ROTATE_90))
+
+    def test_binarize_outputs_black_and_white(self, page):
+        """Test that binarization leaves only two intensities"""
+        values = set(np.unique(np.asarray(binarize(page))).tolist())
+        assert values <= {0, 255}
+
+    def test_preprocess_straightens_sideways_tilted_page(self, page):
+        """Test that the full pipeline rotates and deskews a phone scan"""
+        scan = page.rotate(2, resample=Image.Resampling.BICUBIC, expand=True, fillcolor=230)
+        scan = scan.transpose(Image.Transpose.ROTATE_270).convert("RGB")
+
+        processed, rotation = preprocess_scanned_page(scan)
+
+        assert processed.mode == "L"
+        assert not is_sideways(processed)
+        assert estimate_skew_angle(processed) == pytest.approx(0.0, abs=0.5)
+        assert rotation != 0
+
+    def test_contrast_is_stretched(self):
+        """Test that a faint scan uses the full intensity range after preprocessing"""
+        faint = Image.new("L", (200, 200), color=180)
+        ImageDraw.Draw(faint).rectangle([20, 90, 180, 110], fill=140)
+
+        processed, _ = preprocess_scanned_page(faint, auto_rotate=False, deskew=False)
+
+        low, high = processed.getextrema()
+        assert low == 0 and high == 255
@@ -0,0 +1,101 @@
+"""
+Test cases for serving page and figure images in size variants and formats, cached on disk
+"""
+import io
+import os
+from pathlib import Path
+
+import pytest
+from PIL import Image
+
+from textbook.images import (
+    FORMAT_PNG,
+    FORMAT_WEBP,
+    SIZE_FULL,
+    SIZE_THUMB,
+    ImageCache,
+    ImageSettings,
+    encode_variant,
+    image_settings_from_config,
+)
+
+
+class CountingRender:
+    """Renders the source image, counting how often it was asked to"""
+
+    def __init__(self, source: Path):
+        self.source = source
+        self.calls = 0
+
+    def __call__(self) -> Image.Image:
+        self.calls += 1
+        return Image.open(self.source)
+
+
+class TestImages:
+    """Test suite for image variants and their cache"""
+
+    @pytest.fixture
+    def source(self, tmpdir):
+        path = tmpdir / "page.png"
+        Image.new("RGB", (1200, 1600), "white").save(path)
+        return path
+
+    @pytest.fixture
+    def render(self, source):
+        return CountingRender(source)
+
+    def test_settings_from_config(self):
+        """Test that PNG is the default format and invalid settings are rejected"""
+        assert image_settings_from_config({}) == ImageSettings()
+        settings = image_settings_from_config({"images": {"format": "webp", "quality": 60}})
+        assert (settings.format, settings.quality, settings.cache) == (FORMAT_WEBP, 60, True)
+        for section in ({"format": "gif"}, {"quality": 0}, {"quality": 101}, {"cache": "no"}, {"width": 100}):
+            with pytest.raises(ValueError):
+                image_settings_from_config({"images": section})
+
+    def test_variants_are_scaled_and_encoded(self, source):
+        """Test that a variant is scaled down to its width, keeping the aspect ratio, and encoded in its format"""
+        image = Image.open(source)
+        thumb = encode_variant(image, SIZE_THUMB, FORMAT_WEBP)
+        assert thumb[:4] == b"RIFF" and thumb[8:12] == b"WEBP"
+        assert Image.open(io.BytesIO(thumb)).size == (160, 213)
+        full = encode_variant(image, SIZE_FULL, FORMAT_PNG)
+        assert full.startswith(b"\x89PNG") and Image.open(io.BytesIO(full)).size == (1200, 1600)
+        assert len(thumb) < len(full)
+        small = Image.new("RGB", (100, 50))
+        assert Image.open(io.BytesIO(encode_variant(small, SIZE_THUMB, FORMAT_PNG))).size == (100, 50)
+
+    def test_variants_are_cached_per_version_of_the_source(self, tmpdir, source, render):
+        """Test that a variant is rendered once, and again once its source changed"""
+        cache = ImageCache(tmpdir / ".image_cache", ImageSettings(format=FORMAT_WEBP))
+        data, media_type = cache.variant(source, "page 0", SIZE_THUMB, None, render)
+        assert media_type == "image/webp"
+        assert cache.variant(source, "page 0", SIZE_THUMB, None, render) == (data, media_type)
+        assert render.calls == 1
+
+        cache.variant(source, "page 0", SIZE_THUMB, FORMAT_PNG, render)
+        cache.variant(source, "page 1", SIZE_THUMB, None, render)
+        assert render.calls == 3
+
+        stat = source.stat()
+        os.utime(source, ns=(stat.st_atime_ns, stat.st_mtime_ns + 1_000_000_000))
+        cache.variant(source, "page 0", SIZE_THUMB, None, render)
+        assert render.calls == 4
+
+    def test_cache_can_be_disabled(self, tmpdir, source, render):
+        """Test that without the cache every request renders the image and nothing is written"""
+        cache = ImageCache(tmpdir / ".image_cache", ImageSettings(cache=False))
+        for _ in range(2):
+            assert cache.variant(source, "page 0", SIZE_THUMB, None, render)[1] == "image/png"
+        assert render.calls == 2
+        assert not (tmpdir / ".image_cache").exists()
+
+    def test_unknown_variants_are_rejected(self, tmpdir, source, render):
+        """Test that an unknown size or format raises ValueError before rendering"""
+        cache = ImageCache(tmpdir / ".image_cache")
+        with pytest.raises(ValueError):
+            cache.variant(source, "page 0", "huge", None, render)
+        with pytest.raises(ValueError):
+            cache.variant(source, "page 0", SIZE_THUMB, "gif", render)
+        assert render.calls == 0
@@ -0,0 +1,76 @@
+"""
+Test cases for per-document ingestion settings
+"""
+
+import pytest
+
+from tests.conftest import FakeLLM
+from textbook.content import chunk_content, store_chunks
+from textbook.ingestion_settings import IngestionSettings, document_ingestion_settings, document_overrides, ingestion_settings_from_config, store_overrides
+from textbook.library import add_upload
+from textbook.problems import ProblemSchema, ProblemSetSchema, extract_problems
+
+CONTENT_LIST = [
+    {"type": "text", "text": "Groups", "text_level": 1, "page_idx": 0},
+    {"type": "text", "text": "1. Show that the identity is unique.", "page_idx": 0},
+    {"type": "text", "text": "Rings", "text_level": 1, "page_idx": 1},
+    {"type": "text", "text": "1. Show that 0a = 0.", "page_idx": 1},
+]
+
+
+def three_problems(prompt, schema):
+    """Answers each prompt with three problems"""
+    return ProblemSetSchema(problems=[
+        ProblemSchema(number=str(number), statement=f"Problem {number}", difficulty_hint="", figures=[], page_number=0)
+        for number in range(1, 4)
+    ])
+
+
+class TestIngestionSettings:
+    """Test suite for overriding the settings of config.toml per document"""
+
+    def test_settings_from_config(self):
+        """Test that the settings are read from config.toml and invalid ones are rejected"""
+        assert ingestion_settings_from_config({}) == IngestionSettings()
+        assert ingestion_settings_from_config({"ocr_parse_method": "ocr", "target_difficulty": 4}) == IngestionSettings(parse_method="ocr", target_difficulty=4)
+        for config in ({"ocr_parse_method": "scan"}, {"problems_per_section": 0}, {"target_difficulty": 6}, {"ocr_language": " "}):
+            with pytest.raises(ValueError):
+                ingestion_settings_from_config(config)
+
+    def test_overrides_are_stored(self, database):
+        """Test that a document's overrides are applied over config.toml, which it follows for the rest"""
+        defaults = IngestionSettings(ocr_language="ch", problems_per_section=10)
+        document = add_upload(database, "algebra", "a.pdf", 2)
+        assert document_ingestion_settings(database, document.document_id, defaults) == defaults
+
+        settings = store_overrides(database, document.document_id, defaults, {"parse_method": "txt", "problems_per_section": 2})
+        assert settings == IngestionSettings(ocr_language="ch", parse_method="txt", problems_per_section=2)
+        assert document_ingestion_settings(database, document.document_id, defaults) == settings
+        assert document_ingestion_settings(database, document.document_id, IngestionSettings()) == IngestionSettings(parse_method="txt", problems_per_section=2)
+        assert document_ingestion_settings(database, document.document_id, defaults, {}) == defaults
+
+        with pytest.raises(ValueError):
+            store_overrides(database, document.document_id, defaults, {"target_difficulty": 0})
+        with pytest.raises(ValueError):
+            store_overrides(database, document.document_id, defaults, {"dpi": 300})
+        assert document_overrides(database, document.document_id) == {"parse_method": "txt", "problems_per_section": 2}
+
+        store_overrides(database, document.document_id, defaults, {})
+        assert document_overrides(database, document.document_id) == {}
+
+    def test_extraction_follows_settings(self, database):
+        """Test that only so many problems are kept per chapter, the LLM listing those closest to the target first"""
+        document = add_upload(database, "algebra", "a.pdf", 2)
+        store_chunks(database, document.document_id, chunk_content("", CONTENT_LIST)[1])
+
+        llm = FakeLLM(three_problems)
+        extract_problems(database, llm, document.document_id, problems_per_section=2, target_difficulty=4)
+        assert [(problem.section_index, problem.problem_number) for problem in database.get_problems(document.document_id)] == [
+            (0, "1"), (0, "2"), (1, "1"), (1, "2"),
+        ]
+        assert all("closest to difficulty 4" in prompt for prompt in llm.prompts)
+
+        llm = FakeLLM(three_problems)
+        extract_problems(database, llm, document.document_id)
+        assert len(database.get_problems(document.document_id)) == 6
+        assert not any("closest to difficulty" in prompt for prompt in llm.prompts)
@@ -0,0 +1,102 @@
+"""
+Test cases for artifact integrity checks
+"""
+import os
+import tempfile
+
+import pymupdf
+import pytest
+
+from textbook.database import TextBookDatabase, BookInfo
+from textbook.integrity import (
+    CHECKSUM_MISMATCH,
+    ORPHAN_FILE,
+    PAGE_COUNT_MISMATCH,
+    SOURCE_PDF_ARTIFACT,
+    UNREGISTERED_FILE,
+    register_file_artifact,
+    repair_issues,
+    verify_integrity,
+)
+from textbook.library import add_upload
+
+
+class TestIntegrity:
+    """Test suite for verify_integrity and repair_issues"""
+
+    @pytest.fixture
+    def database(self):
+        """Create a TextBookDatabase instance with temporary database"""
+        with tempfile.NamedTemporaryFile(delete=False, suffix='.db') as f:
+            db_path = f.name
+        database = TextBookDatabase(db_path=db_path)
+        yield database
+        database.close()
+        os.unlink(db_path)
+
+    @pytest.fixture
+    def uploads_dir(self):
+        """Create a temporary uploads directory holding a two page PDF"""
+        with tempfile.TemporaryDirectory() as tmpdir:
+            document = pymupdf.open()
+            document.new_page()
+            document.new_page()
+            document.save(os.path.join(tmpdir, "book.pdf"))
+            document.close()
+            yield tmpdir
+
+    @pytest.fixture
+    def book_id(self, database):
+        """Create a book pointing at book.pdf"""
+        with database.new_session() as session:
+            book = BookInfo(book_name="Test Book", book_file_name="book", book_pages=2)
+            session.add(book)
+            session.commit()
+            return book.book_id
+
+    def test_clean_tree_has_no_issues(self, database, uploads_dir, book_id):
+        """Test that a registered, unchanged PDF verifies cleanly"""
+        register_file_artifact(database, uploads_dir, book_id, SOURCE_PDF_ARTIFACT, "book.pdf")
+        assert verify_integrity(database, uploads_dir) == []
+
+    def test_detects_corruption(self, database, uploads_dir, book_id):
+        """Test that a modified PDF is reported as a checksum mismatch"""
+        register_file_artifact(database, uploads_dir, book_id, SOURCE_PDF_ARTIFACT, "book.pdf")
+        with open(os.path.join(uploads_dir, "book.pdf"), "ab") as f:
+            f.write(b"garbage")
+
+        kinds = [issue.kind for issue in verify_integrity(database, uploads_dir)]
+        assert CHECKSUM_MISMATCH in kinds
+
+    def test_detects_orphan_files(self, database, uploads_dir, book_id):
+        """Test that files not referenced by any book are reported"""
+        register_file_artifact(database, uploads_dir, book_id, SOURCE_PDF_ARTIFACT, "book.pdf")
+        with open(os.path.join(uploads_dir, "stray.pdf"), "wb") as f:
+            f.write(b"stray")
+
+        issues = verify_integrity(database, uploads_dir)
+        assert [(issue.kind, issue.path) for issue in issues] == [(ORPHAN_FILE, "stray.pdf")]
+        assert not issues[0].recoverable
+
+    def test_library_uploads_are_referenced(self, database, uploads_dir, book_id):
+        """Test that a document's uploaded PDF and its OCR markdown are not reported as orphans"""
+        register_file_artifact(database, uploads_dir, book_id, SOURCE_PDF_ARTIFACT, "book.pdf")
+        add_upload(database, "algebra", "0b6f9c1e.pdf", 1)
+        for file_name in ("0b6f9c1e.pdf", "0b6f9c1e.md"):
+            with open(os.path.join(uploads_dir, file_name), "wb") as f:
+                f.write(b"upload")
+
+        assert verify_integrity(database, uploads_dir) == []
+
+    def test_repairs_recoverable_issues(self, database, uploads_dir, book_id):
+        """Test that missing digests and wrong page counts are regenerated"""
+        with database.new_session() as session:
+            session.query(BookInfo).filter(BookInfo.book_id == book_id).update({BookInfo.book_pages: 7})
+            session.commit()
+
+        issues = verify_integrity(database, uploads_dir)
+        assert {issue.kind for issue in issues} == {UNREGISTERED_FILE, PAGE_COUNT_MISMATCH}
+
+        repaired = repair_issues(database, uploads_dir, issues)
+        assert len(repaired) == 2
+        assert verify_integrity(database, uploads_dir) == []
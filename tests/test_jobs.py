@@ -0,0 +1,417 @@
+"""
+Test cases for the background job pool
+"""
+import threading
+import time
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.jobs import CANCELLED_ERROR, INTERRUPTED_ERROR, JOB_CANCELLED, JOB_FAILED, JOB_INTERRUPTED, JOB_PENDING, JOB_RUNNING, JOB_SUCCEEDED, PRIORITY_LOW, JobPool, JobRetention, concurrency_from_config, retention_from_config
+from textbook.provider_errors import ERROR_AUTH, USER_MESSAGES, ModelError
+
+
+def fail_until_resumed(params, handle):
+    if not params.get("ready"):
+        raise RuntimeError("MinerU is down")
+    return {"markdown_file_name": "a.md"}
+
+
+def fail_on_provider(params, handle):
+    raise ModelError(ERROR_AUTH, "400 API key not valid. Please pass a valid API key.")
+
+
+def run_until_cancelled(params, handle):
+    handle.report_progress("render", 15, "page 12/80 rendered")
+    while not handle.cancellation_token.cancelled:
+        time.sleep(0.01)
+    handle.cancellation_token.raise_if_cancelled()
+    return {"pages": 80}
+
+
+def settle(job_ids, timeout: float = 5):
+    """Wait for the threads of finished jobs to end, a job is only dropped from memory once its thread ended"""
+    names = {f"job-{job_id}" for job_id in job_ids}
+    deadline = time.monotonic() + timeout
+    while any(thread.name in names for thread in threading.enumerate()) and time.monotonic() < deadline:
+        time.sleep(0.01)
+
+
+class TestJobPool:
+    """Test suite for running jobs in the background"""
+
+    @pytest.fixture
+    def pool(self):
+        pool = JobPool(concurrency={"slow": 1})
+        pool.register("ocr", lambda params, handle: {"markdown_file_name": params["pdf_file_name"].replace(".pdf", ".md")})
+        pool.register("flaky", fail_until_resumed)
+        pool.register("slow", run_until_cancelled)
+        pool.register("prompt", fail_on_provider)
+        yield pool
+        pool.shutdown()
+
+    def test_job_result_is_kept(self, pool):
+        """Test that a finished job keeps the result of its handler"""
+        job = pool.submit("ocr", {"pdf_file_name": "a.pdf"})
+        finished = pool.wait(job.job_id, timeout=5)
+
+        assert (finished.status, finished.progress, finished.finished) == (JOB_SUCCEEDED, 1.0, True)
+        assert finished.result == {"markdown_file_name": "a.md"}
+        assert finished.started_at is not None and finished.finished_at >= finished.started_at
+
+    def test_failed_job_keeps_error(self, pool):
+        """Test that an exception fails the job with its message"""
+        finished = pool.wait(pool.submit("flaky").job_id, timeout=5)
+        assert (finished.status, finished.error, finished.result) == (JOB_FAILED, "MinerU is down", None)
+        assert finished.error_category is None
+
+    def test_provider_error_keeps_category(self, pool):
+        """Test that a job failing on the LLM provider keeps the category and the message to show users"""
+        finished = pool.wait(pool.submit("prompt").job_id, timeout=5)
+        assert (finished.status, finished.error, finished.error_category) == (JOB_FAILED, USER_MESSAGES[ERROR_AUTH], ERROR_AUTH)
+
+    def test_progress_is_reported(self, pool):
+        """Test that a running job shows the stage, share done and detail its handler reported"""
+        job = pool.submit("slow")
+        while pool.get_job_status(job.job_id).stage is None:
+            time.sleep(0.01)
+
+        running = pool.get_job_status(job.job_id)
+        assert (running.stage, running.progress, running.detail) == ("render", 0.15, "page 12/80 rendered")
+        pool.cancel_job(job.job_id)
+
+    def test_watch_wakes_up_on_change(self, pool):
+        """Test that watching a job returns as soon as it changed since the version the watcher saw"""
+        job = pool.submit("slow")
+        version, seen = pool.watch(job.job_id)
+        while seen.stage is None:
+            version, seen = pool.watch(job.job_id, version, timeout=5)
+        assert seen.detail == "page 12/80 rendered"
+        assert pool.watch(job.job_id, version, timeout=0.05)[0] == version
+
+        pool.cancel_job(job.job_id)
+        version, seen = pool.watch(job.job_id, version, timeout=5)
+        assert seen.status == JOB_CANCELLED
+        assert pool.watch("missing") == (0, None)
+
+    def test_cancel_running_job(self, pool):
+        """Test that a cancelled job stops at its next check and can be cancelled only once"""
+        job = pool.submit("slow")
+        while pool.get_job_status(job.job_id).status != JOB_RUNNING:
+            time.sleep(0.01)
+
+        cancelled = pool.cancel_job(job.job_id)
+        assert (cancelled.status, cancelled.error, cancelled.finished) == (JOB_CANCELLED, CANCELLED_ERROR, True)
+        pool.shutdown(timeout=5)
+        assert (pool.wait(job.job_id).status, pool.wait(job.job_id).result) == (JOB_CANCELLED, None)
+        with pytest.raises(ValueError):
+            pool.cancel_job(job.job_id)
+        assert pool.cancel_job("missing") is None
+
+    def test_jobs_queue_for_a_free_worker(self, pool):
+        """Test that a job waits in the queue while its category has no free worker"""
+        first = pool.submit("slow")
+        second = pool.submit("slow")
+        assert (second.status, second.queue_position) == (JOB_PENDING, 1)
+        assert pool.queue_depths()["slow"] == 1
+
+        pool.cancel_job(first.job_id)
+        while pool.get_job_status(second.job_id).status != JOB_RUNNING:
+            time.sleep(0.01)
+        assert pool.get_job_status(second.job_id).queue_position is None
+        assert pool.queue_depths()["slow"] == 0
+        pool.cancel_job(second.job_id)
+
+    def test_low_priority_jobs_queue_last(self, pool):
+        """Test that low priority jobs wait behind normal jobs submitted after them"""
+        first = pool.submit("slow")
+        low = pool.submit("slow", priority=PRIORITY_LOW)
+        normal = pool.submit("slow")
+        assert (pool.get_job_status(normal.job_id).queue_position, pool.get_job_status(low.job_id).queue_position) == (1, 2)
+        assert pool.low_priority_jobs() == 1
+
+        for job in (first, low, normal):
+            pool.cancel_job(job.job_id)
+        assert pool.low_priority_jobs() == 0
+        with pytest.raises(ValueError):
+            pool.submit("slow", priority="urgent")
+
+    def test_low_priority_jobs_leave_a_worker_free(self):
+        """Test that low priority jobs do not take the last worker of their category"""
+        pool = JobPool(concurrency={"slow": 2})
+        pool.register("slow", run_until_cancelled)
+        jobs = [pool.submit("slow", priority=PRIORITY_LOW) for _ in range(2)]
+        assert (jobs[1].status, jobs[1].queue_position) == (JOB_PENDING, 1)
+
+        normal = pool.submit("slow")
+        while pool.get_job_status(normal.job_id).status != JOB_RUNNING:
+            time.sleep(0.01)
+        for job in (*jobs, normal):
+            pool.cancel_job(job.job_id)
+        pool.shutdown(timeout=5)
+
+    def test_low_priority_jobs_keep_off_a_single_worker(self):
+        """Test that low priority jobs do not take the only worker of a category, which stays free for normal jobs"""
+        pool = JobPool(concurrency={"slow": 1})
+        pool.register("slow", run_until_cancelled)
+        low = pool.submit("slow", priority=PRIORITY_LOW)
+        assert not pool.runs_low_priority("slow") and pool.runs_low_priority("ocr")
+        time.sleep(0.05)
+        assert pool.get_job_status(low.job_id).status == JOB_PENDING
+
+        normal = pool.submit("slow")
+        while pool.get_job_status(normal.job_id).status != JOB_RUNNING:
+            time.sleep(0.01)
+        assert pool.get_job_status(low.job_id).queue_position == 1
+        for job in (low, normal):
+            pool.cancel_job(job.job_id)
+        pool.shutdown(timeout=5)
+
+    def test_categories_have_independent_limits(self):
+        """Test that jobs queueing for a busy category do not hold up those of another one"""
+        pool = JobPool(concurrency={"ocr": 1, "llm": 2})
+        pool.register("ocr", run_until_cancelled, "ocr")
+        pool.register("prompt", run_until_cancelled, "llm")
+        ocr_jobs = [pool.submit("ocr") for _ in range(2)]
+        prompt_jobs = [pool.submit("prompt") for _ in range(2)]
+        assert [job.queue_position for job in ocr_jobs] == [None, 1]
+        assert [job.queue_position for job in prompt_jobs] == [None, None]
+        assert pool.running_jobs() == {"ocr": 1, "llm": 2}
+        assert pool.queue_depths() == {"ocr": 1, "llm": 0}
+
+        for job in (*ocr_jobs, *prompt_jobs):
+            pool.cancel_job(job.job_id)
+        pool.shutdown(timeout=5)
+
+    def test_paused_category_keeps_jobs_pending(self):
+        """Test that jobs of a paused category wait in its queue and start when it is resumed"""
+        pool = JobPool()
+        pool.register("ocr", lambda params, handle: {}, "ocr")
+        pool.register("prompt", lambda params, handle: {}, "llm")
+        pool.pause("ocr")
+        job = pool.submit("ocr")
+        assert pool.wait(pool.submit("prompt").job_id, timeout=5).status == JOB_SUCCEEDED
+        assert (pool.get_job_status(job.job_id).status, pool.queue_depths()["ocr"], pool.paused()) == (JOB_PENDING, 1, ["ocr"])
+
+        pool.unpause("ocr")
+        assert pool.wait(job.job_id, timeout=5).status == JOB_SUCCEEDED
+        assert pool.paused() == []
+        pool.shutdown(timeout=5)
+
+    def test_concurrency_from_config(self):
+        """Test that the [jobs] section sets the workers per category, over the older job_concurrency table"""
+        assert concurrency_from_config({}) == {}
+        config = {"job_concurrency": {"ocr": 3, "render": 1}, "jobs": {"ocr_workers": 1, "llm_workers": 12, "retention_days": 30}}
+        assert concurrency_from_config(config) == {"ocr": 1, "render": 1, "llm": 12}
+        assert JobPool(concurrency=concurrency_from_config(config)).workers("llm") == 12
+        with pytest.raises(ValueError):
+            concurrency_from_config({"jobs": {"ocr_workers": "two"}})
+        with pytest.raises(ValueError):
+            JobPool(concurrency=concurrency_from_config({"jobs": {"llm_workers": 0}}))
+
+    def test_retention_from_config(self):
+        """Test that finished jobs are kept a day by default and invalid retention settings are rejected"""
+        assert retention_from_config({"jobs": {"ocr_workers": 1}}) == JobRetention()
+        assert retention_from_config({"jobs": {"retention_hours": 0.5, "max_finished_jobs": 0}}).max_finished_jobs == 0
+        for section in ({"retention_hours": 0}, {"sweep_seconds": "often"}, {"max_finished_jobs": -1}, {"max_finished_jobs": 1.5}):
+            with pytest.raises(ValueError):
+                retention_from_config({"jobs": section})
+
+    def test_finished_jobs_are_swept(self):
+        """Test that finished jobs past the retention or beyond the cap are dropped, running jobs and their groups kept"""
+        pool = JobPool(concurrency={"slow": 1}, retention=JobRetention(retention_hours=1, max_finished_jobs=2))
+        pool.register("ocr", lambda params, handle: {"pages": 3})
+        pool.register("slow", run_until_cancelled)
+        finished = [pool.wait(pool.submit("ocr", {"pdf_file_name": f"{name}.pdf"}).job_id, timeout=5) for name in "abc"]
+        group = pool.submit_group([("ocr", {"pdf_file_name": "d.pdf"}), ("slow", {})])
+        pool.wait(group.jobs[0].job_id, timeout=5)
+        settle([job.job_id for job in finished] + [group.jobs[0].job_id])
+
+        # Beyond the cap the oldest goes, and without a database it is gone
+        assert pool.sweep() == 1
+        assert pool.get_job_status(finished[0].job_id) is None
+        assert pool.tracked() == 4
+
+        later = datetime.now(timezone.utc) + timedelta(hours=2)
+        assert pool.sweep(now=later) == 2
+        assert pool.get_group(group.group_id).total == 2
+
+        pool.cancel_job(group.jobs[1].job_id)
+        settle([group.jobs[1].job_id])
+        assert pool.sweep(now=later) == 2
+        assert pool.tracked() == 0
+        pool.shutdown()
+
+    def test_group_status_is_aggregated(self, pool):
+        """Test that a group counts its completed jobs and keeps its first failure"""
+        group = pool.submit_group([("ocr", {"pdf_file_name": "a.pdf"}), ("flaky", {}), ("ocr", {"pdf_file_name": "b.pdf"})])
+        for job in group.jobs:
+            pool.wait(job.job_id, timeout=5)
+
+        finished = pool.get_group(group.group_id)
+        assert [job.job_id for job in finished.jobs] == [job.job_id for job in group.jobs]
+        assert (finished.status, finished.completed, finished.total) == (JOB_FAILED, 2, 3)
+        assert finished.counts == {JOB_SUCCEEDED: 2, JOB_FAILED: 1}
+        assert (finished.first_failure.job_id, finished.first_failure.error) == (group.jobs[1].job_id, "MinerU is down")
+
+        # A resumed job stays in its group
+        assert pool.resume(group.jobs[1].job_id).group_id == group.group_id
+        pool.wait(group.jobs[1].job_id, timeout=5)
+        assert pool.get_group(group.group_id).total == 3
+
+        assert pool.get_group("missing") is None
+        with pytest.raises(ValueError):
+            pool.submit_group([])
+        with pytest.raises(ValueError):
+            pool.submit_group([("ocr", {"pdf_file_name": "a.pdf"}), ("transcribe", {})])
+
+    def test_unknown_job(self, pool):
+        """Test that a job the pool never had is None and unknown kinds are rejected"""
+        assert pool.get_job_status("missing") is None
+        assert pool.wait("missing") is None
+        with pytest.raises(ValueError):
+            pool.submit("transcribe")
+
+    def test_concurrent_resumes_start_the_job_once(self):
+        """Test that of two callers resuming a job at once, one starts it and the other is refused"""
+        runs = []
+        release = threading.Event()
+
+        def fail_then_wait(params, handle):
+            runs.append(handle.job_id)
+            if len(runs) == 1:
+                raise RuntimeError("MinerU is down")
+            release.wait(5)
+            return {"markdown_file_name": "a.md"}
+
+        pool = JobPool()
+        pool.register("ocr", fail_then_wait)
+        job = pool.submit("ocr")
+        assert pool.wait(job.job_id, timeout=5).status == JOB_FAILED
+
+        barrier = threading.Barrier(2)
+        outcomes = []
+
+        def resume():
+            barrier.wait()
+            try:
+                outcomes.append(pool.resume(job.job_id).status)
+            except ValueError:
+                outcomes.append("refused")
+
+        threads = [threading.Thread(target=resume) for _ in range(2)]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join(5)
+        release.set()
+        assert pool.wait(job.job_id, timeout=5).status == JOB_SUCCEEDED
+        assert sorted(outcomes) == [JOB_PENDING, "refused"] and len(runs) == 2
+        pool.shutdown()
+
+
+class TestJobStore:
+    """Test suite for keeping jobs in the database across restarts"""
+
+    def test_history_survives_restart(self, database):
+        """Test that a new pool finds the jobs of the previous one with their status transitions"""
+        pool = JobPool(database)
+        pool.register("ocr", lambda params, handle: handle.report_progress("ocr", 50, "page 2/3") or {"pages": 3})
+        job = pool.submit("ocr", {"pdf_file_name": "a.pdf"})
+        pool.wait(job.job_id, timeout=5)
+        pool.shutdown()
+
+        restarted = JobPool(database)
+        stored = restarted.get_job_status(job.job_id)
+        assert (stored.status, stored.params, stored.result) == (JOB_SUCCEEDED, {"pdf_file_name": "a.pdf"}, {"pages": 3})
+        assert (stored.progress, stored.stage, stored.detail) == (1.0, "ocr", "page 2/3")
+        assert [event.status for event in database.get_job_events(job.job_id)] == [JOB_PENDING, JOB_RUNNING, JOB_SUCCEEDED]
+        assert [listed.job_id for listed in restarted.list_jobs(status=JOB_SUCCEEDED)] == [job.job_id]
+
+    def test_interrupted_jobs_can_be_resumed(self, database):
+        """Test that jobs left running are marked interrupted on startup and run again when resumed"""
+        pool = JobPool(database)
+        pool.register("flaky", fail_until_resumed)
+        job = pool.submit("flaky", {"ready": True})
+        pool.wait(job.job_id, timeout=5)
+        pool.shutdown()
+        stored = database.get_job(job.job_id)
+        stored.status = JOB_RUNNING
+        database.save_job(stored)
+
+        restarted = JobPool(database)
+        restarted.register("flaky", fail_until_resumed)
+        assert [interrupted.job_id for interrupted in restarted.recover()] == [job.job_id]
+        assert (restarted.get_job_status(job.job_id).status, restarted.get_job_status(job.job_id).error) == (JOB_INTERRUPTED, INTERRUPTED_ERROR)
+
+        resumed = restarted.resume(job.job_id)
+        assert resumed.job_id == job.job_id
+        assert restarted.wait(job.job_id, timeout=5).status == JOB_SUCCEEDED
+        with pytest.raises(ValueError):
+            restarted.resume(job.job_id)
+        assert restarted.resume("missing") is None
+        restarted.shutdown()
+
+    def test_groups_survive_restart(self, database):
+        """Test that a new pool aggregates the jobs of a group submitted before the restart"""
+        pool = JobPool(database)
+        pool.register("ocr", lambda params, handle: {"pages": 3})
+        group = pool.submit_group([("ocr", {"pdf_file_name": "a.pdf"}), ("ocr", {"pdf_file_name": "b.pdf"})])
+        for job in group.jobs:
+            pool.wait(job.job_id, timeout=5)
+        pool.shutdown()
+
+        stored = JobPool(database).get_group(group.group_id)
+        assert (stored.status, stored.completed, stored.total, stored.first_failure) == (JOB_SUCCEEDED, 2, 2, None)
+        assert {job.group_id for job in stored.jobs} == {group.group_id}
+
+    def test_dead_letter_jobs_are_retried(self, database):
+        """Test that failed jobs wait in the dead-letter queue until retried once as a new job, also after a restart"""
+        pool = JobPool(database)
+        pool.register("flaky", fail_until_resumed)
+        pool.register("ocr", lambda params, handle: {"pages": 3})
+        failed = pool.wait(pool.submit("flaky", {"pdf_file_name": "a.pdf"}).job_id, timeout=5)
+        pool.wait(pool.submit("ocr").job_id, timeout=5)
+        pool.shutdown()
+
+        restarted = JobPool(database)
+        restarted.register("flaky", fail_until_resumed)
+        assert [job.job_id for job in restarted.dead_letter()] == [failed.job_id]
+        assert restarted.dead_letter(kind="ocr") == []
+
+        retry = restarted.retry(failed.job_id)
+        assert (retry.retry_of, retry.params) == (failed.job_id, {"pdf_file_name": "a.pdf"}) and retry.job_id != failed.job_id
+        retried = restarted.wait(retry.job_id, timeout=5)
+        assert retried.status == JOB_FAILED
+        assert restarted.get_job_status(failed.job_id).error == "MinerU is down"
+        assert [job.job_id for job in restarted.retries(failed.job_id)] == [retry.job_id]
+        assert [job.job_id for job in restarted.dead_letter()] == [retry.job_id]
+
+        with pytest.raises(ValueError):
+            restarted.retry(failed.job_id)
+        succeeded = restarted.wait(restarted.submit("flaky", {"ready": True}).job_id, timeout=5)
+        with pytest.raises(ValueError):
+            restarted.retry(succeeded.job_id)
+        assert restarted.retry("missing") is None
+        restarted.shutdown()
+
+    def test_purge_drops_finished_jobs(self, database):
+        """Test that purged jobs are read from the stored history, until it is purged as well"""
+        pool = JobPool(database)
+        pool.register("ocr", lambda params, handle: {"pages": 3})
+        pool.register("flaky", fail_until_resumed)
+        succeeded = pool.wait(pool.submit("ocr").job_id, timeout=5)
+        failed = pool.wait(pool.submit("flaky").job_id, timeout=5)
+        settle([succeeded.job_id, failed.job_id])
+
+        assert (pool.purge(older_than=timedelta(hours=1)).evicted, pool.tracked()) == (0, 2)
+        result = pool.purge()
+        assert (result.evicted, result.deleted, pool.tracked()) == (2, 0, 0)
+        assert pool.get_job_status(succeeded.job_id).status == JOB_SUCCEEDED
+        assert [job.job_id for job in pool.dead_letter()] == [failed.job_id]
+
+        assert pool.purge(history=True).deleted == 2
+        assert pool.get_job_status(succeeded.job_id) is None
+        assert database.get_job_events(succeeded.job_id) == []
+        assert pool.dead_letter() == []
+        pool.shutdown()
@@ -0,0 +1,49 @@
+"""
+Test cases for repairing near-valid JSON from LLMs
+"""
+import json
+
+import pytest
+from pydantic import BaseModel, ValidationError
+
+from textbook.json_repair import FIX_CODE_FENCE, FIX_SURROUNDING_TEXT, FIX_TRAILING_COMMA, FIX_UNQUOTED_KEY, RepairMetrics, repair_json, validate_with_repair
+
+
+class Problem(BaseModel):
+    number: str
+    statement: str
+
+
+class TestRepair:
+    """Test suite for fixing the syntax of near-valid JSON"""
+
+    def test_repair_json(self):
+        """Test that fences, trailing commas and unquoted keys are fixed and strings are left alone"""
+        repaired, fixes = repair_json('```json\n{number: "1.2", "statement": "a, } b: c", tags: [1, 2,],}\n```')
+        assert json.loads(repaired) == {"number": "1.2", "statement": "a, } b: c", "tags": [1, 2]}
+        assert fixes == [FIX_CODE_FENCE, FIX_UNQUOTED_KEY, FIX_TRAILING_COMMA]
+
+        repaired, fixes = repair_json('Here it is: {"a": true, b: null, "c": "say \\"x:\\""} hope it helps')
+        assert json.loads(repaired) == {"a": True, "b": None, "c": 'say "x:"'}
+        assert fixes == [FIX_SURROUNDING_TEXT, FIX_UNQUOTED_KEY]
+
+    def test_valid_json_is_unchanged(self):
+        """Test that valid JSON needs no fixes"""
+        assert repair_json('{"a": 1e5, "b": [true, false, null]}') == ('{"a": 1e5, "b": [true, false, null]}', [])
+
+
+class TestValidation:
+    """Test suite for validating answers with a repair step"""
+
+    def test_metrics_per_model(self):
+        """Test that valid, repaired and unrepairable answers are counted per model"""
+        metrics = RepairMetrics()
+        assert validate_with_repair('{"number": "1", "statement": "a"}', Problem, "flash", metrics) == Problem(number="1", statement="a")
+        assert validate_with_repair('{number: "1", statement: "a",}', Problem, "flash", metrics) == Problem(number="1", statement="a")
+        with pytest.raises(ValidationError):
+            validate_with_repair('{number: "1"}', Problem, "pro", metrics)
+
+        stats = metrics.snapshot()
+        assert (stats["flash"].responses, stats["flash"].repaired, stats["flash"].failed) == (2, 1, 0)
+        assert stats["flash"].fixes == {FIX_UNQUOTED_KEY: 1, FIX_TRAILING_COMMA: 1}
+        assert (stats["pro"].responses, stats["pro"].repaired, stats["pro"].failed) == (1, 0, 1)
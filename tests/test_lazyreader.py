@@ -0,0 +1,100 @@
+"""
+Test cases for the terminal review loop and the embedded local mode
+"""
+import tempfile
+from pathlib import Path
+
+import pytest
+
+from lazyreader.__main__ import chapter_id, main
+from lazyreader.client import ApiError
+from lazyreader.local import LocalClient
+from lazyreader.review import ReviewSession, render, run_review
+from textbook.database import FlashcardInfo
+
+
+def make_cards():
+    return [
+        {"card_id": 1, "front": "Define a topology", "back": "A collection of open sets", "book_name": "Topology", "is_new": True},
+        {"card_id": 2, "front": "Define a basis", "back": "Sets generating the topology", "book_name": "Topology", "is_new": False},
+    ]
+
+
+class TestReviewSession:
+    """Test suite for grading cards from the keyboard"""
+
+    def test_grade_needs_reveal(self):
+        """Test that grade keys do nothing until the back is shown"""
+        session = ReviewSession(make_cards())
+        assert session.press("3", 1.0) is None
+        assert session.press(" ", 1.0) is None and session.revealed
+        assert session.press("x", 2.0) is None
+        assert session.press("3", 2.5) == {"card_id": 1, "grade": 3, "duration_ms": 2500}
+        assert (session.index, session.revealed) == (1, False)
+
+    def test_quit_keeps_graded(self):
+        """Test that quitting ends the session with the grades so far"""
+        session = ReviewSession(make_cards())
+        session.press(" ", 0.0)
+        session.press("1", 1.0)
+        session.press("q", 2.0)
+        assert session.done and session.card is None
+        assert [review["grade"] for review in session.graded] == [1]
+
+    def test_run_review_submits_each_grade(self):
+        """Test that every grade is submitted as soon as it is given"""
+        submitted = []
+        keys = iter([" ", "4", "\r", "2"])
+        screens = []
+        session = run_review({"cards": make_cards(), "due_count": 2}, submitted.append, lambda: next(keys), out=lambda text, end="": screens.append(text))
+
+        assert [(review["card_id"], review["grade"]) for review in submitted] == [(1, 4), (2, 2)]
+        assert session.done and "Reviewed 2 cards" in screens[-1]
+        assert "Define a topology" in screens[0] and "A collection of open sets" not in screens[0]
+        assert "A collection of open sets" in render(ReviewSession(make_cards(), revealed=True), 2)
+
+
+class TestLocalClient:
+    """Test suite for studying without a server"""
+
+    @pytest.fixture
+    def client(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            with LocalClient(Path(tmpdir) / "lazyreader", "alice") as client:
+                yield client
+
+    def test_review_round_trip(self, client):
+        """Test that cards are reviewed against the database in the data directory"""
+        book = client.database.create_book("analysis", "me", "sequences", "analysis", 3)
+        card_ids = client.database.create_flashcards(book.book_id, [FlashcardInfo(front="front", back="back")])
+
+        batch = client.review_batch()
+        assert [(card["card_id"], card["book_name"], card["is_new"]) for card in batch["cards"]] == [(card_ids[0], "analysis", True)]
+        submitted = client.submit_reviews([{"card_id": card_ids[0], "grade": 3, "duration_ms": 1200}])
+        assert [review["card_id"] for review in submitted["reviews"]] == card_ids
+        assert client.review_batch()["cards"] == []
+        assert (client.data_dir / "textbook_context.db").exists()
+
+    def test_chapters_are_numbered_within_the_book(self, client, capsys):
+        """Test that the chapter number of the quiz command is resolved within its book, not used as a chapter ID"""
+        topology = client.database.create_book("topology", "me", "spaces", "topology", 30)
+        analysis = client.database.create_book("analysis", "me", "sequences", "analysis", 30)
+        client.database.try_create_chapter_info(topology.book_id, "Spaces", "1", 1, 10)
+        client.database.try_create_chapter_info(analysis.book_id, "Limits", "2", 11, 20)
+        sequences = client.database.try_create_chapter_info(analysis.book_id, "Sequences", "1", 1, 10)
+
+        assert chapter_id(client, analysis.book_id, 1) == sequences
+        with pytest.raises(ApiError, match="Chapter 3 not found in book"):
+            chapter_id(client, analysis.book_id, 3)
+        assert main(["--local", "--data-dir", str(client.data_dir), "quiz", "--book", str(topology.book_id), "--chapter", "2"]) == 1
+        assert "it has 1 chapters" in capsys.readouterr().out
+
+    def test_errors_match_server(self, client):
+        """Test that invalid requests fail with ApiError like the server's"""
+        with pytest.raises(ApiError) as error:
+            client.submit_reviews([{"card_id": 999, "grade": 3}])
+        assert error.value.status_code == 400
+        with pytest.raises(ApiError):
+            client.quiz(1, 1)
+        with pytest.raises(ApiError):
+            client.cancel_job("job")
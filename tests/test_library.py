@@ -0,0 +1,102 @@
+"""
+Test cases for the document library
+"""
+
+import pytest
+
+from textbook.library import (
+    DOCUMENT_TYPE_ARTICLE,
+    DOCUMENT_TYPE_PROBLEM_SET,
+    DOCUMENT_TYPE_TEXTBOOK,
+    DOCUMENT_TYPE_UNKNOWN,
+    OCR_DONE,
+    OCR_NOT_NEEDED,
+    OCR_PENDING,
+    add_upload,
+    delete_document,
+    detect_type,
+    document_tags,
+    list_documents,
+    parse_tags,
+    rename_document,
+    set_ocr_status,
+    tag_document,
+)
+from textbook.reader import BOOK_SOURCE_WEB_ARTICLE
+
+
+class TestDetection:
+    """Test suite for telling problem sets from textbooks"""
+
+    def test_detect_type(self):
+        """Test that numbered problems with little text between them make a problem set"""
+        problems = "\n\n".join(f"## Problem {number}\nShow that the sum of two even numbers is even." for number in range(1, 6))
+        assert detect_type(problems) == DOCUMENT_TYPE_PROBLEM_SET
+        assert detect_type("# Chapter 1\n" + "Groups are sets with an operation. " * 400 + "\nExercise 1\nExercise 2\nExercise 3") == DOCUMENT_TYPE_TEXTBOOK
+        assert detect_type("  ") == DOCUMENT_TYPE_UNKNOWN
+
+    def test_parse_tags(self):
+        """Test that tags are lowercased, deduplicated and joined by dashes"""
+        assert parse_tags(["Linear Algebra", "linear-algebra", " ", "Midterm"]) == ["linear-algebra", "midterm"]
+
+
+class TestLibrary:
+    """Test suite for keeping, renaming, tagging and deleting documents"""
+
+    def test_books_are_cataloged_once(self, database):
+        """Test that listing the library adds every book once, with its type and OCR status from its source"""
+        book = database.create_book("Algebra", "Artin", "", "algebra", 500)
+        article = database.create_book("Monads", "", "", "monads", 3)
+        database.update_book_source(article.book_id, BOOK_SOURCE_WEB_ARTICLE, "https://example.com/monads")
+
+        documents = list_documents(database)
+        assert [(document.title, document.detected_type, document.ocr_status) for document in documents] == [
+            ("Algebra", DOCUMENT_TYPE_TEXTBOOK, OCR_DONE),
+            ("Monads", DOCUMENT_TYPE_ARTICLE, OCR_NOT_NEEDED),
+        ]
+        assert documents[0].source_path == "algebra.pdf" and documents[0].book_id == book.book_id
+        assert documents[1].source_path == "https://example.com/monads"
+        assert len(list_documents(database)) == 2
+        assert [document.title for document in list_documents(database, detected_type=DOCUMENT_TYPE_ARTICLE)] == ["Monads"]
+        with pytest.raises(ValueError):
+            list_documents(database, detected_type="novel")
+
+    def test_upload_follows_ocr(self, database):
+        """Test that an upload is pending until its OCR is done and then has the detected type"""
+        document = add_upload(database, "homework", "a.pdf", 2)
+        assert (document.ocr_status, document.detected_type) == (OCR_PENDING, DOCUMENT_TYPE_UNKNOWN)
+
+        set_ocr_status(database, document.document_id, OCR_DONE, DOCUMENT_TYPE_PROBLEM_SET)
+        assert [stored.title for stored in list_documents(database, ocr_status=OCR_DONE)] == ["homework"]
+        assert database.get_document(document.document_id).detected_type == DOCUMENT_TYPE_PROBLEM_SET
+
+    def test_rename_and_tag(self, database):
+        """Test that renaming a document renames its book and tags can be added and removed"""
+        book = database.create_book("algebra", "", "", "algebra", 500)
+        document = list_documents(database)[0]
+
+        renamed = rename_document(database, document.document_id, " Algebra ", "Artin")
+        assert (renamed.title, renamed.author) == ("Algebra", "Artin")
+        assert (database.get_book_by_id(book.book_id).book_name, database.get_book_by_id(book.book_id).book_author) == ("Algebra", "Artin")
+        with pytest.raises(ValueError):
+            rename_document(database, document.document_id, " ")
+
+        tag_document(database, document.document_id, add=["Group Theory", "exam"])
+        tagged = tag_document(database, document.document_id, add=["qualifying"], remove=["Exam"])
+        assert document_tags(tagged) == ["group-theory", "qualifying"]
+        assert [stored.document_id for stored in list_documents(database, tag="group theory")] == [document.document_id]
+        assert list_documents(database, tag="exam") == []
+        assert rename_document(database, 999, "Missing") is None
+        assert tag_document(database, 999, add=["a"]) is None
+
+    def test_delete_document(self, database):
+        """Test that deleting a document deletes its book and returns its files"""
+        book = database.create_book("Algebra", "", "", "algebra", 500)
+        document = list_documents(database)[0]
+        upload = add_upload(database, "homework", "a.pdf")
+
+        assert delete_document(database, document.document_id) == ["algebra.pdf"]
+        assert database.get_book_by_id(book.book_id) is None
+        assert delete_document(database, upload.document_id) == ["a.pdf", "a.md", "a.md.zst", "a_images"]
+        assert list_documents(database) == []
+        assert delete_document(database, upload.document_id) is None
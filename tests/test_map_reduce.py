@@ -0,0 +1,64 @@
+"""
+Test cases for mapping chunks of long documents and reducing the results
+"""
+import pytest
+
+from textbook.jobs import CancellationToken, JobCancelled
+from textbook.map_reduce import MAP_PROGRESS_SHARE, MapReduceError, map_reduce
+from textbook.provider_errors import ERROR_AUTH, ERROR_CONTENT_FILTER, ModelError
+
+
+def double_unless_blocked(chunk):
+    if chunk == "blocked":
+        raise ModelError(ERROR_CONTENT_FILTER, "Response was blocked due to SAFETY")
+    return chunk * 2
+
+
+class TestMapReduce:
+    """Test suite for the map-reduce helper"""
+
+    def test_results_are_reduced_in_order(self):
+        """Test that the reduce step gets every mapped result in chunk order and progress ends at the reduce step"""
+        progress = []
+        result = map_reduce(["a", "b", "c", "d"], double_unless_blocked, "".join, workers=3, report_progress=lambda *update: progress.append(update))
+
+        assert (result.result, result.chunks, result.failures) == ("aabbccdd", 4, [])
+        assert progress[0] == ("map", 0, "0/4 chunks mapped")
+        assert progress[-2] == ("map", MAP_PROGRESS_SHARE, "4/4 chunks mapped, 0 failed")
+        assert progress[-1][:2] == ("reduce", MAP_PROGRESS_SHARE)
+
+    def test_failed_chunks_are_skipped(self):
+        """Test that a few failed chunks are left out and listed with their category"""
+        result = map_reduce(["a", "blocked", "c", "d"], double_unless_blocked, "".join)
+        assert result.result == "aaccdd"
+        assert [(failure.index, failure.error_category) for failure in result.failures] == [(1, ERROR_CONTENT_FILTER)]
+        assert result.summary()["failed_chunks"][0]["index"] == 1
+
+        with pytest.raises(MapReduceError) as error:
+            map_reduce(["a", "blocked", "c", "d"], double_unless_blocked, "".join, max_failure_ratio=0)
+        assert len(error.value.failures) == 1
+
+    def test_failing_alike_raises_the_error(self):
+        """Test that when every chunk fails the same way the job fails with that error"""
+        def unauthorized(chunk):
+            raise ModelError(ERROR_AUTH, "API key not valid")
+
+        with pytest.raises(ModelError) as error:
+            map_reduce(["a", "b"], unauthorized, "".join)
+        assert error.value.category == ERROR_AUTH
+        with pytest.raises(ValueError):
+            map_reduce([], double_unless_blocked, "".join)
+
+    def test_cancelled(self):
+        """Test that chunks are no longer mapped once the job is cancelled"""
+        token = CancellationToken()
+        mapped = []
+
+        def cancel_after_first(chunk):
+            mapped.append(chunk)
+            token.cancel()
+            return chunk
+
+        with pytest.raises(JobCancelled):
+            map_reduce(["a", "b", "c"], cancel_after_first, "".join, workers=1, cancellation_token=token)
+        assert mapped == ["a"]
@@ -0,0 +1,196 @@
+"""
+Test cases for structured output on backends without schema support
+"""
+import os
+from typing import Optional
+
+import pytest
+import structlog
+from PIL import Image
+from pydantic import BaseModel, ValidationError
+
+from textbook.generation import GenerationParams, GenerationRecord
+from textbook.json_repair import extract_json
+from textbook.model import IMAGE_TYPE, LLM, SCHEMA_MODE_NATIVE, SCHEMA_MODE_PROMPTED, schema_instructions, schema_repair_attempts_from_config
+from textbook.provider_errors import ERROR_QUOTA, ModelError
+from textbook.safety import SAFETY_OPTION, SafetySettings
+
+
+class Problem(BaseModel):
+    number: str
+    statement: str
+
+
+class FakeResponse:
+    def __init__(self, text):
+        self._text = text
+
+    def text(self):
+        return self._text
+
+
+class FakeModel:
+    """A text model without schema support answering with canned responses"""
+
+    def __init__(self, answers):
+        self.answers = list(answers)
+        self.prompts = []
+
+    class Options(BaseModel):
+        safety_settings: Optional[list] = None
+        temperature: Optional[float] = None
+        max_output_tokens: Optional[int] = None
+
+    def prompt(self, prompt, attachments=None, **kwargs):
+        assert "schema" not in kwargs
+        self.prompts.append(prompt)
+        self.options = kwargs
+        self.attachments = [(attachment.type, os.path.exists(attachment.path)) for attachment in attachments or []]
+        answer = self.answers.pop(0)
+        if isinstance(answer, Exception):
+            raise answer
+        return FakeResponse(answer)
+
+
+def prompted_llm(answers) -> LLM:
+    model = LLM.__new__(LLM)
+    model.logger = structlog.get_logger("LLM")
+    model.model_name = "fake"
+    model.text_model = FakeModel(answers)
+    model.schema_mode = SCHEMA_MODE_PROMPTED
+    return model
+
+
+class TestPromptedSchema:
+    """Test suite for the prompted JSON fallback"""
+
+    def test_extract_json(self):
+        """Test that code fences and text around the object are dropped"""
+        assert extract_json('Sure:\n```json\n{"number": "1.2"}\n```') == '{"number": "1.2"}'
+        assert extract_json('Here it is {"number": "1.2"} hope it helps') == '{"number": "1.2"}'
+        assert '"statement"' in schema_instructions("Extract the problem", Problem)
+
+    def test_invalid_answer_is_repaired(self):
+        """Test that an answer failing validation is sent back once with the error"""
+        model = prompted_llm(['{"number": "1.2"}', '```json\n{"number": "1.2", "statement": "Show that"}\n```'])
+        problem = model.prompt_with_schema("Extract the problem", Problem)
+
+        assert problem == Problem(number="1.2", statement="Show that")
+        assert len(model.text_model.prompts) == 2
+        assert "not valid against the schema" in model.text_model.prompts[1]
+
+    def test_near_valid_answer_is_not_sent_back(self):
+        """Test that an answer with a trailing comma is repaired locally instead of asking again"""
+        model = prompted_llm(['{"number": "1.2", "statement": "Show that",}'])
+        assert model.prompt_with_schema("Extract the problem", Problem) == Problem(number="1.2", statement="Show that")
+        assert len(model.text_model.prompts) == 1
+
+    def test_answers_are_logged(self):
+        """Test that every answer is logged with its prompt, rejected ones with the validation error, accepted ones parsed"""
+        records = []
+        model = prompted_llm(['{"number": "1.2"}', '{"number": "1.2", "statement": "Show that",}'])
+        model.prompt_log = type("FakeLog", (), {"record": lambda self, *entry, **details: records.append((*entry, details))})()
+        model.prompt_with_schema("Extract the problem", Problem)
+
+        assert [(name, schema_name, error is not None) for name, _, _, schema_name, error, _ in records] == [("fake", "Problem", True), ("fake", "Problem", False)]
+        assert records[1][1].startswith("Extract the problem")
+        assert records[0][5] == {"parsed": None, "attempt": 0}
+        assert records[1][5] == {"parsed": '{"number":"1.2","statement":"Show that"}', "attempt": 1}
+
+    def test_gives_up_after_repair(self):
+        """Test that a second invalid answer raises the validation error"""
+        model = prompted_llm(["no idea", "still no idea"])
+        with pytest.raises(ValidationError):
+            model.prompt_with_schema("Extract the problem", Problem)
+
+    def test_repair_attempts_are_configured(self):
+        """Test that invalid answers are sent back as many times as configured before giving up"""
+        assert schema_repair_attempts_from_config({}) == 1
+        assert schema_repair_attempts_from_config({"schema_repair_attempts": 3}) == 3
+        for attempts in (-1, 6, 1.5, True):
+            with pytest.raises(ValueError):
+                schema_repair_attempts_from_config({"schema_repair_attempts": attempts})
+
+        model = prompted_llm(["no idea", "still no idea", '{"number": "1.2", "statement": "Show that"}'])
+        model.schema_repair_attempts = 2
+        assert model.prompt_with_schema("Extract the problem", Problem) == Problem(number="1.2", statement="Show that")
+        assert "still no idea" in model.text_model.prompts[2]
+
+        model = prompted_llm(["no idea"])
+        model.schema_repair_attempts = 0
+        with pytest.raises(ValidationError):
+            model.prompt_with_schema("Extract the problem", Problem)
+        assert len(model.text_model.prompts) == 1
+
+    def test_native_schema_answers_are_repaired(self):
+        """Test that backends with schema support also get an invalid answer back, with the schema on every prompt"""
+        model = prompted_llm(['{"number": "1.2"}', '{"number": "1.2", "statement": "Show that"}'])
+        model.schema_mode = SCHEMA_MODE_NATIVE
+        schemas = []
+        prompt = model.text_model.prompt
+        model.text_model.prompt = lambda text, schema=None, **kwargs: (schemas.append(schema), prompt(text, **kwargs))[1]
+        assert model.prompt_with_schema("Extract the problem", Problem) == Problem(number="1.2", statement="Show that")
+        assert schemas == [Problem, Problem]
+        assert model.text_model.prompts[0] == "Extract the problem"
+        assert "not valid against the schema" in model.text_model.prompts[1]
+
+    def test_provider_error_is_mapped(self):
+        """Test that an error of the provider is raised as a ModelError with its category"""
+        model = prompted_llm([Exception("429 Resource has been exhausted (e.g. check quota).")])
+        with pytest.raises(ModelError) as error:
+            model.prompt_with_schema("Extract the problem", Problem)
+        assert error.value.category == ERROR_QUOTA
+        assert error.value.detail.startswith("429")
+
+    def test_images_are_attached(self):
+        """Test that images go with the prompt for models that can see them, and are left out otherwise"""
+        images = [Image.new("RGB", (20, 30), "white"), Image.new("RGB", (20, 30), "black")]
+        model = prompted_llm(['{"number": "1.2", "statement": "Show that"}', '{"number": "1.3", "statement": "Show that"}'])
+        assert not model.supports_images
+        model.prompt_with_schema_and_images("Extract the problem", Problem, images)
+        assert model.text_model.attachments == []
+
+        model.text_model.attachment_types = {"image/jpeg", IMAGE_TYPE}
+        assert model.prompt_with_schema_and_images("Extract the problem", Problem, images).number == "1.3"
+        assert model.text_model.attachments == [(IMAGE_TYPE, True), (IMAGE_TYPE, True)]
+
+    def test_safety_settings_are_passed(self):
+        """Test that safety settings go with every prompt when the model supports them, and are dropped otherwise"""
+        model = prompted_llm(['{"number": "1.2", "statement": "Show that"}'])
+        model.prompt_options = model._supported_options(SafetySettings("gemini", {"dangerous_content": "block_none"}).prompt_options())
+        model.prompt_with_schema("Extract the problem", Problem)
+        assert model.text_model.options[SAFETY_OPTION] == [{"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "BLOCK_NONE"}]
+
+        model.text_model.Options = BaseModel
+        assert model._supported_options({SAFETY_OPTION: []}) == {}
+
+    def test_generation_parameters_are_passed(self):
+        """Test that a model with other generation parameters prompts with them, and the original is left as it was"""
+        model = prompted_llm(['{"number": "1.2", "statement": "Show that"}'])
+        model.safety_options = {}
+        model.generation = GenerationParams()
+        model.prompt_options = {}
+        assert model.with_generation(GenerationParams()) is model
+
+        checker = model.with_generation(GenerationParams(temperature=0.0, max_tokens=512))
+        checker.prompt_with_schema("Extract the problem", Problem)
+        assert model.text_model.options == {"temperature": 0.0, "max_output_tokens": 512}
+        assert model.prompt_options == {} and checker.model_name == "fake"
+
+        # The fake plugin has no seed option, the seed is not sent and not recorded
+        seeded = model.with_generation(GenerationParams(temperature=0.0, seed=42))
+        assert seeded.generation_record() == GenerationRecord("fake", 0.0, None)
+
+    def test_usage_is_recorded(self):
+        """Test that the tokens the provider reports are recorded, and estimated from the text when it reports none"""
+        records = []
+        model = prompted_llm(['{"number": "1.2", "statement": "Show that"}', '{"number": "1.3", "statement": "Show that"}'])
+        model.usage_tracker = type("FakeTracker", (), {"record": lambda self, *entry: records.append(entry)})()
+        counts = type("Usage", (), {"input": 120, "output": 30})()
+        model.text_model.prompt = lambda prompt, **kwargs: type("Response", (FakeResponse,), {"usage": lambda self: counts})(model.text_model.answers.pop(0))
+        model.prompt_with_schema("Extract the problem", Problem)
+        assert records == [("fake", 120, 30, False)]
+
+        del model.text_model.prompt
+        model.prompt_with_schema("Extract the problem", Problem)
+        assert records[1][0] == "fake" and records[1][3] is True and records[1][2] == len('{"number": "1.3", "statement": "Show that"}') // 4
@@ -0,0 +1,169 @@
+"""
+Test cases for Jupyter notebook ingestion and code exercises
+"""
+import json
+
+import pymupdf
+import pytest
+
+from textbook.notebook import (
+    NotebookError,
+    ingest_notebook,
+    markdown_language,
+    notebook_to_markdown,
+    parse_notebook,
+)
+from textbook.reader import (
+    BOOK_SOURCE_NOTEBOOK,
+    EXERCISE_TYPE_CODE,
+    CodeExerciseSchema,
+    CodeExercisesSchema,
+    LazyTextbookReader,
+    PageSchema,
+    PageSummarySchema,
+)
+from textbook.web_article import markdown_to_html
+
+
+def make_notebook(cells, language="python", title=None) -> str:
+    metadata = {"kernelspec": {"name": "python3", "language": language}, "language_info": {"name": language}}
+    if title:
+        metadata["title"] = title
+    return json.dumps({"nbformat": 4, "nbformat_minor": 5, "metadata": metadata, "cells": cells})
+
+
+CELLS = [
+    {"cell_type": "markdown", "source": ["# Gradient descent\n", "\n", "We minimize $f(x) = x^2$.\n", "## Setup\n", "Import the libraries."]},
+    {"cell_type": "code", "source": "import numpy as np\n\ndef grad(x):\n    return 2 * x", "outputs": []},
+    {"cell_type": "code", "source": ["print(grad(3))"], "outputs": [{"output_type": "stream", "name": "stdout", "text": ["6\n"]}]},
+    {"cell_type": "markdown", "source": "## Iterations"},
+    {"cell_type": "code", "source": "x", "outputs": [{"output_type": "display_data", "data": {"image/png": "iVBORw0KGgo="}}]},
+    {"cell_type": "code", "source": "", "outputs": []},
+]
+
+
+class TestNotebookConversion:
+    """Test suite for notebook parsing and markdown conversion"""
+
+    def test_parse_notebook(self):
+        """Test that cells, outputs and the kernel language are read, empty cells dropped"""
+        notebook = parse_notebook(make_notebook(CELLS))
+
+        assert notebook.language == "python"
+        assert [cell.cell_type for cell in notebook.cells] == ["markdown", "code", "code", "markdown", "code"]
+        assert notebook.cells[1].source == "import numpy as np\n\ndef grad(x):\n    return 2 * x"
+        assert notebook.cells[2].outputs == ["6\n"]
+        assert notebook.cells[4].outputs == ["[image output]"]
+
+    @pytest.mark.parametrize("raw", ["not json", json.dumps({"metadata": {}}), json.dumps({"nbformat": 3, "cells": []}), make_notebook([])])
+    def test_invalid_notebooks_are_rejected(self, raw):
+        """Test that files that are not usable nbformat 4 notebooks raise"""
+        with pytest.raises(NotebookError):
+            parse_notebook(raw)
+
+    def test_code_cells_are_preserved(self):
+        """Test that code cells keep their blank lines and are tagged with the language"""
+        markdown, _ = notebook_to_markdown(parse_notebook(make_notebook(CELLS)), "lesson")
+
+        assert "```python\nimport numpy as np\n\ndef grad(x):\n    return 2 * x\n```" in markdown
+        assert "```output\n6\n```" in markdown
+
+    def test_leading_heading_becomes_title(self):
+        """Test that the opening level 1 heading is the title and the rest are headings"""
+        markdown, headings = notebook_to_markdown(parse_notebook(make_notebook(CELLS)), "lesson")
+
+        assert markdown.startswith("# Gradient descent\n\nWe minimize")
+        assert headings == [(2, "Setup"), (2, "Iterations")]
+
+    def test_explicit_title_keeps_leading_heading(self):
+        """Test that a given title does not swallow the notebook's first heading"""
+        markdown, headings = notebook_to_markdown(parse_notebook(make_notebook(CELLS)), "Week 3", use_leading_heading=False)
+
+        assert markdown.startswith("# Week 3\n\n# Gradient descent")
+        assert headings[0] == (1, "Gradient descent")
+
+    def test_code_containing_fences(self):
+        """Test that code containing a fence is wrapped in a longer fence and rendered whole"""
+        cells = [{"cell_type": "code", "source": 'doc = """\n```\nexample\n```\n"""', "outputs": []}]
+        markdown, _ = notebook_to_markdown(parse_notebook(make_notebook(cells)), "fences")
+
+        assert "````python\n" in markdown
+        html = markdown_to_html(markdown)
+        assert '<pre>doc = """\n```\nexample\n```\n"""</pre>' in html
+
+    def test_long_outputs_are_truncated(self):
+        """Test that long outputs keep their first lines only"""
+        cells = [{"cell_type": "code", "source": "train()", "outputs": [{"output_type": "stream", "text": "".join(f"epoch {i}\n" for i in range(50))}]}]
+        markdown, _ = notebook_to_markdown(parse_notebook(make_notebook(cells)), "training")
+
+        assert "epoch 19\n... (30 more lines)" in markdown
+        assert "epoch 20" not in markdown
+
+    def test_markdown_language(self):
+        """Test that the language is recovered from the first code fence, skipping outputs"""
+        assert markdown_language("# t\n\n```output\n1\n```\n\n```julia\nx = 1\n```\n") == "julia"
+        assert markdown_language("# t\n\nno code") == "python"
+
+    def test_info_string_is_not_rendered(self):
+        """Test that the fence language does not leak into the rendered code"""
+        assert markdown_to_html("```python\nx = 1\n```") == "<pre>x = 1</pre>"
+
+
+class FakeCodeLLM:
+    """Returns fixed summaries and exercises, recording the prompts"""
+
+    def __init__(self):
+        self.prompts = []
+
+    def prompt_with_schema(self, prompt, schema):
+        self.prompts.append(prompt)
+        if schema is PageSchema:
+            return PageSchema(page_summary=[PageSummarySchema(title="gradient", summary="`grad(x)` returns 2x")], has_exercises=False)
+        assert schema is CodeExercisesSchema
+        return CodeExercisesSchema(exercises=[
+            CodeExerciseSchema(description="Write grad for x^3", starter_code="def grad(x):\n    pass", solution_code="def grad(x):\n    return 3 * x ** 2", difficulty_level=9),
+        ])
+
+
+class TestNotebookIngestion:
+    """Test suite for notebook ingestion, summaries and code exercises"""
+
+    @pytest.fixture
+    def ingested(self, tmpdir, database):
+        ingested = ingest_notebook(make_notebook(CELLS), tmpdir / "nb.pdf", tmpdir / "nb.md", fallback_title="lesson")
+        with pymupdf.open(tmpdir / "nb.pdf") as document:
+            book = database.create_book(ingested.title, "", "python", "nb", len(document))
+        database.update_book_source(book.book_id, BOOK_SOURCE_NOTEBOOK)
+        return ingested
+
+    def test_ingest_notebook(self, tmpdir, ingested):
+        """Test that the markdown and PDF are written and headings become chapters"""
+        assert ingested.title == "Gradient descent"
+        assert (tmpdir / "nb.md").read_text(encoding="utf-8") == ingested.markdown
+        with pymupdf.open(tmpdir / "nb.pdf") as document:
+            assert "def grad(x):" in document[0].get_text()
+        assert [chapter["title"] for chapter in ingested.toc["chapters"]] == ["Setup", "Iterations"]
+
+    def test_notebook_pages_use_code_prompt(self, tmpdir, database, ingested):
+        """Test that notebook pages are summarized with the code-aware prompt"""
+        llm = FakeCodeLLM()
+        with LazyTextbookReader(tmpdir / "nb.pdf", llm, database) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            reader.create_or_update_page_info(0)
+
+        assert "course notebook" in llm.prompts[0]
+
+    def test_generate_code_exercises(self, tmpdir, database, ingested):
+        """Test that generated exercises are stored as code exercises"""
+        with LazyTextbookReader(tmpdir / "nb.pdf", FakeCodeLLM(), database) as reader:  # type: ignore[arg-type]
+            reader.check_if_book_exists_and_load()
+            exercise_ids = reader.generate_code_exercises(0, "python", count=1)
+            book_id = reader.book_info.book_id
+
+        assert len(exercise_ids) == 1
+        exercise = database.get_exercise_info(exercise_ids[0])
+        assert exercise.exercise_type == EXERCISE_TYPE_CODE
+        assert exercise.starter_code == "def grad(x):\n    pass"
+        assert exercise.code_language == "python"
+        assert [item.exercise_id for item in database.get_exercises_by_book_id(book_id, EXERCISE_TYPE_CODE)] == exercise_ids
+        assert database.get_exercises_by_book_id(book_id, "text") == []
@@ -0,0 +1,224 @@
+"""
+Test cases for the OCR backends selected in config.toml
+"""
+import io
+import json
+import sys
+import types
+import zipfile
+
+import pymupdf
+import pytest
+import requests
+from PIL import Image
+
+import textbook.mineru as mineru
+import textbook.ocr as ocr
+from textbook.circuit_breaker import BREAKER_OPEN, SERVICE_MINERU, CircuitBreakers, CircuitBreakerSettings
+from textbook.mineru import MinerURequest, MinerUSettings, ocr_pdf_artifacts, read_zip_results
+from textbook.ocr import (
+    OCR_MINERU,
+    OCR_TESSERACT,
+    MinerUBackend,
+    OcrBackend,
+    OcrResult,
+    OcrSettings,
+    TesseractBackend,
+    create_ocr_backend,
+    ocr_settings_from_config,
+    register_ocr_backend,
+)
+from textbook.provider_errors import ERROR_TIMEOUT, ModelError
+
+PAGE_TEXT = "Theorem 1.2. Every group of prime order is cyclic."
+
+
+class FakeTesseract(types.ModuleType):
+    """Stands in for pytesseract, recording the language of every image it reads"""
+
+    def __init__(self):
+        super().__init__("pytesseract")
+        self.pytesseract = types.SimpleNamespace(tesseract_cmd=None)
+        self.languages = []
+
+    def image_to_string(self, image, lang="eng"):
+        self.languages.append(lang)
+        return f"scanned page {len(self.languages)}\n"
+
+
+class TestOcrBackends:
+    """Test suite for choosing and running OCR backends"""
+
+    @pytest.fixture
+    def pdf_path(self, tmpdir):
+        # A page with a text layer, then a blank page standing in for a scan
+        path = tmpdir / "algebra.pdf"
+        with pymupdf.open() as document:
+            document.new_page().insert_text((72, 72), PAGE_TEXT)
+            document.new_page()
+            document.save(path)
+        return path
+
+    @pytest.fixture
+    def tesseract(self, monkeypatch):
+        fake = FakeTesseract()
+        monkeypatch.setitem(sys.modules, "pytesseract", fake)
+        return fake
+
+    def test_settings_from_config(self):
+        """Test that MinerU is the default backend and invalid settings are rejected"""
+        assert ocr_settings_from_config({}) == OcrSettings()
+        assert isinstance(create_ocr_backend(), MinerUBackend)
+        settings = ocr_settings_from_config({"ocr": {"backend": OCR_TESSERACT, "tesseract_cmd": "/opt/bin/tesseract", "dpi": 200}})
+        backend = create_ocr_backend(settings)
+        assert isinstance(backend, TesseractBackend) and (backend.tesseract_cmd, backend.dpi) == ("/opt/bin/tesseract", 200)
+        for section in ({"backend": "textract"}, {"dpi": 50}, {"tesseract_cmd": ""}, {"languages": ["en"]}):
+            with pytest.raises(ValueError):
+                ocr_settings_from_config({"ocr": section})
+
+    def test_mineru_settings_from_config(self):
+        """Test that the timeouts and upload limit of MinerU requests are read from the [ocr] section"""
+        settings = ocr_settings_from_config({"ocr": {"mineru_connect_timeout": 5, "mineru_request_timeout": 600, "mineru_timeout_retries": 0, "mineru_max_upload_mb": 50}})
+        assert settings.mineru == MinerUSettings(connect_timeout_seconds=5, request_timeout_seconds=600, max_upload_mb=50, timeout_retries=0)
+        assert create_ocr_backend(settings).settings == settings.mineru
+        for section in ({"mineru_request_timeout": 0}, {"mineru_max_upload_mb": "big"}, {"mineru_timeout_retries": -1}, {"mineru_timeout_retries": 1.5}):
+            with pytest.raises(ValueError):
+                ocr_settings_from_config({"ocr": section})
+
+    def test_mineru_requests_time_out(self, pdf_path, monkeypatch):
+        """Test that a request MinerU does not answer is sent again, then fails with the timeout category"""
+        timeouts = []
+
+        def post(endpoint, files, data, timeout):
+            timeouts.append(timeout)
+            raise requests.exceptions.ReadTimeout("Read timed out")
+
+        monkeypatch.setattr(mineru.requests, "post", post)
+        monkeypatch.setattr(mineru, "circuit_breakers", CircuitBreakers(CircuitBreakerSettings(failure_threshold=10, reset_seconds=60)))
+        settings = MinerUSettings(connect_timeout_seconds=2, request_timeout_seconds=30, timeout_retries=2)
+        with pytest.raises(ModelError) as error:
+            MinerURequest([str(pdf_path)], settings=settings).request()
+        assert error.value.category == ERROR_TIMEOUT and "on 3 attempts" in error.value.detail
+        assert timeouts == [(2, 30)] * 3
+
+    def test_mineru_zip_responses(self, pdf_path, monkeypatch):
+        """Test that a zip archive is read like a JSON answer, with the figures and the content list"""
+        content_list = [{"type": "image", "img_path": "images/fig1.jpg", "page_idx": 0}]
+        archive = io.BytesIO()
+        with zipfile.ZipFile(archive, "w") as zip_file:
+            zip_file.writestr("algebra/algebra.md", "# Groups\n\n![](images/fig1.jpg)")
+            zip_file.writestr("algebra/algebra_content_list.json", json.dumps(content_list))
+            zip_file.writestr("algebra/images/fig1.jpg", b"\xff\xd8jpeg")
+        forms = []
+
+        def post(endpoint, files, data, timeout):
+            forms.append(data)
+            response = types.SimpleNamespace(content=archive.getvalue(), raise_for_status=lambda: None)
+            response.json = lambda: pytest.fail("read as JSON")
+            return response
+
+        monkeypatch.setattr(mineru.requests, "post", post)
+        monkeypatch.setattr(mineru, "circuit_breakers", CircuitBreakers())
+        result = ocr_pdf_artifacts(str(pdf_path), settings=MinerUSettings(zip_response=True))
+        assert forms[0]["response_format_zip"] is True
+        assert (result.markdown, result.content_list, result.images) == ("# Groups\n\n![](images/fig1.jpg)", content_list, {"fig1.jpg": b"\xff\xd8jpeg"})
+        with pytest.raises(ValueError, match="invalid zip"):
+            read_zip_results(b"not a zip")
+        with zipfile.ZipFile(archive, "a") as zip_file:
+            zip_file.writestr("algebra/images/figures/fig1.jpg", b"\xff\xd8other")
+        with pytest.raises(ValueError, match="two images named fig1.jpg"):
+            read_zip_results(archive.getvalue())
+
+    def test_mineru_unreadable_answers_end_the_probe(self, pdf_path, monkeypatch):
+        """Test that a probe answered with a corrupt zip opens the breaker again instead of blocking every later request"""
+        now = [0.0]
+        answers = [b"not a zip", b"still not a zip"]
+
+        def post(endpoint, files, data, timeout):
+            return types.SimpleNamespace(content=answers.pop(0), raise_for_status=lambda: None)
+
+        breakers = CircuitBreakers(CircuitBreakerSettings(failure_threshold=1, reset_seconds=30), lambda: now[0])
+        breakers.get(SERVICE_MINERU).record_failure()
+        monkeypatch.setattr(mineru.requests, "post", post)
+        monkeypatch.setattr(mineru, "circuit_breakers", breakers)
+        settings = MinerUSettings(zip_response=True)
+        now[0] = 30
+        with pytest.raises(ValueError, match="invalid zip"):
+            MinerURequest([str(pdf_path)], settings=settings).request()
+        assert breakers.get(SERVICE_MINERU).status().state == BREAKER_OPEN
+        now[0] = 60
+        with pytest.raises(ValueError, match="invalid zip"):
+            MinerURequest([str(pdf_path)], settings=settings).request()
+        assert answers == []
+
+    def test_mineru_refuses_large_uploads(self, pdf_path, monkeypatch):
+        """Test that a file above the upload limit is refused before anything is sent to MinerU"""
+        monkeypatch.setattr(mineru.requests, "post", lambda *args, **kwargs: pytest.fail("uploaded"))
+        with pytest.raises(ValueError, match="mineru_max_upload_mb"):
+            MinerURequest([str(pdf_path)], settings=MinerUSettings(max_upload_mb=0.0001)).request()
+
+    def test_backends_can_be_registered(self, monkeypatch):
+        """Test that a backend registered by name can be selected in config.toml"""
+
+        class MarkerBackend(OcrBackend):
+            name = "marker"
+
+            def unavailable(self):
+                return None
+
+            def ocr_pdf(self, pdf_path, language, parse_method, job_id=None):
+                return OcrResult("# Groups", [{"type": "text", "text": "# Groups", "page_idx": 0}])
+
+            def ocr_image(self, image):
+                return ""
+
+        monkeypatch.setattr(ocr, "_backends", dict(ocr._backends))
+        register_ocr_backend("marker", lambda settings: MarkerBackend())
+        backend = create_ocr_backend(ocr_settings_from_config({"ocr": {"backend": "marker"}}))
+        assert backend.ocr_pdf("algebra.pdf", "en", "auto").markdown == "# Groups"
+
+    def test_mineru_output_dir_is_named_after_the_job(self, monkeypatch):
+        """Test that MinerU writes the outputs of an OCR job to a directory of its own"""
+        calls = []
+        monkeypatch.setattr(ocr, "ocr_pdf_artifacts", lambda *args, **kwargs: calls.append((args, kwargs)) or OcrResult("# Groups"))
+        assert MinerUBackend().ocr_pdf("algebra.pdf", "ch", "ocr", job_id="job-1").markdown == "# Groups"
+        (pdf_path, lang_list, parse_method), kwargs = calls[0]
+        assert (pdf_path, lang_list, parse_method) == ("algebra.pdf", ["ch"], "ocr")
+        assert kwargs["output_dir"].endswith("/job-1")
+
+    def test_mineru_reads_page_ranges(self, monkeypatch):
+        """Test that MinerU reads a range of pages from the whole PDF, its blocks put back on their pages"""
+        calls = []
+        result = OcrResult("# Groups", [{"type": "text", "text": "# Groups", "page_idx": 0}, {"type": "text", "text": "Lagrange", "page_idx": 1}])
+        monkeypatch.setattr(ocr, "ocr_pdf_artifacts", lambda *args, **kwargs: calls.append(kwargs) or result)
+        pages = MinerUBackend().ocr_pages("algebra.pdf", [40, 41], "en", "auto", job_id="job-1")
+        assert (calls[0]["start_page_id"], calls[0]["end_page_id"]) == (40, 41)
+        assert [block["page_idx"] for block in pages.content_list] == [40, 41]
+
+    def test_tesseract_reads_the_text_layer_first(self, pdf_path, tesseract):
+        """Test that pages with a text layer are read from it and the others are OCRed, keeping their page"""
+        result = TesseractBackend().ocr_pdf(str(pdf_path), "ch", "auto")
+        assert [block["page_idx"] for block in result.content_list] == [0, 1]
+        assert result.content_list[0]["text"] == PAGE_TEXT
+        assert result.content_list[1]["text"] == "scanned page 1"
+        assert result.markdown == f"{PAGE_TEXT}\n\nscanned page 1"
+        assert (tesseract.languages, result.images) == (["chi_sim"], {})
+
+        # Every page is OCRed with parse_method ocr, none with txt
+        assert len(TesseractBackend().ocr_pdf(str(pdf_path), "deu", "ocr").content_list) == 2
+        assert tesseract.languages[-2:] == ["deu", "deu"]
+        assert [block["page_idx"] for block in TesseractBackend().ocr_pdf(str(pdf_path), "en", "txt").content_list] == [0]
+        assert len(tesseract.languages) == 3
+
+    def test_tesseract_reads_page_images(self, tesseract):
+        """Test that pages without a text layer are read by the configured binary"""
+        backend = TesseractBackend(tesseract_cmd="/opt/bin/tesseract")
+        assert backend.ocr_image(Image.new("RGB", (100, 100), "white")) == "scanned page 1"
+        assert tesseract.pytesseract.tesseract_cmd == "/opt/bin/tesseract"
+
+    def test_unavailable_backends_explain_why(self, tesseract):
+        """Test that a backend whose binary is missing says how to install it"""
+        guidance = TesseractBackend(tesseract_cmd="/nonexistent/tesseract").unavailable()
+        assert guidance is not None and "/nonexistent/tesseract" in guidance
+        assert MinerUBackend().unavailable() is None
+        assert create_ocr_backend(OcrSettings(backend=OCR_MINERU)).name == OCR_MINERU
@@ -0,0 +1,60 @@
+"""
+Test cases for PDF validation before ingestion
+"""
+from pathlib import Path
+
+import pymupdf
+import pytest
+
+from textbook.pdf_validation import (
+    PDF_CORRUPTED,
+    PDF_ENCRYPTED,
+    PDF_WRONG_PASSWORD,
+    PDFValidationError,
+    prepare_pdf,
+)
+
+
+class TestPreparePdf:
+    """Test suite for prepare_pdf"""
+
+    def _write_pdf(self, path: Path, **save_options):
+        document = pymupdf.open()
+        document.new_page().insert_text((72, 72), "hello")
+        document.save(path, **save_options)
+        document.close()
+
+    def test_plain_pdf_passes(self, tmpdir):
+        """Test that an ordinary PDF is accepted untouched"""
+        path = tmpdir / "plain.pdf"
+        self._write_pdf(path)
+        result = prepare_pdf(path)
+        assert result.page_count == 1
+        assert not result.decrypted
+        assert not result.repaired
+
+    def test_encrypted_pdf_requires_password(self, tmpdir):
+        """Test that encrypted PDFs are rejected without a password and decrypted with one"""
+        path = tmpdir / "encrypted.pdf"
+        self._write_pdf(path, encryption=pymupdf.PDF_ENCRYPT_AES_256, user_pw="secret", owner_pw="owner")
+
+        with pytest.raises(PDFValidationError) as error:
+            prepare_pdf(path)
+        assert error.value.code == PDF_ENCRYPTED
+
+        with pytest.raises(PDFValidationError) as error:
+            prepare_pdf(path, password="wrong")
+        assert error.value.code == PDF_WRONG_PASSWORD
+
+        assert prepare_pdf(path, password="secret").decrypted
+        with pymupdf.open(path) as document:
+            assert not document.needs_pass
+
+    def test_garbage_is_corrupted(self, tmpdir):
+        """Test that a file that is not a PDF is reported as corrupted"""
+        path = tmpdir / "garbage.pdf"
+        path.write_bytes(b"this is not a pdf")
+        with pytest.raises(PDFValidationError) as error:
+            prepare_pdf(path)
+        assert error.value.code == PDF_CORRUPTED
+
@@ -0,0 +1,115 @@
+"""
+Test cases for the job pipelines submitting each node once its dependencies succeeded
+"""
+import threading
+import time
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.jobs import JOB_FAILED, JOB_SUCCEEDED, JobPool
+from textbook.pipeline import NODE_BLOCKED, PIPELINE_FAILED, PIPELINE_RUNNING, PIPELINE_SUCCEEDED, PipelineNode, PipelineRunner, order_nodes
+
+
+def settled(runner: PipelineRunner, pipeline_id: str, timeout: float = 5):
+    """The status of a pipeline once it stopped running"""
+    deadline = time.monotonic() + timeout
+    status = runner.status(pipeline_id)
+    while status.status == PIPELINE_RUNNING and time.monotonic() < deadline:
+        time.sleep(0.01)
+        status = runner.status(pipeline_id)
+    return status
+
+
+class TestPipeline:
+    """Test suite for running jobs in dependency order"""
+
+    @pytest.fixture
+    def down(self):
+        return {"segmentation": False}
+
+    @pytest.fixture
+    def pool(self, down):
+        def segment(params, handle):
+            if down["segmentation"]:
+                raise RuntimeError("The LLM is down")
+            return {"sections": params["inputs"]["ocr"]["pages"] // 10}
+
+        pool = JobPool()
+        pool.register("ocr", lambda params, handle: {"pages": 80})
+        pool.register("segmentation", segment)
+        pool.register("problem_extraction", lambda params, handle: {"problem_ids": list(range(params["inputs"]["segmentation"]["sections"]))})
+        pool.register("solution", lambda params, handle: {"solution_ids": params["inputs"]["extraction"]["problem_ids"]})
+        yield pool
+        pool.shutdown()
+
+    @pytest.fixture
+    def nodes(self):
+        return [
+            PipelineNode("solutions", "solution", depends_on=["extraction"]),
+            PipelineNode("extraction", "problem_extraction", depends_on=["segmentation"]),
+            PipelineNode("segmentation", "segmentation", depends_on=["ocr"]),
+            PipelineNode("ocr", "ocr"),
+        ]
+
+    def test_invalid_graphs_are_rejected(self, nodes):
+        """Test that nodes are ordered after their dependencies and invalid graphs are rejected"""
+        assert [node.name for node in order_nodes(nodes)] == ["ocr", "segmentation", "extraction", "solutions"]
+        invalid = [
+            [],
+            [PipelineNode("ocr", "ocr"), PipelineNode("ocr", "ocr")],
+            [PipelineNode("segmentation", "segmentation", depends_on=["ocr"])],
+            [PipelineNode("a", "ocr", depends_on=["b"]), PipelineNode("b", "ocr", depends_on=["a"])],
+        ]
+        for graph in invalid:
+            with pytest.raises(ValueError):
+                order_nodes(graph)
+
+    def test_results_are_passed_along(self, pool, nodes):
+        """Test that each node runs with the results of its dependencies and the jobs share the pipeline's group"""
+        runner = PipelineRunner(pool)
+        pipeline = runner.submit(nodes)
+        status = settled(runner, pipeline.pipeline_id)
+        assert status.status == PIPELINE_SUCCEEDED
+        assert pool.get_job_status(pipeline.job_ids["solutions"]).result == {"solution_ids": list(range(8))}
+        assert pool.get_group(pipeline.pipeline_id).total == 4
+
+    def test_failure_blocks_dependents_until_resumed(self, pool, nodes, down):
+        """Test that the nodes after a failed job are blocked, and resuming it carries on with the pipeline"""
+        down["segmentation"] = True
+        runner = PipelineRunner(pool)
+        pipeline = runner.submit(nodes)
+        status = settled(runner, pipeline.pipeline_id)
+        assert status.status == PIPELINE_FAILED
+        assert [node.status for node in status.nodes] == [JOB_SUCCEEDED, JOB_FAILED, NODE_BLOCKED, NODE_BLOCKED]
+        assert set(pipeline.job_ids) == {"ocr", "segmentation"}
+
+        down["segmentation"] = False
+        pool.resume(pipeline.job_ids["segmentation"])
+        assert settled(runner, pipeline.pipeline_id).status == PIPELINE_SUCCEEDED
+
+    def test_finished_pipelines_are_swept_with_their_jobs(self, pool, nodes):
+        """Test that a finished pipeline is dropped once the sweeper drops its jobs, a running one is kept"""
+        runner = PipelineRunner(pool)
+        finished = runner.submit(nodes)
+        assert settled(runner, finished.pipeline_id).status == PIPELINE_SUCCEEDED
+        release = threading.Event()
+        pool.register("render", lambda params, handle: release.wait(5))
+        running = runner.submit([PipelineNode("render", "render")])
+
+        later = datetime.now(timezone.utc) + timedelta(days=2)
+        swept = pool.sweep(now=later)
+        deadline = time.monotonic() + 5
+        while swept < 4 and time.monotonic() < deadline:
+            # The thread of the last job may still be announcing it
+            time.sleep(0.01)
+            swept += pool.sweep(now=later)
+        assert swept == 4
+        assert runner.status(finished.pipeline_id) is None
+        assert runner.status(running.pipeline_id).status == PIPELINE_RUNNING
+        release.set()
+
+    def test_unknown_kind_is_rejected(self, pool):
+        """Test that a pipeline with a node no handler runs is not started"""
+        with pytest.raises(ValueError):
+            PipelineRunner(pool).submit([PipelineNode("render", "render")])
@@ -0,0 +1,59 @@
+"""
+Test cases for per user study preferences
+"""
+
+import pytest
+
+from textbook.preferences import (
+    DEFAULT_DAILY_NEW_CARD_LIMIT,
+    DEFAULT_DIFFICULTY,
+    SCHEDULER_BLOCKED,
+    SCHEDULER_INTERLEAVED,
+    load_preferences,
+    update_preferences,
+)
+from textbook.reader import code_exercise_prompt, flashcard_prompt
+
+
+class TestPreferences:
+    """Test suite for storing and applying user preferences"""
+
+    def test_defaults_without_stored_preferences(self, database):
+        """Test that a user without a row gets the defaults"""
+        preferences = load_preferences(database, "alice")
+        assert preferences.user_id == "alice"
+        assert preferences.default_difficulty == DEFAULT_DIFFICULTY
+        assert preferences.daily_new_card_limit == DEFAULT_DAILY_NEW_CARD_LIMIT
+        assert preferences.scheduler == SCHEDULER_INTERLEAVED
+        assert preferences.output_language is None
+        assert database.get_user_preferences("alice") is None
+
+    def test_partial_updates_keep_other_fields(self, database):
+        """Test that an update only changes the given fields and is kept per user"""
+        update_preferences(database, "alice", default_difficulty=5, output_language="German")
+        update_preferences(database, "alice", scheduler=SCHEDULER_BLOCKED)
+        update_preferences(database, "bob", daily_new_card_limit=3, load_balancing=True)
+
+        alice = load_preferences(database, "alice")
+        assert (alice.default_difficulty, alice.output_language, alice.scheduler) == (5, "German", SCHEDULER_BLOCKED)
+        assert alice.daily_new_card_limit == DEFAULT_DAILY_NEW_CARD_LIMIT
+        bob = load_preferences(database, "bob")
+        assert (bob.daily_new_card_limit, bob.default_difficulty, bob.output_language) == (3, DEFAULT_DIFFICULTY, None)
+        assert bob.load_balancing and not alice.load_balancing
+
+    def test_invalid_preferences_are_rejected(self, database):
+        """Test that out of range values and unknown schedulers are not stored"""
+        with pytest.raises(ValueError):
+            update_preferences(database, "alice", default_difficulty=9)
+        with pytest.raises(ValueError):
+            update_preferences(database, "alice", scheduler="random")
+        with pytest.raises(ValueError):
+            update_preferences(database, "alice", notification_hour=24)
+        assert database.get_user_preferences("alice") is None
+
+    def test_generation_prompts_follow_preferences(self):
+        """Test that the difficulty and output language reach the generation prompts"""
+        prompt = code_exercise_prompt("page", "python", 2, difficulty=4, output_language="French")
+        assert "difficulty_level of 4" in prompt and "in French" in prompt
+        assert "in French" in flashcard_prompt("1. theorem", 1, "French")
+        assert "write the" not in flashcard_prompt("1. theorem", 1)
@@ -0,0 +1,89 @@
+"""
+Test cases for prefetching what readers will likely want next
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.content import chunk_content, store_chunks
+from textbook.database import ProblemInfo
+from textbook.jobs import JOB_KIND_DOCUMENT_SUMMARY, JOB_KIND_PROBLEM_EXTRACTION, PRIORITY_LOW
+from textbook.library import add_upload
+from textbook.prefetch import PrefetchSettings, Prefetcher, next_chapter, prefetch_settings_from_config
+
+MARKDOWN = "# Groups\nA group is a monoid with inverses.\n## Subgroups\nA subgroup is closed.\n# Rings\nA ring has two operations.\n# Fields\nA field divides."
+NOW = datetime(2024, 3, 1, 9, 0, tzinfo=timezone.utc)
+
+
+class FakeJobPool:
+    """Records the jobs submitted, which stay pending"""
+
+    def __init__(self, single_worker=()):
+        self.submitted = []
+        self.single_worker = set(single_worker)  # Kinds whose category has one worker
+
+    def submit(self, kind, params=None, priority="normal"):
+        self.submitted.append((kind, params, priority))
+        return (kind, params)
+
+    def low_priority_jobs(self):
+        return sum(priority == PRIORITY_LOW for _, _, priority in self.submitted)
+
+    def runs_low_priority(self, kind):
+        return kind not in self.single_worker
+
+
+class TestPrefetch:
+    """Test suite for prefetching the next chapter within the budget"""
+
+    @pytest.fixture
+    def document_id(self, database):
+        document = add_upload(database, "algebra", "a.pdf", 1)
+        store_chunks(database, document.document_id, chunk_content(MARKDOWN)[1])
+        return document.document_id
+
+    def test_next_chapter(self, database, document_id):
+        """Test that the chapter after the one a section belongs to comes next"""
+        assert [next_chapter(database, document_id, index).title for index in (0, 1)] == ["Rings", "Rings"]
+        assert next_chapter(database, document_id, 3) is None
+
+    def test_next_chapter_is_prefetched_once(self, database, document_id):
+        """Test that the problems of the next chapter and the summary are prefetched once, at low priority"""
+        pool = FakeJobPool()
+        prefetcher = Prefetcher(database, pool, PrefetchSettings(max_pending=5))
+        rings = next_chapter(database, document_id, 0).section_index
+
+        prefetcher.chapter_read(document_id, 0, NOW)
+        assert pool.submitted == [
+            (JOB_KIND_PROBLEM_EXTRACTION, {"document_id": document_id, "section_index": rings}, PRIORITY_LOW),
+            (JOB_KIND_DOCUMENT_SUMMARY, {"document_id": document_id}, PRIORITY_LOW),
+        ]
+        assert prefetcher.chapter_read(document_id, 1, NOW) == []
+
+        fields = next_chapter(database, document_id, rings).section_index
+        database.replace_problems(document_id, [ProblemInfo(statement="Show that Q is a field.", section_index=fields)], [fields])
+        assert prefetcher.chapter_read(document_id, rings, NOW) == []
+
+    def test_budget_is_kept(self, database, document_id):
+        """Test that nothing is prefetched beyond the pending and daily limits, or when prefetching is off"""
+        pool = FakeJobPool()
+        assert len(Prefetcher(database, pool, PrefetchSettings(max_pending=1)).chapter_read(document_id, 0, NOW)) == 1
+
+        pool = FakeJobPool()
+        prefetcher = Prefetcher(database, pool, PrefetchSettings(max_pending=5, daily_limit=1))
+        assert len(prefetcher.chapter_read(document_id, 0, NOW)) == 1
+        assert prefetcher.chapter_read(document_id, 0, NOW) == []
+        assert len(prefetcher.chapter_read(document_id, 0, NOW + timedelta(days=1))) == 1
+
+        assert Prefetcher(database, FakeJobPool(), PrefetchSettings(enabled=False)).chapter_read(document_id, 0, NOW) == []
+
+        pool = FakeJobPool(single_worker=[JOB_KIND_PROBLEM_EXTRACTION])
+        Prefetcher(database, pool, PrefetchSettings(max_pending=5)).chapter_read(document_id, 0, NOW)
+        assert [kind for kind, _, _ in pool.submitted] == [JOB_KIND_DOCUMENT_SUMMARY]
+
+    def test_settings_from_config(self):
+        """Test that the budget is read from config.toml and negative limits are rejected"""
+        assert prefetch_settings_from_config({}) == PrefetchSettings()
+        assert prefetch_settings_from_config({"prefetch": False, "prefetch_daily_limit": 5}) == PrefetchSettings(enabled=False, daily_limit=5)
+        with pytest.raises(ValueError):
+            prefetch_settings_from_config({"prefetch_max_pending": -1})
@@ -0,0 +1,110 @@
+"""
+Test cases for scoring extracted problems and the review queue of flagged ones
+"""
+
+import pytest
+
+from tests.conftest import FakeLLM
+from textbook.content import chunk_content, store_chunks
+from textbook.database import ProblemInfo
+from textbook.library import add_upload
+from textbook.problem_quality import (
+    QUALITY_APPROVED,
+    QUALITY_FLAGGED,
+    QUALITY_PASSED,
+    ProblemQualitySettings,
+    QualityJudgmentSchema,
+    RestatedProblemSchema,
+    duplicates,
+    grounding,
+    problem_issues,
+    problem_quality_settings_from_config,
+    review_problem,
+    score_problems,
+)
+
+SECTION = "# Groups\nA group is a monoid with inverses.\n\nProblem 1. Show that the identity of a group is unique."
+
+
+def judge(unanswerable="", restated=None):
+    """Judges the problems containing a word unanswerable, and restates problems with a canned statement"""
+
+    def answer(prompt, schema):
+        if schema is RestatedProblemSchema:
+            return RestatedProblemSchema(found=restated is not None, statement=restated or "")
+        assert schema is QualityJudgmentSchema
+        statement = prompt.split("Problem:")[1].split("Related passages")[0]
+        if unanswerable and unanswerable in statement:
+            return QualityJudgmentSchema(answerable=False, ambiguous=False, issues=["The data is missing."])
+        return QualityJudgmentSchema(answerable=True, ambiguous=False, issues=[])
+
+    return answer
+
+
+class TestProblemQuality:
+    """Test suite for the quality checks of extracted problems"""
+
+    @pytest.fixture
+    def document_id(self, database):
+        document = add_upload(database, "algebra", "a.pdf", 1)
+        store_chunks(database, document.document_id, chunk_content(SECTION)[1])
+        return document.document_id
+
+    def test_settings_from_config(self):
+        """Test that the checks are on by default and invalid thresholds rejected"""
+        assert problem_quality_settings_from_config({}) == ProblemQualitySettings()
+        settings = problem_quality_settings_from_config({"problem_quality_threshold": 0.4, "problem_quality_regenerate": False})
+        assert (settings.threshold, settings.regenerate) == (0.4, False)
+        for threshold in (1.5, "high", True):
+            with pytest.raises(ValueError):
+                problem_quality_settings_from_config({"problem_quality_threshold": threshold})
+
+    def test_grounding_and_duplicates(self):
+        """Test that statements are grounded by the share of their words on their pages and repeats are found"""
+        assert grounding("Show that the identity is unique.", SECTION) == 1.0
+        assert grounding("Compute the determinant of the matrix.", SECTION) < 0.5
+        problems = [
+            ProblemInfo(problem_id=1, statement="Show that the identity of a group is unique."),
+            ProblemInfo(problem_id=2, statement="Show that inverses are unique."),
+            ProblemInfo(problem_id=3, statement="Show that the identity of a group is unique"),
+        ]
+        assert duplicates(problems) == {3: 1}
+
+    def test_low_scores_are_flagged(self, database, document_id):
+        """Test that made up, unanswerable and repeated problems are flagged and left out of the new problems"""
+        passed, made_up, unanswerable, repeated = database.replace_problems(document_id, [
+            ProblemInfo(problem_number="1", statement="Show that the identity of a group is unique.", section_index=0),
+            ProblemInfo(problem_number="2", statement="Compute the determinant of the rotation matrix.", section_index=0),
+            ProblemInfo(problem_number="3", statement="Show that the group is a monoid with inverses of order missing.", section_index=0),
+            ProblemInfo(problem_number="1", statement="Show that the identity of a group is unique.", section_index=0),
+        ])
+        reports = score_problems(database, FakeLLM(judge(unanswerable="missing")), document_id, settings=ProblemQualitySettings(regenerate=False))
+        assert [report.problem_id for report in reports] == [passed, made_up, unanswerable, repeated]
+        assert reports[3].duplicate_of == passed and reports[3].score == 0
+
+        assert database.get_problem(passed).quality_status == QUALITY_PASSED
+        flagged = database.get_problems_by_quality(QUALITY_FLAGGED, document_id)
+        assert {problem.problem_id for problem in flagged} == {made_up, unanswerable, repeated}
+        assert flagged[-1].problem_id == unanswerable  # Lowest score first, the unanswerable one is partly grounded
+        assert problem_issues(database.get_problem(unanswerable))[-1] == "The data is missing."
+        assert problem_issues(database.get_problem(repeated)) == [f"Duplicate of problem {passed}"]
+        assert [problem.problem_id for problem in database.get_new_problems("alice", 10, document_id)] == [passed]
+
+        review_problem(database, made_up, QUALITY_APPROVED)
+        assert [problem.problem_id for problem in database.get_new_problems("alice", 10, document_id)] == [passed, made_up]
+        with pytest.raises(ValueError):
+            review_problem(database, repeated, "maybe")
+        with pytest.raises(ValueError):
+            review_problem(database, 999, QUALITY_APPROVED)
+
+    def test_low_scores_are_extracted_again(self, database, document_id):
+        """Test that a garbled problem is extracted again from its section and passes when the new statement does"""
+        [problem_id] = database.replace_problems(document_id, [
+            ProblemInfo(problem_number="1", statement="Shw tht th idntity f a grp s unque.", section_index=0),
+        ])
+        llm = FakeLLM(judge(restated="Show that the identity of a group is unique."))
+        [report] = score_problems(database, llm, document_id, [problem_id])
+        assert report.regenerated and report.score == 1.0
+        assert "monoid with inverses" in llm.prompts[1]
+        problem = database.get_problem(problem_id)
+        assert (problem.statement, problem.quality_status) == ("Show that the identity of a group is unique.", QUALITY_PASSED)
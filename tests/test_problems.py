@@ -0,0 +1,89 @@
+"""
+Test cases for extracting problems from OCR output
+"""
+
+import pytest
+
+from tests.conftest import FakeLLM
+from textbook.content import chunk_content, store_chunks
+from textbook.library import add_upload
+from textbook.generation import GenerationRecord
+from textbook.problems import PROBLEM_EXTRACTION_PROMPT_VERSION, WHOLE_DOCUMENT, ProblemSchema, ProblemSetSchema, document_chapters, extract_problems, problem_figures
+
+CONTENT_LIST = [
+    {"type": "text", "text": "Groups", "text_level": 1, "page_idx": 0},
+    {"type": "text", "text": "1. Show that the identity is unique.", "page_idx": 0},
+    {"type": "text", "text": "Subgroups", "text_level": 2, "page_idx": 1},
+    {"type": "text", "text": "2.* Show that the kernel is a subgroup, see Figure 1.", "page_idx": 1},
+    {"type": "text", "text": "Rings", "text_level": 1, "page_idx": 1},
+    {"type": "text", "text": "1. Show that 0a = 0.", "page_idx": 2},
+]
+
+
+def problem_per_page(prompt, schema):
+    """Answers each prompt with one problem per page marker"""
+    pages = [int(line.strip()[len("[Page "):-1]) for line in prompt.splitlines() if line.strip().startswith("[Page ")]
+    return ProblemSetSchema(problems=[
+        ProblemSchema(number=f" {page} ", statement=f"Problem on page {page}", difficulty_hint="*" if page == 1 else "", figures=["Figure 1", " "] if page == 1 else [], page_number=page)
+        for page in pages or [0]
+    ] + [ProblemSchema(number="", statement=" ", difficulty_hint="", figures=[], page_number=0)])
+
+
+class TestProblemExtraction:
+    """Test suite for extracting problems by chapter and storing them"""
+
+    def test_extract_by_chapter(self, database):
+        """Test that each top-level section is prompted with its part of its pages and every problem is stored"""
+        document = add_upload(database, "algebra", "a.pdf", 3)
+        store_chunks(database, document.document_id, chunk_content("", CONTENT_LIST)[1])
+        assert [(chapter.section_index, chapter.title) for chapter in document_chapters(database, document.document_id)] == [(0, "Groups"), (2, "Rings")]
+
+        llm = FakeLLM(problem_per_page, ProblemSetSchema)
+        problem_ids = extract_problems(database, llm, document.document_id)
+        assert len(llm.prompts) == 2
+        assert "kernel" in llm.prompts[0] and "# Rings" not in llm.prompts[0]
+        assert "kernel" not in llm.prompts[1] and "[Page 1]\n# Rings" in llm.prompts[1]
+
+        problems = database.get_problems(document.document_id)
+        assert [problem.problem_id for problem in problems] == problem_ids
+        assert [(problem.problem_number, problem.page_number, problem.section_index) for problem in problems] == [
+            ("0", 0, 0), ("1", 1, 0), ("1", 1, 2), ("2", 2, 2),
+        ]
+        assert (problems[1].difficulty_hint, problem_figures(problems[1])) == ("*", ["Figure 1"])
+        assert (problems[0].difficulty_hint, problem_figures(problems[0])) == (None, [])
+
+    def test_generation_is_recorded(self, database):
+        """Test that each problem records the model, temperature, seed, prompt version and digest of its chapter"""
+        document = add_upload(database, "algebra", "a.pdf", 3)
+        store_chunks(database, document.document_id, chunk_content("", CONTENT_LIST)[1])
+        extract_problems(database, FakeLLM(problem_per_page, ProblemSetSchema), document.document_id, generation=GenerationRecord("flash", 0.0, 42))
+
+        problems = database.get_problems(document.document_id)
+        assert {(problem.model_name, problem.temperature, problem.seed, problem.prompt_version) for problem in problems} == {
+            ("flash", 0.0, 42, PROBLEM_EXTRACTION_PROMPT_VERSION),
+        }
+        assert problems[0].source_sha256 == problems[1].source_sha256 != problems[2].source_sha256
+
+        extract_problems(database, FakeLLM(problem_per_page, ProblemSetSchema), document.document_id, section_index=2)
+        assert [problem.source_sha256 for problem in database.get_problems(document.document_id, section_index=2)] == [problems[2].source_sha256] * 2
+        assert database.get_problems(document.document_id, section_index=2)[0].seed is None
+
+    def test_extract_again_replaces(self, database):
+        """Test that extracting a section replaces only its problems and a document without headings is one chapter"""
+        document = add_upload(database, "algebra", "a.pdf", 3)
+        store_chunks(database, document.document_id, chunk_content("", CONTENT_LIST)[1])
+        extract_problems(database, FakeLLM(problem_per_page, ProblemSetSchema), document.document_id)
+
+        extract_problems(database, FakeLLM(problem_per_page, ProblemSetSchema), document.document_id, section_index=2)
+        assert len(database.get_problems(document.document_id)) == 4
+        assert len(database.get_problems(document.document_id, section_index=2)) == 2
+        with pytest.raises(ValueError):
+            extract_problems(database, FakeLLM(problem_per_page, ProblemSetSchema), document.document_id, section_index=7)
+
+        notes = add_upload(database, "notes", "b.pdf", 1)
+        with pytest.raises(ValueError):
+            extract_problems(database, FakeLLM(problem_per_page, ProblemSetSchema), notes.document_id)
+        store_chunks(database, notes.document_id, chunk_content("", [{"type": "text", "text": "Exercise 1. Prove it.", "page_idx": 0}])[1])
+        assert document_chapters(database, notes.document_id)[0].title == WHOLE_DOCUMENT
+        extract_problems(database, FakeLLM(problem_per_page, ProblemSetSchema), notes.document_id)
+        assert [(problem.page_number, problem.section_index) for problem in database.get_problems(notes.document_id)] == [(0, None)]
@@ -0,0 +1,75 @@
+"""
+Test cases for LLM profiles and routing tasks to them
+"""
+
+import pytest
+
+from tests.conftest import FakeLLM
+from textbook.jobs import JOB_KIND_DOCUMENT_SUMMARY, JOB_KIND_GLOSSARY, JOB_KIND_SOLUTION
+from textbook.model import SCHEMA_MODE_NATIVE
+from textbook.profiles import DEFAULT_PROFILE, TASK_EXPLAIN, ModelRouter, profiles_from_config, routing_from_config
+
+CONFIG = {
+    "profiles": {
+        "fast": {"model": "flash-lite"},
+        "cheap": {"model": "flash-lite"},
+        "reasoning": {"model": "pro", "schema_mode": SCHEMA_MODE_NATIVE},
+        "same": {"model": "flash"},
+    },
+    "routing": {JOB_KIND_DOCUMENT_SUMMARY: "fast", JOB_KIND_SOLUTION: "reasoning"},
+}
+
+
+class TestProfiles:
+    """Test suite for the profiles of config.toml and the model router"""
+
+    def _router(self):
+        profiles = profiles_from_config(CONFIG)
+        created = []
+
+        def create(profile):
+            created.append(profile.name)
+            return FakeLLM(model_name=profile.model_name)
+
+        return ModelRouter(FakeLLM(model_name="flash"), profiles, routing_from_config(CONFIG, profiles), create), created
+
+    def test_profiles_from_config(self):
+        """Test that profiles are read with their schema mode and invalid ones are rejected"""
+        profiles = profiles_from_config(CONFIG)
+        assert profiles["reasoning"].model_name == "pro" and profiles["reasoning"].schema_mode == SCHEMA_MODE_NATIVE
+        assert profiles_from_config({}) == {}
+
+        for invalid in (
+            {"default": {"model": "pro"}},
+            {"fast": {"model": ""}},
+            {"fast": {"model": "pro", "temperature": 0.2}},
+            {"fast": {"model": "pro", "schema_mode": "json"}},
+            {"fast": "pro"},
+        ):
+            with pytest.raises(ValueError):
+                profiles_from_config({"profiles": invalid})
+
+    def test_routing_from_config(self):
+        """Test that routes must name known tasks and profiles"""
+        profiles = profiles_from_config(CONFIG)
+        assert routing_from_config({"routing": {JOB_KIND_GLOSSARY: DEFAULT_PROFILE}}, profiles) == {JOB_KIND_GLOSSARY: DEFAULT_PROFILE}
+        with pytest.raises(ValueError):
+            routing_from_config({"routing": {"translation": "fast"}}, profiles)
+        with pytest.raises(ValueError):
+            routing_from_config({"routing": {JOB_KIND_GLOSSARY: "vision"}}, profiles)
+
+    def test_router(self):
+        """Test that tasks go to their profile, a request can pick another and profiles share equal models"""
+        router, created = self._router()
+        # fast and cheap share a model, same is the default model
+        assert created == ["fast", "reasoning"]
+        assert router.models["cheap"] is router.models["fast"]
+        assert router.models["same"] is router.default
+
+        assert router.model_for(JOB_KIND_DOCUMENT_SUMMARY).model_name == "flash-lite"
+        assert router.model_for(JOB_KIND_SOLUTION).model_name == "pro"
+        assert router.model_for(TASK_EXPLAIN) is router.default
+        assert router.model_for(JOB_KIND_SOLUTION, "fast").model_name == "flash-lite"
+        assert router.model_for(JOB_KIND_SOLUTION, DEFAULT_PROFILE) is router.default
+        with pytest.raises(ValueError):
+            router.model_for(JOB_KIND_SOLUTION, "vision")
@@ -0,0 +1,67 @@
+"""
+Test cases for logging prompts and responses
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.jobs import JobPool
+from textbook.prompt_log import LOG_MODE_DISABLED, LOG_MODE_FULL, LOG_MODE_TRUNCATED, REDACTED, PromptLog, PromptLogSettings, redact, settings_from_config, truncate
+
+
+class TestPrivacy:
+    """Test suite for redacting and truncating what is logged"""
+
+    def test_redact(self):
+        """Test that e-mail addresses, tokens and configured patterns are replaced"""
+        text = "Mail ada@example.com with Bearer abc.def and pbs_secret, student 12345678"
+        assert redact(text, [r"[\w.+-]+@[\w-]+\.[\w.-]+", r"(?i)bearer\s+[\w.~+/-]+=*", r"\bpbs_[\w-]+", r"\b\d{8}\b"]) == f"Mail {REDACTED} with {REDACTED} and {REDACTED}, student {REDACTED}"
+
+    def test_truncate(self):
+        """Test that only texts longer than the limit are cut"""
+        assert truncate("short", 10) == ("short", False)
+        cut, truncated = truncate("x" * 20, 10)
+        assert cut.startswith("x" * 10) and truncated
+
+    def test_settings_from_config(self):
+        """Test that the log is disabled by default and invalid settings are rejected"""
+        assert settings_from_config({}).mode == LOG_MODE_DISABLED
+        assert settings_from_config({"prompt_log": "truncated", "prompt_log_max_chars": 100}).max_chars == 100
+        with pytest.raises(ValueError):
+            settings_from_config({"prompt_log": "everything"})
+        with pytest.raises(ValueError):
+            settings_from_config({"prompt_log": "full", "prompt_log_redactions": ["("]})
+
+
+class TestPromptLog:
+    """Test suite for recording, reading and expiring log entries"""
+
+    def test_disabled_log_keeps_nothing(self, database):
+        """Test that nothing is stored when the log is disabled"""
+        log = PromptLog(database, PromptLogSettings())
+        assert log.record("flash", "Extract the problems", "{}") is None
+        assert log.entries() == []
+
+    def test_entries_per_job(self, database):
+        """Test that prompts sent from a job are logged with its ID, redacted and truncated"""
+        log = PromptLog(database, PromptLogSettings(mode=LOG_MODE_TRUNCATED, max_chars=40, redactions=[r"Alice"]))
+        pool = JobPool()
+        pool.register("extract", lambda params, handle: {"log_id": log.record("flash", "Alice (alice@example.com) asks: " + "x" * 100, "{}", "Problem", parsed="{}", attempt=1).log_id})
+        job = pool.submit("extract")
+        pool.wait(job.job_id, timeout=5)
+        pool.shutdown()
+        log.record("flash", "Outside of jobs", None, error="timeout")
+
+        [entry] = log.entries(job_id=job.job_id)
+        assert entry.prompt.startswith(f"{REDACTED} ({REDACTED}) asks: ")
+        assert (entry.truncated, entry.prompt_chars, entry.response, entry.schema_name) == (True, 132, "{}", "Problem")
+        assert (entry.parsed, entry.attempt) == ("{}", 1)
+        assert [entry.job_id for entry in log.entries()] == [None, job.job_id]
+
+    def test_retention(self, database):
+        """Test that entries older than the retention window are deleted"""
+        log = PromptLog(database, PromptLogSettings(mode=LOG_MODE_FULL, retention_days=7))
+        log.record("flash", "Extract the problems", "{}")
+        assert log.purge(datetime.now(timezone.utc) + timedelta(days=6)) == 0
+        assert log.purge(datetime.now(timezone.utc) + timedelta(days=8)) == 1
+        assert log.entries() == []
@@ -0,0 +1,55 @@
+"""
+Test cases for the prompt template registry and explanation styles
+"""
+
+import pytest
+
+from textbook.digest import chunk_summary_prompt, reduce_summary_prompt
+from textbook.explain import explanation_prompt, select_range
+from textbook.prompt_templates import (
+    KIND_EXPLANATION_STYLE,
+    STYLE_ANALOGY,
+    STYLE_ELI5,
+    STYLE_RIGOROUS,
+    STYLE_VISUAL_DESCRIPTION,
+    PromptTemplate,
+    PromptTemplateRegistry,
+    check_explanation_style,
+    registry,
+    style_rules,
+)
+
+
+class TestPromptTemplates:
+    """Test suite for looking up prompt templates and adding their rules to prompts"""
+
+    def test_registry(self):
+        """Test that templates are looked up by kind and name and unknown ones are rejected"""
+        templates = PromptTemplateRegistry()
+        templates.register("tone", PromptTemplate("dry", "Dry", ["be dry"]))
+        templates.register("tone", PromptTemplate("dry", "Drier", ["be drier"]))
+        assert templates.names("tone") == ["dry"]
+        assert templates.get("tone", "dry").rules == ["be drier"]
+        assert templates.names("other") == []
+        with pytest.raises(ValueError):
+            templates.get("tone", "witty")
+
+    def test_explanation_styles(self):
+        """Test that the four styles are registered and unknown styles are rejected"""
+        assert registry.names(KIND_EXPLANATION_STYLE) == [STYLE_ANALOGY, STYLE_ELI5, STYLE_RIGOROUS, STYLE_VISUAL_DESCRIPTION]
+        check_explanation_style(None)
+        check_explanation_style(STYLE_ELI5)
+        with pytest.raises(ValueError):
+            check_explanation_style("sonnet")
+        assert style_rules(None) == ""
+        assert style_rules(STYLE_RIGOROUS).startswith("\n    - be rigorous")
+
+    def test_styles_in_prompts(self):
+        """Test that the rules of a style end up in the explain and summary prompts, and only with a style"""
+        selection = select_range("Every bounded sequence has a convergent subsequence.", 0, 52)
+        plain = explanation_prompt("Analysis", selection, [], [])
+        analogy = explanation_prompt("Analysis", selection, [], [], style=STYLE_ANALOGY)
+        assert "analogy" not in plain and "where the analogy breaks down" in analogy
+        assert "plain words" in chunk_summary_prompt("Analysis", "pages", STYLE_ELI5)
+        assert "diagram" in reduce_summary_prompt("Analysis", "summaries", STYLE_VISUAL_DESCRIPTION)
+        assert "plain words" not in chunk_summary_prompt("Analysis", "pages")
@@ -0,0 +1,60 @@
+"""
+Test cases for mapping LLM provider errors into categories
+"""
+import pytest
+
+from textbook.provider_errors import (
+    ERROR_AUTH,
+    ERROR_CONTENT_FILTER,
+    ERROR_QUOTA,
+    ERROR_SERVER,
+    ERROR_TIMEOUT,
+    USER_MESSAGES,
+    ModelError,
+    classify_error,
+    provider_error,
+)
+
+
+class FakeResponse:
+    def __init__(self, status_code):
+        self.status_code = status_code
+
+
+class HTTPError(Exception):
+    """An error with the HTTP response on it, like those of httpx"""
+
+    def __init__(self, message, status_code):
+        super().__init__(message)
+        self.response = FakeResponse(status_code)
+
+
+class TestClassification:
+    """Test suite for telling provider errors apart"""
+
+    def test_status_codes(self):
+        """Test that the HTTP status of an error decides its category when it has a telling one"""
+        assert classify_error(HTTPError("Unauthorized", 401)) == ERROR_AUTH
+        assert classify_error(HTTPError("Too Many Requests", 429)) == ERROR_QUOTA
+        assert classify_error(HTTPError("Gateway Timeout", 504)) == ERROR_TIMEOUT
+        assert classify_error(Exception("429 Resource has been exhausted (e.g. check quota).")) == ERROR_QUOTA
+        assert classify_error(HTTPError("Internal error encountered.", 500)) == ERROR_SERVER
+
+    def test_messages(self):
+        """Test that errors without a telling status are classified by their type and message"""
+        assert classify_error(Exception("400 API key not valid. Please pass a valid API key.")) == ERROR_AUTH
+        assert classify_error(Exception("Response was blocked due to SAFETY")) == ERROR_CONTENT_FILTER
+        assert classify_error(Exception("You exceeded your current quota")) == ERROR_QUOTA
+        assert classify_error(TimeoutError()) == ERROR_TIMEOUT
+        assert classify_error(type("ReadTimeout", (Exception,), {})("")) == ERROR_TIMEOUT
+        assert classify_error(ConnectionError("Connection reset by peer")) == ERROR_SERVER
+
+    def test_model_error(self):
+        """Test that a ModelError shows the user message and keeps the provider's"""
+        error = provider_error(Exception("400 API key not valid."), "gemini-3-flash-preview")
+        assert (str(error), error.user_message, error.category) == (USER_MESSAGES[ERROR_AUTH], USER_MESSAGES[ERROR_AUTH], ERROR_AUTH)
+        assert (error.detail, error.model_name) == ("400 API key not valid.", "gemini-3-flash-preview")
+        assert provider_error(error) is error
+        assert provider_error(TimeoutError()).detail == "TimeoutError"
+        with pytest.raises(ValueError):
+            ModelError("billing", "")
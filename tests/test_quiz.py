@@ -0,0 +1,189 @@
+"""
+Test cases for the mastery model and interleaved quiz assembly
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.database import GradingJudgmentInfo
+from textbook.grading import JUDGMENT_INITIAL
+from textbook.preferences import SCHEDULER_BLOCKED, Preferences
+from textbook.quiz import (
+    SLOT_CURRENT,
+    SLOT_REVIEW,
+    SLOT_WEAK,
+    Attempt,
+    ExerciseCandidate,
+    QuizItem,
+    QuizMix,
+    assemble_quiz,
+    assemble_quiz_from_candidates,
+    book_mastery,
+    estimate_mastery,
+    interleave,
+    new_items_today,
+    review_interval_days,
+    topic_mastery,
+)
+
+NOW = datetime(2024, 3, 1, 12, tzinfo=timezone.utc)
+
+
+def _days_ago(days: float) -> datetime:
+    return NOW - timedelta(days=days)
+
+
+class TestMasteryModel:
+    """Test suite for estimating mastery and review intervals from attempts"""
+
+    def test_mastery_without_attempts_is_zero(self):
+        """Test that an unattempted chapter has no mastery"""
+        assert estimate_mastery([], NOW) == 0.0
+
+    def test_prior_keeps_one_answer_below_full_mastery(self):
+        """Test that a single perfect answer is not full mastery, but several are closer to it"""
+        one = estimate_mastery([Attempt(NOW, 1.0)], NOW)
+        four = estimate_mastery([Attempt(NOW, 1.0)] * 4, NOW)
+        assert one == pytest.approx(0.5)
+        assert one < four < 1.0
+
+    def test_recent_attempts_weigh_more(self):
+        """Test that a recent failure lowers mastery more than an old one"""
+        recent_failure = [Attempt(_days_ago(30), 1.0), Attempt(_days_ago(0), 0.0)]
+        old_failure = [Attempt(_days_ago(30), 0.0), Attempt(_days_ago(0), 1.0)]
+        assert estimate_mastery(recent_failure, NOW) < estimate_mastery(old_failure, NOW)
+
+    def test_review_interval_doubles_with_correct_streak(self):
+        """Test that the interval grows with consecutive correct attempts and resets on a failure"""
+        assert review_interval_days([Attempt(_days_ago(1), 0.2)]) is None
+        assert review_interval_days([Attempt(_days_ago(3), 0.9)]) == 1.0
+        assert review_interval_days([Attempt(_days_ago(3), 0.9), Attempt(_days_ago(2), 0.8), Attempt(_days_ago(1), 1.0)]) == 4.0
+        assert review_interval_days([Attempt(_days_ago(3), 0.9), Attempt(_days_ago(2), 0.1), Attempt(_days_ago(1), 1.0)]) == 1.0
+
+    def test_topic_overdue(self):
+        """Test that a chapter is due once its interval has passed, and right away after a failure"""
+        assert topic_mastery(1, [Attempt(_days_ago(3), 0.9)], NOW).overdue == pytest.approx(3.0)
+        assert topic_mastery(1, [Attempt(_days_ago(0.5), 0.9)], NOW).overdue < 1
+        assert topic_mastery(1, [Attempt(_days_ago(0), 0.1)], NOW).overdue >= 1
+        assert topic_mastery(1, [], NOW).overdue == 0.0
+
+    def test_naive_datetimes_are_utc(self):
+        """Test that naive datetimes from SQLite are read as UTC"""
+        naive = _days_ago(2).replace(tzinfo=None)
+        assert topic_mastery(1, [Attempt(naive, 0.9)], NOW).overdue == pytest.approx(2.0)
+
+
+class TestQuizAssembly:
+    """Test suite for mixing and interleaving quiz problems"""
+
+    def test_mix_counts_sum_to_size(self):
+        """Test that the proportions are rounded by largest remainder and normalized"""
+        assert QuizMix(0.5, 0.3, 0.2).counts(10) == {SLOT_CURRENT: 5, SLOT_WEAK: 3, SLOT_REVIEW: 2}
+        assert sum(QuizMix(1, 1, 1).counts(7).values()) == 7
+        assert QuizMix(2, 0, 0).counts(4) == {SLOT_CURRENT: 4, SLOT_WEAK: 0, SLOT_REVIEW: 0}
+        with pytest.raises(ValueError):
+            QuizMix(0, 0, 0).counts(5)
+
+    def test_interleave_avoids_consecutive_chapters(self):
+        """Test that consecutive problems come from different chapters when possible"""
+        items = [QuizItem(index, 1, SLOT_CURRENT, 0.0) for index in range(4)] + [QuizItem(10 + index, 2, SLOT_WEAK, 0.0) for index in range(3)]
+        ordered = interleave(items)
+        assert sorted(item.exercise_id for item in ordered) == sorted(item.exercise_id for item in items)
+        chapters = [item.chapter_id for item in ordered]
+        assert all(first != second for first, second in zip(chapters, chapters[1:]))
+
+    def _candidates(self):
+        # Chapter 1 mastered long ago, chapter 2 failed recently, chapter 3 is the current one
+        candidates = [ExerciseCandidate(100 + index, 1, [Attempt(_days_ago(10), 1.0)] * 3) for index in range(5)]
+        candidates += [ExerciseCandidate(200 + index, 2, [Attempt(_days_ago(1), 0.1)]) for index in range(5)]
+        candidates += [ExerciseCandidate(300 + index, 3) for index in range(10)]
+        return candidates
+
+    def test_assembly_mixes_slots(self):
+        """Test that weak chapters fill the weak slot and overdue chapters the review slot"""
+        items, masteries = assemble_quiz_from_candidates(self._candidates(), 3, [1, 2], 10, QuizMix(0.5, 0.3, 0.2), now=NOW, seed=1)
+        assert len(items) == 10
+        assert len({item.exercise_id for item in items}) == 10
+        slots = {slot: [item.chapter_id for item in items if item.slot == slot] for slot in (SLOT_CURRENT, SLOT_WEAK, SLOT_REVIEW)}
+        assert slots[SLOT_CURRENT] == [3] * 5
+        assert slots[SLOT_WEAK][0] == 2
+        assert len(slots[SLOT_REVIEW]) == 2
+        assert masteries[2].mastery < masteries[1].mastery
+        chapters = [item.chapter_id for item in items]
+        assert all(first != second for first, second in zip(chapters, chapters[1:]))
+
+    def test_assembly_fills_short_slots(self):
+        """Test that a quiz on the first chapter, without earlier chapters, is drawn from it alone"""
+        items, _ = assemble_quiz_from_candidates(self._candidates(), 3, [], 8, now=NOW, seed=1)
+        assert [item.chapter_id for item in items] == [3] * 8
+
+    def test_new_limit_caps_unattempted_exercises(self):
+        """Test that at most new_limit unattempted exercises are picked, the rest coming from attempted ones"""
+        items, _ = assemble_quiz_from_candidates(self._candidates(), 3, [1, 2], 10, now=NOW, seed=1, new_limit=2)
+        assert [item.chapter_id for item in items].count(3) == 2
+        assert len(items) == 10
+
+    def test_target_difficulty_orders_new_exercises(self):
+        """Test that unattempted exercises closest to the target difficulty are picked first"""
+        candidates = [ExerciseCandidate(difficulty, 1, difficulty_level=difficulty) for difficulty in (1, 2, 4, 5)]
+        items, _ = assemble_quiz_from_candidates(candidates, 1, [], 2, now=NOW, seed=3, target_difficulty=4)
+        assert sorted(item.exercise_id for item in items) == [4, 5]
+
+    def test_new_items_today(self):
+        """Test that only exercises first attempted today count as new today"""
+        candidates = [
+            ExerciseCandidate(1, 1, [Attempt(_days_ago(0.1), 1.0)]),
+            ExerciseCandidate(2, 1, [Attempt(_days_ago(3), 1.0), Attempt(_days_ago(0.1), 1.0)]),
+            ExerciseCandidate(3, 1),
+        ]
+        assert new_items_today(candidates, NOW) == 1
+
+    def test_assembly_is_reproducible_with_seed(self):
+        """Test that the same seed gives the same quiz"""
+        first, _ = assemble_quiz_from_candidates(self._candidates(), 3, [1, 2], 6, now=NOW, seed=7)
+        second, _ = assemble_quiz_from_candidates(self._candidates(), 3, [1, 2], 6, now=NOW, seed=7)
+        assert [item.exercise_id for item in first] == [item.exercise_id for item in second]
+
+
+class TestQuizFromDatabase:
+    """Test suite for assembling quizzes from stored exercises and attempts"""
+
+    def test_assemble_quiz_by_chapter(self, database):
+        """Test that exercises are assigned to chapters through the alignment offset and attempts count towards mastery"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        database.update_book_alignment_offset(book.book_id, 2)
+        first = database.try_create_chapter_info(book.book_id, "Sequences", "1", 1, 5)
+        second = database.try_create_chapter_info(book.book_id, "Series", "2", 6, 10)
+        # PDF pages 4 and 10 are printed pages 2 and 8
+        earlier = database.create_exercise(book.book_id, 4, "Show that 1/n converges")
+        current = [database.create_exercise(book.book_id, 10, f"Series exercise {index}") for index in range(3)]
+        database.create_attempt(earlier, "It does", [GradingJudgmentInfo(model_name="test", reason=JUDGMENT_INITIAL, score=0.2, is_correct=False, confidence=0.9, feedback="")])
+
+        items, _ = assemble_quiz(database, book.book_id, second, 4, seed=0)
+        assert sorted(item.exercise_id for item in items) == sorted(current + [earlier])
+        assert next(item for item in items if item.exercise_id == earlier).chapter_id == first
+
+        blocked, _ = assemble_quiz(database, book.book_id, second, 4, seed=0, preferences=Preferences("default", scheduler=SCHEDULER_BLOCKED))
+        assert sorted(item.exercise_id for item in blocked) == sorted(current)
+        # The earlier exercise was first attempted today, which uses up a limit of one
+        limited, _ = assemble_quiz(database, book.book_id, second, 4, seed=0, preferences=Preferences("default", daily_new_card_limit=1))
+        assert [item.exercise_id for item in limited] == [earlier]
+
+        masteries = book_mastery(database, book.book_id)
+        assert masteries[first].attempts == 1 and masteries[second].attempts == 0
+        with pytest.raises(ValueError):
+            assemble_quiz(database, book.book_id, 999, 4)
+
+    def test_voided_attempts_do_not_count(self, database):
+        """Test that a voided attempt is left out of mastery but kept on its exercise"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        chapter = database.try_create_chapter_info(book.book_id, "Sequences", "1", 0, 5)
+        exercise = database.create_exercise(book.book_id, 2, "Show that 1/n converges")
+        attempt = database.create_attempt(exercise, "It does", [GradingJudgmentInfo(model_name="test", reason=JUDGMENT_INITIAL, score=1.0, is_correct=True, confidence=0.9, feedback="")])
+
+        assert book_mastery(database, book.book_id)[chapter].attempts == 1
+        voided = database.void_attempt(attempt)
+        assert voided is not None and voided.voided_at is not None
+        assert book_mastery(database, book.book_id)[chapter].attempts == 0
+        assert len(database.get_attempts_by_exercise_id(exercise)) == 1
+        assert database.void_attempt(9999) is None
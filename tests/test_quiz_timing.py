@@ -0,0 +1,83 @@
+"""
+Test cases for quiz timing analytics
+"""
+from datetime import datetime, timezone
+
+import pytest
+
+from textbook.database import GradingJudgmentInfo
+from textbook.grading import JUDGMENT_INITIAL
+from textbook.quiz import book_mastery, load_exercise_candidates, topic_mastery
+from textbook.quiz_timing import SLOW_MASTERY_FACTOR, accurate_but_slow_chapters, record_answer_timing, record_skip, timing_analytics
+
+
+def _judgment(score: float) -> GradingJudgmentInfo:
+    return GradingJudgmentInfo(model_name="test", reason=JUDGMENT_INITIAL, score=score, is_correct=score >= 0.5, confidence=0.9, feedback="")
+
+
+class TestQuizTiming:
+    """Test suite for recording quiz timings and summarizing them"""
+
+    def _answer(self, database, exercise_id: int, score: float, think_seconds: float, answer_changes: int = 0):
+        attempt_id = database.create_attempt(exercise_id, "answer", [_judgment(score)])
+        return record_answer_timing(database, attempt_id, think_seconds, answer_changes)
+
+    def test_accurate_but_slow_chapter(self, database):
+        """Test that a chapter answered well but much slower than usual is flagged and its mastery scaled down"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        fast = database.try_create_chapter_info(book.book_id, "Sequences", "1", 0, 5)
+        slow = database.try_create_chapter_info(book.book_id, "Series", "2", 6, 10)
+        quick = database.create_exercise(book.book_id, 2, "Show that 1/n converges")
+        hard = database.create_exercise(book.book_id, 8, "Show that the harmonic series diverges")
+        for _ in range(4):
+            self._answer(database, quick, 1.0, 20)
+        for _ in range(3):
+            self._answer(database, hard, 0.9, 120, answer_changes=2)
+        record_skip(database, hard, 30, answer_changes=1)
+
+        chapter_of = {candidate.exercise_id: candidate.chapter_id for candidate in load_exercise_candidates(database, book.book_id)}
+        analytics = timing_analytics(database, book.book_id, chapter_of)
+        assert analytics.median_think_seconds == 20
+        chapters = {stats.key: stats for stats in analytics.by_chapter}
+        assert not chapters[str(fast)].accurate_but_slow
+        series = chapters[str(slow)]
+        assert series.accurate_but_slow
+        assert (series.answered, series.skipped) == (3, 1)
+        assert series.skip_rate == pytest.approx(0.25)
+        assert series.median_think_seconds == 120
+        assert series.mean_answer_changes == pytest.approx(7 / 4)
+        assert [(stats.key, stats.answered) for stats in analytics.by_question_type] == [("text", 7)]
+        assert accurate_but_slow_chapters(database, book.book_id, chapter_of) == {slow}
+
+        masteries = book_mastery(database, book.book_id)
+        assert masteries[slow].accurate_but_slow and not masteries[fast].accurate_but_slow
+        attempts = next(candidate.attempts for candidate in load_exercise_candidates(database, book.book_id) if candidate.exercise_id == hard)
+        unscaled = topic_mastery(slow, attempts, datetime.now(timezone.utc))
+        assert masteries[slow].mastery == pytest.approx(unscaled.mastery * SLOW_MASTERY_FACTOR, rel=1e-3)
+
+    def test_slow_inaccurate_chapter_is_not_flagged(self, database):
+        """Test that slow answers with low scores are weak rather than accurate but slow"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        chapter = database.try_create_chapter_info(book.book_id, "Sequences", "1", 0, 5)
+        other = database.try_create_chapter_info(book.book_id, "Series", "2", 6, 10)
+        quick = database.create_exercise(book.book_id, 8, "Sum a geometric series")
+        hard = database.create_exercise(book.book_id, 2, "Show that 1/n converges")
+        for _ in range(4):
+            self._answer(database, quick, 1.0, 20)
+        for _ in range(3):
+            self._answer(database, hard, 0.3, 120)
+
+        assert accurate_but_slow_chapters(database, book.book_id, {quick: other, hard: chapter}) == set()
+        assert not book_mastery(database, book.book_id)[chapter].accurate_but_slow
+
+    def test_invalid_timings(self, database):
+        """Test that unknown attempts and exercises and negative timings are rejected"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        exercise = database.create_exercise(book.book_id, 2, "Show that 1/n converges")
+        with pytest.raises(ValueError):
+            record_answer_timing(database, 9999, 10)
+        with pytest.raises(ValueError):
+            record_skip(database, 9999, 10)
+        with pytest.raises(ValueError):
+            record_skip(database, exercise, -1)
+        assert timing_analytics(database, book.book_id, {}).median_think_seconds is None
@@ -0,0 +1,56 @@
+"""
+Test cases for the rate limits of the LLM provider
+"""
+import pytest
+
+from textbook.rate_limit import ProviderRateLimiter, RateLimits, estimate_tokens, rate_limits_from_config
+
+
+class FakeClock:
+    """A clock that only moves when slept on"""
+
+    def __init__(self):
+        self.now = 0.0
+
+    def __call__(self):
+        return self.now
+
+    def sleep(self, seconds):
+        self.now += seconds
+
+
+class TestRateLimit:
+    """Test suite for holding prompts back within the requests and tokens allowed per minute"""
+
+    def test_limits_from_config(self):
+        """Test that the limits are read per provider and invalid ones are rejected"""
+        config = {"rate_limits": {"gemini": {"requests_per_minute": 15}, "openai": {"tokens_per_minute": 90000}}}
+        assert rate_limits_from_config(config, "gemini") == RateLimits(requests_per_minute=15)
+        assert rate_limits_from_config({}, "gemini") == RateLimits()
+        for section in ({"requests_per_minute": 0}, {"tokens_per_minute": "many"}, {"requests_per_day": 100}):
+            with pytest.raises(ValueError):
+                rate_limits_from_config({"rate_limits": {"gemini": section}}, "gemini")
+
+    def test_requests_wait_for_their_turn(self):
+        """Test that a burst of a minute's requests goes out at once and the next ones at the refill rate"""
+        clock = FakeClock()
+        limiter = ProviderRateLimiter(RateLimits(requests_per_minute=6), clock, clock.sleep)
+        assert [limiter.acquire(10) for _ in range(6)] == [0.0] * 6
+        assert limiter.acquire(10) == pytest.approx(10.0)
+        assert limiter.acquire(10) == pytest.approx(10.0)
+        assert clock.now == pytest.approx(20.0) and limiter.waited_seconds == pytest.approx(20.0)
+
+    def test_tokens_wait_for_their_turn(self):
+        """Test that long answers hold back the next prompts, and prompts above a minute's tokens wait for a full bucket"""
+        clock = FakeClock()
+        limiter = ProviderRateLimiter(RateLimits(tokens_per_minute=600), clock, clock.sleep)
+        assert limiter.acquire(estimate_tokens("x" * 800)) == 0.0
+        limiter.charge(500)
+        assert limiter.acquire(100) == pytest.approx(20.0)
+        assert limiter.acquire(5000) == pytest.approx(60.0)
+
+    def test_no_limits(self):
+        """Test that a provider without limits is never held back"""
+        clock = FakeClock()
+        limiter = ProviderRateLimiter(RateLimits(), clock, clock.sleep)
+        assert sum(limiter.acquire(100000) for _ in range(100)) == 0.0
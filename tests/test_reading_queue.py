@@ -0,0 +1,104 @@
+"""
+Test cases for the incremental reading queue and cloze extracts
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.database import FlashcardInfo
+from textbook.reading_queue import (
+    CLOZE_BLANK,
+    FIRST_READING_INTERVAL_DAYS,
+    QUEUE_CARD,
+    QUEUE_READING,
+    READING_INTERVAL_FACTOR,
+    cloze_cards,
+    enqueue_section,
+    extract_to_cloze,
+    intermix,
+    record_reading,
+    study_queue,
+)
+
+
+class TestQueueing:
+    """Test suite for intermixing reading items and building cloze cards"""
+
+    def test_intermix_places_reading_after_every_few_cards(self):
+        """Test that a reading item follows every fourth card and leftovers come last"""
+        queue = intermix(list(range(9)), ["a", "b", "c"], every=4)
+        assert [kind for kind, _ in queue] == [QUEUE_CARD] * 4 + [QUEUE_READING] + [QUEUE_CARD] * 4 + [QUEUE_READING] + [QUEUE_CARD, QUEUE_READING]
+        assert intermix([], ["a"]) == [(QUEUE_READING, "a")]
+
+    def test_cloze_cards_blank_each_term(self):
+        """Test that every term gives one card with all of its occurrences blanked"""
+        passage = "A Cauchy sequence converges in a complete space. Every Cauchy sequence is bounded."
+        cards = cloze_cards(passage, ["Cauchy", "complete", "Cauchy"])
+        assert cards == [(passage.replace("Cauchy", CLOZE_BLANK), "Cauchy"), (passage.replace("complete", CLOZE_BLANK), "complete")]
+
+    def test_cloze_terms_must_be_in_passage(self):
+        """Test that unknown or missing terms are rejected"""
+        with pytest.raises(ValueError):
+            cloze_cards("A bounded sequence", ["Cauchy"])
+        with pytest.raises(ValueError):
+            cloze_cards("A bounded sequence", [" "])
+
+
+class TestReadingQueue:
+    """Test suite for storing and scheduling the reading queue"""
+
+    @pytest.fixture
+    def book_id(self, database):
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        database.update_book_alignment_offset(book.book_id, 2)
+        chapter = database.try_create_chapter_info(book.book_id, "Sequences", "1", 1, 10)
+        database.try_create_section_info(book.book_id, chapter, "Limits", "1.1", 3, 6)
+        return book.book_id
+
+    def test_reading_intervals_grow_until_done(self, database, book_id):
+        """Test that each reading pushes an item further out and done takes it out of the queue"""
+        now = datetime.now(timezone.utc)
+        section = database.get_sections_by_book_id(book_id)[0]
+        item = enqueue_section(database, "alice", book_id, section.section_id, now=now)
+        assert (item.page_number, item.title) == (5, "Limits")
+        assert [due.reading_id for due in database.get_due_reading_items("alice", now, 10)] == [item.reading_id]
+
+        first = record_reading(database, item.reading_id, now=now)
+        second = record_reading(database, item.reading_id, now=now)
+        assert (first.interval_days, second.interval_days) == (FIRST_READING_INTERVAL_DAYS, FIRST_READING_INTERVAL_DAYS * READING_INTERVAL_FACTOR)
+        assert database.get_due_reading_items("alice", now + timedelta(hours=1), 10) == []
+
+        record_reading(database, item.reading_id, done=True, now=now)
+        assert database.get_reading_items("alice") == []
+        assert len(database.get_reading_items("alice", include_done=True)) == 1
+        with pytest.raises(ValueError):
+            enqueue_section(database, "alice", book_id, 9999)
+
+    def test_extract_creates_highlight_cards_and_reading_item(self, database, book_id):
+        """Test that an extract stores the passage, one card per term in the passage's chapter, and enqueues it"""
+        passage = "Every convergent sequence is bounded."
+        extract = extract_to_cloze(database, "alice", book_id, 4, 10, 10 + len(passage), passage, ["convergent", "bounded"], enqueue=True)
+
+        cards = database.get_flashcards_by_ids(extract.card_ids)
+        assert [card.back for card in cards] == ["convergent", "bounded"]
+        assert all(card.chapter_id is not None and card.page_number == 4 for card in cards)
+        assert database.get_highlights_by_book_id(book_id, "alice")[0].text == passage
+        assert extract.reading_item is not None and extract.reading_item.text == passage
+
+        # Nothing is stored when a term is not in the passage
+        with pytest.raises(ValueError):
+            extract_to_cloze(database, "alice", book_id, 4, 0, 5, "Every", ["missing"])
+        assert len(database.get_highlights_by_book_id(book_id, "alice")) == 1
+
+    def test_study_queue_mixes_cards_and_reading(self, database, book_id):
+        """Test that due reading items are mixed into the review cards within the limit"""
+        now = datetime.now(timezone.utc)
+        database.create_flashcards(book_id, [FlashcardInfo(front=f"front {index}", back="back") for index in range(8)])
+        section = database.get_sections_by_book_id(book_id)[0]
+        enqueue_section(database, "alice", book_id, section.section_id, now=now)
+
+        queue = study_queue(database, "alice", 6, daily_new_limit=20, now=now)
+        assert [kind for kind, _ in queue] == [QUEUE_CARD] * 4 + [QUEUE_READING, QUEUE_CARD]
+
+        database.set_book_status(book_id, suspended=True)
+        assert study_queue(database, "alice", 6, daily_new_limit=20, now=now) == []
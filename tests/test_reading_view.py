@@ -0,0 +1,64 @@
+"""
+Test cases for the chapter reading view and highlights
+"""
+from types import SimpleNamespace
+
+from textbook.cross_reference import CrossReferenceIndex
+from textbook.database import HighlightInfo
+from textbook.reading_view import locate_highlight, render_page, section_practice
+
+PAGE = """Theorem 3.2 (Bolzano-Weierstrass). Every bounded sequence has a convergent subsequence.
+Proof. By Lemma 3.1 every bounded sequence has a monotone subsequence."""
+
+
+class TestRenderPage:
+    """Test suite for rendering the markdown of a page"""
+
+    def test_anchors_and_links(self):
+        """Test that the page, its sections and its entities get anchors and references are linked"""
+        index = CrossReferenceIndex([(7, "lemma", "3.1", 3)])
+        markdown = render_page(PAGE, 5, index, [2], [(9, "Every bounded sequence has a convergent subsequence.")])
+
+        assert markdown.startswith('<a id="page-5"></a><a id="section-2"></a><a id="entity-9"></a>Theorem 3.2')
+        assert "[Lemma 3.1](#entity-7)" in markdown
+
+    def test_highlights_are_marked(self):
+        """Test that highlights wrap their passage, overlapping ones cut where the previous ends"""
+        start = PAGE.index("Every bounded")
+        highlights = [(1, start, start + 22, "Every bounded sequence"), (2, start + 6, start + 40, PAGE[start + 6:start + 40])]
+        markdown = render_page(PAGE, 5, CrossReferenceIndex([]), highlights=highlights)
+
+        assert '<mark id="highlight-1">Every bounded sequence</mark>' in markdown
+        assert f'<mark id="highlight-2">{PAGE[start + 22:start + 40]}</mark>' in markdown
+
+    def test_moved_highlight_is_found_by_its_text(self):
+        """Test that a highlight whose offsets no longer match is found again by its passage, or dropped"""
+        assert locate_highlight(PAGE, 0, 5, "Proof") == (PAGE.index("Proof"), PAGE.index("Proof") + 5)
+        assert locate_highlight(PAGE, 0, 5, "missing passage") is None
+
+    def test_section_practice_counts(self):
+        """Test that exercises are counted per section through the alignment offset"""
+        sections = [SimpleNamespace(section_id=1, start_page_number=1, end_page_number=4), SimpleNamespace(section_id=2, start_page_number=5, end_page_number=8)]
+        # PDF pages 4 and 9 are printed pages 2 and 7, PDF page 30 is outside the chapter
+        practice = section_practice(sections, [(10, 4), (11, 9), (12, 9), (13, 30)], {11}, 2)
+
+        assert (practice[1].problem_count, practice[1].attempted_count) == (1, 0)
+        assert (practice[2].problem_count, practice[2].attempted_count) == (2, 1)
+
+
+class TestHighlights:
+    """Test suite for storing highlights"""
+
+    def test_highlights_per_user_and_page(self, database):
+        """Test that highlights are kept per user, in reading order, and can be deleted"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        later = database.create_highlight(HighlightInfo(user_id="alice", page_number=6, char_start=0, char_end=5, text="Proof", book_id=book.book_id))
+        first = database.create_highlight(HighlightInfo(user_id="alice", page_number=5, char_start=10, char_end=20, text="bounded", note="key", book_id=book.book_id))
+        database.create_highlight(HighlightInfo(user_id="bob", page_number=5, char_start=0, char_end=5, text="Every", book_id=book.book_id))
+
+        assert [highlight.highlight_id for highlight in database.get_highlights_by_book_id(book.book_id, "alice")] == [first.highlight_id, later.highlight_id]
+        assert [highlight.highlight_id for highlight in database.get_highlights_by_book_id(book.book_id, "alice", 6, 6)] == [later.highlight_id]
+
+        assert database.delete_highlight(first.highlight_id)
+        assert not database.delete_highlight(first.highlight_id)
+        assert len(database.get_highlights_by_book_id(book.book_id, "alice")) == 1
@@ -0,0 +1,47 @@
+"""
+Test cases for rendering PDF pages to images
+"""
+import tempfile
+from pathlib import Path
+
+import pymupdf
+import pytest
+from PIL import Image
+
+from textbook.rendering import RenderOptions, pdf_to_image_files, pdf_to_images
+
+
+class TestRenderPages:
+    """Test suite for rendering pages to images with options"""
+
+    @pytest.fixture
+    def pdf_path(self):
+        with tempfile.TemporaryDirectory() as tmpdir:
+            path = Path(tmpdir) / "pages.pdf"
+            document = pymupdf.open()
+            for number in range(3):
+                document.new_page(width=144, height=72).insert_text((10, 40), f"page {number + 1}")
+            document.save(path)
+            document.close()
+            yield path
+
+    def test_dpi_and_grayscale(self, pdf_path):
+        """Test that pages are rendered at the DPI, in grayscale if asked, skipping pages out of range"""
+        images = pdf_to_images(pdf_path, [0, 2, 5], RenderOptions(dpi=144, grayscale=True))
+        assert len(images) == 2
+        assert images[0].size == (288, 144) and images[0].mode == "L"
+        assert pdf_to_images(pdf_path, [1])[0].mode == "RGB"
+
+    def test_pages_are_written_to_files(self, pdf_path):
+        """Test that every page is written to its own file in the format asked for"""
+        output_dir = pdf_path.parent / "images"
+        paths = pdf_to_image_files(pdf_path, [0, 1], output_dir, RenderOptions(dpi=72, image_format="jpeg", quality=70))
+        assert [path.name for path in paths] == ["page_0001.jpg", "page_0002.jpg"]
+        with Image.open(paths[1]) as image:
+            assert image.format == "JPEG" and image.size == (144, 72)
+
+    def test_invalid_options_are_rejected(self, pdf_path):
+        """Test that a DPI out of range, an unknown format or a quality out of range raise ValueError"""
+        for options in (RenderOptions(dpi=2000), RenderOptions(image_format="tiff"), RenderOptions(image_format="jpeg", quality=0)):
+            with pytest.raises(ValueError):
+                pdf_to_images(pdf_path, [0], options)
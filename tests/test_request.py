@@ -1,7 +1,13 @@
 """
 Test cases for MinerURequest class
 """
-from textbook.mineru import MinerURequest
+import os
+import tempfile
+from pathlib import Path
+
+import pytest
+
+from textbook.mineru import MinerURequest, MinerUResult, OUTPUT_ROOT, cleanup_output_dir, new_output_dir, store_images
 
 def test_mineru_request():
     """
@@ -27,3 +33,36 @@ def test_mineru_request():
         for test_string in test_contained_strings:
             markdown = list(results.values())[0]["md_content"]
             assert test_string in markdown, f"Results should contain the string {test_string} for file {file}"
+
+
+def test_output_dir_per_request():
+    """
+    Test that every request gets its own output directory, and that only those are cleaned up
+    """
+    assert MinerURequest(files=[]).params["output_dir"] != MinerURequest(files=[]).params["output_dir"]
+    assert MinerURequest(files=[], output_dir=new_output_dir("job-1")).params["output_dir"] == f"{OUTPUT_ROOT.rstrip('/')}/job-1"
+
+    output_dir = new_output_dir()
+    os.makedirs(f"{output_dir}/images")
+    assert cleanup_output_dir(output_dir) and not os.path.exists(output_dir)
+    assert not cleanup_output_dir(output_dir)
+    assert not cleanup_output_dir(OUTPUT_ROOT)
+    assert not cleanup_output_dir(os.path.dirname(os.path.abspath(OUTPUT_ROOT)))
+
+
+def test_store_images():
+    """
+    Test that the images MinerU returned are stored next to the upload and the markdown links to them there
+    """
+    with tempfile.TemporaryDirectory() as uploads_dir:
+        result = MinerUResult("# Groups\n\n![](images/abc.jpg)\n", images={"abc.jpg": b"jpeg", "../escape.jpg": b"jpeg"})
+        assert store_images(uploads_dir, "algebra.pdf", result) == "# Groups\n\n![](algebra_images/abc.jpg)\n"
+        assert sorted(path.name for path in (Path(uploads_dir) / "algebra_images").iterdir()) == ["abc.jpg", "escape.jpg"]
+
+        for images in ({"a/fig.jpg": b"jpeg", "b/fig.jpg": b"png"}, {"..": b"jpeg"}, {"": b"jpeg"}):
+            with pytest.raises(ValueError):
+                store_images(uploads_dir, "algebra.pdf", MinerUResult("# Groups\n", images=images))
+        assert sorted(path.name for path in (Path(uploads_dir) / "algebra_images").iterdir()) == ["abc.jpg", "escape.jpg"]
+
+        assert store_images(uploads_dir, "algebra.pdf", MinerUResult("# Groups\n")) == "# Groups\n"
+        assert not (Path(uploads_dir) / "algebra_images").exists()
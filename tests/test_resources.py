@@ -0,0 +1,82 @@
+"""
+Test cases for watching the free disk space and memory of the server
+"""
+import threading
+
+import pytest
+
+from textbook.resources import EVENT_DISK_LOW, EVENT_MEMORY_HIGH, OutOfSpaceError, ResourceLimits, ResourceMonitor, resource_limits_from_config
+
+
+class FakeResources:
+    """Free disk space and memory set by the test, and the webhooks posted"""
+
+    def __init__(self):
+        self.free_disk_mb = 5000
+        self.rss_mb = 200
+        self.webhooks = []
+        self.webhook_posted = threading.Event()
+        self.pressure = []
+
+    def monitor(self, limits):
+        return ResourceMonitor(
+            "uploads",
+            limits,
+            on_pressure=self.pressure.append,
+            measure_disk=lambda path: self.free_disk_mb,
+            measure_rss=lambda: self.rss_mb,
+            send_webhook=self.post,
+        )
+
+    def post(self, url, payload):
+        self.webhooks.append((url, payload))
+        self.webhook_posted.set()
+
+
+class TestResources:
+    """Test suite for the disk and memory thresholds"""
+
+    def test_limits_from_config(self):
+        """Test that the [resources] section sets the thresholds and invalid ones are rejected"""
+        assert resource_limits_from_config({}) == ResourceLimits()
+        limits = resource_limits_from_config({"resources": {"min_free_disk_mb": 0, "max_rss_mb": 2048, "webhook_url": ""}})
+        assert (limits.min_free_disk_mb, limits.max_rss_mb, limits.webhook_url) == (0, 2048, None)
+        for section in ({"min_free_disk_mb": -1}, {"max_rss_mb": -5}, {"check_interval_seconds": 0}):
+            with pytest.raises(ValueError):
+                resource_limits_from_config({"resources": section})
+
+    def test_warnings_once_per_crossing(self):
+        """Test that a threshold crossed warns once, pauses until the pressure is gone and warns again next time"""
+        resources = FakeResources()
+        monitor = resources.monitor(ResourceLimits(min_free_disk_mb=1000, max_rss_mb=1000))
+        assert not monitor.check().under_pressure
+        assert resources.pressure == []
+
+        resources.free_disk_mb = 500
+        assert monitor.check().disk_low
+        resources.rss_mb = 1500
+        assert monitor.check().memory_high
+        assert monitor.warnings == {EVENT_DISK_LOW: 1, EVENT_MEMORY_HIGH: 1}
+        assert resources.pressure == [True]
+
+        resources.free_disk_mb, resources.rss_mb = 5000, 200
+        monitor.check()
+        resources.free_disk_mb = 100
+        monitor.check()
+        assert monitor.warnings == {EVENT_DISK_LOW: 2, EVENT_MEMORY_HIGH: 1}
+        assert resources.pressure == [True, False, True]
+        assert resources.webhooks == []
+
+    def test_require_space(self):
+        """Test that a write is refused with a clear error while the disk is low, and posted to the webhook"""
+        resources = FakeResources()
+        monitor = resources.monitor(ResourceLimits(min_free_disk_mb=1000, webhook_url="https://hooks.example.com"))
+        assert monitor.require_space().free_disk_mb == 5000
+
+        resources.free_disk_mb = 10
+        with pytest.raises(OutOfSpaceError, match="Server out of space"):
+            monitor.require_space()
+        # Webhooks are posted on their own thread, a slow endpoint never holds up the upload refused
+        assert resources.webhook_posted.wait(timeout=5)
+        assert resources.webhooks[0][0] == "https://hooks.example.com"
+        assert resources.webhooks[0][1]["event"] == EVENT_DISK_LOW
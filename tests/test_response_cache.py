@@ -0,0 +1,104 @@
+"""
+Test cases for caching the responses of identical LLM requests
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+import structlog
+from pydantic import BaseModel
+
+from textbook.jobs import JobPool
+from textbook.model import LLM, SCHEMA_MODE_PROMPTED
+from textbook.response_cache import ResponseCache, ResponseCacheSettings, cache_key, response_cache_settings_from_config
+
+
+class Summary(BaseModel):
+    summary: str
+
+
+class FakeResponse:
+    def __init__(self, text):
+        self._text = text
+
+    def text(self):
+        return self._text
+
+
+class FakeModel:
+    """A text model answering every prompt with the next numbered summary"""
+
+    def __init__(self):
+        self.calls = 0
+
+    def prompt(self, prompt, attachments=None, **kwargs):
+        self.calls += 1
+        return FakeResponse(f'{{"summary": "Summary {self.calls}"}}')
+
+
+def cached_llm(cache: ResponseCache) -> LLM:
+    model = LLM.__new__(LLM)
+    model.logger = structlog.get_logger("LLM")
+    model.model_name = "fake"
+    model.text_model = FakeModel()
+    model.schema_mode = SCHEMA_MODE_PROMPTED
+    model.response_cache = cache
+    return model
+
+
+class TestResponseCache:
+    """Test suite for the response cache in front of the LLM"""
+
+    def test_settings_from_config(self):
+        """Test that the cache is on by default and invalid limits rejected"""
+        assert response_cache_settings_from_config({}) == ResponseCacheSettings()
+        settings = response_cache_settings_from_config({"response_cache": {"ttl_hours": 2, "max_entries": 10}})
+        assert (settings.ttl_hours, settings.max_entries) == (2, 10)
+        for section in ({"ttl_hours": 0}, {"max_entries": 0}, {"max_entries": 1.5}, {"size": 10}):
+            with pytest.raises(ValueError):
+                response_cache_settings_from_config({"response_cache": section})
+
+    def test_cache_key(self):
+        """Test that the key changes with the model, prompt, schema and options"""
+        key = cache_key("fake", "Summarize", Summary, {"temperature": 0.2}, [])
+        assert key == cache_key("fake", "Summarize", Summary, {"temperature": 0.2}, [])
+        assert key != cache_key("other", "Summarize", Summary, {"temperature": 0.2}, [])
+        assert key != cache_key("fake", "Summarize again", Summary, {"temperature": 0.2}, [])
+        assert key != cache_key("fake", "Summarize", Summary, {"temperature": 0.7}, [])
+
+    def test_identical_requests_are_answered_from_the_cache(self, database):
+        """Test that the provider is prompted once per distinct request and expired responses are prompted again"""
+        cache = ResponseCache(database, ResponseCacheSettings())
+        model = cached_llm(cache)
+        assert model.prompt_with_schema("Summarize chapter 1", Summary).summary == "Summary 1"
+        assert model.prompt_with_schema("Summarize chapter 1", Summary).summary == "Summary 1"
+        assert model.prompt_with_schema("Summarize chapter 2", Summary).summary == "Summary 2"
+        assert model.text_model.calls == 2
+
+        assert cache.purge(datetime.now(timezone.utc) + timedelta(hours=200)) == 2
+        assert model.prompt_with_schema("Summarize chapter 1", Summary).summary == "Summary 3"
+
+        model.response_cache = ResponseCache(database, ResponseCacheSettings(enabled=False))
+        assert model.prompt_with_schema("Summarize chapter 1", Summary).summary == "Summary 4"
+
+    def test_least_recently_used_are_evicted(self, database):
+        """Test that the cache keeps at most max_entries responses, dropping the least recently used"""
+        model = cached_llm(ResponseCache(database, ResponseCacheSettings(max_entries=2)))
+        for chapter in (1, 2, 1, 3):
+            model.prompt_with_schema(f"Summarize chapter {chapter}", Summary)
+        assert model.text_model.calls == 3
+        model.prompt_with_schema("Summarize chapter 1", Summary)
+        assert model.text_model.calls == 3
+        model.prompt_with_schema("Summarize chapter 2", Summary)
+        assert model.text_model.calls == 4
+
+    def test_force_refresh_jobs_prompt_again(self, database):
+        """Test that a job submitted with force_refresh skips the cache and replaces what it held"""
+        model = cached_llm(ResponseCache(database, ResponseCacheSettings()))
+        pool = JobPool(database)
+        pool.register("document_summary", lambda params, handle: {"summary": model.prompt_with_schema("Summarize chapter 1", Summary).summary})
+        results = []
+        for force_refresh in (False, False, True, False):
+            job = pool.submit("document_summary", {"document_id": 1, "force_refresh": force_refresh})
+            results.append(pool.wait(job.job_id, timeout=5).result["summary"])
+        pool.shutdown()
+        assert results == ["Summary 1", "Summary 1", "Summary 2", "Summary 2"]
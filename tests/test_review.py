@@ -0,0 +1,155 @@
+"""
+Test cases for flashcard review scheduling and review batches
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.database import FlashcardInfo
+from textbook.review import (
+    FIRST_INTERVAL_DAYS,
+    GRADE_AGAIN,
+    GRADE_EASY,
+    GRADE_GOOD,
+    GRADE_HARD,
+    INITIAL_EASE,
+    MIN_EASE,
+    RELEARN_INTERVAL_DAYS,
+    SECOND_INTERVAL_DAYS,
+    CardSchedule,
+    ReviewGrade,
+    balance_schedule,
+    fuzz_days,
+    preview_intervals,
+    review_batch,
+    schedule_review,
+    submit_reviews,
+    undo_last_review,
+)
+
+NOW = datetime(2024, 3, 1, 12, tzinfo=timezone.utc)
+
+
+class TestScheduling:
+    """Test suite for the SM-2 review schedule"""
+
+    def test_new_card_intervals(self):
+        """Test that successful reviews of a new card grow from one to six days, then by the ease"""
+        first = schedule_review(None, GRADE_GOOD, NOW)
+        second = schedule_review(first, GRADE_GOOD, NOW)
+        third = schedule_review(second, GRADE_GOOD, NOW)
+        assert (first.interval_days, second.interval_days) == (FIRST_INTERVAL_DAYS, SECOND_INTERVAL_DAYS)
+        assert third.interval_days == pytest.approx(SECOND_INTERVAL_DAYS * INITIAL_EASE)
+        assert third.due_at == NOW + timedelta(days=third.interval_days)
+
+    def test_failing_resets_and_counts_lapse(self):
+        """Test that failing a learned card starts it over and lowers its ease"""
+        learned = CardSchedule(15.0, 2.5, 3, 0, NOW)
+        failed = schedule_review(learned, GRADE_AGAIN, NOW)
+        assert (failed.interval_days, failed.repetitions, failed.lapses) == (RELEARN_INTERVAL_DAYS, 0, 1)
+        assert failed.ease == pytest.approx(2.3)
+        assert schedule_review(None, GRADE_AGAIN, NOW).lapses == 0
+
+    def test_ease_has_a_floor(self):
+        """Test that repeated failures do not lower the ease below the minimum"""
+        schedule = None
+        for _ in range(10):
+            schedule = schedule_review(schedule, GRADE_AGAIN, NOW)
+        assert schedule.ease == MIN_EASE
+
+    def test_grades_order_intervals(self):
+        """Test that better grades never give shorter intervals"""
+        intervals = preview_intervals(CardSchedule(10.0, 2.5, 4, 0, NOW), NOW)
+        assert intervals[GRADE_AGAIN] < intervals[GRADE_HARD] < intervals[GRADE_GOOD] < intervals[GRADE_EASY]
+
+    def test_load_balancing_moves_to_least_loaded_day(self):
+        """Test that a long interval moves within its fuzz to the emptiest day, and short intervals stay exact"""
+        schedule = CardSchedule(40.0, 2.5, 3, 0, NOW + timedelta(days=40))
+        due = (NOW + timedelta(days=40)).date()
+        assert fuzz_days(40.0) == 2 and fuzz_days(2.0) == 0
+        load = {due: 5, due - timedelta(days=1): 3, due + timedelta(days=1): 3, due - timedelta(days=2): 4, due + timedelta(days=2): 1}
+        balanced = balance_schedule(schedule, load)
+        assert balanced.interval_days == 42.0 and balanced.due_at == NOW + timedelta(days=42)
+        # Without a difference in load the due date is kept
+        assert balance_schedule(schedule, {}) == schedule
+        short = CardSchedule(1.0, 2.5, 1, 0, NOW + timedelta(days=1))
+        assert balance_schedule(short, {short.due_at.date(): 9}) == short
+
+    def test_invalid_grade(self):
+        """Test that grades outside 1 to 4 are rejected"""
+        with pytest.raises(ValueError):
+            schedule_review(None, 5, NOW)
+
+
+class TestReviewBatch:
+    """Test suite for fetching and submitting review batches"""
+
+    @pytest.fixture
+    def card_ids(self, database):
+        book = database.create_book("analysis", "me", "sequences", "analysis", 3)
+        return database.create_flashcards(book.book_id, [FlashcardInfo(front=f"front {index}", back=f"back {index}") for index in range(5)])
+
+    def test_new_cards_respect_daily_limit(self, database, card_ids):
+        """Test that new cards are limited per day, counting the cards already introduced today"""
+        now = datetime.now(timezone.utc)
+        batch = review_batch(database, "alice", 10, daily_new_limit=3, now=now)
+        assert [card.card_id for card, state in batch] == card_ids[:3]
+        assert all(state is None for _, state in batch)
+
+        submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_GOOD)], now=now)
+        assert [card.card_id for card, _ in review_batch(database, "alice", 10, daily_new_limit=3, now=now)] == card_ids[1:3]
+        # Another user has their own schedule
+        assert len(review_batch(database, "bob", 10, daily_new_limit=3, now=now)) == 3
+
+    def test_due_cards_come_first(self, database, card_ids):
+        """Test that failed cards come back once due, before new cards"""
+        now = datetime.now(timezone.utc)
+        submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_AGAIN), ReviewGrade(card_ids[1], GRADE_GOOD)], now=now)
+
+        later = now + timedelta(hours=1)
+        batch = review_batch(database, "alice", 2, daily_new_limit=10, now=later)
+        assert [card.card_id for card, _ in batch] == [card_ids[0], card_ids[2]]
+        assert batch[0][1] is not None and batch[1][1] is None
+        assert database.count_due_cards("alice", later) == 1
+
+    def test_batch_is_atomic(self, database, card_ids):
+        """Test that a batch with an unknown card or invalid grade stores nothing"""
+        with pytest.raises(ValueError):
+            submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_GOOD), ReviewGrade(9999, GRADE_GOOD)])
+        with pytest.raises(ValueError):
+            submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_GOOD), ReviewGrade(card_ids[1], 7)])
+        assert database.get_card_states("alice", card_ids) == []
+
+    def test_repeated_card_in_batch_builds_on_first_review(self, database, card_ids):
+        """Test that two reviews of one card in a batch are scheduled in order"""
+        first = datetime.now(timezone.utc) - timedelta(days=2)
+        reviews = submit_reviews(database, "alice", [
+            ReviewGrade(card_ids[0], GRADE_GOOD, reviewed_at=first + timedelta(days=1)),
+            ReviewGrade(card_ids[0], GRADE_GOOD, reviewed_at=first),
+        ])
+        assert [review.interval_days for review in reviews] == [FIRST_INTERVAL_DAYS, SECOND_INTERVAL_DAYS]
+        state = database.get_card_states("alice", [card_ids[0]])[0]
+        assert (state.repetitions, state.interval_days) == (2, SECOND_INTERVAL_DAYS)
+
+    def test_undo_restores_previous_schedule(self, database, card_ids):
+        """Test that undoing reverts reviews one by one, down to a new card"""
+        now = datetime.now(timezone.utc)
+        submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_GOOD)], now=now)
+        submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_AGAIN)], now=now)
+
+        review, state = undo_last_review(database, "alice", card_ids[0], now=now)
+        assert review.grade == GRADE_AGAIN
+        assert (state.repetitions, state.interval_days) == (1, FIRST_INTERVAL_DAYS)
+
+        review, state = undo_last_review(database, "alice", card_ids[0], now=now)
+        assert review.grade == GRADE_GOOD and state is None
+        assert database.get_card_states("alice", [card_ids[0]]) == []
+        assert undo_last_review(database, "alice", card_ids[0], now=now) is None
+
+    def test_undo_is_time_limited(self, database, card_ids):
+        """Test that reviews older than the undo window are kept"""
+        now = datetime.now(timezone.utc)
+        submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_GOOD)], now=now)
+        with pytest.raises(ValueError):
+            undo_last_review(database, "alice", card_ids[0], now=now + timedelta(hours=1))
+        assert len(database.get_card_states("alice", [card_ids[0]])) == 1
@@ -0,0 +1,26 @@
+"""
+Test cases for provider safety settings
+"""
+import pytest
+
+from textbook.safety import SAFETY_OPTION, safety_settings_from_config
+
+
+class TestSafetySettings:
+    """Test suite for reading safety settings from config.toml"""
+
+    def test_thresholds_become_prompt_options(self):
+        """Test that configured thresholds are passed in the provider's names and nothing is passed by default"""
+        config = {"safety_settings": {"gemini": {"dangerous_content": "BLOCK_ONLY_HIGH", "sexually_explicit": "block_none"}}}
+        assert safety_settings_from_config(config, "gemini").prompt_options() == {SAFETY_OPTION: [
+            {"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "BLOCK_ONLY_HIGH"},
+            {"category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "BLOCK_NONE"},
+        ]}
+        assert safety_settings_from_config({}, "gemini").prompt_options() == {}
+        assert safety_settings_from_config(config, "openai").prompt_options() == {}
+
+    def test_invalid_settings(self):
+        """Test that unknown providers, categories and thresholds are rejected"""
+        for configured in ({"bard": {}}, {"gemini": {"violence": "block_none"}}, {"gemini": {"harassment": "never"}}):
+            with pytest.raises(ValueError):
+                safety_settings_from_config({"safety_settings": configured}, "gemini")
@@ -0,0 +1,78 @@
+"""
+Test cases for segmenting OCR markdown into chapters and sections
+"""
+
+from tests.conftest import FakeLLM
+from textbook.content import chunk_content, get_sections, store_chunks
+from textbook.database import ProblemInfo
+from textbook.library import add_upload
+from textbook.problems import document_chapters
+from textbook.segmentation import OutlineSchema, HeadingLevelSchema, document_outline, find_headings, infer_levels, segment_document
+
+# MinerU marked every heading "#" and missed the bold chapter title
+MARKDOWN = """# Contents
+
+1 Groups ........ 1
+
+# 1 Groups
+
+A group is a set with an operation.
+
+# 1.1 Subgroups
+
+A subgroup is closed.
+
+## Exercises
+
+1. Show that the identity is unique.
+
+**Chapter 2**
+
+Chapter 2 is about rings.
+
+# 2.1 Ideals
+
+An ideal absorbs products.
+
+# Notes
+
+# Index
+"""
+
+
+def drop_notes_heading(prompt, schema):
+    """Drops the "Notes" heading and keeps the other levels"""
+    index = next(int(line.strip().split(".")[0]) for line in prompt.splitlines() if "] Notes |" in line)
+    return OutlineSchema(headings=[HeadingLevelSchema(index=index, level=0), HeadingLevelSchema(index=99, level=1)])
+
+
+class TestSegmentation:
+    """Test suite for finding headings, inferring their levels and storing the outline"""
+
+    def test_levels(self):
+        """Test that levels come from numbering, then the table of contents, then the numbered heading before"""
+        headings = find_headings(MARKDOWN)
+        assert [heading.title for heading in headings] == ["Contents", "1 Groups", "1.1 Subgroups", "Exercises", "Chapter 2", "2.1 Ideals", "Notes", "Index"]
+        assert infer_levels(headings) == [1, 1, 2, 3, 1, 2, 3, 1]
+        assert infer_levels(headings, [{"title": "Notes", "level": 1, "page_number": 30}])[6] == 1
+
+    def test_segment_document(self, database):
+        """Test that the sections are replaced, problems follow their chapter and the outline is stored"""
+        document = add_upload(database, "algebra", "a.pdf", 1)
+        markdown, chunks = chunk_content(MARKDOWN)
+        store_chunks(database, document.document_id, chunks)
+        assert [chapter.title for chapter in document_chapters(database, document.document_id)][1:3] == ["1 Groups", "1.1 Subgroups"]
+        database.replace_problems(document.document_id, [ProblemInfo(statement="Show that the identity is unique.", section_index=2)])
+        assert document_outline(database, document.document_id) is None
+
+        llm = FakeLLM(drop_notes_heading, OutlineSchema)
+        outline = segment_document(database, document.document_id, markdown, llm)
+        assert len(llm.prompts) == 1 and "[level 3] Exercises" in llm.prompts[0]
+        assert [section.title for section in get_sections(database, document.document_id)] == ["Contents", "1 Groups", "1.1 Subgroups", "Exercises", "Chapter 2", "2.1 Ideals", "Index"]
+        assert [(chapter.section_index, chapter.title) for chapter in document_chapters(database, document.document_id)] == [(0, "Contents"), (1, "1 Groups"), (4, "Chapter 2"), (6, "Index")]
+        assert [problem.section_index for problem in database.get_problems(document.document_id)] == [1]
+
+        assert [node["title"] for node in outline] == ["Contents", "1 Groups", "Chapter 2", "Index"]
+        assert outline[1]["children"][0]["title"] == "1.1 Subgroups"
+        assert outline[1]["children"][0]["children"][0]["title"] == "Exercises"
+        assert document_outline(database, document.document_id) == outline
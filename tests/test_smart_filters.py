@@ -0,0 +1,99 @@
+"""
+Test cases for saved smart filters of exercises and drawing quizzes from them
+"""
+
+import pytest
+
+from textbook.database import GradingJudgmentInfo, TextBookDatabase
+from textbook.grading import JUDGMENT_INITIAL
+from textbook.quiz import assemble_quiz
+from textbook.smart_filters import (
+    PROGRESS_SOLVED,
+    PROGRESS_UNATTEMPTED,
+    PROGRESS_UNSOLVED,
+    FilterCriteria,
+    criteria_from_dict,
+    evaluate_filter,
+    filter_criteria,
+    matching_exercises,
+    require_filter,
+    save_filter,
+    update_filter,
+)
+
+
+def _grade(database: TextBookDatabase, exercise_id: int, score: float):
+    database.create_attempt(exercise_id, "answer", [GradingJudgmentInfo(model_name="test", reason=JUDGMENT_INITIAL, score=score, is_correct=score >= 0.7, confidence=0.9, feedback="")])
+
+
+class TestSmartFilters:
+    """Test suite for saving and evaluating smart filters"""
+
+    @pytest.fixture
+    def book(self, database):
+        """Seven chapters of two pages, each with an easy and a hard exercise"""
+        book = database.create_book("algebra", "me", "groups", "algebra", 20)
+        chapters, exercises = [], {}
+        for number in range(1, 8):
+            chapter_id = database.try_create_chapter_info(book.book_id, f"Chapter {number}", str(number), number * 2, number * 2 + 1)
+            chapters.append(chapter_id)
+            exercises[number] = (
+                database.create_exercise(book.book_id, number * 2, f"Compute the order of group {number}", difficulty_level=1),
+                database.create_exercise(book.book_id, number * 2 + 1, f"Prove the Sylow theorem for case {number}", difficulty_level=5),
+            )
+        return book.book_id, chapters, exercises
+
+    def test_criteria_are_validated(self):
+        """Test that unknown or contradicting criteria are rejected"""
+        assert criteria_from_dict({"chapter_from": 5, "chapter_to": 7, "progress": PROGRESS_UNSOLVED}) == FilterCriteria(chapter_from=5, chapter_to=7, progress=PROGRESS_UNSOLVED)
+        for data in ({"chapter": 5}, {"chapter_from": 0}, {"chapter_from": 7, "chapter_to": 5}, {"progress": "hard"}, {"min_difficulty": 6}, {"min_difficulty": 4, "max_difficulty": 2}, {"exercise_type": "essay"}):
+            with pytest.raises(ValueError):
+                criteria_from_dict(data)
+
+    def test_unsolved_hard_problems_of_a_chapter_range(self, database, book):
+        """Test that chapter numbers, progress, difficulty and words all narrow the exercises matched"""
+        book_id, chapters, exercises = book
+        _grade(database, exercises[5][1], 1.0)
+        _grade(database, exercises[6][1], 0.2)
+        criteria = FilterCriteria(chapter_from=5, chapter_to=7, progress=PROGRESS_UNSOLVED, min_difficulty=4)
+        matched = matching_exercises(database, book_id, criteria)
+        assert [candidate.exercise_id for candidate in matched] == [exercises[6][1], exercises[7][1]]
+        assert [candidate.chapter_id for candidate in matched] == chapters[5:7]
+
+        assert [candidate.exercise_id for candidate in matching_exercises(database, book_id, FilterCriteria(progress=PROGRESS_SOLVED))] == [exercises[5][1]]
+        assert len(matching_exercises(database, book_id, FilterCriteria(progress=PROGRESS_UNATTEMPTED))) == 12
+        assert [candidate.exercise_id for candidate in matching_exercises(database, book_id, FilterCriteria(chapter_ids=[chapters[0]], text="ORDER group"))] == [exercises[1][0]]
+
+        database.set_chapter_status(chapters[6], archived=True)
+        assert [candidate.exercise_id for candidate in matching_exercises(database, book_id, criteria)] == [exercises[6][1]]
+
+    def test_filters_are_saved_per_user(self, database, book):
+        """Test that names are unique per user, filters read back as saved and other users cannot read them"""
+        book_id, _, exercises = book
+        saved = save_filter(database, "alice", " Hard ", book_id, FilterCriteria(min_difficulty=4))
+        assert (saved.name, filter_criteria(saved)) == ("Hard", FilterCriteria(min_difficulty=4))
+        assert len(evaluate_filter(database, saved)) == 7
+        with pytest.raises(ValueError):
+            save_filter(database, "alice", "Hard", book_id, FilterCriteria())
+        with pytest.raises(LookupError):
+            save_filter(database, "alice", "Missing book", 999, FilterCriteria())
+        assert save_filter(database, "bob", "Hard", book_id, FilterCriteria()).filter_id != saved.filter_id
+
+        updated = update_filter(database, saved, "Easy", book_id, FilterCriteria(max_difficulty=2))
+        assert [candidate.exercise_id for candidate in evaluate_filter(database, updated)][:1] == [exercises[1][0]]
+        assert [smart_filter.name for smart_filter in database.get_smart_filters("alice")] == ["Easy"]
+        with pytest.raises(LookupError):
+            require_filter(database, saved.filter_id, "bob")
+        assert database.delete_smart_filter(saved.filter_id)
+        assert database.get_smart_filters("alice") == []
+
+    def test_quiz_picks_only_filtered_exercises(self, database, book):
+        """Test that a quiz drawn with a filter picks only the exercises it matches"""
+        book_id, chapters, exercises = book
+        # Failed easy exercises make the earlier chapters weak and due for review
+        for number in range(1, 7):
+            _grade(database, exercises[number][0], 0.2)
+        hard = {candidate.exercise_id for candidate in matching_exercises(database, book_id, FilterCriteria(min_difficulty=4))}
+        items, masteries = assemble_quiz(database, book_id, chapters[6], 10, seed=0, exercise_ids=hard)
+        assert len(items) == 7 and {item.exercise_id for item in items} == hard
+        assert chapters[6] in masteries
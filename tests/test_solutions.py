@@ -0,0 +1,75 @@
+"""
+Test cases for generating stepwise solutions of problems
+"""
+
+import pymupdf
+import pytest
+
+from tests.conftest import FakeLLM
+from textbook.content import chunk_content, store_chunks
+from textbook.database import ProblemInfo
+from textbook.library import add_upload
+from textbook.solutions import SolutionSchema, generate_solution, solution_hints, solution_steps
+
+
+class TestSolutions:
+    """Test suite for generating and storing solutions"""
+
+    @pytest.fixture
+    def problem_id(self, database):
+        document = add_upload(database, "algebra", "a.pdf", 1)
+        store_chunks(database, document.document_id, chunk_content("# Groups\nA group is a monoid with inverses.")[1])
+        return database.replace_problems(document.document_id, [
+            ProblemInfo(problem_number="1", statement="Show that the identity is unique.", figures="Figure 1", section_index=0),
+        ])[0]
+
+    def test_solution_is_stored(self, database, problem_id):
+        """Test that the solution is generated with the problem's section and replaces the one before"""
+        llm = FakeLLM(SolutionSchema(steps=[" Let e and f be identities. ", "", "Then e = ef = f."], final_answer="The identity is unique.", hints=["Take two identities.", " "]), SolutionSchema)
+        generate_solution(database, llm, problem_id)
+        assert "monoid with inverses" in llm.prompts[0] and "Figure 1" in llm.prompts[0]
+
+        solution = database.get_solution(problem_id)
+        assert solution_steps(solution) == ["Let e and f be identities.", "Then e = ef = f."]
+        assert (solution.final_answer, solution_hints(solution), solution.model_name) == ("The identity is unique.", ["Take two identities."], "fake")
+
+        generate_solution(database, FakeLLM(SolutionSchema(steps=["e = ef = f"], final_answer="Unique.", hints=[]), SolutionSchema), problem_id)
+        assert database.get_solution(problem_id).final_answer == "Unique."
+
+    def test_figures_are_attached(self, database, tmpdir):
+        """Test that models that can see images get the pages of a problem's figures from its PDF"""
+        document = add_upload(database, "geometry", "g.pdf", 2)
+        pdf = pymupdf.open()
+        for text in ("Figure 1: a triangle", "Figure 2: a circle"):
+            pdf.new_page().insert_text((72, 72), text)
+        pdf.save(tmpdir / "g.pdf")
+        pdf.close()
+        problem_id = database.replace_problems(document.document_id, [
+            ProblemInfo(problem_number="1", statement="Find the area of the triangle in Figure 1.", figures="Figure 1", page_number=1),
+        ])[0]
+        solution = SolutionSchema(steps=["A = bh/2"], final_answer="6", hints=[])
+
+        llm = FakeLLM(solution, SolutionSchema)
+        generate_solution(database, llm, problem_id, uploads_dir=str(tmpdir))
+        assert llm.images == [] and "which you cannot see: Figure 1" in llm.prompts[0]
+
+        llm = FakeLLM(solution, SolutionSchema)
+        llm.supports_images = True
+        generate_solution(database, llm, problem_id, uploads_dir=str(tmpdir))
+        assert len(llm.images) == 1 and len(llm.images[0]) == 1  # The last page has no next page
+        assert "on the attached images of its pages: Figure 1" in llm.prompts[0]
+
+    def test_invalid_solution_is_rejected(self, database, problem_id):
+        """Test that a solution without steps is not stored and unknown problems are rejected"""
+        with pytest.raises(ValueError):
+            generate_solution(database, FakeLLM(SolutionSchema(steps=[" "], final_answer="Unique.", hints=[]), SolutionSchema), problem_id)
+        assert database.get_solution(problem_id) is None
+        with pytest.raises(ValueError):
+            generate_solution(database, FakeLLM(None, SolutionSchema), 999)
+
+    def test_solution_is_replaced_with_its_problem(self, database, problem_id):
+        """Test that extracting the problems again deletes the solutions of the old ones"""
+        generate_solution(database, FakeLLM(SolutionSchema(steps=["e = ef = f"], final_answer="Unique.", hints=[]), SolutionSchema), problem_id)
+        document_id = database.get_problem(problem_id).document_id
+        database.replace_problems(document_id, [])
+        assert database.get_solution(problem_id) is None
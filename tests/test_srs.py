@@ -0,0 +1,61 @@
+"""
+Test cases for the spaced repetition of problems
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.database import ProblemInfo
+from textbook.library import add_upload
+from textbook.review import GRADE_AGAIN, GRADE_GOOD
+from textbook.srs import grade_problem, problem_review_queue
+
+NOW = datetime(2024, 3, 1, 9, 0, tzinfo=timezone.utc)
+
+
+class TestProblemReviews:
+    """Test suite for scheduling and queueing problem reviews"""
+
+    @pytest.fixture
+    def problem_ids(self, database):
+        document = add_upload(database, "algebra", "a.pdf", 1)
+        return database.replace_problems(document.document_id, [
+            ProblemInfo(problem_number=str(number), statement=f"Problem {number}") for number in range(1, 4)
+        ])
+
+    def test_grades_are_scheduled(self, database, problem_ids):
+        """Test that passing a problem spaces it out and failing it brings it back the same day"""
+        state = grade_problem(database, "ada", problem_ids[0], GRADE_GOOD, NOW)
+        assert (state.interval_days, state.repetitions, state.lapses) == (1.0, 1, 0)
+
+        state = grade_problem(database, "ada", problem_ids[0], GRADE_GOOD, NOW + timedelta(days=1))
+        assert (state.interval_days, state.repetitions) == (6.0, 2)
+
+        state = grade_problem(database, "ada", problem_ids[0], GRADE_AGAIN, NOW + timedelta(days=7))
+        assert (state.repetitions, state.lapses) == (0, 1)
+        assert state.interval_days < 1
+        assert database.get_problem_state("grace", problem_ids[0]) is None
+
+    def test_invalid_grades_are_rejected(self, database, problem_ids):
+        """Test that unknown problems and grades are not recorded"""
+        with pytest.raises(ValueError):
+            grade_problem(database, "ada", 999, GRADE_GOOD, NOW)
+        with pytest.raises(ValueError):
+            grade_problem(database, "ada", problem_ids[0], 5, NOW)
+        assert database.get_problem_state("ada", problem_ids[0]) is None
+
+    def test_due_problems_come_before_new_ones(self, database, problem_ids):
+        """Test that the queue lists due problems, then new ones up to the limit"""
+        grade_problem(database, "ada", problem_ids[1], GRADE_GOOD, NOW)
+        grade_problem(database, "ada", problem_ids[2], GRADE_GOOD, NOW + timedelta(days=5))
+
+        queue = problem_review_queue(database, "ada", 10, 1, now=NOW + timedelta(days=2))
+        assert [(problem.problem_id, state is not None) for problem, state in queue] == [(problem_ids[1], True), (problem_ids[0], False)]
+
+        assert problem_review_queue(database, "ada", 10, 0, now=NOW) == []
+
+    def test_schedules_are_replaced_with_their_problem(self, database, problem_ids):
+        """Test that extracting the problems again deletes the schedules of the old ones"""
+        grade_problem(database, "ada", problem_ids[0], GRADE_GOOD, NOW)
+        database.replace_problems(database.get_problem(problem_ids[0]).document_id, [])
+        assert database.get_problem_state("ada", problem_ids[0]) is None
@@ -0,0 +1,107 @@
+"""
+Test cases for archiving and suspending documents, decks and cards
+"""
+from datetime import datetime, timedelta, timezone
+from types import SimpleNamespace
+
+import pytest
+
+from textbook.database import FlashcardInfo
+from textbook.quiz import assemble_quiz
+from textbook.review import GRADE_AGAIN, ReviewGrade, review_batch, submit_reviews
+from textbook.status import (
+    STATUS_ACTIVE,
+    STATUS_ALL,
+    STATUS_ARCHIVED,
+    STATUS_SUSPENDED,
+    apply_status,
+    item_status,
+    matches_status,
+)
+
+
+def _item(archived: bool = False, suspended: bool = False) -> SimpleNamespace:
+    now = datetime.now(timezone.utc)
+    return SimpleNamespace(archived_at=now if archived else None, suspended_at=now if suspended else None)
+
+
+class TestStatus:
+    """Test suite for effective statuses and status filters"""
+
+    def test_parent_state_applies_to_children(self):
+        """Test that a card is suspended or archived with its chapter or book, archiving winning"""
+        assert item_status(_item(), None, _item()) == STATUS_ACTIVE
+        assert item_status(_item(), _item(suspended=True), _item()) == STATUS_SUSPENDED
+        assert item_status(_item(suspended=True), _item(), _item(archived=True)) == STATUS_ARCHIVED
+
+    def test_default_filter_hides_archived(self):
+        """Test that listings hide archived items unless asked for them"""
+        assert matches_status(STATUS_SUSPENDED, None)
+        assert not matches_status(STATUS_ARCHIVED, None)
+        assert matches_status(STATUS_ARCHIVED, STATUS_ARCHIVED) and matches_status(STATUS_ARCHIVED, STATUS_ALL)
+        assert not matches_status(STATUS_ACTIVE, STATUS_SUSPENDED)
+
+    def test_apply_keeps_first_timestamp(self):
+        """Test that archiving twice keeps the first moment, and None leaves a state unchanged"""
+        item = _item(archived=True)
+        archived_at = item.archived_at
+        apply_status(item, True, None)
+        assert item.archived_at == archived_at and item.suspended_at is None
+        apply_status(item, False, True)
+        assert item.archived_at is None and item.suspended_at is not None
+
+
+class TestStatusFromDatabase:
+    """Test suite for leaving archived and suspended items out of reviews and quizzes"""
+
+    def test_review_queue_skips_suspended_and_archived(self, database):
+        """Test that suspended or archived cards, chapters and books are left out of new and due cards"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        chapter = database.try_create_chapter_info(book.book_id, "Sequences", "1", 1, 5)
+        card_ids = database.create_flashcards(book.book_id, [
+            FlashcardInfo(front="limit", back="..."),
+            FlashcardInfo(front="Cauchy", back="...", chapter_id=chapter),
+            FlashcardInfo(front="bounded", back="..."),
+        ])
+        now = datetime.now(timezone.utc)
+        submit_reviews(database, "alice", [ReviewGrade(card_id, GRADE_AGAIN) for card_id in card_ids], now=now)
+        later = now + timedelta(hours=1)
+        assert database.count_due_cards("alice", later) == 3
+
+        database.set_flashcard_status(card_ids[0], suspended=True)
+        database.set_chapter_status(chapter, archived=True)
+        assert [card.card_id for card, _ in review_batch(database, "alice", 10, 10, now=later)] == [card_ids[2]]
+
+        database.set_book_status(book.book_id, suspended=True)
+        assert database.count_due_cards("alice", later) == 0
+        database.set_book_status(book.book_id, suspended=False)
+        database.set_flashcard_status(card_ids[0], suspended=False)
+        assert database.count_due_cards("alice", later) == 2
+        # The card and its schedule are retained
+        assert len(database.get_card_states("alice", card_ids)) == 3
+
+    def test_new_cards_skip_suspended(self, database):
+        """Test that a suspended card is not introduced as a new card"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        card_ids = database.create_flashcards(book.book_id, [FlashcardInfo(front=f"front {index}", back="back") for index in range(2)])
+        database.set_flashcard_status(card_ids[0], suspended=True)
+        assert [card.card_id for card in database.get_new_flashcards("alice", 10)] == [card_ids[1]]
+        assert database.set_flashcard_status(9999, archived=True) is None
+
+    def test_quiz_leaves_out_archived_chapters(self, database):
+        """Test that archived earlier chapters are not mixed in, and an archived chapter or book cannot be quizzed"""
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        first = database.try_create_chapter_info(book.book_id, "Sequences", "1", 1, 5)
+        second = database.try_create_chapter_info(book.book_id, "Series", "2", 6, 10)
+        database.create_exercise(book.book_id, 2, "Show that 1/n converges")
+        current = [database.create_exercise(book.book_id, 8, f"Series exercise {index}") for index in range(2)]
+
+        database.set_chapter_status(first, archived=True)
+        items, _ = assemble_quiz(database, book.book_id, second, 4, seed=0)
+        assert sorted(item.exercise_id for item in items) == sorted(current)
+        with pytest.raises(ValueError):
+            assemble_quiz(database, book.book_id, first, 4)
+
+        database.set_book_status(book.book_id, archived=True)
+        with pytest.raises(ValueError):
+            assemble_quiz(database, book.book_id, second, 4)
@@ -0,0 +1,115 @@
+"""
+Test cases for the study event stream and the projections replayed from it
+"""
+from datetime import date, datetime, timedelta, timezone
+
+import pytest
+
+from textbook.database import FlashcardInfo, GradingJudgmentInfo, ReadingItemInfo, StudyEventInfo
+from textbook.grading import JUDGMENT_INITIAL
+from textbook.preferences import DEFAULT_USER_ID
+from textbook.projections import Activity, backfill_events, projected_mastery, user_activity
+from textbook.quiz import book_mastery
+from textbook.reading_queue import record_reading
+from textbook.review import GRADE_AGAIN, GRADE_GOOD, ReviewGrade, submit_reviews, undo_last_review
+from textbook.study_events import (
+    EVENT_ATTEMPTED,
+    EVENT_GRADED,
+    EVENT_READ,
+    EVENT_REVIEW_UNDONE,
+    EVENT_REVIEWED,
+    EVENT_VOIDED,
+    decode_payload,
+)
+
+
+def _judgment(score: float) -> GradingJudgmentInfo:
+    return GradingJudgmentInfo(model_name="test", reason=JUDGMENT_INITIAL, score=score, is_correct=score >= 0.7, confidence=0.9, feedback="")
+
+
+class TestActivity:
+    """Test suite for counting streaks of days with activity"""
+
+    def test_streaks(self):
+        """Test that a streak runs up to today, or yesterday while today has no activity yet"""
+        today = date(2024, 3, 10)
+        activity = Activity(days={today - timedelta(days=offset): {EVENT_ATTEMPTED: 1} for offset in (1, 2, 3, 6, 7)})
+        assert activity.current_streak(today) == 3
+        assert activity.current_streak(today + timedelta(days=1)) == 0
+        assert activity.longest_streak() == 3
+        assert Activity().longest_streak() == 0
+
+
+class TestStudyEvents:
+    """Test suite for appending study events and replaying them"""
+
+    @pytest.fixture
+    def book(self, database):
+        book = database.create_book("algebra", "me", "groups", "algebra", 20)
+        chapter_id = database.try_create_chapter_info(book.book_id, "Groups", "1", 1, 10)
+        exercise_ids = [database.create_exercise(book.book_id, page, f"Exercise on page {page}") for page in (2, 3)]
+        return book.book_id, chapter_id, exercise_ids
+
+    def test_actions_append_events(self, database, book):
+        """Test that attempts, gradings, voiding, reviews, undoing and reading are each appended as they are stored"""
+        book_id, _, exercise_ids = book
+        attempt_id = database.create_attempt(exercise_ids[0], "answer", [_judgment(0.4)])
+        database.add_grading_judgment(attempt_id, _judgment(0.9))
+        database.void_attempt(attempt_id)
+        card_ids = database.create_flashcards(book_id, [FlashcardInfo(front="front", back="back")])
+        submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_GOOD)])
+        undo_last_review(database, "alice", card_ids[0])
+        item = database.create_reading_item(ReadingItemInfo(user_id="alice", title="Groups", due_at=datetime.now(timezone.utc), book_id=book_id))
+        record_reading(database, item.reading_id, done=True)
+
+        events = database.get_study_events()
+        assert [event.event_kind for event in events] == [EVENT_ATTEMPTED, EVENT_GRADED, EVENT_GRADED, EVENT_VOIDED, EVENT_REVIEWED, EVENT_REVIEW_UNDONE, EVENT_READ]
+        assert [decode_payload(event.payload)["score"] for event in events[1:3]] == [0.4, 0.9]
+        assert {event.user_id for event in events[:4]} == {None}
+        assert decode_payload(events[6].payload)["done"] is True
+        assert [event.event_kind for event in database.get_study_events(user_id="alice")] == [EVENT_REVIEWED, EVENT_REVIEW_UNDONE, EVENT_READ]
+        assert len(database.get_study_events(user_id=DEFAULT_USER_ID, include_unattributed=True)) == 4
+
+    def test_projected_mastery_matches_stored_attempts(self, database, book):
+        """Test that mastery replayed from the events is the mastery computed from the attempts, voided attempts left out"""
+        book_id, chapter_id, exercise_ids = book
+        database.create_attempt(exercise_ids[0], "answer", [_judgment(1.0)])
+        regraded = database.create_attempt(exercise_ids[1], "answer", [_judgment(0.2)])
+        database.add_grading_judgment(regraded, _judgment(0.8))
+        database.void_attempt(database.create_attempt(exercise_ids[1], "answer", [_judgment(0.0)]))
+
+        now = datetime.now(timezone.utc)
+        projected, stored = projected_mastery(database, book_id, now)[chapter_id], book_mastery(database, book_id)[chapter_id]
+        assert projected.attempts == stored.attempts == 2
+        assert projected.mastery == pytest.approx(stored.mastery, abs=1e-6)
+
+    def test_activity_takes_back_corrections(self, database, book):
+        """Test that undone reviews and voided attempts no longer count as activity"""
+        book_id, _, exercise_ids = book
+        card_ids = database.create_flashcards(book_id, [FlashcardInfo(front=f"front {index}", back="back") for index in range(2)])
+        now = datetime.now(timezone.utc)
+        submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_GOOD, reviewed_at=now - timedelta(days=1)), ReviewGrade(card_ids[1], GRADE_AGAIN, reviewed_at=now)], now=now)
+        undo_last_review(database, "alice", card_ids[1], now=now)
+        database.void_attempt(database.create_attempt(exercise_ids[0], "answer", [_judgment(1.0)]))
+
+        activity = user_activity(database, "alice")
+        assert activity.totals[EVENT_REVIEWED] == 1
+        assert list(activity.days) == [(now - timedelta(days=1)).date()]
+        assert activity.current_streak(now.date()) == 1
+        assert user_activity(database, DEFAULT_USER_ID).totals[EVENT_ATTEMPTED] == 0
+
+    def test_backfill_appends_history_once(self, database, book):
+        """Test that the history of a database from before the stream is appended in the order it happened, only once"""
+        book_id, _, exercise_ids = book
+        database.create_attempt(exercise_ids[0], "answer", [_judgment(0.5)])
+        card_ids = database.create_flashcards(book_id, [FlashcardInfo(front="front", back="back")])
+        submit_reviews(database, "alice", [ReviewGrade(card_ids[0], GRADE_GOOD)])
+        appended = [(event.event_kind, event.subject_id) for event in database.get_study_events()]
+        assert backfill_events(database) == 0
+
+        with database.new_session() as session:
+            session.query(StudyEventInfo).delete()
+            session.commit()
+        assert backfill_events(database) == len(appended)
+        assert [(event.event_kind, event.subject_id) for event in database.get_study_events()] == appended
+        assert backfill_events(database) == 0
@@ -0,0 +1,103 @@
+"""
+Test cases for the search-as-you-type suggestions of titles, headings and entity names
+"""
+import time
+
+import pytest
+
+from textbook.database import BookInfo, ChapterInfo, ContentChunkInfo, DocumentInfo, EntityInfo, SectionInfo
+from textbook.suggest import (
+    SUGGESTION_ENTITY,
+    SUGGESTION_HEADING,
+    SUGGESTION_TITLE,
+    PrefixIndex,
+    Suggestion,
+    SuggestionIndex,
+    normalize,
+)
+
+
+class TestSuggestions:
+    """Test suite for the prefix index of search suggestions"""
+
+    @pytest.fixture
+    def library(self, database):
+        """A book with a chapter, its sections and entities, and an uploaded document with section headings"""
+        with database.new_session() as session:
+            book = BookInfo(book_name="Finite Group Theory", book_author="isaacs", book_file_name="groups", book_pages=10)
+            session.add(book)
+            session.flush()
+            chapter = ChapterInfo(title="Sylow Theory", start_page_number=2, end_page_number=8, book_id=book.book_id)
+            session.add(chapter)
+            session.flush()
+            for page, title in ((3, "Exercises"), (6, "Exercises"), (4, "Fusion")):
+                session.add(SectionInfo(title=title, start_page_number=page, end_page_number=page + 1, chapter_id=chapter.chapter_id, book_id=book.book_id))
+            session.add(EntityInfo(entity_kind="theorem", entity_name="Sylow's theorem", page_number=4, chapter_id=chapter.chapter_id, book_id=book.book_id))
+            session.add(EntityInfo(entity_kind="definition", entity_label="1.1", page_number=2, book_id=book.book_id))
+            document = DocumentInfo(title="Théorie de Galois", ocr_status="done", detected_type="textbook")
+            session.add(document)
+            session.flush()
+            session.add(ContentChunkInfo(chunk_kind="section", chunk_index=0, title="Field Extensions", page_start=1, char_start=0, char_end=10, text="...", document_id=document.document_id))
+            session.add(ContentChunkInfo(chunk_kind="page", chunk_index=1, page_start=1, char_start=0, char_end=10, text="Fields everywhere", document_id=document.document_id))
+            session.commit()
+            return book.book_id, chapter.chapter_id, document.document_id
+
+    def test_normalize(self):
+        """Test that case, accents and punctuation are ignored"""
+        assert normalize("  Théorie de  GALOIS ") == "theorie de galois"
+        assert normalize("Sylow's theorem (2.3)") == "sylow s theorem 2 3"
+        assert normalize("—") == ""
+
+    def test_terms_complete_from_any_word(self, database, library):
+        """Test that titles, headings and entity names complete a prefix of any of their words, ignoring accents"""
+        book_id, chapter_id, document_id = library
+        index = SuggestionIndex(database)
+        [title] = index.suggest("finite")
+        assert (title.text, title.kind, title.book_id) == ("Finite Group Theory", SUGGESTION_TITLE, book_id)
+        [heading] = index.suggest("exten")
+        assert (heading.kind, heading.document_id, heading.section_index, heading.page_number) == (SUGGESTION_HEADING, document_id, 0, 1)
+        assert [suggestion.text for suggestion in index.suggest("theorie de g")] == ["Théorie de Galois"]
+        assert [suggestion.text for suggestion in index.suggest("GAL")] == ["Théorie de Galois"]
+        assert index.suggest("everywhere") == [] and index.suggest("  ") == []
+
+    def test_ranking_and_kinds(self, database, library):
+        """Test that whole-term matches come first, then titles, headings and entities, and kinds filter them"""
+        book_id, chapter_id, _ = library
+        index = SuggestionIndex(database)
+        suggestions = index.suggest("syl")
+        assert [(suggestion.text, suggestion.kind) for suggestion in suggestions] == [("Sylow Theory", SUGGESTION_HEADING), ("Sylow's theorem", SUGGESTION_ENTITY)]
+        assert suggestions[0].chapter_id == chapter_id
+        assert (suggestions[1].entity_kind, suggestions[1].page_number) == ("theorem", 4)
+        # Matching from a later word ranks after matching from the start
+        assert [suggestion.text for suggestion in index.suggest("theor")] == ["Théorie de Galois", "Finite Group Theory", "Sylow Theory", "Sylow's theorem"]
+        assert [suggestion.kind for suggestion in index.suggest("theor", kinds=[SUGGESTION_ENTITY])] == [SUGGESTION_ENTITY]
+        assert len(index.suggest("t", limit=2)) == 2
+
+    def test_repeated_headings_are_suggested_once(self, database, library):
+        """Test that the headings a book repeats, like its exercises, are suggested once"""
+        assert [suggestion.text for suggestion in SuggestionIndex(database).suggest("exer")] == ["Exercises"]
+
+    def test_index_is_rebuilt_when_stale(self, database, library):
+        """Test that a stale index answers while it is rebuilt in the background"""
+        book_id, _, _ = library
+        index = SuggestionIndex(database, refresh_seconds=0)
+        assert index.build() == 8
+        with database.new_session() as session:
+            session.add(ChapterInfo(title="Solvable Groups", start_page_number=8, end_page_number=10, book_id=book_id))
+            session.commit()
+        assert index.suggest("solv") == []
+        deadline = time.monotonic() + 5
+        while not index.suggest("solv") and time.monotonic() < deadline:
+            time.sleep(0.01)
+        assert [suggestion.text for suggestion in index.suggest("solv")] == ["Solvable Groups"]
+
+    def test_lookup_is_fast_for_a_large_library(self):
+        """Test that a lookup takes well under the 20ms of a keystroke in a library of 100,000 terms"""
+        words = ["group", "ring", "field", "module", "lemma", "theorem", "sylow", "galois", "ideal", "vector"]
+        suggestions = [Suggestion(f"{words[n % 10]} {words[n // 10 % 10]} {words[n // 100 % 10]} {n}", SUGGESTION_HEADING) for n in range(100_000)]
+        index = PrefixIndex(suggestions)
+        started = time.perf_counter()
+        for prefix in ("g", "gr", "group ri", "syl", "galois field 12", "x"):
+            index.lookup(prefix)
+        assert (time.perf_counter() - started) / 6 < 0.02
+        assert [suggestion.text for suggestion in index.lookup(suggestions[9871].text)] == [suggestions[9871].text]
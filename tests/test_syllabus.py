@@ -0,0 +1,80 @@
+"""
+Test cases for importing course syllabi and planning by them
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from tests.conftest import FakeLLM
+from textbook.database import GradingJudgmentInfo
+from textbook.grading import JUDGMENT_INITIAL
+from textbook.planner import study_digest, study_plan
+from textbook.preferences import Preferences
+from textbook.syllabus import SyllabusSchema, SyllabusTopicSchema, import_syllabus, syllabus_text, upcoming_topics
+
+
+SYLLABUS = SyllabusSchema(topics=[
+    SyllabusTopicSchema(week=1, title="Limits of sequences", description="Epsilon-N definition", chapter=1),
+    SyllabusTopicSchema(week=2, title="Series", description="Convergence tests", chapter=2),
+    SyllabusTopicSchema(week=2, title="Course review", description="", chapter=7),
+    SyllabusTopicSchema(week=60, title="Beyond the course", description="", chapter=None),
+])
+
+
+class TestSyllabusText:
+    """Test suite for reading uploaded syllabi"""
+
+    def test_text_files(self):
+        """Test that text files are read and other files rejected"""
+        assert syllabus_text("course.md", "Week 1: Limits".encode("utf-8")) == "Week 1: Limits"
+        with pytest.raises(ValueError):
+            syllabus_text("course.docx", b"PK")
+
+
+class TestSyllabus:
+    """Test suite for importing a syllabus and the topics coming up"""
+
+    @pytest.fixture
+    def book_id(self, database):
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        database.try_create_chapter_info(book.book_id, "Sequences", "1", 1, 10)
+        database.try_create_chapter_info(book.book_id, "Series", "2", 11, 20)
+        return book.book_id
+
+    def test_topics_are_aligned_to_chapters(self, database, book_id):
+        """Test that topics get their chapter and week, made up chapters and weeks are dropped"""
+        llm = FakeLLM(SYLLABUS, SyllabusSchema)
+        starts_on = datetime(2026, 9, 7, tzinfo=timezone.utc).date()
+        topics = import_syllabus(database, llm, "alice", book_id, "Week 1: limits. Week 2: series.", starts_on)
+        chapters = database.get_chapters_by_book_id(book_id)
+
+        assert "1. Sequences" in llm.prompts[0] and "2. Series" in llm.prompts[0]
+        assert [(topic.week, topic.title, topic.chapter_id) for topic in topics] == [
+            (1, "Limits of sequences", chapters[0].chapter_id),
+            (2, "Series", chapters[1].chapter_id),
+            (2, "Course review", None),
+        ]
+        assert topics[1].week_starts_at.date() == starts_on + timedelta(weeks=1)
+        assert topics[2].description is None
+
+        import_syllabus(database, FakeLLM(SyllabusSchema(topics=SYLLABUS.topics[:1]), SyllabusSchema), "alice", book_id, "Week 1: limits.", starts_on)
+        assert [topic.title for topic in database.get_syllabus_topics("alice", book_id)] == ["Limits of sequences"]
+        with pytest.raises(ValueError):
+            import_syllabus(database, FakeLLM(SyllabusSchema(topics=[]), SyllabusSchema), "alice", book_id, "Nothing", starts_on)
+
+    def test_weakest_upcoming_topics_first(self, database, book_id):
+        """Test that the topics of this week and next come up weakest chapter first, in the plan and the digest"""
+        exercise_id = database.create_exercise(book_id, 4, "Show that 1/n converges")
+        database.create_attempt(exercise_id, "Converges", [GradingJudgmentInfo(model_name="test", reason=JUDGMENT_INITIAL, score=1.0, is_correct=True, confidence=0.9, feedback="")])
+        today = datetime.now(timezone.utc).date()
+        syllabus = SyllabusSchema(topics=[topic.model_copy(update={"week": 1}) for topic in SYLLABUS.topics[:2]])
+        import_syllabus(database, FakeLLM(syllabus, SyllabusSchema), "alice", book_id, "Week 1: limits and series.", today)
+
+        focus = upcoming_topics(database, "alice", book_id)
+        assert [item.topic.title for item in focus] == ["Series", "Limits of sequences"]
+        assert focus[0].mastery == 0.0 and focus[1].mastery > 0
+        assert upcoming_topics(database, "bob", book_id) == []
+
+        plan = study_plan(database, Preferences("alice"), 2, book_id)
+        assert [item.topic.title for item in plan[0].syllabus] == ["Series", "Limits of sequences"] and plan[1].syllabus == []
+        assert [item.topic.title for item in study_digest(database, "alice", book_id).coming_up] == ["Series", "Limits of sequences"]
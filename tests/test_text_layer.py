@@ -0,0 +1,96 @@
+"""
+Test cases for reading uploads from their text layer, OCRing only their scanned pages
+"""
+
+import pymupdf
+import pytest
+from PIL import Image
+
+from textbook.ocr import OcrBackend, OcrResult
+from textbook.text_layer import extract_with_text_layer, read_text_layer, text_layer_blocks
+
+BODY = "Let G be a finite group and p a prime dividing its order. Then G has a subgroup of order p."
+
+
+class RecordingBackend(OcrBackend):
+    """Answers a block per page of the PDFs it is given, recording their page counts"""
+
+    name = "recording"
+
+    def __init__(self):
+        self.page_counts = []
+
+    def unavailable(self):
+        return None
+
+    def ocr_pdf(self, pdf_path, language, parse_method, job_id=None):
+        with pymupdf.open(pdf_path) as document:
+            self.page_counts.append(len(document))
+            blocks = [{"type": "text", "text": f"scanned {index}", "page_idx": index} for index in range(len(document))]
+        return OcrResult("\n\n".join(block["text"] for block in blocks), blocks, {"figure.jpg": b"jpeg"})
+
+    def ocr_image(self, image):
+        return ""
+
+
+class TestTextLayer:
+    """Test suite for text layer extraction"""
+
+    @pytest.fixture
+    def scan(self, tmpdir):
+        path = tmpdir / "scan.png"
+        Image.new("RGB", (200, 300), "white").save(path)
+        return path
+
+    def make_pdf(self, tmpdir, pages, scan=None):
+        """A PDF of text pages, a scanned page (an image without text) where a page is None"""
+        path = tmpdir / "book.pdf"
+        with pymupdf.open() as document:
+            for page_content in pages:
+                page = document.new_page()
+                if page_content is None:
+                    page.insert_image(page.rect, filename=str(scan))
+                    continue
+                heading, body = page_content
+                if heading:
+                    page.insert_text((72, 72), heading, fontsize=20)
+                page.insert_textbox(pymupdf.Rect(72, 100, 520, 400), body, fontsize=11)
+            document.new_page()  # Blank, neither read nor OCRed
+            document.save(path)
+        return str(path)
+
+    def test_text_layer_is_read_with_headings(self, tmpdir):
+        """Test that digital pages are read without OCR and larger text becomes headings"""
+        pdf_path = self.make_pdf(tmpdir, [("1 Groups", BODY), (None, BODY)])
+        layer = read_text_layer(pdf_path)
+        assert (layer.page_count, sorted(layer.pages), layer.scanned) == (3, [0, 1], [])
+        blocks = text_layer_blocks(layer)
+        assert blocks[0] == {"type": "text", "text": "1 Groups", "page_idx": 0, "text_level": 1}
+        assert all("text_level" not in block for block in blocks[1:])
+
+        backend = RecordingBackend()
+        result = extract_with_text_layer(backend, pdf_path, "en", "auto")
+        assert backend.page_counts == [] and result.images == {}
+        assert result.markdown.startswith("# 1 Groups\n\nLet G be a finite group")
+
+    def test_only_scanned_pages_are_ocred(self, tmpdir, scan):
+        """Test that scanned pages are OCRed in a PDF of their own and their blocks put back on their pages"""
+        pdf_path = self.make_pdf(tmpdir, [None, ("", BODY), None], scan)
+        assert read_text_layer(pdf_path).scanned == [0, 2]
+        backend = RecordingBackend()
+        result = extract_with_text_layer(backend, pdf_path, "en", "auto")
+        assert backend.page_counts == [2]
+        assert [(block["page_idx"], block["text"][:9]) for block in result.content_list] == [(0, "scanned 0"), (1, "Let G be "), (2, "scanned 1")]
+        assert result.images == {"figure.jpg": b"jpeg"}
+
+    def test_parse_method_chooses_the_source(self, tmpdir, scan):
+        """Test that ocr OCRs the whole PDF, txt reads only the text layer, and a scan without text is OCRed whole"""
+        mixed = self.make_pdf(tmpdir, [None, ("", BODY)], scan)
+        backend = RecordingBackend()
+        assert len(extract_with_text_layer(backend, mixed, "en", "ocr").content_list) == 3
+        assert [block["page_idx"] for block in extract_with_text_layer(backend, mixed, "en", "txt").content_list] == [1]
+        assert backend.page_counts == [3]
+
+        scanned = self.make_pdf(tmpdir, [None, None], scan)
+        extract_with_text_layer(backend, scanned, "en", "auto")
+        assert backend.page_counts == [3, 3]
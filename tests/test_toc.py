@@ -0,0 +1,79 @@
+"""
+Test cases for detecting the table of contents in OCR output
+"""
+
+from textbook.content import chunk_content, store_chunks
+from textbook.library import add_upload
+from textbook.toc import analyze_toc, document_toc, find_toc
+from textbook.utils.toc_detection import markdown_toc_features, parse_toc_entries
+
+TOC_PAGE = """# Contents
+
+Preface ........ vii
+1 Groups ........ 1
+1.1 Subgroups ........ 4
+1.2 Cosets . . . . . 9
+Chapter 2 Rings 15
+2.1 Ideals  21
+3 Fields ..... 30
+Index 99"""
+TOC_CONTINUED = """3.1 Extensions ........ 33
+3.2 Splitting fields ........ 40
+3.3 Finite fields ........ 44
+4 Galois theory ........ 47
+4.1 The fundamental theorem ........ 52
+4.2 Solvable groups ........ 58
+4.3 Solving by radicals ........ 63
+5 Modules ........ 70"""
+BODY_PAGE = """# 1 Groups
+
+A group is a set with an associative operation. In 1830 Galois used them.
+Let $G$ be a group with 12 elements."""
+
+
+def _content_list(pages):
+    return [{"type": "text", "text": line, "page_idx": page_idx} for page_idx, page in enumerate(pages) for line in page.split("\n") if line]
+
+
+class TestToc:
+    """Test suite for the table of contents features, entries and stored analysis"""
+
+    def test_features(self):
+        """Test that the contents heading, chapter lines and dotted leaders are counted"""
+        assert markdown_toc_features(TOC_PAGE) == ({"has_contents_heading": True}, {"chapter_line_count": 6, "dotted_leader_count": 4})
+        assert markdown_toc_features(BODY_PAGE) == ({"has_contents_heading": False}, {"chapter_line_count": 0, "dotted_leader_count": 0})
+
+    def test_entries(self):
+        """Test that entries are parsed with their page numbers and levels, skipping roman page numbers"""
+        assert parse_toc_entries(TOC_PAGE) == [
+            {"title": "1 Groups", "page_number": 1, "level": 1},
+            {"title": "1.1 Subgroups", "page_number": 4, "level": 2},
+            {"title": "1.2 Cosets", "page_number": 9, "level": 2},
+            {"title": "Chapter 2 Rings", "page_number": 15, "level": 1},
+            {"title": "2.1 Ideals", "page_number": 21, "level": 2},
+            {"title": "3 Fields", "page_number": 30, "level": 1},
+            {"title": "Index", "page_number": 99, "level": 1},
+        ]
+
+    def test_toc_pages(self):
+        """Test that the table of contents runs from its first page until the next page that is not part of it"""
+        assert find_toc([BODY_PAGE, TOC_PAGE, TOC_CONTINUED, BODY_PAGE, TOC_PAGE]) == [1, 2]
+        assert find_toc([BODY_PAGE, BODY_PAGE]) == []
+
+    def test_analysis_is_stored(self, database):
+        """Test that whether a document has a table of contents and its entries are stored with it"""
+        document = add_upload(database, "algebra", "a.pdf", 4)
+        assert analyze_toc(database, document.document_id) is None
+        store_chunks(database, document.document_id, chunk_content("", _content_list(["Algebra", TOC_PAGE, TOC_CONTINUED, BODY_PAGE]))[1])
+
+        analysis = analyze_toc(database, document.document_id)
+        assert analysis.has_toc
+        assert [entry["title"] for entry in analysis.entries][-2:] == ["4.3 Solving by radicals", "5 Modules"]
+        assert database.get_document(document.document_id).has_toc is True
+        assert document_toc(database, document.document_id) == analysis.entries
+
+        other = add_upload(database, "notes", "b.pdf", 1)
+        store_chunks(database, other.document_id, chunk_content("", _content_list([BODY_PAGE]))[1])
+        assert not analyze_toc(database, other.document_id).has_toc
+        assert database.get_document(other.document_id).has_toc is False
+        assert document_toc(database, other.document_id) == []
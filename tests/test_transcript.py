@@ -0,0 +1,146 @@
+"""
+Test cases for lecture transcript ingestion
+"""
+
+import pytest
+
+from textbook.transcript import (
+    Cue,
+    TranscriptError,
+    fetch_youtube_captions,
+    format_timestamp,
+    group_sections,
+    ingest_transcript,
+    parse_timestamp,
+    parse_transcript,
+    video_deep_link,
+    youtube_video_id,
+)
+
+SRT = """1
+00:00:01,000 --> 00:00:03,500
+Welcome to the lecture on <i>convex</i> sets.
+
+2
+00:00:03,500 --> 00:00:06,000
+Today we cover the definition
+and a few examples.
+"""
+
+ROLLING_VTT = """WEBVTT
+Kind: captions
+Language: en
+
+00:00:00.000 --> 00:00:02.000 align:start position:0%
+so today we are going<00:00:00.800><c> to</c>
+
+00:00:02.000 --> 00:00:04.000 align:start position:0%
+so today we are going to
+talk about convex sets
+
+00:00:04.000 --> 00:00:06.000 align:start position:0%
+talk about convex sets
+and why they matter
+"""
+
+PLAIN_TEXT = """0:00
+Welcome back everyone
+1:05 now the second part
+which continues here
+[1:02:03] and the last part
+"""
+
+
+class TestTranscriptParsing:
+    """Test suite for transcript parsing and normalization"""
+
+    def test_timestamps(self):
+        """Test parsing and formatting of timestamps"""
+        assert parse_timestamp("01:02:03,500") == pytest.approx(3723.5)
+        assert parse_timestamp("02:03.25") == pytest.approx(123.25)
+        assert format_timestamp(3723.9) == "01:02:03"
+
+    def test_parse_srt(self):
+        """Test that SRT cues keep their timing and lose their markup"""
+        cues = parse_transcript(SRT, "lecture.srt")
+        assert cues == [
+            Cue(1.0, 3.5, "Welcome to the lecture on convex sets."),
+            Cue(3.5, 6.0, "Today we cover the definition and a few examples."),
+        ]
+
+    def test_rolling_captions_are_deduplicated(self):
+        """Test that auto-generated captions do not repeat their previous line"""
+        cues = parse_transcript(ROLLING_VTT, "lecture.vtt")
+        assert [cue.text for cue in cues] == ["so today we are going to", "talk about convex sets", "and why they matter"]
+
+    def test_repeated_words_are_kept(self):
+        """Test that an ordinary repeated word across cues is not treated as a rolling duplicate"""
+        cues = parse_transcript("1\n00:00:01,000 --> 00:00:02,000\nthis is the\n\n2\n00:00:02,000 --> 00:00:03,000\nthe end\n")
+        assert [cue.text for cue in cues] == ["this is the", "the end"]
+
+    def test_parse_timestamped_text(self):
+        """Test plain text transcripts with timestamps on their own line or before the text"""
+        cues = parse_transcript(PLAIN_TEXT, "lecture.txt")
+        assert [(cue.start, cue.text) for cue in cues] == [
+            (0.0, "Welcome back everyone"),
+            (65.0, "now the second part which continues here"),
+            (3723.0, "and the last part"),
+        ]
+        assert cues[0].end == 65.0
+
+    def test_text_without_timestamps_is_rejected(self):
+        """Test that a file without any timing raises"""
+        with pytest.raises(TranscriptError):
+            parse_transcript("just some notes\nwithout times", "notes.txt")
+
+
+class TestTranscriptSegmentation:
+    """Test suite for sections, links and ingestion"""
+
+    @pytest.fixture
+    def cues(self):
+        return [Cue(index * 20.0, index * 20.0 + 20.0, f"This is sentence number {index}.") for index in range(45)]
+
+    def test_group_sections(self, cues):
+        """Test that sections cover roughly five minutes and paragraphs keep their start"""
+        sections = group_sections(cues)
+
+        assert [section.start for section in sections] == [0.0, 300.0, 600.0]
+        assert sections[-1].end == 900.0
+        assert sections[0].paragraphs[0] == (0.0, "This is sentence number 0. This is sentence number 1. This is sentence number 2.")
+        assert sections[1].title.startswith("[00:05:00] This is sentence number 15.")
+
+    def test_youtube_video_id(self):
+        """Test the URL shapes YouTube uses"""
+        assert youtube_video_id("https://www.youtube.com/watch?v=abc123&list=x") == "abc123"
+        assert youtube_video_id("https://youtu.be/abc123?t=4") == "abc123"
+        assert youtube_video_id("https://www.youtube.com/embed/abc123") == "abc123"
+        assert youtube_video_id("https://example.com/watch?v=abc123") is None
+
+    def test_video_deep_link(self):
+        """Test links into YouTube and other videos"""
+        assert video_deep_link("https://youtu.be/abc123", 125.7) == "https://www.youtube.com/watch?v=abc123&t=125s"
+        assert video_deep_link("https://cdn.example.com/lecture.mp4", 60) == "https://cdn.example.com/lecture.mp4#t=60"
+        assert video_deep_link(None, 60) is None
+        assert video_deep_link("https://youtu.be/abc123", None) is None
+
+    def test_ingest_transcript(self, tmpdir, cues):
+        """Test that chapters and pages are anchored to time ranges"""
+        transcript = ingest_transcript("Convexity lecture", cues, tmpdir / "lecture.pdf", tmpdir / "lecture.md", "https://youtu.be/abc123")
+
+        markdown = (tmpdir / "lecture.md").read_text(encoding="utf-8")
+        assert markdown.startswith("# Convexity lecture")
+        assert "## [00:05:00]" in markdown
+
+        titles = [chapter["title"] for chapter in transcript.toc["chapters"]]
+        assert len(titles) == 3
+        assert transcript.chapter_ranges[titles[1]] == (300.0, 600.0)
+
+        assert transcript.page_ranges[0][0] == 0.0
+        starts = [start for start, _ in transcript.page_ranges if start is not None]
+        assert starts == sorted(starts)
+
+    def test_captions_need_a_youtube_url(self):
+        """Test that caption fetching is only attempted for YouTube videos"""
+        with pytest.raises(TranscriptError):
+            fetch_youtube_captions("https://cdn.example.com/lecture.mp4")
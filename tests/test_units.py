@@ -0,0 +1,75 @@
+"""
+Test cases for numeric answers with units and tolerances
+"""
+import pytest
+
+from textbook.units import DIMENSIONLESS, NumericAnswer, check_numeric, dimension_text, parse_quantity, parse_unit, validate_numeric_answer
+
+
+class TestUnits:
+    """Test suite for parsing quantities and converting them to SI"""
+
+    def test_prefixed_and_derived_units(self):
+        """Test that prefixes, derived units, powers and division convert to SI"""
+        assert parse_unit("kJ")[0] == pytest.approx(1e3)
+        assert parse_unit("µm")[0] == parse_unit("um")[0] == pytest.approx(1e-6)
+        assert parse_unit("hPa")[0] == pytest.approx(100)
+        assert parse_unit("min")[0] == 60 and parse_unit("mmol")[0] == pytest.approx(1e-3)
+        assert dimension_text(parse_unit("J/(mol K)")[1]) == "m^2 kg s^-2 K^-1 mol^-1"
+        assert parse_unit("m/s^2")[1] == parse_unit("m s^-2")[1] == parse_unit("m·s⁻²")[1]
+        assert parse_unit("")[1] == DIMENSIONLESS
+
+    def test_division_applies_to_the_rest_of_the_unit(self):
+        """Test that the terms after "/" are all divided by, up to the closing parenthesis"""
+        joules_per_mole_kelvin = parse_unit("J/(mol K)")
+        for unit in ("J/mol K", "J/mol·K", "J/mol*K", "J mol^-1 K^-1"):
+            assert parse_unit(unit) == joules_per_mole_kelvin
+        assert parse_unit("W/m^2 K")[1] == parse_unit("W m^-2 K^-1")[1]
+        assert parse_unit("m/s/s")[1] == parse_unit("m/s^2")[1]
+        assert parse_unit("(J/mol) K")[1] == parse_unit("J K/mol")[1]
+
+    def test_quantities_are_read_as_written(self):
+        """Test that scientific notation, LaTeX and equations are read, and what is not a quantity is not"""
+        assert parse_quantity("3.0e8 m/s").value == pytest.approx(3e8)
+        assert parse_quantity("3.0 \\times 10^{8}\\,\\mathrm{m/s}").value == pytest.approx(3e8)
+        assert parse_quantity("$v = 12\\ \\mathrm{km/h}$").value == pytest.approx(12 / 3.6)
+        assert parse_quantity("2 eV").value == pytest.approx(3.204353268e-19)
+        for text in ("2x+2", "about twenty metres", "20 °C", "5 furlongs"):
+            assert parse_quantity(text) is None
+
+    def test_invalid_units_are_rejected(self):
+        """Test that unknown units, unbalanced parentheses and invalid tolerances raise ValueError"""
+        for unit in ("furlong", "m^", "(m", "m/"):
+            with pytest.raises(ValueError):
+                parse_unit(unit)
+        for answer in (NumericAnswer(float("nan"), "m"), NumericAnswer(1.0, "m", abs_tolerance=-1), NumericAnswer(1.0, "m", rel_tolerance=1.5)):
+            with pytest.raises(ValueError):
+                validate_numeric_answer(answer)
+
+
+class TestCheckNumeric:
+    """Test suite for grading answers against a numeric answer"""
+
+    def test_equivalent_units_are_correct(self):
+        """Test that any unit of the same dimension is converted before comparing within the tolerance"""
+        answer = NumericAnswer(2.4, "kJ", rel_tolerance=0.01)
+        for text in ("2400 J", "0.0024 MJ", "2.41 kJ", "2.4 \\mathrm{kJ}"):
+            assert check_numeric(text, answer)[0] is True
+        assert check_numeric("2.5 kJ", answer) == (False, "The unit is right but the value is not, check your calculation.")
+
+    def test_absolute_and_default_tolerances(self):
+        """Test that the absolute tolerance is in the unit of the answer, and 1% applies when none is set"""
+        answer = NumericAnswer(9.81, "m/s^2", abs_tolerance=0.05)
+        assert check_numeric("985 cm/s^2", answer)[0] is True
+        assert check_numeric("9.87 m/s^2", answer)[0] is False
+        assert check_numeric("9.9 m/s^2", NumericAnswer(9.81, "m/s^2"))[0] is True
+        assert check_numeric("10 m/s^2", NumericAnswer(9.81, "m/s^2"))[0] is False
+
+    def test_wrong_dimensions_are_wrong(self):
+        """Test that a missing unit or a unit of another dimension is wrong, with feedback saying so"""
+        answer = NumericAnswer(2.4, "kJ")
+        correct, feedback = check_numeric("2.4", answer)
+        assert not correct and "no unit" in feedback
+        correct, feedback = check_numeric("2.4 kW", answer)
+        assert not correct and "m^2 kg s^-3" in feedback
+        assert check_numeric("the energy is conserved", answer) is None
@@ -0,0 +1,53 @@
+"""
+Test cases for recording the tokens and cost of LLM calls
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.jobs import JobPool
+from textbook.usage import ModelPrice, UsageTracker, pricing_from_config, usage_summary
+
+
+class TestPricing:
+    """Test suite for the [pricing] section of config.toml"""
+
+    def test_pricing_from_config(self):
+        """Test that prices are read per model and invalid ones rejected"""
+        prices = pricing_from_config({"pricing": {"flash": {"input_per_million": 0.3, "output_per_million": 2.5}}})
+        assert prices == {"flash": ModelPrice(0.3, 2.5)}
+        assert prices["flash"].cost(1_000_000, 2_000_000) == pytest.approx(5.3)
+        assert pricing_from_config({}) == {}
+        with pytest.raises(ValueError):
+            pricing_from_config({"pricing": {"flash": {"input_per_million": -1}}})
+        with pytest.raises(ValueError):
+            pricing_from_config({"pricing": {"flash": {"per_call": 0.01}}})
+
+
+class TestUsage:
+    """Test suite for recording and summing up usage"""
+
+    def test_calls_are_recorded_per_job_and_document(self, database):
+        """Test that calls made from a job get its kind and document, and the cost of priced models"""
+        tracker = UsageTracker(database, {"flash": ModelPrice(1.0, 4.0)})
+        pool = JobPool(database)
+        pool.register("document_summary", lambda params, handle: {
+            "usage_ids": [tracker.record("flash", 1000, 500).usage_id, tracker.record("local", 200, 100, estimated=True).usage_id],
+        })
+        job = pool.submit("document_summary", {"document_id": 7})
+        pool.wait(job.job_id, timeout=5)
+        pool.shutdown()
+        tracker.record("flash", 10, 5)
+
+        summary = usage_summary(database, datetime.now(timezone.utc) - timedelta(days=1))
+        assert (summary.totals.calls, summary.totals.input_tokens, summary.totals.output_tokens) == (3, 1210, 605)
+        assert summary.totals.cost == pytest.approx((1010 * 1.0 + 505 * 4.0) / 1_000_000)
+        assert (summary.totals.unpriced_calls, summary.totals.estimated_calls) == (1, 1)
+        assert list(summary.documents) == [7] and summary.documents[7].calls == 2
+        [job_usage] = summary.jobs
+        assert (job_usage.job_id, job_usage.job_kind, job_usage.document_id) == (job.job_id, "document_summary", 7)
+        assert list(summary.days) == [datetime.now(timezone.utc).date()]
+        assert summary.models["local"].cost == 0 and summary.models["local"].unpriced_calls == 1
+
+        assert usage_summary(database, datetime.now(timezone.utc) - timedelta(days=1), job_id=job.job_id).totals.calls == 2
+        assert usage_summary(database, datetime.now(timezone.utc) + timedelta(days=1)).totals.calls == 0
@@ -0,0 +1,43 @@
+"""
+Test cases for priming caches and models on startup before the server reports ready
+"""
+
+from tests.conftest import FakeLLM
+from textbook.generation import generation_settings_from_config
+from textbook.jobs import JOB_KIND_ANSWER_CHECK, JOB_KIND_HINT_LADDER
+from textbook.json_repair import json_schema
+from textbook.profiles import TASKS, ModelRouter, ModelProfile
+from textbook.segmentation import OutlineSchema
+from textbook.warmup import Warmup, priming_steps
+
+
+class TestWarmup:
+    """Test suite for the startup priming"""
+
+    def test_steps_prime_schemas_database_and_models(self, database):
+        """Test that the server is ready once the schemas, library and task models were primed"""
+        settings = generation_settings_from_config({"generation": {JOB_KIND_ANSWER_CHECK: {"temperature": 0}, JOB_KIND_HINT_LADDER: {"temperature": 1.2}}}, TASKS)
+        profiles = {"pro": ModelProfile("pro", "pro-model")}
+        router = ModelRouter(FakeLLM(model_name="flash"), profiles, {}, lambda profile: FakeLLM(model_name=profile.model_name), settings)
+        warmup = Warmup(priming_steps(database, router))
+        assert not warmup.ready
+
+        warmup.start()
+        assert warmup.wait(timeout=5) and warmup.ready
+        reports = {report.name: report for report in warmup.reports()}
+        assert set(reports) == {"schemas", "database", "models"}
+        assert all(report.error is None for report in reports.values())
+        assert reports["models"].primed == 4  # Two tasks for each of the two profiles
+        assert reports["schemas"].primed > 0
+        assert json_schema.cache_info().currsize >= reports["schemas"].primed
+        assert json_schema(OutlineSchema) is json_schema(OutlineSchema)
+
+    def test_failing_step_does_not_block_readiness(self):
+        """Test that a failing step is reported and the server still becomes ready"""
+        def fail():
+            raise RuntimeError("disk I/O error")
+
+        warmup = Warmup([("database", fail), ("schemas", lambda: 3)])
+        warmup.run()
+        assert warmup.ready
+        assert [(report.name, report.primed, report.error) for report in warmup.reports()] == [("database", 0, "disk I/O error"), ("schemas", 3, None)]
@@ -0,0 +1,71 @@
+"""
+Test cases for fingerprinting exports and tracing leaked copies
+"""
+
+import pytest
+
+from textbook.database import ProblemInfo
+from textbook.problems import problem_set_markdown
+from textbook.watermark import (
+    EXPORT_PROBLEM_SET,
+    WATERMARK_METADATA,
+    WATERMARK_ZERO_WIDTH,
+    Watermark,
+    WatermarkSettings,
+    find_fingerprints,
+    issue_watermark,
+    trace_export,
+    watermark_settings_from_config,
+    zero_width,
+)
+
+PROBLEMS = [
+    ProblemInfo(problem_number="1", statement="Show that the identity is unique."),
+    ProblemInfo(problem_number=None, statement="Show that $e^{-1} = e$.\n\nDeduce the inverse of a product."),
+]
+
+
+class TestWatermark:
+    """Test suite for the fingerprints of exports"""
+
+    def test_settings_from_config(self):
+        """Test that watermarks are off by default and unknown modes rejected"""
+        assert watermark_settings_from_config({}) == WatermarkSettings()
+        assert watermark_settings_from_config({"watermark": {"mode": "zero-width", "instance_id": "physics-101"}}) == WatermarkSettings(WATERMARK_ZERO_WIDTH, "physics-101")
+        with pytest.raises(ValueError):
+            watermark_settings_from_config({"watermark": {"mode": "visible"}})
+        with pytest.raises(ValueError):
+            watermark_settings_from_config({"watermark": {"user": "alice"}})
+
+    def test_zero_width_fingerprint_survives_excerpts(self):
+        """Test that every paragraph carries the invisible fingerprint, so an excerpt is traced without the header"""
+        watermark = Watermark("0123456789abcdef", "physics-101", WATERMARK_ZERO_WIDTH)
+        markdown = problem_set_markdown("Groups", PROBLEMS, watermark)
+        assert markdown.startswith("<!-- instance: physics-101, fingerprint: 0123456789abcdef -->")
+        assert markdown.count(zero_width("0123456789abcdef")) == 3
+
+        excerpt = markdown.split("## Problem 2")[1].split("\n\n")[2]
+        assert excerpt.startswith("Deduce the inverse") and find_fingerprints(excerpt) == ["0123456789abcdef"]
+        assert find_fingerprints("Deduce the inverse of a product.") == []
+
+    def test_metadata_fingerprint_is_only_in_the_header(self):
+        """Test that metadata watermarks leave the text as it is"""
+        markdown = problem_set_markdown("Groups", PROBLEMS, Watermark("0123456789abcdef", "physics-101", WATERMARK_METADATA))
+        assert zero_width("0123456789abcdef") not in markdown
+        assert find_fingerprints(markdown) == ["0123456789abcdef"]
+        assert problem_set_markdown("Groups", PROBLEMS) == "# Groups\n\n## Problem 1\n\nShow that the identity is unique.\n\n## Problem 2\n\nShow that $e^{-1} = e$.\n\nDeduce the inverse of a product.\n"
+
+    def test_exports_are_traced(self, database):
+        """Test that each export gets its own fingerprint, traced back to its user, and none when disabled"""
+        settings = WatermarkSettings(WATERMARK_ZERO_WIDTH, "physics-101")
+        alice = issue_watermark(database, settings, "alice", EXPORT_PROBLEM_SET, 7)
+        bob = issue_watermark(database, settings, "bob", EXPORT_PROBLEM_SET, 7)
+        assert alice.fingerprint != bob.fingerprint
+
+        leaked = "Found on a forum: " + alice.text("Show that the identity is unique.")
+        [export] = trace_export(database, leaked)
+        assert (export.user_id, export.instance_id, export.export_kind, export.subject_id) == ("alice", "physics-101", EXPORT_PROBLEM_SET, 7)
+        assert trace_export(database, "Show that the identity is unique.") == []
+        assert trace_export(database, zero_width("fedcba9876543210")) == []
+
+        assert issue_watermark(database, WatermarkSettings(), "alice", EXPORT_PROBLEM_SET, 7) is None
@@ -0,0 +1,157 @@
+"""
+Test cases for web article ingestion
+"""
+
+import pymupdf
+import pytest
+
+from textbook import web_article
+from textbook.web_article import (
+    WebArticleError,
+    extract_article,
+    fetch_html,
+    headings_to_toc,
+    ingest_article,
+    markdown_to_html,
+)
+
+ARTICLE_URL = "https://example.com/posts/convex-sets"
+
+ARTICLE_HTML = """
+<html>
+<head>
+  <title>Convex Sets | Example Blog</title>
+  <meta name="author" content="Ada Lovelace">
+  <meta name="keywords" content="convexity, optimization">
+  <script>var tracking = true;</script>
+</head>
+<body>
+  <nav><a href="/">Home</a> <a href="/archive">Archive</a></nav>
+  <div class="sidebar-widget"><p>Subscribe to the newsletter to get every new post, straight to your inbox.</p></div>
+  <article class="post">
+    <h1>Convex Sets</h1>
+    <p>A set is <b>convex</b> if, for any two of its points, the segment between them stays inside, see <a href="/definitions">the definitions</a>.</p>
+    <h2>Examples</h2>
+    <p>Balls, half-spaces, and polyhedra are all convex, which follows directly from the definition.</p>
+    <ul><li>Euclidean ball</li><li>Half-space <code>a^T x &lt;= b</code></li></ul>
+    <h3>Non-examples</h3>
+    <p>A crescent is not convex, because some segments between its points leave it.</p>
+    <pre><code>def is_convex(points):
+
+    return True</code></pre>
+    <h2>Operations</h2>
+    <blockquote><p>Intersections of convex sets are convex, for any number of sets.</p></blockquote>
+  </article>
+  <div class="comments"><p>Great post, thanks for writing it, I learned a lot from reading this!</p></div>
+  <footer>(c) Example Blog</footer>
+</body>
+</html>
+"""
+
+
+class TestWebArticle:
+    """Test suite for web_article"""
+
+    def test_extract_keeps_main_content(self):
+        """Test that the article body is kept and boilerplate is dropped"""
+        article = extract_article(ARTICLE_HTML, ARTICLE_URL)
+
+        assert article.title == "Convex Sets"
+        assert article.byline == "Ada Lovelace"
+        assert article.keywords == "convexity, optimization"
+        assert "segment between them stays inside" in article.markdown
+        assert "Subscribe" not in article.markdown
+        assert "Great post" not in article.markdown
+        assert "Archive" not in article.markdown
+        assert "tracking" not in article.markdown
+
+    def test_extract_converts_to_markdown(self):
+        """Test headings, emphasis, links, lists and code blocks"""
+        markdown = extract_article(ARTICLE_HTML, ARTICLE_URL).markdown
+
+        assert markdown.startswith("# Convex Sets\n")
+        assert "## Examples" in markdown
+        assert "### Non-examples" in markdown
+        assert "**convex**" in markdown
+        assert "[the definitions](https://example.com/definitions)" in markdown
+        assert "- Euclidean ball" in markdown
+        assert "`a^T x <= b`" in markdown
+        assert "```\ndef is_convex(points):\n\n    return True\n```" in markdown
+        assert "> Intersections of convex sets are convex" in markdown
+
+    def test_headings_are_collected_without_title(self):
+        """Test that the opening heading becomes the title rather than a chapter"""
+        article = extract_article(ARTICLE_HTML, ARTICLE_URL)
+        assert article.headings == [(2, "Examples"), (3, "Non-examples"), (2, "Operations")]
+
+    def test_markdown_to_html_keeps_code_block_together(self):
+        """Test that blank lines inside fenced code do not split the block"""
+        html = markdown_to_html("# Title\n\n```\na\n\nb\n```\n\ntext")
+        assert "<pre>a\n\nb</pre>" in html
+        assert "<p>text</p>" in html
+
+    def test_headings_to_toc(self):
+        """Test that the shallowest level becomes chapters and the next one sections"""
+        toc = headings_to_toc("Convex Sets", [(2, "Examples"), (3, "Non-examples"), (2, "Operations")], [1, 1, 2])
+
+        assert [chapter["title"] for chapter in toc["chapters"]] == ["Convex Sets", "Examples", "Operations"]
+        assert toc["chapters"][1]["sections"] == [{"index_string": "1.1", "title": "Non-examples", "page_number": 1}]
+
+    def test_headings_to_toc_without_headings(self):
+        """Test that an article without headings is a single chapter"""
+        toc = headings_to_toc("Short note", [], [])
+        assert toc == {"chapters": [{"index_string": "1", "title": "Short note", "page_number": 0, "sections": []}]}
+
+    def test_duplicate_headings_are_made_unique(self):
+        """Test that repeated headings do not collapse into one chapter"""
+        toc = headings_to_toc("Notes", [(2, "Summary"), (2, "Summary")], [0, 0])
+        assert [chapter["title"] for chapter in toc["chapters"]] == ["Summary", "Summary (2)"]
+
+    def test_ingest_article_writes_markdown_and_pdf(self, tmpdir):
+        """Test the full pipeline with a stubbed fetch"""
+        pdf_path = tmpdir / "article.pdf"
+        markdown_path = tmpdir / "article.md"
+
+        article, toc = ingest_article(ARTICLE_URL, pdf_path, markdown_path, fetch=lambda url: (ARTICLE_HTML, url))
+
+        assert markdown_path.read_text(encoding="utf-8") == article.markdown
+        with pymupdf.open(pdf_path) as document:
+            assert len(document) >= 1
+            assert "Convex Sets" in document[0].get_text()
+        assert [chapter["title"] for chapter in toc["chapters"]] == ["Examples", "Operations"]
+
+    def test_empty_page_is_rejected(self):
+        """Test that a page without readable content raises"""
+        with pytest.raises(WebArticleError):
+            extract_article("<html><body><nav>menu</nav></body></html>", ARTICLE_URL)
+
+    @pytest.mark.parametrize("url", ["file:///etc/passwd", "ftp://example.com/a", "http://localhost/admin", "http://127.0.0.1:8000/books"])
+    def test_non_public_urls_are_rejected(self, url):
+        """Test that only public http(s) URLs are fetched"""
+        with pytest.raises(WebArticleError):
+            fetch_html(url)
+
+    def test_redirects_to_private_addresses_are_not_followed(self, monkeypatch):
+        """Test that every redirect hop is checked before it is requested, and hosts are connected to by the checked address"""
+        addresses = {"example.com": "93.184.216.34", "169.254.169.254": "169.254.169.254"}
+        monkeypatch.setattr(web_article.socket, "getaddrinfo", lambda host, port: [(None, None, None, "", (addresses[host], 0))])
+        requested = []
+
+        class Redirect:
+            status_code = 302
+            headers = {"Location": "http://169.254.169.254/latest/meta-data/"}
+
+            def __enter__(self):
+                return self
+
+            def __exit__(self, *exc):
+                return False
+
+        def get(session, url, **kwargs):
+            requested.append((url, kwargs["headers"]["Host"], kwargs["allow_redirects"]))
+            return Redirect()
+
+        monkeypatch.setattr(web_article.requests.Session, "get", get)
+        with pytest.raises(WebArticleError, match="non-public"):
+            fetch_html("http://example.com/posts/convex-sets")
+        assert requested == [("http://93.184.216.34/posts/convex-sets", "example.com", False)]
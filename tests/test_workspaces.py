@@ -0,0 +1,112 @@
+"""
+Test cases for instructor workspaces: the roster, assignments and analytics
+"""
+from datetime import datetime, timedelta, timezone
+
+import pytest
+
+from textbook.database import FlashcardInfo, GradingJudgmentInfo
+from textbook.grading import JUDGMENT_INITIAL
+from textbook.reading_queue import record_reading
+from textbook.review import GRADE_AGAIN, GRADE_GOOD, ReviewGrade, submit_reviews
+from textbook.workspaces import (
+    ASSIGNMENT_QUIZ,
+    ASSIGNMENT_READING,
+    ROLE_INSTRUCTOR,
+    add_member,
+    assign,
+    create_workspace,
+    require_member,
+    student_activity,
+    topic_analytics,
+)
+
+
+class TestWorkspaces:
+    """Test suite for workspaces"""
+
+    @pytest.fixture
+    def book_id(self, database):
+        book = database.create_book("analysis", "me", "sequences", "analysis", 20)
+        chapter = database.try_create_chapter_info(book.book_id, "Sequences", "1", 1, 10)
+        database.try_create_section_info(book.book_id, chapter, "Limits", "1.1", 1, 5)
+        database.try_create_section_info(book.book_id, chapter, "Cauchy sequences", "1.2", 6, 10)
+        series = database.try_create_chapter_info(book.book_id, "Series", "2", 11, 20)
+        database.try_create_section_info(book.book_id, series, "Convergence tests", "2.1", 11, 20)
+        database.create_exercise(book.book_id, 4, "Show that 1/n converges")
+        return book.book_id
+
+    @pytest.fixture
+    def workspace_id(self, database):
+        workspace = create_workspace(database, "Analysis 101", "prof")
+        for student in ("alice", "bob", "carol"):
+            add_member(database, workspace.workspace_id, student)
+        return workspace.workspace_id
+
+    def test_roster_and_roles(self, database, workspace_id):
+        """Test that only members reach a workspace and only instructors manage it"""
+        assert require_member(database, workspace_id, "prof", ROLE_INSTRUCTOR).role == ROLE_INSTRUCTOR
+        assert [member.user_id for member in database.get_workspace_members(workspace_id)] == ["prof", "alice", "bob", "carol"]
+        assert [workspace.name for workspace in database.get_workspaces("alice")] == ["Analysis 101"]
+        with pytest.raises(PermissionError):
+            require_member(database, workspace_id, "alice", ROLE_INSTRUCTOR)
+        with pytest.raises(PermissionError):
+            require_member(database, workspace_id, "mallory")
+        with pytest.raises(ValueError):
+            require_member(database, workspace_id + 1, "prof")
+        with pytest.raises(ValueError):
+            add_member(database, workspace_id, "alice")
+
+    def test_assignments_are_delivered(self, database, book_id, workspace_id):
+        """Test that readings and quizzes reach every student, including those added later, and not the instructor"""
+        chapter = database.get_chapters_by_book_id(book_id)[0]
+        assign(database, workspace_id, ASSIGNMENT_READING, book_id, chapter.chapter_id)
+        assign(database, workspace_id, ASSIGNMENT_QUIZ, book_id, chapter.chapter_id)
+        add_member(database, workspace_id, "dave")
+
+        for student in ("alice", "dave"):
+            assert [item.title for item in database.get_reading_items(student, book_id)] == ["Limits", "Cauchy sequences"]
+            assert database.get_chapter_quiz_by_chapter(student, chapter.chapter_id) is not None
+        assert database.get_reading_items("prof", book_id) == []
+        # The whole book adds only the sections not queued yet
+        assign(database, workspace_id, ASSIGNMENT_READING, book_id)
+        assert [item.title for item in database.get_reading_items("alice", book_id)] == ["Limits", "Cauchy sequences", "Convergence tests"]
+        with pytest.raises(ValueError):
+            assign(database, workspace_id, ASSIGNMENT_QUIZ, book_id)
+
+    def test_analytics_withhold_small_groups(self, database, book_id, workspace_id):
+        """Test that counts are reported but rates over fewer than three students are withheld"""
+        chapter = database.get_chapters_by_book_id(book_id)[0]
+        assign(database, workspace_id, ASSIGNMENT_READING, book_id, chapter.chapter_id)
+        for item in database.get_reading_items("alice", book_id):
+            record_reading(database, item.reading_id, done=True)
+        card_ids = database.create_flashcards(book_id, [FlashcardInfo(front="Limit of 1/n", back="0", chapter_id=chapter.chapter_id)])
+        now = datetime.now(timezone.utc)
+        for student, grade in (("alice", GRADE_GOOD), ("bob", GRADE_AGAIN)):
+            submit_reviews(database, student, [ReviewGrade(card_ids[0], GRADE_GOOD)], now=now)
+            submit_reviews(database, student, [ReviewGrade(card_ids[0], grade)], now=now + timedelta(days=1))
+
+        [topic] = topic_analytics(database, workspace_id)
+        assert (topic.title, topic.students, topic.read, topic.reviewers) == ("Sequences", 3, 1, 2)
+        assert topic.retention is None
+
+        submit_reviews(database, "carol", [ReviewGrade(card_ids[0], GRADE_GOOD)], now=now)
+        submit_reviews(database, "carol", [ReviewGrade(card_ids[0], GRADE_GOOD)], now=now + timedelta(days=1))
+        [topic] = topic_analytics(database, workspace_id)
+        assert topic.reviewers == 3 and topic.retention == pytest.approx(2 / 3)
+
+    def test_activity_needs_sharing(self, database, book_id, workspace_id):
+        """Test that a student's activity is only shown once they share it"""
+        chapter = database.get_chapters_by_book_id(book_id)[0]
+        assign(database, workspace_id, ASSIGNMENT_QUIZ, book_id, chapter.chapter_id)
+        with pytest.raises(PermissionError):
+            student_activity(database, workspace_id, "alice")
+
+        database.update_workspace_member(workspace_id, "alice", share_attempts=True)
+        [exercise] = database.get_exercises_by_book_id(book_id)
+        database.create_attempt(exercise.exercise_id, "Converges", [GradingJudgmentInfo(model_name="test", reason=JUDGMENT_INITIAL, score=1.0, is_correct=True, confidence=0.9, feedback="")])
+        activity = student_activity(database, workspace_id, "alice")
+        assert [progress.quiz.chapter_id for progress in activity.quizzes] == [chapter.chapter_id]
+        assert activity.readings == [] and activity.review_grades == {}
+        with pytest.raises(PermissionError):
+            student_activity(database, workspace_id, "prof")
@@ -0,0 +1,123 @@
+# Administrative commands, run with `uv run python -m textbook.admin <command>`
+#
+#   verify            check recorded digests, missing files and inconsistent rows
+#   verify --repair   additionally regenerate the recoverable issues
+#   create-token      create a scoped API token for a user, e.g. the first admin token when tokens are required
+#   compress          compress the text and OCR markdown stored before compression was enabled, then vacuum
+
+import argparse
+import os
+import sys
+import tomllib
+from pathlib import Path
+from warnings import warn
+
+from textbook.compression import compression_settings_from_config, configure_compression, read_markdown, write_markdown
+from textbook.database import TextBookDatabase
+from textbook.integrity import verify_integrity, repair_issues
+from textbook.api_tokens import create_token, SCOPES
+
+
+def load_config() -> dict:
+    if not os.path.exists("config.toml"):
+        warn("config.toml file not found, using default config")
+        return {}
+
+    try:
+        with open("config.toml", "rb") as f:
+            return tomllib.load(f)
+    except tomllib.TOMLDecodeError as e:
+        warn(f"Error loading config.toml file: {e}")
+        return {}
+
+
+def verify(database: TextBookDatabase, uploads_dir: str, repair: bool) -> int:
+    issues = verify_integrity(database, uploads_dir)
+    if not issues:
+        print("No integrity issues found")
+        return 0
+
+    for issue in issues:
+        marker = "recoverable" if issue.recoverable else "manual"
+        book = f"book {issue.book_id}" if issue.book_id is not None else "no book"
+        print(f"[{issue.kind}] ({marker}) {book} {issue.path or ''}: {issue.detail}")
+
+    recoverable = [issue for issue in issues if issue.recoverable]
+    if not repair:
+        if recoverable:
+            print(f"{len(recoverable)} of {len(issues)} issues can be repaired, rerun with --repair")
+        return 1
+
+    repaired = repair_issues(database, uploads_dir, issues)
+    print(f"Repaired {len(repaired)} of {len(issues)} issues")
+    return 0 if len(repaired) == len(issues) else 1
+
+
+def create_api_token(database: TextBookDatabase, user_id: str, name: str, scopes: list[str]) -> int:
+    try:
+        info, token = create_token(database, user_id, name, scopes)
+    except ValueError as e:
+        print(e)
+        return 1
+    print(f"Created token {info.token_id} for {user_id} with scopes {info.scopes}, it is not shown again:")
+    print(token)
+    return 0
+
+
+def compress(database: TextBookDatabase, uploads_dir: str) -> int:
+    written = database.compress_stored_text()
+    for table, rows in written.items():
+        print(f"{table}: {rows} rows compressed")
+
+    files = 0
+    for document in database.get_documents():
+        if not document.source_path or not document.source_path.endswith(".pdf"):
+            continue
+        markdown_path = (Path(uploads_dir) / document.source_path).with_suffix(".md")
+        if not markdown_path.exists():
+            continue
+        markdown = read_markdown(markdown_path)
+        if markdown is not None and write_markdown(markdown_path, markdown) != markdown_path:
+            files += 1
+    print(f"{files} markdown files compressed")
+
+    size = os.path.getsize(database.db_path)
+    database.vacuum()
+    print(f"Database vacuumed from {size} to {os.path.getsize(database.db_path)} bytes")
+    return 0
+
+
+def main(argv=None) -> int:
+    config = load_config()
+
+    parser = argparse.ArgumentParser(prog="textbook.admin", description="Administrative commands")
+    parser.add_argument("--db-path", default=config.get("db_path", "textbook_context.db"), help="Path to the database file")
+    parser.add_argument("--uploads-dir", default=config.get("uploads_dir", "uploads"), help="Directory holding uploaded files")
+    subparsers = parser.add_subparsers(dest="command", required=True)
+
+    verify_parser = subparsers.add_parser("verify", help="Verify stored artifacts against their recorded digests")
+    verify_parser.add_argument("--repair", action="store_true", help="Regenerate recoverable artifacts")
+
+    token_parser = subparsers.add_parser("create-token", help="Create a scoped API token for a user")
+    token_parser.add_argument("user_id", help="User the token acts for")
+    token_parser.add_argument("name", help="What the token is for")
+    token_parser.add_argument("--scope", action="append", required=True, choices=SCOPES, help="Scope to grant, repeat for several")
+
+    subparsers.add_parser("compress", help="Compress the text and OCR markdown stored before compression was enabled")
+
+    args = parser.parse_args(argv)
+    configure_compression(compression_settings_from_config(config))
+
+    with TextBookDatabase(db_path=args.db_path) as database:
+        if args.command == "verify":
+            return verify(database, args.uploads_dir, args.repair)
+        if args.command == "create-token":
+            return create_api_token(database, args.user_id, args.name, args.scope)
+        if args.command == "compress":
+            return compress(database, args.uploads_dir)
+
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,169 @@
+# Analytics export
+# Dumps the study history for offline analysis in notebooks, either as one Parquet file per table or as a single
+# DuckDB database. The tables are the attempts, the reviews and the problems (exercises) as stored, voided attempts
+# and undone reviews included with their timestamps, plus the study sessions derived from them: activity with less
+# than SESSION_GAP_MINUTES between two answers counts as one session. Attempts are not kept per user, they count
+# towards the sessions of DEFAULT_USER_ID. The study event stream is exported as is, for analyses to replay.
+# Binary columns (embeddings, related chapters) are left out.
+
+from dataclasses import dataclass
+from datetime import datetime, timedelta
+from pathlib import Path
+from typing import Callable, List, Optional, Tuple
+
+from sqlalchemy import Boolean, DateTime, Float, Integer, LargeBinary
+
+from textbook.database import AttemptInfo, ExerciseInfo, ReviewInfo, StudyEventInfo, TextBookDatabase
+from textbook.jobs import CancellationToken
+from textbook.preferences import DEFAULT_USER_ID
+from textbook.timestamps import as_utc
+
+EXPORT_FORMAT_PARQUET = "parquet"
+EXPORT_FORMAT_DUCKDB = "duckdb"
+EXPORT_FORMATS = (EXPORT_FORMAT_PARQUET, EXPORT_FORMAT_DUCKDB)
+DUCKDB_FILE_NAME = "analytics.duckdb"
+SESSION_GAP_MINUTES = 30
+
+SESSION_COLUMNS = [
+    ("session_number", "BIGINT"),
+    ("user_id", "VARCHAR"),
+    ("started_at", "TIMESTAMP"),
+    ("ended_at", "TIMESTAMP"),
+    ("review_count", "BIGINT"),
+    ("attempt_count", "BIGINT"),
+]
+
+# Kinds of study activity a session is made of
+ACTIVITY_REVIEW = "review"
+ACTIVITY_ATTEMPT = "attempt"
+
+
+@dataclass
+class ExportTable:
+    name: str
+    columns: List[Tuple[str, str]]  # (name, DuckDB type)
+    rows: List[tuple]
+
+
+def _duckdb_type(column_type) -> Optional[str]:
+    if isinstance(column_type, LargeBinary):
+        return None
+    if isinstance(column_type, Boolean):
+        return "BOOLEAN"
+    if isinstance(column_type, Integer):
+        return "BIGINT"
+    if isinstance(column_type, Float):
+        return "DOUBLE"
+    if isinstance(column_type, DateTime):
+        return "TIMESTAMP"
+    return "VARCHAR"
+
+
+def _model_table(name: str, model, records) -> ExportTable:
+    columns = [(column.name, _duckdb_type(column.type)) for column in model.__table__.columns]
+    columns = [(column_name, column_type) for column_name, column_type in columns if column_type is not None]
+    rows = [tuple(getattr(record, column_name) for column_name, _ in columns) for record in records]
+    return ExportTable(name, columns, rows)
+
+
+def study_sessions(activity: List[Tuple[str, datetime, str]], gap_minutes: int = SESSION_GAP_MINUTES) -> List[tuple]:
+    """
+    Group study activity into sessions per user
+
+    Args:
+        activity: (user_id, moment, kind) of every review and attempt, kind being ACTIVITY_REVIEW or ACTIVITY_ATTEMPT
+        gap_minutes: A longer pause between two answers starts a new session
+
+    Returns:
+        List[tuple]: Rows of SESSION_COLUMNS, by user and start
+    """
+    sessions = []
+    gap = timedelta(minutes=gap_minutes)
+    for user_id, moment, kind in sorted(activity, key=lambda entry: (entry[0], as_utc(entry[1]))):
+        moment = as_utc(moment)
+        last = sessions[-1] if sessions else None
+        if last is None or last[1] != user_id or moment - last[3] > gap:
+            last = [len(sessions) + 1, user_id, moment, moment, 0, 0]
+            sessions.append(last)
+        last[3] = moment
+        last[4 if kind == ACTIVITY_REVIEW else 5] += 1
+    return [tuple(session) for session in sessions]
+
+
+def collect_tables(database: TextBookDatabase) -> List[ExportTable]:
+    """The attempts, reviews, problems, study events and derived study sessions to export"""
+    with database.new_session() as session:
+        attempts = session.query(AttemptInfo).order_by(AttemptInfo.attempt_id).all()
+        reviews = session.query(ReviewInfo).order_by(ReviewInfo.review_id).all()
+        problems = session.query(ExerciseInfo).order_by(ExerciseInfo.exercise_id).all()
+        events = session.query(StudyEventInfo).order_by(StudyEventInfo.event_id).all()
+
+        activity = [(review.user_id, review.reviewed_at, ACTIVITY_REVIEW) for review in reviews if review.undone_at is None]
+        activity.extend((DEFAULT_USER_ID, attempt.created_at, ACTIVITY_ATTEMPT) for attempt in attempts if attempt.voided_at is None)
+        return [
+            _model_table("attempts", AttemptInfo, attempts),
+            _model_table("reviews", ReviewInfo, reviews),
+            _model_table("problems", ExerciseInfo, problems),
+            _model_table("events", StudyEventInfo, events),
+            ExportTable("sessions", SESSION_COLUMNS, study_sessions(activity)),
+        ]
+
+
+def _naive_utc(value):
+    # DuckDB TIMESTAMP columns hold naive UTC times
+    return as_utc(value).replace(tzinfo=None) if isinstance(value, datetime) else value
+
+
+def write_export(
+    tables: List[ExportTable],
+    output_dir: Path,
+    export_format: str,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+) -> List[str]:
+    """
+    Write tables as Parquet files or into a DuckDB database, checking the cancellation token before each table
+
+    Args:
+        report_progress: Gets the stage, percentage and detail before each table, like JobHandle.report_progress
+
+    Returns:
+        List[str]: The names of the files written in output_dir
+    """
+    if export_format not in EXPORT_FORMATS:
+        raise ValueError(f"Unsupported export format: {export_format}, expected one of {', '.join(EXPORT_FORMATS)}")
+    import duckdb
+
+    output_dir.mkdir(parents=True, exist_ok=True)
+    database_path = output_dir / DUCKDB_FILE_NAME if export_format == EXPORT_FORMAT_DUCKDB else ":memory:"
+    connection = duckdb.connect(str(database_path))
+    try:
+        file_names = [DUCKDB_FILE_NAME] if export_format == EXPORT_FORMAT_DUCKDB else []
+        for index, table in enumerate(tables):
+            if cancellation_token is not None:
+                cancellation_token.raise_if_cancelled()
+            if report_progress is not None:
+                report_progress("export", 100 * index / len(tables), f"table {index + 1}/{len(tables)} {table.name}")
+            connection.execute(f"CREATE TABLE {table.name} ({', '.join(f'{name} {column_type}' for name, column_type in table.columns)})")
+            if table.rows:
+                placeholders = ", ".join("?" for _ in table.columns)
+                connection.executemany(f"INSERT INTO {table.name} VALUES ({placeholders})", [[_naive_utc(value) for value in row] for row in table.rows])
+            if export_format == EXPORT_FORMAT_PARQUET:
+                file_name = f"{table.name}.parquet"
+                parquet_path = str(output_dir / file_name).replace("'", "''")
+                connection.execute(f"COPY {table.name} TO '{parquet_path}' (FORMAT PARQUET)")
+                file_names.append(file_name)
+        return file_names
+    finally:
+        connection.close()
+
+
+def export_analytics(
+    database: TextBookDatabase,
+    output_dir: Path,
+    export_format: str = EXPORT_FORMAT_PARQUET,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+) -> List[str]:
+    """Export the study history for offline analysis, returns the names of the files written in output_dir"""
+    return write_export(collect_tables(database), output_dir, export_format, cancellation_token, report_progress)
@@ -0,0 +1,123 @@
+# Answer checking
+# A learner's answer to a problem is compared with the problem's stored reference solution by the LLM, in a job. The
+# answer has to match the verdict schema: correct, partially_correct or wrong, with feedback on what is right and
+# what is missing. Verdicts are validated with the response, spelled-out ones like "Partially correct" are read as
+# their value and anything else is rejected, so a prompted backend is asked to repair it. Problems without a
+# reference solution cannot be checked, their solution is generated first. An answer that is an expression equivalent
+# to the final answer, "2(x+1)" for "2x+2", is correct without prompting (see equivalence). Problems with a numeric
+# answer, a value with a unit and a tolerance, have answers that are a number with a unit decided by arithmetic, with
+# or without a solution (see units). Problems with a chemical structure have answers that are a formula, or SMILES,
+# compared in canonical form (see chemistry).
+
+from typing import Callable, Literal, Optional
+
+from pydantic import BaseModel, field_validator
+
+from textbook.chemistry import check_structure
+from textbook.database import NumericAnswerInfo, ProblemInfo, SolutionInfo, TextBookDatabase
+from textbook.equivalence import answers_equivalent
+from textbook.jobs import CancellationToken
+from textbook.model import LLM
+from textbook.solutions import solution_steps
+from textbook.units import NumericAnswer, check_numeric
+
+VERDICT_CORRECT = "correct"
+VERDICT_PARTIALLY_CORRECT = "partially_correct"
+VERDICT_WRONG = "wrong"
+VERDICTS = (VERDICT_CORRECT, VERDICT_PARTIALLY_CORRECT, VERDICT_WRONG)
+MAX_ANSWER_CHARS = 20000
+
+
+class AnswerCheckSchema(BaseModel):
+    verdict: Literal["correct", "partially_correct", "wrong"]
+    feedback: str
+
+    @field_validator("verdict", mode="before")
+    @classmethod
+    def _normalize_verdict(cls, value):
+        # Models write "Partially correct" or "partially-correct" as often as the value itself
+        return "_".join(value.lower().replace("-", " ").split()) if isinstance(value, str) else value
+
+
+def answer_check_prompt(problem: ProblemInfo, solution: SolutionInfo, answer: str) -> str:
+    steps = "\n".join(f"{number}. {step}" for number, step in enumerate(solution_steps(solution), start=1))
+    return f"""
+    Check a learner's answer to the following problem against the reference solution with rules:
+    - verdict is correct if the answer reaches the final answer with valid reasoning, partially_correct if it has the right approach or part of the result but errors or gaps, wrong otherwise
+    - an answer may take another route than the reference solution, judge whether its own reasoning is valid
+    - feedback speaks to the learner in 1 to 4 sentences: what is right, then the first mistake or missing step, without giving the rest of the solution away
+    - use LaTeX for mathematics
+
+    Problem {problem.problem_number or ""}:
+     {problem.statement}
+
+    Reference solution:
+     {steps}
+    Final answer: {solution.final_answer}
+
+    Learner's answer:
+     {answer}
+    """
+
+
+def numeric_answer(numeric_answer: NumericAnswerInfo) -> NumericAnswer:
+    return NumericAnswer(numeric_answer.value, numeric_answer.unit, numeric_answer.abs_tolerance, numeric_answer.rel_tolerance)
+
+
+def deterministic_verdict(database: TextBookDatabase, problem_id: int, answer: str) -> Optional[AnswerCheckSchema]:
+    """
+    The verdict on an answer decided without the LLM: a number with a unit against the problem's numeric answer, a
+    formula or SMILES against its chemical structure
+
+    Returns:
+        Optional[AnswerCheckSchema]: None when the problem has neither or the answer is not comparable with them
+    """
+    stored = database.get_numeric_answer(problem_id)
+    checked = check_numeric(answer, numeric_answer(stored)) if stored is not None else None
+    if checked is None:
+        problem = database.get_problem(problem_id)
+        if problem is not None and (problem.chemical_formula or problem.smiles):
+            checked = check_structure(answer, problem.chemical_formula, problem.smiles)
+    if checked is None:
+        return None
+    correct, feedback = checked
+    return AnswerCheckSchema(verdict=VERDICT_CORRECT if correct else VERDICT_WRONG, feedback=feedback)
+
+
+def check_answer(
+    database: TextBookDatabase,
+    llm: LLM,
+    problem_id: int,
+    answer: str,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+) -> AnswerCheckSchema:
+    """
+    Compare an answer with the reference solution of a problem
+
+    Raises:
+        ValueError: If the problem does not exist, has no solution yet or the answer is empty or too long
+    """
+    answer = answer.strip()
+    if not answer:
+        raise ValueError("The answer is empty")
+    if len(answer) > MAX_ANSWER_CHARS:
+        raise ValueError(f"The answer is longer than {MAX_ANSWER_CHARS} characters")
+    problem = database.get_problem(problem_id)
+    if problem is None:
+        raise ValueError(f"Problem not found: {problem_id}")
+    deterministic = deterministic_verdict(database, problem_id, answer)
+    if deterministic is not None:
+        return deterministic
+    solution = database.get_solution(problem_id)
+    if solution is None:
+        raise ValueError(f"No solution generated for problem {problem_id}, generate one to check answers against")
+    if solution.final_answer and answers_equivalent(answer, solution.final_answer):
+        return AnswerCheckSchema(verdict=VERDICT_CORRECT, feedback=f"Correct, your answer is equivalent to ${solution.final_answer.strip().strip('$')}$.")
+    if report_progress is not None:
+        report_progress("check", 0, f"Checking the answer to problem {problem.problem_number or problem_id}")
+
+    verdict = llm.prompt_with_schema(answer_check_prompt(problem, solution, answer), schema=AnswerCheckSchema)
+    if cancellation_token is not None:
+        cancellation_token.raise_if_cancelled()
+    return AnswerCheckSchema(verdict=verdict.verdict, feedback=verdict.feedback.strip())
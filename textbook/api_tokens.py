@@ -0,0 +1,100 @@
+# Scoped API tokens
+# Scripts and integrations, like a status bar widget showing the due cards, authenticate with a bearer token that
+# acts for one user and grants only some scopes: read:stats for the review, mastery and queue statistics,
+# write:review to record reviews and readings, and admin for everything else. Only the SHA-256 digest of a token is
+# stored, the token itself is shown once when it is created. A token without the admin scope may only name its own
+# user in the path, query or JSON body of a request, requests that name no user in the query act for the token's user.
+# Requests without a token keep working as before unless the server is configured to require tokens.
+
+import hashlib
+import re
+import secrets
+from datetime import datetime, timezone
+from typing import List, Optional, Tuple
+
+from textbook.database import ApiTokenInfo, TextBookDatabase
+
+SCOPE_READ_STATS = "read:stats"
+SCOPE_WRITE_REVIEW = "write:review"
+SCOPE_ADMIN = "admin"  # Grants every scope
+SCOPES = (SCOPE_READ_STATS, SCOPE_WRITE_REVIEW, SCOPE_ADMIN)
+
+TOKEN_PREFIX = "pbs_"
+
+# (method, path pattern, scope) of the routes narrower scopes grant, every other route needs admin
+ROUTE_SCOPES: List[Tuple[str, str, str]] = [
+    ("GET", r"/review/batch", SCOPE_READ_STATS),
+    ("GET", r"/review/forecast", SCOPE_READ_STATS),
+    ("POST", r"/review/simulate", SCOPE_READ_STATS),
+    ("GET", r"/books/\d+/mastery", SCOPE_READ_STATS),
+    ("GET", r"/books/\d+/quiz-timing", SCOPE_READ_STATS),
+    ("GET", r"/reading/queue", SCOPE_READ_STATS),
+    ("GET", r"/study/queue", SCOPE_READ_STATS),
+    ("GET", r"/users/[^/]+/chapter-quizzes", SCOPE_READ_STATS),
+    ("GET", r"/chapter-quizzes/\d+", SCOPE_READ_STATS),
+    ("GET", r"/users/[^/]+/study-plan", SCOPE_READ_STATS),
+    ("GET", r"/users/[^/]+/digest", SCOPE_READ_STATS),
+    ("GET", r"/users/[^/]+/syllabus", SCOPE_READ_STATS),
+    ("GET", r"/users/[^/]+/filters(/\d+(/exercises)?)?", SCOPE_READ_STATS),
+    ("GET", r"/events/activity", SCOPE_READ_STATS),
+    ("GET", r"/workspaces", SCOPE_READ_STATS),
+    ("GET", r"/workspaces/\d+/assignments", SCOPE_READ_STATS),
+    ("GET", r"/grading/metrics", SCOPE_READ_STATS),
+    ("POST", r"/review/batch", SCOPE_WRITE_REVIEW),
+    ("POST", r"/review/\d+/undo", SCOPE_WRITE_REVIEW),
+    ("POST", r"/reading/\d+/read", SCOPE_WRITE_REVIEW),
+]
+PUBLIC_ROUTES = (("GET", "/"), ("GET", "/health"), ("GET", "/ready"), ("GET", "/capabilities"))
+
+
+def token_digest(token: str) -> str:
+    return hashlib.sha256(token.encode("utf-8")).hexdigest()
+
+
+def parse_scopes(scopes: List[str]) -> List[str]:
+    """Deduplicated scopes, raises ValueError for unknown or missing ones"""
+    scopes = list(dict.fromkeys(scope.strip() for scope in scopes if scope.strip()))
+    if not scopes:
+        raise ValueError("At least one scope is required")
+    unknown = [scope for scope in scopes if scope not in SCOPES]
+    if unknown:
+        raise ValueError(f"Unknown scopes: {', '.join(unknown)}, expected some of {', '.join(SCOPES)}")
+    return scopes
+
+
+def token_scopes(token: ApiTokenInfo) -> List[str]:
+    return token.scopes.split()
+
+
+def has_scope(token: ApiTokenInfo, scope: str) -> bool:
+    scopes = token_scopes(token)
+    return SCOPE_ADMIN in scopes or scope in scopes
+
+
+def required_scope(method: str, path: str) -> Optional[str]:
+    """The scope a request needs, None for public routes"""
+    path = path.rstrip("/") or "/"
+    if (method.upper(), path) in PUBLIC_ROUTES:
+        return None
+    for route_method, pattern, scope in ROUTE_SCOPES:
+        if route_method == method.upper() and re.fullmatch(pattern, path):
+            return scope
+    return SCOPE_ADMIN
+
+
+def create_token(database: TextBookDatabase, user_id: str, name: str, scopes: List[str]) -> Tuple[ApiTokenInfo, str]:
+    """Create a token for a user, returns its record and the token itself, which is not stored"""
+    if not name.strip():
+        raise ValueError("A token needs a name")
+    token = TOKEN_PREFIX + secrets.token_urlsafe(32)
+    info = database.create_api_token(ApiTokenInfo(user_id=user_id, name=name.strip(), scopes=" ".join(parse_scopes(scopes)), token_sha256=token_digest(token)))
+    return info, token
+
+
+def authenticate(database: TextBookDatabase, token: str, now: Optional[datetime] = None) -> Optional[ApiTokenInfo]:
+    """The valid token record of a bearer token, None if it is unknown or revoked"""
+    info = database.get_api_token_by_digest(token_digest(token))
+    if info is None or info.revoked_at is not None:
+        return None
+    database.touch_api_token(info.token_id, now or datetime.now(timezone.utc))
+    return info
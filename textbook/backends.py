@@ -0,0 +1,148 @@
+# LLM backends
+# Models are reached through the llm package. The backend serving them is chosen in the [llm] section of config.toml,
+# by default the LLM_PROVIDER environment variable (gemini), with the model LLM_MODEL_NAME otherwise:
+#   [llm]
+#   backend = "ollama"
+#   model = "llama3.1"
+#   base_url = "http://gpu-box:11434/v1"   # for backends served somewhere else than their usual address
+# Most backends are the models of an llm plugin, which has to be installed (`llm install llm-anthropic`). The others
+# speak the OpenAI API and are reached with llm's OpenAI client at their base URL; Azure OpenAI needs the base_url of
+# its resource, an `api_version` and the deployment as the model. Local backends (Ollama, LM Studio) need no API key,
+# the others read theirs from the environment variables they are known by, see credentials.
+# Run against a local server, no prompt or page of a document leaves the machine, which matters for copyrighted
+# textbooks. The base URL can also be set with LLM_BASE_URL, and self-hosted OpenAI-compatible servers (vLLM,
+# llama.cpp) are used without a key with `require_api_key = false`:
+#   [llm]
+#   backend = "openai"
+#   model = "qwen2.5-7b-instruct"
+#   base_url = "http://localhost:8000/v1"
+#   require_api_key = false
+
+import ipaddress
+from dataclasses import dataclass
+from typing import Dict, Optional, Tuple
+from urllib.parse import urlparse
+
+
+@dataclass(frozen=True)
+class Backend:
+    name: str
+    env_vars: Tuple[str, ...]  # The variables its key is usually found in, the first set wins
+    plugin: Optional[str] = None  # The llm plugin providing its models, None for OpenAI-compatible backends
+    base_url: Optional[str] = None  # Of OpenAI-compatible backends, None when every server has its own
+    requires_key: bool = True
+    default_model: Optional[str] = None
+    embedding_model: Optional[str] = None
+
+    @property
+    def openai_compatible(self) -> bool:
+        return self.plugin is None and self.name != OPENAI
+
+
+GEMINI = "gemini"
+OPENAI = "openai"
+AZURE_OPENAI = "azure_openai"
+
+BACKENDS: Dict[str, Backend] = {backend.name: backend for backend in (
+    Backend(GEMINI, ("LLM_GEMINI_KEY", "GEMINI_API_KEY", "GOOGLE_API_KEY"), "llm-gemini", default_model="gemini-3-flash-preview", embedding_model="gemini-embedding-001"),
+    # Built into llm
+    Backend(OPENAI, ("OPENAI_API_KEY",), default_model="gpt-4o-mini", embedding_model="text-embedding-3-small"),
+    Backend("anthropic", ("ANTHROPIC_API_KEY",), "llm-anthropic"),
+    Backend("mistral", ("MISTRAL_API_KEY", "LLM_MISTRAL_KEY"), "llm-mistral", embedding_model="mistral-embed"),
+    Backend("groq", ("GROQ_API_KEY", "LLM_GROQ_KEY"), "llm-groq"),
+    Backend("cohere", ("COHERE_API_KEY", "LLM_COHERE_KEY"), "llm-command-r"),
+    Backend("perplexity", ("PERPLEXITY_API_KEY", "LLM_PERPLEXITY_KEY"), "llm-perplexity"),
+    Backend("openrouter", ("OPENROUTER_API_KEY", "OPENROUTER_KEY"), base_url="https://openrouter.ai/api/v1"),
+    Backend("deepseek", ("DEEPSEEK_API_KEY",), base_url="https://api.deepseek.com"),
+    Backend("xai", ("XAI_API_KEY",), base_url="https://api.x.ai/v1"),
+    Backend("together", ("TOGETHER_API_KEY",), base_url="https://api.together.xyz/v1"),
+    Backend("fireworks", ("FIREWORKS_API_KEY",), base_url="https://api.fireworks.ai/inference/v1"),
+    Backend(AZURE_OPENAI, ("AZURE_OPENAI_API_KEY",)),
+    Backend("ollama", (), base_url="http://localhost:11434/v1", requires_key=False),
+    Backend("lmstudio", (), base_url="http://localhost:1234/v1", requires_key=False),
+)}
+
+
+@dataclass
+class BackendSettings:
+    backend: str
+    model: Optional[str] = None  # The default model, by default LLM_MODEL_NAME or the backend's
+    base_url: Optional[str] = None  # Overrides the backend's, OpenAI-compatible backends only
+    api_version: Optional[str] = None  # Azure OpenAI only
+    require_api_key: Optional[bool] = None  # Whether prompting needs a key, by default whether the backend does
+
+    @property
+    def spec(self) -> Backend:
+        return BACKENDS[self.backend]
+
+    @property
+    def requires_key(self) -> bool:
+        return self.spec.requires_key if self.require_api_key is None else self.require_api_key
+
+    @property
+    def url(self) -> Optional[str]:
+        """The address prompts are sent to, None for plugin backends whose plugin knows it"""
+        return self.base_url or self.spec.base_url
+
+
+def get_backend(name: str) -> Backend:
+    """A backend by name, raises ValueError for unknown ones"""
+    backend = BACKENDS.get(name)
+    if backend is None:
+        raise ValueError(f"Unsupported LLM backend: {name}, expected one of {', '.join(BACKENDS)}")
+    return backend
+
+
+def backend_settings_from_config(config: dict, default_backend: Optional[str] = None, default_base_url: Optional[str] = None) -> BackendSettings:
+    """
+    The backend of the [llm] section of config.toml
+
+    Args:
+        default_backend: The backend without one in config.toml, e.g. of LLM_PROVIDER
+        default_base_url: The base URL without one in config.toml, e.g. of LLM_BASE_URL
+
+    Raises:
+        ValueError: For invalid settings
+    """
+    section = config.get("llm", {})
+    unknown = set(section) - {"backend", "model", "base_url", "api_version", "require_api_key"}
+    if unknown:
+        raise ValueError(f"Unknown llm settings: {', '.join(sorted(unknown))}")
+    for name in ("backend", "model", "base_url", "api_version"):
+        if name in section and (not isinstance(section[name], str) or not section[name].strip()):
+            raise ValueError(f"llm.{name} must be a non-empty string")
+    if not isinstance(section.get("require_api_key", True), bool):
+        raise ValueError("llm.require_api_key must be true or false")
+    backend = get_backend(section.get("backend", default_backend or GEMINI).strip())
+    base_url = section.get("base_url", default_base_url)
+    settings = BackendSettings(
+        backend=backend.name,
+        model=section["model"].strip() if "model" in section else None,
+        base_url=base_url.strip() if base_url and base_url.strip() else None,
+        api_version=section["api_version"].strip() if "api_version" in section else None,
+        require_api_key=section.get("require_api_key"),
+    )
+    if settings.base_url and backend.plugin is not None:
+        raise ValueError(f"llm.base_url is only supported by OpenAI-compatible backends, {backend.name} is served by the {backend.plugin} plugin")
+    if backend.name == AZURE_OPENAI and not (settings.base_url and settings.api_version):
+        raise ValueError(f"The {AZURE_OPENAI} backend needs the base_url of the resource and an api_version in [llm]")
+    if settings.api_version and backend.name != AZURE_OPENAI:
+        raise ValueError(f"llm.api_version is only supported by the {AZURE_OPENAI} backend")
+    if settings.require_api_key is False and not (backend.openai_compatible or settings.base_url):
+        raise ValueError(f"{backend.name} always needs an API key, only servers at a base_url can be used without one")
+    return settings
+
+
+def is_local(settings: BackendSettings) -> bool:
+    """Whether prompts go to a server on this machine or a private network rather than a cloud API"""
+    url = settings.url if settings.spec.openai_compatible or settings.base_url else None
+    host = urlparse(url).hostname if url else None
+    if not host:
+        return False
+    if host == "localhost" or host.endswith(".local"):
+        return True
+    try:
+        address = ipaddress.ip_address(host)
+    except ValueError:
+        return False
+    return address.is_loopback or address.is_private
@@ -0,0 +1,454 @@
+# Portable document bundles
+# A bundle is a zip archive holding a versioned manifest.json and the source PDF of a single book.
+# The manifest carries everything derived from the book (TOC, page summaries, exercises and optionally
+# embeddings) so that a book can be moved between instances without re-running the LLM pipeline.
+# With watermarks enabled the manifest names the exporting instance and the export's fingerprint, and in zero-width
+# mode the exercise texts carry it too (see watermark).
+
+import base64
+import hashlib
+import io
+import json
+import os
+import uuid
+import zipfile
+from datetime import datetime, timezone
+from pathlib import Path
+from typing import Optional, List, Dict, Union
+
+from pydantic import BaseModel, ValidationError
+
+from textbook.database import (
+    TextBookDatabase,
+    BookInfo,
+    ChapterInfo,
+    SectionInfo,
+    PageInfo,
+    ExerciseInfo,
+    ExerciseDetails,
+)
+from textbook.integrity import SOURCE_PDF_ARTIFACT
+from textbook.watermark import Watermark
+
+BUNDLE_FORMAT_VERSION = 1
+SUPPORTED_BUNDLE_FORMAT_VERSIONS = (1,)
+MANIFEST_FILE_NAME = "manifest.json"
+SOURCE_PDF_FILE_NAME = "source.pdf"
+
+
+class BundleError(ValueError):
+    """Raised when a bundle cannot be exported or is rejected on import"""
+    pass
+
+
+# Pydantic models for the manifest
+class BundleSourcePdf(BaseModel):
+    file_name: str
+    original_file_name: Optional[str] = None
+    sha256: str
+    size: int
+
+
+class BundleBook(BaseModel):
+    book_name: Optional[str] = None
+    book_author: Optional[str] = None
+    book_pages: Optional[int] = None
+    book_keywords: Optional[str] = None
+    book_summary: Optional[str] = None
+    book_toc_end_page: Optional[int] = None
+    book_alignment_offset: Optional[int] = None
+    book_ingestion_mode: Optional[str] = None
+    book_source_type: Optional[str] = None
+    book_source_url: Optional[str] = None
+    book_embedding: Optional[str] = None  # base64, only when embeddings are included
+
+
+class BundleChapter(BaseModel):
+    chapter_id: int  # id on the exporting instance, only used to link sections and exercises
+    title: str
+    start_page_number: int
+    end_page_number: Optional[int] = None
+    summary: Optional[str] = None
+    book_index_string: Optional[str] = None
+    start_seconds: Optional[float] = None
+    end_seconds: Optional[float] = None
+
+
+class BundleSection(BaseModel):
+    section_id: int  # id on the exporting instance, only used to link exercises
+    chapter_id: Optional[int] = None
+    title: str
+    start_page_number: int
+    end_page_number: int
+    summary: Optional[str] = None
+    book_index_string: Optional[str] = None
+
+
+class BundlePage(BaseModel):
+    page_number: int
+    summary: Optional[str] = None
+    transcription: Optional[str] = None
+    transcription_confidence: Optional[float] = None
+    transcription_source: Optional[str] = None
+    start_seconds: Optional[float] = None
+    end_seconds: Optional[float] = None
+    embedding: Optional[str] = None  # base64, only when embeddings are included
+
+
+class BundleExercise(BaseModel):
+    exercise_description: str
+    page_number: int
+    embedding: Optional[str] = None  # base64, only when embeddings are included
+    exercise_type: Optional[str] = None
+    starter_code: Optional[str] = None
+    solution_code: Optional[str] = None
+    code_language: Optional[str] = None
+    study_guide: Optional[str] = None
+    estimated_time_to_complete: Optional[int] = None
+    difficulty_level: Optional[int] = None
+    chapter_id: Optional[int] = None
+    section_id: Optional[int] = None
+
+
+class BundleManifest(BaseModel):
+    format_version: int
+    exported_at: str
+    includes_embeddings: bool = False
+    instance_id: Optional[str] = None  # Of the exporting instance, with the fingerprint of watermarked exports
+    fingerprint: Optional[str] = None
+    source_pdf: BundleSourcePdf
+    book: BundleBook
+    chapters: List[BundleChapter] = []
+    sections: List[BundleSection] = []
+    pages: List[BundlePage] = []
+    exercises: List[BundleExercise] = []
+
+
+def _encode_blob(blob: Optional[bytes], include: bool) -> Optional[str]:
+    if not include or blob is None:
+        return None
+    return base64.b64encode(blob).decode("ascii")
+
+
+def _decode_blob(blob: Optional[str]) -> Optional[bytes]:
+    if blob is None:
+        return None
+    return base64.b64decode(blob)
+
+
+def _optional_int(value) -> Optional[int]:
+    # exercise_details stores chapter/section references in string columns
+    if value is None or value == "":
+        return None
+    return int(value)
+
+
+def build_manifest(
+    database: TextBookDatabase,
+    book_id: int,
+    pdf_bytes: bytes,
+    include_embeddings: bool = False,
+    watermark: Optional[Watermark] = None,
+) -> BundleManifest:
+    """Collect all rows belonging to a book into a bundle manifest"""
+    def marked(text: Optional[str]) -> Optional[str]:
+        return watermark.text(text) if watermark is not None and text is not None else text
+
+    with database.new_session() as session:
+        book = session.query(BookInfo).filter(BookInfo.book_id == book_id).first()
+        if book is None:
+            raise BundleError(f"Book not found: {book_id}")
+
+        chapters = session.query(ChapterInfo).filter(ChapterInfo.book_id == book_id).order_by(ChapterInfo.start_page_number).all()
+        sections = session.query(SectionInfo).filter(SectionInfo.book_id == book_id).order_by(SectionInfo.start_page_number).all()
+        pages = session.query(PageInfo).filter(PageInfo.book_id == book_id).order_by(PageInfo.page_number).all()
+        exercises = session.query(ExerciseInfo).filter(ExerciseInfo.book_id == book_id).order_by(ExerciseInfo.page_number).all()
+
+        return BundleManifest(
+            format_version=BUNDLE_FORMAT_VERSION,
+            exported_at=datetime.now(timezone.utc).isoformat(),
+            includes_embeddings=include_embeddings,
+            instance_id=watermark.instance_id if watermark else None,
+            fingerprint=watermark.fingerprint if watermark else None,
+            source_pdf=BundleSourcePdf(
+                file_name=SOURCE_PDF_FILE_NAME,
+                original_file_name=book.book_file_name,
+                sha256=hashlib.sha256(pdf_bytes).hexdigest(),
+                size=len(pdf_bytes),
+            ),
+            book=BundleBook(
+                book_name=book.book_name,
+                book_author=book.book_author,
+                book_pages=book.book_pages,
+                book_keywords=book.book_keywords,
+                book_summary=book.book_summary,
+                book_toc_end_page=book.book_toc_end_page,
+                book_alignment_offset=book.book_alignment_offset,
+                book_ingestion_mode=book.book_ingestion_mode,
+                book_source_type=book.book_source_type,
+                book_source_url=book.book_source_url,
+                book_embedding=_encode_blob(book.book_embedding, include_embeddings),
+            ),
+            chapters=[
+                BundleChapter(
+                    chapter_id=chapter.chapter_id,
+                    title=chapter.title,
+                    start_page_number=chapter.start_page_number,
+                    end_page_number=chapter.end_page_number,
+                    summary=chapter.summary,
+                    book_index_string=chapter.book_index_string,
+                    start_seconds=chapter.start_seconds,
+                    end_seconds=chapter.end_seconds,
+                )
+                for chapter in chapters
+            ],
+            sections=[
+                BundleSection(
+                    section_id=section.section_id,
+                    chapter_id=section.chapter_id,
+                    title=section.title,
+                    start_page_number=section.start_page_number,
+                    end_page_number=section.end_page_number,
+                    summary=section.summary,
+                    book_index_string=section.book_index_string,
+                )
+                for section in sections
+            ],
+            pages=[
+                BundlePage(
+                    page_number=page.page_number,
+                    summary=page.summary,
+                    transcription=page.transcription,
+                    transcription_confidence=page.transcription_confidence,
+                    transcription_source=page.transcription_source,
+                    start_seconds=page.start_seconds,
+                    end_seconds=page.end_seconds,
+                    embedding=_encode_blob(page.embedding, include_embeddings),
+                )
+                for page in pages
+            ],
+            exercises=[
+                BundleExercise(
+                    exercise_description=marked(exercise.exercise_description),
+                    page_number=exercise.page_number,
+                    embedding=_encode_blob(exercise.embedding, include_embeddings),
+                    exercise_type=exercise.exercise_type,
+                    starter_code=exercise.starter_code,
+                    solution_code=exercise.solution_code,
+                    code_language=exercise.code_language,
+                    study_guide=marked(exercise.details.study_guide) if exercise.details else None,
+                    estimated_time_to_complete=exercise.details.estimated_time_to_complete if exercise.details else None,
+                    difficulty_level=exercise.details.difficulty_level if exercise.details else None,
+                    chapter_id=_optional_int(exercise.details.chapter_id) if exercise.details else None,
+                    section_id=_optional_int(exercise.details.section_id) if exercise.details else None,
+                )
+                for exercise in exercises
+            ],
+        )
+
+
+def export_book_bundle(
+    database: TextBookDatabase,
+    book_id: int,
+    pdf_path: Path,
+    include_embeddings: bool = False,
+    watermark: Optional[Watermark] = None,
+) -> bytes:
+    """
+    Export a book as a zip bundle
+
+    Args:
+        database: The database holding the book
+        book_id: The ID of the book to export
+        pdf_path: Path to the source PDF of the book
+        include_embeddings: Whether to include embedding blobs in the manifest
+        watermark: The fingerprint of the export, None for an unmarked export
+
+    Returns:
+        bytes: The zip archive
+    """
+    if not os.path.exists(pdf_path):
+        raise BundleError(f"PDF file not found: {pdf_path}")
+
+    with open(pdf_path, "rb") as f:
+        pdf_bytes = f.read()
+
+    manifest = build_manifest(database, book_id, pdf_bytes, include_embeddings, watermark)
+
+    buffer = io.BytesIO()
+    with zipfile.ZipFile(buffer, "w", compression=zipfile.ZIP_DEFLATED) as archive:
+        archive.writestr(MANIFEST_FILE_NAME, manifest.model_dump_json(indent=2))
+        archive.writestr(SOURCE_PDF_FILE_NAME, pdf_bytes)
+    return buffer.getvalue()
+
+
+def load_manifest(raw: Union[str, bytes]) -> BundleManifest:
+    """Parse and validate a bundle manifest, rejecting unsupported format versions"""
+    try:
+        data = json.loads(raw)
+    except json.JSONDecodeError as e:
+        raise BundleError(f"Manifest is not valid JSON: {e}")
+
+    if not isinstance(data, dict):
+        raise BundleError("Manifest must be a JSON object")
+
+    format_version = data.get("format_version")
+    if format_version not in SUPPORTED_BUNDLE_FORMAT_VERSIONS:
+        raise BundleError(
+            f"Unsupported bundle format version: {format_version} (supported: {', '.join(str(v) for v in SUPPORTED_BUNDLE_FORMAT_VERSIONS)})"
+        )
+
+    try:
+        return BundleManifest.model_validate(data)
+    except ValidationError as e:
+        raise BundleError(f"Invalid bundle manifest: {e}")
+
+
+def read_bundle(bundle: bytes) -> tuple[BundleManifest, bytes]:
+    """Open a bundle archive and return its validated manifest and source PDF"""
+    try:
+        archive = zipfile.ZipFile(io.BytesIO(bundle))
+    except zipfile.BadZipFile:
+        raise BundleError("Bundle is not a valid zip archive")
+
+    with archive:
+        names = archive.namelist()
+        if MANIFEST_FILE_NAME not in names:
+            raise BundleError(f"Bundle is missing {MANIFEST_FILE_NAME}")
+
+        manifest = load_manifest(archive.read(MANIFEST_FILE_NAME))
+
+        if manifest.source_pdf.file_name not in names:
+            raise BundleError(f"Bundle is missing the source PDF: {manifest.source_pdf.file_name}")
+        pdf_bytes = archive.read(manifest.source_pdf.file_name)
+
+    if hashlib.sha256(pdf_bytes).hexdigest() != manifest.source_pdf.sha256:
+        raise BundleError("Source PDF checksum does not match the manifest")
+
+    return manifest, pdf_bytes
+
+
+def import_book_bundle(database: TextBookDatabase, bundle: bytes, uploads_dir: str) -> int:
+    """
+    Import a zip bundle as a new book
+
+    Args:
+        database: The database to import into
+        bundle: The zip archive produced by export_book_bundle
+        uploads_dir: Directory the source PDF is written to
+
+    Returns:
+        int: The ID of the newly created book
+    """
+    manifest, pdf_bytes = read_bundle(bundle)
+
+    book_file_name = str(uuid.uuid4())
+    pdf_path = Path(uploads_dir) / f"{book_file_name}.pdf"
+    with open(pdf_path, "wb") as f:
+        f.write(pdf_bytes)
+
+    try:
+        with database.new_session() as session:
+            book = BookInfo(
+                book_name=manifest.book.book_name,
+                book_author=manifest.book.book_author,
+                book_pages=manifest.book.book_pages,
+                book_keywords=manifest.book.book_keywords,
+                book_summary=manifest.book.book_summary,
+                book_toc_end_page=manifest.book.book_toc_end_page,
+                book_alignment_offset=manifest.book.book_alignment_offset,
+                book_ingestion_mode=manifest.book.book_ingestion_mode,
+                book_source_type=manifest.book.book_source_type,
+                book_source_url=manifest.book.book_source_url,
+                book_embedding=_decode_blob(manifest.book.book_embedding),
+                book_file_name=book_file_name,
+            )
+            session.add(book)
+            session.flush()
+
+            # Ids in the manifest belong to the exporting instance, remap them to the new rows
+            chapter_ids: Dict[int, int] = {}
+            for chapter in manifest.chapters:
+                chapter_info = ChapterInfo(
+                    title=chapter.title,
+                    start_page_number=chapter.start_page_number,
+                    end_page_number=chapter.end_page_number,
+                    summary=chapter.summary,
+                    book_index_string=chapter.book_index_string,
+                    start_seconds=chapter.start_seconds,
+                    end_seconds=chapter.end_seconds,
+                    book_id=book.book_id,
+                )
+                session.add(chapter_info)
+                session.flush()
+                chapter_ids[chapter.chapter_id] = chapter_info.chapter_id
+
+            section_ids: Dict[int, int] = {}
+            for section in manifest.sections:
+                section_info = SectionInfo(
+                    title=section.title,
+                    start_page_number=section.start_page_number,
+                    end_page_number=section.end_page_number,
+                    summary=section.summary,
+                    chapter_id=chapter_ids.get(section.chapter_id) if section.chapter_id is not None else None,
+                    book_index_string=section.book_index_string,
+                    book_id=book.book_id,
+                )
+                session.add(section_info)
+                session.flush()
+                section_ids[section.section_id] = section_info.section_id
+
+            page_ids: Dict[int, int] = {}
+            for page in manifest.pages:
+                page_info = PageInfo(
+                    page_number=page.page_number,
+                    summary=page.summary,
+                    transcription=page.transcription,
+                    transcription_confidence=page.transcription_confidence,
+                    transcription_source=page.transcription_source,
+                    start_seconds=page.start_seconds,
+                    end_seconds=page.end_seconds,
+                    embedding=_decode_blob(page.embedding),
+                    book_id=book.book_id,
+                )
+                session.add(page_info)
+                session.flush()
+                page_ids[page.page_number] = page_info.page_id
+
+            for exercise in manifest.exercises:
+                exercise_info = ExerciseInfo(
+                    exercise_description=exercise.exercise_description,
+                    page_number=exercise.page_number,
+                    page_id=page_ids.get(exercise.page_number),
+                    embedding=_decode_blob(exercise.embedding),
+                    exercise_type=exercise.exercise_type,
+                    starter_code=exercise.starter_code,
+                    solution_code=exercise.solution_code,
+                    code_language=exercise.code_language,
+                    book_id=book.book_id,
+                )
+                session.add(exercise_info)
+                session.flush()
+
+                if any(value is not None for value in (exercise.study_guide, exercise.estimated_time_to_complete, exercise.difficulty_level, exercise.chapter_id, exercise.section_id)):
+                    chapter_id = chapter_ids.get(exercise.chapter_id) if exercise.chapter_id is not None else None
+                    section_id = section_ids.get(exercise.section_id) if exercise.section_id is not None else None
+                    session.add(ExerciseDetails(
+                        exercise_id=exercise_info.exercise_id,
+                        study_guide=exercise.study_guide,
+                        estimated_time_to_complete=exercise.estimated_time_to_complete,
+                        difficulty_level=exercise.difficulty_level,
+                        chapter_id=chapter_id,
+                        section_id=section_id,
+                        book_id=book.book_id,
+                    ))
+
+            session.commit()
+            book_id = book.book_id
+
+        database.register_artifact(book_id, SOURCE_PDF_ARTIFACT, pdf_path.name, manifest.source_pdf.sha256, len(pdf_bytes))
+        return book_id
+    except Exception:
+        if os.path.exists(pdf_path):
+            os.remove(pdf_path)
+        raise
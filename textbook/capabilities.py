@@ -0,0 +1,78 @@
+# Capabilities
+# Which optional subsystems of the server are enabled, so clients can hide what cannot work and the endpoints of a
+# disabled one answer 501 with how to enable it, rather than failing with a 500. A subsystem is enabled when what it
+# needs is configured (an LLM key, the OCR backend) and it is not turned off in the [features] section of config.toml:
+#   [features]
+#   grading = true     # grading attempts and self-explanations with the LLM
+#   embeddings = true  # the embedding model of the LLM provider
+#   mineru = true      # OCR of uploaded documents, with MinerU or the backend of the [ocr] section
+# Text to speech has no backend in this server yet, it is always reported disabled.
+
+from dataclasses import dataclass
+from typing import Dict, Optional
+
+FEATURE_LLM = "llm"
+FEATURE_EMBEDDINGS = "embeddings"
+FEATURE_GRADING = "grading"
+FEATURE_MINERU = "mineru"
+FEATURE_TTS = "tts"
+FEATURES = (FEATURE_LLM, FEATURE_EMBEDDINGS, FEATURE_GRADING, FEATURE_MINERU, FEATURE_TTS)
+CONFIGURABLE_FEATURES = (FEATURE_GRADING, FEATURE_EMBEDDINGS, FEATURE_MINERU)  # Can be turned off in [features]
+
+# How to enable each feature, by the reason it is disabled
+LLM_GUIDANCE = "Set the API key of the LLM backend in the environment of the server (LLM_GEMINI_KEY for gemini), or in the [credentials] section of config.toml, and restart it"
+TURNED_OFF_GUIDANCE = "Set {feature} = true in the [features] section of config.toml and restart the server"
+EMBEDDINGS_GUIDANCE = "Set LLM_EMBEDDING_MODEL_NAME to an embedding model of the LLM provider"
+TTS_GUIDANCE = "Text to speech is not available in this version of the server"
+
+
+@dataclass
+class Capability:
+    name: str
+    enabled: bool
+    guidance: Optional[str] = None  # How to enable it, None when enabled
+
+
+def feature_flags_from_config(config: dict) -> Dict[str, bool]:
+    """The features turned on or off in the [features] section of config.toml, raises ValueError for invalid ones"""
+    section = config.get("features", {})
+    unknown = set(section) - set(CONFIGURABLE_FEATURES)
+    if unknown:
+        raise ValueError(f"Unknown features: {', '.join(sorted(unknown))}, expected some of {', '.join(CONFIGURABLE_FEATURES)}")
+    for name, value in section.items():
+        if not isinstance(value, bool):
+            raise ValueError(f"features.{name} must be true or false, got {value!r}")
+    return {name: section.get(name, True) for name in CONFIGURABLE_FEATURES}
+
+
+def resolve_capabilities(
+    flags: Dict[str, bool],
+    llm_available: bool,
+    embedding_model_name: Optional[str],
+    ocr_unavailable: Optional[str],
+) -> Dict[str, Capability]:
+    """
+    Every feature with whether it is enabled and, if not, how to enable it
+
+    Args:
+        ocr_unavailable: How to make the OCR backend usable, None when it is
+    """
+
+    def capability(name: str, requirements: list) -> Capability:
+        # The first requirement missing is the one to fix first
+        for met, guidance in requirements:
+            if not met:
+                return Capability(name, False, guidance)
+        return Capability(name, True)
+
+    def turned_on(name: str):
+        return flags.get(name, True), TURNED_OFF_GUIDANCE.format(feature=name)
+
+    llm = (llm_available, LLM_GUIDANCE)
+    return {
+        FEATURE_LLM: capability(FEATURE_LLM, [llm]),
+        FEATURE_EMBEDDINGS: capability(FEATURE_EMBEDDINGS, [turned_on(FEATURE_EMBEDDINGS), llm, (bool(embedding_model_name), EMBEDDINGS_GUIDANCE)]),
+        FEATURE_GRADING: capability(FEATURE_GRADING, [turned_on(FEATURE_GRADING), llm]),
+        FEATURE_MINERU: capability(FEATURE_MINERU, [turned_on(FEATURE_MINERU), (ocr_unavailable is None, ocr_unavailable)]),
+        FEATURE_TTS: Capability(FEATURE_TTS, False, TTS_GUIDANCE),
+    }
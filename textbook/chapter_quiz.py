@@ -0,0 +1,118 @@
+# Chapter quizzes
+# A chapter is read once the user is done with the reading items of all its sections. Then a short diagnostic quiz
+# on the chapter alone is enqueued for the user, and pending quizzes are the user's notifications until answered.
+# The quiz asks the chapter's exercises never attempted first. Its answers are graded attempts like any other, so
+# they seed the mastery model: the chapter starts with a mastery and a review interval, and from then on regular
+# quizzes practice it as a weak chapter or one due for review. A chapter is quizzed once per user.
+# The quiz size is set in config.toml:
+#   chapter_quiz = true       # off with false
+#   chapter_quiz_size = 5     # exercises per quiz
+
+import json
+from dataclasses import dataclass
+from datetime import datetime, timezone
+from typing import Dict, List, Optional
+
+from textbook.database import ChapterQuizInfo, ReadingItemInfo, TextBookDatabase
+from textbook.quiz import QuizMix, TopicMastery, assemble_quiz, book_mastery
+from textbook.timestamps import as_utc
+
+DEFAULT_QUIZ_SIZE = 5
+MAX_QUIZ_SIZE = 20
+DIAGNOSTIC_MIX = QuizMix(1, 0, 0)  # The chapter read only
+
+
+@dataclass
+class ChapterQuizSettings:
+    enabled: bool = True
+    size: int = DEFAULT_QUIZ_SIZE  # Exercises per quiz
+
+
+@dataclass
+class ChapterQuizProgress:
+    quiz: ChapterQuizInfo
+    exercise_ids: List[int]
+    scores: Dict[int, float]  # Of the exercises answered since the quiz was enqueued, by exercise, the latest answer counts
+    mastery: Optional[TopicMastery]  # Of the chapter, seeded by the answers
+
+    @property
+    def score(self) -> Optional[float]:
+        """The mean score of the answers, None before the first"""
+        return sum(self.scores.values()) / len(self.scores) if self.scores else None
+
+
+def chapter_quiz_settings_from_config(config: dict) -> ChapterQuizSettings:
+    """The chapter quiz settings of config.toml, raises ValueError for invalid ones"""
+    settings = ChapterQuizSettings(
+        enabled=bool(config.get("chapter_quiz", True)),
+        size=config.get("chapter_quiz_size", DEFAULT_QUIZ_SIZE),
+    )
+    if not isinstance(settings.size, int) or not 1 <= settings.size <= MAX_QUIZ_SIZE:
+        raise ValueError(f"chapter_quiz_size must be between 1 and {MAX_QUIZ_SIZE}")
+    return settings
+
+
+def completed_chapter(database: TextBookDatabase, item: ReadingItemInfo) -> Optional[int]:
+    """The chapter of a section reading item when it is done and so are the items of the chapter's other sections"""
+    if item.done_at is None or item.section_id is None:
+        return None
+    sections = database.get_sections_by_book_id(item.book_id)
+    section = next((section for section in sections if section.section_id == item.section_id), None)
+    if section is None or section.chapter_id is None:
+        return None
+    done = {
+        reading.section_id
+        for reading in database.get_reading_items(item.user_id, item.book_id, include_done=True)
+        if reading.done_at is not None and reading.section_id is not None
+    }
+    chapter_sections = {other.section_id for other in sections if other.chapter_id == section.chapter_id}
+    return section.chapter_id if chapter_sections <= done else None
+
+
+def enqueue_chapter_quiz(database: TextBookDatabase, user_id: str, book_id: int, chapter_id: int, size: int = DEFAULT_QUIZ_SIZE) -> Optional[ChapterQuizInfo]:
+    """
+    Enqueue the diagnostic quiz on a chapter a user read
+
+    Returns:
+        Optional[ChapterQuizInfo]: The quiz, None when the chapter was quizzed before, is archived or has no exercises
+    """
+    if database.get_chapter_quiz_by_chapter(user_id, chapter_id) is not None:
+        return None
+    try:
+        # Without preferences no new items limit applies, a diagnostic quiz is mostly new exercises
+        items, _ = assemble_quiz(database, book_id, chapter_id, size, DIAGNOSTIC_MIX)
+    except ValueError:
+        return None
+    if not items:
+        return None
+    return database.create_chapter_quiz(ChapterQuizInfo(
+        user_id=user_id,
+        exercise_ids=json.dumps([item.exercise_id for item in items]),
+        chapter_id=chapter_id,
+        book_id=book_id,
+    ))
+
+
+def reading_done(database: TextBookDatabase, item: ReadingItemInfo, settings: Optional[ChapterQuizSettings] = None) -> Optional[ChapterQuizInfo]:
+    """Enqueue the quiz on the chapter a reading item completes, returns the quiz enqueued if any"""
+    settings = settings or ChapterQuizSettings()
+    if not settings.enabled:
+        return None
+    chapter_id = completed_chapter(database, item)
+    if chapter_id is None:
+        return None
+    return enqueue_chapter_quiz(database, item.user_id, item.book_id, chapter_id, settings.size)
+
+
+def chapter_quiz_progress(database: TextBookDatabase, quiz: ChapterQuizInfo, now: Optional[datetime] = None) -> ChapterQuizProgress:
+    """The answers of a quiz so far and the chapter's mastery, completing the quiz once every exercise is answered"""
+    exercise_ids = json.loads(quiz.exercise_ids)
+    created_at = as_utc(quiz.created_at)
+    scores: Dict[int, float] = {}
+    for attempt in database.get_attempts_by_book_id(quiz.book_id):
+        if attempt.exercise_id in exercise_ids and attempt.score is not None and as_utc(attempt.created_at) >= created_at:
+            scores[attempt.exercise_id] = attempt.score
+    if quiz.completed_at is None and len(scores) == len(exercise_ids):
+        quiz = database.complete_chapter_quiz(quiz.quiz_id, now or datetime.now(timezone.utc)) or quiz
+    mastery = book_mastery(database, quiz.book_id).get(quiz.chapter_id) if scores else None
+    return ChapterQuizProgress(quiz, exercise_ids, scores, mastery)
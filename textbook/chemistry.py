@@ -0,0 +1,281 @@
+# Chemical structures
+# Chemistry and biology problems and cards can carry the structure they are about: a chemical formula ("CuSO4·5H2O",
+# "SO4^2-") and a SMILES string ("CC(=O)O"), which clients render as a structure. Both are validated and
+# canonicalized when they are set, so every client gets one spelling of a compound: formulas in Hill order (carbon,
+# hydrogen, then the other elements alphabetically, all alphabetically without carbon) with hydrates and groups
+# expanded, SMILES in RDKit's canonical form. RDKit is optional (uv sync --extra chemistry); without it SMILES are
+# checked for their syntax and kept as written. An answer to a problem with a structure is graded by comparing
+# canonical forms: a formula against its formula, a SMILES string against its SMILES when RDKit is installed.
+# Answers that are neither are left to the LLM.
+
+import re
+from collections import Counter
+from typing import Dict, List, Optional, Tuple
+
+MAX_FORMULA_CHARS = 200
+MAX_SMILES_CHARS = 2000
+MAX_COUNT = 10000  # Of an element in a formula
+
+ELEMENTS = frozenset("""
+H He Li Be B C N O F Ne Na Mg Al Si P S Cl Ar K Ca Sc Ti V Cr Mn Fe Co Ni Cu Zn Ga Ge As Se Br Kr Rb Sr Y Zr Nb Mo
+Tc Ru Rh Pd Ag Cd In Sn Sb Te I Xe Cs Ba La Ce Pr Nd Pm Sm Eu Gd Tb Dy Ho Er Tm Yb Lu Hf Ta W Re Os Ir Pt Au Hg Tl
+Pb Bi Po At Rn Fr Ra Ac Th Pa U Np Pu Am Cm Bk Cf Es Fm Md No Lr Rf Db Sg Bh Hs Mt Ds Rg Cn Nh Fl Mc Lv Ts Og D T
+""".split())  # With deuterium and tritium
+
+_SUBSCRIPTS = str.maketrans("₀₁₂₃₄₅₆₇₈₉", "0123456789")
+_SUPERSCRIPTS = str.maketrans("⁰¹²³⁴⁵⁶⁷⁸⁹⁺⁻", "0123456789+-")
+_WRAPPERS = re.compile(r"\\(?:ce|mathrm|text)\s*\{([^{}]*)\}")
+_SUBSCRIPT_MARK = re.compile(r"_\{?(\d+)\}?")
+_CHARGE = re.compile(r"(?:\^\{?(?P<digits>\d*)(?P<sign>[+-])\}?|(?P<bare>[+-]+))$")
+_HYDRATE_SEPARATORS = ("·", "•", "⋅", "*", "\\cdot", ".")
+_TOKEN = re.compile(r"(?P<element>[A-Z][a-z]?)|(?P<open>[(\[])|(?P<close>[)\]])|(?P<count>\d+)")
+
+# SMILES atoms outside brackets, with aromatic ones in lower case
+_ORGANIC_SUBSET = ("Cl", "Br", "B", "C", "N", "O", "P", "S", "F", "I", "b", "c", "n", "o", "p", "s", "*")
+_SMILES_CHARS = re.compile(r"[A-Za-z0-9@+\-\[\]()=#$/\\%.:*~]+")
+_BRACKET_ATOM = re.compile(r"\[(?P<isotope>\d*)(?P<symbol>[A-Z][a-z]?|[a-z][a-z]?|\*)(?P<rest>[^\[\]]*)\]")
+
+
+def _rdkit():
+    try:
+        from rdkit import Chem, RDLogger
+    except ImportError:
+        return None
+    RDLogger.DisableLog("rdApp.*")  # Invalid SMILES are reported by the ValueError raised here
+    return Chem
+
+
+def _normalize_formula(text: str) -> str:
+    text = text.strip().strip("$").strip()
+    text = _WRAPPERS.sub(lambda match: match.group(1), text)
+    text = _SUBSCRIPT_MARK.sub(lambda match: match.group(1), text)
+    return text.translate(_SUBSCRIPTS).translate(_SUPERSCRIPTS).replace(" ", "")
+
+
+def _charge(text: str) -> Tuple[str, int]:
+    # "SO4^2-", "SO4^{2-}" or "Cl-", the digits of "SO42-" being its count
+    match = _CHARGE.search(text)
+    if match is None:
+        return text, 0
+    if match.group("bare"):
+        signs = match.group("bare")
+        if len(set(signs)) > 1:
+            raise ValueError(f"invalid charge '{signs}'")
+        return text[:match.start()], len(signs) if signs[0] == "+" else -len(signs)
+    magnitude = int(match.group("digits") or 1)
+    return text[:match.start()], magnitude if match.group("sign") == "+" else -magnitude
+
+
+def _count_part(text: str) -> Counter:
+    counts: List[Counter] = [Counter()]
+    last: Optional[Counter] = None  # The element or group the next count multiplies
+    position = 0
+    while position < len(text):
+        match = _TOKEN.match(text, position)
+        if match is None:
+            raise ValueError(f"unexpected '{text[position:]}'")
+        position = match.end()
+        if match.group("element"):
+            element = match.group("element")
+            if element not in ELEMENTS:
+                raise ValueError(f"unknown element '{element}'")
+            last = Counter({element: 1})
+            counts[-1].update(last)
+        elif match.group("open"):
+            counts.append(Counter())
+            last = None
+        elif match.group("close"):
+            if len(counts) == 1:
+                raise ValueError("unbalanced parentheses")
+            last = counts.pop()
+            if not last:
+                raise ValueError("empty parentheses")
+            counts[-1].update(last)
+        else:
+            if last is None:
+                raise ValueError(f"count {match.group('count')} of nothing")
+            count = int(match.group("count"))
+            if not 0 < count <= MAX_COUNT:
+                raise ValueError(f"count {count} is out of range")
+            counts[-1].update({element: number * (count - 1) for element, number in last.items()})
+            last = None
+    if len(counts) > 1:
+        raise ValueError("unbalanced parentheses")
+    return counts[0]
+
+
+def parse_formula(text: str) -> Tuple[Dict[str, int], int]:
+    """
+    The element counts and the charge of a chemical formula, hydrates ("CuSO4·5H2O") and groups ("Ca(OH)2") expanded
+
+    Raises:
+        ValueError: If the text is not a formula of known elements
+    """
+    text = _normalize_formula(text)
+    if not text or len(text) > MAX_FORMULA_CHARS:
+        raise ValueError(f"Invalid chemical formula: expected 1 to {MAX_FORMULA_CHARS} characters")
+    try:
+        text, charge = _charge(text)
+        for separator in _HYDRATE_SEPARATORS:
+            text = text.replace(separator, "·")
+        counts: Counter = Counter()
+        for part in text.split("·"):
+            # The leading number of a part is how many of it, "5H2O"
+            match = re.match(r"\d+", part)
+            multiplier = int(match.group()) if match else 1
+            part_counts = _count_part(part[match.end():] if match else part)
+            if not part_counts or not 0 < multiplier <= MAX_COUNT:
+                raise ValueError(f"invalid part '{part}'")
+            counts.update({element: number * multiplier for element, number in part_counts.items()})
+    except ValueError as e:
+        raise ValueError(f"Invalid chemical formula '{text}': {e}") from e
+    return dict(counts), charge
+
+
+def hill_formula(counts: Dict[str, int], charge: int = 0) -> str:
+    """A formula in Hill order, e.g. "C2H6O" or "O4S^2-" """
+    if "C" in counts:
+        elements = ["C"] + (["H"] if "H" in counts else []) + sorted(element for element in counts if element not in ("C", "H"))
+    else:
+        elements = sorted(counts)
+    formula = "".join(element if counts[element] == 1 else f"{element}{counts[element]}" for element in elements)
+    if charge:
+        formula += f"^{'' if abs(charge) == 1 else abs(charge)}{'+' if charge > 0 else '-'}"
+    return formula
+
+
+def canonical_formula(text: str) -> str:
+    """The formula in Hill order, raises ValueError if it is not one"""
+    return hill_formula(*parse_formula(text))
+
+
+def validate_smiles_syntax(smiles: str) -> None:
+    """
+    Check the syntax of a SMILES string without RDKit: its atoms, brackets, branches and ring closures
+
+    Raises:
+        ValueError: If it is not a SMILES string
+    """
+    if not _SMILES_CHARS.fullmatch(smiles):
+        raise ValueError("unexpected characters")
+    depth, open_rings, position = 0, set(), 0
+    while position < len(smiles):
+        character = smiles[position]
+        if character == "[":
+            match = _BRACKET_ATOM.match(smiles, position)
+            symbol = match.group("symbol") if match else None
+            if match is None or (symbol != "*" and symbol.capitalize() not in ELEMENTS):
+                raise ValueError(f"invalid atom at '{smiles[position:]}'")
+            position = match.end()
+            continue
+        if character == "(":
+            depth += 1
+        elif character == ")":
+            depth -= 1
+            if depth < 0:
+                raise ValueError("unbalanced branches")
+        elif character.isdigit() or character == "%":
+            ring = smiles[position:position + 3] if character == "%" else character
+            if character == "%" and not re.fullmatch(r"%\d\d", ring):
+                raise ValueError("invalid ring closure")
+            open_rings ^= {ring}
+            position += len(ring)
+            continue
+        elif character.isalpha() or character == "*":
+            atom = next((atom for atom in _ORGANIC_SUBSET if smiles.startswith(atom, position)), None)
+            if atom is None:
+                raise ValueError(f"invalid atom at '{smiles[position:]}', elements outside the organic subset go in brackets")
+            position += len(atom)
+            continue
+        position += 1
+    if depth:
+        raise ValueError("unbalanced branches")
+    if open_rings:
+        raise ValueError(f"unclosed rings {', '.join(sorted(open_rings))}")
+
+
+def canonical_smiles(smiles: str) -> str:
+    """
+    The canonical form of a SMILES string, as written when RDKit is not installed
+
+    Raises:
+        ValueError: If it is not a SMILES string
+    """
+    smiles = smiles.strip()
+    if not smiles or len(smiles) > MAX_SMILES_CHARS:
+        raise ValueError(f"Invalid SMILES: expected 1 to {MAX_SMILES_CHARS} characters")
+    chem = _rdkit()
+    if chem is None:
+        try:
+            validate_smiles_syntax(smiles)
+        except ValueError as e:
+            raise ValueError(f"Invalid SMILES '{smiles}': {e}") from e
+        return smiles
+    molecule = chem.MolFromSmiles(smiles)
+    if molecule is None:
+        raise ValueError(f"Invalid SMILES '{smiles}': RDKit cannot read it")
+    return chem.MolToSmiles(molecule)
+
+
+def smiles_formula(smiles: str) -> Optional[str]:
+    """The formula of a SMILES string in Hill order, None without RDKit"""
+    chem = _rdkit()
+    molecule = chem.MolFromSmiles(smiles) if chem is not None else None
+    if molecule is None:
+        return None
+    from rdkit.Chem.rdMolDescriptors import CalcMolFormula
+
+    return canonical_formula(CalcMolFormula(molecule))
+
+
+def canonical_structure(chemical_formula: Optional[str], smiles: Optional[str]) -> Tuple[Optional[str], Optional[str]]:
+    """
+    The formula and SMILES of a problem or card in canonical form, blank ones as None
+
+    Raises:
+        ValueError: If either is invalid, or with RDKit, the formula is not the SMILES string's
+    """
+    formula = canonical_formula(chemical_formula) if chemical_formula and chemical_formula.strip() else None
+    canonical = canonical_smiles(smiles) if smiles and smiles.strip() else None
+    if formula is not None and canonical is not None:
+        expected = smiles_formula(canonical)
+        if expected is not None and expected != formula:
+            raise ValueError(f"The chemical formula {formula} is not the formula of the SMILES {canonical}, {expected}")
+    return formula, canonical
+
+
+def check_structure(answer: str, chemical_formula: Optional[str], smiles: Optional[str]) -> Optional[Tuple[bool, str]]:
+    """
+    Check an answer against the structure of a problem by canonical form
+
+    Returns:
+        Optional[Tuple[bool, str]]: Whether it is correct and the feedback, None when the answer is neither a SMILES
+        string comparable with RDKit nor a formula, the LLM judges it then
+    """
+    answer = answer.strip()
+    if smiles and answer == smiles.strip():
+        return True, "Correct, your structure is the expected one."
+    if smiles and _rdkit() is not None:
+        try:
+            if canonical_smiles(answer) == canonical_smiles(smiles):
+                return True, "Correct, your structure is the expected one."
+            answered = True
+        except ValueError:
+            answered = False
+        # A formula like "CO" is also valid SMILES, the formula decides those
+        if answered and not chemical_formula:
+            return False, "Your structure is not the expected one, check its bonds and atoms."
+    if chemical_formula:
+        try:
+            counts, charge = parse_formula(answer)
+        except ValueError:
+            return None
+        if hill_formula(counts, charge) == canonical_formula(chemical_formula):
+            return True, f"Correct, {answer} is {canonical_formula(chemical_formula)}."
+        expected_counts, expected_charge = parse_formula(chemical_formula)
+        if set(counts) != set(expected_counts):
+            return False, "Your formula does not have the expected elements."
+        if counts == expected_counts and charge != expected_charge:
+            return False, "The elements are right but the charge is not."
+        return False, "The elements are right but not how many of each, check the counts."
+    return None
@@ -0,0 +1,134 @@
+# Chunked OCR
+# A 600-page book sent to the OCR backend in one request takes long enough to time out, and a failure anywhere loses
+# every page read. ChunkedOcr wraps the backend of an OCR job and sends the pages in chunks of pages_per_chunk, MinerU
+# reading each range from the PDF with start_page_id and end_page_id, reporting the progress after each chunk. Every
+# chunk read is kept in a cache under <uploads_dir>/.ocr_chunks, keyed by the content of the PDF, the backend, the
+# language and the parse method, so a job deferred while MinerU is down, retried or submitted again after a failure
+# resumes at the first chunk not read. The cache of a document is removed once its OCR output is written. Set in
+# config.toml:
+#   [ocr]
+#   pages_per_chunk = 50  # 0 sends every page in one request
+
+import base64
+import hashlib
+import json
+import os
+import shutil
+from pathlib import Path
+from typing import Callable, Dict, List, Optional, Set
+
+import pymupdf
+from PIL import Image
+
+from textbook.jobs import CancellationToken
+from textbook.ocr import DEFAULT_PAGES_PER_CHUNK, OcrBackend, OcrResult
+
+OCR_CHUNK_DIR = ".ocr_chunks"  # Under the uploads directory
+
+
+def page_chunks(pages: List[int], pages_per_chunk: int) -> List[List[int]]:
+    """The pages in chunks of pages_per_chunk, all of them in one when it is 0"""
+    if pages_per_chunk <= 0:
+        return [pages] if pages else []
+    return [pages[start:start + pages_per_chunk] for start in range(0, len(pages), pages_per_chunk)]
+
+
+def file_digest(path: str) -> str:
+    digest = hashlib.sha256()
+    with open(path, "rb") as file:
+        for block in iter(lambda: file.read(1024 * 1024), b""):
+            digest.update(block)
+    return digest.hexdigest()
+
+
+def _chunk_name(pages: List[int]) -> str:
+    # The first and last page for people looking at the cache, the digest for chunks of scattered scanned pages
+    digest = hashlib.sha256(json.dumps(pages).encode()).hexdigest()[:8]
+    return f"{pages[0]:05d}-{pages[-1]:05d}-{digest}.json"
+
+
+def _encode(result: OcrResult) -> str:
+    images = {name: base64.b64encode(data).decode("ascii") for name, data in result.images.items()}
+    return json.dumps({"markdown": result.markdown, "content_list": result.content_list, "images": images})
+
+
+def _decode(text: str) -> OcrResult:
+    data = json.loads(text)
+    return OcrResult(data["markdown"], data["content_list"], {name: base64.b64decode(image) for name, image in data["images"].items()})
+
+
+class ChunkedOcr(OcrBackend):
+    """An OCR backend reading PDFs in chunks of pages, keeping every chunk read until the output is written"""
+
+    def __init__(
+        self,
+        backend: OcrBackend,
+        cache_root: str,
+        pages_per_chunk: int = DEFAULT_PAGES_PER_CHUNK,
+        cancellation_token: Optional[CancellationToken] = None,
+        report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+        progress_span: float = 90,  # Of the job's progress, the chunks report from 0 up to this
+    ):
+        self.backend = backend
+        self.name = backend.name
+        self.cache_root = Path(cache_root)
+        self.pages_per_chunk = pages_per_chunk
+        self.cancellation_token = cancellation_token
+        self.report_progress = report_progress
+        self.progress_span = progress_span
+        self._cache_dirs: Set[Path] = set()  # Used by this job, removed by discard()
+
+    def unavailable(self) -> Optional[str]:
+        return self.backend.unavailable()
+
+    def ping(self) -> Optional[str]:
+        return self.backend.ping()
+
+    def ocr_image(self, image: Image.Image) -> str:
+        return self.backend.ocr_image(image)
+
+    def cache_dir(self, pdf_path: str, language: str, parse_method: str) -> Path:
+        key = hashlib.sha256(f"{file_digest(pdf_path)}:{self.backend.name}:{language}:{parse_method}".encode()).hexdigest()[:24]
+        return self.cache_root / key
+
+    def ocr_pdf(self, pdf_path: str, language: str, parse_method: str, job_id: Optional[str] = None) -> OcrResult:
+        with pymupdf.open(pdf_path) as document:
+            page_count = len(document)
+        return self.ocr_pages(pdf_path, list(range(page_count)), language, parse_method, job_id)
+
+    def ocr_pages(self, pdf_path: str, pages: List[int], language: str, parse_method: str, job_id: Optional[str] = None) -> OcrResult:
+        if not pages:
+            return OcrResult("")
+        cache_dir = self.cache_dir(pdf_path, language, parse_method)
+        cache_dir.mkdir(parents=True, exist_ok=True)
+        self._cache_dirs.add(cache_dir)
+        chunks = page_chunks(pages, self.pages_per_chunk)
+        results = []
+        for index, chunk in enumerate(chunks):
+            if self.cancellation_token is not None:
+                self.cancellation_token.raise_if_cancelled()
+            path = cache_dir / _chunk_name(chunk)
+            if path.exists():
+                results.append(_decode(path.read_text(encoding="utf-8")))
+                message = f"Pages {chunk[0] + 1}-{chunk[-1] + 1} read before, {index + 1}/{len(chunks)} chunks"
+            else:
+                result = self.backend.ocr_pages(pdf_path, chunk, language, parse_method, job_id)
+                # Written aside first, so a chunk cut off halfway is never read back
+                partial = path.with_suffix(".partial")
+                partial.write_text(_encode(result), encoding="utf-8")
+                os.replace(partial, path)
+                results.append(result)
+                message = f"Read pages {chunk[0] + 1}-{chunk[-1] + 1} with {self.backend.name}, {index + 1}/{len(chunks)} chunks"
+            if self.report_progress is not None:
+                self.report_progress("ocr", self.progress_span * (index + 1) / len(chunks), message)
+        images: Dict[str, bytes] = {}
+        for result in results:
+            images.update(result.images)
+        markdown = "\n\n".join(result.markdown for result in results if result.markdown.strip())
+        return OcrResult(markdown, [block for result in results for block in result.content_list], images)
+
+    def discard(self) -> None:
+        """Remove the chunks read by this job, once its output is written"""
+        for cache_dir in self._cache_dirs:
+            shutil.rmtree(cache_dir, ignore_errors=True)
+        self._cache_dirs.clear()
@@ -0,0 +1,166 @@
+# Circuit breakers
+# When MinerU or the LLM provider is down, every job calling it would wait for its own timeout (five minutes for a
+# MinerU parse) before failing. A breaker per service counts the consecutive calls failing because the service is
+# unreachable, times out or answers with a server error; after failure_threshold of them it opens, and calls fail
+# right away with ServiceUnavailable, a ModelError of category unavailable that defers their job instead of failing
+# it. Once reset_seconds passed the breaker is half-open: a single call goes through as a probe, closing the breaker
+# when it succeeds and opening it again when it fails. Errors saying the request was wrong (an invalid API key, a
+# blocked prompt) do not count, the service answered them. Listeners learn when a breaker closes, e.g. to resume the
+# jobs it deferred. Set in config.toml:
+#   [circuit_breaker]
+#   failure_threshold = 5
+#   reset_seconds = 60
+
+import threading
+import time
+from dataclasses import dataclass
+from typing import Callable, Dict, List, Optional
+
+from textbook.provider_errors import ERROR_UNAVAILABLE, ModelError
+
+SERVICE_MINERU = "mineru"
+
+BREAKER_CLOSED = "closed"
+BREAKER_OPEN = "open"
+BREAKER_HALF_OPEN = "half_open"
+
+DEFAULT_FAILURE_THRESHOLD = 5
+DEFAULT_RESET_SECONDS = 60
+
+
+def llm_service(provider: str) -> str:
+    """The name of the breaker of an LLM provider"""
+    return f"llm:{provider}"
+
+
+@dataclass
+class CircuitBreakerSettings:
+    failure_threshold: int = DEFAULT_FAILURE_THRESHOLD  # Consecutive failures opening the breaker
+    reset_seconds: float = DEFAULT_RESET_SECONDS  # Time open before a probe goes through
+
+
+@dataclass
+class BreakerStatus:
+    service: str
+    state: str
+    consecutive_failures: int
+    trips: int  # Times the breaker opened since the server started
+    rejected: int  # Calls failed fast while it was open
+    retry_in_seconds: Optional[float] = None  # Until the next probe, while open
+
+
+class ServiceUnavailable(ModelError):
+    """A call failed fast because the breaker of its service is open"""
+
+    def __init__(self, service: str, retry_in_seconds: float):
+        super().__init__(ERROR_UNAVAILABLE, f"{service} is unavailable after repeated failures, probed again in {retry_in_seconds:.0f} seconds")
+        self.service = service
+        self.retry_in_seconds = retry_in_seconds
+
+
+def circuit_breaker_settings_from_config(config: dict) -> CircuitBreakerSettings:
+    """The [circuit_breaker] section of config.toml, raises ValueError for invalid settings"""
+    section = config.get("circuit_breaker", {})
+    unknown = set(section) - {"failure_threshold", "reset_seconds"}
+    if unknown:
+        raise ValueError(f"Unknown settings of circuit_breaker: {', '.join(sorted(unknown))}")
+    settings = CircuitBreakerSettings(
+        failure_threshold=section.get("failure_threshold", DEFAULT_FAILURE_THRESHOLD),
+        reset_seconds=section.get("reset_seconds", DEFAULT_RESET_SECONDS),
+    )
+    if isinstance(settings.failure_threshold, bool) or not isinstance(settings.failure_threshold, int) or settings.failure_threshold < 1:
+        raise ValueError("circuit_breaker.failure_threshold must be a positive integer")
+    if isinstance(settings.reset_seconds, bool) or not isinstance(settings.reset_seconds, (int, float)) or settings.reset_seconds <= 0:
+        raise ValueError("circuit_breaker.reset_seconds must be a positive number")
+    return settings
+
+
+class CircuitBreaker:
+    """Fails the calls to a service fast after repeated failures, until a probe succeeds"""
+
+    def __init__(self, service: str, breakers: "CircuitBreakers", clock: Callable[[], float] = time.monotonic):
+        self.service = service
+        self._breakers = breakers
+        self._clock = clock
+        self._lock = threading.Lock()
+        self._state = BREAKER_CLOSED
+        self._failures = 0
+        self._opened_at = 0.0
+        self._probing = False
+        self._trips = 0
+        self._rejected = 0
+
+    def check(self) -> None:
+        """Let a call through, raises ServiceUnavailable while the breaker is open or another probe is under way"""
+        with self._lock:
+            if self._state == BREAKER_CLOSED:
+                return
+            retry_in = self._opened_at + self._breakers.settings.reset_seconds - self._clock()
+            if retry_in <= 0 and not self._probing:
+                self._state = BREAKER_HALF_OPEN
+                self._probing = True
+                return
+            self._rejected += 1
+            raise ServiceUnavailable(self.service, max(retry_in, 0))
+
+    def record_success(self) -> None:
+        with self._lock:
+            closed = self._state != BREAKER_CLOSED
+            self._state = BREAKER_CLOSED
+            self._failures = 0
+            self._probing = False
+        if closed:
+            self._breakers.notify_closed(self.service)
+
+    def record_failure(self) -> None:
+        with self._lock:
+            self._failures += 1
+            if self._state == BREAKER_HALF_OPEN or (self._state == BREAKER_CLOSED and self._failures >= self._breakers.settings.failure_threshold):
+                if self._state == BREAKER_CLOSED:
+                    self._trips += 1
+                self._state = BREAKER_OPEN
+                self._opened_at = self._clock()
+            self._probing = False
+
+    def status(self) -> BreakerStatus:
+        with self._lock:
+            retry_in = None
+            if self._state != BREAKER_CLOSED:
+                retry_in = round(max(self._opened_at + self._breakers.settings.reset_seconds - self._clock(), 0), 1)
+            return BreakerStatus(self.service, self._state, self._failures, self._trips, self._rejected, retry_in)
+
+
+class CircuitBreakers:
+    """The breakers of the services, created on first use"""
+
+    def __init__(self, settings: Optional[CircuitBreakerSettings] = None, clock: Callable[[], float] = time.monotonic):
+        self.settings = settings or CircuitBreakerSettings()
+        self._clock = clock
+        self._lock = threading.Lock()
+        self._breakers: Dict[str, CircuitBreaker] = {}
+        self._listeners: List[Callable[[str], None]] = []
+
+    def get(self, service: str) -> CircuitBreaker:
+        with self._lock:
+            if service not in self._breakers:
+                self._breakers[service] = CircuitBreaker(service, self, self._clock)
+            return self._breakers[service]
+
+    def on_closed(self, listener: Callable[[str], None]) -> None:
+        """Call a listener with the service whenever a breaker closes again"""
+        with self._lock:
+            self._listeners.append(listener)
+
+    def notify_closed(self, service: str) -> None:
+        with self._lock:
+            listeners = list(self._listeners)
+        for listener in listeners:
+            listener(service)
+
+    def snapshot(self) -> List[BreakerStatus]:
+        with self._lock:
+            breakers = list(self._breakers.values())
+        return sorted((breaker.status() for breaker in breakers), key=lambda status: status.service)
+
+
+circuit_breakers = CircuitBreakers()
@@ -0,0 +1,118 @@
+# Compression of stored text
+# A library of textbooks keeps the markdown of every document more than once: the OCR output next to its PDF, and in
+# the database the text of every page and section chunk, the prompts embedding them in the prompt log and the cached
+# responses. Markdown compresses well, so large text is stored zstd-compressed and decompressed transparently by the
+# storage layer: columns of the CompressedText type in the database, and write_markdown and read_markdown for the OCR
+# markdown, kept as <name>.md.zst next to the PDF. Text shorter than min_bytes is stored as is, compressing it would
+# save little. Text stored before compression was enabled is read as it is, so existing libraries need no migration:
+# rows and files are compressed as they are written again, or all at once with `python -m textbook.admin compress`.
+# Set in config.toml:
+#   [compression]
+#   enabled = true
+#   level = 3  # 1 to 22, higher is smaller and slower
+#   min_bytes = 1024
+
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Any, Optional
+
+import zstandard
+from sqlalchemy import LargeBinary
+from sqlalchemy.types import TypeDecorator
+
+ZSTD_MAGIC = b"\x28\xb5\x2f\xfd"  # Starts every zstd frame, and no UTF-8 text
+COMPRESSED_SUFFIX = ".zst"
+DEFAULT_LEVEL = 3
+MAX_LEVEL = 22
+DEFAULT_MIN_BYTES = 1024
+
+
+@dataclass
+class CompressionSettings:
+    enabled: bool = True
+    level: int = DEFAULT_LEVEL
+    min_bytes: int = DEFAULT_MIN_BYTES  # Text shorter than this is stored uncompressed
+
+
+def compression_settings_from_config(config: dict) -> CompressionSettings:
+    """The [compression] section of config.toml, raises ValueError for invalid settings"""
+    section = config.get("compression", {})
+    unknown = set(section) - {"enabled", "level", "min_bytes"}
+    if unknown:
+        raise ValueError(f"Unknown settings of compression: {', '.join(sorted(unknown))}")
+    settings = CompressionSettings(
+        enabled=section.get("enabled", True),
+        level=section.get("level", DEFAULT_LEVEL),
+        min_bytes=section.get("min_bytes", DEFAULT_MIN_BYTES),
+    )
+    if not isinstance(settings.enabled, bool):
+        raise ValueError("compression.enabled must be true or false")
+    if isinstance(settings.level, bool) or not isinstance(settings.level, int) or not 1 <= settings.level <= MAX_LEVEL:
+        raise ValueError(f"compression.level must be an integer between 1 and {MAX_LEVEL}")
+    if isinstance(settings.min_bytes, bool) or not isinstance(settings.min_bytes, int) or settings.min_bytes < 0:
+        raise ValueError("compression.min_bytes must be a non-negative integer")
+    return settings
+
+
+compression_settings = CompressionSettings()
+
+
+def configure_compression(settings: CompressionSettings):
+    """Set the settings the storage layer compresses with, on startup"""
+    global compression_settings
+    compression_settings = settings
+
+
+def compress_text(text: str, settings: Optional[CompressionSettings] = None) -> bytes:
+    """The text as UTF-8, zstd-compressed when compression is enabled, the text is long enough and it gets smaller"""
+    settings = settings or compression_settings
+    data = text.encode("utf-8")
+    if not settings.enabled or len(data) < settings.min_bytes:
+        return data
+    compressed = zstandard.ZstdCompressor(level=settings.level).compress(data)
+    return compressed if len(compressed) < len(data) else data
+
+
+def decompress_text(data: bytes) -> str:
+    """Text stored by compress_text, compressed or not"""
+    if data.startswith(ZSTD_MAGIC):
+        data = zstandard.ZstdDecompressor().decompress(data)
+    return data.decode("utf-8")
+
+
+class CompressedText(TypeDecorator):
+    """A text column stored compressed, values written before as plain text are read as they are"""
+
+    impl = LargeBinary
+    cache_ok = True
+
+    def process_bind_param(self, value: Optional[str], dialect: Any) -> Optional[bytes]:
+        return compress_text(value) if value is not None else None
+
+    def process_result_value(self, value: Any, dialect: Any) -> Optional[str]:
+        if value is None or isinstance(value, str):
+            return value
+        return decompress_text(bytes(value))
+
+
+def compressed_path(path: Path) -> Path:
+    return path.with_name(path.name + COMPRESSED_SUFFIX)
+
+
+def write_markdown(path: Path, markdown: str) -> Path:
+    """Write markdown to path, or compressed next to it, removing the other copy; returns the path written"""
+    data = compress_text(markdown)
+    target = compressed_path(path) if data.startswith(ZSTD_MAGIC) else path
+    target.write_bytes(data)
+    stale = path if target != path else compressed_path(path)
+    if stale.exists():
+        stale.unlink()
+    return target
+
+
+def read_markdown(path: Path) -> Optional[str]:
+    """The markdown written to path by write_markdown, compressed or not, None if there is none"""
+    for candidate in (compressed_path(path), path):
+        if candidate.exists():
+            return decompress_text(candidate.read_bytes())
+    return None
@@ -0,0 +1,188 @@
+# Chunked OCR content
+# MinerU answers with the markdown of a whole document. To build prompts from a page or a section instead of the
+# whole book, the OCR output is split into chunks: one per page, from the page of every block of MinerU's content
+# list, and one per markdown heading, running until the next heading of the same or a higher level. Chunks keep their
+# character offsets in the document's markdown file, which is rebuilt from the content list so the offsets match it.
+# Without a content list the markdown is kept as is and only split into sections, with no pages.
+
+import re
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Tuple
+
+from textbook.compression import read_markdown
+from textbook.database import ContentChunkInfo, TextBookDatabase
+
+CHUNK_PAGE = "page"
+CHUNK_SECTION = "section"
+
+PAGE_SEPARATOR = "\n\n"
+HEADING = re.compile(r"^(#{1,6})[ \t]+(.+?)[ \t#]*$", re.MULTILINE)
+
+
+@dataclass
+class ContentChunk:
+    kind: str  # CHUNK_PAGE or CHUNK_SECTION
+    index: int  # The page number (0-based) of pages, the position among the sections of sections
+    char_start: int
+    char_end: int
+    text: str
+    title: Optional[str] = None  # The heading of sections
+    level: Optional[int] = None  # The heading level of sections, 1 for "#"
+    page_start: Optional[int] = None
+    page_end: Optional[int] = None  # Inclusive
+
+
+def block_markdown(block: Dict[str, Any]) -> str:
+    """The markdown of a block of MinerU's content list, empty for blocks without text"""
+    block_type = block.get("type")
+    if block_type == "text":
+        text = (block.get("text") or "").strip()
+        level = block.get("text_level")
+        return f"{'#' * min(int(level), 6)} {text}" if level and text else text
+    if block_type == "equation":
+        return (block.get("text") or "").strip()
+    if block_type == "table":
+        caption = " ".join(block.get("table_caption") or [])
+        return "\n\n".join(part for part in (caption.strip(), (block.get("table_body") or "").strip()) if part)
+    if block_type == "image":
+        return " ".join(block.get("image_caption") or []).strip()
+    return (block.get("text") or "").strip()
+
+
+def page_chunks(content_list: List[Dict[str, Any]]) -> Tuple[str, List[ContentChunk]]:
+    """The markdown of a content list, its pages separated by a blank line, and a chunk per page"""
+    page_count = max((int(block.get("page_idx", 0)) for block in content_list), default=-1) + 1
+    pages: List[List[str]] = [[] for _ in range(page_count)]
+    for block in content_list:
+        markdown = block_markdown(block)
+        if markdown:
+            pages[int(block.get("page_idx", 0))].append(markdown)
+
+    chunks = []
+    offset = 0
+    for page_number, blocks in enumerate(pages):
+        if page_number > 0:
+            offset += len(PAGE_SEPARATOR)
+        text = "\n\n".join(blocks)
+        chunks.append(ContentChunk(CHUNK_PAGE, page_number, offset, offset + len(text), text, page_start=page_number, page_end=page_number))
+        offset += len(text)
+    return PAGE_SEPARATOR.join(chunk.text for chunk in chunks), chunks
+
+
+def _page_at(pages: List[ContentChunk], offset: int) -> Optional[int]:
+    # The page a character offset falls on, separators count towards the page before them
+    page_number = None
+    for page in pages:
+        if page.char_start > offset:
+            break
+        page_number = page.index
+    return page_number
+
+
+def section_chunks(markdown: str, pages: Optional[List[ContentChunk]] = None) -> List[ContentChunk]:
+    """A chunk per heading of the markdown, up to the next heading of the same or a higher level"""
+    return heading_chunks(markdown, [(match.start(), len(match.group(1)), match.group(2).strip()) for match in HEADING.finditer(markdown)], pages)
+
+
+def heading_chunks(markdown: str, headings: List[Tuple[int, int, str]], pages: Optional[List[ContentChunk]] = None) -> List[ContentChunk]:
+    """A section chunk per heading, given by its offset, level and title, up to the next heading of the same or a higher level"""
+    chunks = []
+    for position, (start, level, title) in enumerate(headings):
+        end = next((following for following, following_level, _ in headings[position + 1:] if following_level <= level), len(markdown))
+        text = markdown[start:end].rstrip()
+        chunk = ContentChunk(CHUNK_SECTION, position, start, start + len(text), text, title=title, level=level)
+        if pages:
+            chunk.page_start = _page_at(pages, start)
+            chunk.page_end = _page_at(pages, max(start, start + len(text) - 1))
+        chunks.append(chunk)
+    return chunks
+
+
+def chunk_content(markdown: str, content_list: Optional[List[Dict[str, Any]]] = None) -> Tuple[str, List[ContentChunk]]:
+    """
+    Split OCR output into page and section chunks
+
+    Args:
+        markdown: The markdown MinerU returned, kept as is when there is no content list
+        content_list: MinerU's blocks with their page_idx
+
+    Returns:
+        Tuple[str, List[ContentChunk]]: The markdown to store, which the offsets refer to, and the chunks, pages first
+    """
+    if content_list:
+        markdown, pages = page_chunks(content_list)
+        return markdown, pages + section_chunks(markdown, pages)
+    return markdown, section_chunks(markdown)
+
+
+def _chunk_info(chunk: ContentChunk) -> ContentChunkInfo:
+    return ContentChunkInfo(
+        chunk_kind=chunk.kind,
+        chunk_index=chunk.index,
+        title=chunk.title,
+        level=chunk.level,
+        page_start=chunk.page_start,
+        page_end=chunk.page_end,
+        char_start=chunk.char_start,
+        char_end=chunk.char_end,
+        text=chunk.text,
+    )
+
+
+def _content_chunk(chunk: ContentChunkInfo) -> ContentChunk:
+    return ContentChunk(chunk.chunk_kind, chunk.chunk_index, chunk.char_start, chunk.char_end, chunk.text, chunk.title, chunk.level, chunk.page_start, chunk.page_end)
+
+
+def store_chunks(database: TextBookDatabase, document_id: int, chunks: List[ContentChunk]) -> int:
+    """Replace the chunks of a document, returns how many were stored"""
+    return database.replace_content_chunks(document_id, [_chunk_info(chunk) for chunk in chunks])
+
+
+def store_sections(database: TextBookDatabase, document_id: int, sections: List[ContentChunk]) -> int:
+    """Replace the section chunks of a document, keeping its pages, returns how many were stored"""
+    return database.replace_content_chunks(document_id, [_chunk_info(section) for section in sections], CHUNK_SECTION)
+
+
+def get_pages(database: TextBookDatabase, document_id: int) -> List[ContentChunk]:
+    return [_content_chunk(page) for page in database.get_content_chunks(document_id, CHUNK_PAGE)]
+
+
+def document_markdown(database: TextBookDatabase, document_id: int) -> Optional[str]:
+    """The markdown of a document rebuilt from its pages, None for documents without pages"""
+    pages = database.get_content_chunks(document_id, CHUNK_PAGE)
+    return PAGE_SEPARATOR.join(page.text for page in pages) if pages else None
+
+
+def source_markdown(database: TextBookDatabase, document_id: int, uploads_dir: str) -> Optional[str]:
+    """The markdown the chunk offsets of a document refer to, None for documents without OCR output"""
+    markdown = document_markdown(database, document_id)
+    if markdown is not None:
+        return markdown
+    # Without pages the chunks came from the markdown stored next to the uploaded PDF
+    document = database.get_document(document_id)
+    if document is None or not document.source_path:
+        return None
+    return read_markdown((Path(uploads_dir) / document.source_path).with_suffix(".md"))
+
+
+def get_page_markdown(database: TextBookDatabase, document_id: int, page_number: int) -> Optional[ContentChunkInfo]:
+    """The markdown of a page of a document, None if the document has no such page"""
+    return database.get_content_chunk(document_id, CHUNK_PAGE, page_number)
+
+
+def get_pages_markdown(database: TextBookDatabase, document_id: int, start_page: int, end_page: int) -> str:
+    """The markdown of a range of pages, end_page included, for prompts about part of a document"""
+    if end_page < start_page:
+        raise ValueError("end_page must not be before start_page")
+    pages = [chunk for chunk in database.get_content_chunks(document_id, CHUNK_PAGE) if start_page <= chunk.chunk_index <= end_page]
+    return PAGE_SEPARATOR.join(page.text for page in pages)
+
+
+def get_sections(database: TextBookDatabase, document_id: int) -> List[ContentChunkInfo]:
+    return database.get_content_chunks(document_id, CHUNK_SECTION)
+
+
+def get_section(database: TextBookDatabase, document_id: int, section_index: int) -> Optional[ContentChunkInfo]:
+    """A section of a document with its markdown, None if the document has no such section"""
+    return database.get_content_chunk(document_id, CHUNK_SECTION, section_index)
@@ -0,0 +1,148 @@
+# Credentials
+# The API key of the LLM provider is looked up in this order, the first one found wins:
+#   1. the environment: the variable named by `env` in config.toml, then the provider's usual variables
+#   2. a file holding only the key, `api_key_file` in config.toml
+#   3. `api_key` in config.toml itself, for development setups
+#   4. the OS keyring, when the keyring package is installed, under the service "problembasedselfstudy" and the
+#      provider's name (`keyring set problembasedselfstudy gemini`), unless `keyring = false`
+#   [credentials.gemini]
+#   env = "MY_GEMINI_KEY"
+#   api_key_file = "~/.config/problembasedselfstudy/gemini.key"
+# Keys are held in a SecretString, whose str and repr are masked, so a key logged by accident does not leak. Only
+# where a key came from is logged.
+
+import os
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Callable, Mapping, Optional
+
+from textbook.backends import BACKENDS
+
+KEYRING_SERVICE = "problembasedselfstudy"
+MASK = "********"
+
+# The variables each provider's key is usually found in, the first set wins
+PROVIDER_ENV_VARS = {name: backend.env_vars for name, backend in BACKENDS.items()}
+
+SOURCE_ENV = "env"
+SOURCE_FILE = "file"
+SOURCE_CONFIG = "config"
+SOURCE_KEYRING = "keyring"
+
+
+class SecretString:
+    """A secret that does not show itself when printed or logged, reveal() returns it"""
+
+    __slots__ = ("_value",)
+
+    def __init__(self, value: str):
+        self._value = value
+
+    def reveal(self) -> str:
+        return self._value
+
+    def __str__(self) -> str:
+        return MASK
+
+    def __repr__(self) -> str:
+        return f"SecretString('{MASK}')"
+
+    def __eq__(self, other) -> bool:
+        return isinstance(other, SecretString) and other._value == self._value
+
+    def __hash__(self) -> int:
+        return hash(self._value)
+
+
+@dataclass
+class Credential:
+    secret: SecretString
+    source: str  # SOURCE_ENV, SOURCE_FILE, SOURCE_CONFIG or SOURCE_KEYRING
+    location: Optional[str] = None  # The variable or file name, safe to log
+
+    def describe(self) -> str:
+        return f"{self.source} {self.location}" if self.location else self.source
+
+
+@dataclass
+class CredentialSettings:
+    env: Optional[str] = None
+    api_key_file: Optional[str] = None
+    api_key: Optional[SecretString] = None
+    keyring: bool = True
+
+
+def credential_settings_from_config(config: dict, provider: str) -> CredentialSettings:
+    """The credentials of a provider in config.toml, raises ValueError for invalid ones"""
+    section = config.get("credentials", {}).get(provider, {})
+    unknown = set(section) - {"env", "api_key_file", "api_key", "keyring"}
+    if unknown:
+        raise ValueError(f"Unknown credentials settings for {provider}: {', '.join(sorted(unknown))}")
+    for name in ("env", "api_key_file", "api_key"):
+        if name in section and (not isinstance(section[name], str) or not section[name].strip()):
+            raise ValueError(f"credentials.{provider}.{name} must be a non-empty string")
+    if not isinstance(section.get("keyring", True), bool):
+        raise ValueError(f"credentials.{provider}.keyring must be true or false")
+    api_key = section.get("api_key")
+    return CredentialSettings(
+        env=section.get("env"),
+        api_key_file=section.get("api_key_file"),
+        api_key=SecretString(api_key.strip()) if api_key else None,
+        keyring=section.get("keyring", True),
+    )
+
+
+def _keyring_lookup(service: str, name: str) -> Optional[str]:
+    try:
+        import keyring
+    except ImportError:
+        return None
+    try:
+        return keyring.get_password(service, name)
+    except Exception:
+        # No keyring backend on this machine, e.g. a headless server
+        return None
+
+
+def env_api_key(provider: str, environ: Optional[Mapping[str, str]] = None, env: Optional[str] = None) -> Optional[Credential]:
+    """The key of a provider in the environment, from the variable named in config.toml or the provider's usual ones"""
+    environ = os.environ if environ is None else environ
+    names = ((env,) if env else ()) + PROVIDER_ENV_VARS.get(provider, ())
+    for name in names:
+        value = environ.get(name, "").strip()
+        if value:
+            return Credential(SecretString(value), SOURCE_ENV, name)
+    return None
+
+
+def resolve_api_key(
+    provider: str,
+    config: dict,
+    environ: Optional[Mapping[str, str]] = None,
+    keyring_lookup: Callable[[str, str], Optional[str]] = _keyring_lookup,
+) -> Optional[Credential]:
+    """
+    The API key of a provider by the precedence above, None if there is none
+
+    Raises:
+        ValueError: If the credentials in config.toml are invalid, or the key file is missing or empty
+    """
+    settings = credential_settings_from_config(config, provider)
+    credential = env_api_key(provider, environ, settings.env)
+    if credential is not None:
+        return credential
+    if settings.api_key_file:
+        path = Path(settings.api_key_file).expanduser()
+        if not path.is_file():
+            raise ValueError(f"credentials.{provider}.api_key_file does not exist: {path}")
+        value = path.read_text(encoding="utf-8").strip()
+        if not value:
+            raise ValueError(f"credentials.{provider}.api_key_file is empty: {path}")
+        return Credential(SecretString(value), SOURCE_FILE, str(path))
+    if settings.api_key is not None:
+        return Credential(settings.api_key, SOURCE_CONFIG)
+    if settings.keyring:
+        value = keyring_lookup(KEYRING_SERVICE, provider)
+        if value and value.strip():
+            return Credential(SecretString(value.strip()), SOURCE_KEYRING, f"{KEYRING_SERVICE}/{provider}")
+    return None
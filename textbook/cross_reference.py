@@ -0,0 +1,232 @@
+# Cross references within a document ("see Theorem 3.2", "Eq. (4.7)", "Section 2.3")
+# Numbered blocks (theorems, definitions, figures, displayed equations, ...) are found where they are stated and
+# collected into an index per document. References in the text are then resolved against that index and the
+# TOC, and turned into anchors the reader view and generated problems can link to.
+
+import re
+from dataclasses import dataclass
+from typing import Dict, Iterable, List, Optional, Tuple
+
+from textbook.database import TextBookDatabase
+
+# Entity kinds, stored on entity_info.entity_kind
+ENTITY_THEOREM = "theorem"
+ENTITY_LEMMA = "lemma"
+ENTITY_COROLLARY = "corollary"
+ENTITY_PROPOSITION = "proposition"
+ENTITY_DEFINITION = "definition"
+ENTITY_EXAMPLE = "example"
+ENTITY_REMARK = "remark"
+ENTITY_EXERCISE = "exercise"
+ENTITY_FIGURE = "figure"
+ENTITY_TABLE = "table"
+ENTITY_EQUATION = "equation"
+ENTITY_KINDS = (ENTITY_THEOREM, ENTITY_LEMMA, ENTITY_COROLLARY, ENTITY_PROPOSITION, ENTITY_DEFINITION, ENTITY_EXAMPLE, ENTITY_REMARK, ENTITY_EXERCISE, ENTITY_FIGURE, ENTITY_TABLE, ENTITY_EQUATION)
+
+# Reference targets that are part of the TOC rather than the entity index
+TARGET_SECTION = "section"
+TARGET_CHAPTER = "chapter"
+
+# Many books number theorems, lemmas, corollaries and propositions with one shared counter
+SHARED_COUNTER_KINDS = (ENTITY_THEOREM, ENTITY_LEMMA, ENTITY_COROLLARY, ENTITY_PROPOSITION, ENTITY_DEFINITION)
+
+# How an entity was found, stored on entity_info.entity_source
+ENTITY_SOURCE_PATTERN = "pattern"  # Numbered block headers and equation numbers in the page text
+ENTITY_SOURCE_LLM = "llm"  # Schema extraction per chapter, also finds unnumbered and named entities
+
+MAX_STATEMENT_LENGTH = 300  # Characters of the stating line kept as the entity statement
+
+_KIND_WORDS = {
+    "theorem": ENTITY_THEOREM, "thm": ENTITY_THEOREM,
+    "lemma": ENTITY_LEMMA,
+    "corollary": ENTITY_COROLLARY, "cor": ENTITY_COROLLARY,
+    "proposition": ENTITY_PROPOSITION, "prop": ENTITY_PROPOSITION,
+    "definition": ENTITY_DEFINITION, "def": ENTITY_DEFINITION, "defn": ENTITY_DEFINITION,
+    "example": ENTITY_EXAMPLE, "ex": ENTITY_EXAMPLE,
+    "remark": ENTITY_REMARK,
+    "exercise": ENTITY_EXERCISE,
+    "figure": ENTITY_FIGURE, "fig": ENTITY_FIGURE,
+    "table": ENTITY_TABLE,
+    "equation": ENTITY_EQUATION, "eq": ENTITY_EQUATION, "eqs": ENTITY_EQUATION,
+    "section": TARGET_SECTION, "sec": TARGET_SECTION,
+    "chapter": TARGET_CHAPTER, "ch": TARGET_CHAPTER, "chap": TARGET_CHAPTER,
+}
+
+_LABEL = r"\d+(?:\.\d+)*[a-z]?"
+# A numbered block starts a line: "Theorem 3.2 (Bolzano-Weierstrass). Every bounded ..."
+_ENTITY_HEADER = re.compile(
+    rf"^\s*(?:\*\*)?(Theorem|Lemma|Corollary|Proposition|Definition|Example|Remark|Exercise|Figure|Fig\.|Table)\s+({_LABEL})\.?(?:\*\*)?\s*(?:\(([^)]{{1,80}})\))?",
+    re.IGNORECASE | re.MULTILINE,
+)
+# A displayed equation ends its line with its number: "f(x) = x^2   (4.7)"
+_EQUATION_LABEL = re.compile(rf"^(.*[=<>≤≥\\].*?)\s*\(\s*({_LABEL})\s*\)\s*$", re.MULTILINE)
+_REFERENCE = re.compile(
+    rf"\b(?P<word>Theorems?|Thm\.|Lemmas?|Corollary|Corollaries|Cor\.|Propositions?|Prop\.|Definitions?|Defn?\.|Examples?|Ex\.|Remarks?|Exercises?|Figures?|Figs?\.|Tables?|Equations?|Eqs?\.|Sections?|Sec\.|Chapters?|Chap\.|Ch\.)\s*(?:\((?P<parenthesized>{_LABEL})\)|(?P<label>{_LABEL}))"
+    rf"|§\s*(?P<section>{_LABEL})"
+    rf"|(?<![\w(])\((?P<bare>{_LABEL})\)(?=[\s,.;:)]|$)",
+)
+
+
+@dataclass
+class LabeledEntity:
+    kind: str
+    label: str
+    page_number: int
+    name: Optional[str] = None
+    statement: Optional[str] = None
+
+
+@dataclass
+class Reference:
+    kind: str
+    label: str
+    text: str  # The matched text, e.g. "Theorem 3.2"
+    page_number: int
+    start: int  # Offsets of text in the page content
+    end: int
+    bare: bool = False  # A number in parentheses without a kind word, only kept when it resolves
+
+
+@dataclass
+class ResolvedTarget:
+    anchor: str
+    page_number: Optional[int]
+    entity_id: Optional[int] = None
+    section_id: Optional[int] = None
+    chapter_id: Optional[int] = None
+
+
+def _kind(word: str) -> Optional[str]:
+    word = word.lower().rstrip(".")
+    if word in _KIND_WORDS:
+        return _KIND_WORDS[word]
+    # Plurals ("Theorems 3.1", "Corollaries")
+    for suffix, replacement in (("ies", "y"), ("s", "")):
+        if word.endswith(suffix) and word[: -len(suffix)] + replacement in _KIND_WORDS:
+            return _KIND_WORDS[word[: -len(suffix)] + replacement]
+    return None
+
+
+def entity_anchor(entity_id: int) -> str:
+    return f"entity-{entity_id}"
+
+
+def section_anchor(section_id: int) -> str:
+    return f"section-{section_id}"
+
+
+def chapter_anchor(chapter_id: int) -> str:
+    return f"chapter-{chapter_id}"
+
+
+# ------------------------------------------------------------
+# Detection
+# ------------------------------------------------------------
+
+def find_labeled_entities(page_text: str, page_number: int) -> List[LabeledEntity]:
+    """Find the numbered blocks stated on a page, and the displayed equations numbered on it"""
+    entities: List[LabeledEntity] = []
+    for match in _ENTITY_HEADER.finditer(page_text):
+        kind = _kind(match.group(1))
+        if kind is None:
+            continue
+        line_end = page_text.find("\n", match.start())
+        statement = page_text[match.start(): line_end if line_end != -1 else len(page_text)].strip()
+        entities.append(LabeledEntity(kind, match.group(2), page_number, match.group(3), statement[:MAX_STATEMENT_LENGTH]))
+
+    for match in _EQUATION_LABEL.finditer(page_text):
+        entities.append(LabeledEntity(ENTITY_EQUATION, match.group(2), page_number, None, match.group(1).strip()[:MAX_STATEMENT_LENGTH]))
+    return entities
+
+
+def find_references(page_text: str, page_number: int) -> List[Reference]:
+    """Find the references on a page, skipping the headers of the blocks stated on it"""
+    header_starts = {match.start(1) for match in _ENTITY_HEADER.finditer(page_text)}
+    equation_labels = {match.start(2) for match in _EQUATION_LABEL.finditer(page_text)}
+
+    references: List[Reference] = []
+    for match in _REFERENCE.finditer(page_text):
+        if match.group("word"):
+            if match.start("word") in header_starts:
+                continue
+            kind, label = _kind(match.group("word")), match.group("parenthesized") or match.group("label")
+        elif match.group("section"):
+            kind, label = TARGET_SECTION, match.group("section")
+        else:
+            # A bare "(4.7)" is an equation reference, unless it is the number of an equation displayed here
+            if match.start("bare") in equation_labels:
+                continue
+            references.append(Reference(ENTITY_EQUATION, match.group("bare"), match.group(0), page_number, match.start(), match.end(), bare=True))
+            continue
+        if kind is not None:
+            references.append(Reference(kind, label, match.group(0), page_number, match.start(), match.end()))
+    return references
+
+
+# ------------------------------------------------------------
+# Resolution
+# ------------------------------------------------------------
+
+class CrossReferenceIndex:
+    """
+    Resolves references against the entity index and the TOC of one document
+
+    Args:
+        entities: (entity_id, kind, label, page_number) of the indexed entities
+        sections: (section_id, index_string, start_page_number) of the sections
+        chapters: (chapter_id, index_string, start_page_number) of the chapters
+    """
+
+    def __init__(self, entities: Iterable[Tuple[int, str, Optional[str], int]], sections: Iterable[Tuple[int, Optional[str], int]] = (), chapters: Iterable[Tuple[int, Optional[str], int]] = ()):
+        self.entities: Dict[Tuple[str, str], Tuple[int, int]] = {}
+        self.shared: Dict[str, Tuple[int, int]] = {}
+        # The first occurrence wins, later ones are usually running headers or restatements
+        for entity_id, kind, label, page_number in entities:
+            if label is None:
+                continue
+            self.entities.setdefault((kind, label), (entity_id, page_number))
+            if kind in SHARED_COUNTER_KINDS:
+                self.shared.setdefault(label, (entity_id, page_number))
+        self.sections = {index_string: (section_id, page) for section_id, index_string, page in sections if index_string}
+        self.chapters = {index_string: (chapter_id, page) for chapter_id, index_string, page in chapters if index_string}
+
+    def resolve(self, kind: str, label: str) -> Optional[ResolvedTarget]:
+        if (kind, label) in self.entities:
+            entity_id, page_number = self.entities[(kind, label)]
+            return ResolvedTarget(entity_anchor(entity_id), page_number, entity_id=entity_id)
+        if kind in SHARED_COUNTER_KINDS and label in self.shared:
+            entity_id, page_number = self.shared[label]
+            return ResolvedTarget(entity_anchor(entity_id), page_number, entity_id=entity_id)
+        if kind == TARGET_SECTION:
+            if label in self.sections:
+                section_id, page_number = self.sections[label]
+                return ResolvedTarget(section_anchor(section_id), page_number, section_id=section_id)
+            # "Section 3" in a book whose top level is chapters
+            kind = TARGET_CHAPTER
+        if kind == TARGET_CHAPTER and label in self.chapters:
+            chapter_id, page_number = self.chapters[label]
+            return ResolvedTarget(chapter_anchor(chapter_id), page_number, chapter_id=chapter_id)
+        return None
+
+
+def load_cross_reference_index(database: TextBookDatabase, book_id: int) -> CrossReferenceIndex:
+    return CrossReferenceIndex(
+        [(entity.entity_id, entity.entity_kind, entity.entity_label, entity.page_number) for entity in database.get_entities_by_book_id(book_id)],
+        [(section.section_id, section.book_index_string, section.start_page_number) for section in database.get_sections_by_book_id(book_id)],
+        [(chapter.chapter_id, chapter.book_index_string, chapter.start_page_number) for chapter in database.get_chapters_by_book_id(book_id)],
+    )
+
+
+def link_references(text: str, index: CrossReferenceIndex) -> str:
+    """Turn the resolvable references in text into markdown links to their anchors"""
+    linked: List[str] = []
+    position = 0
+    for reference in find_references(text, -1):
+        target = index.resolve(reference.kind, reference.label)
+        if target is None:
+            continue
+        linked.append(text[position: reference.start])
+        linked.append(f"[{reference.text}](#{target.anchor})")
+        position = reference.end
+    linked.append(text[position:])
+    return "".join(linked)
@@ -1,36 +1,104 @@
 # The TextBookContext class is used to read/write the context of a textbook to database
 # Currently implemented with SQLite3, with room for PostgreSQL implementation later
 # The following tables are used to store the context of the textbook:
-# book_info: table of book information, a table with columns: book_id (auto-increment), book_name (str), book_author (str),  book_pages (int), book_keywords (str), book_summary (str), book_embedding (BLOB),
-# chapter_info: table of chapter summaries, a table with columns: chapter_id (auto-increment), start_page_number (int), end_page_number, summary, book_id, book_index_string (str)
+# book_info: table of book information, a table with columns: book_id (auto-increment), book_name (str), book_author (str),  book_pages (int), book_keywords (str), book_summary (str), book_embedding (BLOB), book_ingestion_mode (str), book_source_type (str), book_source_url (str), archived_at (datetime), suspended_at (datetime)
+# chapter_info: table of chapter summaries, a table with columns: chapter_id (auto-increment), start_page_number (int), end_page_number, summary, book_id, book_index_string (str), start_seconds (float), end_seconds (float), archived_at (datetime), suspended_at (datetime)
 # section_info: table of sections, a table with columns: section_id (auto-increment), start_page_number (int), end_page_number (int), summary, chapter_id, book_id, book_index_string (str)
-# page_info: table of page summaries, a table with columns: page_id (auto-increment), page_number (not auto-increment), summary, embedding (BLOB), related_chapters (BLOB), related_sections (BLOB), book_id
-# exercise_info: table of exercise information, a table with columns: exercise_id (not auto-increment), exercise_description, page_number (int), related_chapters (BLOB), related_sections (BLOB), embedding (BLOB), book_id
+# page_info: table of page summaries, a table with columns: page_id (auto-increment), page_number (not auto-increment), summary, embedding (BLOB), related_chapters (BLOB), related_sections (BLOB), transcription (str), transcription_confidence (float), transcription_source (str), start_seconds (float), end_seconds (float), book_id
+# exercise_info: table of exercise information, a table with columns: exercise_id (not auto-increment), exercise_description, page_number (int), related_chapters (BLOB), related_sections (BLOB), embedding (BLOB), exercise_type (str), starter_code (str), solution_code (str), code_language (str), book_id
 # exercise_details: table of exercise details, a table with columns: exercise_id (not auto-increment), study_guide (str), estimated_time_to_complete (int), difficulty_level (int), chapter_id, section_id, book_id 
+# entity_info: table of numbered entities (theorems, definitions, equations, ...), a table with columns: entity_id (auto-increment), entity_kind (str), entity_label (str), entity_name (str), statement (str), page_number (int), chapter_id, section_id, entity_source (str), book_id
+# cross_reference_info: table of in-text references, a table with columns: reference_id (auto-increment), reference_kind (str), reference_label (str), reference_text (str), page_number (int), char_start (int), char_end (int), target_entity_id, target_section_id, target_chapter_id, target_page_number (int), book_id
+# flashcard_info: table of flashcards, a table with columns: card_id (auto-increment), front (str), back (str), entity_id, entity_kind (str), chapter_id, page_number (int), chemical_formula (str), smiles (str), created_at (datetime), archived_at (datetime), suspended_at (datetime), book_id
+# card_state_info: table of the review schedule of a flashcard per user, a table with columns: state_id (auto-increment), card_id, user_id (str), interval_days (float), ease (float), repetitions (int), lapses (int), due_at (datetime), introduced_at (datetime), last_reviewed_at (datetime), book_id
+# review_info: table of flashcard reviews and the schedule they produced, a table with columns: review_id (auto-increment), card_id, user_id (str), grade (int), duration_ms (int), interval_days (float), ease (float), repetitions (int), lapses (int), due_at (datetime), previous_interval_days (float), previous_ease (float), previous_repetitions (int), previous_lapses (int), previous_due_at (datetime), previous_reviewed_at (datetime), reviewed_at (datetime), undone_at (datetime), book_id
+# attempt_info: table of graded exercise attempts, a table with columns: attempt_id (auto-increment), exercise_id, answer (str), study_mode (str), score (float), is_correct (bool), confidence (float), feedback (str), grading_context (str), created_at (datetime), voided_at (datetime), book_id
+# grading_judgment_info: table of every grading of an attempt, a table with columns: judgment_id (auto-increment), attempt_id, model_name (str), reason (str), score (float), is_correct (bool), confidence (float), feedback (str), created_at (datetime)
+# misconception_info: table of misconceptions found when grading, a table with columns: misconception_id (auto-increment), description (str), attempt_id, exercise_id, created_at (datetime), book_id
+# dispute_info: table of disputed gradings and their manual review, a table with columns: dispute_id (auto-increment), attempt_id, comment (str), status (str), original_score (float), original_is_correct (bool), escalated_score (float), escalated_is_correct (bool), resolved_score (float), resolved_is_correct (bool), reviewer_note (str), created_at (datetime), resolved_at (datetime), book_id
+# reflection_info: table of the reflection journal, self-explanations of solved exercises, a table with columns: reflection_id (auto-increment), attempt_id, exercise_id, explanation (str), completeness (float), solution_steps (str), missing_steps (str), feedback (str), chapter_id, created_at (datetime), book_id
+# question_timing_info: table of how quiz questions were answered or skipped, a table with columns: timing_id (auto-increment), exercise_id, attempt_id, think_seconds (float), answer_changes (int), skipped (bool), created_at (datetime), book_id
+# highlight_info: table of passages highlighted while reading, a table with columns: highlight_id (auto-increment), user_id (str), page_number (int), char_start (int), char_end (int), text (str), note (str), created_at (datetime), book_id
+# reading_item_info: table of the incremental reading queue, sections and excerpts a user reads in spaced portions, a table with columns: reading_id (auto-increment), user_id (str), section_id, page_number (int), title (str), text (str), interval_days (float), read_count (int), due_at (datetime), last_read_at (datetime), done_at (datetime), created_at (datetime), book_id
+# chapter_quiz_info: table of the diagnostic quizzes given after a chapter is read, a table with columns: quiz_id (auto-increment), user_id (str), exercise_ids (str), created_at (datetime), completed_at (datetime), chapter_id, book_id
+# syllabus_topic_info: table of the topics of a user's course syllabus by week, a table with columns: topic_id (auto-increment), user_id (str), week (int), week_starts_at (datetime), title (str), description (str), chapter_id, created_at (datetime), book_id
+# smart_filter_info: table of the saved searches of exercises per user, a table with columns: filter_id (auto-increment), user_id (str), name (str), criteria (str), created_at (datetime), updated_at (datetime), book_id
+# user_preferences_info: table of study preferences per user, a table with columns: user_id (str), default_difficulty (int), daily_new_card_limit (int), output_language (str), notifications_enabled (bool), notification_hour (int), scheduler (str), load_balancing (bool), updated_at (datetime)
+# job_info: table of background jobs, a table with columns: job_id (str), job_kind (str), status (str), progress (float), stage (str), progress_detail (str), params (str), result (str), error (str), error_category (str), created_at (datetime), started_at (datetime), finished_at (datetime)
+# job_event_info: table of the status transitions of background jobs, a table with columns: event_id (auto-increment), job_id, status (str), created_at (datetime)
+# workspace_info: table of the workspaces of instructors, a table with columns: workspace_id (auto-increment), name (str), created_at (datetime)
+# workspace_member_info: table of the roster of workspaces, a table with columns: member_id (auto-increment), workspace_id, user_id (str), role (str), share_attempts (bool), joined_at (datetime)
+# assignment_info: table of the readings and quizzes assigned to a workspace, a table with columns: assignment_id (auto-increment), workspace_id, assignment_kind (str), chapter_id, due_at (datetime), created_at (datetime), book_id
+# api_token_info: table of scoped API tokens for scripts and integrations, a table with columns: token_id (auto-increment), user_id (str), name (str), scopes (str), token_sha256 (str), created_at (datetime), last_used_at (datetime), revoked_at (datetime)
+# document_info: table of the document library, every ingested book, problem set or uploaded PDF, a table with columns: document_id (auto-increment), title (str), author (str), source_path (str), page_count (int), ocr_status (str), detected_type (str), tags (str), job_id (str), summary (str), has_toc (bool), toc (str), ingestion_settings (str), outline (str), created_at (datetime), updated_at (datetime), book_id
+# content_chunk_info: table of the OCR output of a document split into pages and sections, a table with columns: chunk_id (auto-increment), chunk_kind (str), chunk_index (int), title (str), level (int), page_start (int), page_end (int), char_start (int), char_end (int), text (str), document_id
+# problem_info: table of the problems extracted from a document's OCR output, a table with columns: problem_id (auto-increment), problem_number (str), statement (str), difficulty_hint (str), figures (str), page_number (int), section_index (int), quality_score (float), quality_status (str), quality_issues (str), chemical_formula (str), smiles (str), model_name (str), temperature (float), seed (int), prompt_version (int), source_sha256 (str), created_at (datetime), document_id
+# hint_info: table of the hint ladders of problems, a table with columns: hint_id (auto-increment), level (int), tier (str), text (str), model_name (str), created_at (datetime), problem_id
+# problem_state_info: table of the review schedule of a problem per user, a table with columns: state_id (auto-increment), problem_id, user_id (str), interval_days (float), ease (float), repetitions (int), lapses (int), due_at (datetime), introduced_at (datetime), last_reviewed_at (datetime), document_id
+# problem_review_info: table of the reviews of problems, a table with columns: review_id (auto-increment), problem_id, user_id (str), grade (int), interval_days (float), ease (float), repetitions (int), lapses (int), due_at (datetime), reviewed_at (datetime), document_id
+# glossary_term_info: table of the glossaries of documents, a table with columns: term_id (auto-increment), term (str), definition (str), page_number (int), document_id
+# tutoring_message_info: table of the tutoring session history, questions about passages and the explanations given, a table with columns: message_id (auto-increment), session_id (str), role (str), content (str), char_start (int), char_end (int), created_at (datetime), document_id
+# solution_info: table of the stepwise solutions generated for problems, a table with columns: solution_id (auto-increment), steps (str), final_answer (str), hints (str), model_name (str), created_at (datetime), problem_id
+# numeric_answer_info: table of the numeric answers of problems, a value with its unit checked without the LLM, a table with columns: numeric_answer_id (auto-increment), value (float), unit (str), abs_tolerance (float), rel_tolerance (float), created_at (datetime), problem_id
+# prompt_log_info: table of LLM prompts and responses kept for debugging, a table with columns: log_id (auto-increment), job_id (str), model_name (str), schema_name (str), prompt (str), response (str), error (str), prompt_chars (int), response_chars (int), truncated (bool), created_at (datetime)
+# study_event_info: table of the append-only stream of study activity, a table with columns: event_id (auto-increment), event_kind (str), user_id (str), subject_kind (str), subject_id (int), payload (str), occurred_at (datetime), recorded_at (datetime), book_id (int), document_id (int)
+# usage_info: table of the tokens of every LLM call and their estimated cost, a table with columns: usage_id (auto-increment), job_id (str), job_kind (str), document_id (int), model_name (str), input_tokens (int), output_tokens (int), estimated (bool), cost (float), created_at (datetime)
+# response_cache_info: table of the validated LLM responses kept to answer identical requests again, a table with columns: cache_key (str), model_name (str), schema_name (str), response (str), hits (int), created_at (datetime), last_used_at (datetime)
+# export_fingerprint_info: table of the fingerprints embedded in exports, to trace leaked copies to who exported them, a table with columns: fingerprint (str), instance_id (str), user_id (str), export_kind (str), subject_id (int), created_at (datetime)
+# artifact_info: table of files stored on disk for a book, a table with columns: artifact_id (auto-increment), artifact_kind (str), artifact_path (str), artifact_sha256 (str), artifact_size (int), created_at (datetime), book_id
+# Opening a database creates the tables it lacks, and adds the columns of newer versions to the tables it has, so
+# databases created by an earlier version are upgraded in place. Columns are only ever added, never changed.
 
 import os
+from datetime import datetime, timezone
 from pathlib import Path
-from typing import Optional, List
+from typing import Dict, Optional, List, Tuple
 from sqlalchemy import (
     create_engine,
     String,
     Integer,
+    Float,
+    Boolean,
     Text,
     LargeBinary,
+    DateTime,
     ForeignKey,
     Index,
     UniqueConstraint,
+    func,
+    inspect,
+    or_,
+    select,
+    text,
 )
 from sqlalchemy.orm import (
     DeclarativeBase,
     Mapped,
     mapped_column,
     relationship,
+    selectinload,
     Session,
 )
 from sqlalchemy.engine import Engine
+from sqlalchemy.orm.attributes import flag_modified
 from sqlalchemy.exc import IntegrityError
 
+from textbook.compression import CompressedText
+from textbook.status import apply_status
+from textbook.study_events import (
+    EVENT_ATTEMPTED,
+    EVENT_GRADED,
+    EVENT_READ,
+    EVENT_REVIEW_UNDONE,
+    EVENT_REVIEWED,
+    EVENT_VOIDED,
+    SUBJECT_CARD,
+    SUBJECT_EXERCISE,
+    SUBJECT_PROBLEM,
+    SUBJECT_READING,
+    encode_payload,
+)
+
 
 class Base(DeclarativeBase):
     """Base class for all SQLAlchemy models"""
@@ -51,6 +119,11 @@ class BookInfo(Base):
     book_file_name: Mapped[Optional[str]] = mapped_column(String, nullable=True)
     book_toc_end_page: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
     book_alignment_offset: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    book_ingestion_mode: Mapped[Optional[str]] = mapped_column(String, nullable=True)  # "printed" or "handwritten"
+    book_source_type: Mapped[Optional[str]] = mapped_column(String, nullable=True)  # "pdf", "image", "web_article", "transcript" or "notebook"
+    book_source_url: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    archived_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)  # Hidden from quizzes, reviews and listings
+    suspended_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)  # Left out of review queues
     
     # Relationships to other tables
     chapters: Mapped[list["ChapterInfo"]] = relationship(
@@ -73,9 +146,69 @@ class BookInfo(Base):
         back_populates="book",
         cascade="all, delete-orphan"
     )
+    artifacts: Mapped[list["ArtifactInfo"]] = relationship(
+        "ArtifactInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    entities: Mapped[list["EntityInfo"]] = relationship(
+        "EntityInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    cross_references: Mapped[list["CrossReferenceInfo"]] = relationship(
+        "CrossReferenceInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    flashcards: Mapped[list["FlashcardInfo"]] = relationship(
+        "FlashcardInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    attempts: Mapped[list["AttemptInfo"]] = relationship(
+        "AttemptInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    highlights: Mapped[list["HighlightInfo"]] = relationship(
+        "HighlightInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    reading_items: Mapped[list["ReadingItemInfo"]] = relationship(
+        "ReadingItemInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    chapter_quizzes: Mapped[list["ChapterQuizInfo"]] = relationship(
+        "ChapterQuizInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    syllabus_topics: Mapped[list["SyllabusTopicInfo"]] = relationship(
+        "SyllabusTopicInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    smart_filters: Mapped[list["SmartFilterInfo"]] = relationship(
+        "SmartFilterInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    assignments: Mapped[list["AssignmentInfo"]] = relationship(
+        "AssignmentInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
+    documents: Mapped[list["DocumentInfo"]] = relationship(
+        "DocumentInfo",
+        back_populates="book",
+        cascade="all, delete-orphan"
+    )
 
     def __repr__(self) -> str:
-        return f"BookInfo(book_id={self.book_id}, book_name={self.book_name}, book_author={self.book_author}, book_pages={self.book_pages}, book_keywords={self.book_keywords}, book_summary={self.book_summary}, book_embedding={self.book_embedding}, book_file_name={self.book_file_name}, book_toc_end_page={self.book_toc_end_page}, book_alignment_offset={self.book_alignment_offset})"
+        return f"BookInfo(book_id={self.book_id}, book_name={self.book_name}, book_author={self.book_author}, book_pages={self.book_pages}, book_keywords={self.book_keywords}, book_summary={self.book_summary}, book_embedding={self.book_embedding}, book_file_name={self.book_file_name}, book_toc_end_page={self.book_toc_end_page}, book_alignment_offset={self.book_alignment_offset}, book_ingestion_mode={self.book_ingestion_mode}, book_source_type={self.book_source_type}, book_source_url={self.book_source_url}, archived_at={self.archived_at}, suspended_at={self.suspended_at})"
     
     def __str__(self) -> str:
         return self.__repr__()
@@ -90,6 +223,10 @@ class ChapterInfo(Base):
         end_page_number: The page number of the last page of the chapter
         summary: The summary of the chapter
         book_index_string: The index string of the chapter
+        start_seconds: Where the chapter starts in the source video, for transcripts
+        end_seconds: Where the chapter ends in the source video, for transcripts
+        archived_at: When the chapter's deck was archived, hiding it from quizzes, reviews and listings
+        suspended_at: When the chapter's deck was suspended, leaving its cards out of review queues
         book_id: The ID of the book
     """
     
@@ -101,6 +238,10 @@ class ChapterInfo(Base):
     end_page_number: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
     summary: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
     book_index_string: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    start_seconds: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    end_seconds: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    archived_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    suspended_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
     book_id: Mapped[int] = mapped_column(
         Integer,
         ForeignKey("book_info.book_id", ondelete="CASCADE"),
@@ -189,6 +330,13 @@ class PageInfo(Base):
     embedding: Mapped[Optional[bytes]] = mapped_column(LargeBinary, nullable=True)
     related_chapters: Mapped[Optional[bytes]] = mapped_column(LargeBinary, nullable=True)
     related_sections: Mapped[Optional[bytes]] = mapped_column(LargeBinary, nullable=True)
+    # Vision LLM transcription of handwritten pages, replaced by the user's text when corrected
+    transcription: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    transcription_confidence: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    transcription_source: Mapped[Optional[str]] = mapped_column(String, nullable=True)  # "llm" or "manual"
+    # Time range in the source video covered by the page, for transcripts
+    start_seconds: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    end_seconds: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
     book_id: Mapped[int] = mapped_column(
         Integer,
         ForeignKey("book_info.book_id", ondelete="CASCADE"),
@@ -231,6 +379,10 @@ class ExerciseInfo(Base):
     related_chapters: Mapped[Optional[bytes]] = mapped_column(LargeBinary, nullable=True)
     related_sections: Mapped[Optional[bytes]] = mapped_column(LargeBinary, nullable=True)
     embedding: Mapped[Optional[bytes]] = mapped_column(LargeBinary, nullable=True)
+    exercise_type: Mapped[Optional[str]] = mapped_column(String, nullable=True)  # "text" (default) or "code"
+    starter_code: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    solution_code: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    code_language: Mapped[Optional[str]] = mapped_column(String, nullable=True)
     book_id: Mapped[int] = mapped_column(
         Integer,
         ForeignKey("book_info.book_id", ondelete="CASCADE"),
@@ -253,6 +405,13 @@ class ExerciseInfo(Base):
         cascade="all, delete-orphan",
         uselist=False
     )
+
+    # Timings of the answers and skips of the exercise in quizzes
+    timings: Mapped[list["QuestionTimingInfo"]] = relationship(
+        "QuestionTimingInfo",
+        back_populates="exercise",
+        cascade="all, delete-orphan"
+    )
     
     # Indexes for common queries
     __table_args__ = (
@@ -309,223 +468,3467 @@ class ExerciseDetails(Base):
     )
 
 
-class TextBookDatabase:
-    """Context manager for textbook database operations"""
-    
-    def __init__(
-        self,
-        db_path: Optional[str] = None,
-        db_type: str = "sqlite"
-    ):
-        """
-        Initialize TextBookContext
-        
-        Args:
-            db_path: Path to the database file (for SQLite) or connection string
-            db_type: Type of database ("sqlite" or "postgresql")
-        """
-        if db_type not in ("sqlite", "postgresql"):
-            raise ValueError(f"Unsupported database type: {db_type}")
-        
-        if db_type == "postgresql":
-            raise NotImplementedError("PostgreSQL support is not yet implemented")
-        
-        if db_path is None:
-            db_path = "textbook_context.db"
-        
-        # Ensure directory exists
-        db_dir = os.path.dirname(db_path)
-        if db_dir:
-            Path(db_dir).mkdir(parents=True, exist_ok=True)
-        
-        # Create SQLite connection string
-        db_url = f"sqlite:///{db_path}"
-        
-        self.db_path = db_path
-        self.db_type = db_type
-        self.engine: Engine = create_engine(db_url, echo=False)
-        
-        # Create all tables
-        Base.metadata.create_all(self.engine)
-        
-        # Create session (will be created per operation or can be used as context manager)
-        self._session: Optional[Session] = None
-    
-    @property
-    def session(self) -> Session:
-        """Get or create a database session (lazy creation)"""
-        if self._session is None:
-            self._session = Session(self.engine)
-        return self._session
-    
-    def new_session(self) -> Session:
-        """
-        Create a new database session
-        
-        Returns:
-            A new Session instance. Caller is responsible for closing it.
-        """
-        return Session(self.engine)
-    
-    def close(self):
-        """Close the database session"""
-        if self._session:
-            self._session.close()
-            self._session = None
-    
-    def __enter__(self):
-        return self
-    
-    def __exit__(self, exc_type, exc_val, exc_tb):
-        self.close()
-    
-    # Compatibility property for tests that access conn directly
-    @property
-    def conn(self):
-        """Compatibility property for backward compatibility with raw SQLite connection"""
-        # Return None to indicate SQLAlchemy is being used instead of raw connection
-        return None
-
-    
-
-    
-    # ------------------------------------------------------------
-    # Book related functions
-    # ------------------------------------------------------------
-
-    def get_all_books(self) -> list[BookInfo]:
-        """Get all books from the database"""
-        with self.new_session() as session:
-            return _query_all_books(session)
+class ArtifactInfo(Base):
+    """Model for files stored on disk for a book, with the digest recorded when they were written
 
-    def get_book_by_file_name(self, book_file_name: str) -> Optional[BookInfo]:
-        with self.new_session() as session:
-            return _query_book_by_file_name(session, book_file_name)
+    Args:
+        artifact_id: The ID of the artifact
+        artifact_kind: The kind of artifact (e.g. "source_pdf")
+        artifact_path: The path of the file, relative to the uploads directory
+        artifact_sha256: The SHA-256 digest of the file content
+        artifact_size: The size of the file in bytes
+        created_at: When the artifact was recorded
+        book_id: The ID of the book
+    """
+    __tablename__ = "artifact_info"
 
-    def create_book(self, book_name: str, book_author: str, book_keywords: str, book_file_name: str, page_count: int) -> BookInfo:
-        with self.new_session() as session:
-            book_info = _create_book_and_return_info(session, book_name, book_author, book_keywords, book_file_name, page_count)
-            if book_info is None:
-                raise ValueError("Failed to create book")
-            return book_info
-    
-    def get_book_toc_end_page(self, book_id: int, default_value: int) -> int:
-        with self.new_session() as session:
-            book_info = _query_book_by_id(session, book_id)
-            if book_info is not None and book_info.book_toc_end_page is not None:
-                return book_info.book_toc_end_page
-            return default_value
-        
-    def update_book_toc_end_page(self, book_id: int, toc_end_page: int) -> None:
-        with self.new_session() as session:
-            session.query(BookInfo).filter(BookInfo.book_id == book_id).update({BookInfo.book_toc_end_page: toc_end_page})
-            session.commit()
-    
-    def get_book_alignment_offset(self, book_id: int, default_value: int) -> int:
-        with self.new_session() as session:
-            book_info = _query_book_by_id(session, book_id)
-            if book_info is not None and book_info.book_alignment_offset is not None:
-                return book_info.book_alignment_offset
-            return default_value
-        
-    def update_book_alignment_offset(self, book_id: int, alignment_offset: int) -> None:
-        with self.new_session() as session:
-            session.query(BookInfo).filter(BookInfo.book_id == book_id).update({BookInfo.book_alignment_offset: alignment_offset})
-            session.commit()
-        
-    def delete_book_by_file_name(self, book_file_name: str) -> bool:
-        with self.new_session() as session:
-            book = _query_book_by_file_name(session, book_file_name)
-            if book is None:
-                return False
-            
-            session.delete(book)
-            session.commit()
-            return True
-    # ------------------------------------------------------------
-    # TOC related functions
-    # ------------------------------------------------------------
+    artifact_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    artifact_kind: Mapped[str] = mapped_column(String, nullable=False)
+    artifact_path: Mapped[str] = mapped_column(String, nullable=False)
+    artifact_sha256: Mapped[str] = mapped_column(String, nullable=False)
+    artifact_size: Mapped[int] = mapped_column(Integer, nullable=False)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
 
-    def delete_toc_by_book_id(self, book_id: int) -> None:
-        with self.new_session() as session:
-            _delete_sections_by_book_id(session, book_id)
-            _delete_chapters_by_book_id(session, book_id)
-            session.commit()
-    
-    # ------------------------------------------------------------
-    # Chapter related functions
-    # ------------------------------------------------------------
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="artifacts"
+    )
 
-    def try_create_chapter_info(self, book_id: int, title: str, index_string: str, start_page: int, end_page: int) -> Optional[int]:
-        with self.new_session() as session:
-            existing = _query_chapter_by_book_id_and_title(session, book_id, title)
-            if existing:
-                return existing.chapter_id
-            
-            chapter_info = ChapterInfo(
-                title=title,
-                book_index_string=index_string,
-                start_page_number=start_page,
-                end_page_number=end_page,
-                book_id=book_id
-            )
-            return _try_save(session, chapter_info)
-    
-    def get_chapters_by_book_id(self, book_id: int) -> list[ChapterInfo]:
-        with self.new_session() as session:
-            return _query_chapters_by_book_id(session, book_id)
-        
-    def get_chapters_by_book_id_and_page_range(self, book_id: int, start_page_number: int, end_page_number: int) -> List[ChapterInfo]:
-        with self.new_session() as session:
-            return _query_chapters_by_book_id_and_page_range(session, book_id, start_page_number, end_page_number)
-    
-    # ------------------------------------------------------------
-    # Section related functions
-    # ------------------------------------------------------------
+    # Indexes for common queries
+    __table_args__ = (
+        UniqueConstraint("artifact_path", name="uq_artifact_info_artifact_path"),
+        Index("idx_artifact_info_book_id", "book_id"),
+    )
 
-    def try_create_section_info(self, book_id: int, chapter_id: int, title: str, index_string: str, start_page: int, end_page: int) -> Optional[int]:
-        with self.new_session() as session:
-            existing = _query_section_by_book_id_and_title(session, book_id, title)
-            if existing:
-                return existing.section_id
-            
-            section_info = SectionInfo(
-                title=title,
-                book_index_string=index_string,
-                start_page_number=start_page,
-                end_page_number=end_page,
-                chapter_id=chapter_id,
-                book_id=book_id
-            )
-            return _try_save(session, section_info)
-        
-    def get_sections_by_book_id(self, book_id: int) -> list[SectionInfo]:
-        with self.new_session() as session:
-            return _query_sections_by_book_id(session, book_id)
 
-    def get_sections_by_book_id_and_page_range(self, book_id: int, start_page_number: int, end_page_number: int) -> List[SectionInfo]:
-        with self.new_session() as session:
-            return _query_sections_by_book_id_and_page_range(session, book_id, start_page_number, end_page_number)
+class EntityInfo(Base):
+    """Model for numbered entities of a book (theorems, definitions, equations, ...), the targets of cross references
 
-    # ------------------------------------------------------------
-    # Page related functions
-    # ------------------------------------------------------------
+    Args:
+        entity_id: The ID of the entity
+        entity_kind: The kind of entity (e.g. "theorem", "equation")
+        entity_label: The number of the entity in the book (e.g. "3.2")
+        entity_name: The name given to the entity, if any (e.g. "Bolzano-Weierstrass")
+        statement: The text stating the entity
+        page_number: The page the entity is stated on
+        chapter_id: The ID of the chapter containing the entity
+        section_id: The ID of the section containing the entity
+        entity_source: How the entity was found ("pattern" or "llm")
+        book_id: The ID of the book
+    """
+    __tablename__ = "entity_info"
 
-    def try_create_page_info(self, book_id: int, page_number: int, summary: str) -> Optional[int]:
-        with self.new_session() as session:
-            existing = _query_page_by_book_id_and_page_number(session, book_id, page_number)
-            if existing:
-                return existing.page_id
+    entity_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    entity_kind: Mapped[str] = mapped_column(String, nullable=False)
+    entity_label: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    entity_name: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    statement: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    page_number: Mapped[int] = mapped_column(Integer, nullable=False)
+    chapter_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("chapter_info.chapter_id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    section_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("section_info.section_id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    entity_source: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="entities"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_entity_info_book_id", "book_id"),
+        Index("idx_entity_info_book_id_entity_kind", "book_id", "entity_kind"),
+    )
+
+
+class CrossReferenceInfo(Base):
+    """Model for references within the text of a book ("see Theorem 3.2") and what they resolve to
+
+    Args:
+        reference_id: The ID of the reference
+        reference_kind: The kind of the referenced target (an entity kind, "section" or "chapter")
+        reference_label: The referenced number (e.g. "3.2")
+        reference_text: The text of the reference as it appears on the page
+        page_number: The page the reference appears on
+        char_start: The offset of the reference in the page content
+        char_end: The offset of the end of the reference in the page content
+        target_entity_id: The ID of the referenced entity, if resolved to one
+        target_section_id: The ID of the referenced section, if resolved to one
+        target_chapter_id: The ID of the referenced chapter, if resolved to one
+        target_page_number: The page of the resolved target
+        book_id: The ID of the book
+    """
+    __tablename__ = "cross_reference_info"
+
+    reference_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    reference_kind: Mapped[str] = mapped_column(String, nullable=False)
+    reference_label: Mapped[str] = mapped_column(String, nullable=False)
+    reference_text: Mapped[str] = mapped_column(String, nullable=False)
+    page_number: Mapped[int] = mapped_column(Integer, nullable=False)
+    char_start: Mapped[int] = mapped_column(Integer, nullable=False)
+    char_end: Mapped[int] = mapped_column(Integer, nullable=False)
+    target_entity_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("entity_info.entity_id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    target_section_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("section_info.section_id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    target_chapter_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("chapter_info.chapter_id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    target_page_number: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="cross_references"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_cross_reference_info_book_id_page_number", "book_id", "page_number"),
+        Index("idx_cross_reference_info_target_entity_id", "target_entity_id"),
+    )
+
+
+class FlashcardInfo(Base):
+    """Model for flashcards generated from a book
+
+    Args:
+        card_id: The ID of the card
+        front: The prompt side of the card
+        back: The answer side of the card
+        entity_id: The ID of the entity the card was generated from, if any
+        entity_kind: The kind of that entity, kept when the entity index is rebuilt
+        chapter_id: The ID of the chapter the card belongs to
+        page_number: The page the card's content is on
+        chemical_formula: The formula of the compound the card is about in Hill order, None for none
+        smiles: The canonical SMILES of its structure, None for none
+        created_at: When the card was created
+        archived_at: When the card was archived, hiding it from reviews and listings
+        suspended_at: When the card was suspended, leaving it out of review queues
+        book_id: The ID of the book
+    """
+    __tablename__ = "flashcard_info"
+
+    card_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    front: Mapped[str] = mapped_column(Text, nullable=False)
+    back: Mapped[str] = mapped_column(Text, nullable=False)
+    entity_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("entity_info.entity_id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    entity_kind: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    chapter_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("chapter_info.chapter_id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    page_number: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    chemical_formula: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    smiles: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    archived_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    suspended_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="flashcards"
+    )
+
+    # Review schedules and reviews of the card
+    states: Mapped[list["CardStateInfo"]] = relationship(
+        "CardStateInfo",
+        back_populates="card",
+        cascade="all, delete-orphan"
+    )
+    reviews: Mapped[list["ReviewInfo"]] = relationship(
+        "ReviewInfo",
+        back_populates="card",
+        cascade="all, delete-orphan"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_flashcard_info_book_id", "book_id"),
+        Index("idx_flashcard_info_chapter_id", "chapter_id"),
+    )
+
+
+class CardStateInfo(Base):
+    """Model for the review schedule of a flashcard for one user
+
+    Args:
+        state_id: The ID of the state
+        card_id: The ID of the card
+        user_id: The ID of the user
+        interval_days: The current interval between reviews in days
+        ease: The factor the interval grows by after a successful review
+        repetitions: The successful reviews since the card was last failed
+        lapses: How often the card was failed after it had been learned
+        due_at: When the card is due for review
+        introduced_at: When the user first reviewed the card
+        last_reviewed_at: When the user last reviewed the card
+        book_id: The ID of the book
+    """
+    __tablename__ = "card_state_info"
+
+    state_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    card_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("flashcard_info.card_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    interval_days: Mapped[float] = mapped_column(Float, nullable=False)
+    ease: Mapped[float] = mapped_column(Float, nullable=False)
+    repetitions: Mapped[int] = mapped_column(Integer, nullable=False)
+    lapses: Mapped[int] = mapped_column(Integer, nullable=False)
+    due_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    introduced_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    last_reviewed_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to card
+    card: Mapped[Optional["FlashcardInfo"]] = relationship(
+        "FlashcardInfo",
+        back_populates="states"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        UniqueConstraint("user_id", "card_id", name="uq_card_state_info_user_id_card_id"),
+        Index("idx_card_state_info_user_id_due_at", "user_id", "due_at"),
+    )
+
+
+class ReviewInfo(Base):
+    """Model for a review of a flashcard
+
+    Args:
+        review_id: The ID of the review
+        card_id: The ID of the card
+        user_id: The ID of the user
+        grade: 1 (again), 2 (hard), 3 (good) or 4 (easy)
+        duration_ms: How long the user took to answer, if known
+        interval_days, ease, repetitions, lapses, due_at: The schedule of the card after the review
+        previous_interval_days, previous_ease, previous_repetitions, previous_lapses, previous_due_at,
+        previous_reviewed_at: The schedule of the card before the review, None if the card was new
+        reviewed_at: When the card was reviewed
+        undone_at: When the review was undone, None if it counts
+        book_id: The ID of the book
+    """
+    __tablename__ = "review_info"
+
+    review_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    card_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("flashcard_info.card_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    grade: Mapped[int] = mapped_column(Integer, nullable=False)
+    duration_ms: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    interval_days: Mapped[float] = mapped_column(Float, nullable=False)
+    ease: Mapped[float] = mapped_column(Float, nullable=False)
+    repetitions: Mapped[int] = mapped_column(Integer, nullable=False)
+    lapses: Mapped[int] = mapped_column(Integer, nullable=False)
+    due_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    previous_interval_days: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    previous_ease: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    previous_repetitions: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    previous_lapses: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    previous_due_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    previous_reviewed_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    reviewed_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    undone_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to card
+    card: Mapped[Optional["FlashcardInfo"]] = relationship(
+        "FlashcardInfo",
+        back_populates="reviews"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_review_info_user_id_card_id", "user_id", "card_id"),
+    )
+
+
+class AttemptInfo(Base):
+    """Model for graded attempts at an exercise
+
+    Args:
+        attempt_id: The ID of the attempt
+        exercise_id: The ID of the exercise
+        answer: The submitted answer
+        study_mode: "practice", or "self_explanation" when the learner explains the solution afterwards
+        score: The grade between 0 and 1
+        is_correct: Whether the answer was judged correct
+        confidence: The confidence of the grader in the grade, between 0 and 1
+        feedback: The feedback of the grader
+        grading_context: The context the grading prompt was assembled from
+        created_at: When the attempt was graded
+        voided_at: When the learner voided the attempt, None if it counts
+        book_id: The ID of the book
+
+    The grade fields hold the judgment that counts, the last one in judgments. Voided attempts are kept but left
+    out of mastery, misconceptions and grading metrics.
+    """
+    __tablename__ = "attempt_info"
+
+    attempt_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    exercise_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("exercise_info.exercise_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    answer: Mapped[str] = mapped_column(Text, nullable=False)
+    study_mode: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    score: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    is_correct: Mapped[Optional[bool]] = mapped_column(Boolean, nullable=True)
+    confidence: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    feedback: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    grading_context: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    voided_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="attempts"
+    )
+
+    # Misconceptions found in the answer
+    misconceptions: Mapped[list["MisconceptionInfo"]] = relationship(
+        "MisconceptionInfo",
+        back_populates="attempt",
+        cascade="all, delete-orphan"
+    )
+
+    # Every grading of the answer, the initial one and escalations
+    judgments: Mapped[list["GradingJudgmentInfo"]] = relationship(
+        "GradingJudgmentInfo",
+        back_populates="attempt",
+        cascade="all, delete-orphan",
+        order_by="GradingJudgmentInfo.judgment_id"
+    )
+
+    # Disputes of the grade
+    disputes: Mapped[list["DisputeInfo"]] = relationship(
+        "DisputeInfo",
+        back_populates="attempt",
+        cascade="all, delete-orphan"
+    )
+
+    # Self-explanations written after the attempt
+    reflections: Mapped[list["ReflectionInfo"]] = relationship(
+        "ReflectionInfo",
+        back_populates="attempt",
+        cascade="all, delete-orphan"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_attempt_info_exercise_id", "exercise_id"),
+        Index("idx_attempt_info_book_id", "book_id"),
+    )
+
+
+class GradingJudgmentInfo(Base):
+    """Model for one grading of an attempt by one model
+
+    Args:
+        judgment_id: The ID of the judgment
+        attempt_id: The ID of the attempt
+        model_name: The model that graded
+        reason: Why the attempt was graded ("initial", "low_confidence", "dispute" or "external")
+        score: The grade between 0 and 1
+        is_correct: Whether the answer was judged correct
+        confidence: The confidence of the model in the grade, between 0 and 1
+        feedback: The feedback of the model
+        created_at: When the judgment was made
+    """
+    __tablename__ = "grading_judgment_info"
+
+    judgment_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    attempt_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("attempt_info.attempt_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    model_name: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    reason: Mapped[str] = mapped_column(String, nullable=False)
+    score: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    is_correct: Mapped[Optional[bool]] = mapped_column(Boolean, nullable=True)
+    confidence: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    feedback: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+
+    # Relationship to attempt
+    attempt: Mapped[Optional["AttemptInfo"]] = relationship(
+        "AttemptInfo",
+        back_populates="judgments"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_grading_judgment_info_attempt_id", "attempt_id"),
+    )
+
+
+class MisconceptionInfo(Base):
+    """Model for misconceptions found when grading an attempt
+
+    Args:
+        misconception_id: The ID of the misconception
+        description: What the learner got wrong, in one sentence
+        attempt_id: The ID of the attempt it was found in
+        exercise_id: The ID of the exercise of that attempt
+        created_at: When it was found
+        book_id: The ID of the book
+    """
+    __tablename__ = "misconception_info"
+
+    misconception_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    description: Mapped[str] = mapped_column(Text, nullable=False)
+    attempt_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("attempt_info.attempt_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    exercise_id: Mapped[int] = mapped_column(Integer, nullable=False)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to attempt
+    attempt: Mapped[Optional["AttemptInfo"]] = relationship(
+        "AttemptInfo",
+        back_populates="misconceptions"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_misconception_info_created_at", "created_at"),
+        Index("idx_misconception_info_book_id", "book_id"),
+    )
+
+
+class DisputeInfo(Base):
+    """Model for a learner's dispute of a grade, regraded by the escalation model and reviewed manually
+
+    Args:
+        dispute_id: The ID of the dispute
+        attempt_id: The ID of the disputed attempt
+        comment: Why the learner thinks the grade is unfair
+        status: "pending_review" until a reviewer resolves it, then "resolved"
+        original_score: The disputed score
+        original_is_correct: The disputed correctness
+        escalated_score: The score of the escalation model, if it regraded
+        escalated_is_correct: The correctness of the escalation model, if it regraded
+        resolved_score: The score the reviewer settled on
+        resolved_is_correct: The correctness the reviewer settled on
+        reviewer_note: The note of the reviewer
+        created_at: When the dispute was filed
+        resolved_at: When the dispute was resolved
+        book_id: The ID of the book
+    """
+    __tablename__ = "dispute_info"
+
+    dispute_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    attempt_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("attempt_info.attempt_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    comment: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    status: Mapped[str] = mapped_column(String, nullable=False)
+    original_score: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    original_is_correct: Mapped[Optional[bool]] = mapped_column(Boolean, nullable=True)
+    escalated_score: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    escalated_is_correct: Mapped[Optional[bool]] = mapped_column(Boolean, nullable=True)
+    resolved_score: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    resolved_is_correct: Mapped[Optional[bool]] = mapped_column(Boolean, nullable=True)
+    reviewer_note: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    resolved_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to attempt
+    attempt: Mapped[Optional["AttemptInfo"]] = relationship(
+        "AttemptInfo",
+        back_populates="disputes"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_dispute_info_status", "status"),
+        Index("idx_dispute_info_attempt_id", "attempt_id"),
+    )
+
+
+class ReflectionInfo(Base):
+    """Model for a self-explanation of a solved exercise, an entry of the reflection journal
+
+    Args:
+        reflection_id: The ID of the reflection
+        attempt_id: The ID of the attempt it explains
+        exercise_id: The ID of the exercise
+        explanation: The learner's explanation of their solution
+        completeness: How completely the explanation covers the solution steps, between 0 and 1
+        solution_steps: The steps of the solution it was evaluated against, one per line
+        missing_steps: The steps the explanation leaves out or gets wrong, one per line
+        feedback: The feedback on the explanation
+        chapter_id: The ID of the chapter of the exercise
+        created_at: When the reflection was written
+        book_id: The ID of the book
+    """
+    __tablename__ = "reflection_info"
+
+    reflection_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    attempt_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("attempt_info.attempt_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    exercise_id: Mapped[int] = mapped_column(Integer, nullable=False)
+    explanation: Mapped[str] = mapped_column(Text, nullable=False)
+    completeness: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    solution_steps: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    missing_steps: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    feedback: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    chapter_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("chapter_info.chapter_id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to attempt
+    attempt: Mapped[Optional["AttemptInfo"]] = relationship(
+        "AttemptInfo",
+        back_populates="reflections"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_reflection_info_book_id_chapter_id", "book_id", "chapter_id"),
+    )
+
+
+class QuestionTimingInfo(Base):
+    """Model for how a quiz question was answered or skipped
+
+    Args:
+        timing_id: The ID of the timing
+        exercise_id: The ID of the exercise asked
+        attempt_id: The ID of the graded attempt, None when the question was skipped
+        think_seconds: Seconds from showing the question to submitting or skipping it
+        answer_changes: How many times the answer was changed before it was submitted or skipped
+        skipped: Whether the question was skipped without an answer
+        created_at: When the question was answered or skipped
+        book_id: The ID of the book
+    """
+    __tablename__ = "question_timing_info"
+
+    timing_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    exercise_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("exercise_info.exercise_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    attempt_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("attempt_info.attempt_id", ondelete="CASCADE"),
+        nullable=True,
+    )
+    think_seconds: Mapped[float] = mapped_column(Float, nullable=False)
+    answer_changes: Mapped[int] = mapped_column(Integer, nullable=False, default=0)
+    skipped: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to exercise
+    exercise: Mapped[Optional["ExerciseInfo"]] = relationship(
+        "ExerciseInfo",
+        back_populates="timings"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_question_timing_info_book_id", "book_id"),
+        Index("idx_question_timing_info_exercise_id", "exercise_id"),
+    )
+
+
+class HighlightInfo(Base):
+    """Model for a passage highlighted while reading
+
+    Args:
+        highlight_id: The ID of the highlight
+        user_id: The ID of the user, chosen by the client
+        page_number: The page the passage is on
+        char_start: Where the passage starts in the page text
+        char_end: Where the passage ends in the page text
+        text: The highlighted passage, to find it again if the page text changes
+        note: The user's note on the passage
+        created_at: When the passage was highlighted
+        book_id: The ID of the book
+    """
+    __tablename__ = "highlight_info"
+
+    highlight_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    page_number: Mapped[int] = mapped_column(Integer, nullable=False)
+    char_start: Mapped[int] = mapped_column(Integer, nullable=False)
+    char_end: Mapped[int] = mapped_column(Integer, nullable=False)
+    text: Mapped[str] = mapped_column(Text, nullable=False)
+    note: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="highlights"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_highlight_info_book_id_user_id_page_number", "book_id", "user_id", "page_number"),
+    )
+
+
+class ReadingItemInfo(Base):
+    """Model for an item of a user's incremental reading queue, a section or an excerpt
+
+    Args:
+        reading_id: The ID of the reading item
+        user_id: The ID of the user, chosen by the client
+        section_id: The ID of the section to read, None for excerpts
+        page_number: The PDF page the section starts or the excerpt is on
+        title: The title shown in the queue
+        text: The excerpt, None for sections
+        interval_days: The interval after the last reading, the next one is longer
+        read_count: How often the item was read
+        due_at: When the item is due to be read next
+        last_read_at: When the item was last read
+        done_at: When the user finished the item, which takes it out of the queue
+        created_at: When the item was enqueued
+        book_id: The ID of the book
+    """
+    __tablename__ = "reading_item_info"
+
+    reading_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    section_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("section_info.section_id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    page_number: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    title: Mapped[str] = mapped_column(String, nullable=False)
+    text: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    interval_days: Mapped[float] = mapped_column(Float, default=0.0)
+    read_count: Mapped[int] = mapped_column(Integer, default=0)
+    due_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    last_read_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    done_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="reading_items"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_reading_item_info_user_id_due_at", "user_id", "due_at"),
+    )
+
+
+class ChapterQuizInfo(Base):
+    """Model for a diagnostic quiz given after a user read a chapter
+
+    Args:
+        quiz_id: The ID of the quiz
+        user_id: The ID of the user, chosen by the client
+        exercise_ids: The IDs of the exercises asked, as a JSON list
+        created_at: When the chapter was read and the quiz enqueued
+        completed_at: When every exercise of the quiz was attempted, None while the quiz is pending
+        chapter_id: The ID of the chapter read
+        book_id: The ID of the book
+    """
+    __tablename__ = "chapter_quiz_info"
+
+    quiz_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    exercise_ids: Mapped[str] = mapped_column(Text, nullable=False)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    completed_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    chapter_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("chapter_info.chapter_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="chapter_quizzes"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        UniqueConstraint("user_id", "chapter_id", name="uq_chapter_quiz_info_user_id_chapter_id"),
+        Index("idx_chapter_quiz_info_user_id_completed_at", "user_id", "completed_at"),
+    )
+
+
+class SyllabusTopicInfo(Base):
+    """Model for a topic of a user's course syllabus, aligned to the chapter of the book covering it
+
+    Args:
+        topic_id: The ID of the topic
+        user_id: The ID of the user, chosen by the client
+        week: The week of the course the topic is covered in, from 1
+        week_starts_at: When that week starts
+        title: The title of the topic, as in the syllabus
+        description: What the syllabus says about the topic
+        chapter_id: The ID of the chapter covering the topic, None if no chapter does
+        created_at: When the syllabus was imported
+        book_id: The ID of the book the course follows
+    """
+    __tablename__ = "syllabus_topic_info"
+
+    topic_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    week: Mapped[int] = mapped_column(Integer, nullable=False)
+    week_starts_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    title: Mapped[str] = mapped_column(String, nullable=False)
+    description: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    chapter_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("chapter_info.chapter_id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="syllabus_topics"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_syllabus_topic_info_user_id_week_starts_at", "user_id", "week_starts_at"),
+    )
+
+
+class SmartFilterInfo(Base):
+    """Model for a saved search of a book's exercises, e.g. the unsolved hard problems of chapters 5 to 7
+
+    Args:
+        filter_id: The ID of the filter
+        user_id: The ID of the user, chosen by the client
+        name: The name of the filter, unique per user
+        criteria: JSON of the criteria exercises are matched against (see smart_filters)
+        created_at: When the filter was saved
+        updated_at: When the filter was last changed
+        book_id: The ID of the book whose exercises the filter searches
+    """
+    __tablename__ = "smart_filter_info"
+
+    filter_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    name: Mapped[str] = mapped_column(String, nullable=False)
+    criteria: Mapped[str] = mapped_column(Text, nullable=False, default="{}")
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    updated_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="smart_filters"
+    )
+
+    __table_args__ = (
+        UniqueConstraint("user_id", "name", name="uq_smart_filter_info_user_id_name"),
+    )
+
+
+class UserPreferencesInfo(Base):
+    """Model for the study preferences of a user
+
+    Args:
+        user_id: The ID of the user, chosen by the client
+        default_difficulty: The difficulty between 1 and 5 generated exercises and quizzes aim for
+        daily_new_card_limit: How many never practiced items are introduced per day
+        output_language: The language generated content is written in, None to keep the language of the source
+        notifications_enabled: Whether to remind the user of due reviews
+        notification_hour: The hour of the day (UTC) to send reminders at
+        scheduler: "interleaved" to mix earlier chapters into practice, or "blocked" to stay on the current chapter
+        load_balancing: Whether review due dates are moved by a few days to the least loaded day
+        updated_at: When the preferences were last changed
+    """
+    __tablename__ = "user_preferences_info"
+
+    user_id: Mapped[str] = mapped_column(String, primary_key=True)
+    default_difficulty: Mapped[int] = mapped_column(Integer, nullable=False)
+    daily_new_card_limit: Mapped[int] = mapped_column(Integer, nullable=False)
+    output_language: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    notifications_enabled: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    notification_hour: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    scheduler: Mapped[str] = mapped_column(String, nullable=False)
+    load_balancing: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    updated_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc), onupdate=lambda: datetime.now(timezone.utc))
+
+
+class JobInfo(Base):
+    """Model for a background job
+
+    Args:
+        job_id: The ID of the job, a UUID
+        job_kind: What the job does, e.g. "ocr" or "analytics_export"
+        status: "pending", "running", "succeeded", "failed", "interrupted" or "cancelled"
+        progress: The share of the work done, between 0 and 1
+        stage: The step the job last reported progress in, e.g. "render" or "ocr"
+        progress_detail: What the job last reported doing, e.g. "page 12/80 rendered"
+        params: The JSON parameters the job runs with, enough to run it again
+        result: The JSON result of a job that succeeded
+        error: Why the job failed
+        error_category: The category of the LLM provider error the job failed with, e.g. "auth" or "quota"
+        group_id: The ID of the group the job was submitted in, None for jobs submitted alone
+        retry_of: The ID of the failed job this job retries, None for jobs submitted anew
+        created_at: When the job was submitted
+        started_at: When the job last started running
+        finished_at: When the job finished
+    """
+    __tablename__ = "job_info"
+
+    job_id: Mapped[str] = mapped_column(String, primary_key=True)
+    job_kind: Mapped[str] = mapped_column(String, nullable=False)
+    status: Mapped[str] = mapped_column(String, nullable=False)
+    progress: Mapped[float] = mapped_column(Float, nullable=False, default=0.0)
+    stage: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    progress_detail: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    params: Mapped[str] = mapped_column(Text, nullable=False, default="{}")
+    result: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    error: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    error_category: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    group_id: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    retry_of: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    started_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    finished_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+
+    # Every status the job went through
+    events: Mapped[list["JobEventInfo"]] = relationship(
+        "JobEventInfo",
+        back_populates="job",
+        cascade="all, delete-orphan",
+        order_by="JobEventInfo.event_id"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_job_info_status", "status"),
+        Index("idx_job_info_created_at", "created_at"),
+        Index("idx_job_info_group_id", "group_id"),
+        Index("idx_job_info_retry_of", "retry_of"),
+    )
+
+
+class JobEventInfo(Base):
+    """Model for a status transition of a background job
+
+    Args:
+        event_id: The ID of the event
+        job_id: The ID of the job
+        status: The status the job moved to
+        created_at: When the job moved to the status
+    """
+    __tablename__ = "job_event_info"
+
+    event_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    job_id: Mapped[str] = mapped_column(
+        String,
+        ForeignKey("job_info.job_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    status: Mapped[str] = mapped_column(String, nullable=False)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+
+    # Relationship to job
+    job: Mapped[Optional["JobInfo"]] = relationship(
+        "JobInfo",
+        back_populates="events"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_job_event_info_job_id", "job_id"),
+    )
+
+
+class ApiTokenInfo(Base):
+    """Model for a scoped API token
+
+    Args:
+        token_id: The ID of the token
+        user_id: The ID of the user the token acts for
+        name: What the token is for, e.g. "status bar"
+        scopes: The space separated scopes the token grants, e.g. "read:stats write:review"
+        token_sha256: The SHA-256 digest of the token, the token itself is only shown when it is created
+        created_at: When the token was created
+        last_used_at: When the token was last used
+        revoked_at: When the token was revoked, None while it is valid
+    """
+    __tablename__ = "api_token_info"
+
+    token_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    name: Mapped[str] = mapped_column(String, nullable=False)
+    scopes: Mapped[str] = mapped_column(String, nullable=False)
+    token_sha256: Mapped[str] = mapped_column(String, nullable=False, unique=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    last_used_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    revoked_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_api_token_info_user_id", "user_id"),
+    )
+
+
+class WorkspaceInfo(Base):
+    """Model for a workspace, a class an instructor assigns readings and quizzes to
+
+    Args:
+        workspace_id: The ID of the workspace
+        name: The name of the workspace, e.g. of the course
+        created_at: When the workspace was created
+    """
+    __tablename__ = "workspace_info"
+
+    workspace_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    name: Mapped[str] = mapped_column(String, nullable=False)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+
+    # The roster
+    members: Mapped[list["WorkspaceMemberInfo"]] = relationship(
+        "WorkspaceMemberInfo",
+        back_populates="workspace",
+        cascade="all, delete-orphan"
+    )
+    assignments: Mapped[list["AssignmentInfo"]] = relationship(
+        "AssignmentInfo",
+        back_populates="workspace",
+        cascade="all, delete-orphan"
+    )
+
+
+class WorkspaceMemberInfo(Base):
+    """Model for a member of a workspace's roster
+
+    Args:
+        member_id: The ID of the membership
+        workspace_id: The ID of the workspace
+        user_id: The ID of the user, chosen by the client
+        role: "instructor" or "student"
+        share_attempts: Whether the student lets the instructors see their individual reviews and quizzes
+        joined_at: When the user was added to the roster
+    """
+    __tablename__ = "workspace_member_info"
+
+    member_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    workspace_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("workspace_info.workspace_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    role: Mapped[str] = mapped_column(String, nullable=False)
+    share_attempts: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    joined_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+
+    # Relationship to workspace
+    workspace: Mapped[Optional["WorkspaceInfo"]] = relationship(
+        "WorkspaceInfo",
+        back_populates="members"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        UniqueConstraint("workspace_id", "user_id", name="uq_workspace_member_info_workspace_id_user_id"),
+        Index("idx_workspace_member_info_user_id", "user_id"),
+    )
+
+
+class AssignmentInfo(Base):
+    """Model for a reading or quiz an instructor assigned to the students of a workspace
+
+    Args:
+        assignment_id: The ID of the assignment
+        workspace_id: The ID of the workspace
+        assignment_kind: "reading" (the sections of a chapter, or of the whole book) or "quiz" (on a chapter)
+        chapter_id: The ID of the chapter assigned, None for the whole book
+        due_at: When the assignment is due, if it is
+        created_at: When the assignment was made
+        book_id: The ID of the book
+    """
+    __tablename__ = "assignment_info"
+
+    assignment_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    workspace_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("workspace_info.workspace_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    assignment_kind: Mapped[str] = mapped_column(String, nullable=False)
+    chapter_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("chapter_info.chapter_id", ondelete="CASCADE"),
+        nullable=True,
+    )
+    due_at: Mapped[Optional[datetime]] = mapped_column(DateTime, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationships to workspace and book
+    workspace: Mapped[Optional["WorkspaceInfo"]] = relationship(
+        "WorkspaceInfo",
+        back_populates="assignments"
+    )
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="assignments"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_assignment_info_workspace_id", "workspace_id"),
+    )
+
+
+class DocumentInfo(Base):
+    """Model for a document of the library
+    
+    Args:
+        document_id: The ID of the document
+        title: The title shown in the library
+        author: The author of the document
+        source_path: The path of the source file, relative to the uploads directory, or the URL it was fetched from
+        page_count: The number of pages
+        ocr_status: "pending", "running", "done", "failed" or "not_needed" for documents that came as text
+        detected_type: "textbook", "problem_set", "article", "transcript", "notebook" or "unknown"
+        tags: The space separated tags of the document, e.g. "calculus midterm"
+        job_id: The ID of the OCR job of an uploaded PDF
+        summary: The summary of the whole document generated from its OCR output
+        has_toc: Whether the first pages of the document have a table of contents, None until they are analyzed
+        toc: The JSON list of the table of contents entries, each with title, page_number and level
+        ingestion_settings: The JSON of the pipeline settings overridden for the document, None if it follows config.toml
+        outline: The JSON tree of the chapters and sections found by segmentation, None until the document is segmented
+        created_at: When the document was added to the library
+        updated_at: When the document was last renamed, tagged or processed
+        book_id: The ID of the book ingested from the document, None for uploads that were only OCRed
+    """
+    __tablename__ = "document_info"
+
+    document_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    title: Mapped[str] = mapped_column(String, nullable=False)
+    author: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    source_path: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    page_count: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    ocr_status: Mapped[str] = mapped_column(String, nullable=False)
+    detected_type: Mapped[str] = mapped_column(String, nullable=False)
+    tags: Mapped[str] = mapped_column(String, nullable=False, default="")
+    job_id: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    summary: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    has_toc: Mapped[Optional[bool]] = mapped_column(Boolean, nullable=True)
+    toc: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    ingestion_settings: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    outline: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    updated_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[Optional[int]] = mapped_column(
+        Integer,
+        ForeignKey("book_info.book_id", ondelete="CASCADE"),
+        nullable=True,
+    )
+
+    # Relationship to book
+    book: Mapped[Optional["BookInfo"]] = relationship(
+        "BookInfo",
+        back_populates="documents"
+    )
+    chunks: Mapped[list["ContentChunkInfo"]] = relationship(
+        "ContentChunkInfo",
+        back_populates="document",
+        cascade="all, delete-orphan"
+    )
+    problems: Mapped[list["ProblemInfo"]] = relationship(
+        "ProblemInfo",
+        back_populates="document",
+        cascade="all, delete-orphan"
+    )
+    glossary: Mapped[list["GlossaryTermInfo"]] = relationship(
+        "GlossaryTermInfo",
+        back_populates="document",
+        cascade="all, delete-orphan"
+    )
+    tutoring_messages: Mapped[list["TutoringMessageInfo"]] = relationship(
+        "TutoringMessageInfo",
+        back_populates="document",
+        cascade="all, delete-orphan"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_document_info_book_id", "book_id"),
+    )
+
+
+class ContentChunkInfo(Base):
+    """Model for a page or section of a document's OCR output
+    
+    Args:
+        chunk_id: The ID of the chunk
+        chunk_kind: "page" or "section"
+        chunk_index: The page number (0-based) of pages, the position among the document's sections of sections
+        title: The heading of a section
+        level: The heading level of a section, 1 for "#"
+        page_start: The first page of the chunk, None when the OCR output had no pages
+        page_end: The last page of the chunk, inclusive
+        char_start: Where the chunk starts in the document's markdown file
+        char_end: Where the chunk ends in the document's markdown file, exclusive
+        text: The markdown of the chunk, stored compressed
+        document_id: The ID of the document
+    """
+    __tablename__ = "content_chunk_info"
+
+    chunk_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    chunk_kind: Mapped[str] = mapped_column(String, nullable=False)
+    chunk_index: Mapped[int] = mapped_column(Integer, nullable=False)
+    title: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    level: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    page_start: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    page_end: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    char_start: Mapped[int] = mapped_column(Integer, nullable=False)
+    char_end: Mapped[int] = mapped_column(Integer, nullable=False)
+    text: Mapped[str] = mapped_column(CompressedText, nullable=False)
+    document_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("document_info.document_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to document
+    document: Mapped[Optional["DocumentInfo"]] = relationship(
+        "DocumentInfo",
+        back_populates="chunks"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        UniqueConstraint("document_id", "chunk_kind", "chunk_index", name="uq_content_chunk_info_document_kind_index"),
+        Index("idx_content_chunk_info_document_id", "document_id"),
+    )
+
+
+class ProblemInfo(Base):
+    """Model for a problem extracted from a document's OCR output
+    
+    Args:
+        problem_id: The ID of the problem
+        problem_number: The number printed with the problem (e.g. "3.2" or "7b"), None if it has none
+        statement: The full statement of the problem, mathematics in LaTeX
+        difficulty_hint: The difficulty the document marks the problem with (e.g. "*", "hard"), None if it marks none
+        figures: The newline separated figures and tables the problem refers to (e.g. "Figure 2.3")
+        page_number: The page the problem is stated on (0-based), None when the OCR output had no pages
+        section_index: The section of the OCR output the problem was extracted from
+        quality_score: How well the problem scored on the quality checks, between 0 and 1, None until checked
+        quality_status: "passed", "flagged" for review, "approved" or "rejected" by a reviewer, None until checked
+        quality_issues: The newline separated issues the quality checks found
+        chemical_formula: The formula of the compound the problem asks for in Hill order, answers are graded against it
+        smiles: The canonical SMILES of its structure, None for none
+        model_name: The name of the model that extracted the problem
+        temperature: The temperature it was prompted with, None for the provider's default
+        seed: The sampling seed it was prompted with, None for none
+        prompt_version: The version of the extraction prompt
+        source_sha256: The SHA-256 digest of the chapter text the problem was extracted from
+        created_at: When the problem was extracted
+        document_id: The ID of the document
+    """
+    __tablename__ = "problem_info"
+
+    problem_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    problem_number: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    statement: Mapped[str] = mapped_column(Text, nullable=False)
+    difficulty_hint: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    figures: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    page_number: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    section_index: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    quality_score: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    quality_status: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    quality_issues: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    chemical_formula: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    smiles: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    model_name: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    temperature: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    seed: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    prompt_version: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    source_sha256: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    document_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("document_info.document_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to document
+    document: Mapped[Optional["DocumentInfo"]] = relationship(
+        "DocumentInfo",
+        back_populates="problems"
+    )
+
+    # The generated solution, if any
+    solution: Mapped[Optional["SolutionInfo"]] = relationship(
+        "SolutionInfo",
+        back_populates="problem",
+        cascade="all, delete-orphan",
+        uselist=False
+    )
+
+    # The numeric answer, if set
+    numeric_answer: Mapped[Optional["NumericAnswerInfo"]] = relationship(
+        "NumericAnswerInfo",
+        back_populates="problem",
+        cascade="all, delete-orphan",
+        uselist=False
+    )
+
+    # The hint ladder, if generated
+    hints: Mapped[list["HintInfo"]] = relationship(
+        "HintInfo",
+        back_populates="problem",
+        cascade="all, delete-orphan",
+        order_by="HintInfo.level"
+    )
+
+    # Review schedules and reviews of the problem, per user
+    states: Mapped[list["ProblemStateInfo"]] = relationship(
+        "ProblemStateInfo",
+        back_populates="problem",
+        cascade="all, delete-orphan"
+    )
+    reviews: Mapped[list["ProblemReviewInfo"]] = relationship(
+        "ProblemReviewInfo",
+        back_populates="problem",
+        cascade="all, delete-orphan"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_problem_info_document_id", "document_id"),
+    )
+
+
+class ProblemStateInfo(Base):
+    """Model for the review schedule of a problem for one user
+
+    Args:
+        state_id: The ID of the state
+        problem_id: The ID of the problem
+        user_id: The ID of the user
+        interval_days: The current interval between reviews in days
+        ease: The factor the interval grows by after a successful review
+        repetitions: The successful reviews since the problem was last failed
+        lapses: How often the problem was failed after it had been solved
+        due_at: When the problem is due for review
+        introduced_at: When the user first reviewed the problem
+        last_reviewed_at: When the user last reviewed the problem
+        document_id: The ID of the document
+    """
+    __tablename__ = "problem_state_info"
+
+    state_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    problem_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("problem_info.problem_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    interval_days: Mapped[float] = mapped_column(Float, nullable=False)
+    ease: Mapped[float] = mapped_column(Float, nullable=False)
+    repetitions: Mapped[int] = mapped_column(Integer, nullable=False)
+    lapses: Mapped[int] = mapped_column(Integer, nullable=False)
+    due_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    introduced_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    last_reviewed_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    document_id: Mapped[int] = mapped_column(Integer, nullable=False)
+
+    # Relationship to problem
+    problem: Mapped[Optional["ProblemInfo"]] = relationship(
+        "ProblemInfo",
+        back_populates="states"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        UniqueConstraint("user_id", "problem_id", name="uq_problem_state_info_user_id_problem_id"),
+        Index("idx_problem_state_info_user_id_due_at", "user_id", "due_at"),
+    )
+
+
+class ProblemReviewInfo(Base):
+    """Model for a review of a problem, with the schedule it produced
+
+    Args:
+        review_id: The ID of the review
+        problem_id: The ID of the problem
+        user_id: The ID of the user
+        grade: 1 (again), 2 (hard), 3 (good) or 4 (easy)
+        interval_days: The interval the review scheduled
+        ease: The ease factor after the review
+        repetitions: The successful reviews in a row after the review
+        lapses: The lapses after the review
+        due_at: When the problem is due next
+        reviewed_at: When the review was given
+        document_id: The ID of the document
+    """
+    __tablename__ = "problem_review_info"
+
+    review_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    problem_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("problem_info.problem_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    grade: Mapped[int] = mapped_column(Integer, nullable=False)
+    interval_days: Mapped[float] = mapped_column(Float, nullable=False)
+    ease: Mapped[float] = mapped_column(Float, nullable=False)
+    repetitions: Mapped[int] = mapped_column(Integer, nullable=False)
+    lapses: Mapped[int] = mapped_column(Integer, nullable=False)
+    due_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    reviewed_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    document_id: Mapped[int] = mapped_column(Integer, nullable=False)
+
+    # Relationship to problem
+    problem: Mapped[Optional["ProblemInfo"]] = relationship(
+        "ProblemInfo",
+        back_populates="reviews"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_problem_review_info_user_id_problem_id", "user_id", "problem_id"),
+    )
+
+
+class GlossaryTermInfo(Base):
+    """Model for a term of a document's glossary
+    
+    Args:
+        term_id: The ID of the term
+        term: The term as the document writes it
+        definition: A short definition in the words of the document
+        page_number: The page the term is introduced on (0-based), None when the OCR output had no pages
+        document_id: The ID of the document
+    """
+    __tablename__ = "glossary_term_info"
+
+    term_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    term: Mapped[str] = mapped_column(String, nullable=False)
+    definition: Mapped[str] = mapped_column(Text, nullable=False)
+    page_number: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    document_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("document_info.document_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to document
+    document: Mapped[Optional["DocumentInfo"]] = relationship(
+        "DocumentInfo",
+        back_populates="glossary"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_glossary_term_info_document_id", "document_id"),
+    )
+
+
+class TutoringMessageInfo(Base):
+    """Model for a message of a tutoring session, a learner's question about a passage or the tutor's explanation
+    
+    Args:
+        message_id: The ID of the message
+        session_id: The ID the client chose for the tutoring session
+        role: "learner" or "tutor"
+        content: The passage and question of the learner, or the explanation of the tutor
+        char_start: Where the passage asked about starts in the document's markdown file
+        char_end: Where the passage ends in the document's markdown file, exclusive
+        created_at: When the message was sent
+        document_id: The ID of the document the passage is from
+    """
+    __tablename__ = "tutoring_message_info"
+
+    message_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    session_id: Mapped[str] = mapped_column(String, nullable=False)
+    role: Mapped[str] = mapped_column(String, nullable=False)
+    content: Mapped[str] = mapped_column(Text, nullable=False)
+    char_start: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    char_end: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    document_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("document_info.document_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to document
+    document: Mapped[Optional["DocumentInfo"]] = relationship(
+        "DocumentInfo",
+        back_populates="tutoring_messages"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_tutoring_message_info_session_id", "session_id"),
+        Index("idx_tutoring_message_info_document_id", "document_id"),
+    )
+
+
+class SolutionInfo(Base):
+    """Model for a stepwise solution of a problem generated by the LLM
+    
+    Args:
+        solution_id: The ID of the solution
+        steps: The JSON list of the steps of the solution, mathematics in LaTeX
+        final_answer: The final answer or conclusion
+        hints: The JSON list of hints to give before showing the steps
+        model_name: The model that generated the solution
+        created_at: When the solution was generated
+        problem_id: The ID of the problem
+    """
+    __tablename__ = "solution_info"
+
+    solution_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    steps: Mapped[str] = mapped_column(Text, nullable=False, default="[]")
+    final_answer: Mapped[str] = mapped_column(Text, nullable=False)
+    hints: Mapped[str] = mapped_column(Text, nullable=False, default="[]")
+    model_name: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    problem_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("problem_info.problem_id", ondelete="CASCADE"),
+        nullable=False,
+        unique=True,
+    )
+
+    # Relationship to problem
+    problem: Mapped[Optional["ProblemInfo"]] = relationship(
+        "ProblemInfo",
+        back_populates="solution"
+    )
+
+
+class NumericAnswerInfo(Base):
+    """Model for the numeric answer of a problem, answers to it are compared in SI within the tolerance
+    
+    Args:
+        numeric_answer_id: The ID of the numeric answer
+        value: The expected value, in unit
+        unit: The unit of the value (e.g. "m/s^2", "kJ"), empty for a pure number
+        abs_tolerance: How far from the value an answer may be, in unit, None for none
+        rel_tolerance: How far from the value an answer may be relative to it, None for none
+        created_at: When the numeric answer was set
+        problem_id: The ID of the problem
+    """
+    __tablename__ = "numeric_answer_info"
+
+    numeric_answer_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    value: Mapped[float] = mapped_column(Float, nullable=False)
+    unit: Mapped[str] = mapped_column(String, nullable=False, default="")
+    abs_tolerance: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    rel_tolerance: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    problem_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("problem_info.problem_id", ondelete="CASCADE"),
+        nullable=False,
+        unique=True,
+    )
+
+    # Relationship to problem
+    problem: Mapped[Optional["ProblemInfo"]] = relationship(
+        "ProblemInfo",
+        back_populates="numeric_answer"
+    )
+
+
+class HintInfo(Base):
+    """Model for a hint of a problem's hint ladder
+    
+    Args:
+        hint_id: The ID of the hint
+        level: The level of the hint, 1 gives the least away
+        tier: "nudge", "approach" or "partial_solution"
+        text: The hint, mathematics in LaTeX
+        model_name: The model that wrote the hint
+        created_at: When the hint was generated
+        problem_id: The ID of the problem
+    """
+    __tablename__ = "hint_info"
+
+    hint_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    level: Mapped[int] = mapped_column(Integer, nullable=False)
+    tier: Mapped[str] = mapped_column(String, nullable=False)
+    text: Mapped[str] = mapped_column(Text, nullable=False)
+    model_name: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    problem_id: Mapped[int] = mapped_column(
+        Integer,
+        ForeignKey("problem_info.problem_id", ondelete="CASCADE"),
+        nullable=False,
+    )
+
+    # Relationship to problem
+    problem: Mapped[Optional["ProblemInfo"]] = relationship(
+        "ProblemInfo",
+        back_populates="hints"
+    )
+
+    # Indexes for common queries
+    __table_args__ = (
+        UniqueConstraint("problem_id", "level", name="uq_hint_info_problem_level"),
+    )
+
+
+class PromptLogInfo(Base):
+    """Model for a logged LLM prompt and its response, redacted and possibly truncated
+    
+    Args:
+        log_id: The ID of the log entry
+        job_id: The ID of the job the prompt was sent for, None outside of jobs
+        model_name: The name of the model prompted
+        schema_name: The name of the schema the response was validated against
+        prompt: The prompt as logged
+        response: The response as logged
+        error: Why the response was rejected, e.g. the validation error
+        parsed: The output validated from a structured response, as JSON, None when it was rejected
+        attempt: 0 for the first answer to a prompt, then the number of the repair asked for
+        prompt_chars: The length of the prompt before truncation
+        response_chars: The length of the response before truncation
+        truncated: Whether the prompt or response was cut
+        created_at: When the response came in
+    """
+    __tablename__ = "prompt_log_info"
+
+    log_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    job_id: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    model_name: Mapped[str] = mapped_column(String, nullable=False)
+    schema_name: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    prompt: Mapped[str] = mapped_column(CompressedText, nullable=False)
+    response: Mapped[Optional[str]] = mapped_column(CompressedText, nullable=True)
+    error: Mapped[Optional[str]] = mapped_column(Text, nullable=True)
+    parsed: Mapped[Optional[str]] = mapped_column(CompressedText, nullable=True)
+    attempt: Mapped[int] = mapped_column(Integer, nullable=False, default=0)
+    prompt_chars: Mapped[int] = mapped_column(Integer, nullable=False)
+    response_chars: Mapped[int] = mapped_column(Integer, nullable=False, default=0)
+    truncated: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_prompt_log_info_job_id", "job_id"),
+        Index("idx_prompt_log_info_created_at", "created_at"),
+    )
+
+
+class UsageInfo(Base):
+    """Model for the tokens of an LLM call and their estimated cost
+
+    Args:
+        usage_id: The ID of the usage record
+        job_id: The ID of the job the call was made for, None outside of jobs
+        job_kind: The kind of that job, e.g. "problem_extraction"
+        document_id: The ID of the document the job worked on, kept after the document is deleted
+        model_name: The name of the model prompted
+        input_tokens: The tokens of the prompt
+        output_tokens: The tokens of the response
+        estimated: Whether the provider did not report the counts and they were estimated from the text
+        cost: The estimated cost in USD, None for models without a configured price
+        created_at: When the response came in
+    """
+    __tablename__ = "usage_info"
+
+    usage_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    job_id: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    job_kind: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    document_id: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    model_name: Mapped[str] = mapped_column(String, nullable=False)
+    input_tokens: Mapped[int] = mapped_column(Integer, nullable=False, default=0)
+    output_tokens: Mapped[int] = mapped_column(Integer, nullable=False, default=0)
+    estimated: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    cost: Mapped[Optional[float]] = mapped_column(Float, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_usage_info_created_at", "created_at"),
+        Index("idx_usage_info_job_id", "job_id"),
+        Index("idx_usage_info_document_id", "document_id"),
+    )
+
+
+class StudyEventInfo(Base):
+    """Model for an event of the study activity stream, never changed once appended (see study_events)
+
+    Args:
+        event_id: The ID of the event, the order of the stream
+        event_kind: What happened, e.g. "attempted" or "reviewed"
+        user_id: The ID of the user, None for attempts, which are not made per user
+        subject_kind: What the event is about: "exercise", "card", "problem" or "reading"
+        subject_id: The ID of the exercise, card, problem or reading item
+        payload: JSON of the details of the event, e.g. the score of a grading or the grade of a review
+        occurred_at: When the action happened
+        recorded_at: When the event was appended, later than occurred_at for backfilled history
+        book_id: The ID of the book, kept after the book is deleted
+        document_id: The ID of the document of a problem, kept after the document is deleted
+    """
+    __tablename__ = "study_event_info"
+
+    event_id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    event_kind: Mapped[str] = mapped_column(String, nullable=False)
+    user_id: Mapped[Optional[str]] = mapped_column(String, nullable=True)
+    subject_kind: Mapped[str] = mapped_column(String, nullable=False)
+    subject_id: Mapped[int] = mapped_column(Integer, nullable=False)
+    payload: Mapped[str] = mapped_column(Text, nullable=False, default="{}")
+    occurred_at: Mapped[datetime] = mapped_column(DateTime, nullable=False)
+    recorded_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    book_id: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    document_id: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_study_event_info_user_id_event_id", "user_id", "event_id"),
+        Index("idx_study_event_info_book_id_event_id", "book_id", "event_id"),
+    )
+
+
+class ResponseCacheInfo(Base):
+    """Model for a validated LLM response kept to answer the identical request again
+
+    Args:
+        cache_key: The SHA-256 of the model, prompt, schema, prompt options and attachments of the request
+        model_name: The name of the model that answered
+        schema_name: The name of the schema the response was validated against
+        response: The validated response as JSON
+        hits: The times the response was served from the cache
+        created_at: When the response came in
+        last_used_at: When the response was last stored or served, the least recently used go first
+    """
+    __tablename__ = "response_cache_info"
+
+    cache_key: Mapped[str] = mapped_column(String, primary_key=True)
+    model_name: Mapped[str] = mapped_column(String, nullable=False)
+    schema_name: Mapped[str] = mapped_column(String, nullable=False)
+    response: Mapped[str] = mapped_column(CompressedText, nullable=False)
+    hits: Mapped[int] = mapped_column(Integer, nullable=False, default=0)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+    last_used_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_response_cache_info_created_at", "created_at"),
+        Index("idx_response_cache_info_last_used_at", "last_used_at"),
+    )
+
+
+class ExportFingerprintInfo(Base):
+    """Model for the fingerprint embedded in an export, naming who exported what
+
+    Args:
+        fingerprint: The random hexadecimal fingerprint embedded in the export
+        instance_id: The server instance that made the export
+        user_id: The user the export was made for
+        export_kind: What was exported, e.g. "book_bundle" or "problem_set"
+        subject_id: The ID of the book or document exported
+        created_at: When the export was made
+    """
+    __tablename__ = "export_fingerprint_info"
+
+    fingerprint: Mapped[str] = mapped_column(String, primary_key=True)
+    instance_id: Mapped[str] = mapped_column(String, nullable=False)
+    user_id: Mapped[str] = mapped_column(String, nullable=False)
+    export_kind: Mapped[str] = mapped_column(String, nullable=False)
+    subject_id: Mapped[Optional[int]] = mapped_column(Integer, nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime, default=lambda: datetime.now(timezone.utc))
+
+    # Indexes for common queries
+    __table_args__ = (
+        Index("idx_export_fingerprint_info_user_id", "user_id"),
+    )
+
+
+def _sql_literal(value) -> str:
+    if isinstance(value, bool):
+        return "1" if value else "0"
+    if isinstance(value, (int, float)):
+        return str(value)
+    return "'" + str(value).replace("'", "''") + "'"
+
+
+def add_missing_columns(engine: Engine) -> List[str]:
+    """
+    Add the columns of the models missing from existing tables, which create_all leaves as they are
+
+    Safe to run on every start: only missing columns are added, with their indexes. A column that cannot be null is
+    added with its default, or as nullable when it has none, since SQLite cannot add it otherwise.
+
+    Returns:
+        The "table.column" names added
+    """
+    inspector = inspect(engine)
+    tables = set(inspector.get_table_names())
+    added = []
+    with engine.begin() as connection:
+        for table in Base.metadata.sorted_tables:
+            if table.name not in tables:
+                continue
+            existing = {column["name"] for column in inspector.get_columns(table.name)}
+            missing = [column for column in table.columns if column.name not in existing]
+            for column in missing:
+                ddl = f'ALTER TABLE "{table.name}" ADD COLUMN "{column.name}" {column.type.compile(dialect=engine.dialect)}'
+                default = column.default.arg if column.default is not None and column.default.is_scalar else None
+                if default is not None:
+                    ddl += f" {'NOT NULL ' if not column.nullable else ''}DEFAULT {_sql_literal(default)}"
+                connection.execute(text(ddl))
+                added.append(f"{table.name}.{column.name}")
+            if missing:
+                for index in table.indexes:
+                    index.create(connection, checkfirst=True)
+    return added
+
+
+class TextBookDatabase:
+    """Context manager for textbook database operations"""
+    
+    def __init__(
+        self,
+        db_path: Optional[str] = None,
+        db_type: str = "sqlite"
+    ):
+        """
+        Initialize TextBookContext
+        
+        Args:
+            db_path: Path to the database file (for SQLite) or connection string
+            db_type: Type of database ("sqlite" or "postgresql")
+        """
+        if db_type not in ("sqlite", "postgresql"):
+            raise ValueError(f"Unsupported database type: {db_type}")
+        
+        if db_type == "postgresql":
+            raise NotImplementedError("PostgreSQL support is not yet implemented")
+        
+        if db_path is None:
+            db_path = "textbook_context.db"
+        
+        # Ensure directory exists
+        db_dir = os.path.dirname(db_path)
+        if db_dir:
+            Path(db_dir).mkdir(parents=True, exist_ok=True)
+        
+        # Create SQLite connection string
+        db_url = f"sqlite:///{db_path}"
+        
+        self.db_path = db_path
+        self.db_type = db_type
+        self.engine: Engine = create_engine(db_url, echo=False)
+        
+        # Create all tables, and upgrade those of an earlier version
+        Base.metadata.create_all(self.engine)
+        add_missing_columns(self.engine)
+        
+        # Create session (will be created per operation or can be used as context manager)
+        self._session: Optional[Session] = None
+    
+    @property
+    def session(self) -> Session:
+        """Get or create a database session (lazy creation)"""
+        if self._session is None:
+            self._session = Session(self.engine)
+        return self._session
+    
+    def new_session(self) -> Session:
+        """
+        Create a new database session
+        
+        Returns:
+            A new Session instance. Caller is responsible for closing it.
+        """
+        return Session(self.engine)
+    
+    def close(self):
+        """Close the database session"""
+        if self._session:
+            self._session.close()
+            self._session = None
+    
+    def __enter__(self):
+        return self
+    
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        self.close()
+    
+    # Compatibility property for tests that access conn directly
+    @property
+    def conn(self):
+        """Compatibility property for backward compatibility with raw SQLite connection"""
+        # Return None to indicate SQLAlchemy is being used instead of raw connection
+        return None
+
+    
+
+    
+    # ------------------------------------------------------------
+    # Book related functions
+    # ------------------------------------------------------------
+
+    def get_all_books(self) -> list[BookInfo]:
+        """Get all books from the database"""
+        with self.new_session() as session:
+            return _query_all_books(session)
+
+    def get_book_by_id(self, book_id: int) -> Optional[BookInfo]:
+        with self.new_session() as session:
+            return _query_book_by_id(session, book_id)
+
+    def get_book_by_file_name(self, book_file_name: str) -> Optional[BookInfo]:
+        with self.new_session() as session:
+            return _query_book_by_file_name(session, book_file_name)
+
+    def create_book(self, book_name: str, book_author: str, book_keywords: str, book_file_name: str, page_count: int, ingestion_mode: Optional[str] = None) -> BookInfo:
+        with self.new_session() as session:
+            book_info = _create_book_and_return_info(session, book_name, book_author, book_keywords, book_file_name, page_count, ingestion_mode)
+            if book_info is None:
+                raise ValueError("Failed to create book")
+            return book_info
+    
+    def get_book_toc_end_page(self, book_id: int, default_value: int) -> int:
+        with self.new_session() as session:
+            book_info = _query_book_by_id(session, book_id)
+            if book_info is not None and book_info.book_toc_end_page is not None:
+                return book_info.book_toc_end_page
+            return default_value
+        
+    def update_book_toc_end_page(self, book_id: int, toc_end_page: int) -> None:
+        with self.new_session() as session:
+            session.query(BookInfo).filter(BookInfo.book_id == book_id).update({BookInfo.book_toc_end_page: toc_end_page})
+            session.commit()
+    
+    def get_book_alignment_offset(self, book_id: int, default_value: int) -> int:
+        with self.new_session() as session:
+            book_info = _query_book_by_id(session, book_id)
+            if book_info is not None and book_info.book_alignment_offset is not None:
+                return book_info.book_alignment_offset
+            return default_value
+        
+    def update_book_alignment_offset(self, book_id: int, alignment_offset: int) -> None:
+        with self.new_session() as session:
+            session.query(BookInfo).filter(BookInfo.book_id == book_id).update({BookInfo.book_alignment_offset: alignment_offset})
+            session.commit()
+        
+    def update_book_source(self, book_id: int, source_type: str, source_url: Optional[str] = None) -> None:
+        with self.new_session() as session:
+            session.query(BookInfo).filter(BookInfo.book_id == book_id).update({BookInfo.book_source_type: source_type, BookInfo.book_source_url: source_url})
+            session.commit()
+
+    def set_book_status(self, book_id: int, archived: Optional[bool] = None, suspended: Optional[bool] = None) -> Optional[BookInfo]:
+        """Archive or suspend a book, or undo it, None leaves a state unchanged"""
+        with self.new_session() as session:
+            return _save_status(session, _query_book_by_id(session, book_id), archived, suspended)
+
+    def delete_book_by_file_name(self, book_file_name: str) -> bool:
+        with self.new_session() as session:
+            book = _query_book_by_file_name(session, book_file_name)
+            if book is None:
+                return False
+            
+            session.delete(book)
+            session.commit()
+            return True
+    # ------------------------------------------------------------
+    # TOC related functions
+    # ------------------------------------------------------------
+
+    def delete_toc_by_book_id(self, book_id: int) -> None:
+        with self.new_session() as session:
+            _delete_sections_by_book_id(session, book_id)
+            _delete_chapters_by_book_id(session, book_id)
+            session.commit()
+    
+    # ------------------------------------------------------------
+    # Chapter related functions
+    # ------------------------------------------------------------
+
+    def try_create_chapter_info(self, book_id: int, title: str, index_string: str, start_page: int, end_page: int) -> Optional[int]:
+        with self.new_session() as session:
+            existing = _query_chapter_by_book_id_and_title(session, book_id, title)
+            if existing:
+                return existing.chapter_id
+            
+            chapter_info = ChapterInfo(
+                title=title,
+                book_index_string=index_string,
+                start_page_number=start_page,
+                end_page_number=end_page,
+                book_id=book_id
+            )
+            return _try_save(session, chapter_info)
+    
+    def get_chapters_by_book_id(self, book_id: int) -> list[ChapterInfo]:
+        with self.new_session() as session:
+            return _query_chapters_by_book_id(session, book_id)
+        
+    def update_chapter_time_range(self, chapter_id: int, start_seconds: Optional[float], end_seconds: Optional[float]) -> None:
+        with self.new_session() as session:
+            session.query(ChapterInfo).filter(ChapterInfo.chapter_id == chapter_id).update({ChapterInfo.start_seconds: start_seconds, ChapterInfo.end_seconds: end_seconds})
+            session.commit()
+
+    def get_chapters_by_book_id_and_page_range(self, book_id: int, start_page_number: int, end_page_number: int) -> List[ChapterInfo]:
+        with self.new_session() as session:
+            return _query_chapters_by_book_id_and_page_range(session, book_id, start_page_number, end_page_number)
+
+    def set_chapter_status(self, chapter_id: int, archived: Optional[bool] = None, suspended: Optional[bool] = None) -> Optional[ChapterInfo]:
+        """Archive or suspend the deck of a chapter, or undo it, None leaves a state unchanged"""
+        with self.new_session() as session:
+            chapter = session.query(ChapterInfo).filter(ChapterInfo.chapter_id == chapter_id).first()
+            return _save_status(session, chapter, archived, suspended)
+    
+    # ------------------------------------------------------------
+    # Section related functions
+    # ------------------------------------------------------------
+
+    def try_create_section_info(self, book_id: int, chapter_id: int, title: str, index_string: str, start_page: int, end_page: int) -> Optional[int]:
+        with self.new_session() as session:
+            existing = _query_section_by_book_id_and_title(session, book_id, title)
+            if existing:
+                return existing.section_id
+            
+            section_info = SectionInfo(
+                title=title,
+                book_index_string=index_string,
+                start_page_number=start_page,
+                end_page_number=end_page,
+                chapter_id=chapter_id,
+                book_id=book_id
+            )
+            return _try_save(session, section_info)
+        
+    def get_sections_by_book_id(self, book_id: int) -> list[SectionInfo]:
+        with self.new_session() as session:
+            return _query_sections_by_book_id(session, book_id)
+
+    def get_sections_by_book_id_and_page_range(self, book_id: int, start_page_number: int, end_page_number: int) -> List[SectionInfo]:
+        with self.new_session() as session:
+            return _query_sections_by_book_id_and_page_range(session, book_id, start_page_number, end_page_number)
+
+    # ------------------------------------------------------------
+    # Page related functions
+    # ------------------------------------------------------------
+
+    def try_create_page_info(self, book_id: int, page_number: int, summary: str) -> Optional[int]:
+        with self.new_session() as session:
+            existing = _query_page_by_book_id_and_page_number(session, book_id, page_number)
+            if existing:
+                # Pages created by a transcription have no summary yet
+                if existing.summary is None and summary:
+                    existing.summary = summary
+                    session.commit()
+                return existing.page_id
             page_info = PageInfo(
                 page_number=page_number,
-                summary=summary,
-                book_id=book_id
+                summary=summary,
+                book_id=book_id
+            )
+            return _try_save(session, page_info)
+
+    def get_page_info(self, page_id: int) -> Optional[PageInfo]:
+        with self.new_session() as session:
+            return _query_page_by_id(session, page_id)
+
+    def get_pages_by_book_id(self, book_id: int) -> list[PageInfo]:
+        with self.new_session() as session:
+            return _query_pages_by_book_id(session, book_id)
+
+    def get_page_by_book_id_and_page_number(self, book_id: int, page_number: int) -> Optional[PageInfo]:
+        with self.new_session() as session:
+            return _query_page_by_book_id_and_page_number(session, book_id, page_number)
+
+    def save_page_time_range(self, book_id: int, page_number: int, start_seconds: Optional[float], end_seconds: Optional[float]) -> int:
+        """Store the video time range of a page, creating the page row if it does not exist yet"""
+        with self.new_session() as session:
+            page_info = _query_page_by_book_id_and_page_number(session, book_id, page_number)
+            if page_info is None:
+                page_info = PageInfo(page_number=page_number, book_id=book_id)
+                session.add(page_info)
+            page_info.start_seconds = start_seconds
+            page_info.end_seconds = end_seconds
+            session.commit()
+            return page_info.page_id
+
+    def save_page_transcription(self, book_id: int, page_number: int, transcription: str, confidence: float, source: str) -> int:
+        """Store the transcription of a page, creating the page row if it does not exist yet"""
+        with self.new_session() as session:
+            page_info = _query_page_by_book_id_and_page_number(session, book_id, page_number)
+            if page_info is None:
+                page_info = PageInfo(page_number=page_number, book_id=book_id)
+                session.add(page_info)
+            page_info.transcription = transcription
+            page_info.transcription_confidence = confidence
+            page_info.transcription_source = source
+            session.commit()
+            return page_info.page_id
+
+    # ------------------------------------------------------------
+    # Exercise related functions
+    # ------------------------------------------------------------
+
+    def create_exercise(self, book_id: int, page_number: int, description: str, exercise_type: Optional[str] = None, starter_code: Optional[str] = None, solution_code: Optional[str] = None, code_language: Optional[str] = None, study_guide: Optional[str] = None, difficulty_level: Optional[int] = None) -> int:
+        with self.new_session() as session:
+            page_info = _query_page_by_book_id_and_page_number(session, book_id, page_number)
+            exercise = ExerciseInfo(
+                exercise_description=description,
+                page_number=page_number,
+                page_id=page_info.page_id if page_info is not None else None,
+                exercise_type=exercise_type,
+                starter_code=starter_code,
+                solution_code=solution_code,
+                code_language=code_language,
+                book_id=book_id,
+            )
+            if study_guide is not None or difficulty_level is not None:
+                exercise.details = ExerciseDetails(study_guide=study_guide, difficulty_level=difficulty_level, book_id=book_id)
+            session.add(exercise)
+            session.commit()
+            return exercise.exercise_id
+
+    def get_exercise_info(self, exercise_id: int) -> Optional[ExerciseInfo]:
+        with self.new_session() as session:
+            return _query_exercise_by_id(session, exercise_id)
+
+    def get_exercises_by_book_id(self, book_id: int, exercise_type: Optional[str] = None) -> list[ExerciseInfo]:
+        with self.new_session() as session:
+            return _query_exercises_by_book_id(session, book_id, exercise_type)
+
+    # ------------------------------------------------------------
+    # Entity and cross reference related functions
+    # ------------------------------------------------------------
+
+    def replace_entities(self, book_id: int, source: str, entities: List[EntityInfo], chapter_id: Optional[int] = None) -> List[int]:
+        """Replace the entities a source found for a book (or one of its chapters), keeping the entities of other sources"""
+        with self.new_session() as session:
+            query = session.query(EntityInfo).filter(EntityInfo.book_id == book_id, EntityInfo.entity_source == source)
+            if chapter_id is not None:
+                query = query.filter(EntityInfo.chapter_id == chapter_id)
+            query.delete()
+            for entity in entities:
+                entity.book_id = book_id
+                entity.entity_source = source
+                session.add(entity)
+            session.commit()
+            return [entity.entity_id for entity in entities]
+
+    def get_entities_by_book_id(self, book_id: int, entity_kinds: Optional[List[str]] = None) -> list[EntityInfo]:
+        with self.new_session() as session:
+            return _query_entities_by_book_id(session, book_id, entity_kinds)
+
+    def replace_cross_references(self, book_id: int, references: List[CrossReferenceInfo]) -> int:
+        with self.new_session() as session:
+            session.query(CrossReferenceInfo).filter(CrossReferenceInfo.book_id == book_id).delete()
+            for reference in references:
+                reference.book_id = book_id
+                session.add(reference)
+            session.commit()
+            return len(references)
+
+    def get_cross_references_by_book_id(self, book_id: int, page_number: Optional[int] = None) -> list[CrossReferenceInfo]:
+        with self.new_session() as session:
+            return _query_cross_references_by_book_id(session, book_id, page_number)
+
+    # ------------------------------------------------------------
+    # Flashcard related functions
+    # ------------------------------------------------------------
+
+    def create_flashcards(self, book_id: int, cards: List[FlashcardInfo]) -> List[int]:
+        with self.new_session() as session:
+            for card in cards:
+                card.book_id = book_id
+                session.add(card)
+            session.commit()
+            return [card.card_id for card in cards]
+
+    def get_flashcards_by_book_id(self, book_id: int, chapter_id: Optional[int] = None, entity_kinds: Optional[List[str]] = None) -> list[FlashcardInfo]:
+        with self.new_session() as session:
+            return _query_flashcards_by_book_id(session, book_id, chapter_id, entity_kinds)
+
+    def get_flashcards_by_ids(self, card_ids: List[int]) -> list[FlashcardInfo]:
+        with self.new_session() as session:
+            return session.query(FlashcardInfo).filter(FlashcardInfo.card_id.in_(card_ids)).order_by(FlashcardInfo.card_id).all()
+
+    def set_flashcard_structure(self, card_id: int, chemical_formula: Optional[str], smiles: Optional[str]) -> Optional[FlashcardInfo]:
+        """Set the structure of a card, None if there is no such card"""
+        with self.new_session() as session:
+            card = session.query(FlashcardInfo).filter(FlashcardInfo.card_id == card_id).first()
+            if card is None:
+                return None
+            card.chemical_formula = chemical_formula
+            card.smiles = smiles
+            session.commit()
+            session.refresh(card)
+            return card
+
+    def set_flashcard_status(self, card_id: int, archived: Optional[bool] = None, suspended: Optional[bool] = None) -> Optional[FlashcardInfo]:
+        """Archive or suspend a card, or undo it, None leaves a state unchanged"""
+        with self.new_session() as session:
+            card = session.query(FlashcardInfo).filter(FlashcardInfo.card_id == card_id).first()
+            return _save_status(session, card, archived, suspended)
+
+    # ------------------------------------------------------------
+    # Review related functions
+    # ------------------------------------------------------------
+
+    def get_card_states(self, user_id: str, card_ids: List[int]) -> list[CardStateInfo]:
+        with self.new_session() as session:
+            return session.query(CardStateInfo).filter(CardStateInfo.user_id == user_id, CardStateInfo.card_id.in_(card_ids)).all()
+
+    def get_due_card_states(self, user_id: str, now: datetime, limit: int, book_id: Optional[int] = None) -> list[CardStateInfo]:
+        """The states of the cards due by now, most overdue first, with their cards"""
+        with self.new_session() as session:
+            return _query_due_card_states(session, user_id, now, book_id).limit(limit).all()
+
+    def count_due_cards(self, user_id: str, now: datetime, book_id: Optional[int] = None) -> int:
+        with self.new_session() as session:
+            return _query_due_card_states(session, user_id, now, book_id).count()
+
+    def get_new_flashcards(self, user_id: str, limit: int, book_id: Optional[int] = None) -> list[FlashcardInfo]:
+        """Cards the user has never reviewed, in the order they were created"""
+        with self.new_session() as session:
+            return _query_new_flashcards(session, user_id, book_id).order_by(FlashcardInfo.card_id).limit(limit).all()
+
+    def count_new_flashcards(self, user_id: str, book_id: Optional[int] = None) -> int:
+        with self.new_session() as session:
+            return _query_new_flashcards(session, user_id, book_id).count()
+
+    def get_reviewable_card_states(self, user_id: str, book_id: Optional[int] = None) -> list[CardStateInfo]:
+        """The schedules of all of a user's cards that are neither archived nor suspended"""
+        with self.new_session() as session:
+            query = _filter_reviewable_cards(session.query(CardStateInfo).join(FlashcardInfo, CardStateInfo.card_id == FlashcardInfo.card_id)).filter(CardStateInfo.user_id == user_id)
+            if book_id is not None:
+                query = query.filter(CardStateInfo.book_id == book_id)
+            return query.order_by(CardStateInfo.due_at).all()
+
+    def count_cards_introduced(self, user_id: str, since: datetime) -> int:
+        """The number of cards the user reviewed for the first time since a moment"""
+        with self.new_session() as session:
+            return session.query(CardStateInfo).filter(CardStateInfo.user_id == user_id, CardStateInfo.introduced_at >= since).count()
+
+    def save_reviews(self, user_id: str, reviews: List[ReviewInfo]) -> List[int]:
+        """Store reviews and move the schedules of their cards to the last review, in one transaction"""
+        with self.new_session() as session:
+            card_ids = list({review.card_id for review in reviews})
+            states = {state.card_id: state for state in session.query(CardStateInfo).filter(CardStateInfo.user_id == user_id, CardStateInfo.card_id.in_(card_ids))}
+            for review in reviews:
+                state = states.get(review.card_id)
+                if state is None:
+                    state = CardStateInfo(card_id=review.card_id, user_id=user_id, introduced_at=review.reviewed_at, book_id=review.book_id)
+                    states[review.card_id] = state
+                    session.add(state)
+                else:
+                    # Snapshot of the schedule before the review, to undo it
+                    review.previous_interval_days = state.interval_days
+                    review.previous_ease = state.ease
+                    review.previous_repetitions = state.repetitions
+                    review.previous_lapses = state.lapses
+                    review.previous_due_at = state.due_at
+                    review.previous_reviewed_at = state.last_reviewed_at
+                state.interval_days = review.interval_days
+                state.ease = review.ease
+                state.repetitions = review.repetitions
+                state.lapses = review.lapses
+                state.due_at = review.due_at
+                state.last_reviewed_at = review.reviewed_at
+                session.add(review)
+            session.flush()
+            session.add_all(_review_event(review) for review in reviews)
+            session.commit()
+            for review in reviews:
+                session.refresh(review)
+            return [review.review_id for review in reviews]
+
+    def get_review_grades(self, user_id: str, book_id: Optional[int] = None) -> list[int]:
+        """The grades of a user's reviews of cards they had seen before, undone reviews aside"""
+        with self.new_session() as session:
+            query = session.query(ReviewInfo.grade).filter(ReviewInfo.user_id == user_id, ReviewInfo.undone_at.is_(None), ReviewInfo.previous_due_at.is_not(None))
+            if book_id is not None:
+                query = query.filter(ReviewInfo.book_id == book_id)
+            return [grade for (grade,) in query.all()]
+
+    def get_last_review(self, user_id: str, card_id: int) -> Optional[ReviewInfo]:
+        """The last review of a card by a user that was not undone"""
+        with self.new_session() as session:
+            return (
+                session.query(ReviewInfo)
+                .filter(ReviewInfo.user_id == user_id, ReviewInfo.card_id == card_id, ReviewInfo.undone_at.is_(None))
+                .order_by(ReviewInfo.reviewed_at.desc(), ReviewInfo.review_id.desc())
+                .first()
+            )
+
+    def undo_review(self, review_id: int) -> Optional[CardStateInfo]:
+        """Mark a review undone and restore the schedule from before it, returns the restored state, None if the card is new again"""
+        with self.new_session() as session:
+            review = session.query(ReviewInfo).filter(ReviewInfo.review_id == review_id).first()
+            if review is None:
+                raise ValueError(f"Review {review_id} not found")
+            state = session.query(CardStateInfo).filter(CardStateInfo.user_id == review.user_id, CardStateInfo.card_id == review.card_id).first()
+            review.undone_at = datetime.now(timezone.utc)
+            session.add(_study_event(EVENT_REVIEW_UNDONE, SUBJECT_CARD, review.card_id, review.undone_at, {"review_id": review.review_id}, review.user_id, book_id=review.book_id))
+            if state is not None and review.previous_due_at is None:
+                session.delete(state)
+                state = None
+            elif state is not None:
+                state.interval_days = review.previous_interval_days
+                state.ease = review.previous_ease
+                state.repetitions = review.previous_repetitions
+                state.lapses = review.previous_lapses
+                state.due_at = review.previous_due_at
+                state.last_reviewed_at = review.previous_reviewed_at
+            session.commit()
+            if state is not None:
+                session.refresh(state)
+            return state
+
+    # ------------------------------------------------------------
+    # Attempt related functions
+    # ------------------------------------------------------------
+
+    def create_attempt(self, exercise_id: int, answer: str, judgments: List[GradingJudgmentInfo], grading_context: Optional[str] = None, misconceptions: Optional[List[str]] = None, study_mode: Optional[str] = None) -> int:
+        """Store an attempt with its judgments, the last one is the grade that counts"""
+        if not judgments:
+            raise ValueError("An attempt needs at least one judgment")
+        final = judgments[-1]
+        with self.new_session() as session:
+            exercise = _query_exercise_by_id(session, exercise_id)
+            if exercise is None:
+                raise ValueError(f"Exercise {exercise_id} not found")
+            attempt = AttemptInfo(
+                exercise_id=exercise_id,
+                answer=answer,
+                study_mode=study_mode,
+                score=final.score,
+                is_correct=final.is_correct,
+                confidence=final.confidence,
+                feedback=final.feedback,
+                grading_context=grading_context,
+                book_id=exercise.book_id,
+            )
+            attempt.judgments = judgments
+            attempt.misconceptions = [
+                MisconceptionInfo(description=description, exercise_id=exercise_id, book_id=exercise.book_id)
+                for description in misconceptions or []
+            ]
+            session.add(attempt)
+            session.flush()
+            session.add(_attempt_event(attempt, EVENT_ATTEMPTED, attempt.created_at, {"study_mode": study_mode}))
+            session.add_all(_graded_event(attempt, judgment) for judgment in judgments)
+            session.commit()
+            return attempt.attempt_id
+
+    def add_grading_judgment(self, attempt_id: int, judgment: GradingJudgmentInfo, misconceptions: Optional[List[str]] = None) -> None:
+        """Record a new judgment of an attempt and make it the grade that counts, replacing the misconceptions if given"""
+        with self.new_session() as session:
+            attempt = _query_attempt_by_id(session, attempt_id)
+            if attempt is None:
+                raise ValueError(f"Attempt {attempt_id} not found")
+            attempt.judgments.append(judgment)
+            attempt.score = judgment.score
+            attempt.is_correct = judgment.is_correct
+            attempt.confidence = judgment.confidence
+            attempt.feedback = judgment.feedback
+            if misconceptions is not None:
+                attempt.misconceptions = [
+                    MisconceptionInfo(description=description, exercise_id=attempt.exercise_id, book_id=attempt.book_id)
+                    for description in misconceptions
+                ]
+            session.flush()
+            session.add(_graded_event(attempt, judgment))
+            session.commit()
+
+    def get_attempt_info(self, attempt_id: int) -> Optional[AttemptInfo]:
+        with self.new_session() as session:
+            return _query_attempt_by_id(session, attempt_id)
+
+    def get_attempts_by_exercise_id(self, exercise_id: int) -> list[AttemptInfo]:
+        with self.new_session() as session:
+            return _query_attempts_by_exercise_id(session, exercise_id)
+
+    def get_recent_misconceptions(self, limit: int, book_id: Optional[int] = None) -> list[MisconceptionInfo]:
+        """Get the most recent misconceptions, newest first, optionally only those of one book"""
+        with self.new_session() as session:
+            return _query_recent_misconceptions(session, limit, book_id)
+
+    def void_attempt(self, attempt_id: int) -> Optional[AttemptInfo]:
+        with self.new_session() as session:
+            attempt = session.query(AttemptInfo).filter(AttemptInfo.attempt_id == attempt_id).first()
+            if attempt is None:
+                return None
+            attempt.voided_at = datetime.now(timezone.utc)
+            session.add(_attempt_event(attempt, EVENT_VOIDED, attempt.voided_at))
+            session.commit()
+            return _query_attempt_by_id(session, attempt_id)
+
+    def get_attempts_by_book_id(self, book_id: int) -> list[AttemptInfo]:
+        """Get the attempts of a book that were not voided"""
+        with self.new_session() as session:
+            return session.query(AttemptInfo).filter(AttemptInfo.book_id == book_id, AttemptInfo.voided_at.is_(None)).order_by(AttemptInfo.created_at).all()
+
+    def get_attempts(self, since: Optional[datetime] = None) -> list[AttemptInfo]:
+        """Get all attempts that were not voided, optionally only those graded since a time, with their judgments"""
+        with self.new_session() as session:
+            query = session.query(AttemptInfo).options(selectinload(AttemptInfo.judgments)).filter(AttemptInfo.voided_at.is_(None))
+            if since is not None:
+                query = query.filter(AttemptInfo.created_at >= since)
+            return query.order_by(AttemptInfo.created_at).all()
+
+    # ------------------------------------------------------------
+    # Reflection related functions
+    # ------------------------------------------------------------
+
+    def create_reflection(self, reflection: ReflectionInfo) -> int:
+        with self.new_session() as session:
+            session.add(reflection)
+            session.commit()
+            return reflection.reflection_id
+
+    def get_reflection_info(self, reflection_id: int) -> Optional[ReflectionInfo]:
+        with self.new_session() as session:
+            return session.query(ReflectionInfo).filter(ReflectionInfo.reflection_id == reflection_id).first()
+
+    def get_reflections_by_book_id(self, book_id: int, chapter_id: Optional[int] = None) -> list[ReflectionInfo]:
+        with self.new_session() as session:
+            return _query_reflections_by_book_id(session, book_id, chapter_id)
+
+    # ------------------------------------------------------------
+    # Question timing related functions
+    # ------------------------------------------------------------
+
+    def create_question_timing(self, timing: QuestionTimingInfo) -> int:
+        with self.new_session() as session:
+            session.add(timing)
+            session.commit()
+            return timing.timing_id
+
+    def get_question_timings_by_book_id(self, book_id: int) -> list[QuestionTimingInfo]:
+        """Get the timings of a book's quiz questions, oldest first"""
+        with self.new_session() as session:
+            return session.query(QuestionTimingInfo).filter(QuestionTimingInfo.book_id == book_id).order_by(QuestionTimingInfo.created_at, QuestionTimingInfo.timing_id).all()
+
+    # ------------------------------------------------------------
+    # Dispute related functions
+    # ------------------------------------------------------------
+
+    def create_dispute(self, attempt_id: int, comment: Optional[str], status: str) -> int:
+        """Record a dispute with the grade of the attempt at that time"""
+        with self.new_session() as session:
+            attempt = _query_attempt_by_id(session, attempt_id)
+            if attempt is None:
+                raise ValueError(f"Attempt {attempt_id} not found")
+            dispute = DisputeInfo(
+                attempt_id=attempt_id,
+                comment=comment,
+                status=status,
+                original_score=attempt.score,
+                original_is_correct=attempt.is_correct,
+                book_id=attempt.book_id,
+            )
+            session.add(dispute)
+            session.commit()
+            return dispute.dispute_id
+
+    def update_dispute_escalation(self, dispute_id: int, score: Optional[float], is_correct: Optional[bool]) -> None:
+        with self.new_session() as session:
+            session.query(DisputeInfo).filter(DisputeInfo.dispute_id == dispute_id).update({DisputeInfo.escalated_score: score, DisputeInfo.escalated_is_correct: is_correct})
+            session.commit()
+
+    def resolve_dispute(self, dispute_id: int, status: str, score: float, is_correct: bool, note: Optional[str]) -> None:
+        with self.new_session() as session:
+            dispute = _query_dispute_by_id(session, dispute_id)
+            if dispute is None:
+                raise ValueError(f"Dispute {dispute_id} not found")
+            dispute.status = status
+            dispute.resolved_score = score
+            dispute.resolved_is_correct = is_correct
+            dispute.reviewer_note = note
+            dispute.resolved_at = datetime.now(timezone.utc)
+            session.commit()
+
+    def get_dispute_info(self, dispute_id: int) -> Optional[DisputeInfo]:
+        with self.new_session() as session:
+            return _query_dispute_by_id(session, dispute_id)
+
+    def get_disputes(self, status: Optional[str] = None, attempt_id: Optional[int] = None) -> list[DisputeInfo]:
+        with self.new_session() as session:
+            return _query_disputes(session, status, attempt_id)
+
+    # ------------------------------------------------------------
+    # Highlight related functions
+    # ------------------------------------------------------------
+
+    def create_highlight(self, highlight: HighlightInfo) -> HighlightInfo:
+        with self.new_session() as session:
+            session.add(highlight)
+            session.commit()
+            session.refresh(highlight)
+            return highlight
+
+    def get_highlights_by_book_id(self, book_id: int, user_id: str, start_page_number: Optional[int] = None, end_page_number: Optional[int] = None) -> list[HighlightInfo]:
+        with self.new_session() as session:
+            return _query_highlights_by_book_id(session, book_id, user_id, start_page_number, end_page_number)
+
+    def delete_highlight(self, highlight_id: int) -> bool:
+        with self.new_session() as session:
+            highlight = session.query(HighlightInfo).filter(HighlightInfo.highlight_id == highlight_id).first()
+            if highlight is None:
+                return False
+            session.delete(highlight)
+            session.commit()
+            return True
+
+    # ------------------------------------------------------------
+    # Reading queue related functions
+    # ------------------------------------------------------------
+
+    def create_reading_item(self, item: ReadingItemInfo) -> ReadingItemInfo:
+        with self.new_session() as session:
+            session.add(item)
+            session.commit()
+            session.refresh(item)
+            return item
+
+    def get_reading_item(self, reading_id: int) -> Optional[ReadingItemInfo]:
+        with self.new_session() as session:
+            return session.query(ReadingItemInfo).filter(ReadingItemInfo.reading_id == reading_id).first()
+
+    def get_reading_items(self, user_id: str, book_id: Optional[int] = None, include_done: bool = False) -> list[ReadingItemInfo]:
+        """The reading queue of a user, next due first"""
+        with self.new_session() as session:
+            query = session.query(ReadingItemInfo).filter(ReadingItemInfo.user_id == user_id)
+            if book_id is not None:
+                query = query.filter(ReadingItemInfo.book_id == book_id)
+            if not include_done:
+                query = query.filter(ReadingItemInfo.done_at.is_(None))
+            return query.order_by(ReadingItemInfo.due_at, ReadingItemInfo.reading_id).all()
+
+    def get_due_reading_items(self, user_id: str, now: datetime, limit: int, book_id: Optional[int] = None) -> list[ReadingItemInfo]:
+        """The unfinished reading items due by now, most overdue first, leaving out archived and suspended books"""
+        with self.new_session() as session:
+            query = session.query(ReadingItemInfo).join(BookInfo, ReadingItemInfo.book_id == BookInfo.book_id).filter(
+                ReadingItemInfo.user_id == user_id,
+                ReadingItemInfo.done_at.is_(None),
+                ReadingItemInfo.due_at <= now,
+                BookInfo.archived_at.is_(None),
+                BookInfo.suspended_at.is_(None),
+            )
+            if book_id is not None:
+                query = query.filter(ReadingItemInfo.book_id == book_id)
+            return query.order_by(ReadingItemInfo.due_at, ReadingItemInfo.reading_id).limit(limit).all()
+
+    def save_reading_progress(self, reading_id: int, interval_days: float, due_at: datetime, read_at: datetime, done: bool) -> Optional[ReadingItemInfo]:
+        with self.new_session() as session:
+            item = session.query(ReadingItemInfo).filter(ReadingItemInfo.reading_id == reading_id).first()
+            if item is None:
+                return None
+            item.interval_days = interval_days
+            item.due_at = due_at
+            item.read_count += 1
+            item.last_read_at = read_at
+            item.done_at = read_at if done else None
+            session.add(_reading_event(item))
+            session.commit()
+            session.refresh(item)
+            return item
+
+    def save_extract(self, highlight: HighlightInfo, cards: List[FlashcardInfo], reading_item: Optional[ReadingItemInfo] = None) -> Tuple[HighlightInfo, List[int], Optional[ReadingItemInfo]]:
+        """Store an extracted passage as a highlight, its cards and optionally a reading item, in one transaction"""
+        with self.new_session() as session:
+            session.add(highlight)
+            session.add_all(cards)
+            if reading_item is not None:
+                session.add(reading_item)
+            session.commit()
+            session.refresh(highlight)
+            if reading_item is not None:
+                session.refresh(reading_item)
+            return highlight, [card.card_id for card in cards], reading_item
+
+    # ------------------------------------------------------------
+    # Chapter quiz related functions
+    # ------------------------------------------------------------
+
+    def create_chapter_quiz(self, quiz: ChapterQuizInfo) -> ChapterQuizInfo:
+        with self.new_session() as session:
+            session.add(quiz)
+            session.commit()
+            session.refresh(quiz)
+            return quiz
+
+    def get_chapter_quiz(self, quiz_id: int) -> Optional[ChapterQuizInfo]:
+        with self.new_session() as session:
+            return session.query(ChapterQuizInfo).filter(ChapterQuizInfo.quiz_id == quiz_id).first()
+
+    def get_chapter_quiz_by_chapter(self, user_id: str, chapter_id: int) -> Optional[ChapterQuizInfo]:
+        with self.new_session() as session:
+            return session.query(ChapterQuizInfo).filter(ChapterQuizInfo.user_id == user_id, ChapterQuizInfo.chapter_id == chapter_id).first()
+
+    def get_chapter_quizzes(self, user_id: str, include_completed: bool = False) -> list[ChapterQuizInfo]:
+        """The chapter quizzes of a user, newest first"""
+        with self.new_session() as session:
+            query = session.query(ChapterQuizInfo).filter(ChapterQuizInfo.user_id == user_id)
+            if not include_completed:
+                query = query.filter(ChapterQuizInfo.completed_at.is_(None))
+            return query.order_by(ChapterQuizInfo.created_at.desc(), ChapterQuizInfo.quiz_id.desc()).all()
+
+    def complete_chapter_quiz(self, quiz_id: int, completed_at: datetime) -> Optional[ChapterQuizInfo]:
+        with self.new_session() as session:
+            quiz = session.query(ChapterQuizInfo).filter(ChapterQuizInfo.quiz_id == quiz_id).first()
+            if quiz is None:
+                return None
+            quiz.completed_at = completed_at
+            session.commit()
+            session.refresh(quiz)
+            return quiz
+
+    # ------------------------------------------------------------
+    # Syllabus related functions
+    # ------------------------------------------------------------
+
+    def replace_syllabus(self, user_id: str, book_id: int, topics: List[SyllabusTopicInfo]) -> List[SyllabusTopicInfo]:
+        """Replace the syllabus a user follows a book with"""
+        with self.new_session() as session:
+            session.query(SyllabusTopicInfo).filter(SyllabusTopicInfo.user_id == user_id, SyllabusTopicInfo.book_id == book_id).delete()
+            for topic in topics:
+                topic.user_id = user_id
+                topic.book_id = book_id
+                session.add(topic)
+            session.commit()
+            for topic in topics:
+                session.refresh(topic)
+            return topics
+
+    def get_syllabus_topics(self, user_id: str, book_id: Optional[int] = None) -> list[SyllabusTopicInfo]:
+        """The syllabus topics of a user, of one book or all, by week"""
+        with self.new_session() as session:
+            query = session.query(SyllabusTopicInfo).filter(SyllabusTopicInfo.user_id == user_id)
+            if book_id is not None:
+                query = query.filter(SyllabusTopicInfo.book_id == book_id)
+            return query.order_by(SyllabusTopicInfo.week_starts_at, SyllabusTopicInfo.topic_id).all()
+
+    # ------------------------------------------------------------
+    # Smart filter related functions
+    # ------------------------------------------------------------
+
+    def create_smart_filter(self, smart_filter: SmartFilterInfo) -> SmartFilterInfo:
+        with self.new_session() as session:
+            session.add(smart_filter)
+            session.commit()
+            session.refresh(smart_filter)
+            return smart_filter
+
+    def get_smart_filter(self, filter_id: int) -> Optional[SmartFilterInfo]:
+        with self.new_session() as session:
+            return session.query(SmartFilterInfo).filter(SmartFilterInfo.filter_id == filter_id).first()
+
+    def get_smart_filter_by_name(self, user_id: str, name: str) -> Optional[SmartFilterInfo]:
+        with self.new_session() as session:
+            return session.query(SmartFilterInfo).filter(SmartFilterInfo.user_id == user_id, SmartFilterInfo.name == name).first()
+
+    def get_smart_filters(self, user_id: str, book_id: Optional[int] = None) -> list[SmartFilterInfo]:
+        """The filters a user saved, of one book or all, by name"""
+        with self.new_session() as session:
+            query = session.query(SmartFilterInfo).filter(SmartFilterInfo.user_id == user_id)
+            if book_id is not None:
+                query = query.filter(SmartFilterInfo.book_id == book_id)
+            return query.order_by(SmartFilterInfo.name).all()
+
+    def update_smart_filter(self, filter_id: int, name: str, criteria: str, book_id: int) -> Optional[SmartFilterInfo]:
+        with self.new_session() as session:
+            smart_filter = session.query(SmartFilterInfo).filter(SmartFilterInfo.filter_id == filter_id).first()
+            if smart_filter is None:
+                return None
+            smart_filter.name = name
+            smart_filter.criteria = criteria
+            smart_filter.book_id = book_id
+            smart_filter.updated_at = datetime.now(timezone.utc)
+            session.commit()
+            session.refresh(smart_filter)
+            return smart_filter
+
+    def delete_smart_filter(self, filter_id: int) -> bool:
+        with self.new_session() as session:
+            deleted = session.query(SmartFilterInfo).filter(SmartFilterInfo.filter_id == filter_id).delete()
+            session.commit()
+            return deleted > 0
+
+    # ------------------------------------------------------------
+    # User preferences related functions
+    # ------------------------------------------------------------
+
+    def get_user_preferences(self, user_id: str) -> Optional[UserPreferencesInfo]:
+        with self.new_session() as session:
+            return _query_user_preferences_by_id(session, user_id)
+
+    def save_user_preferences(self, user_id: str, default_difficulty: int, daily_new_card_limit: int, output_language: Optional[str], notifications_enabled: bool, notification_hour: Optional[int], scheduler: str, load_balancing: bool = False) -> UserPreferencesInfo:
+        """Create or replace the preferences of a user"""
+        with self.new_session() as session:
+            preferences = _query_user_preferences_by_id(session, user_id)
+            if preferences is None:
+                preferences = UserPreferencesInfo(user_id=user_id)
+                session.add(preferences)
+            preferences.default_difficulty = default_difficulty
+            preferences.daily_new_card_limit = daily_new_card_limit
+            preferences.output_language = output_language
+            preferences.notifications_enabled = notifications_enabled
+            preferences.notification_hour = notification_hour
+            preferences.scheduler = scheduler
+            preferences.load_balancing = load_balancing
+            session.commit()
+            session.refresh(preferences)
+            return preferences
+
+    # ------------------------------------------------------------
+    # Artifact related functions
+    # ------------------------------------------------------------
+
+    def register_artifact(self, book_id: int, kind: str, path: str, sha256: str, size: int) -> int:
+        """Record the digest of a file, replacing any previous record for the same path"""
+        with self.new_session() as session:
+            artifact = _query_artifact_by_path(session, path)
+            if artifact is None:
+                artifact = ArtifactInfo(artifact_path=path, book_id=book_id, artifact_kind=kind, artifact_sha256=sha256, artifact_size=size)
+                session.add(artifact)
+            else:
+                artifact.book_id = book_id
+                artifact.artifact_kind = kind
+                artifact.artifact_sha256 = sha256
+                artifact.artifact_size = size
+            session.commit()
+            return artifact.artifact_id
+
+    def get_artifacts_by_book_id(self, book_id: int) -> list[ArtifactInfo]:
+        with self.new_session() as session:
+            return _query_artifacts_by_book_id(session, book_id)
+
+    def get_all_artifacts(self) -> list[ArtifactInfo]:
+        with self.new_session() as session:
+            return session.query(ArtifactInfo).order_by(ArtifactInfo.artifact_id).all()
+
+    # ------------------------------------------------------------
+    # API token related functions
+    # ------------------------------------------------------------
+
+    def create_api_token(self, token: ApiTokenInfo) -> ApiTokenInfo:
+        with self.new_session() as session:
+            session.add(token)
+            session.commit()
+            session.refresh(token)
+            return token
+
+    def get_api_token_by_digest(self, token_sha256: str) -> Optional[ApiTokenInfo]:
+        with self.new_session() as session:
+            return session.query(ApiTokenInfo).filter(ApiTokenInfo.token_sha256 == token_sha256).first()
+
+    def get_api_tokens(self, user_id: str, include_revoked: bool = False) -> list[ApiTokenInfo]:
+        with self.new_session() as session:
+            query = session.query(ApiTokenInfo).filter(ApiTokenInfo.user_id == user_id)
+            if not include_revoked:
+                query = query.filter(ApiTokenInfo.revoked_at.is_(None))
+            return query.order_by(ApiTokenInfo.token_id).all()
+
+    def revoke_api_token(self, token_id: int) -> Optional[ApiTokenInfo]:
+        """Revoke a token, revoking it again keeps the first revocation time"""
+        with self.new_session() as session:
+            token = session.query(ApiTokenInfo).filter(ApiTokenInfo.token_id == token_id).first()
+            if token is None:
+                return None
+            if token.revoked_at is None:
+                token.revoked_at = datetime.now(timezone.utc)
+            session.commit()
+            session.refresh(token)
+            return token
+
+    def touch_api_token(self, token_id: int, now: datetime) -> None:
+        with self.new_session() as session:
+            session.query(ApiTokenInfo).filter(ApiTokenInfo.token_id == token_id).update({ApiTokenInfo.last_used_at: now})
+            session.commit()
+
+    # ------------------------------------------------------------
+    # Workspace related functions
+    # ------------------------------------------------------------
+
+    def create_workspace(self, workspace: WorkspaceInfo, instructor: WorkspaceMemberInfo) -> WorkspaceInfo:
+        """Create a workspace with its first instructor"""
+        with self.new_session() as session:
+            session.add(workspace)
+            session.flush()
+            instructor.workspace_id = workspace.workspace_id
+            session.add(instructor)
+            session.commit()
+            session.refresh(workspace)
+            return workspace
+
+    def get_workspace(self, workspace_id: int) -> Optional[WorkspaceInfo]:
+        with self.new_session() as session:
+            return session.query(WorkspaceInfo).filter(WorkspaceInfo.workspace_id == workspace_id).first()
+
+    def get_workspaces(self, user_id: str) -> list[WorkspaceInfo]:
+        """The workspaces a user is on the roster of"""
+        with self.new_session() as session:
+            return (
+                session.query(WorkspaceInfo)
+                .join(WorkspaceMemberInfo, WorkspaceMemberInfo.workspace_id == WorkspaceInfo.workspace_id)
+                .filter(WorkspaceMemberInfo.user_id == user_id)
+                .order_by(WorkspaceInfo.workspace_id)
+                .all()
+            )
+
+    def get_workspace_member(self, workspace_id: int, user_id: str) -> Optional[WorkspaceMemberInfo]:
+        with self.new_session() as session:
+            return session.query(WorkspaceMemberInfo).filter(WorkspaceMemberInfo.workspace_id == workspace_id, WorkspaceMemberInfo.user_id == user_id).first()
+
+    def get_workspace_members(self, workspace_id: int, role: Optional[str] = None) -> list[WorkspaceMemberInfo]:
+        with self.new_session() as session:
+            query = session.query(WorkspaceMemberInfo).filter(WorkspaceMemberInfo.workspace_id == workspace_id)
+            if role is not None:
+                query = query.filter(WorkspaceMemberInfo.role == role)
+            return query.order_by(WorkspaceMemberInfo.member_id).all()
+
+    def add_workspace_member(self, member: WorkspaceMemberInfo) -> WorkspaceMemberInfo:
+        with self.new_session() as session:
+            session.add(member)
+            session.commit()
+            session.refresh(member)
+            return member
+
+    def update_workspace_member(self, workspace_id: int, user_id: str, role: Optional[str] = None, share_attempts: Optional[bool] = None) -> Optional[WorkspaceMemberInfo]:
+        """Change the role of a member or whether they share their attempts, None leaves a field as it is"""
+        with self.new_session() as session:
+            member = session.query(WorkspaceMemberInfo).filter(WorkspaceMemberInfo.workspace_id == workspace_id, WorkspaceMemberInfo.user_id == user_id).first()
+            if member is None:
+                return None
+            if role is not None:
+                member.role = role
+            if share_attempts is not None:
+                member.share_attempts = share_attempts
+            session.commit()
+            session.refresh(member)
+            return member
+
+    def remove_workspace_member(self, workspace_id: int, user_id: str) -> bool:
+        with self.new_session() as session:
+            removed = session.query(WorkspaceMemberInfo).filter(WorkspaceMemberInfo.workspace_id == workspace_id, WorkspaceMemberInfo.user_id == user_id).delete()
+            session.commit()
+            return removed > 0
+
+    def create_assignment(self, assignment: AssignmentInfo) -> AssignmentInfo:
+        with self.new_session() as session:
+            session.add(assignment)
+            session.commit()
+            session.refresh(assignment)
+            return assignment
+
+    def get_assignments(self, workspace_id: int) -> list[AssignmentInfo]:
+        """The assignments of a workspace, newest first"""
+        with self.new_session() as session:
+            return session.query(AssignmentInfo).filter(AssignmentInfo.workspace_id == workspace_id).order_by(AssignmentInfo.created_at.desc(), AssignmentInfo.assignment_id.desc()).all()
+
+    def get_chapter_review_grades(self, user_ids: List[str], book_id: int) -> list[Tuple[str, Optional[int], int]]:
+        """(user, chapter, grade) of the users' reviews of a book's cards they had seen before, undone reviews aside"""
+        if not user_ids:
+            return []
+        with self.new_session() as session:
+            rows = (
+                session.query(ReviewInfo.user_id, FlashcardInfo.chapter_id, ReviewInfo.grade)
+                .join(FlashcardInfo, FlashcardInfo.card_id == ReviewInfo.card_id)
+                .filter(
+                    ReviewInfo.user_id.in_(user_ids),
+                    ReviewInfo.book_id == book_id,
+                    ReviewInfo.undone_at.is_(None),
+                    ReviewInfo.previous_due_at.is_not(None),
+                )
+                .all()
             )
-            return _try_save(session, page_info)
+            return [(user_id, chapter_id, grade) for user_id, chapter_id, grade in rows]
 
-    def get_page_info(self, page_id: int) -> Optional[PageInfo]:
+    # ------------------------------------------------------------
+    # Document related functions
+    # ------------------------------------------------------------
+
+    def create_document(self, document: DocumentInfo) -> DocumentInfo:
         with self.new_session() as session:
-            return _query_page_by_id(session, page_id)
+            session.add(document)
+            session.commit()
+            session.refresh(document)
+            return document
+
+    def get_document(self, document_id: int) -> Optional[DocumentInfo]:
+        with self.new_session() as session:
+            return _query_document_by_id(session, document_id)
+
+    def get_documents(self) -> list[DocumentInfo]:
+        with self.new_session() as session:
+            return session.query(DocumentInfo).order_by(DocumentInfo.document_id).all()
+
+    def update_document(self, document_id: int, **changes) -> Optional[DocumentInfo]:
+        """Change some columns of a document and its update time, None if there is no such document"""
+        with self.new_session() as session:
+            document = _query_document_by_id(session, document_id)
+            if document is None:
+                return None
+            for column, value in changes.items():
+                setattr(document, column, value)
+            document.updated_at = datetime.now(timezone.utc)
+            session.commit()
+            session.refresh(document)
+            return document
+
+    def delete_document(self, document_id: int) -> bool:
+        with self.new_session() as session:
+            document = _query_document_by_id(session, document_id)
+            if document is None:
+                return False
+            session.delete(document)
+            session.commit()
+            return True
+
+    def replace_content_chunks(self, document_id: int, chunks: List[ContentChunkInfo], chunk_kind: Optional[str] = None) -> int:
+        """Replace the page and section chunks of a document, or only those of a kind, returns how many were stored"""
+        with self.new_session() as session:
+            query = session.query(ContentChunkInfo).filter(ContentChunkInfo.document_id == document_id)
+            if chunk_kind is not None:
+                query = query.filter(ContentChunkInfo.chunk_kind == chunk_kind)
+            query.delete()
+            for chunk in chunks:
+                chunk.document_id = document_id
+                session.add(chunk)
+            session.commit()
+            return len(chunks)
+
+    def get_content_chunks(self, document_id: int, chunk_kind: str) -> list[ContentChunkInfo]:
+        with self.new_session() as session:
+            return session.query(ContentChunkInfo).filter(
+                ContentChunkInfo.document_id == document_id,
+                ContentChunkInfo.chunk_kind == chunk_kind,
+            ).order_by(ContentChunkInfo.chunk_index).all()
+
+    def get_content_chunk(self, document_id: int, chunk_kind: str, chunk_index: int) -> Optional[ContentChunkInfo]:
+        with self.new_session() as session:
+            return session.query(ContentChunkInfo).filter(
+                ContentChunkInfo.document_id == document_id,
+                ContentChunkInfo.chunk_kind == chunk_kind,
+                ContentChunkInfo.chunk_index == chunk_index,
+            ).first()
+
+    def move_problems(self, document_id: int, section_indexes: Dict[int, Optional[int]]) -> int:
+        """Move the problems of a document from sections to others, keyed by their old section, returns how many moved"""
+        with self.new_session() as session:
+            problems = session.query(ProblemInfo).filter(
+                ProblemInfo.document_id == document_id,
+                ProblemInfo.section_index.in_(list(section_indexes)),
+            ).all()
+            for problem in problems:
+                problem.section_index = section_indexes[problem.section_index]
+            session.commit()
+            return len(problems)
+
+    def replace_problems(self, document_id: int, problems: List[ProblemInfo], section_indexes: Optional[List[int]] = None) -> List[int]:
+        """Replace the problems of a document (or of some of its sections), returns the IDs of the new ones"""
+        with self.new_session() as session:
+            query = session.query(ProblemInfo).filter(ProblemInfo.document_id == document_id)
+            if section_indexes is not None:
+                query = query.filter(ProblemInfo.section_index.in_(section_indexes))
+            # Bulk deletes skip the ORM cascade, what belongs to the replaced problems goes first
+            replaced = [problem.problem_id for problem in query.all()]
+            session.query(SolutionInfo).filter(SolutionInfo.problem_id.in_(replaced)).delete()
+            session.query(HintInfo).filter(HintInfo.problem_id.in_(replaced)).delete()
+            session.query(ProblemStateInfo).filter(ProblemStateInfo.problem_id.in_(replaced)).delete()
+            session.query(ProblemReviewInfo).filter(ProblemReviewInfo.problem_id.in_(replaced)).delete()
+            query.delete()
+            for problem in problems:
+                problem.document_id = document_id
+                session.add(problem)
+            session.commit()
+            return [problem.problem_id for problem in problems]
+
+    def get_problems(self, document_id: int, section_index: Optional[int] = None) -> list[ProblemInfo]:
+        with self.new_session() as session:
+            query = session.query(ProblemInfo).filter(ProblemInfo.document_id == document_id)
+            if section_index is not None:
+                query = query.filter(ProblemInfo.section_index == section_index)
+            return query.order_by(ProblemInfo.problem_id).all()
+
+    def get_problem_state(self, user_id: str, problem_id: int) -> Optional[ProblemStateInfo]:
+        with self.new_session() as session:
+            return session.query(ProblemStateInfo).filter(ProblemStateInfo.user_id == user_id, ProblemStateInfo.problem_id == problem_id).first()
+
+    def get_due_problem_states(self, user_id: str, now: datetime, limit: int, document_id: Optional[int] = None) -> list[ProblemStateInfo]:
+        """The schedules of the problems due for a user, most overdue first, with their problems"""
+        with self.new_session() as session:
+            query = session.query(ProblemStateInfo).options(selectinload(ProblemStateInfo.problem)).filter(ProblemStateInfo.user_id == user_id, ProblemStateInfo.due_at <= now)
+            if document_id is not None:
+                query = query.filter(ProblemStateInfo.document_id == document_id)
+            return query.order_by(ProblemStateInfo.due_at, ProblemStateInfo.problem_id).limit(limit).all()
+
+    def get_new_problems(self, user_id: str, limit: int, document_id: Optional[int] = None) -> list[ProblemInfo]:
+        """Problems a user has never reviewed, in extraction order, leaving out those flagged or rejected by the quality checks"""
+        with self.new_session() as session:
+            reviewed = session.query(ProblemStateInfo.problem_id).filter(ProblemStateInfo.user_id == user_id)
+            query = session.query(ProblemInfo).filter(
+                ProblemInfo.problem_id.not_in(reviewed),
+                or_(ProblemInfo.quality_status.is_(None), ProblemInfo.quality_status.not_in(("flagged", "rejected"))),
+            )
+            if document_id is not None:
+                query = query.filter(ProblemInfo.document_id == document_id)
+            return query.order_by(ProblemInfo.problem_id).limit(limit).all()
+
+    def save_problem_review(self, review: ProblemReviewInfo) -> ProblemStateInfo:
+        """Store a review and move the problem's schedule to it, in one transaction"""
+        with self.new_session() as session:
+            state = session.query(ProblemStateInfo).filter(ProblemStateInfo.user_id == review.user_id, ProblemStateInfo.problem_id == review.problem_id).first()
+            if state is None:
+                state = ProblemStateInfo(problem_id=review.problem_id, user_id=review.user_id, introduced_at=review.reviewed_at, document_id=review.document_id)
+                session.add(state)
+            state.interval_days = review.interval_days
+            state.ease = review.ease
+            state.repetitions = review.repetitions
+            state.lapses = review.lapses
+            state.due_at = review.due_at
+            state.last_reviewed_at = review.reviewed_at
+            session.add(review)
+            session.flush()
+            session.add(_problem_review_event(review))
+            session.commit()
+            session.refresh(state)
+            session.refresh(review)
+            return state
+
+    def replace_glossary(self, document_id: int, terms: List[GlossaryTermInfo]) -> List[int]:
+        """Replace the glossary of a document, returns the IDs of the new terms"""
+        with self.new_session() as session:
+            session.query(GlossaryTermInfo).filter(GlossaryTermInfo.document_id == document_id).delete()
+            for term in terms:
+                term.document_id = document_id
+                session.add(term)
+            session.commit()
+            return [term.term_id for term in terms]
+
+    def get_glossary(self, document_id: int) -> list[GlossaryTermInfo]:
+        with self.new_session() as session:
+            return session.query(GlossaryTermInfo).filter(GlossaryTermInfo.document_id == document_id).order_by(GlossaryTermInfo.term_id).all()
+
+    def create_tutoring_messages(self, messages: List[TutoringMessageInfo]) -> List[int]:
+        """Store the messages of an exchange together, returns their IDs"""
+        with self.new_session() as session:
+            session.add_all(messages)
+            session.commit()
+            return [message.message_id for message in messages]
+
+    def get_tutoring_messages(self, session_id: str, limit: Optional[int] = None) -> list[TutoringMessageInfo]:
+        """Get the messages of a tutoring session, oldest first, only the last ones with a limit"""
+        with self.new_session() as session:
+            query = session.query(TutoringMessageInfo).filter(TutoringMessageInfo.session_id == session_id)
+            if limit is not None:
+                messages = query.order_by(TutoringMessageInfo.created_at.desc(), TutoringMessageInfo.message_id.desc()).limit(limit).all()
+                return list(reversed(messages))
+            return query.order_by(TutoringMessageInfo.created_at, TutoringMessageInfo.message_id).all()
+
+    def get_problem(self, problem_id: int) -> Optional[ProblemInfo]:
+        with self.new_session() as session:
+            return _query_problem_by_id(session, problem_id)
+
+    def update_problem(self, problem_id: int, **changes) -> Optional[ProblemInfo]:
+        """Change some columns of a problem, None if there is no such problem"""
+        with self.new_session() as session:
+            problem = _query_problem_by_id(session, problem_id)
+            if problem is None:
+                return None
+            for column, value in changes.items():
+                setattr(problem, column, value)
+            session.commit()
+            session.refresh(problem)
+            return problem
+
+    def get_problems_by_quality(self, quality_status: str, document_id: Optional[int] = None) -> list[ProblemInfo]:
+        """The problems with a quality status, lowest score first, only those of a document if given"""
+        with self.new_session() as session:
+            query = session.query(ProblemInfo).filter(ProblemInfo.quality_status == quality_status)
+            if document_id is not None:
+                query = query.filter(ProblemInfo.document_id == document_id)
+            return query.order_by(ProblemInfo.quality_score, ProblemInfo.problem_id).all()
+
+    def save_solution(self, solution: SolutionInfo) -> SolutionInfo:
+        """Store the solution of a problem, replacing the one generated before"""
+        with self.new_session() as session:
+            session.query(SolutionInfo).filter(SolutionInfo.problem_id == solution.problem_id).delete()
+            session.add(solution)
+            session.commit()
+            session.refresh(solution)
+            return solution
+
+    def get_solution(self, problem_id: int) -> Optional[SolutionInfo]:
+        with self.new_session() as session:
+            return session.query(SolutionInfo).filter(SolutionInfo.problem_id == problem_id).first()
+
+    def save_numeric_answer(self, numeric_answer: NumericAnswerInfo) -> NumericAnswerInfo:
+        """Store the numeric answer of a problem, replacing the one set before"""
+        with self.new_session() as session:
+            session.query(NumericAnswerInfo).filter(NumericAnswerInfo.problem_id == numeric_answer.problem_id).delete()
+            session.add(numeric_answer)
+            session.commit()
+            session.refresh(numeric_answer)
+            return numeric_answer
+
+    def get_numeric_answer(self, problem_id: int) -> Optional[NumericAnswerInfo]:
+        with self.new_session() as session:
+            return session.query(NumericAnswerInfo).filter(NumericAnswerInfo.problem_id == problem_id).first()
+
+    def delete_numeric_answer(self, problem_id: int) -> bool:
+        with self.new_session() as session:
+            deleted = session.query(NumericAnswerInfo).filter(NumericAnswerInfo.problem_id == problem_id).delete()
+            session.commit()
+            return deleted > 0
+
+    def replace_hints(self, problem_id: int, hints: List[HintInfo]) -> List[HintInfo]:
+        """Replace the hint ladder of a problem"""
+        with self.new_session() as session:
+            session.query(HintInfo).filter(HintInfo.problem_id == problem_id).delete()
+            for hint in hints:
+                hint.problem_id = problem_id
+                session.add(hint)
+            session.commit()
+            for hint in hints:
+                session.refresh(hint)
+            return hints
+
+    def get_hints(self, problem_id: int) -> list[HintInfo]:
+        with self.new_session() as session:
+            return session.query(HintInfo).filter(HintInfo.problem_id == problem_id).order_by(HintInfo.level).all()
+
+    # ------------------------------------------------------------
+    # Prompt log related functions
+    # ------------------------------------------------------------
+
+    def create_prompt_log(self, entry: PromptLogInfo) -> PromptLogInfo:
+        with self.new_session() as session:
+            session.add(entry)
+            session.commit()
+            session.refresh(entry)
+            return entry
+
+    def get_prompt_logs(self, job_id: Optional[str] = None, limit: int = 50) -> list[PromptLogInfo]:
+        """The latest log entries first, only those of a job if given"""
+        with self.new_session() as session:
+            query = session.query(PromptLogInfo)
+            if job_id is not None:
+                query = query.filter(PromptLogInfo.job_id == job_id)
+            return query.order_by(PromptLogInfo.log_id.desc()).limit(limit).all()
+
+    def delete_prompt_logs_before(self, cutoff: datetime) -> int:
+        """Delete the log entries older than cutoff, returns how many were deleted"""
+        with self.new_session() as session:
+            deleted = session.query(PromptLogInfo).filter(PromptLogInfo.created_at < cutoff).delete()
+            session.commit()
+            return deleted
+
+    # ------------------------------------------------------------
+    # Usage related functions
+    # ------------------------------------------------------------
+
+    def create_usage(self, usage: UsageInfo) -> UsageInfo:
+        with self.new_session() as session:
+            session.add(usage)
+            session.commit()
+            session.refresh(usage)
+            return usage
+
+    def get_usage(self, since: datetime, document_id: Optional[int] = None, job_id: Optional[str] = None) -> list[UsageInfo]:
+        """The usage records since a moment, oldest first, only those of a document or job if given"""
+        with self.new_session() as session:
+            query = session.query(UsageInfo).filter(UsageInfo.created_at >= since)
+            if document_id is not None:
+                query = query.filter(UsageInfo.document_id == document_id)
+            if job_id is not None:
+                query = query.filter(UsageInfo.job_id == job_id)
+            return query.order_by(UsageInfo.usage_id).all()
+
+    # ------------------------------------------------------------
+    # Study event related functions
+    # ------------------------------------------------------------
+
+    def get_study_events(
+        self,
+        after_id: int = 0,
+        limit: Optional[int] = None,
+        user_id: Optional[str] = None,
+        include_unattributed: bool = False,
+        book_id: Optional[int] = None,
+        kinds: Optional[List[str]] = None,
+    ) -> list[StudyEventInfo]:
+        """The events after an event ID in the order they were appended, only those of a user (and the events without a user if include_unattributed), a book or some kinds if given"""
+        with self.new_session() as session:
+            query = session.query(StudyEventInfo).filter(StudyEventInfo.event_id > after_id)
+            if user_id is not None:
+                query = query.filter(or_(StudyEventInfo.user_id == user_id, StudyEventInfo.user_id.is_(None)) if include_unattributed else StudyEventInfo.user_id == user_id)
+            if book_id is not None:
+                query = query.filter(StudyEventInfo.book_id == book_id)
+            if kinds is not None:
+                query = query.filter(StudyEventInfo.event_kind.in_(kinds))
+            query = query.order_by(StudyEventInfo.event_id)
+            return (query.limit(limit) if limit is not None else query).all()
+
+    def count_study_events(self) -> int:
+        with self.new_session() as session:
+            return session.query(StudyEventInfo).count()
+
+    def backfill_study_events(self) -> int:
+        """
+        Append the events of the study history stored before the stream existed, while the stream is empty
+
+        Attempts come with their judgments and voiding, reviews with their undoing. Reading items only kept their last
+        read, earlier reads of an item are not recovered.
+
+        Returns:
+            int: The number of events appended, 0 if the stream had events already
+        """
+        with self.new_session() as session:
+            if session.query(StudyEventInfo.event_id).first() is not None:
+                return 0
+            events = []
+            for attempt in session.query(AttemptInfo).options(selectinload(AttemptInfo.judgments)).order_by(AttemptInfo.attempt_id):
+                events.append(_attempt_event(attempt, EVENT_ATTEMPTED, attempt.created_at, {"study_mode": attempt.study_mode}))
+                events.extend(_graded_event(attempt, judgment) for judgment in attempt.judgments)
+                if attempt.voided_at is not None:
+                    events.append(_attempt_event(attempt, EVENT_VOIDED, attempt.voided_at))
+            for review in session.query(ReviewInfo).order_by(ReviewInfo.review_id):
+                events.append(_review_event(review))
+                if review.undone_at is not None:
+                    events.append(_study_event(EVENT_REVIEW_UNDONE, SUBJECT_CARD, review.card_id, review.undone_at, {"review_id": review.review_id}, review.user_id, book_id=review.book_id))
+            events.extend(_problem_review_event(review) for review in session.query(ProblemReviewInfo).order_by(ProblemReviewInfo.review_id))
+            events.extend(_reading_event(item) for item in session.query(ReadingItemInfo).filter(ReadingItemInfo.last_read_at.is_not(None)).order_by(ReadingItemInfo.reading_id))
+            # Appended in the order they happened, SQLite returns naive datetimes in UTC
+            events.sort(key=lambda event: event.occurred_at.replace(tzinfo=None))
+            session.add_all(events)
+            session.commit()
+            return len(events)
+
+    # ------------------------------------------------------------
+    # Export fingerprint related functions
+    # ------------------------------------------------------------
+
+    def create_export_fingerprint(self, entry: ExportFingerprintInfo) -> ExportFingerprintInfo:
+        with self.new_session() as session:
+            session.add(entry)
+            session.commit()
+            session.refresh(entry)
+            return entry
+
+    def get_export_fingerprints(self, fingerprints: List[str]) -> list[ExportFingerprintInfo]:
+        """The recorded fingerprints among those given, oldest first"""
+        with self.new_session() as session:
+            query = session.query(ExportFingerprintInfo).filter(ExportFingerprintInfo.fingerprint.in_(fingerprints))
+            return query.order_by(ExportFingerprintInfo.created_at).all()
+
+    # ------------------------------------------------------------
+    # Response cache related functions
+    # ------------------------------------------------------------
+
+    def save_cached_response(self, entry: ResponseCacheInfo) -> ResponseCacheInfo:
+        """Create or replace the cached response of a request"""
+        with self.new_session() as session:
+            entry = session.merge(entry)
+            session.commit()
+            session.refresh(entry)
+            return entry
+
+    def use_cached_response(self, cache_key: str, not_before: datetime) -> Optional[ResponseCacheInfo]:
+        """The cached response of a request if stored after not_before, counting the hit"""
+        with self.new_session() as session:
+            entry = session.query(ResponseCacheInfo).filter(
+                ResponseCacheInfo.cache_key == cache_key,
+                ResponseCacheInfo.created_at >= not_before,
+            ).first()
+            if entry is None:
+                return None
+            entry.hits += 1
+            entry.last_used_at = datetime.now(timezone.utc)
+            session.commit()
+            session.refresh(entry)
+            return entry
+
+    def delete_cached_responses_before(self, cutoff: datetime) -> int:
+        """Delete the cached responses stored before cutoff, returns how many were deleted"""
+        with self.new_session() as session:
+            deleted = session.query(ResponseCacheInfo).filter(ResponseCacheInfo.created_at < cutoff).delete()
+            session.commit()
+            return deleted
+
+    def trim_cached_responses(self, max_entries: int) -> int:
+        """Delete the least recently used cached responses beyond max_entries, returns how many were deleted"""
+        with self.new_session() as session:
+            kept = session.query(ResponseCacheInfo.cache_key).order_by(ResponseCacheInfo.last_used_at.desc()).limit(max_entries)
+            deleted = session.query(ResponseCacheInfo).filter(ResponseCacheInfo.cache_key.notin_(kept.scalar_subquery())).delete(synchronize_session=False)
+            session.commit()
+            return deleted
+
+    # ------------------------------------------------------------
+    # Compression related functions
+    # ------------------------------------------------------------
+
+    def compress_stored_text(self) -> Dict[str, int]:
+        """Write the compressed columns of the rows stored before compression again, returns the rows written per table"""
+        written = {}
+        for model in (ContentChunkInfo, PromptLogInfo, ResponseCacheInfo):
+            columns = [column.key for column in model.__table__.columns if isinstance(column.type, CompressedText)]
+            with self.new_session() as session:
+                # Rows written before are stored as text, the others as blobs
+                rows = session.query(model).filter(or_(*(func.typeof(getattr(model, column)) == "text" for column in columns))).all()
+                for row in rows:
+                    for column in columns:
+                        flag_modified(row, column)
+                session.commit()
+                written[model.__tablename__] = len(rows)
+        return written
+
+    def vacuum(self):
+        """Rebuild the database file, giving the space freed by deleted or compressed rows back to the disk"""
+        with self.engine.connect().execution_options(isolation_level="AUTOCOMMIT") as connection:
+            connection.exec_driver_sql("VACUUM")
+
+    # ------------------------------------------------------------
+    # Suggestion related functions
+    # ------------------------------------------------------------
+
+    def get_suggestion_sources(self) -> Dict[str, list]:
+        """The titles, headings and entity names search suggestions complete, as rows of the columns needed only"""
+        with self.new_session() as session:
+            return {
+                "documents": session.query(DocumentInfo.document_id, DocumentInfo.title, DocumentInfo.book_id).all(),
+                "books": session.query(BookInfo.book_id, BookInfo.book_name).filter(BookInfo.archived_at.is_(None), BookInfo.book_name.is_not(None)).all(),
+                "chapters": session.query(ChapterInfo.chapter_id, ChapterInfo.title, ChapterInfo.start_page_number, ChapterInfo.book_id).all(),
+                "sections": session.query(SectionInfo.section_id, SectionInfo.title, SectionInfo.start_page_number, SectionInfo.book_id).all(),
+                # The headings of OCR output, without the text of their sections
+                "document_sections": session.query(ContentChunkInfo.chunk_index, ContentChunkInfo.title, ContentChunkInfo.page_start, ContentChunkInfo.document_id)
+                .filter(ContentChunkInfo.chunk_kind == "section", ContentChunkInfo.title.is_not(None)).all(),
+                "entities": session.query(EntityInfo.entity_id, EntityInfo.entity_name, EntityInfo.entity_kind, EntityInfo.page_number, EntityInfo.book_id)
+                .filter(EntityInfo.entity_name.is_not(None)).all(),
+            }
+
+    # ------------------------------------------------------------
+    # Job related functions
+    # ------------------------------------------------------------
+
+    def save_job(self, job: JobInfo) -> JobInfo:
+        """Create or replace a job, recording an event when its status changed"""
+        with self.new_session() as session:
+            stored = _query_job_by_id(session, job.job_id)
+            if stored is None:
+                stored = JobInfo(job_id=job.job_id)
+                session.add(stored)
+            if stored.status != job.status:
+                session.add(JobEventInfo(job_id=job.job_id, status=job.status, created_at=datetime.now(timezone.utc)))
+            for column in JobInfo.__table__.columns.keys():
+                setattr(stored, column, getattr(job, column))
+            session.commit()
+            session.refresh(stored)
+            return stored
+
+    def get_job(self, job_id: str) -> Optional[JobInfo]:
+        with self.new_session() as session:
+            return _query_job_by_id(session, job_id)
+
+    def get_jobs(self, status: Optional[str] = None, job_kind: Optional[str] = None, limit: int = 50) -> list[JobInfo]:
+        """Jobs, newest first"""
+        with self.new_session() as session:
+            query = session.query(JobInfo)
+            if status is not None:
+                query = query.filter(JobInfo.status == status)
+            if job_kind is not None:
+                query = query.filter(JobInfo.job_kind == job_kind)
+            return query.order_by(JobInfo.created_at.desc()).limit(limit).all()
+
+    def get_group_jobs(self, group_id: str) -> list[JobInfo]:
+        """The jobs of a group, oldest first"""
+        with self.new_session() as session:
+            return session.query(JobInfo).filter(JobInfo.group_id == group_id).order_by(JobInfo.created_at).all()
+
+    def get_job_retries(self, job_id: str) -> list[JobInfo]:
+        """The jobs retrying a job, oldest first"""
+        with self.new_session() as session:
+            return session.query(JobInfo).filter(JobInfo.retry_of == job_id).order_by(JobInfo.created_at).all()
+
+    def get_dead_letter_jobs(self, statuses: List[str], job_kind: Optional[str] = None, limit: int = 50) -> list[JobInfo]:
+        """Jobs in one of some statuses that no job retries, newest first"""
+        with self.new_session() as session:
+            retried = select(JobInfo.retry_of).where(JobInfo.retry_of.is_not(None))
+            query = session.query(JobInfo).filter(JobInfo.status.in_(statuses), JobInfo.job_id.not_in(retried))
+            if job_kind is not None:
+                query = query.filter(JobInfo.job_kind == job_kind)
+            return query.order_by(JobInfo.created_at.desc()).limit(limit).all()
+
+    def get_job_events(self, job_id: str) -> list[JobEventInfo]:
+        with self.new_session() as session:
+            return session.query(JobEventInfo).filter(JobEventInfo.job_id == job_id).order_by(JobEventInfo.event_id).all()
+
+    def delete_jobs(self, statuses: List[str], finished_before: Optional[datetime] = None) -> int:
+        """Delete the jobs in one of some statuses, finished before a time if given, with their events; returns how many"""
+        with self.new_session() as session:
+            query = session.query(JobInfo.job_id).filter(JobInfo.status.in_(statuses))
+            if finished_before is not None:
+                query = query.filter(JobInfo.finished_at < finished_before)
+            job_ids = [job_id for (job_id,) in query.all()]
+            for start in range(0, len(job_ids), 500):
+                batch = job_ids[start:start + 500]
+                session.query(JobEventInfo).filter(JobEventInfo.job_id.in_(batch)).delete(synchronize_session=False)
+                session.query(JobInfo).filter(JobInfo.job_id.in_(batch)).delete(synchronize_session=False)
+            session.commit()
+            return len(job_ids)
+
+    def move_jobs(self, from_statuses: List[str], status: str, error: Optional[str] = None) -> list[JobInfo]:
+        """Move every job in one of some statuses to another, e.g. the jobs left running when the server stopped"""
+        with self.new_session() as session:
+            now = datetime.now(timezone.utc)
+            jobs = session.query(JobInfo).filter(JobInfo.status.in_(from_statuses)).all()
+            for job in jobs:
+                job.status = status
+                job.error = error
+                job.finished_at = now
+                session.add(JobEventInfo(job_id=job.job_id, status=status, created_at=now))
+            session.commit()
+            for job in jobs:
+                session.refresh(job)
+            return jobs
+
+def _save_status(session: Session, item: Optional[Base], archived: Optional[bool], suspended: Optional[bool]):
+    """Apply an archive or suspend change to a book, chapter or card and return it, None if it does not exist"""
+    if item is None:
+        return None
+    apply_status(item, archived, suspended)
+    session.commit()
+    session.refresh(item)
+    return item
 
 def _try_save(session: Session, obj: Base):
     return_field = None
@@ -585,13 +3988,14 @@ def _query_all_books(session: Session) -> list[BookInfo]:
     """Query all books"""
     return session.query(BookInfo).all()
 
-def _create_book_and_return_info(session: Session, book_name: str, book_author: str, book_keywords: str, book_file_name: str, page_count: int) -> Optional[BookInfo]:
+def _create_book_and_return_info(session: Session, book_name: str, book_author: str, book_keywords: str, book_file_name: str, page_count: int, ingestion_mode: Optional[str] = None) -> Optional[BookInfo]:
         book_info = BookInfo(
             book_name=book_name,
             book_author=book_author,
             book_keywords=book_keywords,
             book_file_name=book_file_name,
             book_pages=page_count,
+            book_ingestion_mode=ingestion_mode,
         )
         session.add(book_info)
         session.commit()
@@ -666,4 +4070,224 @@ def _query_page_by_book_id_and_page_number(session: Session, book_id: int, page_
 
 def _query_page_by_id(session: Session, page_id: int) -> Optional[PageInfo]:
     """Query page by ID"""
-    return session.query(PageInfo).filter(PageInfo.page_id == page_id).first()
\ No newline at end of file
+    return session.query(PageInfo).filter(PageInfo.page_id == page_id).first()
+
+# ------------------------------------------------------------
+# Exercise related functions
+# ------------------------------------------------------------
+
+def _query_exercise_by_id(session: Session, exercise_id: int) -> Optional[ExerciseInfo]:
+    """Query exercise by ID, with its details"""
+    return session.query(ExerciseInfo).options(selectinload(ExerciseInfo.details)).filter(ExerciseInfo.exercise_id == exercise_id).first()
+
+def _query_exercises_by_book_id(session: Session, book_id: int, exercise_type: Optional[str] = None) -> list[ExerciseInfo]:
+    """Query exercises by book ID, optionally of one type (exercises without a type are text exercises)"""
+    query = session.query(ExerciseInfo).options(selectinload(ExerciseInfo.details)).filter(ExerciseInfo.book_id == book_id)
+    if exercise_type == "text":
+        query = query.filter((ExerciseInfo.exercise_type == "text") | (ExerciseInfo.exercise_type.is_(None)))
+    elif exercise_type is not None:
+        query = query.filter(ExerciseInfo.exercise_type == exercise_type)
+    return query.order_by(ExerciseInfo.page_number, ExerciseInfo.exercise_id).all()
+
+# ------------------------------------------------------------
+# Entity and cross reference related functions
+# ------------------------------------------------------------
+
+def _query_entities_by_book_id(session: Session, book_id: int, entity_kinds: Optional[List[str]] = None) -> list[EntityInfo]:
+    """Query entities by book ID, optionally only some kinds"""
+    query = session.query(EntityInfo).filter(EntityInfo.book_id == book_id)
+    if entity_kinds:
+        query = query.filter(EntityInfo.entity_kind.in_(entity_kinds))
+    return query.order_by(EntityInfo.page_number, EntityInfo.entity_id).all()
+
+def _query_cross_references_by_book_id(session: Session, book_id: int, page_number: Optional[int] = None) -> list[CrossReferenceInfo]:
+    """Query cross references by book ID, optionally only one page"""
+    query = session.query(CrossReferenceInfo).filter(CrossReferenceInfo.book_id == book_id)
+    if page_number is not None:
+        query = query.filter(CrossReferenceInfo.page_number == page_number)
+    return query.order_by(CrossReferenceInfo.page_number, CrossReferenceInfo.char_start).all()
+
+# ------------------------------------------------------------
+# Flashcard related functions
+# ------------------------------------------------------------
+
+def _query_flashcards_by_book_id(session: Session, book_id: int, chapter_id: Optional[int] = None, entity_kinds: Optional[List[str]] = None) -> list[FlashcardInfo]:
+    """Query flashcards by book ID, optionally only one chapter or some entity kinds"""
+    query = session.query(FlashcardInfo).filter(FlashcardInfo.book_id == book_id)
+    if chapter_id is not None:
+        query = query.filter(FlashcardInfo.chapter_id == chapter_id)
+    if entity_kinds:
+        query = query.filter(FlashcardInfo.entity_kind.in_(entity_kinds))
+    return query.order_by(FlashcardInfo.card_id).all()
+
+def _filter_reviewable_cards(query):
+    """Keep the cards of a query over flashcard_info that are neither archived nor suspended, nor in an archived or suspended chapter or book"""
+    query = query.join(BookInfo, FlashcardInfo.book_id == BookInfo.book_id).outerjoin(ChapterInfo, FlashcardInfo.chapter_id == ChapterInfo.chapter_id)
+    return query.filter(
+        FlashcardInfo.archived_at.is_(None), FlashcardInfo.suspended_at.is_(None),
+        BookInfo.archived_at.is_(None), BookInfo.suspended_at.is_(None),
+        ChapterInfo.archived_at.is_(None), ChapterInfo.suspended_at.is_(None),
+    )
+
+# ------------------------------------------------------------
+# Review related functions
+# ------------------------------------------------------------
+
+def _query_due_card_states(session: Session, user_id: str, now: datetime, book_id: Optional[int] = None):
+    """Query the states of a user's cards due by now, most overdue first, with their cards"""
+    query = session.query(CardStateInfo).options(selectinload(CardStateInfo.card)).join(FlashcardInfo, CardStateInfo.card_id == FlashcardInfo.card_id)
+    query = _filter_reviewable_cards(query).filter(CardStateInfo.user_id == user_id, CardStateInfo.due_at <= now)
+    if book_id is not None:
+        query = query.filter(CardStateInfo.book_id == book_id)
+    return query.order_by(CardStateInfo.due_at, CardStateInfo.card_id)
+
+def _query_new_flashcards(session: Session, user_id: str, book_id: Optional[int] = None):
+    """Query the reviewable cards a user has never reviewed"""
+    reviewed = session.query(CardStateInfo.card_id).filter(CardStateInfo.user_id == user_id)
+    query = _filter_reviewable_cards(session.query(FlashcardInfo)).filter(FlashcardInfo.card_id.not_in(reviewed))
+    if book_id is not None:
+        query = query.filter(FlashcardInfo.book_id == book_id)
+    return query
+
+# ------------------------------------------------------------
+# Attempt related functions
+# ------------------------------------------------------------
+
+def _query_attempt_by_id(session: Session, attempt_id: int) -> Optional[AttemptInfo]:
+    """Query attempt by ID, with its misconceptions and judgments"""
+    return session.query(AttemptInfo).options(selectinload(AttemptInfo.misconceptions), selectinload(AttemptInfo.judgments)).filter(AttemptInfo.attempt_id == attempt_id).first()
+
+def _query_attempts_by_exercise_id(session: Session, exercise_id: int) -> list[AttemptInfo]:
+    """Query attempts by exercise ID, with their misconceptions and judgments"""
+    return session.query(AttemptInfo).options(selectinload(AttemptInfo.misconceptions), selectinload(AttemptInfo.judgments)).filter(AttemptInfo.exercise_id == exercise_id).order_by(AttemptInfo.attempt_id).all()
+
+def _query_recent_misconceptions(session: Session, limit: int, book_id: Optional[int] = None) -> list[MisconceptionInfo]:
+    """Query the most recent misconceptions, optionally only one book"""
+    # Misconceptions of voided attempts no longer count
+    query = session.query(MisconceptionInfo).join(AttemptInfo, MisconceptionInfo.attempt_id == AttemptInfo.attempt_id).filter(AttemptInfo.voided_at.is_(None))
+    if book_id is not None:
+        query = query.filter(MisconceptionInfo.book_id == book_id)
+    return query.order_by(MisconceptionInfo.created_at.desc(), MisconceptionInfo.misconception_id.desc()).limit(limit).all()
+
+# ------------------------------------------------------------
+# Reflection related functions
+# ------------------------------------------------------------
+
+def _query_reflections_by_book_id(session: Session, book_id: int, chapter_id: Optional[int] = None) -> list[ReflectionInfo]:
+    """Query reflections by book ID, oldest first, optionally only one chapter"""
+    query = session.query(ReflectionInfo).filter(ReflectionInfo.book_id == book_id)
+    if chapter_id is not None:
+        query = query.filter(ReflectionInfo.chapter_id == chapter_id)
+    return query.order_by(ReflectionInfo.created_at, ReflectionInfo.reflection_id).all()
+
+# ------------------------------------------------------------
+# Dispute related functions
+# ------------------------------------------------------------
+
+def _query_dispute_by_id(session: Session, dispute_id: int) -> Optional[DisputeInfo]:
+    """Query dispute by ID"""
+    return session.query(DisputeInfo).filter(DisputeInfo.dispute_id == dispute_id).first()
+
+def _query_disputes(session: Session, status: Optional[str] = None, attempt_id: Optional[int] = None) -> list[DisputeInfo]:
+    """Query disputes, oldest first, optionally only one status or attempt"""
+    query = session.query(DisputeInfo)
+    if status is not None:
+        query = query.filter(DisputeInfo.status == status)
+    if attempt_id is not None:
+        query = query.filter(DisputeInfo.attempt_id == attempt_id)
+    return query.order_by(DisputeInfo.created_at, DisputeInfo.dispute_id).all()
+
+# ------------------------------------------------------------
+# Highlight related functions
+# ------------------------------------------------------------
+
+def _query_highlights_by_book_id(session: Session, book_id: int, user_id: str, start_page_number: Optional[int] = None, end_page_number: Optional[int] = None) -> list[HighlightInfo]:
+    """Query a user's highlights by book ID in reading order, optionally only a page range"""
+    query = session.query(HighlightInfo).filter(HighlightInfo.book_id == book_id, HighlightInfo.user_id == user_id)
+    if start_page_number is not None:
+        query = query.filter(HighlightInfo.page_number >= start_page_number)
+    if end_page_number is not None:
+        query = query.filter(HighlightInfo.page_number <= end_page_number)
+    return query.order_by(HighlightInfo.page_number, HighlightInfo.char_start, HighlightInfo.highlight_id).all()
+
+# ------------------------------------------------------------
+# User preferences related functions
+# ------------------------------------------------------------
+
+def _query_user_preferences_by_id(session: Session, user_id: str) -> Optional[UserPreferencesInfo]:
+    """Query the preferences of a user"""
+    return session.query(UserPreferencesInfo).filter(UserPreferencesInfo.user_id == user_id).first()
+
+# ------------------------------------------------------------
+# Artifact related functions
+# ------------------------------------------------------------
+
+def _query_artifact_by_path(session: Session, path: str) -> Optional[ArtifactInfo]:
+    """Query artifact by path"""
+    return session.query(ArtifactInfo).filter(ArtifactInfo.artifact_path == path).first()
+
+def _query_artifacts_by_book_id(session: Session, book_id: int) -> list[ArtifactInfo]:
+    """Query artifacts by book ID"""
+    return session.query(ArtifactInfo).filter(ArtifactInfo.book_id == book_id).order_by(ArtifactInfo.artifact_id).all()
+
+# ------------------------------------------------------------
+# Document related functions
+# ------------------------------------------------------------
+
+def _query_document_by_id(session: Session, document_id: int) -> Optional[DocumentInfo]:
+    """Query document by ID"""
+    return session.query(DocumentInfo).filter(DocumentInfo.document_id == document_id).first()
+
+def _query_problem_by_id(session: Session, problem_id: int) -> Optional[ProblemInfo]:
+    """Query problem by ID"""
+    return session.query(ProblemInfo).filter(ProblemInfo.problem_id == problem_id).first()
+
+# ------------------------------------------------------------
+# Job related functions
+# ------------------------------------------------------------
+
+def _query_job_by_id(session: Session, job_id: str) -> Optional[JobInfo]:
+    """Query job by ID"""
+    return session.query(JobInfo).filter(JobInfo.job_id == job_id).first()
+
+# ------------------------------------------------------------
+# Study event related functions
+# ------------------------------------------------------------
+
+def _study_event(event_kind: str, subject_kind: str, subject_id: int, occurred_at: Optional[datetime], payload: Optional[dict] = None, user_id: Optional[str] = None, book_id: Optional[int] = None, document_id: Optional[int] = None) -> StudyEventInfo:
+    """A study event to append in the transaction of the action"""
+    return StudyEventInfo(
+        event_kind=event_kind,
+        user_id=user_id,
+        subject_kind=subject_kind,
+        subject_id=subject_id,
+        payload=encode_payload(payload),
+        occurred_at=occurred_at or datetime.now(timezone.utc),
+        book_id=book_id,
+        document_id=document_id,
+    )
+
+def _attempt_event(attempt: AttemptInfo, event_kind: str, occurred_at: Optional[datetime], payload: Optional[dict] = None) -> StudyEventInfo:
+    """An event of an attempt, which is not made per user"""
+    return _study_event(event_kind, SUBJECT_EXERCISE, attempt.exercise_id, occurred_at, {"attempt_id": attempt.attempt_id, **(payload or {})}, book_id=attempt.book_id)
+
+def _graded_event(attempt: AttemptInfo, judgment: GradingJudgmentInfo) -> StudyEventInfo:
+    return _attempt_event(attempt, EVENT_GRADED, judgment.created_at, {
+        "score": judgment.score,
+        "is_correct": judgment.is_correct,
+        "confidence": judgment.confidence,
+        "model_name": judgment.model_name,
+        "reason": judgment.reason,
+    })
+
+def _review_event(review: ReviewInfo) -> StudyEventInfo:
+    payload = {"review_id": review.review_id, "grade": review.grade, "duration_ms": review.duration_ms, "interval_days": review.interval_days, "first_review": review.previous_due_at is None}
+    return _study_event(EVENT_REVIEWED, SUBJECT_CARD, review.card_id, review.reviewed_at, payload, review.user_id, book_id=review.book_id)
+
+def _problem_review_event(review: ProblemReviewInfo) -> StudyEventInfo:
+    payload = {"review_id": review.review_id, "grade": review.grade, "interval_days": review.interval_days}
+    return _study_event(EVENT_REVIEWED, SUBJECT_PROBLEM, review.problem_id, review.reviewed_at, payload, review.user_id, document_id=review.document_id)
+
+def _reading_event(item: ReadingItemInfo) -> StudyEventInfo:
+    payload = {"read_count": item.read_count, "done": item.done_at is not None, "section_id": item.section_id, "page_number": item.page_number}
+    return _study_event(EVENT_READ, SUBJECT_READING, item.reading_id, item.last_read_at, payload, item.user_id, book_id=item.book_id)
@@ -0,0 +1,96 @@
+# Mastery decay
+# Mastery (see quiz) is estimated from the graded attempts of a chapter and stays put while the chapter is left
+# alone, but what is not practiced is forgotten. The projected mastery of a chapter halves over a half-life since
+# its last attempt, and the half-life grows with the chapter's review interval, so material answered correctly many
+# times in a row fades slower. A chapter is about to be forgotten when its projected mastery drops below
+# FORGET_THRESHOLD within the horizon, and a refresh is suggested REFRESH_LEAD_DAYS before, while it is still
+# mostly known. Chapters never mastered above the threshold are weak rather than forgotten, quizzes practice those;
+# archived chapters, and archived or suspended books, are left out.
+
+import math
+from dataclasses import dataclass
+from datetime import date, datetime, timedelta, timezone
+from typing import List, Optional
+
+from textbook.database import TextBookDatabase
+from textbook.quiz import TopicMastery, book_mastery
+from textbook.timestamps import as_utc
+
+DECAY_HALF_LIFE_DAYS = 14.0  # Of a chapter with a review interval of a day or none, longer intervals scale it
+FORGET_THRESHOLD = 0.5  # Projected mastery below which a chapter is forgotten
+REFRESH_HORIZON_DAYS = 7  # Chapters forgotten within this many days are suggested for a refresh
+REFRESH_LEAD_DAYS = 1  # A refresh is suggested this many days before the chapter is forgotten
+
+
+@dataclass
+class RefreshSuggestion:
+    book_id: int
+    chapter_id: int
+    title: str
+    mastery: float  # As estimated from the attempts
+    projected_mastery: float  # Decayed to now
+    forget_on: date  # The (UTC) day the projected mastery drops below the threshold, today if it already has
+    refresh_on: date
+
+    @property
+    def message(self) -> str:
+        return f"You're about to forget {self.title}"
+
+
+def _age_days(moment: datetime, now: datetime) -> float:
+    return max((as_utc(now) - as_utc(moment)).total_seconds() / 86400, 0.0)
+
+
+def decay_half_life_days(mastery: TopicMastery) -> float:
+    return DECAY_HALF_LIFE_DAYS * max(1.0, mastery.review_interval_days or 1.0)
+
+
+def projected_mastery(mastery: TopicMastery, at: datetime) -> float:
+    """The mastery of a chapter decayed from its last attempt until at"""
+    if mastery.last_attempt_at is None:
+        return mastery.mastery
+    return mastery.mastery * 0.5 ** (_age_days(mastery.last_attempt_at, at) / decay_half_life_days(mastery))
+
+
+def days_until_forgotten(mastery: TopicMastery, now: datetime, threshold: float = FORGET_THRESHOLD) -> Optional[float]:
+    """Days from now until the projected mastery drops below the threshold, 0 if it has; None for chapters never mastered above it"""
+    if mastery.last_attempt_at is None or mastery.mastery <= threshold or threshold <= 0:
+        return None
+    days = decay_half_life_days(mastery) * math.log2(mastery.mastery / threshold) - _age_days(mastery.last_attempt_at, now)
+    return max(days, 0.0)
+
+
+def refresh_suggestions(
+    database: TextBookDatabase,
+    now: Optional[datetime] = None,
+    book_id: Optional[int] = None,
+    threshold: float = FORGET_THRESHOLD,
+    horizon_days: int = REFRESH_HORIZON_DAYS,
+) -> List[RefreshSuggestion]:
+    """The chapters forgotten within the horizon, of one book or all, soonest first"""
+    now = as_utc(now or datetime.now(timezone.utc))
+    books = [book for book in database.get_all_books() if book_id is None or book.book_id == book_id]
+    suggestions = []
+    for book in books:
+        if book.archived_at is not None or book.suspended_at is not None:
+            continue
+        masteries = book_mastery(database, book.book_id)
+        for chapter in database.get_chapters_by_book_id(book.book_id):
+            mastery = masteries.get(chapter.chapter_id)
+            if chapter.archived_at is not None or mastery is None:
+                continue
+            days = days_until_forgotten(mastery, now, threshold)
+            if days is None or days > horizon_days:
+                continue
+            forget_on = (now + timedelta(days=days)).date()
+            suggestions.append(RefreshSuggestion(
+                book_id=book.book_id,
+                chapter_id=chapter.chapter_id,
+                title=chapter.title,
+                mastery=mastery.mastery,
+                projected_mastery=projected_mastery(mastery, now),
+                forget_on=forget_on,
+                refresh_on=max(now.date(), forget_on - timedelta(days=REFRESH_LEAD_DAYS)),
+            ))
+    suggestions.sort(key=lambda suggestion: (suggestion.forget_on, suggestion.projected_mastery))
+    return suggestions
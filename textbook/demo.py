@@ -0,0 +1,82 @@
+# Demo document
+# A short public domain textbook on divisibility and primes ships in demo_content, together with everything the LLM
+# would generate for it: the summary, the glossary and its exercises with their solutions and hint ladders. Seeding
+# the demo stores all of it as a library document, without OCR or a single prompt, so the reader, problems, hints
+# and problem reviews can be tried before an LLM provider is configured. The server seeds it on its first run, into
+# an empty library, unless `seed_demo = false` in config.toml, and POST /admin/seed-demo seeds it on demand.
+# Seeding twice keeps the document seeded first.
+
+import json
+from pathlib import Path
+from typing import Optional, Tuple
+
+from textbook.content import chunk_content, store_chunks
+from textbook.database import DocumentInfo, GlossaryTermInfo, HintInfo, ProblemInfo, SolutionInfo, TextBookDatabase
+from textbook.hints import HINT_TIERS
+from textbook.library import DOCUMENT_TYPE_TEXTBOOK, OCR_NOT_NEEDED, document_tags
+from textbook.problems import document_chapters
+from textbook.segmentation import segment_document
+
+DEMO_DIR = Path(__file__).parent / "demo_content"
+DEMO_MARKDOWN = DEMO_DIR / "sample_textbook.md"
+DEMO_FIXTURES = DEMO_DIR / "fixtures.json"
+DEMO_SOURCE_PATH = "demo_content/sample_textbook.md"  # Marks the demo document in the library
+DEMO_TAG = "demo"
+
+
+def find_demo(database: TextBookDatabase) -> Optional[DocumentInfo]:
+    """The seeded demo document, None if it was not seeded or was deleted"""
+    return next((document for document in database.get_documents() if document.source_path == DEMO_SOURCE_PATH and DEMO_TAG in document_tags(document)), None)
+
+
+def is_first_run(database: TextBookDatabase) -> bool:
+    """Whether the library is empty, with neither documents nor books"""
+    return not database.get_documents() and not database.get_all_books()
+
+
+def seed_demo(database: TextBookDatabase) -> Tuple[DocumentInfo, bool]:
+    """
+    Store the demo document with its pre-generated content
+
+    Returns:
+        Tuple[DocumentInfo, bool]: The demo document, and whether it was seeded now rather than before
+    """
+    existing = find_demo(database)
+    if existing is not None:
+        return existing, False
+    markdown = DEMO_MARKDOWN.read_text(encoding="utf-8")
+    fixtures = json.loads(DEMO_FIXTURES.read_text(encoding="utf-8"))
+    model_name = fixtures["model_name"]
+
+    document = database.create_document(DocumentInfo(
+        title=fixtures["title"],
+        author=fixtures["author"],
+        source_path=DEMO_SOURCE_PATH,
+        ocr_status=OCR_NOT_NEEDED,
+        detected_type=DOCUMENT_TYPE_TEXTBOOK,
+        tags=DEMO_TAG,
+    ))
+    document_id = document.document_id
+    store_chunks(database, document_id, chunk_content(markdown)[1])
+    segment_document(database, document_id, markdown)
+
+    chapters = {chapter.title: chapter.section_index for chapter in document_chapters(database, document_id)}
+    problem_ids = database.replace_problems(document_id, [
+        ProblemInfo(problem_number=problem["number"], statement=problem["statement"], section_index=chapters[problem["chapter"]])
+        for problem in fixtures["problems"]
+    ])
+    for problem_id, problem in zip(problem_ids, fixtures["problems"]):
+        solution = problem["solution"]
+        database.save_solution(SolutionInfo(
+            problem_id=problem_id,
+            steps=json.dumps(solution["steps"]),
+            final_answer=solution["final_answer"],
+            hints=json.dumps(solution["hints"]),
+            model_name=model_name,
+        ))
+        database.replace_hints(problem_id, [
+            HintInfo(level=level, tier=tier, text=text, model_name=model_name)
+            for level, (tier, text) in enumerate(zip(HINT_TIERS, problem["hints"]), start=1)
+        ])
+    database.replace_glossary(document_id, [GlossaryTermInfo(term=term["term"], definition=term["definition"]) for term in fixtures["glossary"]])
+    return database.update_document(document_id, summary=fixtures["summary"]), True
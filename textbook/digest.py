@@ -0,0 +1,150 @@
+# Document digests
+# Summaries and glossaries of whole uploaded documents, built from their OCR output with map_reduce: the pages are
+# sent a batch at a time, each batch is summarized or has its terms listed, and the partial results are reduced into
+# one, the summaries by another prompt and the glossaries by merging the terms. Documents without pages are sent a
+# chapter at a time. Building a digest again replaces it.
+
+from typing import Callable, Dict, List, Optional
+
+from pydantic import BaseModel
+
+from textbook.content import CHUNK_PAGE
+from textbook.database import GlossaryTermInfo, TextBookDatabase
+from textbook.jobs import CancellationToken
+from textbook.map_reduce import MapReduceResult, map_reduce
+from textbook.model import LLM
+from textbook.problems import document_chapters
+from textbook.prompt_templates import style_rules
+
+DIGEST_PAGES_PER_PROMPT = 8
+
+
+class ChunkSummarySchema(BaseModel):
+    summary: str
+
+
+class GlossaryTermSchema(BaseModel):
+    term: str
+    definition: str
+    page_number: int
+
+
+class GlossarySchema(BaseModel):
+    terms: List[GlossaryTermSchema]
+
+
+def chunk_summary_prompt(title: str, pages: str, style: Optional[str] = None) -> str:
+    return f"""
+    Summarize the following pages of "{title}" with rules:
+    - use bullet points for the main ideas, key definitions and results, in the order the pages present them
+    - keep the notation of the pages and use LaTeX for mathematics
+    - do not summarize exercises, only mention which topics they practice{style_rules(style)}
+
+    Pages:
+     {pages}
+    """
+
+
+def reduce_summary_prompt(title: str, summaries: str, style: Optional[str] = None) -> str:
+    return f"""
+    Combine the following summaries of consecutive parts of "{title}" into one summary of the whole document with rules:
+    - start with one paragraph on what the document covers, then bullet points per part in order
+    - merge points repeated across parts and keep the key definitions and results
+    - keep the notation of the summaries and use LaTeX for mathematics{style_rules(style)}
+
+    Summaries:
+     {summaries}
+    """
+
+
+def glossary_prompt(title: str, pages: str) -> str:
+    return f"""
+    List the technical terms defined or introduced in the following pages of "{title}" with rules:
+    - term is the term as the pages write it, in singular
+    - definition is a one or two sentence definition in the words of the pages, LaTeX for mathematics
+    - page_number is the number in the [Page n] marker of the page the term is introduced on, 0 if there are no markers
+    - only list terms the pages define or explain, not ones merely used
+
+    Pages:
+     {pages}
+    """
+
+
+def document_batches(database: TextBookDatabase, document_id: int, pages_per_prompt: int = DIGEST_PAGES_PER_PROMPT) -> List[str]:
+    """The text of a document a batch of pages at a time, marked with their page numbers, or a chapter at a time without pages"""
+    pages = database.get_content_chunks(document_id, CHUNK_PAGE)
+    if not pages:
+        return [chapter.text for chapter in document_chapters(database, document_id)]
+    return [
+        "\n\n".join(f"[Page {page.chunk_index}]\n{page.text}" for page in pages[start:start + pages_per_prompt])
+        for start in range(0, len(pages), pages_per_prompt)
+    ]
+
+
+def summarize_document(
+    database: TextBookDatabase,
+    llm: LLM,
+    document_id: int,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+    style: Optional[str] = None,
+) -> MapReduceResult[str]:
+    """
+    Summarize a document batch by batch and store the combined summary on it
+
+    A summary in an explanation style is an alternative to read on demand, it is returned without replacing the
+    stored summary.
+    """
+    document = database.get_document(document_id)
+    if document is None:
+        raise ValueError(f"Document not found: {document_id}")
+
+    def summarize(pages: str) -> str:
+        return llm.prompt_with_schema(chunk_summary_prompt(document.title, pages, style), schema=ChunkSummarySchema).summary.strip()
+
+    def combine(summaries: List[str]) -> str:
+        if len(summaries) == 1:
+            return summaries[0]
+        text = "\n\n".join(f"Part {number}:\n{summary}" for number, summary in enumerate(summaries, start=1))
+        return llm.prompt_with_schema(reduce_summary_prompt(document.title, text, style), schema=ChunkSummarySchema).summary.strip()
+
+    summary = map_reduce(document_batches(database, document_id), summarize, combine, cancellation_token=cancellation_token, report_progress=report_progress)
+    if style is None:
+        database.update_document(document_id, summary=summary.result)
+    return summary
+
+
+def merge_glossaries(glossaries: List[List[GlossaryTermSchema]]) -> List[GlossaryTermSchema]:
+    """The terms of partial glossaries in alphabetical order, a term listed twice keeps its first definition"""
+    terms: Dict[str, GlossaryTermSchema] = {}
+    for glossary in glossaries:
+        for term in glossary:
+            key = " ".join(term.term.lower().split())
+            if key and term.definition.strip() and key not in terms:
+                terms[key] = term
+    return [terms[key] for key in sorted(terms)]
+
+
+def build_glossary(
+    database: TextBookDatabase,
+    llm: LLM,
+    document_id: int,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+) -> MapReduceResult[List[int]]:
+    """List the terms of a document batch by batch and store them as its glossary"""
+    document = database.get_document(document_id)
+    if document is None:
+        raise ValueError(f"Document not found: {document_id}")
+    has_pages = bool(database.get_content_chunks(document_id, CHUNK_PAGE))
+
+    def list_terms(pages: str) -> List[GlossaryTermSchema]:
+        return llm.prompt_with_schema(glossary_prompt(document.title, pages), schema=GlossarySchema).terms
+
+    def store(glossaries: List[List[GlossaryTermSchema]]) -> List[int]:
+        return database.replace_glossary(document_id, [
+            GlossaryTermInfo(term=term.term.strip(), definition=term.definition.strip(), page_number=term.page_number if has_pages else None)
+            for term in merge_glossaries(glossaries)
+        ])
+
+    return map_reduce(document_batches(database, document_id), list_terms, store, cancellation_token=cancellation_token, report_progress=report_progress)
@@ -0,0 +1,167 @@
+# Symbolic answer equivalence
+# Answers that are a number or an expression, "2(x+1)" for the reference answer "2x+2", are checked without the LLM:
+# both are parsed with SymPy and compared, exactly by expanding their difference, then numerically at random points
+# for what expanding cannot settle (trigonometric identities, logarithms). Only equivalence is decided here; an
+# answer that differs, or is more than an expression, is left to the LLM, which also judges reasoning and partial
+# credit. An answer "x = 2" is compared by its last side. SymPy's parser evaluates Python, so the text is sandboxed:
+# it has to match a grammar of numbers, single letter variables, operators and a few known functions before it
+# reaches the parser, and powers too large to compute or expand are refused. SymPy is optional (uv sync --extra symbolic),
+# without it every answer goes to the LLM.
+
+import math
+import random
+import re
+import string
+from typing import List, Optional
+
+MAX_EXPRESSION_CHARS = 200
+MAX_NUMBER_DIGITS = 15
+MAX_EXPONENT = 100  # Integer powers beyond this are not computed
+MAX_EXPANDED_TERMS = 10000  # Answers that may expand to more terms are not expanded, ((x+y)^99)^99 among them
+SAMPLE_POINTS = 8
+MIN_SAMPLED_POINTS = 4  # Points where both answers are defined, fewer decide nothing
+RELATIVE_TOLERANCE = 1e-9
+
+FUNCTIONS = {
+    "sin": "sin", "cos": "cos", "tan": "tan", "cot": "cot", "sec": "sec", "csc": "csc",
+    "asin": "asin", "acos": "acos", "atan": "atan", "arcsin": "asin", "arccos": "acos", "arctan": "atan",
+    "sinh": "sinh", "cosh": "cosh", "tanh": "tanh",
+    "exp": "exp", "log": "log", "ln": "log", "sqrt": "sqrt", "abs": "Abs",
+}
+CONSTANTS = {"pi": "pi", "e": "E"}
+
+_TOKEN = re.compile(r"\s*(?:(?P<number>\d+(?:\.\d*)?|\.\d+)|(?P<name>[A-Za-z]+)|(?P<operator>\*\*|[-+*/^()]))")
+# Written forms of the operators, in LaTeX or typeset
+_REPLACEMENTS = (("\\cdot", "*"), ("\\times", "*"), ("×", "*"), ("·", "*"), ("−", "-"), ("\\pi", "pi"), ("π", "pi"), ("\\left", ""), ("\\right", ""))
+
+
+def _sympy():
+    try:
+        import sympy
+    except ImportError:
+        return None
+    return sympy
+
+
+def _normalize(text: str) -> str:
+    text = text.strip().strip("$").strip().rstrip(".")
+    for written, operator in _REPLACEMENTS:
+        text = text.replace(written, operator)
+    return text
+
+
+def is_expression(text: str) -> bool:
+    """Whether the text is only numbers, single letter variables, operators and known functions"""
+    if not text.strip() or len(text) > MAX_EXPRESSION_CHARS:
+        return False
+    position = 0
+    while position < len(text):
+        match = _TOKEN.match(text, position)
+        if match is None:
+            return not text[position:].strip()
+        if match.group("number") and len(match.group("number").replace(".", "")) > MAX_NUMBER_DIGITS:
+            return False
+        name = match.group("name")
+        if name and len(name) > 1 and name not in FUNCTIONS and name not in CONSTANTS:
+            return False
+        position = match.end()
+    return True
+
+
+def _expanded_terms(expression) -> int:
+    # An upper bound on the terms of the expanded expression, capped just above MAX_EXPANDED_TERMS: a sum of t terms to
+    # the power n expands to at most C(n + t - 1, t - 1) terms, so nested powers multiply their exponents
+    if expression.is_Add:
+        terms = sum(_expanded_terms(arg) for arg in expression.args)
+    elif expression.is_Mul:
+        terms = math.prod(_expanded_terms(arg) for arg in expression.args)
+    elif expression.is_Pow and expression.exp.is_number:
+        base_terms = _expanded_terms(expression.base)
+        terms = math.comb(int(abs(expression.exp.evalf(15))) + base_terms - 1, base_terms - 1)
+    else:
+        # Symbols, numbers, and functions, whose arguments are expanded on their own
+        terms = max([1] + [_expanded_terms(arg) for arg in expression.args])
+    return min(terms, MAX_EXPANDED_TERMS + 1)
+
+
+def _too_large(sympy, expression) -> bool:
+    # Exponents without variables are estimated in floating point, towers like 9^9^9 included
+    if any(not power.exp.free_symbols and abs(power.exp.evalf(15)) > MAX_EXPONENT for power in expression.atoms(sympy.Pow)):
+        return True
+    return _expanded_terms(expression) > MAX_EXPANDED_TERMS
+
+
+def parse_expression(text: str):
+    """
+    Parse an expression written by a learner, None when it is not one
+
+    Returns:
+        The SymPy expression, None when SymPy is not installed or the text is not an expression of the grammar
+    """
+    sympy = _sympy()
+    text = _normalize(text)
+    if sympy is None or not is_expression(text):
+        return None
+    from sympy.parsing.sympy_parser import convert_xor, implicit_multiplication_application, parse_expr, standard_transformations
+
+    # Letters are variables, even those SymPy names something (E, I, N, S)
+    names = {letter: sympy.Symbol(letter) for letter in string.ascii_letters}
+    names.update({name: getattr(sympy, function) for name, function in FUNCTIONS.items()})
+    names.update({name: getattr(sympy, constant) for name, constant in CONSTANTS.items()})
+    transformations = standard_transformations + (implicit_multiplication_application, convert_xor)
+    try:
+        # Unevaluated first, so powers too large to compute are refused before they are
+        if _too_large(sympy, parse_expr(text, local_dict=names, transformations=transformations, evaluate=False)):
+            return None
+        return parse_expr(text, local_dict=names, transformations=transformations)
+    except (SyntaxError, TypeError, ValueError, AttributeError, sympy.SympifyError):
+        return None
+
+
+def _answer_value(text: str) -> Optional[str]:
+    # "x = 2" is compared by its last side, every side has to be an expression
+    sides = text.split("=")
+    if not all(is_expression(_normalize(side)) for side in sides):
+        return None
+    return sides[-1]
+
+
+def _sampled_values(sympy, expressions: List, rng: random.Random) -> Optional[List[complex]]:
+    symbols = sorted(set().union(*(expression.free_symbols for expression in expressions)), key=str)
+    # Positive points, where logarithms and roots are defined
+    values = {symbol: sympy.Float(rng.uniform(0.5, 2.5)) for symbol in symbols}
+    try:
+        results = [complex(expression.evalf(subs=values)) for expression in expressions]
+    except (TypeError, ValueError, ZeroDivisionError):
+        return None
+    if any(value != value or abs(value) == float("inf") for value in results):
+        return None
+    return results
+
+
+def answers_equivalent(answer: str, reference: str, seed: int = 0) -> Optional[bool]:
+    """
+    Whether an answer is the reference answer in another form, e.g. "2(x+1)" and "2x+2"
+
+    Returns:
+        Optional[bool]: None when either is not an expression or SymPy is not installed, the LLM decides then
+    """
+    sympy = _sympy()
+    answer_value, reference_value = _answer_value(answer), _answer_value(reference)
+    if sympy is None or answer_value is None or reference_value is None:
+        return None
+    expressions = [parse_expression(answer_value), parse_expression(reference_value)]
+    if any(expression is None for expression in expressions):
+        return None
+    if sympy.expand(expressions[0] - expressions[1]) == 0:
+        return True
+    rng = random.Random(seed)
+    sampled = 0
+    for _ in range(SAMPLE_POINTS):
+        values = _sampled_values(sympy, expressions, rng)
+        if values is None:
+            continue
+        sampled += 1
+        if abs(values[0] - values[1]) > RELATIVE_TOLERANCE * max(1.0, abs(values[0]), abs(values[1])):
+            return False
+    return True if sampled >= MIN_SAMPLED_POINTS else None
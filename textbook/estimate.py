@@ -0,0 +1,192 @@
+# Generation cost estimates
+# Before starting the jobs of a document, POST /estimate tells what they would cost: the prompts each task would send
+# are built from the document's OCR output the way its job builds them (problem extraction a few pages of a chapter
+# at a time, summaries and glossaries a batch of pages at a time, solutions and hints a problem at a time with the
+# start of its section), and their tokens are estimated from the characters. The tokens of the responses are the
+# prompts' times the ratio of output to input tokens the task's jobs had over the last 90 days in usage_info, a
+# default per task until enough calls were recorded. The cost uses the [pricing] section of config.toml for the model
+# each task is routed to, tasks of a model without a price count their tokens only. Solutions and hints of a document
+# whose problems are not extracted yet are estimated for as many problems as its extraction is expected to list.
+# Summaries and glossaries always cover the whole document, the other tasks one section if asked.
+
+import math
+from dataclasses import dataclass, field
+from datetime import datetime, timedelta, timezone
+from typing import Callable, Dict, List, Optional
+
+from textbook.content import CHUNK_PAGE
+from textbook.database import ProblemInfo, TextBookDatabase
+from textbook.digest import chunk_summary_prompt, document_batches, glossary_prompt, reduce_summary_prompt
+from textbook.hints import hint_ladder_prompt
+from textbook.jobs import JOB_KIND_DOCUMENT_SUMMARY, JOB_KIND_GLOSSARY, JOB_KIND_HINT_LADDER, JOB_KIND_PROBLEM_EXTRACTION, JOB_KIND_SOLUTION
+from textbook.problems import document_chapters, extraction_batches, problem_extraction_prompt, problem_figures
+from textbook.rate_limit import estimate_tokens
+from textbook.solutions import SOLUTION_CONTEXT_CHARS, problem_context, solution_prompt
+from textbook.usage import ModelPrice
+
+ESTIMATED_TASKS = (JOB_KIND_PROBLEM_EXTRACTION, JOB_KIND_DOCUMENT_SUMMARY, JOB_KIND_GLOSSARY, JOB_KIND_SOLUTION, JOB_KIND_HINT_LADDER)
+# Output tokens per input token, until the task's jobs made enough calls to measure it
+DEFAULT_OUTPUT_RATIOS = {
+    JOB_KIND_PROBLEM_EXTRACTION: 0.4,
+    JOB_KIND_DOCUMENT_SUMMARY: 0.15,
+    JOB_KIND_GLOSSARY: 0.2,
+    JOB_KIND_SOLUTION: 0.5,
+    JOB_KIND_HINT_LADDER: 0.3,
+}
+CALIBRATION_DAYS = 90
+MIN_CALIBRATION_CALLS = 10
+TOKENS_PER_EXTRACTED_PROBLEM = 150  # Of extraction output, to expect a problem count before extracting
+
+
+@dataclass
+class TaskEstimate:
+    task: str
+    model_name: str
+    prompts: int = 0
+    input_tokens: int = 0
+    output_tokens: int = 0
+    cost: Optional[float] = None  # USD, None for a model without a price
+    calibrated: bool = False  # Whether the output ratio was measured on recorded calls
+    assumed_problems: Optional[int] = None  # Problems expected but not extracted yet, solved and hinted in the estimate
+
+
+@dataclass
+class DocumentEstimate:
+    document_id: int
+    section_index: Optional[int]
+    tasks: List[TaskEstimate] = field(default_factory=list)
+
+    @property
+    def prompts(self) -> int:
+        return sum(task.prompts for task in self.tasks)
+
+    @property
+    def input_tokens(self) -> int:
+        return sum(task.input_tokens for task in self.tasks)
+
+    @property
+    def output_tokens(self) -> int:
+        return sum(task.output_tokens for task in self.tasks)
+
+    @property
+    def cost(self) -> float:
+        """Of the tasks with a price"""
+        return sum(task.cost for task in self.tasks if task.cost is not None)
+
+    @property
+    def unpriced_tasks(self) -> List[str]:
+        return [task.task for task in self.tasks if task.cost is None]
+
+
+def output_ratios(database: TextBookDatabase, now: Optional[datetime] = None) -> Dict[str, float]:
+    """The output tokens per input token of the tasks with enough calls recorded over the last CALIBRATION_DAYS"""
+    since = (now or datetime.now(timezone.utc)) - timedelta(days=CALIBRATION_DAYS)
+    calls: Dict[str, List[int]] = {}  # Calls, input and output tokens by task
+    for usage in database.get_usage(since):
+        if usage.job_kind in ESTIMATED_TASKS and usage.input_tokens > 0:
+            totals = calls.setdefault(usage.job_kind, [0, 0, 0])
+            totals[0] += 1
+            totals[1] += usage.input_tokens
+            totals[2] += usage.output_tokens
+    return {task: output / input_tokens for task, (count, input_tokens, output) in calls.items() if count >= MIN_CALIBRATION_CALLS}
+
+
+class Estimator:
+    """Estimates the prompts, tokens and cost of the tasks of a document"""
+
+    def __init__(
+        self,
+        database: TextBookDatabase,
+        model_name_for: Callable[[str], str],
+        prices: Dict[str, ModelPrice],
+        ratios: Optional[Dict[str, float]] = None,
+    ):
+        self.database = database
+        self.model_name_for = model_name_for  # The model a task is routed to
+        self.prices = prices
+        self.ratios = output_ratios(database) if ratios is None else ratios
+
+    def _estimate(self, task: str, prompts: List[str], extra_input_tokens: int = 0, extra_prompts: int = 0) -> TaskEstimate:
+        estimate = TaskEstimate(task, self.model_name_for(task), calibrated=task in self.ratios)
+        ratio = self.ratios.get(task, DEFAULT_OUTPUT_RATIOS[task])
+        estimate.prompts = len(prompts) + extra_prompts
+        estimate.input_tokens = sum(estimate_tokens(prompt) for prompt in prompts) + extra_input_tokens
+        estimate.output_tokens = math.ceil(estimate.input_tokens * ratio)
+        price = self.prices.get(estimate.model_name)
+        if price is not None:
+            estimate.cost = price.cost(estimate.input_tokens, estimate.output_tokens)
+        return estimate
+
+    def extraction(self, document_id: int, section_index: Optional[int] = None) -> TaskEstimate:
+        pages = self.database.get_content_chunks(document_id, CHUNK_PAGE)
+        prompts = [
+            problem_extraction_prompt(chapter.title, text)
+            for chapter in document_chapters(self.database, document_id, section_index)
+            for _, text in extraction_batches(chapter, pages)
+        ]
+        return self._estimate(JOB_KIND_PROBLEM_EXTRACTION, prompts)
+
+    def summary(self, document_id: int) -> TaskEstimate:
+        title = self._title(document_id)
+        prompts = [chunk_summary_prompt(title, pages) for pages in document_batches(self.database, document_id)]
+        if len(prompts) <= 1:
+            return self._estimate(JOB_KIND_DOCUMENT_SUMMARY, prompts)
+        # The combining prompt reads the summaries of the batches
+        summaries = self._estimate(JOB_KIND_DOCUMENT_SUMMARY, prompts).output_tokens
+        combining = estimate_tokens(reduce_summary_prompt(title, "")) + summaries
+        return self._estimate(JOB_KIND_DOCUMENT_SUMMARY, prompts, extra_input_tokens=combining, extra_prompts=1)
+
+    def glossary(self, document_id: int) -> TaskEstimate:
+        title = self._title(document_id)
+        return self._estimate(JOB_KIND_GLOSSARY, [glossary_prompt(title, pages) for pages in document_batches(self.database, document_id)])
+
+    def per_problem(self, task: str, document_id: int, section_index: Optional[int] = None) -> TaskEstimate:
+        """Solutions or hint ladders of the problems extracted, or of the problems extraction is expected to list"""
+        problems = self.database.get_problems(document_id, section_index)
+        if problems:
+            return self._estimate(task, [self._problem_prompt(task, problem, problem_context(self.database, problem)) for problem in problems])
+        pages = self.database.get_content_chunks(document_id, CHUNK_PAGE)
+        prompts = []
+        for chapter in document_chapters(self.database, document_id, section_index):
+            extraction = self._estimate(JOB_KIND_PROBLEM_EXTRACTION, [problem_extraction_prompt(chapter.title, text) for _, text in extraction_batches(chapter, pages)])
+            expected = ProblemInfo(statement="x" * TOKENS_PER_EXTRACTED_PROBLEM * 4)
+            prompts.extend([self._problem_prompt(task, expected, chapter.text[:SOLUTION_CONTEXT_CHARS])] * (extraction.output_tokens // TOKENS_PER_EXTRACTED_PROBLEM))
+        estimate = self._estimate(task, prompts)
+        estimate.assumed_problems = len(prompts)
+        return estimate
+
+    def document(self, document_id: int, tasks: Optional[List[str]] = None, section_index: Optional[int] = None) -> DocumentEstimate:
+        """
+        The estimates of tasks on a document, by default of every task generating its content
+
+        Raises:
+            ValueError: If a task is not estimated, the document does not exist or has no OCR output
+        """
+        tasks = list(tasks or ESTIMATED_TASKS)
+        unknown = [task for task in tasks if task not in ESTIMATED_TASKS]
+        if unknown:
+            raise ValueError(f"Cannot estimate {', '.join(unknown)}, expected some of {', '.join(ESTIMATED_TASKS)}")
+        self._title(document_id)
+        estimate = DocumentEstimate(document_id, section_index)
+        for task in dict.fromkeys(tasks):
+            if task == JOB_KIND_PROBLEM_EXTRACTION:
+                estimate.tasks.append(self.extraction(document_id, section_index))
+            elif task == JOB_KIND_DOCUMENT_SUMMARY:
+                estimate.tasks.append(self.summary(document_id))
+            elif task == JOB_KIND_GLOSSARY:
+                estimate.tasks.append(self.glossary(document_id))
+            else:
+                estimate.tasks.append(self.per_problem(task, document_id, section_index))
+        return estimate
+
+    def _title(self, document_id: int) -> str:
+        document = self.database.get_document(document_id)
+        if document is None:
+            raise ValueError(f"Document not found: {document_id}")
+        return document.title
+
+    @staticmethod
+    def _problem_prompt(task: str, problem: ProblemInfo, context: str) -> str:
+        if task == JOB_KIND_SOLUTION:
+            return solution_prompt(problem, context)
+        return hint_ladder_prompt(problem.statement, problem_figures(problem), context)
@@ -0,0 +1,164 @@
+# Passage explanations
+# While reading, a learner selects a passage and asks for it to be explained. The explanation is grounded in the
+# document rather than the model's memory: the prompt carries the text around the selection and the passages of the
+# document sharing the most rare words with it, retrieved from its pages (or its sections, for documents without
+# pages) by TF-IDF. With a tutoring session, the last messages of the session go into the prompt too, so follow-up
+# questions build on what was explained before, and the exchange is added to the session once it is complete.
+
+import math
+import re
+from collections import Counter
+from dataclasses import dataclass
+from typing import List, Optional, Sequence
+
+from textbook.content import CHUNK_PAGE, CHUNK_SECTION
+from textbook.database import ContentChunkInfo, TextBookDatabase, TutoringMessageInfo
+from textbook.prompt_templates import style_rules
+
+CONTEXT_CHARS = 1500  # Of the text before and after the selection sent with it
+MAX_SELECTION_CHARS = 4000
+RETRIEVED_PASSAGES = 3
+PASSAGE_CHARS = 1200  # Of each retrieved passage in the prompt
+HISTORY_MESSAGES = 6  # Last messages of the tutoring session in the prompt
+MIN_WORD_LENGTH = 3
+WORD = re.compile(r"[^\W\d_]+")
+STOP_WORDS = frozenset(
+    "the and for are but not you all any can had her was one our out has him his how its may new now see two who "
+    "did get let put say she too use that this with from have they will what when your which their there been were "
+    "into than then them these some such only also more most other each where while would could should about".split()
+)
+
+ROLE_LEARNER = "learner"
+ROLE_TUTOR = "tutor"
+
+
+@dataclass
+class Selection:
+    text: str
+    before: str  # Up to CONTEXT_CHARS before the selection
+    after: str
+    context_start: int  # Where the text before starts in the document's markdown
+    context_end: int  # Where the text after ends, exclusive
+
+
+@dataclass
+class Passage:
+    chunk_kind: str  # CHUNK_PAGE or CHUNK_SECTION
+    chunk_index: int
+    title: Optional[str]
+    page_start: Optional[int]
+    text: str
+    score: float
+
+
+def select_range(markdown: str, char_start: int, char_end: int, context_chars: int = CONTEXT_CHARS) -> Selection:
+    """
+    The selected text of a document's markdown with the text around it
+
+    Raises:
+        ValueError: If the range is outside the markdown, empty or longer than MAX_SELECTION_CHARS
+    """
+    if char_start < 0 or char_end > len(markdown) or char_end <= char_start:
+        raise ValueError(f"Invalid range {char_start}-{char_end}, the document's markdown has {len(markdown)} characters")
+    if char_end - char_start > MAX_SELECTION_CHARS:
+        raise ValueError(f"The selection is longer than {MAX_SELECTION_CHARS} characters, select a shorter passage")
+    text = markdown[char_start:char_end]
+    if not text.strip():
+        raise ValueError("The selection is blank")
+    context_start = max(0, char_start - context_chars)
+    context_end = min(len(markdown), char_end + context_chars)
+    return Selection(text, markdown[context_start:char_start], markdown[char_end:context_end], context_start, context_end)
+
+
+def words(text: str) -> List[str]:
+    """The lowercased words of a text that carry meaning, for retrieval"""
+    return [word for word in (match.lower() for match in WORD.findall(text)) if len(word) >= MIN_WORD_LENGTH and word not in STOP_WORDS]
+
+
+def retrieve_passages(chunks: Sequence[ContentChunkInfo], query: str, exclude_start: int, exclude_end: int, limit: int = RETRIEVED_PASSAGES) -> List[Passage]:
+    """The chunks most related to the query by TF-IDF, best first, leaving out those overlapping the excluded range"""
+    counts = [Counter(words(chunk.text)) for chunk in chunks]
+    frequencies = Counter(word for chunk_counts in counts for word in chunk_counts)
+    terms = set(words(query))
+    passages = []
+    for chunk, chunk_counts in zip(chunks, counts):
+        length = sum(chunk_counts.values())
+        if length == 0 or (chunk.char_start < exclude_end and exclude_start < chunk.char_end):
+            continue
+        # Sublinear term frequency and a length normalization, so long chapters do not win by size alone
+        score = sum(
+            (1 + math.log(chunk_counts[term])) * math.log(1 + len(chunks) / frequencies[term])
+            for term in terms if chunk_counts[term]
+        ) / math.sqrt(length)
+        if score > 0:
+            passages.append(Passage(chunk.chunk_kind, chunk.chunk_index, chunk.title, chunk.page_start, chunk.text, score))
+    passages.sort(key=lambda passage: -passage.score)
+    return passages[:limit]
+
+
+def document_passages(database: TextBookDatabase, document_id: int) -> List[ContentChunkInfo]:
+    """The chunks passages are retrieved from: the pages of a document, or its sections without pages"""
+    return database.get_content_chunks(document_id, CHUNK_PAGE) or database.get_content_chunks(document_id, CHUNK_SECTION)
+
+
+def _passage_label(passage: Passage) -> str:
+    if passage.chunk_kind == CHUNK_PAGE:
+        return f"Page {passage.chunk_index}"
+    return f"Section {passage.title}" if passage.title else f"Section {passage.chunk_index}"
+
+
+def explanation_prompt(
+    title: str,
+    selection: Selection,
+    passages: List[Passage],
+    history: List[TutoringMessageInfo],
+    question: Optional[str] = None,
+    style: Optional[str] = None,
+) -> str:
+    """The prompt explaining a passage, in the register of an explanation style of the prompt templates if given"""
+    related = "\n\n".join(f"[{_passage_label(passage)}]\n{passage.text[:PASSAGE_CHARS]}" for passage in passages) or "None"
+    conversation = "\n\n".join(f"{message.role.capitalize()}: {message.content}" for message in history) or "None"
+    return f"""
+    A learner reading "{title}" selected the passage below and asks: {question or "What does this passage mean?"}
+    Answer with rules:
+    - ground the explanation in the document: use its notation and definitions and name the related passages you draw on
+    - explain step by step, spelling out the notation, claims and steps the passage leaves implicit
+    - say so when the document does not settle the question instead of guessing
+    - build on the earlier conversation without repeating it
+    - use markdown, and LaTeX for mathematics{style_rules(style)}
+
+    Text before the passage:
+     {selection.before}
+
+    Passage:
+     {selection.text}
+
+    Text after the passage:
+     {selection.after}
+
+    Related passages of the document:
+     {related}
+
+    Earlier conversation:
+     {conversation}
+    """
+
+
+def save_exchange(
+    database: TextBookDatabase,
+    session_id: str,
+    document_id: int,
+    char_start: int,
+    char_end: int,
+    selection: str,
+    question: Optional[str],
+    explanation: str,
+) -> List[int]:
+    """Add a question about a passage and its explanation to a tutoring session, returns the IDs of the two messages"""
+    asked = "\n".join(f"> {line}" for line in selection.splitlines())
+    if question:
+        asked = f"{asked}\n\n{question}"
+    return database.create_tutoring_messages([
+        TutoringMessageInfo(session_id=session_id, role=ROLE_LEARNER, content=asked, char_start=char_start, char_end=char_end, document_id=document_id),
+        TutoringMessageInfo(session_id=session_id, role=ROLE_TUTOR, content=explanation, char_start=char_start, char_end=char_end, document_id=document_id),
+    ])
@@ -0,0 +1,194 @@
+# External grading services
+# Some answers are judged better by a program than by a model: a computer algebra system deciding whether two
+# expressions are equal, a test runner for code. A grading service is set per exercise type ("text" or "code") in
+# the [grading_services] section of config.toml. An attempt at an exercise of that type is graded by the LLM as
+# usual, then posted to the service, and the service's verdict is merged with the model's grade into a judgment of
+# its own ("external"), the one that counts. A service that is down, slow or answers nonsense never fails the
+# attempt: the model's grade stands alone. Calls go through a circuit breaker per service, so a service that is down
+# is skipped right away instead of being waited for on every attempt.
+#   [grading_services.code]
+#   url = "https://grader.example.com/grade"
+#   secret_env = "CODE_GRADER_SECRET"  # the variable holding the signing key
+#   weight = 0.5                       # share of the service's verdict in the merged grade
+#   timeout_seconds = 10
+# The service gets a JSON body {"exercise_id", "exercise_type", "problem", "reference_solution", "answer",
+# "model_grade": {"score", "is_correct"}}, signed with the hex HMAC-SHA256 of "<timestamp>.<body>" in the
+# X-Grading-Signature header, the Unix timestamp in X-Grading-Timestamp so old requests can be rejected. It answers
+# {"score": 0 to 1, "is_correct": bool, "feedback": optional, "confidence": optional 0 to 1}.
+
+import hashlib
+import hmac
+import json
+import os
+import time
+from dataclasses import dataclass
+from typing import Callable, Dict, Mapping, Optional
+
+import requests
+from pydantic import BaseModel
+
+from textbook.circuit_breaker import CircuitBreakers, ServiceUnavailable, circuit_breakers
+from textbook.credentials import SecretString
+from textbook.database import ExerciseInfo, GradingJudgmentInfo
+from textbook.grading import JUDGMENT_EXTERNAL
+
+EXERCISE_TYPE_TEXT = "text"  # Exercises without a type
+DEFAULT_WEIGHT = 0.5
+DEFAULT_TIMEOUT_SECONDS = 10.0
+SIGNATURE_HEADER = "X-Grading-Signature"
+TIMESTAMP_HEADER = "X-Grading-Timestamp"
+
+
+@dataclass
+class GradingService:
+    exercise_type: str
+    url: str
+    secret: SecretString
+    weight: float = DEFAULT_WEIGHT  # Of the service's verdict, the model's grade weighs the rest
+    timeout_seconds: float = DEFAULT_TIMEOUT_SECONDS
+
+    @property
+    def name(self) -> str:
+        """The name of its circuit breaker, and the model name of its judgments"""
+        return f"grading:{self.exercise_type}"
+
+
+class ExternalVerdict(BaseModel):
+    score: float
+    is_correct: bool
+    feedback: Optional[str] = None
+    confidence: Optional[float] = None
+
+
+def grading_services_from_config(config: dict, environ: Optional[Mapping[str, str]] = None) -> Dict[str, GradingService]:
+    """The [grading_services] section of config.toml by exercise type, raises ValueError for invalid settings"""
+    environ = os.environ if environ is None else environ
+    services = {}
+    for exercise_type, section in config.get("grading_services", {}).items():
+        if not isinstance(section, dict):
+            raise ValueError(f"grading_services.{exercise_type} must be a table with the url of the service")
+        unknown = set(section) - {"url", "secret_env", "weight", "timeout_seconds"}
+        if unknown:
+            raise ValueError(f"Unknown settings of grading_services.{exercise_type}: {', '.join(sorted(unknown))}")
+        url = section.get("url")
+        if not isinstance(url, str) or not url.startswith(("http://", "https://")):
+            raise ValueError(f"grading_services.{exercise_type}.url must be the http(s) URL of the service")
+        secret_env = section.get("secret_env")
+        if not isinstance(secret_env, str) or not secret_env:
+            raise ValueError(f"grading_services.{exercise_type}.secret_env must name the variable holding the signing key")
+        if not environ.get(secret_env):
+            raise ValueError(f"Set {secret_env} to the signing key of the {exercise_type} grading service")
+        weight = section.get("weight", DEFAULT_WEIGHT)
+        if isinstance(weight, bool) or not isinstance(weight, (int, float)) or not 0 < weight <= 1:
+            raise ValueError(f"grading_services.{exercise_type}.weight must be a number above 0 and at most 1")
+        timeout_seconds = section.get("timeout_seconds", DEFAULT_TIMEOUT_SECONDS)
+        if isinstance(timeout_seconds, bool) or not isinstance(timeout_seconds, (int, float)) or timeout_seconds <= 0:
+            raise ValueError(f"grading_services.{exercise_type}.timeout_seconds must be a positive number")
+        services[exercise_type] = GradingService(exercise_type, url, SecretString(environ[secret_env]), weight, timeout_seconds)
+    return services
+
+
+def sign(secret: SecretString, timestamp: str, body: bytes) -> str:
+    """The signature of a request body, services compute it the same way to verify it"""
+    return hmac.new(secret.reveal().encode(), timestamp.encode() + b"." + body, hashlib.sha256).hexdigest()
+
+
+def post_signed(url: str, body: bytes, headers: Dict[str, str], timeout: float) -> requests.Response:
+    return requests.post(url, data=body, headers=headers, timeout=timeout)
+
+
+def _clamp(value: float) -> float:
+    return min(max(value, 0.0), 1.0)
+
+
+def merge_verdict(grade: GradingJudgmentInfo, verdict: ExternalVerdict, service: GradingService) -> GradingJudgmentInfo:
+    """
+    The model's grade and the service's verdict in one judgment
+
+    The score and confidence are averaged by the weight of the service, the correctness is the service's when it
+    weighs at least half, and the service's feedback follows the model's.
+    """
+    weight = service.weight
+    confidence = verdict.confidence if verdict.confidence is not None else 1.0
+    feedback = "\n\n".join(part for part in (grade.feedback, (verdict.feedback or "").strip()) if part)
+    return GradingJudgmentInfo(
+        model_name=service.name,
+        reason=JUDGMENT_EXTERNAL,
+        score=(1 - weight) * (grade.score or 0.0) + weight * _clamp(verdict.score),
+        is_correct=verdict.is_correct if weight >= 0.5 else grade.is_correct,
+        confidence=(1 - weight) * (grade.confidence or 0.0) + weight * _clamp(confidence),
+        feedback=feedback,
+    )
+
+
+class GradingServices:
+    """The grading services of config.toml, asked for a verdict on the attempts at exercises of their type"""
+
+    def __init__(
+        self,
+        services: Dict[str, GradingService],
+        breakers: CircuitBreakers = circuit_breakers,
+        send: Callable[[str, bytes, Dict[str, str], float], requests.Response] = post_signed,
+        clock: Callable[[], float] = time.time,
+    ):
+        self.services = services
+        self._breakers = breakers
+        self._send = send
+        self._clock = clock
+
+    def service_for(self, exercise: ExerciseInfo) -> Optional[GradingService]:
+        return self.services.get(exercise.exercise_type or EXERCISE_TYPE_TEXT)
+
+    def request_body(self, exercise: ExerciseInfo, answer: str, grade: GradingJudgmentInfo) -> bytes:
+        reference_solution = exercise.solution_code or (exercise.details.study_guide if exercise.details else None)
+        return json.dumps({
+            "exercise_id": exercise.exercise_id,
+            "exercise_type": exercise.exercise_type or EXERCISE_TYPE_TEXT,
+            "problem": exercise.exercise_description,
+            "reference_solution": reference_solution,
+            "answer": answer,
+            "model_grade": {"score": grade.score, "is_correct": grade.is_correct},
+        }).encode()
+
+    def grade(self, exercise: ExerciseInfo, answer: str, grade: GradingJudgmentInfo) -> Optional[GradingJudgmentInfo]:
+        """
+        Merge the verdict of the exercise type's service with the model's grade
+
+        Returns:
+            Optional[GradingJudgmentInfo]: The merged judgment, None when no service grades the type or it failed
+        """
+        service = self.service_for(exercise)
+        if service is None:
+            return None
+        body = self.request_body(exercise, answer, grade)
+        timestamp = str(int(self._clock()))
+        headers = {
+            "Content-Type": "application/json",
+            TIMESTAMP_HEADER: timestamp,
+            SIGNATURE_HEADER: sign(service.secret, timestamp, body),
+        }
+        breaker = self._breakers.get(service.name)
+        try:
+            breaker.check()
+            response = self._send(service.url, body, headers, service.timeout_seconds)
+            response.raise_for_status()
+            verdict = ExternalVerdict.model_validate(response.json())
+            breaker.record_success()
+        except ServiceUnavailable as e:
+            print(f"Skipping the {exercise.exercise_type or EXERCISE_TYPE_TEXT} grading service: {e.detail}")
+            return None
+        except ValueError as e:
+            # Not JSON, or not a verdict: the service answered
+            breaker.record_success()
+            print(f"Invalid verdict of the grading service at {service.url}, keeping the model's grade: {e}")
+            return None
+        except requests.exceptions.RequestException as e:
+            # Rejecting a request (4xx) is up, no answer or a server error counts against its breaker
+            status = e.response.status_code if e.response is not None else None
+            if status is None or status >= 500:
+                breaker.record_failure()
+            else:
+                breaker.record_success()
+            print(f"Error asking the grading service at {service.url}, keeping the model's grade: {e}")
+            return None
+        return merge_verdict(grade, verdict, service)
@@ -0,0 +1,143 @@
+# Review load forecast
+# Projects how many reviews and new cards a user will face each day under their current settings. Every card due
+# on a day is reviewed that day, passing with the expected retention and otherwise coming back the next day, and
+# new cards are introduced up to the daily limit until none are left. The simulation is seeded, so the same
+# settings give the same forecast.
+# The projected retention of a day is the mean chance of recalling a learned card at its end, assuming exponential
+# forgetting that drops to the pass rate over a card's interval. To try out settings before changing them, the
+# what-if simulation runs the current and the proposed preferences side by side with the pass rate the user actually
+# had on cards they had seen before.
+
+import random
+from dataclasses import dataclass, replace
+from datetime import date, datetime, timedelta, timezone
+from typing import List, Optional
+
+from textbook.database import TextBookDatabase
+from textbook.preferences import Preferences, validate_preferences
+from textbook.review import GRADE_AGAIN, GRADE_GOOD, RELEARN_INTERVAL_DAYS, CardSchedule, balance_schedule, card_schedule, due_per_day, schedule_review
+from textbook.timestamps import as_utc
+
+DEFAULT_RETENTION = 0.9  # Share of reviews expected to pass
+MAX_FORECAST_DAYS = 365
+SIMULATION_DAYS = 90
+MIN_REVIEWS_FOR_RETENTION = 20  # Fewer reviews than this fall back to DEFAULT_RETENTION
+
+
+@dataclass
+class DayLoad:
+    day: date
+    reviews: int
+    new_cards: int
+    projected_retention: Optional[float] = None  # None before any card was learned
+
+
+@dataclass
+class WhatIf:
+    retention: float  # The pass rate both simulations use
+    review_count: int  # Reviews the pass rate was measured on
+    proposed_preferences: Preferences
+    current: List[DayLoad]
+    proposed: List[DayLoad]
+
+
+def simulate_load(
+    schedules: List[CardSchedule],
+    new_cards: int,
+    days: int,
+    daily_new_limit: int,
+    load_balancing: bool = False,
+    retention: float = DEFAULT_RETENTION,
+    start: Optional[datetime] = None,
+    seed: int = 0,
+) -> List[DayLoad]:
+    """The reviews and new cards of each of the next days, starting with the day of start (UTC)"""
+    if not 0 <= retention <= 1:
+        raise ValueError("retention must be between 0 and 1")
+    if not 1 <= days <= MAX_FORECAST_DAYS:
+        raise ValueError(f"days must be between 1 and {MAX_FORECAST_DAYS}")
+    start = as_utc(start or datetime.now(timezone.utc))
+    rng = random.Random(seed)
+    pending = list(schedules)
+    load = due_per_day([schedule.due_at for schedule in pending]) if load_balancing else {}
+    loads = []
+    for offset in range(days):
+        day = start.date() + timedelta(days=offset)
+        day_start = datetime(day.year, day.month, day.day, tzinfo=timezone.utc)
+        moment = max(start, day_start)
+        end_of_day = day_start + timedelta(days=1)
+
+        due = [schedule for schedule in pending if as_utc(schedule.due_at) < end_of_day]
+        pending = [schedule for schedule in pending if as_utc(schedule.due_at) >= end_of_day]
+        introduced = min(daily_new_limit, new_cards)
+        new_cards -= introduced
+        for schedule in due + [None] * introduced:
+            grade = GRADE_GOOD if rng.random() < retention else GRADE_AGAIN
+            next_schedule = schedule_review(schedule, grade, moment)
+            if grade == GRADE_AGAIN:
+                # Failed cards are relearned the next day, the simulation reviews each card at most once a day
+                next_schedule.due_at = end_of_day
+            if load_balancing:
+                next_schedule = balance_schedule(next_schedule, load)
+                load[next_schedule.due_at.date()] = load.get(next_schedule.due_at.date(), 0) + 1
+            pending.append(next_schedule)
+        loads.append(DayLoad(day, len(due), introduced, _mean_recall(pending, retention, end_of_day)))
+    return loads
+
+
+def _mean_recall(schedules: List[CardSchedule], retention: float, moment: datetime) -> Optional[float]:
+    if not schedules:
+        return None
+    recalls = []
+    for schedule in schedules:
+        interval = max(schedule.interval_days, RELEARN_INTERVAL_DAYS)
+        elapsed = (moment - as_utc(schedule.due_at)).total_seconds() / 86400 + interval
+        recalls.append(retention ** (max(elapsed, 0) / interval))
+    return sum(recalls) / len(recalls)
+
+
+def observed_retention(grades: List[int]) -> Optional[float]:
+    """The share of reviews that passed, None if there are too few to tell"""
+    if len(grades) < MIN_REVIEWS_FOR_RETENTION:
+        return None
+    return sum(1 for grade in grades if grade != GRADE_AGAIN) / len(grades)
+
+
+def forecast_load(
+    database: TextBookDatabase,
+    preferences: Preferences,
+    days: int,
+    retention: float = DEFAULT_RETENTION,
+    book_id: Optional[int] = None,
+    now: Optional[datetime] = None,
+) -> List[DayLoad]:
+    """The projected daily load of a user's cards under their preferences, archived and suspended cards aside"""
+    schedules = [card_schedule(state) for state in database.get_reviewable_card_states(preferences.user_id, book_id)]
+    new_cards = database.count_new_flashcards(preferences.user_id, book_id)
+    return simulate_load(schedules, new_cards, days, preferences.daily_new_card_limit, preferences.load_balancing, retention, now)
+
+
+def simulate_settings(
+    database: TextBookDatabase,
+    current: Preferences,
+    days: int = SIMULATION_DAYS,
+    book_id: Optional[int] = None,
+    now: Optional[datetime] = None,
+    **changes,
+) -> WhatIf:
+    """Simulate the load and retention under the current preferences and with some of them changed, from the user's review history"""
+    proposed = replace(current, **changes)
+    validate_preferences(proposed)
+    grades = database.get_review_grades(current.user_id, book_id)
+    retention = observed_retention(grades)
+    retention = DEFAULT_RETENTION if retention is None else retention
+    schedules = [card_schedule(state) for state in database.get_reviewable_card_states(current.user_id, book_id)]
+    new_cards = database.count_new_flashcards(current.user_id, book_id)
+    now = now or datetime.now(timezone.utc)
+    return WhatIf(
+        retention,
+        len(grades),
+        proposed,
+        simulate_load(schedules, new_cards, days, current.daily_new_card_limit, current.load_balancing, retention, now),
+        simulate_load(schedules, new_cards, days, proposed.daily_new_card_limit, proposed.load_balancing, retention, now),
+    )
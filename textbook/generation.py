@@ -0,0 +1,108 @@
+# Generation parameters
+# The sampling temperature, the maximum length of answers and the sampling seed, passed with every prompt. Without
+# settings the provider's defaults apply. The [generation] section of config.toml sets them for every task, and a table per task
+# overrides them, e.g. no randomness where answers are checked and more where hints are brainstormed:
+#   [generation]
+#   temperature = 0.7
+#   max_tokens = 8192
+#   [generation.answer_check]
+#   temperature = 0
+#   [generation.hint_ladder]
+#   temperature = 1.0
+#   [generation.problem_extraction]
+#   temperature = 0
+#   seed = 42
+# A seed with temperature 0 makes generation reproducible as far as the provider allows: the model, temperature and
+# seed are recorded with what is generated, like extracted problems, so a problem set can be generated again from the
+# same content and prompt version. Providers without seeds ignore it, their answers at temperature 0 vary little.
+# The tasks are those of the [routing] section (see profiles). The parameters are passed as options of the model,
+# by the name its plugin knows them by; plugins without them ignore them with a warning, like the safety settings.
+
+from dataclasses import dataclass, field, fields
+from typing import Any, Dict, Iterable, Optional
+
+MAX_TEMPERATURE = 2.0
+MAX_TOKEN_OPTIONS = ("max_tokens", "max_output_tokens")  # The name of the option in the plugins, by preference
+PARAMS = ("temperature", "max_tokens", "seed")  # The settings of [generation] and its tables per task
+
+
+@dataclass(frozen=True)
+class GenerationParams:
+    temperature: Optional[float] = None  # None for the provider's default
+    max_tokens: Optional[int] = None
+    seed: Optional[int] = None
+
+    def merged(self, overrides: "GenerationParams") -> "GenerationParams":
+        """These parameters with the ones set in overrides replacing them"""
+        return GenerationParams(**{
+            param.name: getattr(overrides, param.name) if getattr(overrides, param.name) is not None else getattr(self, param.name)
+            for param in fields(self)
+        })
+
+    def prompt_options(self, supported: Iterable[str]) -> Dict[str, Any]:
+        """The prompt options of the parameters set, max_tokens under the name the model supports"""
+        supported = set(supported)
+        options: Dict[str, Any] = {}
+        if self.temperature is not None:
+            options["temperature"] = self.temperature
+        if self.max_tokens is not None:
+            name = next((name for name in MAX_TOKEN_OPTIONS if name in supported), MAX_TOKEN_OPTIONS[0])
+            options[name] = self.max_tokens
+        if self.seed is not None:
+            options["seed"] = self.seed
+        return options
+
+
+@dataclass(frozen=True)
+class GenerationRecord:
+    """What an artifact was generated with, recorded with it to generate it again identically"""
+    model_name: str
+    temperature: Optional[float] = None  # None for the provider's default
+    seed: Optional[int] = None
+
+
+def deterministic(params: GenerationParams, seed: int) -> GenerationParams:
+    """The parameters with no randomness but the seed's, for reproducible generation"""
+    return params.merged(GenerationParams(temperature=0.0, seed=seed))
+
+
+@dataclass
+class GenerationSettings:
+    default: GenerationParams = GenerationParams()
+    tasks: Dict[str, GenerationParams] = field(default_factory=dict)  # Overrides by task
+
+    def for_task(self, task: Optional[str]) -> GenerationParams:
+        overrides = self.tasks.get(task) if task is not None else None
+        return self.default.merged(overrides) if overrides is not None else self.default
+
+
+def _params_from_section(section: dict, name: str) -> GenerationParams:
+    temperature = section.get("temperature")
+    if temperature is not None and (isinstance(temperature, bool) or not isinstance(temperature, (int, float)) or not 0 <= temperature <= MAX_TEMPERATURE):
+        raise ValueError(f"{name}.temperature must be a number between 0 and {MAX_TEMPERATURE:g}")
+    max_tokens = section.get("max_tokens")
+    if max_tokens is not None and (isinstance(max_tokens, bool) or not isinstance(max_tokens, int) or max_tokens < 1):
+        raise ValueError(f"{name}.max_tokens must be a positive integer")
+    seed = section.get("seed")
+    if seed is not None and (isinstance(seed, bool) or not isinstance(seed, int) or seed < 0):
+        raise ValueError(f"{name}.seed must be a non-negative integer")
+    return GenerationParams(float(temperature) if temperature is not None else None, max_tokens, seed)
+
+
+def generation_settings_from_config(config: dict, tasks: Iterable[str]) -> GenerationSettings:
+    """The [generation] section of config.toml, raises ValueError for invalid settings or unknown tasks"""
+    section = config.get("generation", {})
+    tasks = tuple(tasks)
+    overrides = {}
+    for key, value in section.items():
+        if key in PARAMS:
+            continue
+        if key not in tasks:
+            raise ValueError(f"Unknown generation setting or task: {key}, expected {', '.join(PARAMS)} or one of {', '.join(tasks)}")
+        if not isinstance(value, dict):
+            raise ValueError(f"generation.{key} must be a table with {', '.join(PARAMS)}")
+        unknown = set(value) - set(PARAMS)
+        if unknown:
+            raise ValueError(f"Unknown settings of generation.{key}: {', '.join(sorted(unknown))}")
+        overrides[key] = _params_from_section(value, f"generation.{key}")
+    return GenerationSettings(_params_from_section(section, "generation"), overrides)
@@ -0,0 +1,173 @@
+# Context assembly for grading exercise attempts
+# A grading prompt is built from parts of varying importance: the problem itself, its reference solution, an
+# excerpt of the section it comes from, the definitions and theorems it relies on, and the mistakes the learner
+# made recently. Parts are ranked by priority and fitted into a token budget, so long chapters or long
+# histories cut the least useful context first instead of overflowing the prompt.
+# Learners can dispute grades; disputes are regraded by the escalation model and queued for a manual review,
+# whose outcome measures how accurate the grading is over time.
+
+from dataclasses import dataclass
+from datetime import date, timedelta
+from typing import Dict, Iterable, List, Optional
+
+CHARS_PER_TOKEN = 4  # Rough estimate for English and LaTeX, good enough to budget a prompt
+DEFAULT_CONTEXT_TOKEN_BUDGET = 3000
+MIN_TRUNCATED_TOKENS = 60  # Parts that would be cut below this are dropped instead
+MAX_RECENT_MISCONCEPTIONS = 10
+ESCALATION_CONFIDENCE_THRESHOLD = 0.7  # Gradings the grader is less confident about are redone by the escalation model
+
+# Why a judgment was made, stored on grading_judgment_info.reason
+JUDGMENT_INITIAL = "initial"
+JUDGMENT_LOW_CONFIDENCE = "low_confidence"
+JUDGMENT_DISPUTE = "dispute"
+JUDGMENT_MANUAL = "manual"
+JUDGMENT_EXTERNAL = "external"  # The model's grade merged with the verdict of a grading service (see external_grading)
+
+# Status of a dispute, stored on dispute_info.status
+DISPUTE_PENDING_REVIEW = "pending_review"
+DISPUTE_RESOLVED = "resolved"
+
+# Study modes of an attempt: in self-explanation mode the learner explains their solution after grading,
+# and the explanation is evaluated against the solution steps and kept in the reflection journal
+STUDY_MODE_PRACTICE = "practice"
+STUDY_MODE_SELF_EXPLANATION = "self_explanation"
+STUDY_MODES = (STUDY_MODE_PRACTICE, STUDY_MODE_SELF_EXPLANATION)
+SELF_EXPLANATION_QUESTION = "Explain in a few sentences how you solved this problem: the steps you took, and why each step is justified."
+
+ATTEMPT_VOID_WINDOW_HOURS = 24  # Attempts can be voided, e.g. after submitting by mistake, for this long
+
+SCORE_AGREEMENT_TOLERANCE = 0.2  # Two grades agree when their correctness matches and their scores are this close
+METRICS_EPOCH = date(1970, 1, 5)  # A Monday, so weekly periods start on Mondays
+
+# Priorities of the context parts, lower is kept first
+PRIORITY_PROBLEM = 0  # Always kept, even beyond the budget
+PRIORITY_SOLUTION = 1
+PRIORITY_SOURCE_EXCERPT = 2
+PRIORITY_REFERENCED_ENTITY = 3  # Entities the problem refers to, e.g. "use Theorem 3.2"
+PRIORITY_MISCONCEPTIONS = 4
+PRIORITY_SECTION_ENTITY = 5
+PRIORITY_CHAPTER_ENTITY = 6
+
+TRUNCATION_MARKER = " [...]"
+
+
+@dataclass
+class ContextPart:
+    title: str
+    text: str
+    priority: int
+    truncatable: bool = True  # Whether the part may be cut to fit, otherwise it is kept whole or dropped
+
+
+def estimate_tokens(text: str) -> int:
+    return (len(text) + CHARS_PER_TOKEN - 1) // CHARS_PER_TOKEN
+
+
+def _part_tokens(part: ContextPart) -> int:
+    # The rendered title counts against the budget as well
+    return estimate_tokens(part.title) + estimate_tokens(part.text) + 2
+
+
+def prioritize_context(parts: List[ContextPart], token_budget: int) -> List[ContextPart]:
+    """
+    Select the parts that fit into the token budget, most important first
+
+    Parts with PRIORITY_PROBLEM are always kept. A truncatable part that does not fit whole is cut to the
+    remaining budget, unless less than MIN_TRUNCATED_TOKENS would remain of it. Parts of equal priority are
+    considered in their given order.
+
+    Returns:
+        List[ContextPart]: The kept parts, possibly truncated, in their given order
+    """
+    kept = {}
+    remaining = token_budget
+    for index, part in sorted(enumerate(parts), key=lambda item: item[1].priority):
+        tokens = _part_tokens(part)
+        if part.priority == PRIORITY_PROBLEM or tokens <= remaining:
+            kept[index] = part
+            remaining -= tokens
+            continue
+        available = remaining - estimate_tokens(part.title) - 2 - estimate_tokens(TRUNCATION_MARKER)
+        if part.truncatable and available >= MIN_TRUNCATED_TOKENS:
+            kept[index] = ContextPart(part.title, part.text[: available * CHARS_PER_TOKEN].rstrip() + TRUNCATION_MARKER, part.priority, part.truncatable)
+            remaining = 0
+    return [kept[index] for index in sorted(kept)]
+
+
+def render_context(parts: List[ContextPart]) -> str:
+    return "\n\n".join(f"## {part.title}\n{part.text}" for part in parts)
+
+
+# ------------------------------------------------------------
+# Accuracy metrics
+# ------------------------------------------------------------
+
+@dataclass
+class GradingPeriodMetrics:
+    period_start: date
+    attempts: int
+    escalations: int  # Attempts regraded because of a low confidence
+    disputes: int
+    resolved_disputes: int
+    overturned: int  # Resolved disputes where the reviewer disagreed with the disputed grade
+    dispute_rate: float
+    accuracy: float  # Share of attempts whose grade was not overturned
+    escalation_agreement: Optional[float]  # Share of regraded disputes where the escalation model agreed with the reviewer
+
+
+def grades_agree(score: Optional[float], is_correct: Optional[bool], other_score: Optional[float], other_is_correct: Optional[bool]) -> bool:
+    if is_correct != other_is_correct:
+        return False
+    return score is None or other_score is None or abs(score - other_score) <= SCORE_AGREEMENT_TOLERANCE
+
+
+def _period_start(day: date, period_days: int) -> date:
+    return METRICS_EPOCH + timedelta(days=(day - METRICS_EPOCH).days // period_days * period_days)
+
+
+def grading_metrics(attempts: Iterable, disputes: Iterable, period_days: int = 7) -> List[GradingPeriodMetrics]:
+    """
+    Compute grading accuracy per period from attempts (with their judgments) and disputes
+
+    Disputes count in the period of the attempt they dispute, so a period's accuracy improves or worsens as its
+    disputes are reviewed. Disputes of attempts not given are ignored.
+    """
+    periods: Dict[date, dict] = {}
+    attempt_periods: Dict[int, date] = {}
+    for attempt in attempts:
+        start = _period_start(attempt.created_at.date(), period_days)
+        attempt_periods[attempt.attempt_id] = start
+        period = periods.setdefault(start, {"attempts": 0, "escalations": 0, "disputes": 0, "resolved": 0, "overturned": 0, "regraded": 0, "agreed": 0})
+        period["attempts"] += 1
+        if any(judgment.reason == JUDGMENT_LOW_CONFIDENCE for judgment in attempt.judgments):
+            period["escalations"] += 1
+
+    for dispute in disputes:
+        if dispute.attempt_id not in attempt_periods:
+            continue
+        period = periods[attempt_periods[dispute.attempt_id]]
+        period["disputes"] += 1
+        if dispute.status != DISPUTE_RESOLVED:
+            continue
+        period["resolved"] += 1
+        if not grades_agree(dispute.original_score, dispute.original_is_correct, dispute.resolved_score, dispute.resolved_is_correct):
+            period["overturned"] += 1
+        if dispute.escalated_is_correct is not None:
+            period["regraded"] += 1
+            if grades_agree(dispute.escalated_score, dispute.escalated_is_correct, dispute.resolved_score, dispute.resolved_is_correct):
+                period["agreed"] += 1
+
+    return [
+        GradingPeriodMetrics(
+            period_start=start,
+            attempts=period["attempts"],
+            escalations=period["escalations"],
+            disputes=period["disputes"],
+            resolved_disputes=period["resolved"],
+            overturned=period["overturned"],
+            dispute_rate=period["disputes"] / period["attempts"],
+            accuracy=1 - period["overturned"] / period["attempts"],
+            escalation_agreement=period["agreed"] / period["regraded"] if period["regraded"] else None,
+        )
+        for start, period in sorted(periods.items())
+    ]
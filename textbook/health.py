@@ -0,0 +1,81 @@
+# Dependency health
+# GET /health answers as long as the server is up, for liveness probes, and reports how the services it depends on
+# are doing, so operators can see why OCR or LLM jobs fail without reading the errors of each job. The OCR backend is
+# pinged: MinerU at its health endpoint, Tesseract by looking for its package and binary. The LLM provider is judged
+# from its circuit breaker rather than prompted, a prompt costs tokens on every probe. Pings are kept for
+# PING_CACHE_SECONDS, so frequent probes do not load MinerU, and a server with a dependency down is "degraded".
+
+import threading
+import time
+from dataclasses import dataclass
+from typing import TYPE_CHECKING, Callable, List, Optional, Tuple
+
+from textbook.circuit_breaker import BREAKER_CLOSED, BREAKER_OPEN
+from textbook.ocr import OcrBackend
+
+if TYPE_CHECKING:
+    from textbook.model import LLM
+
+DEPENDENCY_OK = "ok"
+DEPENDENCY_DOWN = "down"
+DEPENDENCY_DISABLED = "disabled"
+
+SERVER_HEALTHY = "healthy"
+SERVER_DEGRADED = "degraded"  # Up, but a dependency is down
+
+DEPENDENCY_LLM = "llm"
+PING_CACHE_SECONDS = 15.0
+
+
+@dataclass
+class DependencyHealth:
+    name: str  # The OCR backend, e.g. "mineru", or "llm"
+    status: str
+    detail: Optional[str] = None  # Why it is down or disabled
+    latency_ms: Optional[float] = None  # Of the ping, None when it was not pinged
+
+
+def ocr_health(backend: OcrBackend, clock: Callable[[], float] = time.monotonic) -> DependencyHealth:
+    started = clock()
+    reason = backend.ping()
+    latency_ms = round((clock() - started) * 1000, 1)
+    return DependencyHealth(backend.name, DEPENDENCY_DOWN if reason else DEPENDENCY_OK, reason, latency_ms)
+
+
+def llm_health(model: Optional["LLM"]) -> DependencyHealth:
+    """The health of the LLM provider from its circuit breaker, without prompting it"""
+    if model is None:
+        return DependencyHealth(DEPENDENCY_LLM, DEPENDENCY_DISABLED, "No LLM is configured, set the API key of the provider")
+    if model.circuit_breaker is None:
+        return DependencyHealth(DEPENDENCY_LLM, DEPENDENCY_OK)
+    status = model.circuit_breaker.status()
+    if status.state == BREAKER_OPEN:
+        return DependencyHealth(DEPENDENCY_LLM, DEPENDENCY_DOWN, f"{status.service} failed {status.consecutive_failures} calls in a row, probed again in {status.retry_in_seconds:.0f} seconds")
+    if status.state != BREAKER_CLOSED:
+        return DependencyHealth(DEPENDENCY_LLM, DEPENDENCY_DOWN, f"{status.service} is being probed after repeated failures")
+    detail = f"{status.consecutive_failures} of the last calls failed" if status.consecutive_failures else None
+    return DependencyHealth(DEPENDENCY_LLM, DEPENDENCY_OK, detail)
+
+
+def server_status(dependencies: List[DependencyHealth]) -> str:
+    return SERVER_DEGRADED if any(dependency.status == DEPENDENCY_DOWN for dependency in dependencies) else SERVER_HEALTHY
+
+
+class HealthProbe:
+    """Checks the dependencies of the server, pinging the OCR backend at most once per cache_seconds"""
+
+    def __init__(self, ocr_backend: OcrBackend, cache_seconds: float = PING_CACHE_SECONDS, clock: Callable[[], float] = time.monotonic):
+        self.ocr_backend = ocr_backend
+        self.cache_seconds = cache_seconds
+        self._clock = clock
+        self._lock = threading.Lock()
+        self._ocr: Optional[Tuple[float, DependencyHealth]] = None  # When it was pinged, and what it answered
+
+    def ocr(self) -> DependencyHealth:
+        with self._lock:
+            if self._ocr is None or self._clock() - self._ocr[0] >= self.cache_seconds:
+                self._ocr = (self._clock(), ocr_health(self.ocr_backend, self._clock))
+            return self._ocr[1]
+
+    def check(self, model: Optional["LLM"]) -> List[DependencyHealth]:
+        return [self.ocr(), llm_health(model)]
@@ -0,0 +1,88 @@
+# Hint ladders
+# Progressive help for a problem, so a learner can reveal as little as they need: a nudge pointing at what to look
+# at, then the approach to take, then a partial solution stopping short of the answer. The LLM writes the three tiers
+# in one answer in a job, they are stored as levels 1 to 3 and read up to the level the learner asked for.
+# Generating the ladder again replaces it.
+
+from typing import Callable, List, Optional
+
+from pydantic import BaseModel
+
+from textbook.database import HintInfo, TextBookDatabase
+from textbook.jobs import CancellationToken
+from textbook.model import LLM
+from textbook.problems import problem_figures, problem_page_images
+from textbook.solutions import figures_rule, problem_context
+
+HINT_TIER_NUDGE = "nudge"
+HINT_TIER_APPROACH = "approach"
+HINT_TIER_PARTIAL_SOLUTION = "partial_solution"
+HINT_TIERS = (HINT_TIER_NUDGE, HINT_TIER_APPROACH, HINT_TIER_PARTIAL_SOLUTION)  # Level 1 to 3
+
+
+class HintLadderSchema(BaseModel):
+    nudge: str
+    approach: str
+    partial_solution: str
+
+
+def hint_ladder_prompt(statement: str, figures: List[str], context: str, figures_attached: bool = False) -> str:
+    return f"""
+    Write three hints of increasing help for the following problem with rules:
+    - nudge points at what to look at or recall, in one or two sentences, without naming the method
+    - approach names the method or theorem to use and how to start, without carrying it out
+    - partial_solution carries out the first part of the solution and stops before the answer
+    - use LaTeX for mathematics and the definitions and notation of the context when it has them
+    - {figures_rule(figures, figures_attached)}
+
+    Problem:
+     {statement}
+
+    Context:
+     {context}
+    """
+
+
+def generate_hint_ladder(
+    database: TextBookDatabase,
+    llm: LLM,
+    problem_id: int,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+    uploads_dir: Optional[str] = None,
+) -> List[HintInfo]:
+    """
+    Generate and store the hint ladder of a problem, replacing the one generated before
+
+    Args:
+        uploads_dir: Where the problem's PDF is, to attach the pages of its figures for models that can see them
+
+    Raises:
+        ValueError: If the problem does not exist or the LLM left a tier empty
+    """
+    problem = database.get_problem(problem_id)
+    if problem is None:
+        raise ValueError(f"Problem not found: {problem_id}")
+    if report_progress is not None:
+        report_progress("hints", 0, f"Writing hints for problem {problem.problem_number or problem_id}")
+
+    images = problem_page_images(database, problem, uploads_dir) if uploads_dir is not None and llm.supports_images else []
+    prompt = hint_ladder_prompt(problem.statement, problem_figures(problem), problem_context(database, problem), figures_attached=bool(images))
+    ladder = llm.prompt_with_schema_and_images(prompt, HintLadderSchema, images) if images else llm.prompt_with_schema(prompt, schema=HintLadderSchema)
+    texts = [ladder.nudge.strip(), ladder.approach.strip(), ladder.partial_solution.strip()]
+    if not all(texts):
+        raise ValueError(f"The hint ladder of problem {problem_id} has an empty tier")
+
+    if cancellation_token is not None:
+        cancellation_token.raise_if_cancelled()
+    return database.replace_hints(problem_id, [
+        HintInfo(level=level, tier=tier, text=text, model_name=llm.model_name)
+        for level, (tier, text) in enumerate(zip(HINT_TIERS, texts), start=1)
+    ])
+
+
+def revealed_hints(database: TextBookDatabase, problem_id: int, level: int) -> List[HintInfo]:
+    """The hints of a problem up to a level, raises ValueError for levels outside the ladder"""
+    if not 1 <= level <= len(HINT_TIERS):
+        raise ValueError(f"level must be between 1 and {len(HINT_TIERS)}")
+    return [hint for hint in database.get_hints(problem_id) if hint.level <= level]
@@ -0,0 +1,149 @@
+# Page and figure images
+# Pages rendered from their PDF and the figures OCR extracted are served as PNG at full size, a few hundred KB each,
+# while the library grid shows them a few hundred pixels wide. An image is served in a size variant, scaled down to
+# fit its width, and in an image format: PNG, or WebP and AVIF, a fraction of the size at the same quality (AVIF
+# needs a Pillow built with libavif). Every variant served is cached on disk under <uploads_dir>/.image_cache, keyed
+# by its source file and the source's modification time, so a replaced PDF or a new OCR is never served stale.
+# Set in config.toml:
+#   [images]
+#   format = "webp"  # png (default), webp or avif, of requests not asking for one
+#   quality = 80     # 1 to 100, of webp and avif
+#   cache = true
+
+import hashlib
+import io
+import os
+import tempfile
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Callable, Dict, Optional, Tuple
+
+from PIL import Image
+
+IMAGE_CACHE_DIR = ".image_cache"  # In the uploads directory
+
+SIZE_THUMB = "thumb"
+SIZE_SMALL = "small"
+SIZE_MEDIUM = "medium"
+SIZE_FULL = "full"
+SIZE_WIDTHS: Dict[str, Optional[int]] = {SIZE_THUMB: 160, SIZE_SMALL: 320, SIZE_MEDIUM: 800, SIZE_FULL: None}
+
+FORMAT_PNG = "png"
+FORMAT_WEBP = "webp"
+FORMAT_AVIF = "avif"
+IMAGE_FORMATS: Dict[str, Tuple[str, str]] = {  # Pillow's name of each format and its media type
+    FORMAT_PNG: ("PNG", "image/png"),
+    FORMAT_WEBP: ("WEBP", "image/webp"),
+    FORMAT_AVIF: ("AVIF", "image/avif"),
+}
+DEFAULT_QUALITY = 80
+
+
+@dataclass
+class ImageSettings:
+    format: str = FORMAT_PNG  # Of requests not asking for a format
+    quality: int = DEFAULT_QUALITY  # Of the lossy formats
+    cache: bool = True
+
+
+def format_supported(image_format: str) -> bool:
+    """Whether the installed Pillow can write the format"""
+    Image.init()
+    return image_format in IMAGE_FORMATS and IMAGE_FORMATS[image_format][0] in Image.SAVE
+
+
+def check_variant(size: str, image_format: str):
+    """Raises ValueError for a size or format that cannot be served"""
+    if size not in SIZE_WIDTHS:
+        raise ValueError(f"Unknown image size: {size}, expected one of {', '.join(SIZE_WIDTHS)}")
+    if image_format not in IMAGE_FORMATS:
+        raise ValueError(f"Unknown image format: {image_format}, expected one of {', '.join(IMAGE_FORMATS)}")
+    if not format_supported(image_format):
+        raise ValueError(f"The {image_format} image format is not supported by the installed Pillow")
+
+
+def image_settings_from_config(config: dict) -> ImageSettings:
+    """The [images] section of config.toml, raises ValueError for invalid settings"""
+    section = config.get("images", {})
+    unknown = set(section) - {"format", "quality", "cache"}
+    if unknown:
+        raise ValueError(f"Unknown settings of images: {', '.join(sorted(unknown))}")
+    settings = ImageSettings(
+        format=section.get("format", FORMAT_PNG),
+        quality=section.get("quality", DEFAULT_QUALITY),
+        cache=section.get("cache", True),
+    )
+    check_variant(SIZE_FULL, settings.format)
+    if isinstance(settings.quality, bool) or not isinstance(settings.quality, int) or not 1 <= settings.quality <= 100:
+        raise ValueError("images.quality must be an integer between 1 and 100")
+    if not isinstance(settings.cache, bool):
+        raise ValueError("images.cache must be true or false")
+    return settings
+
+
+def media_type(image_format: str) -> str:
+    return IMAGE_FORMATS[image_format][1]
+
+
+def encode_variant(image: Image.Image, size: str, image_format: str, quality: int = DEFAULT_QUALITY) -> bytes:
+    """The image scaled down to fit the width of the size variant, never up, and encoded in the format"""
+    width = SIZE_WIDTHS[size]
+    if width is not None and image.width > width:
+        image = image.resize((width, max(1, round(image.height * width / image.width))), Image.Resampling.LANCZOS)
+    if image.mode not in ("RGB", "RGBA", "L"):
+        image = image.convert("RGBA")
+    buffer = io.BytesIO()
+    if image_format == FORMAT_PNG:
+        image.save(buffer, format="PNG", optimize=True)
+    else:
+        image.save(buffer, format=IMAGE_FORMATS[image_format][0], quality=quality)
+    return buffer.getvalue()
+
+
+class ImageCache:
+    """Serves size and format variants of images, rendering each once per version of its source"""
+
+    def __init__(self, directory: Path, settings: Optional[ImageSettings] = None):
+        self.directory = Path(directory)
+        self.settings = settings or ImageSettings()
+
+    def variant(
+        self,
+        source: Path,
+        key: str,
+        size: str,
+        image_format: Optional[str],
+        render: Callable[[], Image.Image],
+    ) -> Tuple[bytes, str]:
+        """
+        An image of source in a size and format, from the cache or rendered and cached
+
+        Args:
+            source: The file the image is rendered from, a new version of it renders the image again
+            key: Which image of the source, e.g. the page and DPI a page is rendered at
+            size: The size variant, thumb, small, medium or full
+            image_format: png, webp or avif, the configured format if None
+            render: Renders the image at full size
+
+        Returns:
+            Tuple[bytes, str]: The encoded image and its media type, raises ValueError for an unknown size or format
+        """
+        image_format = image_format or self.settings.format
+        check_variant(size, image_format)
+        if not self.settings.cache:
+            return encode_variant(render(), size, image_format, self.settings.quality), media_type(image_format)
+
+        version = f"{source.resolve()}|{source.stat().st_mtime_ns}|{key}|{size}|{image_format}|{self.settings.quality}"
+        digest = hashlib.sha256(version.encode("utf-8")).hexdigest()
+        path = self.directory / digest[:2] / f"{digest}.{image_format}"
+        if path.exists():
+            return path.read_bytes(), media_type(image_format)
+
+        data = encode_variant(render(), size, image_format, self.settings.quality)
+        path.parent.mkdir(parents=True, exist_ok=True)
+        # Written to a temporary file and renamed, a request reading the variant meanwhile never sees half of it
+        descriptor, temporary = tempfile.mkstemp(dir=path.parent, suffix=".tmp")
+        with os.fdopen(descriptor, "wb") as f:
+            f.write(data)
+        os.replace(temporary, path)
+        return data, media_type(image_format)
@@ -0,0 +1,84 @@
+# Ingestion settings
+# How an uploaded document goes through the pipeline: the language and parse method MinerU reads it with, how many
+# problems are kept per chapter and the difficulty they are picked around. config.toml sets them for every document:
+#   ocr_language = "en"           # MinerU language code, e.g. "ch" or "latin"
+#   ocr_parse_method = "auto"     # "auto", "txt" for PDFs with a text layer or "ocr" for scans
+#   problems_per_section = 20     # at most this many problems per chapter, every problem without it
+#   target_difficulty = 3         # 1 (routine) to 5 (hardest), the problems kept are picked around it
+# An upload can override any of them, the overrides are stored with the document and every job of its pipeline
+# (the OCR, then problem extraction whether asked for or prefetched) reads the settings through
+# document_ingestion_settings, so changing config.toml later only changes what the document did not override.
+
+import json
+from dataclasses import dataclass, fields, replace
+from typing import Optional
+
+from textbook.database import TextBookDatabase
+
+PARSE_METHODS = ("auto", "txt", "ocr")
+MIN_TARGET_DIFFICULTY = 1
+MAX_TARGET_DIFFICULTY = 5
+CONFIG_KEYS = {
+    "ocr_language": "ocr_language",
+    "parse_method": "ocr_parse_method",
+    "problems_per_section": "problems_per_section",
+    "target_difficulty": "target_difficulty",
+}
+
+
+@dataclass
+class IngestionSettings:
+    ocr_language: str = "en"
+    parse_method: str = "auto"
+    problems_per_section: Optional[int] = None  # None keeps every problem
+    target_difficulty: Optional[int] = None  # None keeps the problems in the order of the text
+
+
+def validate_ingestion_settings(settings: IngestionSettings) -> None:
+    if not settings.ocr_language.strip():
+        raise ValueError("ocr_language cannot be empty")
+    if settings.parse_method not in PARSE_METHODS:
+        raise ValueError(f"Invalid parse_method: {settings.parse_method}, expected one of {', '.join(PARSE_METHODS)}")
+    if settings.problems_per_section is not None and settings.problems_per_section < 1:
+        raise ValueError("problems_per_section must be at least 1")
+    if settings.target_difficulty is not None and not MIN_TARGET_DIFFICULTY <= settings.target_difficulty <= MAX_TARGET_DIFFICULTY:
+        raise ValueError(f"target_difficulty must be between {MIN_TARGET_DIFFICULTY} and {MAX_TARGET_DIFFICULTY}")
+
+
+def ingestion_settings_from_config(config: dict) -> IngestionSettings:
+    """The ingestion settings of config.toml, raises ValueError for invalid ones"""
+    settings = IngestionSettings(**{name: config[key] for name, key in CONFIG_KEYS.items() if key in config})
+    validate_ingestion_settings(settings)
+    return settings
+
+
+def apply_overrides(settings: IngestionSettings, overrides: Optional[dict]) -> IngestionSettings:
+    """The settings with a document's overrides applied, raises ValueError for unknown or invalid ones"""
+    overrides = overrides or {}
+    unknown = set(overrides) - {field.name for field in fields(IngestionSettings)}
+    if unknown:
+        raise ValueError(f"Unknown ingestion settings: {', '.join(sorted(unknown))}")
+    overridden = replace(settings, **overrides)
+    validate_ingestion_settings(overridden)
+    return overridden
+
+
+def document_overrides(database: TextBookDatabase, document_id: int) -> dict:
+    """The settings overridden for a document, empty if it follows config.toml"""
+    document = database.get_document(document_id)
+    if document is None or not document.ingestion_settings:
+        return {}
+    return json.loads(document.ingestion_settings)
+
+
+def document_ingestion_settings(database: TextBookDatabase, document_id: int, defaults: IngestionSettings, overrides: Optional[dict] = None) -> IngestionSettings:
+    """The settings a document is ingested with, the given overrides instead of the stored ones if any"""
+    return apply_overrides(defaults, document_overrides(database, document_id) if overrides is None else overrides)
+
+
+def store_overrides(database: TextBookDatabase, document_id: int, defaults: IngestionSettings, overrides: dict) -> IngestionSettings:
+    """Validate and store the settings overridden for a document, returns the settings it is ingested with"""
+    settings = apply_overrides(defaults, overrides)
+    database.update_document(document_id, ingestion_settings=json.dumps(overrides) if overrides else None)
+    return settings
+
@@ -0,0 +1,169 @@
+# Integrity checks for files stored on disk and the rows derived from them
+# Every file written for a book is recorded in artifact_info with its SHA-256 digest,
+# verify_integrity compares the recorded digests against the uploads directory and the database,
+# and repair_issues regenerates whatever can be recovered without the LLM. Uploads of the document library, a PDF and
+# the markdown its OCR writes next to it, are referenced by the document's source_path.
+
+import hashlib
+import os
+from dataclasses import dataclass, asdict
+from pathlib import Path
+from typing import Optional, List
+
+import pymupdf
+
+from textbook.database import TextBookDatabase, BookInfo, ChapterInfo, SectionInfo, ArtifactInfo, DocumentInfo
+
+SOURCE_PDF_ARTIFACT = "source_pdf"
+ARTICLE_MARKDOWN_ARTIFACT = "article_markdown"
+TRANSCRIPT_MARKDOWN_ARTIFACT = "transcript_markdown"
+NOTEBOOK_MARKDOWN_ARTIFACT = "notebook_markdown"
+
+# Issue kinds
+MISSING_FILE = "missing_file"
+CHECKSUM_MISMATCH = "checksum_mismatch"
+UNREGISTERED_FILE = "unregistered_file"
+ORPHAN_FILE = "orphan_file"
+PAGE_COUNT_MISMATCH = "page_count_mismatch"
+INVALID_PAGE_RANGE = "invalid_page_range"
+
+# Issues that repair_issues can fix without user input
+RECOVERABLE_ISSUES = (UNREGISTERED_FILE, PAGE_COUNT_MISMATCH)
+
+
+@dataclass
+class IntegrityIssue:
+    kind: str
+    detail: str
+    book_id: Optional[int] = None
+    artifact_id: Optional[int] = None
+    path: Optional[str] = None
+
+    @property
+    def recoverable(self) -> bool:
+        return self.kind in RECOVERABLE_ISSUES
+
+    def to_dict(self) -> dict:
+        return {**asdict(self), "recoverable": self.recoverable}
+
+
+def file_sha256(path: Path) -> str:
+    """Compute the SHA-256 digest of a file without loading it into memory at once"""
+    digest = hashlib.sha256()
+    with open(path, "rb") as f:
+        for chunk in iter(lambda: f.read(1024 * 1024), b""):
+            digest.update(chunk)
+    return digest.hexdigest()
+
+
+def register_file_artifact(database: TextBookDatabase, uploads_dir: str, book_id: int, kind: str, file_name: str) -> int:
+    """Record the digest of a file in the uploads directory as an artifact of a book"""
+    path = Path(uploads_dir) / file_name
+    return database.register_artifact(book_id, kind, file_name, file_sha256(path), os.path.getsize(path))
+
+
+def verify_integrity(database: TextBookDatabase, uploads_dir: str) -> List[IntegrityIssue]:
+    """
+    Check recorded artifacts and book rows against the uploads directory
+
+    Returns:
+        List[IntegrityIssue]: Every problem found, empty when everything is consistent
+    """
+    issues: List[IntegrityIssue] = []
+    referenced_files = set()
+
+    with database.new_session() as session:
+        artifacts = session.query(ArtifactInfo).order_by(ArtifactInfo.artifact_id).all()
+        for artifact in artifacts:
+            referenced_files.add(artifact.artifact_path)
+            path = Path(uploads_dir) / artifact.artifact_path
+            if not path.exists():
+                issues.append(IntegrityIssue(MISSING_FILE, f"{artifact.artifact_kind} is missing on disk", artifact.book_id, artifact.artifact_id, artifact.artifact_path))
+            elif file_sha256(path) != artifact.artifact_sha256:
+                issues.append(IntegrityIssue(CHECKSUM_MISMATCH, f"{artifact.artifact_kind} does not match its recorded digest", artifact.book_id, artifact.artifact_id, artifact.artifact_path))
+
+        for book in session.query(BookInfo).order_by(BookInfo.book_id).all():
+            if not book.book_file_name:
+                continue
+
+            pdf_file_name = f"{book.book_file_name}.pdf"
+            pdf_path = Path(uploads_dir) / pdf_file_name
+            referenced_files.add(pdf_file_name)
+
+            if pdf_file_name not in {artifact.artifact_path for artifact in book.artifacts}:
+                if pdf_path.exists():
+                    issues.append(IntegrityIssue(UNREGISTERED_FILE, "source PDF has no recorded digest", book.book_id, path=pdf_file_name))
+                else:
+                    issues.append(IntegrityIssue(MISSING_FILE, "source PDF is missing on disk", book.book_id, path=pdf_file_name))
+
+            total_pages = _count_pages(pdf_path)
+            if total_pages is not None and book.book_pages is not None and book.book_pages != total_pages:
+                issues.append(IntegrityIssue(PAGE_COUNT_MISMATCH, f"book_pages is {book.book_pages} but the PDF has {total_pages} pages", book.book_id, path=pdf_file_name))
+
+            last_page = total_pages - 1 if total_pages is not None else None
+            blocks: List[ChapterInfo | SectionInfo] = [*book.chapters, *book.sections]
+            for block in blocks:
+                if _is_invalid_range(block.start_page_number, block.end_page_number, last_page):
+                    issues.append(IntegrityIssue(
+                        INVALID_PAGE_RANGE,
+                        f"{block.__tablename__} '{block.title}' has page range {block.start_page_number}-{block.end_page_number}, regenerate the TOC with /update-toc",
+                        book.book_id,
+                    ))
+
+        for document in session.query(DocumentInfo).all():
+            if document.source_path:
+                referenced_files.add(document.source_path)
+                referenced_files.add(Path(document.source_path).with_suffix(".md").as_posix())
+
+    if os.path.isdir(uploads_dir):
+        for file_name in sorted(os.listdir(uploads_dir)):
+            if os.path.isfile(Path(uploads_dir) / file_name) and file_name not in referenced_files:
+                issues.append(IntegrityIssue(ORPHAN_FILE, "file is not referenced by any book or document", path=file_name))
+
+    return issues
+
+
+def repair_issues(database: TextBookDatabase, uploads_dir: str, issues: List[IntegrityIssue]) -> List[IntegrityIssue]:
+    """
+    Regenerate the recoverable issues found by verify_integrity
+
+    Returns:
+        List[IntegrityIssue]: The issues that were repaired
+    """
+    repaired: List[IntegrityIssue] = []
+    for issue in issues:
+        if not issue.recoverable or issue.book_id is None or issue.path is None:
+            continue
+
+        if issue.kind == UNREGISTERED_FILE:
+            register_file_artifact(database, uploads_dir, issue.book_id, SOURCE_PDF_ARTIFACT, issue.path)
+        elif issue.kind == PAGE_COUNT_MISMATCH:
+            total_pages = _count_pages(Path(uploads_dir) / issue.path)
+            if total_pages is None:
+                continue
+            with database.new_session() as session:
+                session.query(BookInfo).filter(BookInfo.book_id == issue.book_id).update({BookInfo.book_pages: total_pages})
+                session.commit()
+        repaired.append(issue)
+
+    return repaired
+
+
+def _count_pages(pdf_path: Path) -> Optional[int]:
+    if not pdf_path.exists():
+        return None
+    try:
+        with pymupdf.open(pdf_path) as document:
+            return len(document)
+    except Exception:
+        return None
+
+
+def _is_invalid_range(start_page: int, end_page: Optional[int], last_page: Optional[int]) -> bool:
+    if start_page < 0:
+        return True
+    if end_page is not None and end_page < start_page:
+        return True
+    if last_page is not None and (start_page > last_page or (end_page is not None and end_page > last_page)):
+        return True
+    return False
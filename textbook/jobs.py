@@ -0,0 +1,763 @@
+# Background jobs
+# Long running work, like OCR of an uploaded document, runs in a job so the request that starts it returns right
+# away with the job's ID. Jobs are submitted by kind with JSON parameters, a handler registered for the kind turns
+# the parameters into the JSON result, so a job can be run again from what is stored.
+# Each kind belongs to a category (ocr, llm, render) with a number of workers, so fifty uploads do not all hit MinerU
+# at once: a job runs on its own thread when a worker of its category is free and stays pending in a first in,
+# first out queue until then. The limits are independent, MinerU's GPU saturates at about two parses while the LLM
+# provider takes ten calls or more, so OCR jobs queueing never hold up LLM jobs and the other way around. They are set
+# in the [jobs] section of config.toml:
+#   [jobs]
+#   ocr_workers = 2
+#   llm_workers = 10
+#   render_workers = 2
+# With a database every status change is written through to job_info and job_event_info: the history survives a
+# restart, and jobs that were pending or running when the server stopped are marked interrupted on startup so they
+# can be inspected and resumed.
+# Handlers also get a JobHandle whose cancellation token is set when the job is cancelled. Threads cannot be stopped
+# from outside, so a handler checks the token between its steps and raises JobCancelled; the job is marked cancelled
+# right away and whatever the handler returns afterwards is dropped. The handle also reports progress: the stage the
+# handler is in, the percentage done and a detail like "page 12/80 rendered", which clients show while polling or
+# follow as they happen by watching the job: every change bumps the job's version and wakes up its watchers.
+# A job failing with a ModelError keeps the error's category (auth, quota, ...) next to its message, so clients can
+# tell users to fix their API key rather than to retry.
+# Jobs submitted at low priority, like pre-generating what a user will likely read next, queue behind the other jobs
+# of their category and run on all but one of its workers at most, so they never hold up what a user is waiting for.
+# A category can be paused, e.g. OCR while the disk is almost full: its pending jobs stay queued and new ones are
+# accepted, but none starts until it is resumed. Jobs already running finish.
+# While a handler runs, current_job_id() names its job, so work done on its behalf (like prompting an LLM) can be
+# recorded per job without passing the handle down.
+# A job calling a service whose circuit breaker is open (see circuit_breaker) is deferred rather than failed: nothing
+# is wrong with the job, so it is resumed as soon as the breaker closes again.
+# Related jobs, like extracting the problems of every chapter of a book, can be submitted as a group: each runs as a
+# job of its own, carrying the group's ID, and the group's status is aggregated from its jobs, how many completed out
+# of the total and the first one that failed. Listeners learn when a job finished, which pipelines (see pipeline) use
+# to start the jobs that were waiting for it.
+# Jobs that failed or were interrupted stay in the dead-letter queue until someone looks at them: nothing runs them
+# again by itself. Resuming runs a job again under its own ID; retrying submits its parameters as a new job that
+# records the job it retries, keeping the failed run and its error for inspection. A job is retried once, retry the
+# retry when that fails too. The retry is not part of the failed job's group.
+# Finished jobs are kept in memory for a while, then a sweeper drops them, so a long-running server does not keep every
+# job it ever ran: those finished longer ago than the retention, and the oldest beyond a cap. With a database they are
+# still read from the stored history; without one they are gone. Deferred jobs, jobs whose thread still runs and jobs
+# of a group with jobs left to run are kept. purge drops finished jobs right away, and their stored history if asked.
+# Set in the [jobs] section of config.toml:
+#   retention_hours = 24
+#   max_finished_jobs = 1000
+#   sweep_seconds = 300
+
+import json
+import threading
+import traceback
+import uuid
+from collections import deque
+from contextvars import ContextVar
+from dataclasses import dataclass, field, replace
+from datetime import datetime, timedelta, timezone
+from functools import partial
+from typing import Any, Callable, Deque, Dict, List, Optional, Set, Tuple
+
+from textbook.circuit_breaker import ServiceUnavailable
+from textbook.database import JobInfo, TextBookDatabase
+from textbook.provider_errors import ModelError
+
+# Job statuses
+JOB_PENDING = "pending"
+JOB_RUNNING = "running"
+JOB_SUCCEEDED = "succeeded"
+JOB_FAILED = "failed"
+JOB_INTERRUPTED = "interrupted"  # The server stopped while the job was pending or running
+JOB_CANCELLED = "cancelled"
+JOB_DEFERRED = "deferred"  # A service the job calls is down, it runs again once the service is back
+JOB_STATUSES = (JOB_PENDING, JOB_RUNNING, JOB_SUCCEEDED, JOB_FAILED, JOB_INTERRUPTED, JOB_CANCELLED, JOB_DEFERRED)
+RESUMABLE_STATUSES = (JOB_FAILED, JOB_INTERRUPTED, JOB_CANCELLED, JOB_DEFERRED)
+DEAD_LETTER_STATUSES = (JOB_FAILED, JOB_INTERRUPTED)  # Nothing runs them again until they are retried or resumed
+EVICTABLE_STATUSES = (JOB_SUCCEEDED, JOB_FAILED, JOB_INTERRUPTED, JOB_CANCELLED)  # Deferred jobs wait to be resumed
+
+# Job kinds
+JOB_KIND_OCR = "ocr"
+JOB_KIND_ANALYTICS_EXPORT = "analytics_export"
+JOB_KIND_PROBLEM_EXTRACTION = "problem_extraction"
+JOB_KIND_SOLUTION = "solution"
+JOB_KIND_HINT_LADDER = "hint_ladder"
+JOB_KIND_DOCUMENT_SUMMARY = "document_summary"
+JOB_KIND_GLOSSARY = "glossary"
+JOB_KIND_ANSWER_CHECK = "answer_check"
+JOB_KIND_SEGMENTATION = "segmentation"
+JOB_KIND_SYLLABUS_IMPORT = "syllabus_import"
+JOB_KIND_PROBLEM_QUALITY = "problem_quality"
+
+# Job categories sharing a worker limit
+JOB_CATEGORY_OCR = "ocr"
+JOB_CATEGORY_LLM = "llm"
+JOB_CATEGORY_RENDER = "render"
+DEFAULT_CONCURRENCY = {JOB_CATEGORY_OCR: 2, JOB_CATEGORY_LLM: 10, JOB_CATEGORY_RENDER: 2}
+DEFAULT_WORKERS = 2  # Workers of a category without a configured limit
+
+# Job priorities
+PRIORITY_NORMAL = "normal"
+PRIORITY_LOW = "low"  # Queued behind normal jobs, and kept off the last worker of the category
+JOB_PRIORITIES = (PRIORITY_NORMAL, PRIORITY_LOW)
+
+DEFAULT_RETENTION_HOURS = 24
+DEFAULT_MAX_FINISHED_JOBS = 1000
+DEFAULT_SWEEP_SECONDS = 300
+
+INTERRUPTED_ERROR = "The server stopped before the job finished"
+CANCELLED_ERROR = "The job was cancelled"
+
+
+def concurrency_from_config(config: dict) -> Dict[str, int]:
+    """
+    Workers per category from the [jobs] section of config.toml, "<category>_workers" keys, over the older
+    job_concurrency table keyed by category
+
+    Raises:
+        ValueError: If a limit is not a whole number
+    """
+    concurrency = dict(config.get("job_concurrency", {}))
+    concurrency.update({key[:-len("_workers")]: value for key, value in config.get("jobs", {}).items() if key.endswith("_workers")})
+    for category, workers in concurrency.items():
+        if isinstance(workers, bool) or not isinstance(workers, int):
+            raise ValueError(f"The number of {category} workers must be a whole number, not {workers!r}")
+    return concurrency
+
+
+@dataclass
+class JobRetention:
+    retention_hours: float = DEFAULT_RETENTION_HOURS  # Finished jobs are kept in memory this long
+    max_finished_jobs: int = DEFAULT_MAX_FINISHED_JOBS  # And at most this many, the oldest are dropped first
+    sweep_seconds: float = DEFAULT_SWEEP_SECONDS  # How often the sweeper drops them
+
+
+def retention_from_config(config: dict) -> JobRetention:
+    """The retention of finished jobs from the [jobs] section of config.toml, raises ValueError for invalid settings"""
+    section = config.get("jobs", {})
+    retention = JobRetention(
+        retention_hours=section.get("retention_hours", DEFAULT_RETENTION_HOURS),
+        max_finished_jobs=section.get("max_finished_jobs", DEFAULT_MAX_FINISHED_JOBS),
+        sweep_seconds=section.get("sweep_seconds", DEFAULT_SWEEP_SECONDS),
+    )
+    for name in ("retention_hours", "sweep_seconds"):
+        value = getattr(retention, name)
+        if isinstance(value, bool) or not isinstance(value, (int, float)) or value <= 0:
+            raise ValueError(f"jobs.{name} must be a positive number")
+    if isinstance(retention.max_finished_jobs, bool) or not isinstance(retention.max_finished_jobs, int) or retention.max_finished_jobs < 0:
+        raise ValueError("jobs.max_finished_jobs must be a non-negative whole number")
+    return retention
+
+
+@dataclass
+class PurgeResult:
+    evicted: int  # Finished jobs dropped from memory
+    deleted: int  # Finished jobs deleted from the stored history, with their events
+
+
+_current_job_id: ContextVar[Optional[str]] = ContextVar("current_job_id", default=None)
+
+
+def current_job_id() -> Optional[str]:
+    """The ID of the job whose handler runs on this thread, None outside of jobs"""
+    return _current_job_id.get()
+
+
+class JobCancelled(Exception):
+    """Raised by a handler that stopped because its job was cancelled"""
+
+
+class CancellationToken:
+    def __init__(self):
+        self._event = threading.Event()
+
+    def cancel(self):
+        self._event.set()
+
+    @property
+    def cancelled(self) -> bool:
+        return self._event.is_set()
+
+    def raise_if_cancelled(self):
+        if self.cancelled:
+            raise JobCancelled(CANCELLED_ERROR)
+
+
+@dataclass
+class JobHandle:
+    job_id: str
+    cancellation_token: CancellationToken
+    on_progress: Optional[Callable[[float, str, Optional[str]], Any]] = None  # Gets the share done, stage and detail
+
+    def report_progress(self, stage: str, percent: float, detail: Optional[str] = None):
+        """Report how far the job got, percent being between 0 and 100"""
+        if not 0 <= percent <= 100:
+            raise ValueError("percent must be between 0 and 100")
+        if self.on_progress is not None:
+            self.on_progress(percent / 100, stage, detail)
+
+
+JobHandler = Callable[[Dict[str, Any], JobHandle], Dict[str, Any]]
+
+
+@dataclass
+class Job:
+    job_id: str
+    kind: str
+    status: str
+    created_at: datetime
+    params: Dict[str, Any] = field(default_factory=dict)
+    started_at: Optional[datetime] = None
+    finished_at: Optional[datetime] = None
+    progress: float = 0.0  # Share of the work done, between 0 and 1
+    stage: Optional[str] = None  # Step the job last reported progress in
+    detail: Optional[str] = None  # What the job last reported doing
+    result: Optional[Dict[str, Any]] = None
+    error: Optional[str] = None
+    error_category: Optional[str] = None  # Category of the ModelError the job failed with, e.g. auth or quota
+    queue_position: Optional[int] = None  # Place in the queue of its category while pending, 1 runs next
+    group_id: Optional[str] = None  # The group the job was submitted in, if any
+    retry_of: Optional[str] = None  # The failed job this one retries, if any
+
+    @property
+    def finished(self) -> bool:
+        return self.status in (JOB_SUCCEEDED, *RESUMABLE_STATUSES)
+
+
+@dataclass
+class JobGroup:
+    group_id: str
+    jobs: List[Job]  # In the order they were submitted
+
+    @property
+    def total(self) -> int:
+        return len(self.jobs)
+
+    @property
+    def completed(self) -> int:
+        """Number of jobs that succeeded"""
+        return sum(job.status == JOB_SUCCEEDED for job in self.jobs)
+
+    @property
+    def progress(self) -> float:
+        return sum(job.progress for job in self.jobs) / len(self.jobs) if self.jobs else 0.0
+
+    @property
+    def counts(self) -> Dict[str, int]:
+        """Number of jobs per status"""
+        counts: Dict[str, int] = {}
+        for job in self.jobs:
+            counts[job.status] = counts.get(job.status, 0) + 1
+        return counts
+
+    @property
+    def first_failure(self) -> Optional[Job]:
+        """The job that failed, was interrupted or cancelled first"""
+        failed = [job for job in self.jobs if job.finished and job.status != JOB_SUCCEEDED]
+        return min(failed, key=lambda job: job.finished_at or job.created_at) if failed else None
+
+    @property
+    def status(self) -> str:
+        """pending until a job started, running until all finished, then succeeded or the status of the first failure"""
+        if all(job.status == JOB_PENDING for job in self.jobs):
+            return JOB_PENDING
+        if not all(job.finished for job in self.jobs):
+            return JOB_RUNNING
+        failure = self.first_failure
+        return failure.status if failure is not None else JOB_SUCCEEDED
+
+
+def job_from_info(info: JobInfo) -> Job:
+    return Job(
+        job_id=info.job_id,
+        kind=info.job_kind,
+        status=info.status,
+        created_at=info.created_at,
+        params=json.loads(info.params) if info.params else {},
+        started_at=info.started_at,
+        finished_at=info.finished_at,
+        progress=info.progress,
+        stage=info.stage,
+        detail=info.progress_detail,
+        result=json.loads(info.result) if info.result is not None else None,
+        error=info.error,
+        error_category=info.error_category,
+        group_id=info.group_id,
+        retry_of=info.retry_of,
+    )
+
+
+def job_to_info(job: Job) -> JobInfo:
+    return JobInfo(
+        job_id=job.job_id,
+        job_kind=job.kind,
+        status=job.status,
+        progress=job.progress,
+        stage=job.stage,
+        progress_detail=job.detail,
+        params=json.dumps(job.params),
+        result=json.dumps(job.result) if job.result is not None else None,
+        error=job.error,
+        error_category=job.error_category,
+        group_id=job.group_id,
+        retry_of=job.retry_of,
+        created_at=job.created_at,
+        started_at=job.started_at,
+        finished_at=job.finished_at,
+    )
+
+
+class JobPool:
+    def __init__(self, database: Optional[TextBookDatabase] = None, concurrency: Optional[Dict[str, int]] = None, retention: Optional[JobRetention] = None):
+        """
+        Args:
+            database: Store of the jobs, None to keep them in memory only
+            concurrency: Number of workers per category, overriding DEFAULT_CONCURRENCY
+            retention: How long and how many finished jobs are kept in memory
+        """
+        self.database = database
+        self.retention = retention or JobRetention()
+        self.concurrency = {**DEFAULT_CONCURRENCY, **(concurrency or {})}
+        for category, workers in self.concurrency.items():
+            if workers < 1:
+                raise ValueError(f"The {category} jobs need at least one worker")
+        self._handlers: Dict[str, JobHandler] = {}
+        self._categories: Dict[str, str] = {}
+        self._jobs: Dict[str, Job] = {}
+        self._done: Dict[str, threading.Event] = {}
+        self._tokens: Dict[str, CancellationToken] = {}
+        self._waiting: Dict[str, Deque[str]] = {}
+        self._running: Dict[str, int] = {}
+        self._low_priority: Set[str] = set()  # IDs of the pending low priority jobs
+        self._running_low: Dict[str, int] = {}
+        self._paused: Set[str] = set()  # Categories whose jobs do not start
+        self._threads: List[threading.Thread] = []
+        self._versions: Dict[str, int] = {}  # Number of changes of each job, for watchers
+        self._finished_listeners: List[Callable[[Job], None]] = []
+        self._evicted_listeners: List[Callable[[List[Job]], None]] = []
+        self._lock = threading.Lock()
+        self._retrying = threading.Lock()  # Keeps a job from being retried twice at once
+        self._resuming = threading.Lock()  # Keeps a job from being resumed twice at once
+        self._changed = threading.Condition(self._lock)
+        self._stop_sweeping = threading.Event()
+        self._sweeper: Optional[threading.Thread] = None
+
+    def register(self, kind: str, handler: JobHandler, category: Optional[str] = None):
+        """
+        Set the function running the jobs of a kind, it gets the job parameters and handle and returns the result
+
+        Args:
+            category: Category whose workers run the jobs, defaults to the kind
+        """
+        self._handlers[kind] = handler
+        self._categories[kind] = category or kind
+
+    def registered(self, kind: str) -> bool:
+        return kind in self._handlers
+
+    def on_finished(self, listener: Callable[[Job], None]):
+        """Call a listener with the snapshot of every job that finished, e.g. to start the jobs waiting for it"""
+        self._finished_listeners.append(listener)
+
+    def on_evicted(self, listener: Callable[[List[Job]], None]):
+        """Call a listener with the finished jobs dropped from memory, e.g. to drop what is kept about them"""
+        self._evicted_listeners.append(listener)
+
+    def workers(self, category: str) -> int:
+        return self.concurrency.get(category, DEFAULT_WORKERS)
+
+    def runs_low_priority(self, kind: str) -> bool:
+        """Whether low priority jobs of a kind get to run, a category with a single worker keeps it for normal jobs"""
+        return self.workers(self._categories.get(kind, kind)) > 1
+
+    def pause(self, category: str):
+        """Keep the pending jobs of a category from starting until it is resumed"""
+        with self._lock:
+            self._paused.add(category)
+
+    def unpause(self, category: str):
+        """Start the pending jobs of a paused category again"""
+        with self._lock:
+            self._paused.discard(category)
+            self._dispatch(category)
+
+    def paused(self) -> List[str]:
+        with self._lock:
+            return sorted(self._paused)
+
+    def running_jobs(self) -> Dict[str, int]:
+        """Number of jobs running, per category"""
+        with self._lock:
+            return dict(self._running)
+
+    def queue_depths(self) -> Dict[str, int]:
+        """Number of pending jobs waiting for a worker, per category"""
+        with self._lock:
+            return {category: len(waiting) for category, waiting in self._waiting.items()}
+
+    def recover(self) -> List[Job]:
+        """Mark the stored jobs left pending or running by a previous run of the server as interrupted"""
+        if self.database is None:
+            return []
+        return [job_from_info(info) for info in self.database.move_jobs([JOB_PENDING, JOB_RUNNING], JOB_INTERRUPTED, INTERRUPTED_ERROR)]
+
+    def submit(self, kind: str, params: Optional[Dict[str, Any]] = None, priority: str = PRIORITY_NORMAL, group_id: Optional[str] = None) -> Job:
+        """Run a job of a registered kind in the background, in a group if given"""
+        if kind not in self._handlers:
+            raise ValueError(f"No handler registered for {kind} jobs")
+        if priority not in JOB_PRIORITIES:
+            raise ValueError(f"Unsupported job priority: {priority}, expected one of {', '.join(JOB_PRIORITIES)}")
+        job = Job(job_id=str(uuid.uuid4()), kind=kind, status=JOB_PENDING, created_at=datetime.now(timezone.utc), params=params or {}, group_id=group_id)
+        return self._start(job, priority)
+
+    def submit_group(self, jobs: List[Tuple[str, Dict[str, Any]]], priority: str = PRIORITY_NORMAL) -> JobGroup:
+        """Run related jobs, given by kind and parameters, in the background as one group"""
+        if not jobs:
+            raise ValueError("A job group needs at least one job")
+        for kind, _ in jobs:
+            if kind not in self._handlers:
+                raise ValueError(f"No handler registered for {kind} jobs")
+        if priority not in JOB_PRIORITIES:
+            raise ValueError(f"Unsupported job priority: {priority}, expected one of {', '.join(JOB_PRIORITIES)}")
+        group_id = str(uuid.uuid4())
+        submitted = [
+            self._start(Job(job_id=str(uuid.uuid4()), kind=kind, status=JOB_PENDING, created_at=datetime.now(timezone.utc), params=params, group_id=group_id), priority)
+            for kind, params in jobs
+        ]
+        return JobGroup(group_id, submitted)
+
+    def get_group(self, group_id: str) -> Optional[JobGroup]:
+        """Snapshots of the jobs of a group, from the store if they ran before a restart, None if there is no such group"""
+        with self._lock:
+            jobs = [self._snapshot(job) for job in self._jobs.values() if job.group_id == group_id]
+        if not jobs and self.database is not None:
+            jobs = [job_from_info(info) for info in self.database.get_group_jobs(group_id)]
+        if not jobs:
+            return None
+        return JobGroup(group_id, sorted(jobs, key=lambda job: job.created_at))
+
+    def low_priority_jobs(self) -> int:
+        """Number of low priority jobs pending or running"""
+        with self._lock:
+            return len(self._low_priority) + sum(self._running_low.values())
+
+    def resume(self, job_id: str) -> Optional[Job]:
+        """Run a job that failed, was interrupted, cancelled or deferred again, with the same ID and parameters, None if there is no such job"""
+        with self._resuming:
+            job = self.get_job_status(job_id)
+            if job is None:
+                return None
+            if job.status not in RESUMABLE_STATUSES:
+                raise ValueError(f"Only failed, interrupted, cancelled or deferred jobs can be resumed, the job is {job.status}")
+            if job.kind not in self._handlers:
+                raise ValueError(f"No handler registered for {job.kind} jobs")
+            return self._start(replace(job, status=JOB_PENDING, progress=0.0, stage=None, detail=None, result=None, error=None, error_category=None, finished_at=None))
+
+    def retry(self, job_id: str) -> Optional[Job]:
+        """Submit the parameters of a failed or interrupted job as a new job retrying it, None if there is no such job"""
+        with self._retrying:
+            job = self.get_job_status(job_id)
+            if job is None:
+                return None
+            if job.status not in DEAD_LETTER_STATUSES:
+                raise ValueError(f"Only failed or interrupted jobs can be retried, the job is {job.status}")
+            if job.kind not in self._handlers:
+                raise ValueError(f"No handler registered for {job.kind} jobs")
+            retries = self.retries(job_id)
+            if retries:
+                raise ValueError(f"The job was already retried as {retries[-1].job_id}")
+            return self._start(Job(job_id=str(uuid.uuid4()), kind=job.kind, status=JOB_PENDING, created_at=datetime.now(timezone.utc), params=job.params, retry_of=job.job_id))
+
+    def retries(self, job_id: str) -> List[Job]:
+        """The jobs retrying a job, oldest first"""
+        if self.database is not None:
+            return [job_from_info(info) for info in self.database.get_job_retries(job_id)]
+        with self._lock:
+            return sorted((self._snapshot(job) for job in self._jobs.values() if job.retry_of == job_id), key=lambda job: job.created_at)
+
+    def dead_letter(self, kind: Optional[str] = None, limit: int = 50) -> List[Job]:
+        """Failed and interrupted jobs not retried yet, newest first, the stored history included"""
+        if self.database is not None:
+            return [job_from_info(info) for info in self.database.get_dead_letter_jobs(list(DEAD_LETTER_STATUSES), kind, limit)]
+        with self._lock:
+            retried = {job.retry_of for job in self._jobs.values()}
+            jobs = [
+                self._snapshot(job) for job in self._jobs.values()
+                if job.status in DEAD_LETTER_STATUSES and job.job_id not in retried and (kind is None or job.kind == kind)
+            ]
+        return sorted(jobs, key=lambda job: job.created_at, reverse=True)[:limit]
+
+    def tracked(self) -> int:
+        """Number of jobs kept in memory"""
+        with self._lock:
+            return len(self._jobs)
+
+    def sweep(self, now: Optional[datetime] = None) -> int:
+        """Drop the finished jobs past the retention from memory, returns how many were dropped"""
+        cutoff = (now or datetime.now(timezone.utc)) - timedelta(hours=self.retention.retention_hours)
+        with self._lock:
+            evictable = sorted(self._evictable(), key=lambda job: job.finished_at or job.created_at)
+            expired = [job for job in evictable if (job.finished_at or job.created_at) < cutoff]
+            # The oldest of those within the retention go too while more than the cap are left
+            excess = max(0, len(evictable) - len(expired) - self.retention.max_finished_jobs)
+            evicted = expired + evictable[len(expired):len(expired) + excess]
+            self._evict(evicted)
+            self._threads = [thread for thread in self._threads if thread.is_alive()]
+        self._announce_evicted(evicted)
+        return len(evicted)
+
+    def purge(self, older_than: Optional[timedelta] = None, history: bool = False) -> PurgeResult:
+        """
+        Drop finished jobs from memory right away
+
+        Args:
+            older_than: Only the jobs finished longer ago than this, all finished jobs if None
+            history: Delete them from the stored history as well, the dead-letter queue included
+        """
+        cutoff = datetime.now(timezone.utc) - older_than if older_than is not None else None
+        with self._lock:
+            evicted = [job for job in self._evictable() if cutoff is None or (job.finished_at or job.created_at) < cutoff]
+            self._evict(evicted)
+        self._announce_evicted(evicted)
+        deleted = self.database.delete_jobs(list(EVICTABLE_STATUSES), cutoff) if history and self.database is not None else 0
+        return PurgeResult(len(evicted), deleted)
+
+    def start_sweeper(self) -> None:
+        """Sweep on a timer in the background until the pool shuts down"""
+        def run():
+            while not self._stop_sweeping.wait(self.retention.sweep_seconds):
+                try:
+                    self.sweep()
+                except Exception as e:
+                    print(f"Error sweeping finished jobs: {e}")
+
+        self._sweeper = threading.Thread(target=run, name="job-sweeper", daemon=True)
+        self._sweeper.start()
+
+    def _evictable(self) -> List[Job]:
+        # Finished jobs that can be dropped, the caller holds the lock
+        active_groups = {job.group_id for job in self._jobs.values() if job.group_id is not None and job.status not in EVICTABLE_STATUSES}
+        # A cancelled job's thread may still run its handler
+        alive = {thread.name for thread in self._threads if thread.is_alive()}
+        return [
+            job for job in self._jobs.values()
+            if job.status in EVICTABLE_STATUSES and job.group_id not in active_groups and f"job-{job.job_id}" not in alive
+        ]
+
+    def _evict(self, jobs: List[Job]):
+        # The caller holds the lock
+        for job in jobs:
+            self._jobs.pop(job.job_id, None)
+            self._done.pop(job.job_id, None)
+            self._tokens.pop(job.job_id, None)
+            self._versions.pop(job.job_id, None)
+        if jobs:
+            self._changed.notify_all()
+
+    def resume_deferred(self) -> List[Job]:
+        """Run the jobs deferred while a service was down again, e.g. once its circuit breaker closed"""
+        with self._lock:
+            deferred = [job.job_id for job in self._jobs.values() if job.status == JOB_DEFERRED]
+        resumed = []
+        for job_id in deferred:
+            try:
+                resumed.append(self.resume(job_id))
+            except ValueError:
+                # Resumed meanwhile
+                continue
+        return [job for job in resumed if job is not None]
+
+    def _start(self, job: Job, priority: str = PRIORITY_NORMAL) -> Job:
+        category = self._categories[job.kind]
+        with self._lock:
+            self._jobs[job.job_id] = job
+            self._done[job.job_id] = threading.Event()
+            self._tokens[job.job_id] = CancellationToken()
+            self._persist(job)
+            waiting = self._waiting.setdefault(category, deque())
+            if priority == PRIORITY_LOW:
+                self._low_priority.add(job.job_id)
+                waiting.append(job.job_id)
+            else:
+                self._low_priority.discard(job.job_id)
+                # Ahead of the low priority jobs, behind the other normal ones
+                position = next((index for index, job_id in enumerate(waiting) if job_id in self._low_priority), len(waiting))
+                waiting.insert(position, job.job_id)
+                for job_id in list(waiting)[position + 1:]:
+                    self._notify(job_id)
+            self._dispatch(category)
+            return self._snapshot(self._jobs[job.job_id])
+
+    def _dispatch(self, category: str):
+        # Start the queued jobs of a category while it has free workers, the caller holds the lock
+        if category in self._paused:
+            return
+        waiting = self._waiting.get(category)
+        while waiting and self._running.get(category, 0) < self.workers(category):
+            # Low priority jobs are last in the queue, they never take the last worker, so normal jobs submitted while
+            # they run start right away; with a single worker they do not run at all
+            if waiting[0] in self._low_priority and self._running_low.get(category, 0) >= self.workers(category) - 1:
+                break
+            job = self._jobs[waiting.popleft()]
+            low = job.job_id in self._low_priority
+            self._low_priority.discard(job.job_id)
+            self._running[category] = self._running.get(category, 0) + 1
+            if low:
+                self._running_low[category] = self._running_low.get(category, 0) + 1
+            token = self._tokens[job.job_id]
+            handle = JobHandle(job.job_id, token, partial(self._report_progress, job.job_id, token))
+            thread = threading.Thread(target=self._run, args=(handle, category, self._handlers[job.kind], job.params, low), name=f"job-{job.job_id}", daemon=True)
+            self._threads.append(thread)
+            thread.start()
+            # The jobs behind it moved up in the queue
+            for job_id in waiting:
+                self._notify(job_id)
+
+    def _snapshot(self, job: Job) -> Job:
+        # A copy of a job with its place in the queue, the caller holds the lock
+        waiting = self._waiting.get(self._categories.get(job.kind, job.kind), ())
+        position = list(waiting).index(job.job_id) + 1 if job.status == JOB_PENDING and job.job_id in waiting else None
+        return replace(job, queue_position=position)
+
+    def _run(self, handle: JobHandle, category: str, handler: JobHandler, params: Dict[str, Any], low: bool = False):
+        job_id = handle.job_id
+        _current_job_id.set(job_id)
+        try:
+            if not self._update(job_id, handle.cancellation_token, status=JOB_RUNNING, started_at=datetime.now(timezone.utc)):
+                return
+            result = handler(params, handle)
+            self._update(job_id, handle.cancellation_token, status=JOB_SUCCEEDED, result=result, progress=1.0, finished_at=datetime.now(timezone.utc))
+        except JobCancelled:
+            pass
+        except ServiceUnavailable as e:
+            self._update(job_id, handle.cancellation_token, status=JOB_DEFERRED, error=str(e), error_category=e.category, finished_at=datetime.now(timezone.utc))
+        except Exception as e:
+            print(f"Error in job {job_id}: {traceback.format_exc()}")
+            error_category = e.category if isinstance(e, ModelError) else None
+            self._update(job_id, handle.cancellation_token, status=JOB_FAILED, error=str(e), error_category=error_category, finished_at=datetime.now(timezone.utc))
+        finally:
+            # A cancelled job was marked done when it was cancelled, and may be running again since
+            if not handle.cancellation_token.cancelled:
+                self._done[job_id].set()
+            with self._lock:
+                self._running[category] -= 1
+                if low:
+                    self._running_low[category] -= 1
+                self._dispatch(category)
+            # Cancelled jobs were announced when they were cancelled
+            if not handle.cancellation_token.cancelled:
+                self._announce_finished(job_id)
+
+    def _announce_finished(self, job_id: str):
+        # Called without the lock, listeners may submit jobs
+        job = self.get_job_status(job_id)
+        if job is None:
+            return
+        for listener in list(self._finished_listeners):
+            try:
+                listener(job)
+            except Exception:
+                print(f"Error in a listener of job {job_id}: {traceback.format_exc()}")
+
+    def _announce_evicted(self, jobs: List[Job]):
+        # Called without the lock, like the listeners of finished jobs
+        if not jobs:
+            return
+        for listener in list(self._evicted_listeners):
+            try:
+                listener(jobs)
+            except Exception:
+                print(f"Error in a listener of evicted jobs: {traceback.format_exc()}")
+
+    def _update(self, job_id: str, token: Optional[CancellationToken] = None, **changes) -> bool:
+        # Changes reported by the thread of a job that was cancelled meanwhile are dropped
+        with self._lock:
+            if token is not None and token.cancelled:
+                return False
+            self._jobs[job_id] = replace(self._jobs[job_id], **changes)
+            self._persist(self._jobs[job_id])
+            return True
+
+    def _report_progress(self, job_id: str, token: CancellationToken, progress: float, stage: str, detail: Optional[str]):
+        self._update(job_id, token, progress=progress, stage=stage, detail=detail)
+
+    def cancel_job(self, job_id: str) -> Optional[Job]:
+        """Cancel a pending or running job, None if there is no such job"""
+        cancelled: Optional[Job] = None
+        with self._lock:
+            job = self._jobs.get(job_id)
+            if job is not None and not job.finished:
+                self._tokens[job_id].cancel()
+                waiting = self._waiting.get(self._categories[job.kind], deque())
+                if job_id in waiting:
+                    waiting.remove(job_id)
+                    self._low_priority.discard(job_id)
+                self._jobs[job_id] = replace(job, status=JOB_CANCELLED, error=CANCELLED_ERROR, finished_at=datetime.now(timezone.utc))
+                self._persist(self._jobs[job_id])
+                self._done[job_id].set()
+                cancelled = replace(self._jobs[job_id])
+        if cancelled is not None:
+            self._announce_finished(job_id)
+            return cancelled
+        job = self.get_job_status(job_id)
+        if job is None:
+            return None
+        raise ValueError(f"Only pending or running jobs can be cancelled, the job is {job.status}")
+
+    def _persist(self, job: Job):
+        # Every change goes through here with the lock held
+        if self.database is not None:
+            self.database.save_job(job_to_info(job))
+        self._notify(job.job_id)
+
+    def _notify(self, job_id: str):
+        self._versions[job_id] = self._versions.get(job_id, 0) + 1
+        self._changed.notify_all()
+
+    def watch(self, job_id: str, version: int = -1, timeout: Optional[float] = None) -> Tuple[int, Optional[Job]]:
+        """
+        Block until a job changed since a version of it or the timeout passed
+
+        Returns:
+            Tuple[int, Optional[Job]]: The job's current version and snapshot, None if there is no such job; jobs
+            that ran before a restart never change
+        """
+        with self._changed:
+            if job_id in self._jobs:
+                self._changed.wait_for(lambda: job_id not in self._jobs or self._versions.get(job_id, 0) != version, timeout)
+                # Unless the sweeper dropped it meanwhile
+                if job_id in self._jobs:
+                    return self._versions.get(job_id, 0), self._snapshot(self._jobs[job_id])
+        return 0, self.get_job_status(job_id)
+
+    def get_job_status(self, job_id: str) -> Optional[Job]:
+        """A snapshot of a job, from the store if it ran before a restart, None if there is no such job"""
+        with self._lock:
+            job = self._jobs.get(job_id)
+            if job is not None:
+                return self._snapshot(job)
+        info = self.database.get_job(job_id) if self.database is not None else None
+        return job_from_info(info) if info is not None else None
+
+    def list_jobs(self, status: Optional[str] = None, kind: Optional[str] = None, limit: int = 50) -> List[Job]:
+        """Jobs newest first, the stored history included"""
+        with self._lock:
+            if self.database is not None:
+                return [self._snapshot(job_from_info(info)) for info in self.database.get_jobs(status, kind, limit)]
+            jobs = [self._snapshot(job) for job in self._jobs.values() if (status is None or job.status == status) and (kind is None or job.kind == kind)]
+        return sorted(jobs, key=lambda job: job.created_at, reverse=True)[:limit]
+
+    def wait(self, job_id: str, timeout: Optional[float] = None) -> Optional[Job]:
+        """Block until a job finished or the timeout passed, then return its snapshot"""
+        done = self._done.get(job_id)
+        if done is not None:
+            done.wait(timeout)
+        return self.get_job_status(job_id)
+
+    def shutdown(self, timeout: Optional[float] = None):
+        """Stop the sweeper and wait for the running and queued jobs to finish"""
+        self._stop_sweeping.set()
+        if self._sweeper is not None:
+            self._sweeper.join(timeout=5)
+        joined = 0
+        while joined < len(self._threads):
+            self._threads[joined].join(timeout)
+            joined += 1
@@ -0,0 +1,151 @@
+# JSON repair
+# LLM answers are often almost valid JSON: wrapped in a code fence or in a sentence, with a trailing comma before a
+# closing bracket or with unquoted keys. Such answers are repaired before schema validation instead of failing the
+# job. The metrics count per model how many answers were validated, needed a repair (and which fixes) or could not be
+# repaired, since the server started.
+
+import re
+import threading
+from dataclasses import dataclass, field
+from functools import lru_cache
+from typing import Any, Dict, List, Tuple, TypeVar
+
+from pydantic import BaseModel, ValidationError
+
+FIX_CODE_FENCE = "code_fence"
+FIX_SURROUNDING_TEXT = "surrounding_text"
+FIX_TRAILING_COMMA = "trailing_comma"
+FIX_UNQUOTED_KEY = "unquoted_key"
+
+WORD = re.compile(r"[A-Za-z_$][\w$]*")
+
+T = TypeVar("T", bound=BaseModel)
+
+
+@lru_cache(maxsize=None)
+def json_schema(schema: type[BaseModel]) -> Dict[str, Any]:
+    """The JSON schema of a response schema, generated once per schema; callers must not change it"""
+    return schema.model_json_schema()
+
+
+def extract_json(text: str) -> str:
+    """The JSON object in a response, without code fences or text around it"""
+    fenced = re.search(r"```(?:json)?\s*(.*?)```", text, re.DOTALL)
+    if fenced:
+        text = fenced.group(1)
+    start, end = text.find("{"), text.rfind("}")
+    return text[start:end + 1] if start != -1 and end > start else text.strip()
+
+
+def _skip_whitespace(text: str, index: int) -> int:
+    while index < len(text) and text[index].isspace():
+        index += 1
+    return index
+
+
+def _fix_syntax(text: str) -> Tuple[str, List[str]]:
+    # Drops trailing commas and quotes keys, leaving strings alone
+    repaired: List[str] = []
+    fixes: List[str] = []
+    previous = ""  # The last character outside whitespace, a closed string counts as its quote
+    index = 0
+    while index < len(text):
+        char = text[index]
+        if char == '"':
+            end = index + 1
+            while end < len(text) and text[end] != '"':
+                end += 2 if text[end] == "\\" else 1
+            repaired.append(text[index:end + 1])
+            previous = '"'
+            index = end + 1
+            continue
+        if char == ",":
+            following = _skip_whitespace(text, index + 1)
+            if following < len(text) and text[following] in "}]":
+                fixes.append(FIX_TRAILING_COMMA)
+                index += 1
+                continue
+        word = WORD.match(text, index)
+        if word:
+            after = _skip_whitespace(text, word.end())
+            if previous in ("{", ",") and after < len(text) and text[after] == ":":
+                repaired.append(f'"{word.group()}"')
+                fixes.append(FIX_UNQUOTED_KEY)
+            else:
+                repaired.append(word.group())
+            previous = word.group()[-1]
+            index = word.end()
+            continue
+        repaired.append(char)
+        if not char.isspace():
+            previous = char
+        index += 1
+    return "".join(repaired), fixes
+
+
+def repair_json(text: str) -> Tuple[str, List[str]]:
+    """
+    Repair near-valid JSON from an LLM
+
+    Returns:
+        Tuple[str, List[str]]: The repaired text and the fixes applied, in order and without duplicates
+    """
+    fixes = []
+    if re.search(r"```(?:json)?\s*(.*?)```", text, re.DOTALL):
+        fixes.append(FIX_CODE_FENCE)
+    extracted = extract_json(text)
+    if FIX_CODE_FENCE not in fixes and extracted != text.strip():
+        fixes.append(FIX_SURROUNDING_TEXT)
+    repaired, syntax_fixes = _fix_syntax(extracted)
+    return repaired, list(dict.fromkeys(fixes + syntax_fixes))
+
+
+@dataclass
+class ModelRepairStats:
+    responses: int = 0  # Answers validated against a schema
+    repaired: int = 0  # Answers valid only after a repair
+    failed: int = 0  # Answers invalid even after a repair
+    fixes: Dict[str, int] = field(default_factory=dict)  # Answers per fix applied to make them valid
+
+
+class RepairMetrics:
+    """How often answers needed a repair, per model"""
+
+    def __init__(self):
+        self._lock = threading.Lock()
+        self._models: Dict[str, ModelRepairStats] = {}
+
+    def record(self, model_name: str, fixes: List[str], valid: bool) -> None:
+        with self._lock:
+            stats = self._models.setdefault(model_name, ModelRepairStats())
+            stats.responses += 1
+            if not valid:
+                stats.failed += 1
+            elif fixes:
+                stats.repaired += 1
+                for fix in fixes:
+                    stats.fixes[fix] = stats.fixes.get(fix, 0) + 1
+
+    def snapshot(self) -> Dict[str, ModelRepairStats]:
+        with self._lock:
+            return {model_name: ModelRepairStats(stats.responses, stats.repaired, stats.failed, dict(stats.fixes)) for model_name, stats in self._models.items()}
+
+
+repair_metrics = RepairMetrics()
+
+
+def validate_with_repair(text: str, schema: type[T], model_name: str, metrics: RepairMetrics = repair_metrics) -> T:
+    """Validate an answer against a schema, repairing it first when it is not valid as is, raises the original ValidationError if the repair does not help"""
+    try:
+        result = schema.model_validate_json(text)
+    except ValidationError as error:
+        repaired, fixes = repair_json(text)
+        try:
+            result = schema.model_validate_json(repaired)
+        except ValidationError:
+            metrics.record(model_name, fixes, valid=False)
+            raise error
+        metrics.record(model_name, fixes, valid=True)
+        return result
+    metrics.record(model_name, [], valid=True)
+    return result
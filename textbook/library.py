@@ -0,0 +1,194 @@
+# Document library
+# A durable catalog of everything ingested: books and problem sets uploaded as PDFs or images, web articles,
+# transcripts and notebooks, as well as PDFs uploaded only to be OCRed. Each document keeps its title, author, source,
+# page count, OCR status, the type detected from its content and free-form tags. Books get their document the first
+# time the library is listed, uploads when they are submitted for OCR, and the OCR job keeps the status up to date.
+# Renaming a document renames its book, deleting it deletes the book with its files.
+
+import re
+from typing import List, Optional
+
+from textbook.compression import COMPRESSED_SUFFIX
+from textbook.database import BookInfo, DocumentInfo, TextBookDatabase
+from textbook.mineru import images_dir_name
+from textbook.reader import BOOK_SOURCE_NOTEBOOK, BOOK_SOURCE_TRANSCRIPT, BOOK_SOURCE_WEB_ARTICLE
+
+OCR_PENDING = "pending"
+OCR_RUNNING = "running"
+OCR_DONE = "done"
+OCR_FAILED = "failed"
+OCR_NOT_NEEDED = "not_needed"  # Documents that came as text
+OCR_STATUSES = (OCR_PENDING, OCR_RUNNING, OCR_DONE, OCR_FAILED, OCR_NOT_NEEDED)
+
+DOCUMENT_TYPE_TEXTBOOK = "textbook"
+DOCUMENT_TYPE_PROBLEM_SET = "problem_set"
+DOCUMENT_TYPE_ARTICLE = "article"
+DOCUMENT_TYPE_TRANSCRIPT = "transcript"
+DOCUMENT_TYPE_NOTEBOOK = "notebook"
+DOCUMENT_TYPE_UNKNOWN = "unknown"  # Uploads before their OCR finished
+DOCUMENT_TYPES = (DOCUMENT_TYPE_TEXTBOOK, DOCUMENT_TYPE_PROBLEM_SET, DOCUMENT_TYPE_ARTICLE, DOCUMENT_TYPE_TRANSCRIPT, DOCUMENT_TYPE_NOTEBOOK, DOCUMENT_TYPE_UNKNOWN)
+
+SOURCE_DOCUMENT_TYPES = {
+    BOOK_SOURCE_WEB_ARTICLE: DOCUMENT_TYPE_ARTICLE,
+    BOOK_SOURCE_TRANSCRIPT: DOCUMENT_TYPE_TRANSCRIPT,
+    BOOK_SOURCE_NOTEBOOK: DOCUMENT_TYPE_NOTEBOOK,
+}
+
+# A text is a problem set when it numbers at least this many problems with little text around each of them
+PROBLEM_SET_MIN_PROBLEMS = 3
+PROBLEM_SET_MAX_CHARS_PER_PROBLEM = 2000
+# A book is a problem set when it has at least this many exercises per page
+PROBLEM_SET_EXERCISES_PER_PAGE = 1.0
+
+PROBLEM_HEADING = re.compile(r"^\s*(?:#+\s*)?(?:\*\*)?(?:problem|exercise|question|aufgabe)\s+\d+", re.IGNORECASE | re.MULTILINE)
+
+
+def detect_type(text: str) -> str:
+    """The type of an OCRed document from its markdown, a problem set or a textbook"""
+    if not text.strip():
+        return DOCUMENT_TYPE_UNKNOWN
+    problems = len(PROBLEM_HEADING.findall(text))
+    if problems >= PROBLEM_SET_MIN_PROBLEMS and len(text) / problems <= PROBLEM_SET_MAX_CHARS_PER_PROBLEM:
+        return DOCUMENT_TYPE_PROBLEM_SET
+    return DOCUMENT_TYPE_TEXTBOOK
+
+
+def book_type(book: BookInfo, exercise_count: int) -> str:
+    """The type of an ingested book from its source, or from how many exercises it has per page"""
+    if book.book_source_type in SOURCE_DOCUMENT_TYPES:
+        return SOURCE_DOCUMENT_TYPES[book.book_source_type]
+    if book.book_pages and exercise_count >= book.book_pages * PROBLEM_SET_EXERCISES_PER_PAGE:
+        return DOCUMENT_TYPE_PROBLEM_SET
+    return DOCUMENT_TYPE_TEXTBOOK
+
+
+def parse_tags(tags: List[str]) -> List[str]:
+    """Deduplicated lowercase tags, words within a tag joined by dashes"""
+    tags = ["-".join(tag.lower().split()) for tag in tags]
+    return list(dict.fromkeys(tag for tag in tags if tag))
+
+
+def document_tags(document: DocumentInfo) -> List[str]:
+    return document.tags.split()
+
+
+def add_upload(database: TextBookDatabase, title: str, source_path: str, page_count: Optional[int] = None) -> DocumentInfo:
+    """Add a PDF uploaded for OCR, its type is detected once the OCR is done"""
+    return database.create_document(DocumentInfo(
+        title=title,
+        source_path=source_path,
+        page_count=page_count,
+        ocr_status=OCR_PENDING,
+        detected_type=DOCUMENT_TYPE_UNKNOWN,
+        tags="",
+    ))
+
+
+def sync_books(database: TextBookDatabase) -> List[DocumentInfo]:
+    """Add the books that are not in the library yet, returns the documents added"""
+    cataloged = {document.book_id for document in database.get_documents() if document.book_id is not None}
+    added = []
+    for book in database.get_all_books():
+        if book.book_id in cataloged:
+            continue
+        added.append(database.create_document(DocumentInfo(
+            title=book.book_name or book.book_file_name or f"Book {book.book_id}",
+            author=book.book_author,
+            source_path=book.book_source_url or (f"{book.book_file_name}.pdf" if book.book_file_name else None),
+            page_count=book.book_pages,
+            ocr_status=OCR_NOT_NEEDED if book.book_source_type in SOURCE_DOCUMENT_TYPES else OCR_DONE,
+            detected_type=book_type(book, len(database.get_exercises_by_book_id(book.book_id))),
+            tags="",
+            book_id=book.book_id,
+        )))
+    return added
+
+
+def list_documents(
+    database: TextBookDatabase,
+    tag: Optional[str] = None,
+    detected_type: Optional[str] = None,
+    ocr_status: Optional[str] = None,
+) -> List[DocumentInfo]:
+    """The documents of the library, optionally only those with a tag, type or OCR status"""
+    if detected_type is not None and detected_type not in DOCUMENT_TYPES:
+        raise ValueError(f"Unknown document type: {detected_type}, expected one of {', '.join(DOCUMENT_TYPES)}")
+    if ocr_status is not None and ocr_status not in OCR_STATUSES:
+        raise ValueError(f"Unknown OCR status: {ocr_status}, expected one of {', '.join(OCR_STATUSES)}")
+    sync_books(database)
+    documents = database.get_documents()
+    if tag is not None:
+        wanted = parse_tags([tag])
+        documents = [document for document in documents if set(wanted) <= set(document_tags(document))]
+    if detected_type is not None:
+        documents = [document for document in documents if document.detected_type == detected_type]
+    if ocr_status is not None:
+        documents = [document for document in documents if document.ocr_status == ocr_status]
+    return documents
+
+
+def rename_document(database: TextBookDatabase, document_id: int, title: str, author: Optional[str] = None) -> Optional[DocumentInfo]:
+    """Rename a document and its book, None keeps the author"""
+    if not title.strip():
+        raise ValueError("A document needs a title")
+    changes = {"title": title.strip()}
+    if author is not None:
+        changes["author"] = author.strip() or None
+    document = database.update_document(document_id, **changes)
+    if document is not None and document.book_id is not None:
+        with database.new_session() as session:
+            book_changes = {BookInfo.book_name: document.title}
+            if author is not None:
+                book_changes[BookInfo.book_author] = document.author
+            session.query(BookInfo).filter(BookInfo.book_id == document.book_id).update(book_changes)
+            session.commit()
+    return document
+
+
+def tag_document(database: TextBookDatabase, document_id: int, add: Optional[List[str]] = None, remove: Optional[List[str]] = None) -> Optional[DocumentInfo]:
+    """Add and remove tags of a document, None if there is no such document"""
+    document = database.get_document(document_id)
+    if document is None:
+        return None
+    removed = set(parse_tags(remove or []))
+    tags = [tag for tag in parse_tags(document_tags(document) + parse_tags(add or [])) if tag not in removed]
+    return database.update_document(document_id, tags=" ".join(tags))
+
+
+def set_ocr_status(database: TextBookDatabase, document_id: int, ocr_status: str, detected_type: Optional[str] = None) -> Optional[DocumentInfo]:
+    """Record the progress of a document's OCR, with its detected type once the text is known"""
+    changes = {"ocr_status": ocr_status}
+    if detected_type is not None:
+        changes["detected_type"] = detected_type
+    return database.update_document(document_id, **changes)
+
+
+def delete_document(database: TextBookDatabase, document_id: int) -> Optional[List[str]]:
+    """
+    Delete a document and its book
+
+    Returns:
+        Optional[List[str]]: The files of the document, relative to the uploads directory, for the caller to remove,
+            None if there is no such document
+    """
+    document = database.get_document(document_id)
+    if document is None:
+        return None
+    file_names = []
+    if document.book_id is not None:
+        with database.new_session() as session:
+            book = session.query(BookInfo).filter(BookInfo.book_id == document.book_id).first()
+            if book is not None:
+                file_names.extend(artifact.artifact_path for artifact in book.artifacts)
+                if book.book_file_name:
+                    file_names.append(f"{book.book_file_name}.pdf")
+                # The document goes with its book
+                session.delete(book)
+                session.commit()
+    elif document.source_path:
+        markdown_name = re.sub(r"\.pdf$", ".md", document.source_path)
+        file_names.extend([document.source_path, markdown_name, markdown_name + COMPRESSED_SUFFIX])
+        if document.source_path.endswith(".pdf"):
+            file_names.append(images_dir_name(document.source_path))
+    database.delete_document(document_id)
+    return list(dict.fromkeys(file_names))
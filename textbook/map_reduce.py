@@ -0,0 +1,122 @@
+# Map-reduce over long documents
+# A document too long for one prompt is split into chunks, each chunk is mapped on its own (usually one prompt per
+# chunk) and the mapped results are reduced into one, by another prompt or locally. Summaries, glossaries and entity
+# extraction all work this way, the helper here runs the steps for them inside one job:
+# - chunks are mapped by a few workers at a time, each one reported as progress on the job
+# - a chunk that fails is recorded and skipped, so one refused prompt does not lose a whole book; when more than
+#   max_failure_ratio of the chunks fail the result would be too partial and the job fails instead, with the error of
+#   the chunks when they all failed alike (so a wrong API key still shows as an auth error)
+# - the reduce step gets the mapped results in chunk order
+
+import contextvars
+from concurrent.futures import ThreadPoolExecutor
+from dataclasses import dataclass, field
+from typing import Callable, Generic, List, Optional, Sequence, TypeVar
+
+from textbook.jobs import CancellationToken, JobCancelled
+from textbook.provider_errors import ModelError
+
+C = TypeVar("C")
+M = TypeVar("M")
+R = TypeVar("R")
+
+DEFAULT_MAX_FAILURE_RATIO = 0.25
+DEFAULT_MAP_WORKERS = 2
+MAP_PROGRESS_SHARE = 90  # Percent of the progress the map step takes, the reduce step the rest
+
+
+class MapReduceError(Exception):
+    """Raised when too many chunks failed to map"""
+
+    def __init__(self, message: str, failures: List["ChunkFailure"]):
+        super().__init__(message)
+        self.failures = failures
+
+
+@dataclass
+class ChunkFailure:
+    index: int  # Position of the chunk
+    error: str
+    error_category: Optional[str] = None  # Category of a ModelError, e.g. content_filter
+
+
+@dataclass
+class MapReduceResult(Generic[R]):
+    result: R
+    chunks: int
+    failures: List[ChunkFailure] = field(default_factory=list)
+
+    def summary(self) -> dict:
+        """The counts and failures, for a job result"""
+        return {
+            "chunks": self.chunks,
+            "failed_chunks": [{"index": failure.index, "error": failure.error, "error_category": failure.error_category} for failure in self.failures],
+        }
+
+
+def map_reduce(
+    chunks: Sequence[C],
+    map_chunk: Callable[[C], M],
+    reduce: Callable[[List[M]], R],
+    max_failure_ratio: float = DEFAULT_MAX_FAILURE_RATIO,
+    workers: int = DEFAULT_MAP_WORKERS,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+) -> MapReduceResult[R]:
+    """
+    Map every chunk, skipping those that fail, and reduce the results
+
+    Args:
+        max_failure_ratio: Share of the chunks allowed to fail, 0 fails on the first failed chunk
+        workers: Chunks mapped at the same time
+        report_progress: Gets the stage, percentage and detail, like JobHandle.report_progress
+
+    Raises:
+        MapReduceError: If more than max_failure_ratio of the chunks failed, or the error of the chunks when all of them failed alike
+    """
+    if not chunks:
+        raise ValueError("Nothing to map, there are no chunks")
+    if not 0 <= max_failure_ratio <= 1:
+        raise ValueError("max_failure_ratio must be between 0 and 1")
+
+    mapped: List[Optional[M]] = [None] * len(chunks)
+    errors: List[Optional[Exception]] = [None] * len(chunks)
+    done = 0
+
+    def run(index: int):
+        if cancellation_token is not None and cancellation_token.cancelled:
+            return
+        try:
+            mapped[index] = map_chunk(chunks[index])
+        except JobCancelled:
+            raise
+        except Exception as e:
+            errors[index] = e
+
+    if report_progress is not None:
+        report_progress("map", 0, f"0/{len(chunks)} chunks mapped")
+    with ThreadPoolExecutor(max_workers=max(1, workers)) as executor:
+        # Workers run in a copy of the job's context, so their prompts are still logged for the job
+        for future in [executor.submit(contextvars.copy_context().run, run, index) for index in range(len(chunks))]:
+            future.result()
+            done += 1
+            if report_progress is not None:
+                failed = sum(error is not None for error in errors)
+                report_progress("map", MAP_PROGRESS_SHARE * done / len(chunks), f"{done}/{len(chunks)} chunks mapped, {failed} failed")
+    if cancellation_token is not None:
+        cancellation_token.raise_if_cancelled()
+
+    failures = [
+        ChunkFailure(index, str(error), error.category if isinstance(error, ModelError) else None)
+        for index, error in enumerate(errors) if error is not None
+    ]
+    if len(failures) > max_failure_ratio * len(chunks):
+        failed = [error for error in errors if error is not None]
+        if len(failed) == len(chunks) and len({(type(error), str(error)) for error in failed}) == 1:
+            raise failed[0]
+        raise MapReduceError(f"{len(failures)} of {len(chunks)} chunks failed, first: {failures[0].error}", failures)
+
+    if report_progress is not None:
+        report_progress("reduce", MAP_PROGRESS_SHARE, f"Reducing {len(chunks) - len(failures)} chunks")
+    result = reduce([result for result, error in zip(mapped, errors) if error is None])
+    return MapReduceResult(result, len(chunks), failures)
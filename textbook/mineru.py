@@ -1,9 +1,34 @@
 # This class is used to wrap the request to call the MinerU API
-from typing import List, Dict, Any
+# MinerU writes what it parses to an output directory on its side. Every request gets its own directory there, named
+# after the OCR job when there is one, so concurrent jobs never overwrite each other's files. The images it extracts
+# come back in the response and are stored in the uploads directory next to the document, with the markdown linking
+# to them there. MinerU's API has no endpoint to delete its outputs: they are removed after the request when the
+# output directory is shared with this server (MinerU on the same machine or a shared volume), and otherwise left to
+# MinerU's host. Requests go through MinerU's circuit breaker, so once MinerU is down OCR jobs are deferred right
+# away instead of each waiting for the timeout. ping() asks MinerU's health endpoint whether it is up, for GET /health.
+# MinerUSettings bound every request (set in the [ocr] section of config.toml, see ocr): connecting and answering
+# have their own timeouts, a timed out request is sent again up to timeout_retries times before the job fails with a
+# timeout, and files above max_upload_mb are refused before anything is uploaded.
+# With zip_response, MinerU answers with a zip archive instead of JSON: per file, the markdown, content_list.json and
+# the figures under images/, raw instead of base64 encoded in the JSON, so the answer is about a quarter smaller. The
+# archive is read in memory into the same results as a JSON answer, figures included, so callers do not tell them
+# apart; it does not lower the memory an OCR job needs, which still holds every figure of the document.
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import List, Dict, Any, Optional, Tuple
+import base64
+import io
+import json
+import re
+import shutil
+import uuid
+import zipfile
 import requests
 import os
+
+from textbook.circuit_breaker import SERVICE_MINERU, circuit_breakers
+from textbook.provider_errors import ERROR_TIMEOUT, ModelError
 FIXED_PARAMS = {
-    "output_dir": "./output",
     "lang_list": ["en"],
     "backend": "pipeline",
     "parse_method": "auto",
@@ -20,11 +45,68 @@ FIXED_PARAMS = {
 }
 
 API_BASE_URL = os.getenv("MINERU_API_URL", "http://localhost:8000")
+HEALTH_PATH = os.getenv("MINERU_HEALTH_PATH", "/health")
+PING_TIMEOUT_SECONDS = 3
+OUTPUT_ROOT = os.getenv("MINERU_OUTPUT_DIR", "./output")  # On the MinerU side, holds a directory per request
+IMAGES_DIR_SUFFIX = "_images"  # Of the directory the images of an upload are stored in
+MAX_ZIP_BYTES = 2 * 1024 * 1024 * 1024  # Unpacked, an archive beyond this is refused
+MARKDOWN_IMAGE_LINK = re.compile(r"\]\(images/")
+
+
+@dataclass
+class MinerUSettings:
+    connect_timeout_seconds: float = 10.0
+    request_timeout_seconds: float = 300.0  # Waiting for the answer, large files take minutes
+    max_upload_mb: float = 200.0  # Per file
+    timeout_retries: int = 1  # Times a timed out request is sent again
+    zip_response: bool = False  # Ask for a zip archive instead of JSON
+
+
+def ping(timeout: float = PING_TIMEOUT_SECONDS) -> Optional[str]:
+    """Why MinerU cannot be reached, None when it answers its health endpoint"""
+    url = f"{API_BASE_URL.rstrip('/')}{HEALTH_PATH}"
+    try:
+        response = requests.get(url, timeout=timeout)
+    except requests.exceptions.Timeout:
+        return f"MinerU did not answer {url} within {timeout} seconds"
+    except requests.exceptions.RequestException as e:
+        return f"MinerU is unreachable at {url}: {e}"
+    # Versions without a health endpoint answer 404, they are up too
+    if response.status_code >= 500:
+        return f"MinerU answered {url} with HTTP {response.status_code}"
+    return None
+
+
+def new_output_dir(name: Optional[str] = None) -> str:
+    """A directory of its own for a request under the output root, named after a job or random"""
+    return f"{OUTPUT_ROOT.rstrip('/')}/{name or uuid.uuid4().hex}"
+
+
+def cleanup_output_dir(output_dir: str) -> bool:
+    """Remove a request's outputs when this server can reach them, returns whether they were removed"""
+    root = os.path.abspath(OUTPUT_ROOT)
+    path = os.path.abspath(output_dir)
+    # Never anything outside the output root, nor the root itself
+    if os.path.commonpath([root, path]) != root or path == root or not os.path.isdir(path):
+        return False
+    shutil.rmtree(path, ignore_errors=True)
+    return True
+
+
+@dataclass
+class MinerUResult:
+    markdown: str
+    content_list: List[Dict[str, Any]] = field(default_factory=list)  # Blocks in reading order with their page_idx
+    images: Dict[str, bytes] = field(default_factory=dict)  # Extracted images by file name, linked as images/<name>
+
 
 class MinerURequest:
-    def __init__(self, files: List[str]):
+    def __init__(self, files: List[str], output_dir: Optional[str] = None, settings: Optional[MinerUSettings] = None):
         self.files = files
+        self.settings = settings or MinerUSettings()
         self.params = FIXED_PARAMS.copy()
+        self.params["output_dir"] = output_dir or new_output_dir()
+        self.params["response_format_zip"] = self.settings.zip_response
 
     def set_output_dir(self, output_dir: str):
         self.params["output_dir"] = output_dir
@@ -68,56 +150,295 @@ class MinerURequest:
     def set_end_page_id(self, end_page_id: int):
         self.params["end_page_id"] = end_page_id
 
+    def check_upload_sizes(self):
+        """Raise ValueError for a file above the upload limit, before anything is sent"""
+        limit = self.settings.max_upload_mb
+        for file_path in self.files:
+            if not os.path.exists(file_path):
+                raise FileNotFoundError(f"File not found: {file_path}")
+            size_mb = os.path.getsize(file_path) / (1024 * 1024)
+            if size_mb > limit:
+                raise ValueError(
+                    f"{os.path.basename(file_path)} is {size_mb:.1f} MB, above the {limit:g} MB uploaded to MinerU: "
+                    "split the document or raise mineru_max_upload_mb in the [ocr] section of config.toml"
+                )
+
     def request(self) -> Dict[str, Any]:
         """
         Send the request to the MinerU API and return the results dictionary.
-        
+
+        A request MinerU does not answer in time is sent again, up to timeout_retries times.
+
         Returns:
             Dict[str, Any]: The JSON response from the API containing the parsing results
+
+        Raises:
+            ValueError: A file is above the upload limit
+            ModelError: MinerU timed out on every attempt, with the timeout category
         """
         # Construct the full endpoint URL
         endpoint = f"{API_BASE_URL}/file_parse"
-        
+        breaker = circuit_breakers.get(SERVICE_MINERU)
+        self.check_upload_sizes()
+        attempts = self.settings.timeout_retries + 1
+        for _ in range(attempts):
+            try:
+                return self._send(endpoint, breaker)
+            except requests.exceptions.Timeout as e:
+                error = e
+        raise ModelError(
+            ERROR_TIMEOUT,
+            f"MinerU did not answer {endpoint} in time ({self.settings.connect_timeout_seconds:g} seconds to connect, "
+            f"{self.settings.request_timeout_seconds:g} to answer) on {attempts} attempts: {error}",
+            SERVICE_MINERU,
+        )
+
+    def _send(self, endpoint: str, breaker) -> Dict[str, Any]:
         # Prepare files for multipart/form-data
         files_to_upload = []
         file_handles = []
-        
+
         try:
             for file_path in self.files:
-                if not os.path.exists(file_path):
-                    raise FileNotFoundError(f"File not found: {file_path}")
                 # Open file in binary mode and use the filename
                 file_obj = open(file_path, 'rb')
                 file_handles.append(file_obj)
                 files_to_upload.append(('files', (os.path.basename(file_path), file_obj, 'application/pdf')))
-            
+
             # Prepare form data with all parameters
             data = {}
             for key, value in self.params.items():
                 # FastAPI handles lists in multipart/form-data by accepting them as-is
                 # The requests library will properly format them
                 data[key] = value
-            
-            # Make the POST request
-            response = requests.post(
-                endpoint,
-                files=files_to_upload,
-                data=data,
-                timeout=300  # 5 minute timeout for large files
-            )
-            
-            # Raise an exception for bad status codes
-            response.raise_for_status()
-            
-            # Return the JSON response as a dictionary
-            return response.json().get("results",{})
-            
-        except requests.exceptions.RequestException as e:
-            raise Exception(f"Failed to send request to MinerU API: {str(e)}")
+
+            # Make the POST request, every call let through records its outcome, or a probe would never end
+            breaker.check()
+            try:
+                response = requests.post(
+                    endpoint,
+                    files=files_to_upload,
+                    data=data,
+                    timeout=(self.settings.connect_timeout_seconds, self.settings.request_timeout_seconds),
+                )
+
+                # Raise an exception for bad status codes
+                response.raise_for_status()
+
+                # Return the JSON response as a dictionary, or the archive read into one
+                if self.params["response_format_zip"]:
+                    results = read_zip_results(response.content)
+                else:
+                    results = response.json().get("results",{})
+            except requests.exceptions.Timeout:
+                breaker.record_failure()
+                raise  # Retried by request()
+            except requests.exceptions.RequestException as e:
+                # MinerU rejecting a request (4xx) is up, no answer or a server error counts against its breaker
+                status = e.response.status_code if e.response is not None else None
+                if status is None or status >= 500:
+                    breaker.record_failure()
+                else:
+                    breaker.record_success()
+                raise Exception(f"Failed to send request to MinerU API at {endpoint}: {str(e)}")
+            except BaseException:
+                # An answer that cannot be read is no better than none
+                breaker.record_failure()
+                raise
+            breaker.record_success()
+            return results
+
         finally:
             # Close all file handles
             for file_handle in file_handles:
                 try:
                     file_handle.close()
                 except Exception:
-                    pass  # Ignore errors when closing
\ No newline at end of file
+                    pass  # Ignore errors when closing
+
+def read_zip_results(data: bytes) -> Dict[str, Dict[str, Any]]:
+    """
+    Read the archive MinerU answers with when response_format_zip is set, as the results of a JSON answer
+
+    Every file parsed has a directory in the archive, possibly with a directory of the parse method in it, holding its
+    markdown (<name>.md), its content list (<name>_content_list.json) and its figures under images/.
+
+    Returns:
+        Dict[str, Dict[str, Any]]: By file name, its md_content, content_list and images, the images as bytes
+
+    Raises:
+        ValueError: If the archive is invalid or unpacks to more than MAX_ZIP_BYTES
+    """
+    try:
+        archive = zipfile.ZipFile(io.BytesIO(data))
+    except zipfile.BadZipFile as e:
+        raise ValueError(f"MinerU answered with an invalid zip archive: {e}")
+    results: Dict[str, Dict[str, Any]] = {}
+    with archive:
+        members = [member for member in archive.infolist() if not member.is_dir()]
+        if sum(member.file_size for member in members) > MAX_ZIP_BYTES:
+            raise ValueError(f"MinerU's zip archive unpacks to more than {MAX_ZIP_BYTES // (1024 * 1024)} MB")
+        for member in members:
+            parts = Path(member.filename).parts
+            if len(parts) < 2:
+                continue
+            result = results.setdefault(parts[0], {"md_content": "", "content_list": [], "images": {}})
+            name = parts[-1]
+            if "images" in parts[1:-1]:
+                # Only the file name, a path must not leave the directory the images are stored in
+                if name in result["images"]:
+                    raise ValueError(f"MinerU's zip archive holds two images named {name} for {parts[0]}")
+                result["images"][name] = archive.read(member)
+            elif name.endswith("_content_list.json"):
+                result["content_list"] = json.loads(archive.read(member).decode("utf-8"))
+            elif name.endswith(".md"):
+                result["md_content"] = archive.read(member).decode("utf-8")
+    return results
+
+
+def ocr_pdf(pdf_path: str, settings: Optional[MinerUSettings] = None) -> str:
+    """
+    OCR a whole PDF with MinerU
+
+    Returns:
+        str: The markdown of the document, empty if MinerU returned none
+    """
+    request = MinerURequest(files=[pdf_path], settings=settings)
+    request.set_return_md(True)
+    results = request.request()
+    for file_result in results.values():
+        if isinstance(file_result, dict) and 'md_content' in file_result:
+            return file_result['md_content']
+    return ""
+
+def ocr_image(image_path: str, settings: Optional[MinerUSettings] = None) -> str:
+    """
+    OCR a single image with MinerU, e.g. a page without a text layer
+
+    Returns:
+        str: The markdown of the image, empty if MinerU returned none
+    """
+    request = MinerURequest(files=[image_path], settings=settings)
+    request.set_return_md(True)
+    request.set_start_page_id(0)
+    request.set_end_page_id(0)
+    results = request.request()
+    for file_result in results.values():
+        if isinstance(file_result, dict) and 'md_content' in file_result:
+            return file_result['md_content']
+    for file_result in results.values():
+        if isinstance(file_result, dict):
+            for key in ['content', 'text', 'markdown']:
+                if key in file_result:
+                    return str(file_result[key])
+    return ""
+
+
+def _decode_image(data) -> bytes:
+    # Images come base64 encoded, as data URLs in recent MinerU versions, and raw from a zip archive
+    if isinstance(data, bytes):
+        return data
+    return base64.b64decode(data.split(",", 1)[1] if data.startswith("data:") else data)
+
+
+def ocr_pdf_artifacts(
+    pdf_path: str,
+    lang_list: Optional[List[str]] = None,
+    parse_method: Optional[str] = None,
+    output_dir: Optional[str] = None,
+    return_images: bool = True,
+    settings: Optional[MinerUSettings] = None,
+    start_page_id: Optional[int] = None,
+    end_page_id: Optional[int] = None,
+) -> MinerUResult:
+    """
+    OCR a whole PDF with MinerU, keeping the page of every block and the images extracted
+
+    Args:
+        lang_list: The languages of the document, English by default
+        parse_method: "auto", "txt" or "ocr", auto by default
+        output_dir: Where MinerU writes its outputs, a new directory under the output root by default, removed
+            after the request when this server can reach it
+        settings: The timeouts and upload limit of the request, the defaults of MinerUSettings by default
+        start_page_id: The first page to parse, counted from 0, the first page of the document by default
+        end_page_id: The last page to parse, included, the last page of the document by default
+
+    Returns:
+        MinerUResult: The markdown of the document, its content list and images, empty if MinerU returned none
+    """
+    request = MinerURequest(files=[pdf_path], output_dir=output_dir, settings=settings)
+    request.set_return_md(True)
+    request.set_return_content_list(True)
+    request.set_return_images(return_images)
+    if lang_list:
+        request.set_lang_list(lang_list)
+    if parse_method:
+        request.set_parse_method(parse_method)
+    if start_page_id is not None:
+        request.set_start_page_id(start_page_id)
+    if end_page_id is not None:
+        request.set_end_page_id(end_page_id)
+    try:
+        results = request.request()
+    finally:
+        cleanup_output_dir(request.params["output_dir"])
+    for file_result in results.values():
+        if isinstance(file_result, dict) and 'md_content' in file_result:
+            content_list = file_result.get('content_list') or []
+            # The content list comes as a JSON string
+            if isinstance(content_list, str):
+                content_list = json.loads(content_list)
+            images = {name: _decode_image(data) for name, data in (file_result.get('images') or {}).items()}
+            return MinerUResult(file_result['md_content'], content_list, images)
+    return MinerUResult("")
+
+
+def ocr_pdf_content(pdf_path: str, lang_list: Optional[List[str]] = None, parse_method: Optional[str] = None) -> Tuple[str, List[Dict[str, Any]]]:
+    """
+    OCR a whole PDF with MinerU, keeping the page of every block
+
+    Args:
+        lang_list: The languages of the document, English by default
+        parse_method: "auto", "txt" or "ocr", auto by default
+
+    Returns:
+        Tuple[str, List[Dict[str, Any]]]: The markdown of the document and its content list, the blocks (text,
+            equations, tables, images) in reading order with their type and page_idx, empty if MinerU returned none
+    """
+    result = ocr_pdf_artifacts(pdf_path, lang_list, parse_method, return_images=False)
+    return result.markdown, result.content_list
+
+
+def images_dir_name(pdf_file_name: str) -> str:
+    """The directory the images of an upload are stored in, relative to the uploads directory"""
+    return f"{Path(pdf_file_name).stem}{IMAGES_DIR_SUFFIX}"
+
+
+def store_images(uploads_dir: str, pdf_file_name: str, result: MinerUResult) -> str:
+    """
+    Write the images MinerU extracted from an upload next to it, replacing those of an earlier OCR
+
+    Returns:
+        str: The markdown, its image links pointing to the stored images
+
+    Raises:
+        ValueError: If an image has no file name, or two images have the same one, before anything is replaced
+    """
+    # Only the file name, MinerU's names are digests but a path must not leave the directory
+    file_names: Dict[str, str] = {}
+    for name in result.images:
+        file_name = Path(name).name
+        if file_name in ("", ".", ".."):
+            raise ValueError(f"MinerU returned an image without a file name: {name!r}")
+        if file_name in file_names:
+            raise ValueError(f"MinerU returned two images named {file_name}: {file_names[file_name]!r} and {name!r}")
+        file_names[file_name] = name
+    directory = Path(uploads_dir) / images_dir_name(pdf_file_name)
+    if directory.exists():
+        shutil.rmtree(directory)
+    if not result.images:
+        return result.markdown
+    directory.mkdir(parents=True)
+    for file_name, name in file_names.items():
+        (directory / file_name).write_bytes(result.images[name])
+    return MARKDOWN_IMAGE_LINK.sub(f"]({directory.name}/", result.markdown)
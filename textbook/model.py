@@ -1,49 +1,390 @@
+import copy
+import json
 import os
-from typing import TypeVar, List
+import tempfile
+from pathlib import Path
+from typing import TYPE_CHECKING, Any, Dict, Iterator, Tuple, TypeVar, List, Optional
 
 
 import llm
 from llm import Attachment
 import structlog
 
-from pydantic import BaseModel
+from PIL import Image
+from pydantic import BaseModel, ValidationError
 
-PROVIDER = os.getenv("LLM_PROVIDER", "gemini")
-TEXT_MODEL_NAME = os.getenv("LLM_MODEL_NAME", "gemini-3-flash-preview")
-EMBEDDING_MODEL_NAME = os.getenv("LLM_EMBEDDING_MODEL_NAME", "gemini-embedding-001")
-API_KEY = os.getenv("LLM_GEMINI_KEY")
-if API_KEY is None:
-    raise ValueError("LLM_GEMINI_KEY is not set")
+from textbook.backends import AZURE_OPENAI, BackendSettings
+from textbook.circuit_breaker import CircuitBreaker
+from textbook.credentials import SecretString, env_api_key
+from textbook.generation import GenerationParams, GenerationRecord
+from textbook.json_repair import json_schema, validate_with_repair
+from textbook.provider_errors import ERROR_SERVER, ERROR_TIMEOUT, ModelError, provider_error
+from textbook.rate_limit import ProviderRateLimiter, estimate_tokens
+from textbook.safety import SafetySettings
+
+if TYPE_CHECKING:
+    from textbook.prompt_log import PromptLog
+    from textbook.response_cache import ResponseCache
+    from textbook.usage import UsageTracker
+
+PROVIDER = os.getenv("LLM_PROVIDER", "gemini") # The backend, unless the [llm] section of config.toml sets one
+TEXT_MODEL_NAME = os.getenv("LLM_MODEL_NAME") # Unless [llm] sets one, by default the backend's default model
+BASE_URL = os.getenv("LLM_BASE_URL") # Unless [llm] sets one, of OpenAI-compatible backends such as a local Ollama
+ESCALATION_MODEL_NAME = os.getenv("LLM_ESCALATION_MODEL_NAME") # Stronger model for gradings that need a second opinion, none by default
+EMBEDDING_MODEL_NAME = os.getenv("LLM_EMBEDDING_MODEL_NAME") # By default the backend's embedding model, if it has one
+
+# How structured output is requested: "schema" passes the schema to the backend, "prompted" asks for JSON in the
+# prompt and validates it locally, for backends without schema support; "auto" picks by the model's capabilities
+SCHEMA_MODE_AUTO = "auto"
+SCHEMA_MODE_NATIVE = "schema"
+SCHEMA_MODE_PROMPTED = "prompted"
+SCHEMA_MODES = (SCHEMA_MODE_AUTO, SCHEMA_MODE_NATIVE, SCHEMA_MODE_PROMPTED)
+SCHEMA_MODE = os.getenv("LLM_SCHEMA_MODE", SCHEMA_MODE_AUTO)
+SCHEMA_REPAIR_ATTEMPTS = 1  # Times an invalid response is sent back with the validation error, unless config.toml sets one
+MAX_SCHEMA_REPAIR_ATTEMPTS = 5
+IMAGE_TYPE = "image/png"  # Of the page images attached to prompts, models without it are prompted with the text only
 
 T = TypeVar("T", bound=BaseModel)
 
+
+def schema_instructions(prompt: str, schema: type[BaseModel]) -> str:
+    """The prompt with instructions to answer with JSON matching the schema, for backends without schema support"""
+    return (
+        f"{prompt}\n\n"
+        "Answer with a single JSON object and nothing else, matching this JSON schema:\n"
+        f"{json.dumps(json_schema(schema))}"
+    )
+
+
+def schema_repair_attempts_from_config(config: dict) -> int:
+    """The schema_repair_attempts setting of config.toml, raises ValueError for invalid ones"""
+    attempts = config.get("schema_repair_attempts", SCHEMA_REPAIR_ATTEMPTS)
+    if isinstance(attempts, bool) or not isinstance(attempts, int) or not 0 <= attempts <= MAX_SCHEMA_REPAIR_ATTEMPTS:
+        raise ValueError(f"schema_repair_attempts must be an integer between 0 and {MAX_SCHEMA_REPAIR_ATTEMPTS}")
+    return attempts
+
+
+def repair_prompt(prompt: str, text: str, error: ValidationError) -> str:
+    """The prompt sending an answer that failed validation back with its errors"""
+    return f"{prompt}\n\nYour previous answer was:\n{text}\n\nIt is not valid against the schema:\n{error}\n\nAnswer again with the corrected JSON object only."
+
+
+def response_tokens(response: Any) -> Tuple[Optional[int], Optional[int]]:
+    """The (input, output) tokens a response reports, None where the provider did not"""
+    usage = getattr(response, "usage", None)
+    if not callable(usage):
+        return None, None
+    try:
+        counts = usage()
+    except Exception:
+        # The counts are optional, a plugin failing to give them must not fail the prompt
+        return None, None
+    return getattr(counts, "input", None), getattr(counts, "output", None)
+
+
+def default_model_name(settings: BackendSettings) -> Optional[str]:
+    """The model of the [llm] section of config.toml, LLM_MODEL_NAME, or the backend's default"""
+    return settings.model or TEXT_MODEL_NAME or settings.spec.default_model
+
+
+def embedding_model_name(settings: BackendSettings) -> Optional[str]:
+    return EMBEDDING_MODEL_NAME or settings.spec.embedding_model
+
+
+def create_text_model(settings: BackendSettings, model_name: str, key: Optional[str]) -> Any:
+    """The llm model of a backend, raises ValueError for models llm does not know, e.g. of a plugin not installed"""
+    backend = settings.spec
+    if backend.openai_compatible or settings.base_url:
+        # Imported here, only these backends use llm's OpenAI client directly
+        from llm.default_plugins.openai_models import Chat
+        azure = backend.name == AZURE_OPENAI
+        model = Chat(
+            f"{backend.name}/{model_name}",
+            model_name=model_name,
+            api_base=settings.url,
+            api_type="azure" if azure else None,
+            api_version=settings.api_version,
+            api_engine=model_name if azure else None,  # The deployment
+        )
+    else:
+        try:
+            model = llm.get_model(model_name)
+        except llm.UnknownModelError:
+            install = f", install its plugin with `llm install {backend.plugin}`" if backend.plugin else ""
+            raise ValueError(f"Unknown {backend.name} model: {model_name}{install}")
+    if key is not None:
+        model.key = key
+    elif not settings.requires_key:
+        # Local servers need no key, llm would refuse to prompt without one
+        model.needs_key = None
+    return model
+
+
 class LLM:
-    def __init__(self):
+    prompt_log: Optional["PromptLog"] = None  # Set on startup when prompts are logged
+    usage_tracker: Optional["UsageTracker"] = None  # Set on startup, records the tokens and cost of every call
+    response_cache: Optional["ResponseCache"] = None  # Set on startup, answers identical structured requests again
+    prompt_options: Dict[str, Any] = {}  # Passed with every prompt, e.g. the safety settings and generation parameters
+    rate_limiter: Optional[ProviderRateLimiter] = None  # Shared by the models of the provider, set on startup
+    circuit_breaker: Optional[CircuitBreaker] = None  # Of the provider, set on startup, fails prompts fast while it is down
+    api_key: Optional[SecretString] = None  # Resolved from the credentials on startup, the environment is read without
+    backend: BackendSettings = BackendSettings(PROVIDER)  # The [llm] section of config.toml, set on startup
+    schema_repair_attempts: int = SCHEMA_REPAIR_ATTEMPTS  # Set on startup from config.toml
+
+    def __init__(
+        self,
+        text_model_name: Optional[str] = None,
+        schema_mode: str = SCHEMA_MODE,
+        safety: Optional[SafetySettings] = None,
+        generation: Optional[GenerationParams] = None,
+    ):
+        # Checked when a model is created, so the rest of the package works without a key
+        backend = self.backend.spec
+        credential = env_api_key(backend.name) if self.api_key is None else None
+        api_key = self.api_key or (credential.secret if credential else None)
+        if api_key is None and self.backend.requires_key:
+            variable = f"{backend.env_vars[0]} or " if backend.env_vars else ""
+            raise ValueError(f"No API key for {backend.name}: set {variable}the [credentials.{backend.name}] section of config.toml")
+        text_model_name = text_model_name or default_model_name(self.backend)
+        if text_model_name is None:
+            raise ValueError(f"No model for {backend.name}: set model in the [llm] section of config.toml or LLM_MODEL_NAME")
+        key = api_key.reveal() if api_key is not None else None
         self.logger = structlog.get_logger("LLM")
-        self.text_model = llm.get_model(TEXT_MODEL_NAME) # type: ignore
-        self.embedding_model = llm.get_embedding_model(EMBEDDING_MODEL_NAME) # type: ignore
-        self.text_model.key = API_KEY
-        self.embedding_model.key = API_KEY
+        self.model_name = text_model_name
+        self.text_model = create_text_model(self.backend, text_model_name, key)
+        embedding_name = embedding_model_name(self.backend)
+        self.embedding_model = llm.get_embedding_model(embedding_name) if embedding_name else None # type: ignore
+        if self.embedding_model is not None and key is not None:
+            self.embedding_model.key = key
+        if schema_mode not in SCHEMA_MODES:
+            raise ValueError(f"Unsupported schema mode: {schema_mode}, expected one of {', '.join(SCHEMA_MODES)}")
+        if schema_mode == SCHEMA_MODE_AUTO:
+            schema_mode = SCHEMA_MODE_NATIVE if getattr(self.text_model, "supports_schema", False) else SCHEMA_MODE_PROMPTED
+        self.schema_mode = schema_mode
+        self.safety_options = safety.prompt_options() if safety else {}
+        self.generation = generation or GenerationParams()
+        self.prompt_options = self._supported_options({**self.safety_options, **self._generation_options(self.generation)})
         if not self.health_check():
             raise RuntimeError("LLM health check failed")
         else:
-            self.logger.info("LLM health check passed")
+            self.logger.info("LLM health check passed", schema_mode=self.schema_mode)
     
+    def with_generation(self, generation: GenerationParams) -> "LLM":
+        """This model prompting with other generation parameters, e.g. of a task; the model and its limits are shared"""
+        if generation == self.generation:
+            return self
+        model = copy.copy(self)
+        model.generation = generation
+        model.prompt_options = self._supported_options({**self.safety_options, **self._generation_options(generation)})
+        return model
+
+    def generation_record(self) -> GenerationRecord:
+        """The model, temperature and seed this model prompts with, those its plugin does not support left out"""
+        return GenerationRecord(self.model_name, self.prompt_options.get("temperature"), self.prompt_options.get("seed"))
+
     def prompt_with_schema(self, prompt: str, schema: type[T]) -> T:
-        self.logger.debug("Prompting LLM with prompt", prompt=prompt, schema=schema)
-        response = self.text_model.prompt(prompt, schema=schema)
-        self.logger.debug(f"Response: {response.text()}")
-        return schema.model_validate_json(response.text())
+        return self.prompt_with_schema_and_attachments(prompt, schema, [])
 
     def prompt_with_schema_and_attachments(self, prompt: str, schema: type[T], attachments: List[Attachment]) -> T:
         self.logger.debug("Prompting LLM with prompt", prompt=prompt, schema=schema, attachments=attachments)
-        response = self.text_model.prompt(prompt, schema=schema, attachments=attachments)
-        self.logger.debug(f"Response: {response.text()}")
-        return schema.model_validate_json(response.text())
+        key = self._cache_key(prompt, schema, attachments)
+        if key is not None:
+            cached = self._cached(key, schema)
+            if cached is not None:
+                self.logger.debug("Response served from the cache", model=self.model_name, schema=schema.__name__)
+                return cached
+        result = self._prompt_with_schema(prompt, schema, attachments)
+        if key is not None:
+            self._cache(key, result)
+        return result
+
+    def _prompt_with_schema(self, prompt: str, schema: type[T], attachments: List[Attachment]) -> T:
+        # Backends without schema support are asked for JSON in the prompt
+        native = self.schema_mode == SCHEMA_MODE_NATIVE
+        return self._prompt_for_json(prompt if native else schema_instructions(prompt, schema), schema, attachments, native)
+
+    @property
+    def supports_images(self) -> bool:
+        """Whether the model accepts images with a prompt, e.g. the pages a problem's figures are on"""
+        return IMAGE_TYPE in getattr(self.text_model, "attachment_types", set())
+
+    def prompt_with_schema_and_images(self, prompt: str, schema: type[T], images: List[Image.Image]) -> T:
+        """Prompt with the images attached, or with the prompt alone for models that cannot see them"""
+        if not images or not self.supports_images:
+            return self.prompt_with_schema(prompt, schema)
+        # Attachments are read from files, which only need to outlive the request
+        with tempfile.TemporaryDirectory() as tmpdir:
+            attachments = []
+            for index, image in enumerate(images):
+                path = Path(tmpdir) / f"image_{index}.png"
+                image.save(path, "PNG")
+                attachments.append(Attachment(path=str(path), type=IMAGE_TYPE))
+            return self.prompt_with_schema_and_attachments(prompt, schema, attachments)
+
+    def _prompt_for_json(self, prompt: str, schema: type[T], attachments: List[Attachment], native: bool = False) -> T:
+        # An answer failing validation is sent back with the errors, the last error is raised once the repairs run out,
+        # which fails the job prompting
+        error: Optional[ValidationError] = None
+        options: Dict[str, Any] = {"schema": schema} if native else {}
+        for attempt in range(self.schema_repair_attempts + 1):
+            text = self._complete(prompt, attachments=attachments, **options)
+            self.logger.debug(f"Response: {text}")
+            try:
+                return self._validate(prompt, text, schema, attempt)
+            except ValidationError as e:
+                error = e
+                if attempt < self.schema_repair_attempts:
+                    self.logger.warning("Invalid JSON response, asking for a repair", model=self.model_name, attempt=attempt + 1, error=str(e))
+                    prompt = repair_prompt(prompt, text, e)
+        assert error is not None
+        self.logger.warning("Invalid JSON response after repairs, giving up", model=self.model_name, repairs=self.schema_repair_attempts)
+        raise error
+
+    def stream(self, prompt: str) -> Iterator[str]:
+        """Prompt for free text, yielding the answer in pieces as the provider sends them"""
+        self._check_circuit_breaker()
+        self._wait_for_rate_limit(prompt)
+        pieces = []
+        response = None
+        try:
+            streamed = self.text_model.prompt(prompt, stream=True, **self.prompt_options)
+            for piece in streamed:
+                if not pieces:
+                    # The provider answers, whether or not the client reads the rest
+                    self._record_outcome(None)
+                pieces.append(piece)
+                yield piece
+            # Only a finished response has its counts, asking an unfinished one for them would read the rest
+            response = streamed
+        except Exception as e:
+            raise self._provider_error(e) from e
+        finally:
+            # Charged and logged for what arrived, also when the client stopped reading
+            text = "".join(pieces)
+            if self.rate_limiter is not None:
+                self.rate_limiter.charge(estimate_tokens(text))
+            self._log_prompt(prompt, text, None)
+            self._record_usage(prompt, text, response)
+
+    def _wait_for_rate_limit(self, prompt: str):
+        if self.rate_limiter is not None:
+            waited = self.rate_limiter.acquire(estimate_tokens(prompt))
+            if waited > 0:
+                self.logger.info("Waited for the provider's rate limit", model=self.model_name, seconds=round(waited, 1))
+
+    def _check_circuit_breaker(self):
+        if self.circuit_breaker is not None:
+            self.circuit_breaker.check()
+
+    def _record_outcome(self, error: Optional[ModelError]):
+        # Only an unreachable or failing provider counts against the breaker, it answered rejected requests
+        if self.circuit_breaker is None:
+            return
+        if error is not None and error.category in (ERROR_SERVER, ERROR_TIMEOUT):
+            self.circuit_breaker.record_failure()
+        else:
+            self.circuit_breaker.record_success()
+
+    def _provider_error(self, e: Exception) -> ModelError:
+        error = provider_error(e, self.model_name)
+        self.logger.warning("LLM provider error", model=self.model_name, category=error.category, detail=error.detail)
+        self._record_outcome(error)
+        return error
+
+    def _complete(self, prompt: str, **kwargs) -> str:
+        # The provider is only called once the response text is read, whatever it fails with becomes a ModelError
+        self._check_circuit_breaker()
+        self._wait_for_rate_limit(prompt)
+        try:
+            response = self.text_model.prompt(prompt, **self.prompt_options, **kwargs)
+            text = response.text()
+        except Exception as e:
+            raise self._provider_error(e) from e
+        self._record_outcome(None)
+        if self.rate_limiter is not None:
+            self.rate_limiter.charge(estimate_tokens(text))
+        self._record_usage(prompt, text, response)
+        return text
+
+    def _generation_options(self, generation: GenerationParams) -> Dict[str, Any]:
+        return generation.prompt_options(getattr(getattr(self.text_model, "Options", None), "model_fields", {}))
+
+    def _supported_options(self, options: Dict[str, Any]) -> Dict[str, Any]:
+        # Plugins reject options they do not know, those are dropped with a warning instead
+        fields = getattr(getattr(self.text_model, "Options", None), "model_fields", {})
+        unsupported = [name for name in options if name not in fields]
+        if unsupported:
+            self.logger.warning("Model does not support these options, ignoring them", model=self.model_name, options=unsupported)
+        return {name: value for name, value in options.items() if name in fields}
+
+    def _validate(self, prompt: str, text: str, schema: type[T], attempt: int = 0) -> T:
+        # Validates a response, logging it with the prompt either way, and the output parsed from it when valid
+        try:
+            result = validate_with_repair(text, schema, self.model_name)
+        except ValidationError as e:
+            self._log_prompt(prompt, text, schema, str(e), attempt=attempt)
+            raise
+        self._log_prompt(prompt, text, schema, parsed=result.model_dump_json(), attempt=attempt)
+        return result
+
+    def _log_prompt(
+        self,
+        prompt: str,
+        text: str,
+        schema: Optional[type[BaseModel]],
+        error: Optional[str] = None,
+        parsed: Optional[str] = None,
+        attempt: int = 0,
+    ) -> None:
+        # Free text answers have no schema
+        if self.prompt_log is None:
+            return
+        try:
+            self.prompt_log.record(self.model_name, prompt, text, schema.__name__ if schema else None, error, parsed=parsed, attempt=attempt)
+        except Exception as e:
+            # The log is for debugging, losing an entry must not fail the prompt
+            self.logger.warning("Failed to log prompt", model=self.model_name, error=str(e))
     
+    def _record_usage(self, prompt: str, text: str, response: Any) -> None:
+        # The provider's counts when it reports them, estimated from the text otherwise
+        if self.usage_tracker is None:
+            return
+        input_tokens, output_tokens = response_tokens(response) if response is not None else (None, None)
+        estimated = input_tokens is None or output_tokens is None
+        try:
+            self.usage_tracker.record(
+                self.model_name,
+                input_tokens if input_tokens is not None else estimate_tokens(prompt),
+                output_tokens if output_tokens is not None else estimate_tokens(text),
+                estimated,
+            )
+        except Exception as e:
+            # Like the prompt log, losing a record must not fail the prompt
+            self.logger.warning("Failed to record usage", model=self.model_name, error=str(e))
+
+    def _cache_key(self, prompt: str, schema: type[T], attachments: List[Attachment]) -> Optional[str]:
+        if self.response_cache is None or not self.response_cache.settings.enabled:
+            return None
+        return self.response_cache.key(self.model_name, prompt, schema, self.prompt_options, attachments)
+
+    def _cached(self, key: str, schema: type[T]) -> Optional[T]:
+        # Like the prompt log, a cache that fails only costs a call to the provider
+        assert self.response_cache is not None
+        try:
+            return self.response_cache.get(key, schema)
+        except Exception as e:
+            self.logger.warning("Failed to read the response cache", model=self.model_name, error=str(e))
+            return None
+
+    def _cache(self, key: str, result: BaseModel) -> None:
+        assert self.response_cache is not None
+        try:
+            self.response_cache.put(key, self.model_name, result)
+        except Exception as e:
+            self.logger.warning("Failed to cache the response", model=self.model_name, error=str(e))
+
     def health_check(self) -> bool:
-        response = self.text_model.prompt("Where is the capital of France?")
-        return "paris" in response.text().lower()
+        return "paris" in self._complete("Where is the capital of France?").lower()
 
 if __name__ == "__main__":
     llm = LLM()
@@ -0,0 +1,204 @@
+# Ingestion of Jupyter notebooks (.ipynb) from technical courses
+# Markdown cells are kept as text and code cells as fenced blocks tagged with the notebook language, so the
+# code survives rendering and reaches the LLM verbatim. Text outputs are kept (truncated), rich outputs are
+# only noted. The result is rendered to a PDF like a web article and the markdown headings become the TOC.
+
+import json
+import re
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import List, Optional, Tuple, Union
+
+from textbook.web_article import find_heading_pages, headings_to_toc, render_markdown_to_pdf
+
+NOTEBOOK_EXTENSIONS = (".ipynb",)
+MAX_OUTPUT_LINES = 20  # Longer cell outputs (training logs, dataframes) are cut to their first lines
+DEFAULT_LANGUAGE = "python"
+
+_HEADING = re.compile(r"^(#{1,6})\s+(.+?)\s*#*\s*$")
+
+
+class NotebookError(ValueError):
+    """Raised when a notebook cannot be read"""
+    pass
+
+
+@dataclass
+class NotebookCell:
+    cell_type: str  # "markdown", "code" or "raw"
+    source: str
+    outputs: List[str] = field(default_factory=list)
+
+
+@dataclass
+class Notebook:
+    title: Optional[str]
+    language: str
+    cells: List[NotebookCell]
+
+
+@dataclass
+class IngestedNotebook:
+    title: str
+    notebook: Notebook
+    markdown: str
+    toc: dict  # For LazyTextbookReader.save_toc
+
+
+# ------------------------------------------------------------
+# Parsing
+# ------------------------------------------------------------
+
+def _join_source(source: Union[str, List[str], None]) -> str:
+    # nbformat stores multiline strings either as one string or as a list of lines
+    if source is None:
+        return ""
+    return source if isinstance(source, str) else "".join(source)
+
+
+def _output_text(output: dict) -> Optional[str]:
+    output_type = output.get("output_type")
+    if output_type == "stream":
+        return _join_source(output.get("text"))
+    if output_type == "error":
+        return f"{output.get('ename', 'Error')}: {output.get('evalue', '')}"
+    if output_type in ("execute_result", "display_data"):
+        data = output.get("data", {})
+        if "text/plain" in data:
+            return _join_source(data["text/plain"])
+        if any(mime.startswith("image/") for mime in data):
+            return "[image output]"
+    return None
+
+
+def parse_notebook(raw: Union[str, bytes]) -> Notebook:
+    """Parse nbformat 4 JSON into cells, keeping the kernel language for the code fences"""
+    try:
+        document = json.loads(raw)
+    except (json.JSONDecodeError, UnicodeDecodeError) as e:
+        raise NotebookError(f"Not a valid notebook file: {e}")
+    if not isinstance(document, dict) or not isinstance(document.get("cells"), list):
+        raise NotebookError("Not a valid notebook file: no cells found")
+    if document.get("nbformat", 4) < 4:
+        raise NotebookError("Only nbformat 4 notebooks are supported, re-save the notebook with a recent Jupyter")
+
+    metadata = document.get("metadata", {})
+    language = (
+        metadata.get("language_info", {}).get("name")
+        or metadata.get("kernelspec", {}).get("language")
+        or DEFAULT_LANGUAGE
+    )
+
+    cells: List[NotebookCell] = []
+    for cell in document["cells"]:
+        source = _join_source(cell.get("source")).strip("\n")
+        outputs = [text for text in (_output_text(output) for output in cell.get("outputs", [])) if text and text.strip()]
+        if source.strip() or outputs:
+            cells.append(NotebookCell(cell.get("cell_type", "raw"), source, outputs))
+
+    if not cells:
+        raise NotebookError("The notebook has no content")
+    return Notebook(title=metadata.get("title"), language=language.lower(), cells=cells)
+
+
+# ------------------------------------------------------------
+# Conversion
+# ------------------------------------------------------------
+
+def _truncate_output(text: str) -> str:
+    lines = text.rstrip("\n").split("\n")
+    if len(lines) <= MAX_OUTPUT_LINES:
+        return "\n".join(lines)
+    return "\n".join(lines[:MAX_OUTPUT_LINES] + [f"... ({len(lines) - MAX_OUTPUT_LINES} more lines)"])
+
+
+def _fence(text: str, info: str = "") -> str:
+    # A longer fence than any backtick run inside, so code containing ``` stays in one block
+    longest = max((len(run) for run in re.findall(r"`{3,}", text)), default=2)
+    marker = "`" * (longest + 1)
+    return f"{marker}{info}\n{text}\n{marker}"
+
+
+def _markdown_cell(source: str, headings: List[Tuple[int, str]]) -> List[str]:
+    # Headings are split into their own blocks so they render and are found like article headings
+    blocks: List[str] = []
+    current: List[str] = []
+    in_code = False
+    for line in source.split("\n"):
+        if line.startswith("```"):
+            in_code = not in_code
+        heading = None if in_code else _HEADING.match(line)
+        if heading:
+            if current:
+                blocks.append("\n".join(current).strip("\n"))
+                current = []
+            level, text = len(heading.group(1)), heading.group(2)
+            headings.append((level, text))
+            blocks.append(f"{'#' * level} {text}")
+        else:
+            current.append(line)
+    if current and "\n".join(current).strip():
+        blocks.append("\n".join(current).strip("\n"))
+    return blocks
+
+
+def notebook_to_markdown(notebook: Notebook, title: str, use_leading_heading: bool = True) -> Tuple[str, List[Tuple[int, str]]]:
+    """
+    Convert a notebook to markdown, markdown cells as text and code cells as fenced code
+
+    Args:
+        notebook: The parsed notebook
+        title: The title of the document
+        use_leading_heading: Whether a level 1 heading opening the notebook replaces the title
+
+    Returns:
+        Tuple[str, List[Tuple[int, str]]]: The markdown and its headings as (level, text), without the title
+    """
+    headings: List[Tuple[int, str]] = []
+    blocks: List[str] = [f"# {title}"]
+    for cell in notebook.cells:
+        if cell.cell_type == "markdown":
+            blocks.extend(_markdown_cell(cell.source, headings))
+        elif cell.cell_type == "code":
+            if cell.source.strip():
+                blocks.append(_fence(cell.source, notebook.language))
+            blocks.extend(_fence(_truncate_output(output), "output") for output in cell.outputs)
+        elif cell.source.strip():
+            blocks.append(_fence(cell.source))
+
+    # A notebook usually opens with its own title heading
+    if use_leading_heading and headings and headings[0][0] == 1 and blocks[1] == f"# {headings[0][1]}":
+        blocks = blocks[1:]
+        headings = headings[1:]
+    return "\n\n".join(blocks) + "\n", headings
+
+
+def markdown_language(markdown: str) -> str:
+    """Recover the notebook language from the stored markdown, the info string of its first code fence"""
+    for info in re.findall(r"^`{3,}(\w[\w+#-]*)$", markdown, flags=re.MULTILINE):
+        if info != "output":
+            return info
+    return DEFAULT_LANGUAGE
+
+
+# ------------------------------------------------------------
+# Ingestion
+# ------------------------------------------------------------
+
+def ingest_notebook(raw: Union[str, bytes], pdf_path: Path, markdown_path: Path, title: Optional[str] = None, fallback_title: str = "Notebook") -> IngestedNotebook:
+    """
+    Store a notebook as markdown and PDF, and derive its TOC from the markdown headings
+
+    The title is the given one, else the notebook metadata title, else its opening heading, else fallback_title.
+    """
+    notebook = parse_notebook(raw)
+    explicit_title = title or notebook.title
+    markdown, headings = notebook_to_markdown(notebook, explicit_title or fallback_title, use_leading_heading=explicit_title is None)
+
+    with open(markdown_path, "w", encoding="utf-8") as f:
+        f.write(markdown)
+    render_markdown_to_pdf(markdown, pdf_path)
+
+    document_title = markdown.split("\n", 1)[0][2:]
+    toc = headings_to_toc(document_title, headings, find_heading_pages(pdf_path, headings))
+    return IngestedNotebook(document_title, notebook, markdown, toc)
@@ -0,0 +1,254 @@
+# OCR backends
+# Uploaded documents, and the pages of books without a text layer, are OCRed by a backend chosen in the [ocr] section
+# of config.toml. MinerU, the default, is a separate server (see mineru) that keeps the layout, equations, tables and
+# figures. Tesseract runs in this process, through the pytesseract package and the tesseract binary, for users who do
+# not want to run MinerU: it reads the text of every page, taking the PDF's text layer where there is one unless
+# parse_method is "ocr", and extracts no figures. Every backend returns the markdown with a content list of blocks
+# carrying their page, so chunking, segmentation and the table of contents work the same whatever read the document.
+# Other backends, like marker or Textract, are added with register_ocr_backend and selected by name:
+#   [ocr]
+#   backend = "tesseract"        # mineru (default) or tesseract
+#   tesseract_cmd = "tesseract"  # the binary, when it is not on the PATH
+#   dpi = 300                    # pages are rendered at this resolution for Tesseract
+#   text_layer = true            # read pages with a text layer without OCR (see text_layer)
+#   mineru_connect_timeout = 10  # seconds to connect to MinerU
+#   mineru_request_timeout = 300 # seconds MinerU has to answer a request
+#   mineru_timeout_retries = 1   # times a timed out request is sent again
+#   mineru_max_upload_mb = 200   # larger files are refused before they are uploaded
+#   mineru_zip_response = false  # MinerU answers with a zip archive, the figures not base64 encoded
+#   pages_per_chunk = 50         # OCR jobs send the pages in chunks, resuming after the last one read (see chunked_ocr)
+
+import os
+import shutil
+import tempfile
+from abc import ABC, abstractmethod
+from dataclasses import dataclass, field
+from typing import Callable, Dict, List, Optional
+
+import pymupdf
+from PIL import Image
+
+from textbook.mineru import API_BASE_URL as MINERU_API_URL, MinerUResult, MinerUSettings, new_output_dir, ocr_image, ocr_pdf_artifacts, ping as ping_mineru
+
+OCR_MINERU = "mineru"
+OCR_TESSERACT = "tesseract"
+DEFAULT_DPI = 300
+MIN_TEXT_LAYER_CHARS = 20  # A page with less text than this in its text layer is OCRed
+DEFAULT_PAGES_PER_CHUNK = 50  # Of an OCR job, see chunked_ocr
+
+# Tesseract's languages for MinerU's language codes, other codes are passed to Tesseract as they are (e.g. "deu")
+TESSERACT_LANGUAGES = {
+    "en": "eng",
+    "ch": "chi_sim",
+    "chinese_cht": "chi_tra",
+    "japan": "jpn",
+    "korean": "kor",
+    "latin": "eng",
+    "arabic": "ara",
+    "cyrillic": "rus",
+    "east_slavic": "rus+ukr",
+    "devanagari": "hin",
+    "ta": "tam",
+    "te": "tel",
+    "ka": "kan",
+}
+
+OcrResult = MinerUResult  # The markdown, its content list and the images extracted, whatever the backend
+
+
+@dataclass
+class OcrSettings:
+    backend: str = OCR_MINERU
+    tesseract_cmd: str = "tesseract"
+    dpi: int = DEFAULT_DPI
+    text_layer: bool = True  # Only OCR the pages of uploads without a text layer
+    mineru: MinerUSettings = field(default_factory=MinerUSettings)
+    pages_per_chunk: int = DEFAULT_PAGES_PER_CHUNK  # Sent to the backend at a time by OCR jobs, 0 for all at once
+
+
+class OcrBackend(ABC):
+    """Reads the text of documents and page images"""
+
+    name: str
+
+    @abstractmethod
+    def unavailable(self) -> Optional[str]:
+        """How to make the backend usable, None when it is"""
+
+    def ping(self) -> Optional[str]:
+        """Why the backend cannot OCR right now, None when it can; backends running elsewhere are asked"""
+        return self.unavailable()
+
+    @abstractmethod
+    def ocr_pdf(self, pdf_path: str, language: str, parse_method: str, job_id: Optional[str] = None) -> OcrResult:
+        """OCR a whole PDF, language being a MinerU language code and parse_method auto, txt or ocr"""
+
+    @abstractmethod
+    def ocr_image(self, image: Image.Image) -> str:
+        """The markdown of an image, e.g. a page without a text layer"""
+
+    def ocr_pages(self, pdf_path: str, pages: List[int], language: str, parse_method: str, job_id: Optional[str] = None) -> OcrResult:
+        """OCR some pages of a PDF copied into one of their own, the page_idx of the blocks being those of the PDF"""
+        with tempfile.NamedTemporaryFile(suffix=".pdf", delete=False) as tmp_file:
+            tmp_path = tmp_file.name
+        try:
+            with pymupdf.open(pdf_path) as document, pymupdf.open() as selected:
+                for page_number in pages:
+                    selected.insert_pdf(document, from_page=page_number, to_page=page_number)
+                selected.save(tmp_path)
+            result = self.ocr_pdf(tmp_path, language, parse_method, job_id=job_id)
+        finally:
+            if os.path.exists(tmp_path):
+                os.unlink(tmp_path)
+        return pages_result(result, pages)
+
+
+def pages_result(result: OcrResult, pages: List[int]) -> OcrResult:
+    """The result of OCRing some pages, its blocks put back on them from their index among the pages"""
+    if not result.content_list:
+        # Without blocks there are no pages to put the markdown back on, it goes on the first page OCRed
+        return OcrResult(result.markdown, [{"type": "text", "text": result.markdown, "page_idx": pages[0]}] if result.markdown.strip() else [], result.images)
+    content_list = [{**block, "page_idx": pages[min(int(block.get("page_idx", 0)), len(pages) - 1)]} for block in result.content_list]
+    return OcrResult(result.markdown, content_list, result.images)
+
+
+class MinerUBackend(OcrBackend):
+    name = OCR_MINERU
+
+    def __init__(self, settings: Optional[MinerUSettings] = None):
+        self.settings = settings or MinerUSettings()
+
+    def unavailable(self) -> Optional[str]:
+        return None if MINERU_API_URL else "Set MINERU_API_URL to the URL of a running MinerU API"
+
+    def ping(self) -> Optional[str]:
+        return self.unavailable() or ping_mineru()
+
+    def ocr_pdf(self, pdf_path: str, language: str, parse_method: str, job_id: Optional[str] = None) -> OcrResult:
+        return ocr_pdf_artifacts(pdf_path, [language], parse_method, output_dir=new_output_dir(job_id), settings=self.settings)
+
+    def ocr_pages(self, pdf_path: str, pages: List[int], language: str, parse_method: str, job_id: Optional[str] = None) -> OcrResult:
+        if pages != list(range(pages[0], pages[-1] + 1)):
+            return super().ocr_pages(pdf_path, pages, language, parse_method, job_id)
+        # A range is read from the whole PDF by MinerU itself, which numbers its pages from 0
+        result = ocr_pdf_artifacts(pdf_path, [language], parse_method, output_dir=new_output_dir(job_id), settings=self.settings, start_page_id=pages[0], end_page_id=pages[-1])
+        return pages_result(result, pages)
+
+    def ocr_image(self, image: Image.Image) -> str:
+        with tempfile.NamedTemporaryFile(suffix=".png", delete=False) as tmp_file:
+            tmp_path = tmp_file.name
+        image.save(tmp_path, "PNG")
+        try:
+            return ocr_image(tmp_path, self.settings)
+        finally:
+            if os.path.exists(tmp_path):
+                os.unlink(tmp_path)
+
+
+class TesseractBackend(OcrBackend):
+    name = OCR_TESSERACT
+
+    def __init__(self, tesseract_cmd: str = "tesseract", dpi: int = DEFAULT_DPI):
+        self.tesseract_cmd = tesseract_cmd
+        self.dpi = dpi
+
+    def _pytesseract(self):
+        import pytesseract
+
+        pytesseract.pytesseract.tesseract_cmd = self.tesseract_cmd
+        return pytesseract
+
+    def unavailable(self) -> Optional[str]:
+        try:
+            import pytesseract  # noqa: F401
+        except ImportError:
+            return "Install the pytesseract package (uv sync --extra tesseract) to OCR with Tesseract"
+        if shutil.which(self.tesseract_cmd) is None:
+            return f"Install Tesseract, or set tesseract_cmd in the [ocr] section of config.toml: {self.tesseract_cmd} not found"
+        return None
+
+    def ocr_pdf(self, pdf_path: str, language: str, parse_method: str, job_id: Optional[str] = None) -> OcrResult:
+        pytesseract = self._pytesseract()
+        lang = TESSERACT_LANGUAGES.get(language, language)
+        pages = []
+        with pymupdf.open(pdf_path) as document:
+            for page in document:
+                text = page.get_text().strip()
+                if parse_method == "ocr" or (parse_method != "txt" and len(text) < MIN_TEXT_LAYER_CHARS):
+                    pixmap = page.get_pixmap(matrix=pymupdf.Matrix(self.dpi / 72, self.dpi / 72))
+                    image = Image.frombytes("RGB", (pixmap.width, pixmap.height), pixmap.samples)
+                    text = pytesseract.image_to_string(image, lang=lang).strip()
+                pages.append(text)
+        content_list = [{"type": "text", "text": text, "page_idx": index} for index, text in enumerate(pages) if text]
+        return OcrResult("\n\n".join(block["text"] for block in content_list), content_list)
+
+    def ocr_image(self, image: Image.Image) -> str:
+        return self._pytesseract().image_to_string(image).strip()
+
+
+OcrBackendFactory = Callable[[OcrSettings], OcrBackend]
+
+_backends: Dict[str, OcrBackendFactory] = {
+    OCR_MINERU: lambda settings: MinerUBackend(settings.mineru),
+    OCR_TESSERACT: lambda settings: TesseractBackend(settings.tesseract_cmd, settings.dpi),
+}
+
+
+def register_ocr_backend(name: str, factory: OcrBackendFactory):
+    """Make a backend selectable by name in the [ocr] section, its factory gets the settings of the section"""
+    _backends[name] = factory
+
+
+def ocr_backend_names() -> List[str]:
+    return sorted(_backends)
+
+
+# The settings of MinerU requests in the [ocr] section, by their attribute of MinerUSettings
+_MINERU_KEYS = {
+    "mineru_connect_timeout": "connect_timeout_seconds",
+    "mineru_request_timeout": "request_timeout_seconds",
+    "mineru_max_upload_mb": "max_upload_mb",
+    "mineru_timeout_retries": "timeout_retries",
+    "mineru_zip_response": "zip_response",
+}
+
+
+def ocr_settings_from_config(config: dict) -> OcrSettings:
+    """The [ocr] section of config.toml, raises ValueError for invalid settings"""
+    section = config.get("ocr", {})
+    unknown = set(section) - {"backend", "tesseract_cmd", "dpi", "text_layer", "pages_per_chunk"} - set(_MINERU_KEYS)
+    if unknown:
+        raise ValueError(f"Unknown settings of ocr: {', '.join(sorted(unknown))}")
+    settings = OcrSettings(
+        backend=section.get("backend", OCR_MINERU),
+        tesseract_cmd=section.get("tesseract_cmd", "tesseract"),
+        dpi=section.get("dpi", DEFAULT_DPI),
+        text_layer=section.get("text_layer", True),
+        pages_per_chunk=section.get("pages_per_chunk", DEFAULT_PAGES_PER_CHUNK),
+        mineru=MinerUSettings(**{attribute: section[key] for key, attribute in _MINERU_KEYS.items() if key in section}),
+    )
+    if settings.backend not in _backends:
+        raise ValueError(f"Unknown OCR backend: {settings.backend}, expected one of {', '.join(ocr_backend_names())}")
+    if not isinstance(settings.tesseract_cmd, str) or not settings.tesseract_cmd.strip():
+        raise ValueError("ocr.tesseract_cmd must be the path or name of the tesseract binary")
+    if isinstance(settings.dpi, bool) or not isinstance(settings.dpi, int) or not 72 <= settings.dpi <= 600:
+        raise ValueError("ocr.dpi must be an integer between 72 and 600")
+    if not isinstance(settings.text_layer, bool):
+        raise ValueError("ocr.text_layer must be true or false")
+    if isinstance(settings.pages_per_chunk, bool) or not isinstance(settings.pages_per_chunk, int) or settings.pages_per_chunk < 0:
+        raise ValueError("ocr.pages_per_chunk must be a positive integer, or 0 to send every page at once")
+    for key in ("mineru_connect_timeout", "mineru_request_timeout", "mineru_max_upload_mb"):
+        value = getattr(settings.mineru, _MINERU_KEYS[key])
+        if isinstance(value, bool) or not isinstance(value, (int, float)) or value <= 0:
+            raise ValueError(f"ocr.{key} must be a positive number")
+    retries = settings.mineru.timeout_retries
+    if isinstance(retries, bool) or not isinstance(retries, int) or not 0 <= retries <= 5:
+        raise ValueError("ocr.mineru_timeout_retries must be an integer between 0 and 5")
+    if not isinstance(settings.mineru.zip_response, bool):
+        raise ValueError("ocr.mineru_zip_response must be true or false")
+    return settings
+
+
+def create_ocr_backend(settings: Optional[OcrSettings] = None) -> OcrBackend:
+    settings = settings or OcrSettings()
+    return _backends[settings.backend](settings)
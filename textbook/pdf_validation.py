@@ -0,0 +1,100 @@
+# Validation of uploaded PDFs before they enter the pipeline
+# Password-protected PDFs are decrypted with the supplied password and structurally damaged PDFs
+# are rewritten by MuPDF's repair pass, so that page rendering and MinerU only ever see a clean file.
+
+import os
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Optional
+
+import pymupdf
+
+# Error codes returned to API clients
+PDF_ENCRYPTED = "pdf_encrypted"
+PDF_WRONG_PASSWORD = "pdf_wrong_password"
+PDF_CORRUPTED = "pdf_corrupted"
+PDF_EMPTY = "pdf_empty"
+
+
+class PDFValidationError(ValueError):
+    """Raised when an uploaded PDF cannot be opened, decrypted or repaired"""
+
+    def __init__(self, code: str, message: str):
+        super().__init__(message)
+        self.code = code
+        self.message = message
+
+
+@dataclass
+class PDFValidationResult:
+    page_count: int
+    decrypted: bool = False
+    repaired: bool = False
+
+
+def prepare_pdf(pdf_path: Path, password: Optional[str] = None) -> PDFValidationResult:
+    """
+    Validate a PDF in place, decrypting and repairing it when needed
+
+    Args:
+        pdf_path: Path to the uploaded PDF, rewritten in place when decrypted or repaired
+        password: Password for encrypted PDFs
+
+    Returns:
+        PDFValidationResult: The page count and which fixes were applied
+    """
+    try:
+        document = pymupdf.open(pdf_path, filetype="pdf")
+    except Exception as e:
+        raise PDFValidationError(PDF_CORRUPTED, f"PDF could not be opened: {e}")
+
+    try:
+        decrypted = False
+        if document.needs_pass:
+            if not password:
+                raise PDFValidationError(PDF_ENCRYPTED, "PDF is password protected, provide the password to upload it")
+            if not document.authenticate(password):
+                raise PDFValidationError(PDF_WRONG_PASSWORD, "The password does not unlock this PDF")
+            decrypted = True
+
+        if len(document) == 0:
+            raise PDFValidationError(PDF_EMPTY, "PDF has no pages")
+
+        # Loading every page surfaces broken page trees that opening alone does not
+        try:
+            for page in document:
+                page.get_text()
+        except Exception as e:
+            raise PDFValidationError(PDF_CORRUPTED, f"PDF page could not be read: {e}")
+
+        repaired = bool(document.is_repaired)
+        if decrypted or repaired:
+            _rewrite(document, pdf_path)
+
+        return PDFValidationResult(page_count=len(document), decrypted=decrypted, repaired=repaired)
+    finally:
+        document.close()
+
+
+def convert_image_to_pdf(image_path: Path, pdf_path: Path) -> None:
+    """Wrap a photo (e.g. of a notebook page) in a single page PDF"""
+    try:
+        with pymupdf.open(image_path) as image:
+            pdf_bytes = image.convert_to_pdf()
+    except Exception as e:
+        raise PDFValidationError(PDF_CORRUPTED, f"Image could not be read: {e}")
+
+    with open(pdf_path, "wb") as f:
+        f.write(pdf_bytes)
+
+
+def _rewrite(document: pymupdf.Document, pdf_path: Path):
+    # Save to a sibling file first so a failed write never leaves a half written upload behind
+    tmp_path = Path(f"{pdf_path}.tmp")
+    try:
+        document.save(tmp_path, garbage=3, deflate=True, encryption=pymupdf.PDF_ENCRYPT_NONE)
+    except Exception as e:
+        if tmp_path.exists():
+            os.remove(tmp_path)
+        raise PDFValidationError(PDF_CORRUPTED, f"PDF could not be repaired: {e}")
+    os.replace(tmp_path, pdf_path)
@@ -0,0 +1,158 @@
+# Job pipelines
+# Ingesting a document is a chain of jobs, OCR, then segmentation, then problem extraction, then the solutions of the
+# problems extracted, each starting once the previous one succeeded. A pipeline declares its jobs as named nodes and
+# the nodes each depends on, forming a graph without cycles. The nodes without dependencies are submitted right away;
+# whenever a job of the pipeline finishes, the nodes whose dependencies all succeeded are submitted with their
+# results, by node name, under params["inputs"]. A node depending on a job that failed, was interrupted or cancelled
+# is blocked, along with the nodes depending on it; resuming the failed job carries on from there once it succeeds.
+# A deferred dependency keeps its nodes waiting. The jobs of a pipeline form a group (see jobs) with the pipeline's
+# ID. Pipelines are kept in memory: after a restart their jobs remain, but nodes not yet submitted are dropped. A
+# finished pipeline is dropped along with its jobs once the job retention sweeper drops them.
+
+import threading
+import uuid
+from dataclasses import dataclass, field
+from typing import Any, Dict, List, Optional
+
+from textbook.jobs import JOB_DEFERRED, JOB_PENDING, JOB_RUNNING, JOB_SUCCEEDED, PRIORITY_NORMAL, Job, JobPool
+
+NODE_WAITING = "waiting"  # Not submitted yet, its dependencies did not all succeed so far
+NODE_BLOCKED = "blocked"  # Not submitted, a job it depends on failed, was interrupted or cancelled
+
+PIPELINE_RUNNING = "running"
+PIPELINE_SUCCEEDED = "succeeded"
+PIPELINE_FAILED = "failed"
+
+
+@dataclass
+class PipelineNode:
+    name: str
+    kind: str
+    params: Dict[str, Any] = field(default_factory=dict)
+    depends_on: List[str] = field(default_factory=list)  # Names of the nodes whose jobs must succeed first
+
+
+@dataclass
+class NodeStatus:
+    name: str
+    kind: str
+    status: str  # waiting, blocked, or the status of its job once submitted
+    depends_on: List[str]
+    job_id: Optional[str] = None
+
+
+@dataclass
+class PipelineStatus:
+    pipeline_id: str
+    status: str  # running until every node succeeded or nothing is left to run, then succeeded or failed
+    nodes: List[NodeStatus]
+
+
+@dataclass
+class Pipeline:
+    pipeline_id: str
+    nodes: List[PipelineNode]  # In dependency order
+    job_ids: Dict[str, str] = field(default_factory=dict)  # Job of each node submitted, by node name
+    priority: str = PRIORITY_NORMAL
+
+
+def order_nodes(nodes: List[PipelineNode]) -> List[PipelineNode]:
+    """The nodes in an order where each comes after its dependencies, raises ValueError for an invalid graph"""
+    if not nodes:
+        raise ValueError("A pipeline needs at least one node")
+    by_name: Dict[str, PipelineNode] = {}
+    for node in nodes:
+        if node.name in by_name:
+            raise ValueError(f"Duplicate pipeline node: {node.name}")
+        by_name[node.name] = node
+    for node in nodes:
+        for dependency in node.depends_on:
+            if dependency not in by_name:
+                raise ValueError(f"Node {node.name} depends on an unknown node: {dependency}")
+    ordered: List[PipelineNode] = []
+    placed: set = set()
+    while len(ordered) < len(nodes):
+        ready = [node for node in nodes if node.name not in placed and all(dependency in placed for dependency in node.depends_on)]
+        if not ready:
+            cycle = sorted(node.name for node in nodes if node.name not in placed)
+            raise ValueError(f"Pipeline nodes depend on each other in a cycle: {', '.join(cycle)}")
+        ordered.extend(ready)
+        placed.update(node.name for node in ready)
+    return ordered
+
+
+class PipelineRunner:
+    """Submits the nodes of pipelines to a job pool as their dependencies succeed"""
+
+    def __init__(self, pool: JobPool):
+        self.pool = pool
+        self._pipelines: Dict[str, Pipeline] = {}
+        self._lock = threading.Lock()
+        pool.on_finished(self._job_finished)
+        pool.on_evicted(self._jobs_evicted)
+
+    def submit(self, nodes: List[PipelineNode], priority: str = PRIORITY_NORMAL) -> Pipeline:
+        """Run a pipeline, submitting the nodes without dependencies right away"""
+        ordered = order_nodes(nodes)
+        for node in ordered:
+            if not self.pool.registered(node.kind):
+                raise ValueError(f"No handler registered for {node.kind} jobs")
+        pipeline = Pipeline(str(uuid.uuid4()), ordered, priority=priority)
+        with self._lock:
+            self._pipelines[pipeline.pipeline_id] = pipeline
+        self._advance(pipeline)
+        return pipeline
+
+    def status(self, pipeline_id: str) -> Optional[PipelineStatus]:
+        """The status of each node of a pipeline, None if there is no such pipeline"""
+        with self._lock:
+            pipeline = self._pipelines.get(pipeline_id)
+            job_ids = dict(pipeline.job_ids) if pipeline is not None else {}
+        if pipeline is None:
+            return None
+        jobs = {name: self.pool.get_job_status(job_id) for name, job_id in job_ids.items()}
+        statuses: Dict[str, str] = {}
+        nodes = []
+        for node in pipeline.nodes:
+            job = jobs.get(node.name)
+            if job is not None:
+                statuses[node.name] = job.status
+            elif any(statuses[dependency] not in (JOB_PENDING, JOB_RUNNING, JOB_SUCCEEDED, JOB_DEFERRED, NODE_WAITING) for dependency in node.depends_on):
+                statuses[node.name] = NODE_BLOCKED
+            else:
+                statuses[node.name] = NODE_WAITING
+            nodes.append(NodeStatus(node.name, node.kind, statuses[node.name], list(node.depends_on), job.job_id if job is not None else None))
+        if all(status == JOB_SUCCEEDED for status in statuses.values()):
+            overall = PIPELINE_SUCCEEDED
+        elif any(status in (JOB_PENDING, JOB_RUNNING, JOB_DEFERRED, NODE_WAITING) for status in statuses.values()):
+            overall = PIPELINE_RUNNING
+        else:
+            overall = PIPELINE_FAILED
+        return PipelineStatus(pipeline_id, overall, nodes)
+
+    def _job_finished(self, job: Job):
+        with self._lock:
+            pipeline = self._pipelines.get(job.group_id) if job.group_id is not None else None
+        if pipeline is not None and job.status == JOB_SUCCEEDED:
+            self._advance(pipeline)
+
+    def _jobs_evicted(self, jobs: List[Job]):
+        # The pool only drops the jobs of a group once none of them is pending, running or deferred
+        with self._lock:
+            for group_id in {job.group_id for job in jobs if job.group_id is not None}:
+                self._pipelines.pop(group_id, None)
+
+    def _advance(self, pipeline: Pipeline):
+        # Submit the nodes whose dependencies all succeeded, with their results; the lock keeps two jobs finishing at
+        # once from submitting a node twice
+        with self._lock:
+            for node in pipeline.nodes:
+                if node.name in pipeline.job_ids:
+                    continue
+                dependencies = [self.pool.get_job_status(pipeline.job_ids[name]) if name in pipeline.job_ids else None for name in node.depends_on]
+                if not all(dependency is not None and dependency.status == JOB_SUCCEEDED for dependency in dependencies):
+                    continue
+                inputs = {name: dependency.result for name, dependency in zip(node.depends_on, dependencies) if dependency is not None}
+                params = {**node.params, "inputs": inputs} if inputs else dict(node.params)
+                job = self.pool.submit(node.kind, params, pipeline.priority, group_id=pipeline.pipeline_id)
+                pipeline.job_ids[node.name] = job.job_id
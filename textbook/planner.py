@@ -0,0 +1,80 @@
+# Study planner
+# Plans the coming days of study: the reviews and new cards of each day as forecast from the user's cards (see
+# forecast), with refresh sessions of the chapters about to be forgotten (see decay) on the days they are suggested,
+# so they are practiced before their projected mastery drops below the threshold, and the topics of the user's course
+# syllabus (see syllabus) on the day their week starts. A plan focused on a smart filter (see smart_filters) only
+# refreshes the chapters the filter matches exercises of. The digest sums up today: the reviews due, the chapter
+# quizzes waiting to be taken, the chapters about to be forgotten and what the course covers this week and next,
+# weakest chapter first.
+
+from collections import defaultdict
+from dataclasses import dataclass
+from datetime import date, datetime, timezone
+from typing import Dict, List, Optional, Set
+
+from textbook.chapter_quiz import ChapterQuizProgress, chapter_quiz_progress
+from textbook.database import TextBookDatabase
+from textbook.decay import FORGET_THRESHOLD, REFRESH_HORIZON_DAYS, RefreshSuggestion, refresh_suggestions
+from textbook.forecast import forecast_load
+from textbook.preferences import Preferences
+from textbook.syllabus import UPCOMING_DAYS, SyllabusFocus, upcoming_topics
+
+
+@dataclass
+class PlanDay:
+    day: date
+    reviews: int
+    new_cards: int
+    refresh: List[RefreshSuggestion]  # Chapters to refresh that day
+    syllabus: List[SyllabusFocus]  # Topics of the course week starting that day
+
+
+@dataclass
+class StudyDigest:
+    user_id: str
+    due_reviews: int
+    chapter_quizzes: List[ChapterQuizProgress]  # Pending, newest first
+    refresh: List[RefreshSuggestion]  # Chapters forgotten within REFRESH_HORIZON_DAYS, soonest first
+    coming_up: List[SyllabusFocus]  # Syllabus topics of this week and the next UPCOMING_DAYS, weakest first
+
+
+def study_plan(
+    database: TextBookDatabase,
+    preferences: Preferences,
+    days: int,
+    book_id: Optional[int] = None,
+    threshold: float = FORGET_THRESHOLD,
+    now: Optional[datetime] = None,
+    chapter_ids: Optional[Set[int]] = None,
+) -> List[PlanDay]:
+    """The reviews, new cards and refresh sessions of each of the next days, starting today (UTC), refreshing chapter_ids only if given"""
+    now = now or datetime.now(timezone.utc)
+    loads = forecast_load(database, preferences, days, book_id=book_id, now=now)
+    refresh: Dict[date, List[RefreshSuggestion]] = defaultdict(list)
+    for suggestion in refresh_suggestions(database, now, book_id, threshold, horizon_days=days):
+        if chapter_ids is None or suggestion.chapter_id in chapter_ids:
+            refresh[suggestion.refresh_on].append(suggestion)
+    syllabus: Dict[date, List[SyllabusFocus]] = defaultdict(list)
+    for focus in upcoming_topics(database, preferences.user_id, book_id, now, days):
+        # The week under way is planned for today
+        syllabus[max(focus.topic.week_starts_at.date(), now.date())].append(focus)
+    return [PlanDay(load.day, load.reviews, load.new_cards, refresh.get(load.day, []), syllabus.get(load.day, [])) for load in loads]
+
+
+def study_digest(database: TextBookDatabase, user_id: str, book_id: Optional[int] = None, now: Optional[datetime] = None) -> StudyDigest:
+    """What a user has to do today, of one book or all"""
+    now = now or datetime.now(timezone.utc)
+    quizzes = []
+    for quiz in database.get_chapter_quizzes(user_id):
+        if book_id is not None and quiz.book_id != book_id:
+            continue
+        progress = chapter_quiz_progress(database, quiz, now)
+        if progress.quiz.completed_at is None:
+            quizzes.append(progress)
+    return StudyDigest(
+        user_id=user_id,
+        due_reviews=database.count_due_cards(user_id, now, book_id),
+        chapter_quizzes=quizzes,
+        refresh=refresh_suggestions(database, now, book_id, horizon_days=REFRESH_HORIZON_DAYS),
+        coming_up=upcoming_topics(database, user_id, book_id, now, UPCOMING_DAYS),
+    )
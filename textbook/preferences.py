@@ -0,0 +1,78 @@
+# Study preferences per user
+# There are no accounts: clients identify the learner by an id of their choosing, DEFAULT_USER_ID when a single
+# person uses the app. Users without stored preferences get the defaults below, so every consumer (quiz assembly,
+# review batches, exercise and flashcard generation) reads preferences through load_preferences instead of the table.
+
+from dataclasses import dataclass, replace
+from typing import Optional
+
+from textbook.database import TextBookDatabase
+
+DEFAULT_USER_ID = "default"
+
+MIN_DIFFICULTY = 1
+MAX_DIFFICULTY = 5
+DEFAULT_DIFFICULTY = 3
+DEFAULT_DAILY_NEW_CARD_LIMIT = 20
+
+# How practice is scheduled, stored on user_preferences_info.scheduler
+SCHEDULER_INTERLEAVED = "interleaved"  # Mix the current chapter with weak and due earlier chapters
+SCHEDULER_BLOCKED = "blocked"  # Only practice the current chapter
+SCHEDULERS = (SCHEDULER_INTERLEAVED, SCHEDULER_BLOCKED)
+
+
+@dataclass
+class Preferences:
+    user_id: str
+    default_difficulty: int = DEFAULT_DIFFICULTY
+    daily_new_card_limit: int = DEFAULT_DAILY_NEW_CARD_LIMIT
+    output_language: Optional[str] = None  # None keeps the language of the source
+    notifications_enabled: bool = False
+    notification_hour: Optional[int] = None  # UTC
+    scheduler: str = SCHEDULER_INTERLEAVED
+    load_balancing: bool = False  # Move review due dates by a few days to avoid pileups
+
+
+def validate_preferences(preferences: Preferences) -> None:
+    if not MIN_DIFFICULTY <= preferences.default_difficulty <= MAX_DIFFICULTY:
+        raise ValueError(f"default_difficulty must be between {MIN_DIFFICULTY} and {MAX_DIFFICULTY}")
+    if preferences.daily_new_card_limit < 0:
+        raise ValueError("daily_new_card_limit must not be negative")
+    if preferences.notification_hour is not None and not 0 <= preferences.notification_hour <= 23:
+        raise ValueError("notification_hour must be between 0 and 23")
+    if preferences.scheduler not in SCHEDULERS:
+        raise ValueError(f"Invalid scheduler: {preferences.scheduler}, expected one of {', '.join(SCHEDULERS)}")
+
+
+def load_preferences(database: TextBookDatabase, user_id: str = DEFAULT_USER_ID) -> Preferences:
+    """The stored preferences of a user, or the defaults"""
+    stored = database.get_user_preferences(user_id)
+    if stored is None:
+        return Preferences(user_id)
+    return Preferences(
+        user_id=user_id,
+        default_difficulty=stored.default_difficulty,
+        daily_new_card_limit=stored.daily_new_card_limit,
+        output_language=stored.output_language,
+        notifications_enabled=stored.notifications_enabled,
+        notification_hour=stored.notification_hour,
+        scheduler=stored.scheduler,
+        load_balancing=stored.load_balancing,
+    )
+
+
+def update_preferences(database: TextBookDatabase, user_id: str, **changes) -> Preferences:
+    """Change some preferences of a user, keeping the others"""
+    preferences = replace(load_preferences(database, user_id), **changes)
+    validate_preferences(preferences)
+    database.save_user_preferences(
+        user_id,
+        preferences.default_difficulty,
+        preferences.daily_new_card_limit,
+        preferences.output_language,
+        preferences.notifications_enabled,
+        preferences.notification_hour,
+        preferences.scheduler,
+        preferences.load_balancing,
+    )
+    return preferences
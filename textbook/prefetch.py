@@ -0,0 +1,89 @@
+# Prefetching
+# Generating problems or a summary takes the LLM a while, so what a user will likely want next is generated before
+# they ask: when they read a chapter of a document, the problems of the next chapter and the document's summary are
+# submitted as low priority jobs, which wait behind everything users asked for. Nothing is generated twice, what is
+# already stored or was prefetched since the server started is skipped, and so are the kinds of jobs whose category
+# has a single worker, which low priority jobs never take.
+# Prefetching spends the provider's quota on content nobody may read, so it stays within a budget set in config.toml:
+#   prefetch = true               # off with false
+#   prefetch_max_pending = 2      # low priority jobs pending or running at once
+#   prefetch_daily_limit = 20     # jobs prefetched per day (UTC)
+
+import threading
+from dataclasses import dataclass
+from datetime import date, datetime, timezone
+from typing import List, Optional, Set, Tuple
+
+from textbook.database import TextBookDatabase
+from textbook.jobs import Job, JobPool, JOB_KIND_DOCUMENT_SUMMARY, JOB_KIND_PROBLEM_EXTRACTION, PRIORITY_LOW
+from textbook.problems import Chapter, document_chapters
+
+DEFAULT_MAX_PENDING = 2
+DEFAULT_DAILY_LIMIT = 20
+
+
+@dataclass
+class PrefetchSettings:
+    enabled: bool = True
+    max_pending: int = DEFAULT_MAX_PENDING  # Low priority jobs pending or running at once
+    daily_limit: int = DEFAULT_DAILY_LIMIT  # Jobs prefetched per day (UTC)
+
+
+def prefetch_settings_from_config(config: dict) -> PrefetchSettings:
+    """The prefetch settings of config.toml, raises ValueError for invalid ones"""
+    settings = PrefetchSettings(
+        enabled=bool(config.get("prefetch", True)),
+        max_pending=config.get("prefetch_max_pending", DEFAULT_MAX_PENDING),
+        daily_limit=config.get("prefetch_daily_limit", DEFAULT_DAILY_LIMIT),
+    )
+    if settings.max_pending < 0 or settings.daily_limit < 0:
+        raise ValueError("prefetch_max_pending and prefetch_daily_limit cannot be negative")
+    return settings
+
+
+def next_chapter(database: TextBookDatabase, document_id: int, section_index: int) -> Optional[Chapter]:
+    """The chapter after the one a section belongs to, None for the last chapter or documents without headings"""
+    chapters = [chapter for chapter in document_chapters(database, document_id) if chapter.section_index is not None]
+    return next((chapter for chapter in chapters if chapter.section_index > section_index), None)
+
+
+class Prefetcher:
+    def __init__(self, database: TextBookDatabase, job_pool: JobPool, settings: Optional[PrefetchSettings] = None):
+        self.database = database
+        self.job_pool = job_pool
+        self.settings = settings or PrefetchSettings()
+        self._prefetched: Set[Tuple[str, int, Optional[int]]] = set()  # Kind, document and section of the jobs submitted
+        self._day: Optional[date] = None
+        self._submitted_today = 0
+        self._lock = threading.Lock()
+
+    def chapter_read(self, document_id: int, section_index: int, now: Optional[datetime] = None) -> List[Job]:
+        """Prefetch what a user reading a section of a document will likely want next, returns the jobs submitted"""
+        if not self.settings.enabled:
+            return []
+        document = self.database.get_document(document_id)
+        if document is None:
+            return []
+        wanted: List[Tuple[str, int, Optional[int]]] = []
+        chapter = next_chapter(self.database, document_id, section_index)
+        if chapter is not None and not self.database.get_problems(document_id, chapter.section_index):
+            wanted.append((JOB_KIND_PROBLEM_EXTRACTION, document_id, chapter.section_index))
+        if not document.summary:
+            wanted.append((JOB_KIND_DOCUMENT_SUMMARY, document_id, None))
+
+        today = (now or datetime.now(timezone.utc)).date()
+        jobs = []
+        with self._lock:
+            if self._day != today:
+                self._day, self._submitted_today = today, 0
+            for key in wanted:
+                kind, _, chapter_index = key
+                if key in self._prefetched or not self.job_pool.runs_low_priority(kind):
+                    continue
+                if self._submitted_today >= self.settings.daily_limit or self.job_pool.low_priority_jobs() >= self.settings.max_pending:
+                    break
+                params = {"document_id": document_id} if chapter_index is None else {"document_id": document_id, "section_index": chapter_index}
+                jobs.append(self.job_pool.submit(kind, params, PRIORITY_LOW))
+                self._prefetched.add(key)
+                self._submitted_today += 1
+        return jobs
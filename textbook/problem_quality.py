@@ -0,0 +1,258 @@
+# Problem quality
+# The LLM extracting problems sometimes makes them up, garbles a statement across a page break or lists a problem
+# twice when it spans two batches of pages. Every extracted problem is checked before it is studied:
+# - grounding: the share of the statement's words found on the pages it was extracted from, low for made up problems
+# - answerability and ambiguity: the LLM judges whether the problem can be answered from its statement and the
+#   passages of the document retrieved for it (see explain), and whether it can be read in several ways
+# - duplicates: a statement sharing almost all its words with an earlier problem of the document
+# The score is the grounding, halved for unanswerable problems and lowered for ambiguous ones; duplicates score 0.
+# Problems below the threshold are extracted again from their pages once, and flagged for review when they still
+# score below it. Flagged problems are left out of the new problems to review until a reviewer approves them.
+# Extracted problems are free response, so there are no answer choices whose distractors could duplicate the answer.
+# The checks are set in config.toml:
+#   problem_quality = true
+#   problem_quality_threshold = 0.6
+#   problem_quality_regenerate = true
+
+from dataclasses import dataclass, field
+from typing import Callable, Dict, List, Optional, Sequence, Tuple
+
+from pydantic import BaseModel
+
+from textbook.content import CHUNK_PAGE, CHUNK_SECTION
+from textbook.database import ContentChunkInfo, ProblemInfo, TextBookDatabase
+from textbook.explain import PASSAGE_CHARS, Passage, document_passages, retrieve_passages, words
+from textbook.jobs import CancellationToken
+from textbook.model import LLM
+
+QUALITY_PASSED = "passed"
+QUALITY_FLAGGED = "flagged"
+QUALITY_APPROVED = "approved"
+QUALITY_REJECTED = "rejected"
+REVIEW_DECISIONS = (QUALITY_APPROVED, QUALITY_REJECTED)
+
+DEFAULT_QUALITY_THRESHOLD = 0.6
+DUPLICATE_SIMILARITY = 0.9  # Share of the words of two statements they have in common
+MIN_GROUNDING = 0.8  # Below, the issues say how much of the statement is not on its pages
+SOURCE_PAGES = 2  # The page a problem starts on and the next, where it may continue
+UNANSWERABLE_FACTOR = 0.5
+AMBIGUOUS_FACTOR = 0.7
+RETRIEVED_PASSAGES = 3
+
+
+@dataclass
+class ProblemQualitySettings:
+    enabled: bool = True
+    threshold: float = DEFAULT_QUALITY_THRESHOLD  # Problems scoring below are extracted again or flagged
+    regenerate: bool = True  # Extract a problem scoring below the threshold again before flagging it
+
+
+class QualityJudgmentSchema(BaseModel):
+    answerable: bool
+    ambiguous: bool
+    issues: List[str]
+
+
+class RestatedProblemSchema(BaseModel):
+    found: bool
+    statement: str
+
+
+@dataclass
+class QualityReport:
+    problem_id: int
+    score: float
+    grounding: float
+    answerable: bool
+    ambiguous: bool
+    duplicate_of: Optional[int] = None  # The earlier problem of the document with the same statement
+    issues: List[str] = field(default_factory=list)
+    regenerated: bool = False
+
+
+def problem_quality_settings_from_config(config: dict) -> ProblemQualitySettings:
+    """The problem quality settings of config.toml, raises ValueError for invalid ones"""
+    settings = ProblemQualitySettings(
+        enabled=bool(config.get("problem_quality", True)),
+        threshold=config.get("problem_quality_threshold", DEFAULT_QUALITY_THRESHOLD),
+        regenerate=bool(config.get("problem_quality_regenerate", True)),
+    )
+    if isinstance(settings.threshold, bool) or not isinstance(settings.threshold, (int, float)) or not 0 <= settings.threshold <= 1:
+        raise ValueError("problem_quality_threshold must be a number between 0 and 1")
+    return settings
+
+
+def grounding(statement: str, source: str) -> float:
+    """The share of the distinct words of a statement found in its source, 1 for statements without words"""
+    statement_words = set(words(statement))
+    if not statement_words:
+        return 1.0
+    return len(statement_words & set(words(source))) / len(statement_words)
+
+
+def similarity(first: str, second: str) -> float:
+    """The share of the distinct words of two statements they have in common"""
+    first_words, second_words = set(words(first)), set(words(second))
+    if not first_words or not second_words:
+        return 0.0
+    return len(first_words & second_words) / len(first_words | second_words)
+
+
+def duplicates(problems: Sequence[ProblemInfo]) -> Dict[int, int]:
+    """The problems repeating an earlier one, by problem, with the ID of the earlier one"""
+    found: Dict[int, int] = {}
+    for position, problem in enumerate(problems):
+        for earlier in problems[:position]:
+            if earlier.problem_id not in found and similarity(problem.statement, earlier.statement) >= DUPLICATE_SIMILARITY:
+                found[problem.problem_id] = earlier.problem_id
+                break
+    return found
+
+
+def problem_source(database: TextBookDatabase, problem: ProblemInfo) -> Tuple[str, int, int]:
+    """The text a problem was extracted from, its pages or its section, with its range in the document's markdown"""
+    if problem.page_number is not None:
+        pages = [
+            page for page in database.get_content_chunks(problem.document_id, CHUNK_PAGE)
+            if problem.page_number <= page.chunk_index < problem.page_number + SOURCE_PAGES
+        ]
+        if pages:
+            return "\n\n".join(page.text for page in pages), pages[0].char_start, pages[-1].char_end
+    if problem.section_index is not None:
+        section = database.get_content_chunk(problem.document_id, CHUNK_SECTION, problem.section_index)
+        if section is not None:
+            return section.text, section.char_start, section.char_end
+    return "", 0, 0
+
+
+def quality_prompt(problem: ProblemInfo, passages: List[Passage]) -> str:
+    related = "\n\n".join(f"[{passage.title or f'Page {passage.page_start}'}]\n{passage.text[:PASSAGE_CHARS]}" for passage in passages) or "None"
+    return f"""
+    Judge the following problem, extracted from a textbook, with rules:
+    - answerable is whether a student can solve it from its statement and the definitions and results of the passages below, without missing data or figures
+    - ambiguous is whether the statement can be read in several ways that lead to different answers
+    - issues lists each problem found in one short sentence, empty if there are none
+
+    Problem:
+     {problem.statement}
+
+    Related passages of the textbook:
+     {related}
+    """
+
+
+def restate_prompt(problem: ProblemInfo, source: str) -> str:
+    number = f"problem {problem.problem_number}" if problem.problem_number else "the problem starting with the words below"
+    return f"""
+    Find {number} in the following pages and give its full statement exactly as printed, in LaTeX for mathematics,
+    including its parts (a), (b), ..., without any solution or hint. found is false if the pages do not state it.
+
+    The statement extracted before, possibly garbled:
+     {problem.statement}
+
+    Pages:
+     {source}
+    """
+
+
+def check_problem(
+    database: TextBookDatabase,
+    llm: LLM,
+    problem: ProblemInfo,
+    chunks: Sequence[ContentChunkInfo],
+    duplicate_of: Optional[int] = None,
+) -> QualityReport:
+    """Score a problem on its grounding in its pages, its answerability and ambiguity, and whether it repeats another"""
+    source, start, end = problem_source(database, problem)
+    # Problems of documents without pages or sections are looked for in the whole document
+    problem_grounding = grounding(problem.statement, source or "\n\n".join(chunk.text for chunk in chunks))
+    passages = retrieve_passages(chunks, problem.statement, start, end, RETRIEVED_PASSAGES)
+    judgment = llm.prompt_with_schema(quality_prompt(problem, passages), schema=QualityJudgmentSchema)
+    issues = [issue.strip() for issue in judgment.issues if issue.strip()]
+    if problem_grounding < MIN_GROUNDING:
+        issues.insert(0, f"Only {problem_grounding:.0%} of its words are on the pages it was extracted from")
+    score = problem_grounding * (1 if judgment.answerable else UNANSWERABLE_FACTOR) * (AMBIGUOUS_FACTOR if judgment.ambiguous else 1)
+    if duplicate_of is not None:
+        issues.insert(0, f"Duplicate of problem {duplicate_of}")
+        score = 0.0
+    return QualityReport(problem.problem_id, round(score, 3), round(problem_grounding, 3), judgment.answerable, judgment.ambiguous, duplicate_of, issues)
+
+
+def regenerate_problem(database: TextBookDatabase, llm: LLM, problem: ProblemInfo) -> Optional[ProblemInfo]:
+    """Extract a problem again from its pages, None when they do not state it or give the same statement"""
+    source, _, _ = problem_source(database, problem)
+    if not source:
+        return None
+    restated = llm.prompt_with_schema(restate_prompt(problem, source), schema=RestatedProblemSchema)
+    statement = restated.statement.strip()
+    if not restated.found or not statement or statement == problem.statement:
+        return None
+    return database.update_problem(problem.problem_id, statement=statement)
+
+
+def score_problems(
+    database: TextBookDatabase,
+    llm: LLM,
+    document_id: int,
+    problem_ids: Optional[List[int]] = None,
+    settings: Optional[ProblemQualitySettings] = None,
+    regenerate_llm: Optional[LLM] = None,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+) -> List[QualityReport]:
+    """
+    Check the problems of a document and store their score and status
+
+    Args:
+        problem_ids: Only check these problems, by default every problem of the document
+        regenerate_llm: The model extracting a problem scoring below the threshold again, by default llm
+
+    Returns:
+        List[QualityReport]: The reports of the problems checked, after regeneration
+    """
+    settings = settings or ProblemQualitySettings()
+    problems = database.get_problems(document_id)
+    repeated = duplicates(problems)
+    checked = [problem for problem in problems if problem_ids is None or problem.problem_id in problem_ids]
+    chunks = document_passages(database, document_id)
+    reports = []
+    for position, problem in enumerate(checked):
+        if cancellation_token is not None:
+            cancellation_token.raise_if_cancelled()
+        if report_progress is not None:
+            report_progress("quality", 100 * position / len(checked), f"problem {position + 1}/{len(checked)}")
+        duplicate_of = repeated.get(problem.problem_id)
+        report = check_problem(database, llm, problem, chunks, duplicate_of)
+        # Extracting a duplicate again gives the same problem
+        if report.score < settings.threshold and duplicate_of is None and settings.regenerate:
+            regenerated = regenerate_problem(database, regenerate_llm or llm, problem)
+            if regenerated is not None:
+                report = check_problem(database, llm, regenerated, chunks)
+                report.regenerated = True
+        database.update_problem(
+            problem.problem_id,
+            quality_score=report.score,
+            quality_status=QUALITY_PASSED if report.score >= settings.threshold else QUALITY_FLAGGED,
+            quality_issues="\n".join(report.issues) or None,
+        )
+        reports.append(report)
+    return reports
+
+
+def review_problem(database: TextBookDatabase, problem_id: int, decision: str) -> ProblemInfo:
+    """
+    Approve a problem the quality checks flagged, or reject it
+
+    Raises:
+        ValueError: If the problem does not exist or the decision is not one of REVIEW_DECISIONS
+    """
+    if decision not in REVIEW_DECISIONS:
+        raise ValueError(f"Unsupported decision: {decision}, expected one of {', '.join(REVIEW_DECISIONS)}")
+    problem = database.update_problem(problem_id, quality_status=decision)
+    if problem is None:
+        raise ValueError(f"Problem not found: {problem_id}")
+    return problem
+
+
+def problem_issues(problem: ProblemInfo) -> List[str]:
+    return problem.quality_issues.split("\n") if problem.quality_issues else []
@@ -0,0 +1,202 @@
+# Problem extraction
+# Turns the OCR output of a problem set or textbook into individual problems to study. Each chapter, a top-level
+# section of the document's chunked markdown, is sent to the LLM a few pages per prompt, the part of each page that
+# belongs to the chapter marked with its page number, and the LLM answers with the problems stated there: their
+# number, statement, difficulty hint and the figures they refer to. Extracting a chapter again replaces its problems.
+# A document's ingestion settings can keep only so many problems per chapter, the LLM listing first those closest to
+# a target difficulty.
+# Documents without headings are handled as a single chapter of all their pages.
+# The pages of a problem with figures are rendered from the uploaded PDF for the models that can see them.
+# Each problem records what it was extracted with: the model, temperature and seed, the version of the prompt and the
+# digest of its chapter's text, so that a problem set extracted with a seed at temperature 0 can be extracted again.
+
+import hashlib
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Callable, List, Optional, Tuple
+
+from PIL import Image
+from pydantic import BaseModel
+
+from textbook.content import CHUNK_PAGE, CHUNK_SECTION
+from textbook.database import ContentChunkInfo, ProblemInfo, TextBookDatabase
+from textbook.generation import GenerationRecord
+from textbook.jobs import CancellationToken
+from textbook.model import LLM
+from textbook.rendering import pdf_to_images
+from textbook.watermark import Watermark
+
+PROBLEM_EXTRACTION_PAGES_PER_PROMPT = 4
+PROBLEM_EXTRACTION_PROMPT_VERSION = 1  # Increased whenever problem_extraction_prompt changes
+WHOLE_DOCUMENT = "Whole document"  # Title of the chapter of documents without headings
+PROBLEM_IMAGE_PAGES = 2  # The page a problem starts on and the next, where its figure often is
+
+
+class ProblemSchema(BaseModel):
+    number: str
+    statement: str
+    difficulty_hint: str
+    figures: List[str]
+    page_number: int
+
+
+class ProblemSetSchema(BaseModel):
+    problems: List[ProblemSchema]
+
+
+def problem_extraction_prompt(chapter_title: str, pages: str, target_difficulty: Optional[int] = None) -> str:
+    # Without a target the problems are listed in the order of the text
+    order = f"- list the problems closest to difficulty {target_difficulty} on a scale of 1 (routine) to 5 (hardest) first\n    " if target_difficulty is not None else ""
+    return f"""
+    Extract the problems (exercises, questions, tasks) stated in the following pages of "{chapter_title}" with rules:
+    - number is the number or label printed with the problem (e.g. 3.2, 7b), empty if it has none
+    - statement is the full statement in LaTeX for mathematics, including its parts (a), (b), ..., without any solution or hint
+    - difficulty_hint is how the text marks the difficulty of the problem (e.g. *, **, hard, optional), empty if it marks none
+    - figures are the labels of the figures and tables the problem refers to (e.g. Figure 2.3), empty if none
+    - page_number is the number in the [Page n] marker of the page the problem starts on, 0 if there are no markers
+    - only extract problems posed to the reader, not worked examples
+    {order}
+    Pages:
+     {pages}
+    """
+
+
+@dataclass
+class Chapter:
+    section_index: Optional[int]  # None for documents without headings
+    title: str
+    text: str
+    char_start: int  # Offsets in the document's markdown
+    char_end: int
+    page_start: Optional[int] = None
+    page_end: Optional[int] = None
+
+
+def document_chapters(database: TextBookDatabase, document_id: int, section_index: Optional[int] = None) -> List[Chapter]:
+    """The chapters of a document, its top-level sections or only the given section"""
+    sections = database.get_content_chunks(document_id, CHUNK_SECTION)
+    if section_index is not None:
+        sections = [section for section in sections if section.chunk_index == section_index]
+        if not sections:
+            raise ValueError(f"Section {section_index} not found in the OCR output of document {document_id}")
+    elif sections:
+        top_level = min(section.level or 1 for section in sections)
+        sections = [section for section in sections if (section.level or 1) == top_level]
+    if sections:
+        return [Chapter(section.chunk_index, section.title or f"Section {section.chunk_index}", section.text, section.char_start, section.char_end, section.page_start, section.page_end) for section in sections]
+
+    pages = database.get_content_chunks(document_id, CHUNK_PAGE)
+    if not pages:
+        raise ValueError(f"Document {document_id} has no OCR output, run its OCR first")
+    return [Chapter(None, WHOLE_DOCUMENT, "\n\n".join(page.text for page in pages), pages[0].char_start, pages[-1].char_end, pages[0].chunk_index, pages[-1].chunk_index)]
+
+
+def _chapter_pages(chapter: Chapter, pages: List[ContentChunkInfo]) -> List[Tuple[int, str]]:
+    # (page number, text) of the parts of pages within the chapter, a page shared with the previous or next chapter
+    # only contributes its part of this one
+    chapter_pages = []
+    for page in pages:
+        start, end = max(chapter.char_start, page.char_start), min(chapter.char_end, page.char_end)
+        if start < end:
+            chapter_pages.append((page.chunk_index, page.text[start - page.char_start:end - page.char_start]))
+    return chapter_pages
+
+
+def extraction_batches(chapter: Chapter, pages: List[ContentChunkInfo]) -> List[Tuple[List[int], str]]:
+    """The page numbers and text of every extraction prompt of a chapter, the chapter as a whole without pages"""
+    chapter_pages = _chapter_pages(chapter, pages)
+    batches = [chapter_pages[start:start + PROBLEM_EXTRACTION_PAGES_PER_PROMPT] for start in range(0, len(chapter_pages), PROBLEM_EXTRACTION_PAGES_PER_PROMPT)]
+    if not batches:
+        return [([], chapter.text)]
+    return [([page_number for page_number, _ in batch], "\n\n".join(f"[Page {page_number}]\n{page_text}" for page_number, page_text in batch)) for batch in batches]
+
+
+def extract_problems(
+    database: TextBookDatabase,
+    llm: LLM,
+    document_id: int,
+    section_index: Optional[int] = None,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+    problems_per_section: Optional[int] = None,
+    target_difficulty: Optional[int] = None,
+    generation: Optional[GenerationRecord] = None,
+) -> List[int]:
+    """
+    Extract the problems of a document's chapters with the LLM, replacing those extracted before
+
+    Args:
+        section_index: Only extract the problems of this section, by default those of every chapter
+        problems_per_section: Keep at most this many problems per chapter, by default every problem
+        target_difficulty: Keep the problems closest to this difficulty, from 1 to 5, when there are more
+        generation: What the LLM prompts with, recorded with the problems
+        report_progress: Gets the stage, percentage and detail before each chapter, like JobHandle.report_progress
+
+    Returns:
+        List[int]: The IDs of the problems stored
+    """
+    chapters = document_chapters(database, document_id, section_index)
+    pages = database.get_content_chunks(document_id, CHUNK_PAGE)
+    problems = []
+    for position, chapter in enumerate(chapters):
+        if cancellation_token is not None:
+            cancellation_token.raise_if_cancelled()
+        if report_progress is not None:
+            report_progress("extract", 100 * position / len(chapters), f"chapter {position + 1}/{len(chapters)} {chapter.title}")
+
+        source_sha256 = hashlib.sha256(chapter.text.encode("utf-8")).hexdigest()
+        chapter_problems = []
+        # Without pages the chapter is sent as a whole, its problems have no page
+        for page_numbers, text in extraction_batches(chapter, pages):
+            extracted = llm.prompt_with_schema(problem_extraction_prompt(chapter.title, text, target_difficulty), schema=ProblemSetSchema)
+            for problem in extracted.problems:
+                if not problem.statement.strip():
+                    continue
+                figures = [figure.strip() for figure in problem.figures if figure.strip()]
+                chapter_problems.append(ProblemInfo(
+                    problem_number=problem.number.strip() or None,
+                    statement=problem.statement.strip(),
+                    difficulty_hint=problem.difficulty_hint.strip() or None,
+                    figures="\n".join(figures) or None,
+                    page_number=(problem.page_number if problem.page_number in page_numbers else page_numbers[0]) if page_numbers else None,
+                    section_index=chapter.section_index,
+                    model_name=generation.model_name if generation else None,
+                    temperature=generation.temperature if generation else None,
+                    seed=generation.seed if generation else None,
+                    prompt_version=PROBLEM_EXTRACTION_PROMPT_VERSION,
+                    source_sha256=source_sha256,
+                ))
+        problems.extend(chapter_problems[:problems_per_section])
+
+    if cancellation_token is not None:
+        cancellation_token.raise_if_cancelled()
+    # Problems of the chapters extracted are replaced, a whole document replaces all of them
+    section_indexes = [chapter.section_index for chapter in chapters] if section_index is not None else None
+    return database.replace_problems(document_id, problems, section_indexes)
+
+
+def problem_set_markdown(title: str, problems: List[ProblemInfo], watermark: Optional[Watermark] = None) -> str:
+    """A problem set to share as markdown, headed and marked with the fingerprint of the export if given"""
+    parts = [f"# {title}"]
+    for position, problem in enumerate(problems, 1):
+        parts.append(f"## Problem {problem.problem_number or position}")
+        parts.append(watermark.text(problem.statement) if watermark else problem.statement)
+    markdown = "\n\n".join(parts) + "\n"
+    return watermark.header() + markdown if watermark else markdown
+
+
+def problem_figures(problem: ProblemInfo) -> List[str]:
+    return problem.figures.split("\n") if problem.figures else []
+
+
+def problem_page_images(database: TextBookDatabase, problem: ProblemInfo, uploads_dir: str) -> List[Image.Image]:
+    """The pages a problem with figures is on, rendered from its uploaded PDF, empty without figures or PDF"""
+    if not problem.figures or problem.page_number is None:
+        return []
+    document = database.get_document(problem.document_id)
+    if document is None or not document.source_path or not document.source_path.lower().endswith(".pdf"):
+        return []
+    pdf_path = Path(uploads_dir) / document.source_path
+    if not pdf_path.exists():
+        return []
+    return pdf_to_images(pdf_path, list(range(problem.page_number, problem.page_number + PROBLEM_IMAGE_PAGES)))
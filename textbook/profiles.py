@@ -0,0 +1,139 @@
+# LLM profiles
+# One model does not fit every task: summaries and glossaries of long documents are many prompts a cheap model
+# answers well, while solutions and answer checks are few prompts that need a model that reasons. Named profiles in
+# config.toml each select a model, and the [routing] section sends a task to a profile. Tasks without a route, and
+# servers without profiles, use the default model (LLM_MODEL_NAME), the "default" profile. Requests starting a task
+# can pick another profile by name.
+#   [profiles.fast]
+#   model = "gemini-2.5-flash-lite"
+#   [profiles.reasoning]
+#   model = "gemini-2.5-pro"
+#   schema_mode = "schema"
+#   [routing]
+#   document_summary = "fast"
+#   glossary = "fast"
+#   solution = "reasoning"
+#   answer_check = "reasoning"
+# The profiles share the provider's API key, safety settings and rate limits. The generation parameters of a task
+# (see generation) apply to whichever profile runs it.
+
+from dataclasses import dataclass
+from typing import Callable, Dict, List, Optional
+
+from textbook.jobs import (
+    JOB_KIND_ANSWER_CHECK,
+    JOB_KIND_DOCUMENT_SUMMARY,
+    JOB_KIND_GLOSSARY,
+    JOB_KIND_HINT_LADDER,
+    JOB_KIND_PROBLEM_EXTRACTION,
+    JOB_KIND_PROBLEM_QUALITY,
+    JOB_KIND_SEGMENTATION,
+    JOB_KIND_SOLUTION,
+    JOB_KIND_SYLLABUS_IMPORT,
+)
+from textbook.generation import GenerationSettings
+from textbook.model import LLM, SCHEMA_MODE, SCHEMA_MODES
+
+DEFAULT_PROFILE = "default"
+TASK_EXPLAIN = "explain"  # Explanations of selected passages, streamed rather than run as a job
+TASKS = (
+    JOB_KIND_PROBLEM_EXTRACTION,
+    JOB_KIND_DOCUMENT_SUMMARY,
+    JOB_KIND_GLOSSARY,
+    JOB_KIND_SEGMENTATION,
+    JOB_KIND_SOLUTION,
+    JOB_KIND_HINT_LADDER,
+    JOB_KIND_ANSWER_CHECK,
+    JOB_KIND_SYLLABUS_IMPORT,
+    JOB_KIND_PROBLEM_QUALITY,
+    TASK_EXPLAIN,
+)
+
+
+@dataclass
+class ModelProfile:
+    name: str
+    model_name: str
+    schema_mode: str = SCHEMA_MODE
+
+
+def profiles_from_config(config: dict) -> Dict[str, ModelProfile]:
+    """The profiles of config.toml by name, raises ValueError for invalid ones"""
+    profiles = {}
+    for name, section in config.get("profiles", {}).items():
+        if name == DEFAULT_PROFILE:
+            raise ValueError(f"The profile name {DEFAULT_PROFILE} is reserved for the model of LLM_MODEL_NAME")
+        if not isinstance(section, dict):
+            raise ValueError(f"profiles.{name} must be a table with a model")
+        unknown = set(section) - {"model", "schema_mode"}
+        if unknown:
+            raise ValueError(f"Unknown settings of profile {name}: {', '.join(sorted(unknown))}")
+        model_name = section.get("model")
+        if not isinstance(model_name, str) or not model_name.strip():
+            raise ValueError(f"profiles.{name}.model must name a model")
+        schema_mode = section.get("schema_mode", SCHEMA_MODE)
+        if schema_mode not in SCHEMA_MODES:
+            raise ValueError(f"profiles.{name}.schema_mode must be one of {', '.join(SCHEMA_MODES)}, got {schema_mode!r}")
+        profiles[name] = ModelProfile(name, model_name.strip(), schema_mode)
+    return profiles
+
+
+def routing_from_config(config: dict, profiles: Dict[str, ModelProfile]) -> Dict[str, str]:
+    """The profile of every task routed in config.toml, raises ValueError for unknown tasks or profiles"""
+    routes = config.get("routing", {})
+    unknown = set(routes) - set(TASKS)
+    if unknown:
+        raise ValueError(f"Unknown tasks in [routing]: {', '.join(sorted(unknown))}, expected some of {', '.join(TASKS)}")
+    for task, profile in routes.items():
+        if profile != DEFAULT_PROFILE and profile not in profiles:
+            raise ValueError(f"routing.{task} names an unknown profile: {profile!r}")
+    return dict(routes)
+
+
+class ModelRouter:
+    def __init__(
+        self,
+        default: LLM,
+        profiles: Dict[str, ModelProfile],
+        routes: Dict[str, str],
+        create: Callable[[ModelProfile], LLM],
+        generation: Optional[GenerationSettings] = None,
+    ):
+        """Create the model of every profile, profiles of the default model share it"""
+        self.default = default
+        self.profiles = profiles
+        self.routes = routes
+        self.generation = generation or GenerationSettings()
+        self.models: Dict[str, LLM] = {DEFAULT_PROFILE: default}
+        self.task_models: Dict[tuple, LLM] = {}  # Of the tasks with their own generation parameters, by profile and task
+        created: Dict[tuple, LLM] = {}
+        for name, profile in profiles.items():
+            if profile.model_name == default.model_name and profile.schema_mode == SCHEMA_MODE:
+                self.models[name] = default
+                continue
+            key = (profile.model_name, profile.schema_mode)
+            if key not in created:
+                created[key] = create(profile)
+            self.models[name] = created[key]
+
+    @property
+    def names(self) -> List[str]:
+        return list(self.models)
+
+    def check(self, profile: Optional[str]):
+        """Raises ValueError for a profile that is not configured"""
+        if profile is not None and profile not in self.models:
+            raise ValueError(f"Unknown profile: {profile}, expected one of {', '.join(self.models)}")
+
+    def profile_for(self, task: Optional[str], profile: Optional[str] = None) -> str:
+        """The profile asked for, or the one the task is routed to"""
+        self.check(profile)
+        return profile or self.routes.get(task, DEFAULT_PROFILE)
+
+    def model_for(self, task: Optional[str], profile: Optional[str] = None) -> LLM:
+        name = self.profile_for(task, profile)
+        if task not in self.generation.tasks:
+            return self.models[name]
+        if (name, task) not in self.task_models:
+            self.task_models[(name, task)] = self.models[name].with_generation(self.generation.for_task(task))
+        return self.task_models[(name, task)]
@@ -0,0 +1,174 @@
+# Projections of the study event stream
+# A projection folds the events of study_event_info (see study_events), in the order they were appended, into the
+# state analytics need: the graded attempts mastery is estimated from, and the daily activity streaks are counted
+# from. Nothing a projection computes is stored, so replaying the stream rebuilds it from the whole history after
+# the way it is computed changes, corrections included: voided attempts and undone reviews drop out again. History
+# stored before the stream existed is appended once by backfill_events.
+
+from abc import ABC, abstractmethod
+from collections import Counter
+from dataclasses import dataclass, field, replace
+from datetime import date, datetime, timedelta
+from typing import Dict, List, Optional, Sequence, Tuple
+
+from textbook.database import StudyEventInfo, TextBookDatabase
+from textbook.preferences import DEFAULT_USER_ID
+from textbook.quiz import Attempt, TopicMastery, chapter_masteries, load_exercise_candidates
+from textbook.study_events import (
+    EVENT_ATTEMPTED,
+    EVENT_GRADED,
+    EVENT_KINDS,
+    EVENT_READ,
+    EVENT_REVIEW_UNDONE,
+    EVENT_REVIEWED,
+    EVENT_VOIDED,
+    SUBJECT_CARD,
+    decode_payload,
+)
+from textbook.timestamps import as_utc
+
+REPLAY_BATCH_SIZE = 1000  # Events read from the database at a time
+# The kinds of events a day of activity is counted from
+ACTIVITY_KINDS = (EVENT_ATTEMPTED, EVENT_REVIEWED, EVENT_READ)
+
+
+class Projection(ABC):
+    """State folded from the study events, one event at a time in the order of the stream"""
+
+    kinds: Tuple[str, ...] = EVENT_KINDS  # The kinds of events the projection is given
+
+    @abstractmethod
+    def apply(self, event: StudyEventInfo) -> None:
+        ...
+
+
+@dataclass
+class _ProjectedAttempt:
+    exercise_id: int
+    created_at: datetime
+    score: Optional[float] = None  # Of the last grading, None until graded
+
+
+class MasteryProjection(Projection):
+    """The graded attempts at every exercise, voided ones left out"""
+
+    kinds = (EVENT_ATTEMPTED, EVENT_GRADED, EVENT_VOIDED)
+
+    def __init__(self):
+        self._attempts: Dict[int, _ProjectedAttempt] = {}
+
+    def apply(self, event: StudyEventInfo) -> None:
+        attempt_id = decode_payload(event.payload).get("attempt_id")
+        if event.event_kind == EVENT_ATTEMPTED:
+            self._attempts[attempt_id] = _ProjectedAttempt(event.subject_id, event.occurred_at)
+        elif event.event_kind == EVENT_GRADED and attempt_id in self._attempts:
+            self._attempts[attempt_id].score = decode_payload(event.payload).get("score")
+        elif event.event_kind == EVENT_VOIDED:
+            self._attempts.pop(attempt_id, None)
+
+    def attempts_by_exercise(self) -> Dict[int, List[Attempt]]:
+        attempts: Dict[int, List[Attempt]] = {}
+        for attempt in self._attempts.values():
+            if attempt.score is not None:
+                attempts.setdefault(attempt.exercise_id, []).append(Attempt(attempt.created_at, attempt.score))
+        return attempts
+
+
+@dataclass
+class Activity:
+    days: Dict[date, Counter] = field(default_factory=dict)  # Events of ACTIVITY_KINDS per UTC day, by kind
+    totals: Counter = field(default_factory=Counter)
+
+    def current_streak(self, today: date) -> int:
+        """Consecutive days with activity up to today, or up to yesterday while today has none yet"""
+        day = today if self.days.get(today) else today - timedelta(days=1)
+        streak = 0
+        while self.days.get(day):
+            streak += 1
+            day -= timedelta(days=1)
+        return streak
+
+    def longest_streak(self) -> int:
+        longest, streak, previous = 0, 0, None
+        for day in sorted(day for day, counts in self.days.items() if counts):
+            streak = streak + 1 if previous is not None and day - previous == timedelta(days=1) else 1
+            longest, previous = max(longest, streak), day
+        return longest
+
+
+class ActivityProjection(Projection):
+    """Study activity per day, for streaks and totals, voided attempts and undone reviews taken back"""
+
+    kinds = ACTIVITY_KINDS + (EVENT_VOIDED, EVENT_REVIEW_UNDONE)
+
+    def __init__(self):
+        self.activity = Activity()
+        self._counted: Dict[Tuple[str, int], date] = {}  # The day attempts and card reviews were counted on, by ID
+
+    def _count(self, kind: str, day: date, delta: int):
+        counts = self.activity.days.setdefault(day, Counter())
+        counts[kind] += delta
+        self.activity.totals[kind] += delta
+        if sum(counts.values()) == 0:
+            del self.activity.days[day]
+
+    def apply(self, event: StudyEventInfo) -> None:
+        payload = decode_payload(event.payload)
+        day = as_utc(event.occurred_at).date()
+        if event.event_kind in ACTIVITY_KINDS:
+            self._count(event.event_kind, day, 1)
+            if event.event_kind == EVENT_ATTEMPTED:
+                self._counted[(EVENT_ATTEMPTED, payload.get("attempt_id"))] = day
+            elif event.event_kind == EVENT_REVIEWED and event.subject_kind == SUBJECT_CARD:
+                self._counted[(EVENT_REVIEWED, payload.get("review_id"))] = day
+            return
+        kind = EVENT_ATTEMPTED if event.event_kind == EVENT_VOIDED else EVENT_REVIEWED
+        counted = self._counted.pop((kind, payload.get("attempt_id" if kind == EVENT_ATTEMPTED else "review_id")), None)
+        if counted is not None:
+            self._count(kind, counted, -1)
+
+
+def replay(database: TextBookDatabase, projections: Sequence[Projection], user_id: Optional[str] = None, book_id: Optional[int] = None, batch_size: int = REPLAY_BATCH_SIZE) -> int:
+    """
+    Fold the study events into projections, from the first event
+
+    Args:
+        user_id: Only the events of this user, the attempts included for DEFAULT_USER_ID
+        book_id: Only the events of this book
+
+    Returns:
+        int: The number of events replayed
+    """
+    kinds = sorted({kind for projection in projections for kind in projection.kinds})
+    after_id, replayed = 0, 0
+    while True:
+        events = database.get_study_events(after_id, batch_size, user_id, user_id == DEFAULT_USER_ID, book_id, kinds)
+        for event in events:
+            for projection in projections:
+                if event.event_kind in projection.kinds:
+                    projection.apply(event)
+        replayed += len(events)
+        if len(events) < batch_size:
+            return replayed
+        after_id = events[-1].event_id
+
+
+def projected_mastery(database: TextBookDatabase, book_id: int, now: Optional[datetime] = None) -> Dict[int, TopicMastery]:
+    """The mastery of every chapter of a book from the events of its attempts, as book_mastery computes it"""
+    projection = MasteryProjection()
+    replay(database, [projection], book_id=book_id)
+    attempts = projection.attempts_by_exercise()
+    candidates = [replace(candidate, attempts=attempts.get(candidate.exercise_id, [])) for candidate in load_exercise_candidates(database, book_id)]
+    return chapter_masteries(database, book_id, candidates, now)
+
+
+def user_activity(database: TextBookDatabase, user_id: str, book_id: Optional[int] = None) -> Activity:
+    """The study activity of a user per day, from their events"""
+    projection = ActivityProjection()
+    replay(database, [projection], user_id, book_id)
+    return projection.activity
+
+
+def backfill_events(database: TextBookDatabase) -> int:
+    """Append the study history stored before the event stream existed, once, returns the number of events appended"""
+    return database.backfill_study_events()
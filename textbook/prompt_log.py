@@ -0,0 +1,142 @@
+# Prompt log
+# To debug the quality of what the LLM generates, its prompts and responses can be kept in the database, per job when
+# sent from a job. The log is off unless configured: "full" keeps the text as sent and received, "truncated" keeps the
+# start of each, up to max_chars. Before anything is stored, redaction rules replace what must not end up in the
+# log, e-mail addresses and tokens by default plus any configured patterns. Entries older than the retention window
+# are deleted on startup and whenever the log is read.
+
+import re
+from dataclasses import dataclass, field
+from datetime import datetime, timedelta, timezone
+from typing import List, Optional, Tuple
+
+from textbook.database import PromptLogInfo, TextBookDatabase
+from textbook.jobs import current_job_id
+
+LOG_MODE_FULL = "full"
+LOG_MODE_TRUNCATED = "truncated"
+LOG_MODE_DISABLED = "disabled"
+LOG_MODES = (LOG_MODE_FULL, LOG_MODE_TRUNCATED, LOG_MODE_DISABLED)
+
+DEFAULT_MAX_CHARS = 2000
+DEFAULT_RETENTION_DAYS = 14
+REDACTED = "[REDACTED]"
+TRUNCATION_MARK = "\n[... truncated]"
+
+# Always applied, configured patterns come on top
+DEFAULT_REDACTIONS = [
+    r"[\w.+-]+@[\w-]+\.[\w.-]+",  # E-mail addresses
+    r"(?i)bearer\s+[\w.~+/-]+=*",  # Bearer tokens
+    r"\bpbs_[\w-]+",  # API tokens of this server
+    r"\b(?:sk|AIza)[\w-]{16,}",  # Provider API keys
+]
+
+
+@dataclass
+class PromptLogSettings:
+    mode: str = LOG_MODE_DISABLED
+    max_chars: int = DEFAULT_MAX_CHARS  # Kept of each prompt and response in truncated mode
+    retention_days: int = DEFAULT_RETENTION_DAYS
+    redactions: List[str] = field(default_factory=list)  # Regular expressions replaced by REDACTED, on top of the defaults
+
+
+def settings_from_config(config: dict) -> PromptLogSettings:
+    """The prompt log settings of config.toml, raises ValueError for invalid ones"""
+    settings = PromptLogSettings(
+        mode=config.get("prompt_log", LOG_MODE_DISABLED),
+        max_chars=config.get("prompt_log_max_chars", DEFAULT_MAX_CHARS),
+        retention_days=config.get("prompt_log_retention_days", DEFAULT_RETENTION_DAYS),
+        redactions=list(config.get("prompt_log_redactions", [])),
+    )
+    if settings.mode not in LOG_MODES:
+        raise ValueError(f"Unsupported prompt log mode: {settings.mode}, expected one of {', '.join(LOG_MODES)}")
+    if settings.max_chars < 1 or settings.retention_days < 1:
+        raise ValueError("prompt_log_max_chars and prompt_log_retention_days must be positive")
+    for pattern in settings.redactions:
+        try:
+            re.compile(pattern)
+        except re.error as e:
+            raise ValueError(f"Invalid prompt log redaction {pattern!r}: {e}")
+    return settings
+
+
+def redact(text: str, patterns: List[str]) -> str:
+    for pattern in patterns:
+        text = re.sub(pattern, REDACTED, text)
+    return text
+
+
+def truncate(text: str, max_chars: int) -> Tuple[str, bool]:
+    """The start of a text up to max_chars, and whether it was cut"""
+    if len(text) <= max_chars:
+        return text, False
+    return text[:max_chars] + TRUNCATION_MARK, True
+
+
+class PromptLog:
+    """Records prompts and responses in the database according to the settings"""
+
+    def __init__(self, database: TextBookDatabase, settings: PromptLogSettings):
+        self.database = database
+        self.settings = settings
+        self.patterns = DEFAULT_REDACTIONS + settings.redactions
+
+    @property
+    def enabled(self) -> bool:
+        return self.settings.mode != LOG_MODE_DISABLED
+
+    def record(
+        self,
+        model_name: str,
+        prompt: str,
+        response: Optional[str],
+        schema_name: Optional[str] = None,
+        error: Optional[str] = None,
+        parsed: Optional[str] = None,
+        attempt: int = 0,
+    ) -> Optional[PromptLogInfo]:
+        """
+        Log a prompt and its response for the current job, None when the log is disabled
+
+        Args:
+            parsed: The output validated from a structured response, as JSON
+            attempt: 0 for the first answer to a prompt, then the number of the repair asked for
+        """
+        if not self.enabled:
+            return None
+        logged_prompt = redact(prompt, self.patterns)
+        logged_response = redact(response, self.patterns) if response is not None else None
+        logged_parsed = redact(parsed, self.patterns) if parsed is not None else None
+        truncated = False
+        if self.settings.mode == LOG_MODE_TRUNCATED:
+            logged_prompt, prompt_cut = truncate(logged_prompt, self.settings.max_chars)
+            response_cut = False
+            if logged_response is not None:
+                logged_response, response_cut = truncate(logged_response, self.settings.max_chars)
+            parsed_cut = False
+            if logged_parsed is not None:
+                logged_parsed, parsed_cut = truncate(logged_parsed, self.settings.max_chars)
+            truncated = prompt_cut or response_cut or parsed_cut
+        return self.database.create_prompt_log(PromptLogInfo(
+            job_id=current_job_id(),
+            model_name=model_name,
+            schema_name=schema_name,
+            prompt=logged_prompt,
+            response=logged_response,
+            error=redact(error, self.patterns) if error is not None else None,
+            parsed=logged_parsed,
+            attempt=attempt,
+            prompt_chars=len(prompt),
+            response_chars=len(response or ""),
+            truncated=truncated,
+        ))
+
+    def purge(self, now: Optional[datetime] = None) -> int:
+        """Delete the entries older than the retention window, returns how many were deleted"""
+        now = now or datetime.now(timezone.utc)
+        return self.database.delete_prompt_logs_before(now - timedelta(days=self.settings.retention_days))
+
+    def entries(self, job_id: Optional[str] = None, limit: int = 50) -> List[PromptLogInfo]:
+        """The latest entries within the retention window, only those of a job if given"""
+        self.purge()
+        return self.database.get_prompt_logs(job_id, limit)
@@ -0,0 +1,76 @@
+# Prompt templates
+# The same content can be explained in different registers: as an analogy, as to a child, as a rigorous proof or as
+# a description of the picture to draw. Each register is a template of rules added to the rules of a prompt, kept in
+# a registry by kind and name so prompts look them up instead of each carrying its own variants, and new registers
+# are added in one place. The explanation styles are registered here; prompts without a style keep their rules.
+
+from dataclasses import dataclass
+from typing import Dict, List, Optional
+
+KIND_EXPLANATION_STYLE = "explanation_style"
+
+STYLE_ANALOGY = "analogy"
+STYLE_ELI5 = "eli5"
+STYLE_RIGOROUS = "rigorous"
+STYLE_VISUAL_DESCRIPTION = "visual-description"
+
+
+@dataclass(frozen=True)
+class PromptTemplate:
+    name: str
+    description: str
+    rules: List[str]  # Added to the rules of the prompt
+
+
+class PromptTemplateRegistry:
+    def __init__(self):
+        self._templates: Dict[str, Dict[str, PromptTemplate]] = {}
+
+    def register(self, kind: str, template: PromptTemplate):
+        """Add a template, replacing the one of the same kind and name"""
+        self._templates.setdefault(kind, {})[template.name] = template
+
+    def names(self, kind: str) -> List[str]:
+        return list(self._templates.get(kind, {}))
+
+    def get(self, kind: str, name: str) -> PromptTemplate:
+        """The template of a kind by name, raises ValueError for unknown ones"""
+        template = self._templates.get(kind, {}).get(name)
+        if template is None:
+            raise ValueError(f"Unknown {kind.replace('_', ' ')}: {name}, expected one of {', '.join(self.names(kind))}")
+        return template
+
+
+registry = PromptTemplateRegistry()
+for _template in (
+    PromptTemplate(STYLE_ANALOGY, "An analogy from everyday life, mapped back to the content", [
+        "explain through one analogy from everyday life and say which part of the analogy stands for which part of the content",
+        "say where the analogy breaks down",
+    ]),
+    PromptTemplate(STYLE_ELI5, "Plain words for someone new to the subject", [
+        "explain as to someone new to the subject, in short sentences and plain words",
+        "avoid notation and jargon, and explain a term in plain words the first time it is needed",
+    ]),
+    PromptTemplate(STYLE_RIGOROUS, "Precise definitions and a formal proof or derivation", [
+        "be rigorous: state the definitions and assumptions used, then prove or derive the claims step by step",
+        "justify every step and do not skip cases the content leaves implicit",
+    ]),
+    PromptTemplate(STYLE_VISUAL_DESCRIPTION, "A description of a diagram or picture of the content", [
+        "describe a diagram or picture that shows the content: what is drawn, how it is laid out and what it labels",
+        "then explain the content by walking through the picture",
+    ]),
+):
+    registry.register(KIND_EXPLANATION_STYLE, _template)
+
+
+def check_explanation_style(style: Optional[str]):
+    """Raises ValueError for an explanation style that is not registered"""
+    if style is not None:
+        registry.get(KIND_EXPLANATION_STYLE, style)
+
+
+def style_rules(style: Optional[str]) -> str:
+    """The rules of an explanation style as lines of a prompt's rule list, empty without a style"""
+    if style is None:
+        return ""
+    return "".join(f"\n    - {rule}" for rule in registry.get(KIND_EXPLANATION_STYLE, style).rules)
@@ -0,0 +1,87 @@
+# Provider errors
+# The llm library and its provider plugins raise errors of their own when a prompt fails, with messages like
+# "429 Resource has been exhausted" that mean little to someone studying. They are mapped into a ModelError of one
+# category: auth, quota, content_filter, timeout or server, from the HTTP status of the error when it has one and
+# from its type and message otherwise. A ModelError reads as a message saying what to do about it, the provider's
+# own message is kept as its detail for the logs. Jobs failing with a ModelError keep its category. A call failed
+# fast by an open circuit breaker is a ModelError of category unavailable, raised without calling the provider.
+
+import re
+from typing import Optional
+
+ERROR_AUTH = "auth"
+ERROR_QUOTA = "quota"
+ERROR_CONTENT_FILTER = "content_filter"
+ERROR_TIMEOUT = "timeout"
+ERROR_SERVER = "server"  # Anything else the provider failed with
+ERROR_UNAVAILABLE = "unavailable"  # Not called, its circuit breaker is open after repeated failures
+ERROR_CATEGORIES = (ERROR_AUTH, ERROR_QUOTA, ERROR_CONTENT_FILTER, ERROR_TIMEOUT, ERROR_SERVER, ERROR_UNAVAILABLE)
+
+USER_MESSAGES = {
+    ERROR_AUTH: "API key invalid — update provider settings",
+    ERROR_QUOTA: "Provider quota or rate limit reached — wait a minute and retry, or raise the limit with the provider",
+    ERROR_CONTENT_FILTER: "The provider's content filter blocked the request — relax safety_settings in config.toml or try other pages",
+    ERROR_TIMEOUT: "The provider took too long to answer — retry later",
+    ERROR_SERVER: "The provider failed to answer — retry later, or check the provider's status page",
+    ERROR_UNAVAILABLE: "The service is down after repeated failures — waiting jobs resume once it answers again",
+}
+
+STATUS_CATEGORIES = {401: ERROR_AUTH, 403: ERROR_AUTH, 408: ERROR_TIMEOUT, 429: ERROR_QUOTA, 504: ERROR_TIMEOUT}
+
+# Checked in order against the lowercased type name and message of errors without a telling status
+MESSAGE_PATTERNS = [
+    (ERROR_AUTH, re.compile(r"api[ _-]?key|unauthenticated|unauthorized|permission[ _]denied|invalid[ _]credentials")),
+    (ERROR_QUOTA, re.compile(r"quota|rate[ _-]?limit|resource[ _]exhausted|resource has been exhausted|too many requests")),
+    (ERROR_CONTENT_FILTER, re.compile(r"safety|content[ _-]?filter|blocked|prohibited[ _]content|recitation")),
+    (ERROR_TIMEOUT, re.compile(r"timeout|timed out|deadline[ _]exceeded")),
+]
+
+
+class ModelError(Exception):
+    """A failed prompt, its message is the one to show to users"""
+
+    def __init__(self, category: str, detail: str, model_name: Optional[str] = None):
+        if category not in ERROR_CATEGORIES:
+            raise ValueError(f"Unsupported error category: {category}, expected one of {', '.join(ERROR_CATEGORIES)}")
+        super().__init__(USER_MESSAGES[category])
+        self.category = category
+        self.detail = detail  # The provider's message
+        self.model_name = model_name
+
+    @property
+    def user_message(self) -> str:
+        return str(self)
+
+
+def _status_code(error: BaseException) -> Optional[int]:
+    # Plugins put the HTTP status on the error or on its response, under different names
+    for source in (error, getattr(error, "response", None)):
+        for attribute in ("status_code", "status", "code"):
+            value = getattr(source, attribute, None)
+            if isinstance(value, int) and 400 <= value < 600:
+                return value
+    match = re.match(r"\s*(?:error\s*)?\(?([45]\d\d)\b", str(error), re.IGNORECASE)
+    return int(match.group(1)) if match else None
+
+
+def classify_error(error: BaseException) -> str:
+    """The category of an error raised while prompting a provider"""
+    if isinstance(error, ModelError):
+        return error.category
+    status = _status_code(error)
+    if status in STATUS_CATEGORIES:
+        return STATUS_CATEGORIES[status]
+    if isinstance(error, TimeoutError):
+        return ERROR_TIMEOUT
+    text = f"{type(error).__name__}: {error}".lower()
+    for category, pattern in MESSAGE_PATTERNS:
+        if pattern.search(text):
+            return category
+    return ERROR_SERVER
+
+
+def provider_error(error: BaseException, model_name: Optional[str] = None) -> ModelError:
+    """The ModelError of an error raised while prompting a provider"""
+    if isinstance(error, ModelError):
+        return error
+    return ModelError(classify_error(error), str(error) or type(error).__name__, model_name)
@@ -0,0 +1,305 @@
+# Quiz assembly driven by a mastery model
+# Mastery of a chapter is estimated from the graded attempts at its exercises, weighting recent attempts more.
+# A quiz mixes the current chapter with earlier chapters the learner is weak at and earlier chapters due for
+# review, in configurable proportions, and interleaves the chapters so consecutive problems change topic.
+# The user's preferences choose between this and blocked practice of the current chapter alone, cap how many
+# never attempted exercises are introduced per day, and set the difficulty new exercises are picked around.
+# Archived books and chapters are left out; suspending only affects flashcard reviews.
+# Chapters answered accurately but slowly in quizzes (see quiz_timing) count as a little less mastered.
+
+import random
+from collections import defaultdict
+from dataclasses import dataclass, field, replace
+from datetime import datetime, timezone
+from typing import Dict, List, Optional, Set, Tuple
+
+from textbook.database import TextBookDatabase
+from textbook.preferences import SCHEDULER_BLOCKED, Preferences
+from textbook.quiz_timing import SLOW_MASTERY_FACTOR, accurate_but_slow_chapters
+from textbook.timestamps import as_utc
+
+MASTERY_HALF_LIFE_DAYS = 14.0  # An attempt counts half as much towards mastery after this many days
+MASTERY_PRIOR_WEIGHT = 1.0  # Weight of an imaginary failed attempt, so one lucky answer is not full mastery
+REVIEW_BASE_INTERVAL_DAYS = 1.0  # Spacing after the first correct attempt, doubled after every further one
+CORRECT_SCORE = 0.7  # Attempts scoring at least this count as correct for spacing
+
+# Slots of a quiz
+SLOT_CURRENT = "current"
+SLOT_WEAK = "weak"
+SLOT_REVIEW = "review"
+
+
+@dataclass
+class QuizMix:
+    """Proportions of the quiz drawn from the current chapter, weak earlier chapters and earlier chapters due for review"""
+    current: float = 0.5
+    weak: float = 0.3
+    review: float = 0.2
+
+    def counts(self, size: int) -> Dict[str, int]:
+        total = self.current + self.weak + self.review
+        if total <= 0:
+            raise ValueError("The quiz proportions must not all be zero")
+        shares = {SLOT_CURRENT: self.current / total, SLOT_WEAK: self.weak / total, SLOT_REVIEW: self.review / total}
+        counts = {slot: int(share * size) for slot, share in shares.items()}
+        # Largest remainders get the rounded off problems
+        for slot in sorted(shares, key=lambda slot: shares[slot] * size - counts[slot], reverse=True)[: size - sum(counts.values())]:
+            counts[slot] += 1
+        return counts
+
+
+@dataclass
+class Attempt:
+    created_at: datetime
+    score: float
+
+
+@dataclass
+class TopicMastery:
+    chapter_id: int
+    mastery: float  # Between 0 and 1, 0 without attempts
+    attempts: int
+    last_attempt_at: Optional[datetime]
+    review_interval_days: Optional[float]  # None until the first correct attempt
+    overdue: float  # Days since the last attempt relative to the review interval, due from 1
+    accurate_but_slow: bool = False  # Answered well but slowly, the mastery is scaled down
+
+
+@dataclass
+class ExerciseCandidate:
+    exercise_id: int
+    chapter_id: Optional[int]
+    attempts: List[Attempt] = field(default_factory=list)
+    difficulty_level: Optional[int] = None
+
+
+@dataclass
+class QuizItem:
+    exercise_id: int
+    chapter_id: Optional[int]
+    slot: str
+    mastery: float  # Mastery of the exercise's chapter
+
+
+def _age_days(moment: datetime, now: datetime) -> float:
+    return max((as_utc(now) - as_utc(moment)).total_seconds() / 86400, 0.0)
+
+
+def estimate_mastery(attempts: List[Attempt], now: datetime) -> float:
+    """Recency weighted average score, pulled towards 0 by the prior while there are few attempts"""
+    weights = [0.5 ** (_age_days(attempt.created_at, now) / MASTERY_HALF_LIFE_DAYS) for attempt in attempts]
+    return sum(weight * attempt.score for weight, attempt in zip(weights, attempts)) / (sum(weights) + MASTERY_PRIOR_WEIGHT)
+
+
+def review_interval_days(attempts: List[Attempt]) -> Optional[float]:
+    """The spacing interval after the trailing run of correct attempts, None if the last attempt was not correct"""
+    streak = 0
+    for attempt in sorted(attempts, key=lambda attempt: as_utc(attempt.created_at), reverse=True):
+        if attempt.score < CORRECT_SCORE:
+            break
+        streak += 1
+    return REVIEW_BASE_INTERVAL_DAYS * 2 ** (streak - 1) if streak else None
+
+
+def topic_mastery(chapter_id: int, attempts: List[Attempt], now: datetime) -> TopicMastery:
+    last = max((as_utc(attempt.created_at) for attempt in attempts), default=None)
+    interval = review_interval_days(attempts)
+    if last is None:
+        overdue = 0.0
+    elif interval is None:
+        # Failed last time, due again right away
+        overdue = 1.0 + _age_days(last, now)
+    else:
+        overdue = _age_days(last, now) / interval
+    return TopicMastery(chapter_id, estimate_mastery(attempts, now), len(attempts), last, interval, overdue)
+
+
+def _slowed(mastery: TopicMastery) -> TopicMastery:
+    return replace(mastery, mastery=mastery.mastery * SLOW_MASTERY_FACTOR, accurate_but_slow=True)
+
+
+def _difficulty_distance(candidate: ExerciseCandidate, target_difficulty: Optional[int]) -> int:
+    if target_difficulty is None or candidate.difficulty_level is None:
+        return 0
+    return abs(candidate.difficulty_level - target_difficulty)
+
+
+def _pick(candidates: List[ExerciseCandidate], count: int, taken: set, rng: random.Random, now: datetime, allow_new: bool = True, target_difficulty: Optional[int] = None) -> List[ExerciseCandidate]:
+    # Unattempted exercises first, then those answered worst, closest to the target difficulty, ties broken at random
+    available = [candidate for candidate in candidates if candidate.exercise_id not in taken and (allow_new or candidate.attempts)]
+    rng.shuffle(available)
+    available.sort(key=lambda candidate: (len(candidate.attempts) > 0, estimate_mastery(candidate.attempts, now), _difficulty_distance(candidate, target_difficulty)))
+    return available[:count]
+
+
+def new_items_today(candidates: List[ExerciseCandidate], now: datetime) -> int:
+    """The number of exercises first attempted on the (UTC) day of now"""
+    today = as_utc(now).date()
+    return sum(1 for candidate in candidates if candidate.attempts and min(as_utc(attempt.created_at) for attempt in candidate.attempts).date() == today)
+
+
+def interleave(items: List[QuizItem]) -> List[QuizItem]:
+    """Order the items so consecutive problems come from different chapters wherever possible"""
+    by_chapter: Dict[Optional[int], List[QuizItem]] = defaultdict(list)
+    for item in items:
+        by_chapter[item.chapter_id].append(item)
+    ordered: List[QuizItem] = []
+    previous = object()
+    while len(ordered) < len(items):
+        # The chapter with the most problems left goes next, unless it just went
+        remaining = sorted((chapter for chapter in by_chapter if by_chapter[chapter]), key=lambda chapter: len(by_chapter[chapter]), reverse=True)
+        chapter = next((chapter for chapter in remaining if chapter != previous), remaining[0])
+        ordered.append(by_chapter[chapter].pop(0))
+        previous = chapter
+    return ordered
+
+
+def assemble_quiz_from_candidates(
+    candidates: List[ExerciseCandidate],
+    current_chapter_id: int,
+    earlier_chapter_ids: List[int],
+    size: int,
+    mix: Optional[QuizMix] = None,
+    now: Optional[datetime] = None,
+    seed: Optional[int] = None,
+    new_limit: Optional[int] = None,
+    target_difficulty: Optional[int] = None,
+    slow_chapter_ids: Optional[Set[int]] = None,
+    exercise_ids: Optional[Set[int]] = None,
+) -> Tuple[List[QuizItem], Dict[int, TopicMastery]]:
+    """
+    Assemble a quiz from exercises with their attempts
+
+    Weak chapters are the attempted earlier chapters with the lowest mastery, review chapters those most overdue
+    for review. Slots that cannot be filled are filled from the other slots, the current chapter first. At most
+    new_limit unattempted exercises are included, picked closest to target_difficulty. The mastery of the chapters
+    in slow_chapter_ids, answered accurately but slowly, is scaled down. With exercise_ids only those exercises are
+    picked, while the mastery of a chapter still counts the attempts at all of its exercises.
+
+    Returns:
+        Tuple[List[QuizItem], Dict[int, TopicMastery]]: The interleaved quiz and the mastery of each chapter
+    """
+    now = now or datetime.now(timezone.utc)
+    mix = mix or QuizMix()
+    rng = random.Random(seed)
+    by_chapter: Dict[Optional[int], List[ExerciseCandidate]] = defaultdict(list)
+    for candidate in candidates:
+        by_chapter[candidate.chapter_id].append(candidate)
+
+    masteries = {
+        chapter_id: topic_mastery(chapter_id, [attempt for candidate in by_chapter[chapter_id] for attempt in candidate.attempts], now)
+        for chapter_id in [current_chapter_id] + earlier_chapter_ids
+    }
+    for chapter_id in slow_chapter_ids or ():
+        if chapter_id in masteries:
+            masteries[chapter_id] = _slowed(masteries[chapter_id])
+    attempted = [chapter_id for chapter_id in earlier_chapter_ids if masteries[chapter_id].attempts > 0]
+    weak_order = sorted(attempted, key=lambda chapter_id: masteries[chapter_id].mastery)
+    review_order = sorted((chapter_id for chapter_id in attempted if masteries[chapter_id].overdue >= 1), key=lambda chapter_id: masteries[chapter_id].overdue, reverse=True)
+    slot_chapters = {SLOT_CURRENT: [current_chapter_id], SLOT_WEAK: weak_order, SLOT_REVIEW: review_order}
+
+    items: List[QuizItem] = []
+    # Exercises left out count as taken already
+    taken: set = {candidate.exercise_id for candidate in candidates if exercise_ids is not None and candidate.exercise_id not in exercise_ids}
+    new_count = 0
+
+    def fill(slot: str, count: int) -> int:
+        # Spread the slot's problems over its chapters, the highest ranked chapter first
+        nonlocal new_count
+        chapters = slot_chapters[slot]
+        filled = 0
+        while filled < count:
+            progress = False
+            for chapter_id in chapters:
+                if filled == count:
+                    break
+                picked = _pick(by_chapter[chapter_id], 1, taken, rng, now, new_limit is None or new_count < new_limit, target_difficulty)
+                if picked:
+                    taken.add(picked[0].exercise_id)
+                    new_count += 0 if picked[0].attempts else 1
+                    items.append(QuizItem(picked[0].exercise_id, chapter_id, slot, masteries[chapter_id].mastery))
+                    filled += 1
+                    progress = True
+            if not progress:
+                break
+        return filled
+
+    counts = mix.counts(size)
+    shortfall = sum(count - fill(slot, count) for slot, count in counts.items())
+    for slot in (SLOT_CURRENT, SLOT_WEAK, SLOT_REVIEW):
+        if shortfall <= 0:
+            break
+        shortfall -= fill(slot, shortfall)
+    return interleave(items), masteries
+
+
+def _containing_chapter(chapters, page_number: int):
+    return next((chapter for chapter in chapters if chapter.start_page_number <= page_number <= (chapter.end_page_number if chapter.end_page_number is not None else chapter.start_page_number)), None)
+
+
+def load_exercise_candidates(database: TextBookDatabase, book_id: int) -> List[ExerciseCandidate]:
+    """The exercises of a book with their chapter and graded attempts"""
+    book = database.get_book_by_id(book_id)
+    offset = (book.book_alignment_offset if book is not None else None) or 0
+    chapters = database.get_chapters_by_book_id(book_id)
+    attempts: Dict[int, List[Attempt]] = defaultdict(list)
+    for attempt in database.get_attempts_by_book_id(book_id):
+        if attempt.score is not None:
+            attempts[attempt.exercise_id].append(Attempt(attempt.created_at, attempt.score))
+
+    candidates = []
+    for exercise in database.get_exercises_by_book_id(book_id):
+        # Exercises keep the PDF page, the TOC is in printed page numbers
+        chapter = _containing_chapter(chapters, exercise.page_number - offset)
+        difficulty = exercise.details.difficulty_level if exercise.details is not None else None
+        candidates.append(ExerciseCandidate(exercise.exercise_id, chapter.chapter_id if chapter is not None else None, attempts[exercise.exercise_id], difficulty))
+    return candidates
+
+
+def assemble_quiz(
+    database: TextBookDatabase,
+    book_id: int,
+    chapter_id: int,
+    size: int,
+    mix: Optional[QuizMix] = None,
+    seed: Optional[int] = None,
+    preferences: Optional[Preferences] = None,
+    exercise_ids: Optional[Set[int]] = None,
+) -> Tuple[List[QuizItem], Dict[int, TopicMastery]]:
+    """Assemble a quiz on a chapter of a book, mixed with the earlier chapters unless the preferences ask for blocked practice, of exercise_ids only if given"""
+    book = database.get_book_by_id(book_id)
+    if book is not None and book.archived_at is not None:
+        raise ValueError(f"Book {book_id} is archived")
+    chapters = database.get_chapters_by_book_id(book_id)
+    current = next((chapter for chapter in chapters if chapter.chapter_id == chapter_id), None)
+    if current is None:
+        raise ValueError(f"Chapter {chapter_id} not found in book {book_id}")
+    if current.archived_at is not None:
+        raise ValueError(f"Chapter {chapter_id} is archived")
+    earlier = [chapter.chapter_id for chapter in chapters if chapter.start_page_number < current.start_page_number and chapter.archived_at is None]
+    candidates = load_exercise_candidates(database, book_id)
+    slow = accurate_but_slow_chapters(database, book_id, {candidate.exercise_id: candidate.chapter_id for candidate in candidates})
+    if preferences is None:
+        return assemble_quiz_from_candidates(candidates, chapter_id, earlier, size, mix, seed=seed, slow_chapter_ids=slow, exercise_ids=exercise_ids)
+
+    if preferences.scheduler == SCHEDULER_BLOCKED and mix is None:
+        earlier, mix = [], QuizMix(1, 0, 0)
+    now = datetime.now(timezone.utc)
+    new_limit = max(preferences.daily_new_card_limit - new_items_today(candidates, now), 0)
+    return assemble_quiz_from_candidates(candidates, chapter_id, earlier, size, mix, now, seed, new_limit, preferences.default_difficulty, slow, exercise_ids)
+
+
+def book_mastery(database: TextBookDatabase, book_id: int) -> Dict[int, TopicMastery]:
+    """The mastery of every chapter of a book"""
+    return chapter_masteries(database, book_id, load_exercise_candidates(database, book_id))
+
+
+def chapter_masteries(database: TextBookDatabase, book_id: int, candidates: List[ExerciseCandidate], now: Optional[datetime] = None) -> Dict[int, TopicMastery]:
+    """The mastery of every chapter of a book from the attempts of its exercise candidates"""
+    now = now or datetime.now(timezone.utc)
+    attempts: Dict[Optional[int], List[Attempt]] = defaultdict(list)
+    for candidate in candidates:
+        attempts[candidate.chapter_id].extend(candidate.attempts)
+    slow = accurate_but_slow_chapters(database, book_id, {candidate.exercise_id: candidate.chapter_id for candidate in candidates})
+    masteries = {chapter.chapter_id: topic_mastery(chapter.chapter_id, attempts[chapter.chapter_id], now) for chapter in database.get_chapters_by_book_id(book_id)}
+    return {chapter_id: _slowed(mastery) if chapter_id in slow else mastery for chapter_id, mastery in masteries.items()}
@@ -0,0 +1,139 @@
+# Quiz timing analytics
+# While answering a quiz the client measures how long each question was thought about, how often the answer was
+# changed before it was submitted, and which questions were skipped. The timings are stored next to the graded
+# attempts and summarized per question type (text or code) and per chapter: answers, skips, the median think time,
+# the answer changes and the mean score. A chapter is accurate but slow when its answers score well but take much
+# longer than the learner's answers usually do, a sign the material is known but not yet fluent. The mastery model
+# counts such chapters as a little less mastered, so quizzes keep practicing them.
+
+from collections import defaultdict
+from dataclasses import dataclass
+from statistics import median
+from typing import Dict, List, Optional, Set
+
+from textbook.database import QuestionTimingInfo, TextBookDatabase
+
+ACCURATE_SCORE = 0.8  # Mean score of a chapter's timed answers from which it is accurate
+SLOW_FACTOR = 1.5  # Median think time of a chapter, relative to the learner's median, from which it is slow
+MIN_TIMED_ANSWERS = 3  # Timed answers of a chapter needed before it is judged slow
+SLOW_MASTERY_FACTOR = 0.85  # Mastery of accurate but slow chapters is scaled by this
+QUESTION_TYPE_TEXT = "text"
+
+
+@dataclass
+class TimingStats:
+    key: str  # The question type, or the chapter ID for chapters
+    answered: int
+    skipped: int
+    skip_rate: float
+    median_think_seconds: Optional[float]  # Of the answers, None without any
+    mean_answer_changes: float  # Over answers and skips
+    accuracy: Optional[float]  # Mean score of the answers, None without any
+    accurate_but_slow: bool = False
+
+
+@dataclass
+class TimingAnalytics:
+    median_think_seconds: Optional[float]  # Of every answer of the book
+    by_question_type: List[TimingStats]
+    by_chapter: List[TimingStats]
+
+
+def record_answer_timing(database: TextBookDatabase, attempt_id: int, think_seconds: float, answer_changes: int = 0) -> QuestionTimingInfo:
+    """
+    Store how long a graded attempt took to answer
+
+    Raises:
+        ValueError: If the attempt does not exist or the timing is negative
+    """
+    attempt = database.get_attempt_info(attempt_id)
+    if attempt is None:
+        raise ValueError(f"Attempt not found: {attempt_id}")
+    timing = _timing(attempt.exercise_id, attempt.book_id, think_seconds, answer_changes, attempt_id)
+    database.create_question_timing(timing)
+    return timing
+
+
+def record_skip(database: TextBookDatabase, exercise_id: int, think_seconds: float, answer_changes: int = 0) -> QuestionTimingInfo:
+    """
+    Store a quiz question skipped without an answer
+
+    Raises:
+        ValueError: If the exercise does not exist or the timing is negative
+    """
+    exercise = database.get_exercise_info(exercise_id)
+    if exercise is None:
+        raise ValueError(f"Exercise not found: {exercise_id}")
+    timing = _timing(exercise_id, exercise.book_id, think_seconds, answer_changes)
+    database.create_question_timing(timing)
+    return timing
+
+
+def _timing(exercise_id: int, book_id: int, think_seconds: float, answer_changes: int, attempt_id: Optional[int] = None) -> QuestionTimingInfo:
+    if think_seconds < 0 or answer_changes < 0:
+        raise ValueError("think_seconds and answer_changes cannot be negative")
+    return QuestionTimingInfo(
+        exercise_id=exercise_id,
+        attempt_id=attempt_id,
+        think_seconds=think_seconds,
+        answer_changes=answer_changes,
+        skipped=attempt_id is None,
+        book_id=book_id,
+    )
+
+
+def _stats(key: str, timings: List[QuestionTimingInfo], scores: Dict[int, float], overall_median: Optional[float]) -> TimingStats:
+    answers = [timing for timing in timings if not timing.skipped and timing.attempt_id in scores]
+    skipped = sum(1 for timing in timings if timing.skipped)
+    think = median([timing.think_seconds for timing in answers]) if answers else None
+    accuracy = sum(scores[timing.attempt_id] for timing in answers) / len(answers) if answers else None
+    slow = (
+        len(answers) >= MIN_TIMED_ANSWERS
+        and accuracy is not None and accuracy >= ACCURATE_SCORE
+        and think is not None and overall_median is not None and think >= SLOW_FACTOR * overall_median
+    )
+    return TimingStats(
+        key=key,
+        answered=len(answers),
+        skipped=skipped,
+        skip_rate=skipped / (len(answers) + skipped) if answers or skipped else 0.0,
+        median_think_seconds=think,
+        mean_answer_changes=sum(timing.answer_changes for timing in timings) / len(timings) if timings else 0.0,
+        accuracy=accuracy,
+        accurate_but_slow=slow,
+    )
+
+
+def timing_analytics(database: TextBookDatabase, book_id: int, chapter_of: Dict[int, Optional[int]]) -> TimingAnalytics:
+    """
+    Summarize the quiz timings of a book per question type and per chapter
+
+    Args:
+        chapter_of: The chapter ID of every exercise, see quiz.load_exercise_candidates
+
+    Returns:
+        TimingAnalytics: The statistics, chapters without timings left out; voided attempts do not count
+    """
+    timings = database.get_question_timings_by_book_id(book_id)
+    scores = {attempt.attempt_id: attempt.score for attempt in database.get_attempts_by_book_id(book_id) if attempt.score is not None}
+    types = {exercise.exercise_id: exercise.exercise_type or QUESTION_TYPE_TEXT for exercise in database.get_exercises_by_book_id(book_id)}
+    answered = [timing.think_seconds for timing in timings if not timing.skipped and timing.attempt_id in scores]
+    overall_median = median(answered) if answered else None
+
+    by_type: Dict[str, List[QuestionTimingInfo]] = defaultdict(list)
+    by_chapter: Dict[int, List[QuestionTimingInfo]] = defaultdict(list)
+    for timing in timings:
+        by_type[types.get(timing.exercise_id, QUESTION_TYPE_TEXT)].append(timing)
+        chapter_id = chapter_of.get(timing.exercise_id)
+        if chapter_id is not None:
+            by_chapter[chapter_id].append(timing)
+    return TimingAnalytics(
+        median_think_seconds=overall_median,
+        by_question_type=[_stats(question_type, group, scores, overall_median) for question_type, group in sorted(by_type.items())],
+        by_chapter=[_stats(str(chapter_id), group, scores, overall_median) for chapter_id, group in sorted(by_chapter.items())],
+    )
+
+
+def accurate_but_slow_chapters(database: TextBookDatabase, book_id: int, chapter_of: Dict[int, Optional[int]]) -> Set[int]:
+    """The IDs of the chapters answered accurately but slowly"""
+    return {int(stats.key) for stats in timing_analytics(database, book_id, chapter_of).by_chapter if stats.accurate_but_slow}
@@ -0,0 +1,102 @@
+# Rate limits of the LLM provider
+# A job extracting the problems of a 400 page book sends a hundred prompts in a row, and several jobs run at once,
+# which blows through a provider's quota and fails the job halfway with a quota error. Prompts wait for their turn
+# instead: token buckets, one for requests and one for tokens, refill at the rate allowed per minute and a prompt only
+# goes out once both have room for it. The models of a provider share its buckets, as they share its quota. Tokens are
+# estimated from the characters of the prompt, taken before it is sent, and of the answer, taken after it arrives.
+# The limits are set per provider in config.toml, a provider without limits is never held back:
+#   [rate_limits.gemini]
+#   requests_per_minute = 15
+#   tokens_per_minute = 1000000
+
+import threading
+import time
+from dataclasses import dataclass
+from typing import Callable, Optional
+
+CHARS_PER_TOKEN = 4  # Rough average of English text and LaTeX
+
+
+@dataclass
+class RateLimits:
+    requests_per_minute: Optional[int] = None  # None for no limit
+    tokens_per_minute: Optional[int] = None
+
+
+def rate_limits_from_config(config: dict, provider: str) -> RateLimits:
+    """The rate limits of a provider in config.toml, raises ValueError for invalid ones"""
+    section = config.get("rate_limits", {}).get(provider, {})
+    unknown = set(section) - {"requests_per_minute", "tokens_per_minute"}
+    if unknown:
+        raise ValueError(f"Unknown rate limits for {provider}: {', '.join(sorted(unknown))}")
+    for name, value in section.items():
+        if not isinstance(value, int) or isinstance(value, bool) or value < 1:
+            raise ValueError(f"rate_limits.{provider}.{name} must be a positive integer, got {value!r}")
+    return RateLimits(section.get("requests_per_minute"), section.get("tokens_per_minute"))
+
+
+def estimate_tokens(text: str) -> int:
+    return max(1, len(text) // CHARS_PER_TOKEN)
+
+
+class TokenBucket:
+    def __init__(self, per_minute: int, clock: Callable[[], float] = time.monotonic):
+        """A bucket holding up to a minute's worth, full at first"""
+        self.capacity = float(per_minute)
+        self.rate = per_minute / 60  # Refilled per second
+        self.clock = clock
+        self.level = self.capacity
+        self.updated = clock()
+
+    def _refill(self):
+        now = self.clock()
+        self.level = min(self.capacity, self.level + (now - self.updated) * self.rate)
+        self.updated = now
+
+    def wait_time(self, amount: float) -> float:
+        """Seconds until the bucket has room for an amount, an amount above its capacity waits for a full bucket"""
+        self._refill()
+        missing = min(amount, self.capacity) - self.level
+        return max(0.0, missing / self.rate)
+
+    def take(self, amount: float):
+        # May go below zero, for answers longer than estimated, which holds back the next prompts
+        self._refill()
+        self.level -= amount
+
+
+class ProviderRateLimiter:
+    def __init__(self, limits: RateLimits, clock: Callable[[], float] = time.monotonic, sleep: Callable[[float], None] = time.sleep):
+        self.limits = limits
+        self.requests = TokenBucket(limits.requests_per_minute, clock) if limits.requests_per_minute else None
+        self.tokens = TokenBucket(limits.tokens_per_minute, clock) if limits.tokens_per_minute else None
+        self.sleep = sleep
+        self.waited_seconds = 0.0  # In total since the server started
+        self._lock = threading.Lock()
+
+    def acquire(self, prompt_tokens: int) -> float:
+        """Wait until a prompt of this many tokens may be sent and take it from the buckets, returns the seconds waited"""
+        waited = 0.0
+        # Holding the lock while sleeping queues the prompts in order, none jumps ahead of one already waiting
+        with self._lock:
+            while True:
+                wait = max(
+                    self.requests.wait_time(1) if self.requests else 0.0,
+                    self.tokens.wait_time(prompt_tokens) if self.tokens else 0.0,
+                )
+                if wait <= 0:
+                    break
+                self.sleep(wait)
+                waited += wait
+            if self.requests:
+                self.requests.take(1)
+            if self.tokens:
+                self.tokens.take(prompt_tokens)
+            self.waited_seconds += waited
+        return waited
+
+    def charge(self, answer_tokens: int):
+        """Take the tokens of an answer from the bucket once it arrived"""
+        if self.tokens:
+            with self._lock:
+                self.tokens.take(answer_tokens)
@@ -10,15 +10,46 @@ import pymupdf
 from pydantic import BaseModel
 import structlog
 
-from textbook.database import TextBookDatabase, BookInfo, ChapterInfo
+from textbook.database import TextBookDatabase, BookInfo, ChapterInfo, EntityInfo, CrossReferenceInfo, FlashcardInfo, ExerciseInfo, AttemptInfo, GradingJudgmentInfo, ReflectionInfo
+from textbook.cross_reference import find_labeled_entities, find_references, load_cross_reference_index, ENTITY_SOURCE_PATTERN, ENTITY_SOURCE_LLM, ENTITY_KINDS
+from textbook.external_grading import GradingServices
+from textbook.grading import ContextPart, prioritize_context, render_context, DEFAULT_CONTEXT_TOKEN_BUDGET, MAX_RECENT_MISCONCEPTIONS, ESCALATION_CONFIDENCE_THRESHOLD, JUDGMENT_INITIAL, JUDGMENT_LOW_CONFIDENCE, JUDGMENT_DISPUTE, DISPUTE_PENDING_REVIEW, STUDY_MODE_PRACTICE, PRIORITY_PROBLEM, PRIORITY_SOLUTION, PRIORITY_SOURCE_EXCERPT, PRIORITY_REFERENCED_ENTITY, PRIORITY_MISCONCEPTIONS, PRIORITY_SECTION_ENTITY, PRIORITY_CHAPTER_ENTITY
 from textbook.model import LLM
+from textbook.map_reduce import map_reduce
 from llm import Attachment
-from textbook.mineru import MinerURequest
+from textbook.ocr import MinerUBackend, OcrBackend
 from textbook.utils import detect_toc
+from textbook.utils.image_preprocessing import preprocess_scanned_page
 
 MAX_PAGE_FOR_TOC_DETECTION = 15 # Number of pages to read for TOC detection
 MAX_PAGE_FOR_ALIGNMENT_CHECK = 25 # Number of pages to read for alignment check in worst case, this is longer than the TOC detection because we may need to check both TOC and prefaces if TOC end is not set
 MIN_PAGE_CONTENT_LENGTH = 20 # Minimum length of page content to be considered valid
+HANDWRITING_IMAGE_DPI = 200 # Handwriting needs a higher resolution than printed text to be legible to the vision LLM
+LOW_CONFIDENCE_THRESHOLD = 0.6 # Transcriptions below this confidence are flagged for manual review
+ENTITY_EXTRACTION_PAGES_PER_PROMPT = 8 # Chapters are sent to the entity extraction in chunks of this many pages
+MAX_FLASHCARD_ENTITIES_PER_PROMPT = 20 # Entities sent to the flashcard generation at once
+
+# Ingestion modes, stored on book_info.book_ingestion_mode
+INGESTION_MODE_PRINTED = "printed" # Text layer first, MinerU OCR for scanned pages
+INGESTION_MODE_HANDWRITTEN = "handwritten" # Every page transcribed by the vision LLM
+INGESTION_MODES = (INGESTION_MODE_PRINTED, INGESTION_MODE_HANDWRITTEN)
+
+# Where a book came from, stored on book_info.book_source_type
+BOOK_SOURCE_PDF = "pdf"
+BOOK_SOURCE_IMAGE = "image"
+BOOK_SOURCE_WEB_ARTICLE = "web_article"
+BOOK_SOURCE_TRANSCRIPT = "transcript"
+BOOK_SOURCE_NOTEBOOK = "notebook"
+CODE_BOOK_SOURCES = (BOOK_SOURCE_NOTEBOOK,) # Sources whose pages are summarized with the code-aware prompt
+
+# Exercise types, stored on exercise_info.exercise_type
+EXERCISE_TYPE_TEXT = "text"
+EXERCISE_TYPE_CODE = "code"
+EXERCISE_TYPES = (EXERCISE_TYPE_TEXT, EXERCISE_TYPE_CODE)
+
+# Sources of a page transcription
+TRANSCRIPTION_SOURCE_LLM = "llm"
+TRANSCRIPTION_SOURCE_MANUAL = "manual"
 
 def cover_prompt(cover: str) -> str:
     return f"""
@@ -60,6 +91,124 @@ def page_summary_prompt(page: str, related_chapters: List[str], related_sections
      {page}
     """
 
+def code_page_summary_prompt(page: str, related_chapters: List[str], related_sections: List[str]) -> str:
+    return f"""
+    Extract the summary of the following page of a course notebook with rules:
+    - the page mixes explanations, fenced code cells and their outputs (fenced as output)
+    - use bullet points to summarize the concepts, and for every code cell what it computes and which functions, classes or library APIs it introduces
+    - keep short identifiers, signatures and formulas verbatim in backticks, do not paraphrase them
+    - note the outputs that demonstrate a result, and any errors the notebook shows on purpose
+    - if it contain more than one exercises or "try it yourself" cells, mark this page as containing exercise
+    - if it the page contain more than one chapter or section, summarize the chapter or section separately
+    - all title should be not contain any symbols
+    - all title should be in lower case
+
+    Page: 
+     {page}
+    """
+
+def code_exercise_prompt(page: str, language: str, count: int, difficulty: Optional[int] = None, output_language: Optional[str] = None) -> str:
+    difficulty_rule = f"\n    - aim for a difficulty_level of {difficulty}" if difficulty is not None else ""
+    language_rule = f"\n    - write the descriptions and code comments in {output_language}" if output_language else ""
+    return f"""
+    Write {count} coding exercises in {language} that practice the material of the following page of a course notebook with rules:
+    - each exercise asks for a small, self-contained function or snippet that can be checked by running it
+    - the description states the task, the expected inputs and outputs and one example, without giving the solution away
+    - starter_code is runnable {language} with the signature and a placeholder body, reusing the names the notebook defines where it helps
+    - solution_code is a complete, correct solution that only uses what the page introduces or the standard library
+    - difficulty_level is an integer from 1 (direct application) to 5 (combines several ideas of the page){difficulty_rule}
+    - do not write exercises about content that is not on the page{language_rule}
+
+    Page:
+     {page}
+    """
+
+def entity_extraction_prompt(chapter_title: str, pages: str) -> str:
+    return f"""
+    Extract the named entities stated in the following pages of the chapter "{chapter_title}" with rules:
+    - entities are definitions, theorems, lemmas, corollaries, propositions, examples, remarks, exercises, figures, tables and numbered or named equations
+    - kind is one of: theorem, lemma, corollary, proposition, definition, example, remark, exercise, figure, table, equation
+    - label is the number printed with the entity (e.g. 3.2), empty if it has none
+    - name is the name of the entity if the book gives one or it is well known (e.g. bolzano-weierstrass theorem, cauchy sequence), otherwise empty
+    - statement is the full statement in LaTeX for mathematics, without the proof
+    - page_number is the number in the [Page n] marker of the page the entity is stated on
+    - only extract entities stated on these pages, not the ones merely referenced
+
+    Pages:
+     {pages}
+    """
+
+def flashcard_prompt(entities: str, cards_per_entity: int, output_language: Optional[str] = None) -> str:
+    language_rule = f"\n    - write the front and back in {output_language}" if output_language else ""
+    return f"""
+    Write {cards_per_entity} flashcard(s) for each of the following numbered entities of a textbook with rules:
+    - the front asks one precise question that can be answered from memory, e.g. the statement of a theorem from its name, the term from its definition, the conditions of a lemma
+    - the back is the short, complete answer, mathematics in LaTeX
+    - do not put the answer on the front
+    - entity_number is the number of the entity in the list the card is about{language_rule}
+
+    Entities:
+     {entities}
+    """
+
+def grading_prompt(context: str, answer: str, dispute: Optional[str] = None) -> str:
+    # A disputed grade is regraded with the learner's objection and the previous feedback
+    dispute_text = f"""
+    The learner disputes an earlier grade of this answer, consider the objection on its merits, not as a reason to change the grade by itself:
+     {dispute}
+    """ if dispute else ""
+    return f"""
+    Grade the following answer of a learner to a textbook exercise with rules:
+    - the context contains the problem, and may contain a reference solution, an excerpt of the section the problem comes from, definitions and theorems it relies on, and mistakes the learner made recently
+    - judge the answer against the problem, use the reference solution as a guide, not as the only correct answer
+    - score is a number from 0 (wrong or missing) to 1 (complete and correct), give partial credit for correct steps
+    - is_correct is true only if the answer solves the problem without significant errors
+    - feedback is addressed to the learner, points out what is right and what is wrong, and refers to the definitions and theorems of the context where it helps
+    - misconceptions lists each underlying misunderstanding shown by the answer in one sentence, not mere slips, empty if there are none
+    - if the answer repeats one of the recent mistakes, mention it in the feedback
+    - confidence is a number from 0 to 1 of how sure you are of the score, low when the answer is ambiguous, unusual or hard to verify from the context
+    {dispute_text}
+    Context:
+     {context}
+
+    Answer:
+     {answer}
+    """
+
+def self_explanation_prompt(problem: str, reference_solution: Optional[str], answer: str, explanation: str) -> str:
+    reference_text = f"""
+    Reference solution:
+     {reference_solution}
+    """ if reference_solution else ""
+    return f"""
+    Evaluate a learner's explanation of how they solved a textbook exercise with rules:
+    - solution_steps are the essential steps of a complete solution, in order, one short sentence each; follow the reference solution if given, otherwise solve the problem yourself
+    - missing_steps are the solution steps the explanation leaves out, only states without justification, or gets wrong
+    - completeness is a number from 0 to 1, the share of the solution steps the explanation covers with a correct justification
+    - feedback is addressed to the learner, two or three sentences on what the explanation shows they understood and what to revisit
+    - judge the explanation, not the wording or the answer itself
+    {reference_text}
+    Problem:
+     {problem}
+
+    Answer of the learner:
+     {answer}
+
+    Explanation of the learner:
+     {explanation}
+    """
+
+def handwriting_transcription_prompt() -> str:
+    return """
+    Transcribe the handwritten notes in the attached image with rules:
+    - keep the reading order of the page, including headings, lists and numbering
+    - write mathematics in LaTeX, inline as $...$ and displayed as $$...$$
+    - describe diagrams briefly in square brackets instead of skipping them
+    - do not correct or complete the author's content, transcribe what is written
+    - list every word or formula you could not read with certainty in unclear_passages
+    - confidence is a number between 0 and 1 for how accurately the whole page was transcribed
+    """
+
 # Pydantic model for TOC
 class SectionSchema(BaseModel):
     index_string: str
@@ -92,6 +241,56 @@ class PageSchema(BaseModel):
         return "\n".join([f"{summary.title}: {summary.summary}" for summary in self.page_summary])
 
 
+class CodeExerciseSchema(BaseModel):
+    description: str
+    starter_code: str
+    solution_code: str
+    difficulty_level: int
+
+class CodeExercisesSchema(BaseModel):
+    exercises: List[CodeExerciseSchema]
+
+
+class EntitySchema(BaseModel):
+    kind: str
+    label: str
+    name: str
+    statement: str
+    page_number: int
+
+class EntitiesSchema(BaseModel):
+    entities: List[EntitySchema]
+
+class FlashcardSchema(BaseModel):
+    entity_number: int
+    front: str
+    back: str
+
+class FlashcardsSchema(BaseModel):
+    cards: List[FlashcardSchema]
+
+
+class GradingSchema(BaseModel):
+    score: float
+    is_correct: bool
+    confidence: float
+    feedback: str
+    misconceptions: List[str]
+
+
+class SelfExplanationSchema(BaseModel):
+    solution_steps: List[str]
+    missing_steps: List[str]
+    completeness: float
+    feedback: str
+
+
+class TranscriptionSchema(BaseModel):
+    text: str
+    confidence: float
+    unclear_passages: List[str]
+
+
 def _save_images_to_temp_attachment(image: Image.Image) -> Attachment:
     with tempfile.NamedTemporaryFile(suffix='.png', delete=False) as tmp_file:
         tmp_path = tmp_file.name
@@ -102,9 +301,26 @@ def _remove_temp_attachment(attachment: Attachment):
     if attachment.path and os.path.exists(attachment.path):
         os.unlink(attachment.path)
 
+def _grading_judgment(llm: LLM, reason: str, grade: GradingSchema) -> GradingJudgmentInfo:
+    return GradingJudgmentInfo(
+        model_name=getattr(llm, "model_name", None),
+        reason=reason,
+        score=min(max(grade.score, 0.0), 1.0),
+        is_correct=grade.is_correct,
+        confidence=min(max(grade.confidence, 0.0), 1.0),
+        feedback=grade.feedback,
+    )
+
+def _misconceptions(grade: GradingSchema) -> List[str]:
+    return [misconception.strip() for misconception in grade.misconceptions if misconception.strip()]
+
+def _containing_block(blocks, page_number: int):
+    # Chapters and sections whose end is unknown only cover their first page
+    return next((block for block in blocks if block.start_page_number <= page_number <= (block.end_page_number if block.end_page_number is not None else block.start_page_number)), None)
+
 class LazyTextbookReader:
     
-    def __init__(self, pdf_path: Path, llm: LLM, database: TextBookDatabase, force_text_only_extraction: bool = False):
+    def __init__(self, pdf_path: Path, llm: LLM, database: TextBookDatabase, force_text_only_extraction: bool = False, preprocess_scanned_pages: bool = True, ingestion_mode: Optional[str] = None, ocr_backend: Optional[OcrBackend] = None):
 
         self.logger = structlog.get_logger(__name__)
 
@@ -122,6 +338,17 @@ class LazyTextbookReader:
 
         self.force_text_only_extraction = force_text_only_extraction
 
+        # Deskew, rotate and contrast-stretch pages without a text layer before OCR or vision-LLM calls
+        self.preprocess_scanned_pages = preprocess_scanned_pages
+
+        # Reads the pages without a text layer, MinerU unless [ocr] in config.toml picks another backend
+        self.ocr_backend = ocr_backend or MinerUBackend()
+
+        # Resolved from the book on first use when not given
+        if ingestion_mode is not None and ingestion_mode not in INGESTION_MODES:
+            raise ValueError(f"Unsupported ingestion mode: {ingestion_mode}")
+        self._ingestion_mode = ingestion_mode
+
         # Current book ID
         self.book_info: Optional[BookInfo] = None
 
@@ -130,6 +357,10 @@ class LazyTextbookReader:
             raise FileNotFoundError(f"PDF file not found: {self.pdf_path}")
 
         self.pdf_document = pymupdf.open(self.pdf_path)
+        if self.pdf_document.needs_pass:
+            self.pdf_document.close()
+            self.pdf_document = None
+            raise ValueError(f"PDF file is password protected: {self.pdf_path}")
         return self
     
     def __exit__(self, exc_type, exc_val, exc_tb):
@@ -159,7 +390,7 @@ class LazyTextbookReader:
         else:
             raise ValueError(f"Unsupported text type: {type(text)}")
     
-    def get_page_as_image(self, page_number: int, dpi: int = 150) -> Image.Image:
+    def get_page_as_image(self, page_number: int, dpi: int = 150, preprocess: bool = False) -> Image.Image:
         if not self.pdf_document:
             raise RuntimeError("PDF document not opened. Use context manager.")
         
@@ -172,45 +403,25 @@ class LazyTextbookReader:
         
         img_data = pix.tobytes("png")
         img = Image.open(io.BytesIO(img_data))
+        if preprocess:
+            img, rotation = preprocess_scanned_page(img)
+            self.logger.debug(f"Preprocessed page {page_number}, rotated by {rotation:.2f} degrees")
         return img
     
     def get_page_as_text_from_image(self, page_number: int) -> str:
         if not self.pdf_document:
             raise RuntimeError("PDF document not opened. Use context manager.")
         
-        img = self.get_page_as_image(page_number)
-        
-        with tempfile.NamedTemporaryFile(suffix='.png', delete=False) as tmp_file:
-            tmp_path = tmp_file.name
-        img.save(tmp_path, 'PNG')
-        
-        try:
-            request = MinerURequest(files=[tmp_path])
-            request.set_return_md(True)
-            request.set_start_page_id(0)
-            request.set_end_page_id(0)
-            
-            results = request.request()
-            
-            if results:
-                for file_result in results.values():
-                    if 'md_content' in file_result:
-                        return file_result['md_content']
-                for file_result in results.values():
-                    if isinstance(file_result, dict):
-                        for key in ['content', 'text', 'markdown', 'md_content']:
-                            if key in file_result:
-                                return str(file_result[key])
-            
-            return ""
-        finally:
-            if os.path.exists(tmp_path):
-                os.unlink(tmp_path)
+        img = self.get_page_as_image(page_number, preprocess=self.preprocess_scanned_pages)
+        return self.ocr_backend.ocr_image(img)
     
     def get_page_content(self, page_number: int, apply_alignment_offset: bool = False) -> str:
         if apply_alignment_offset and self.book_info is not None and self.book_info.book_alignment_offset is not None:
             page_number = page_number + self.book_info.book_alignment_offset
 
+        if self.ingestion_mode == INGESTION_MODE_HANDWRITTEN:
+            return self.get_handwritten_page_text(page_number)
+
         extracted_text = self.get_page_as_text(page_number).strip()
 
         if len(extracted_text) < MIN_PAGE_CONTENT_LENGTH and not self.force_text_only_extraction:
@@ -223,15 +434,69 @@ class LazyTextbookReader:
         if apply_alignment_offset and self.book_info is not None and self.book_info.book_alignment_offset is not None:
             page_number = page_number + self.book_info.book_alignment_offset
 
-        image = self.get_page_as_image(page_number)
+        if self.ingestion_mode == INGESTION_MODE_HANDWRITTEN:
+            image = self.get_page_as_image(page_number, dpi=HANDWRITING_IMAGE_DPI, preprocess=self.preprocess_scanned_pages)
+            return self.get_handwritten_page_text(page_number), image
 
         extracted_text = self.get_page_as_text(page_number).strip()
-        if len(extracted_text) < MIN_PAGE_CONTENT_LENGTH:
+        is_scanned = len(extracted_text) < MIN_PAGE_CONTENT_LENGTH
+
+        image = self.get_page_as_image(page_number, preprocess=is_scanned and self.preprocess_scanned_pages)
+        if is_scanned:
             extracted_text = self.get_page_as_text_from_image(page_number)
 
         return extracted_text, image
 
     
+    # ------------------------------------------------------------
+    # Handwriting related functions
+    # ------------------------------------------------------------
+
+    @property
+    def ingestion_mode(self) -> str:
+        if self._ingestion_mode is None:
+            book = self.book_info or self.database.get_book_by_file_name(self.pdf_name)
+            if book is None:
+                return INGESTION_MODE_PRINTED
+            self._ingestion_mode = book.book_ingestion_mode or INGESTION_MODE_PRINTED
+        return self._ingestion_mode
+
+    def transcribe_handwritten_page(self, page_number: int) -> TranscriptionSchema:
+        """Transcribe a page with the vision LLM, bypassing the text layer and MinerU"""
+        image = self.get_page_as_image(page_number, dpi=HANDWRITING_IMAGE_DPI, preprocess=self.preprocess_scanned_pages)
+        attachment = _save_images_to_temp_attachment(image)
+        try:
+            transcription = self.llm.prompt_with_schema_and_attachments(handwriting_transcription_prompt(), schema=TranscriptionSchema, attachments=[attachment])
+        finally:
+            _remove_temp_attachment(attachment)
+
+        transcription.confidence = min(max(transcription.confidence, 0.0), 1.0)
+        if transcription.confidence < LOW_CONFIDENCE_THRESHOLD:
+            self.logger.warning(f"Low confidence transcription for page {page_number}: {transcription.confidence:.2f}", unclear_passages=transcription.unclear_passages)
+        return transcription
+
+    def update_page_transcription(self, page_number: int, overwrite: bool = False) -> Optional[int]:
+        """Transcribe a page and store it, keeping manual corrections unless overwrite is set"""
+        if self.book_info is None or self.book_info.book_id is None:
+            raise ValueError("Book basic information not extracted, please extract it first")
+
+        existing = self.database.get_page_by_book_id_and_page_number(self.book_info.book_id, page_number)
+        if existing is not None and existing.transcription is not None and not overwrite:
+            return existing.page_id
+
+        transcription = self.transcribe_handwritten_page(page_number)
+        return self.database.save_page_transcription(self.book_info.book_id, page_number, transcription.text, transcription.confidence, TRANSCRIPTION_SOURCE_LLM)
+
+    def get_handwritten_page_text(self, page_number: int) -> str:
+        # Before the book row exists (e.g. reading the cover on upload) there is nowhere to store the transcription
+        if self.book_info is None and not self.check_if_book_exists_and_load():
+            return self.transcribe_handwritten_page(page_number).text
+
+        assert self.book_info is not None
+        self.update_page_transcription(page_number)
+        page_info = self.database.get_page_by_book_id_and_page_number(self.book_info.book_id, page_number)
+        return page_info.transcription if page_info is not None and page_info.transcription is not None else ""
+
     # ------------------------------------------------------------
     # Book related functions
     # ------------------------------------------------------------
@@ -251,7 +516,7 @@ class LazyTextbookReader:
         book_basic_info = self.llm.prompt_with_schema_and_attachments(cover_prompt(cover_text), schema=BookSchema, attachments=[cover])
         _remove_temp_attachment(cover)
         # deserialize the response to a BookBasicInfo object
-        book_info = self.database.create_book(book_basic_info.book_name, book_basic_info.book_author, book_basic_info.book_keywords, self.pdf_name, self.get_total_pages())
+        book_info = self.database.create_book(book_basic_info.book_name, book_basic_info.book_author, book_basic_info.book_keywords, self.pdf_name, self.get_total_pages(), self.ingestion_mode)
         self.book_info = book_info
 
     # ------------------------------------------------------------
@@ -433,10 +698,327 @@ class LazyTextbookReader:
         related_sections = [section.title for section in self.database.get_sections_by_book_id_and_page_range(self.book_info.book_id, page_number, page_number)]
         
         page_text = self.get_page_content(page_number)
-        page_summary = self.llm.prompt_with_schema(page_summary_prompt(page_text, related_chapters, related_sections), schema=PageSchema)
+        # Notebook pages are mostly code, the generic prompt loses the identifiers and what the cells compute
+        prompt = code_page_summary_prompt if self.book_info.book_source_type in CODE_BOOK_SOURCES else page_summary_prompt
+        page_summary = self.llm.prompt_with_schema(prompt(page_text, related_chapters, related_sections), schema=PageSchema)
 
         page_id = self.database.try_create_page_info(self.book_info.book_id, page_number, page_summary.full_summary())
 
         return page_id
 
+    # ------------------------------------------------------------
+    # Cross reference related functions
+    # ------------------------------------------------------------
+
+    def update_cross_references(self) -> Tuple[int, int]:
+        """
+        Rebuild the entity index of the book from its pages, then find and resolve the references between pages
+
+        Returns:
+            Tuple[int, int]: The number of indexed entities and the number of references found
+        """
+        if self.book_info is None or self.book_info.book_id is None:
+            raise ValueError("Book basic information not extracted, please extract it first")
+
+        book_id = self.book_info.book_id
+        sections = self.database.get_sections_by_book_id(book_id)
+        chapters = self.database.get_chapters_by_book_id(book_id)
+        offset = self.book_info.book_alignment_offset or 0
+
+        page_texts = [self.get_page_content(page_number) for page_number in range(self.get_total_pages())]
+        entities = []
+        for page_number, page_text in enumerate(page_texts):
+            for entity in find_labeled_entities(page_text, page_number):
+                # The TOC is in printed page numbers, entities keep the page of the PDF
+                section = _containing_block(sections, page_number - offset)
+                chapter = _containing_block(chapters, page_number - offset)
+                entities.append(EntityInfo(
+                    entity_kind=entity.kind,
+                    entity_label=entity.label,
+                    entity_name=entity.name,
+                    statement=entity.statement,
+                    page_number=page_number,
+                    chapter_id=chapter.chapter_id if chapter is not None else None,
+                    section_id=section.section_id if section is not None else None,
+                ))
+        self.database.replace_entities(book_id, ENTITY_SOURCE_PATTERN, entities)
+
+        index = load_cross_reference_index(self.database, book_id)
+        references = []
+        for page_number, page_text in enumerate(page_texts):
+            for reference in find_references(page_text, page_number):
+                target = index.resolve(reference.kind, reference.label)
+                if target is None and reference.bare:
+                    continue
+                references.append(CrossReferenceInfo(
+                    reference_kind=reference.kind,
+                    reference_label=reference.label,
+                    reference_text=reference.text,
+                    page_number=page_number,
+                    char_start=reference.start,
+                    char_end=reference.end,
+                    target_entity_id=target.entity_id if target is not None else None,
+                    target_section_id=target.section_id if target is not None else None,
+                    target_chapter_id=target.chapter_id if target is not None else None,
+                    target_page_number=target.page_number if target is not None else None,
+                ))
+        self.database.replace_cross_references(book_id, references)
+        return len(entities), len(references)
+
+    # ------------------------------------------------------------
+    # Exercise related functions
+    # ------------------------------------------------------------
+
+    def generate_code_exercises(self, page_number: int, language: str, count: int = 2, difficulty: Optional[int] = None, output_language: Optional[str] = None) -> List[int]:
+        """Generate coding exercises from a page and store them as code exercises, around a difficulty and in an output language if given"""
+        if self.book_info is None or self.book_info.book_id is None:
+            raise ValueError("Book basic information not extracted, please extract it first")
+
+        page_text = self.get_page_content(page_number)
+        if len(page_text.strip()) < MIN_PAGE_CONTENT_LENGTH:
+            return []
+
+        generated = self.llm.prompt_with_schema(code_exercise_prompt(page_text, language, count, difficulty, output_language), schema=CodeExercisesSchema)
+        exercise_ids = []
+        for exercise in generated.exercises[:count]:
+            exercise_ids.append(self.database.create_exercise(
+                self.book_info.book_id,
+                page_number,
+                exercise.description,
+                exercise_type=EXERCISE_TYPE_CODE,
+                starter_code=exercise.starter_code,
+                solution_code=exercise.solution_code,
+                code_language=language,
+                difficulty_level=min(max(exercise.difficulty_level, 1), 5),
+            ))
+        return exercise_ids
+
+    # ------------------------------------------------------------
+    # Entity and flashcard related functions
+    # ------------------------------------------------------------
+
+    def extract_chapter_entities(self, chapter_id: int) -> List[int]:
+        """Extract the definitions, theorems, equations, ... of a chapter with the LLM, replacing earlier extractions"""
+        if self.book_info is None or self.book_info.book_id is None:
+            raise ValueError("Book basic information not extracted, please extract it first")
+
+        book_id = self.book_info.book_id
+        chapter = next((chapter for chapter in self.database.get_chapters_by_book_id(book_id) if chapter.chapter_id == chapter_id), None)
+        if chapter is None:
+            raise ValueError(f"Chapter {chapter_id} not found in book {book_id}")
+        sections = self.database.get_sections_by_book_id(book_id)
+        offset = self.book_info.book_alignment_offset or 0
+
+        start_page = chapter.start_page_number
+        end_page = min(chapter.end_page_number if chapter.end_page_number is not None else start_page, self.get_total_pages() - 1 - offset)
+        # Markers carry PDF page numbers, so the LLM reports the page entities keep. Pages are read up front, the
+        # chunks are prompted on worker threads
+        chunks = []
+        for chunk_start in range(start_page, end_page + 1, ENTITY_EXTRACTION_PAGES_PER_PROMPT):
+            pages = range(chunk_start + offset, min(chunk_start + ENTITY_EXTRACTION_PAGES_PER_PROMPT, end_page + 1) + offset)
+            chunks.append((pages, "\n\n".join(f"[Page {page_number}]\n{self.get_page_content(page_number)}" for page_number in pages)))
+
+        def extract(chunk: Tuple[range, str]) -> List[EntityInfo]:
+            pages, text = chunk
+            extracted = self.llm.prompt_with_schema(entity_extraction_prompt(chapter.title, text), schema=EntitiesSchema)
+            entities = []
+            for entity in extracted.entities:
+                kind = entity.kind.strip().lower()
+                if kind not in ENTITY_KINDS:
+                    self.logger.warning(f"Skipping entity of unknown kind {entity.kind} in chapter {chapter_id}")
+                    continue
+                page_number = entity.page_number if entity.page_number in pages else pages[0]
+                section = _containing_block(sections, page_number - offset)
+                entities.append(EntityInfo(
+                    entity_kind=kind,
+                    entity_label=entity.label.strip() or None,
+                    entity_name=entity.name.strip() or None,
+                    statement=entity.statement.strip() or None,
+                    page_number=page_number,
+                    chapter_id=chapter_id,
+                    section_id=section.section_id if section is not None else None,
+                ))
+            return entities
+
+        extraction = map_reduce(chunks, extract, lambda mapped: [entity for entities in mapped for entity in entities])
+        for failure in extraction.failures:
+            self.logger.warning(f"Skipping pages {chunks[failure.index][0][0]}-{chunks[failure.index][0][-1]} of chapter {chapter_id}: {failure.error}")
+        return self.database.replace_entities(book_id, ENTITY_SOURCE_LLM, extraction.result, chapter_id=chapter_id)
+
+    def generate_entity_flashcards(self, entity_kinds: Optional[List[str]] = None, chapter_id: Optional[int] = None, cards_per_entity: int = 1, output_language: Optional[str] = None) -> List[int]:
+        """Generate flashcards from the entity index, only for some entity kinds and chapters if given"""
+        if self.book_info is None or self.book_info.book_id is None:
+            raise ValueError("Book basic information not extracted, please extract it first")
+
+        book_id = self.book_info.book_id
+        entities = [entity for entity in self.database.get_entities_by_book_id(book_id, entity_kinds) if chapter_id is None or entity.chapter_id == chapter_id]
+        entities = [entity for entity in entities if entity.statement]
+
+        card_ids = []
+        for chunk_start in range(0, len(entities), MAX_FLASHCARD_ENTITIES_PER_PROMPT):
+            chunk = entities[chunk_start:chunk_start + MAX_FLASHCARD_ENTITIES_PER_PROMPT]
+            listing = "\n".join(
+                f"{number}. {entity.entity_kind} {entity.entity_label or ''} {f'({entity.entity_name})' if entity.entity_name else ''}: {entity.statement}"
+                for number, entity in enumerate(chunk, start=1)
+            )
+            generated = self.llm.prompt_with_schema(flashcard_prompt(listing, cards_per_entity, output_language), schema=FlashcardsSchema)
+
+            cards = []
+            for card in generated.cards:
+                if not 1 <= card.entity_number <= len(chunk):
+                    continue
+                entity = chunk[card.entity_number - 1]
+                cards.append(FlashcardInfo(
+                    front=card.front,
+                    back=card.back,
+                    entity_id=entity.entity_id,
+                    entity_kind=entity.entity_kind,
+                    chapter_id=entity.chapter_id,
+                    page_number=entity.page_number,
+                ))
+            card_ids.extend(self.database.create_flashcards(book_id, cards))
+        return card_ids
+
+    # ------------------------------------------------------------
+    # Grading related functions
+    # ------------------------------------------------------------
+
+    def assemble_grading_context(self, exercise: ExerciseInfo, token_budget: int = DEFAULT_CONTEXT_TOKEN_BUDGET) -> List[ContextPart]:
+        """
+        Assemble the context to grade an attempt at an exercise, fitted into the token budget
+
+        The context holds the problem, its reference solution, the page it comes from, the entities it refers to or
+        that are stated in its section and chapter, and the learner's recent misconceptions.
+        """
+        if self.book_info is None or self.book_info.book_id is None:
+            raise ValueError("Book basic information not extracted, please extract it first")
+
+        book_id = self.book_info.book_id
+        offset = self.book_info.book_alignment_offset or 0
+        section = _containing_block(self.database.get_sections_by_book_id(book_id), exercise.page_number - offset)
+        chapter = _containing_block(self.database.get_chapters_by_book_id(book_id), exercise.page_number - offset)
+
+        problem = exercise.exercise_description
+        if exercise.starter_code:
+            problem += f"\n\nStarter code ({exercise.code_language or 'code'}):\n{exercise.starter_code}"
+        parts = [ContextPart("Problem", problem, PRIORITY_PROBLEM, truncatable=False)]
+        if exercise.solution_code:
+            parts.append(ContextPart("Reference solution", exercise.solution_code, PRIORITY_SOLUTION, truncatable=False))
+
+        if 0 <= exercise.page_number < self.get_total_pages():
+            source = f"Source: {section.title}" if section is not None else "Source"
+            parts.append(ContextPart(f"{source} (page {exercise.page_number})", self.get_page_content(exercise.page_number), PRIORITY_SOURCE_EXCERPT))
+
+        # Entities the problem refers to first, then those stated near it
+        index = load_cross_reference_index(self.database, book_id)
+        referenced = {target.entity_id for target in (index.resolve(reference.kind, reference.label) for reference in find_references(exercise.exercise_description, exercise.page_number)) if target is not None and target.entity_id is not None}
+        entity_parts = []
+        for entity in self.database.get_entities_by_book_id(book_id):
+            if not entity.statement:
+                continue
+            if entity.entity_id in referenced:
+                priority = PRIORITY_REFERENCED_ENTITY
+            elif section is not None and entity.section_id == section.section_id:
+                priority = PRIORITY_SECTION_ENTITY
+            elif chapter is not None and entity.chapter_id == chapter.chapter_id:
+                priority = PRIORITY_CHAPTER_ENTITY
+            else:
+                continue
+            title = " ".join(part for part in (entity.entity_kind.capitalize(), entity.entity_label, f"({entity.entity_name})" if entity.entity_name else None) if part)
+            entity_parts.append((abs(entity.page_number - exercise.page_number), ContextPart(title, entity.statement, priority)))
+        # Closer entities are kept first within a priority
+        parts.extend(part for _, part in sorted(entity_parts, key=lambda item: item[0]))
+
+        misconceptions = self.database.get_recent_misconceptions(MAX_RECENT_MISCONCEPTIONS)
+        if misconceptions:
+            parts.append(ContextPart("Recent mistakes of the learner", "\n".join(f"- {misconception.description}" for misconception in misconceptions), PRIORITY_MISCONCEPTIONS))
+
+        return prioritize_context(parts, token_budget)
+
+    def grade_attempt(self, exercise_id: int, answer: str, token_budget: int = DEFAULT_CONTEXT_TOKEN_BUDGET, escalation_llm: Optional[LLM] = None, study_mode: str = STUDY_MODE_PRACTICE, grading_services: Optional[GradingServices] = None) -> int:
+        """
+        Grade an answer to an exercise of this book with the assembled context, and store the attempt
+
+        When the grader's confidence is below ESCALATION_CONFIDENCE_THRESHOLD and an escalation model is given, the
+        answer is graded again by it and its grade counts. When a grading service is set for the exercise's type, its
+        verdict is merged with the model's grade and the merged judgment counts, unless the service failed. All
+        judgments are stored.
+        """
+        if self.book_info is None or self.book_info.book_id is None:
+            raise ValueError("Book basic information not extracted, please extract it first")
+
+        exercise = self.database.get_exercise_info(exercise_id)
+        if exercise is None or exercise.book_id != self.book_info.book_id:
+            raise ValueError(f"Exercise {exercise_id} not found in book {self.book_info.book_id}")
+
+        context = render_context(self.assemble_grading_context(exercise, token_budget))
+        prompt = grading_prompt(context, answer)
+        grade = self.llm.prompt_with_schema(prompt, schema=GradingSchema)
+        judgments = [_grading_judgment(self.llm, JUDGMENT_INITIAL, grade)]
+
+        if escalation_llm is not None and judgments[0].confidence < ESCALATION_CONFIDENCE_THRESHOLD:
+            self.logger.info(f"Escalating grading of exercise {exercise_id} with confidence {judgments[0].confidence}")
+            grade = escalation_llm.prompt_with_schema(prompt, schema=GradingSchema)
+            judgments.append(_grading_judgment(escalation_llm, JUDGMENT_LOW_CONFIDENCE, grade))
+
+        if grading_services is not None:
+            external = grading_services.grade(exercise, answer, judgments[-1])
+            if external is not None:
+                judgments.append(external)
+
+        return self.database.create_attempt(exercise_id, answer, judgments, grading_context=context, misconceptions=_misconceptions(grade), study_mode=study_mode)
+
+    def escalate_attempt(self, attempt_id: int, escalation_llm: LLM, reason: str = JUDGMENT_DISPUTE, comment: Optional[str] = None) -> AttemptInfo:
+        """Grade an attempt again with the escalation model, on the context it was first graded with, and make that the grade"""
+        attempt = self.database.get_attempt_info(attempt_id)
+        if attempt is None:
+            raise ValueError(f"Attempt {attempt_id} not found")
+
+        dispute = None
+        if reason == JUDGMENT_DISPUTE:
+            dispute = f"Previous feedback: {attempt.feedback}\n     Objection: {comment or 'none given'}"
+        grade = escalation_llm.prompt_with_schema(grading_prompt(attempt.grading_context or "", attempt.answer, dispute), schema=GradingSchema)
+        judgment = _grading_judgment(escalation_llm, reason, grade)
+        self.database.add_grading_judgment(attempt_id, judgment, _misconceptions(grade))
+        return self.database.get_attempt_info(attempt_id)
+
+    def dispute_attempt(self, attempt_id: int, comment: Optional[str], escalation_llm: Optional[LLM] = None) -> int:
+        """File a dispute of a grade, regrade it with the escalation model if given, and queue it for manual review"""
+        dispute_id = self.database.create_dispute(attempt_id, comment, DISPUTE_PENDING_REVIEW)
+        if escalation_llm is not None:
+            attempt = self.escalate_attempt(attempt_id, escalation_llm, JUDGMENT_DISPUTE, comment)
+            self.database.update_dispute_escalation(dispute_id, attempt.score, attempt.is_correct)
+        return dispute_id
+
+    # ------------------------------------------------------------
+    # Reflection related functions
+    # ------------------------------------------------------------
+
+    def evaluate_self_explanation(self, attempt_id: int, explanation: str) -> int:
+        """Evaluate the learner's explanation of an attempt against the solution steps, and add it to the reflection journal"""
+        if self.book_info is None or self.book_info.book_id is None:
+            raise ValueError("Book basic information not extracted, please extract it first")
 
+        attempt = self.database.get_attempt_info(attempt_id)
+        if attempt is None or attempt.book_id != self.book_info.book_id:
+            raise ValueError(f"Attempt {attempt_id} not found in book {self.book_info.book_id}")
+        exercise = self.database.get_exercise_info(attempt.exercise_id)
+        if exercise is None:
+            raise ValueError(f"Exercise {attempt.exercise_id} not found")
+
+        reference_solution = exercise.solution_code or (exercise.details.study_guide if exercise.details else None)
+        evaluation = self.llm.prompt_with_schema(self_explanation_prompt(exercise.exercise_description, reference_solution, attempt.answer, explanation), schema=SelfExplanationSchema)
+
+        offset = self.book_info.book_alignment_offset or 0
+        chapter = _containing_block(self.database.get_chapters_by_book_id(self.book_info.book_id), exercise.page_number - offset)
+        return self.database.create_reflection(ReflectionInfo(
+            attempt_id=attempt_id,
+            exercise_id=exercise.exercise_id,
+            explanation=explanation,
+            completeness=min(max(evaluation.completeness, 0.0), 1.0),
+            solution_steps="\n".join(step.strip() for step in evaluation.solution_steps if step.strip()),
+            missing_steps="\n".join(step.strip() for step in evaluation.missing_steps if step.strip()),
+            feedback=evaluation.feedback,
+            chapter_id=chapter.chapter_id if chapter is not None else None,
+            book_id=self.book_info.book_id,
+        ))
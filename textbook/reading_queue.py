@@ -0,0 +1,152 @@
+# Incremental reading
+# Users enqueue sections and excerpts to read in portions over time. Every reading pushes an item further out, the
+# interval growing by a fixed factor, until the user marks it done. Due reading items are intermixed with the review
+# cards of a study session, one after every few cards. Extracting a passage turns it into cloze cards in one step:
+# the passage is kept as a highlight, every cloze term becomes a card with the term blanked out, and the passage
+# can be enqueued to be read again.
+
+from dataclasses import dataclass
+from datetime import datetime, timedelta, timezone
+from typing import List, Optional, Tuple, Union
+
+from textbook.database import CardStateInfo, FlashcardInfo, HighlightInfo, ReadingItemInfo, TextBookDatabase
+from textbook.review import review_batch
+
+FIRST_READING_INTERVAL_DAYS = 1.0
+READING_INTERVAL_FACTOR = 2.0
+READING_EVERY_N_CARDS = 4  # A due reading item follows every this many review cards
+CLOZE_BLANK = "[...]"
+MAX_TITLE_LENGTH = 80
+
+# Kinds of study queue entries
+QUEUE_CARD = "card"
+QUEUE_READING = "reading"
+
+
+@dataclass
+class Extract:
+    highlight: HighlightInfo
+    card_ids: List[int]
+    reading_item: Optional[ReadingItemInfo]
+
+
+def next_reading_interval(item: Optional[ReadingItemInfo]) -> float:
+    """The interval in days after reading an item, item is None before it was ever read"""
+    if item is None or item.read_count == 0 or not item.interval_days:
+        return FIRST_READING_INTERVAL_DAYS
+    return item.interval_days * READING_INTERVAL_FACTOR
+
+
+def intermix(cards: List, readings: List, every: int = READING_EVERY_N_CARDS) -> List[Tuple[str, object]]:
+    """Interleave reading items with review cards, one after every few cards, the rest of either kind at the end"""
+    queue: List[Tuple[str, object]] = []
+    readings = list(readings)
+    for index, card in enumerate(cards, start=1):
+        queue.append((QUEUE_CARD, card))
+        if index % every == 0 and readings:
+            queue.append((QUEUE_READING, readings.pop(0)))
+    queue.extend((QUEUE_READING, reading) for reading in readings)
+    return queue
+
+
+def study_queue(
+    database: TextBookDatabase,
+    user_id: str,
+    limit: int,
+    daily_new_limit: int,
+    book_id: Optional[int] = None,
+    now: Optional[datetime] = None,
+) -> List[Tuple[str, Union[Tuple[FlashcardInfo, Optional[CardStateInfo]], ReadingItemInfo]]]:
+    """The next review cards and due reading items of a user, intermixed, at most limit entries"""
+    now = now or datetime.now(timezone.utc)
+    cards = review_batch(database, user_id, limit, daily_new_limit, book_id, now)
+    readings = database.get_due_reading_items(user_id, now, limit, book_id)
+    # Keep room for the reading items the cards would otherwise crowd out
+    reading_share = min(len(readings), max(limit // (READING_EVERY_N_CARDS + 1), 1 if readings else 0))
+    cards = cards[:max(limit - reading_share, 0)]
+    return intermix(cards, readings)[:limit]
+
+
+def enqueue_section(database: TextBookDatabase, user_id: str, book_id: int, section_id: int, now: Optional[datetime] = None) -> ReadingItemInfo:
+    """Add a section of a book to the reading queue, due right away"""
+    section = next((section for section in database.get_sections_by_book_id(book_id) if section.section_id == section_id), None)
+    if section is None:
+        raise ValueError(f"Section {section_id} not found in book {book_id}")
+    book = database.get_book_by_id(book_id)
+    offset = (book.book_alignment_offset if book is not None else None) or 0
+    return database.create_reading_item(ReadingItemInfo(
+        user_id=user_id,
+        section_id=section_id,
+        # Sections have printed page numbers, reading items keep the page of the PDF
+        page_number=section.start_page_number + offset,
+        title=section.title,
+        due_at=now or datetime.now(timezone.utc),
+        book_id=book_id,
+    ))
+
+
+def _excerpt_title(text: str) -> str:
+    first_line = " ".join(text.split())
+    return first_line if len(first_line) <= MAX_TITLE_LENGTH else first_line[:MAX_TITLE_LENGTH - 3].rstrip() + "..."
+
+
+def excerpt_item(user_id: str, book_id: int, page_number: int, text: str, now: datetime) -> ReadingItemInfo:
+    return ReadingItemInfo(user_id=user_id, page_number=page_number, title=_excerpt_title(text), text=text, due_at=now, book_id=book_id)
+
+
+def record_reading(database: TextBookDatabase, reading_id: int, done: bool = False, now: Optional[datetime] = None) -> Optional[ReadingItemInfo]:
+    """Record that an item was read, scheduling its next reading unless the user is done with it"""
+    now = now or datetime.now(timezone.utc)
+    item = database.get_reading_item(reading_id)
+    if item is None:
+        return None
+    interval = next_reading_interval(item)
+    return database.save_reading_progress(reading_id, interval, now + timedelta(days=interval), now, done)
+
+
+def cloze_cards(passage: str, terms: List[str]) -> List[Tuple[str, str]]:
+    """(front, back) of one cloze card per term, the front being the passage with every occurrence of the term blanked"""
+    cards = []
+    for term in dict.fromkeys(term.strip() for term in terms):
+        if not term:
+            continue
+        if term not in passage:
+            raise ValueError(f"Cloze term not found in the passage: {term}")
+        cards.append((passage.replace(term, CLOZE_BLANK), term))
+    if not cards:
+        raise ValueError("At least one cloze term is required")
+    return cards
+
+
+def _containing_chapter(chapters, page_number: int):
+    return next((chapter for chapter in chapters if chapter.start_page_number <= page_number <= (chapter.end_page_number if chapter.end_page_number is not None else chapter.start_page_number)), None)
+
+
+def extract_to_cloze(
+    database: TextBookDatabase,
+    user_id: str,
+    book_id: int,
+    page_number: int,
+    char_start: int,
+    char_end: int,
+    passage: str,
+    terms: List[str],
+    enqueue: bool = False,
+    now: Optional[datetime] = None,
+) -> Extract:
+    """Keep a passage as a highlight and turn it into cloze cards, optionally enqueueing it for reading"""
+    if char_end <= char_start:
+        raise ValueError("char_end must be greater than char_start")
+    now = now or datetime.now(timezone.utc)
+    book = database.get_book_by_id(book_id)
+    if book is None:
+        raise ValueError(f"Book not found: {book_id}")
+    # The TOC is in printed page numbers, the passage is on a page of the PDF
+    chapter = _containing_chapter(database.get_chapters_by_book_id(book_id), page_number - (book.book_alignment_offset or 0))
+    cards = [
+        FlashcardInfo(front=front, back=back, chapter_id=chapter.chapter_id if chapter is not None else None, page_number=page_number, book_id=book_id)
+        for front, back in cloze_cards(passage, terms)
+    ]
+    highlight = HighlightInfo(user_id=user_id, page_number=page_number, char_start=char_start, char_end=char_end, text=passage, book_id=book_id)
+    reading_item = excerpt_item(user_id, book_id, page_number, passage, now) if enqueue else None
+    return Extract(*database.save_extract(highlight, cards, reading_item))
@@ -0,0 +1,118 @@
+# Chapter reading view
+# The reader UI shows a chapter as one markdown document: the text of its pages with the in-text references linked,
+# anchors where sections start and where entities (figures included) are stated, and the user's highlights marked.
+# Alongside the markdown come the chapter's entities, highlights and how many problems each section has to practice,
+# so the whole view is a single request. Pages and highlights use PDF page numbers, the TOC printed page numbers.
+
+from dataclasses import dataclass
+from typing import Dict, List, Optional, Set, Tuple
+
+from textbook.cross_reference import CrossReferenceIndex, entity_anchor, link_references, section_anchor
+
+STATEMENT_MATCH_LENGTH = 60  # Characters of an entity statement looked up in the page text to place its anchor
+
+
+@dataclass
+class SectionPractice:
+    section_id: int
+    problem_count: int
+    attempted_count: int
+
+
+def page_anchor(page_number: int) -> str:
+    return f"page-{page_number}"
+
+
+def highlight_anchor(highlight_id: int) -> str:
+    return f"highlight-{highlight_id}"
+
+
+def locate_highlight(text: str, char_start: int, char_end: int, passage: str) -> Optional[Tuple[int, int]]:
+    """Where a highlight is in the page text, found again by its passage if the text moved, None if it is gone"""
+    if 0 <= char_start < char_end <= len(text) and text[char_start:char_end] == passage:
+        return char_start, char_end
+    position = text.find(passage) if passage else -1
+    if position < 0:
+        return None
+    return position, position + len(passage)
+
+
+def _statement_position(text: str, statement: Optional[str]) -> int:
+    # The start of the line an entity is stated on, the top of the page if it cannot be found
+    lines = (statement or "").strip().splitlines()
+    position = text.find(lines[0][:STATEMENT_MATCH_LENGTH]) if lines else -1
+    if position < 0:
+        return 0
+    return text.rfind("\n", 0, position) + 1
+
+
+def render_page(
+    text: str,
+    page_number: int,
+    index: CrossReferenceIndex,
+    section_ids: List[int] = (),
+    entities: List[Tuple[int, Optional[str]]] = (),
+    highlights: List[Tuple[int, int, int, str]] = (),
+) -> str:
+    """
+    The markdown of a page for the reading view
+
+    Args:
+        text: The text of the page
+        page_number: The page number, for its anchor
+        index: Resolves the references in the text
+        section_ids: The sections starting on the page
+        entities: (entity_id, statement) of the entities stated on the page
+        highlights: (highlight_id, char_start, char_end, text) of the user's highlights on the page
+    """
+    # (position, order, markup): at one position a highlight ends before anchors are placed and a new one starts
+    insertions: List[Tuple[int, int, str]] = [(0, 1, f'<a id="{page_anchor(page_number)}"></a>')]
+    insertions.extend((0, 1, f'<a id="{section_anchor(section_id)}"></a>') for section_id in section_ids)
+    insertions.extend((_statement_position(text, statement), 2, f'<a id="{entity_anchor(entity_id)}"></a>') for entity_id, statement in entities)
+
+    # Overlapping highlights are cut where the previous one ends, so the marks nest properly
+    covered = 0
+    for highlight_id, char_start, char_end, passage in sorted(highlights, key=lambda highlight: (highlight[1], highlight[0])):
+        located = locate_highlight(text, char_start, char_end, passage)
+        if located is None:
+            continue
+        start, end = max(located[0], covered), located[1]
+        if start >= end:
+            continue
+        insertions.append((start, 3, f'<mark id="{highlight_anchor(highlight_id)}">'))
+        insertions.append((end, 0, "</mark>"))
+        covered = end
+
+    parts: List[str] = []
+    position = 0
+    for insert_at, _, markup in sorted(insertions, key=lambda insertion: (insertion[0], insertion[1])):
+        parts.append(text[position:insert_at])
+        parts.append(markup)
+        position = insert_at
+    parts.append(text[position:])
+    return link_references("".join(parts), index)
+
+
+def _containing_section(sections, page_number: int):
+    return next((section for section in sections if section.start_page_number <= page_number <= (section.end_page_number if section.end_page_number is not None else section.start_page_number)), None)
+
+
+def section_practice(sections, exercises: List[Tuple[int, int]], attempted: Set[int], offset: int) -> Dict[int, SectionPractice]:
+    """
+    How many problems each section has to practice, and how many of them were attempted
+
+    Args:
+        sections: The sections, with printed page ranges
+        exercises: (exercise_id, page_number) of the exercises, with PDF page numbers
+        attempted: The IDs of the attempted exercises
+        offset: The alignment offset from printed to PDF page numbers
+    """
+    practice = {section.section_id: SectionPractice(section.section_id, 0, 0) for section in sections}
+    for exercise_id, page_number in exercises:
+        section = _containing_section(sections, page_number - offset)
+        if section is None:
+            continue
+        practice[section.section_id].problem_count += 1
+        if exercise_id in attempted:
+            practice[section.section_id].attempted_count += 1
+    return practice
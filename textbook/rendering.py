@@ -0,0 +1,91 @@
+# Rendering PDF pages to images
+# Pages of a PDF validated by pdf_validation can be rendered to images, e.g. to show a vision model the figures of a
+# problem, at a DPI, in grayscale, and as PNG, JPEG or WebP. Long ranges of pages are better written to files one page
+# at a time with pdf_to_image_files than held in memory together.
+
+from dataclasses import dataclass
+from pathlib import Path
+from typing import List, Optional
+
+import pymupdf
+from PIL import Image, features
+
+PAGE_IMAGE_DPI = 150
+MIN_RENDER_DPI = 36
+MAX_RENDER_DPI = 600
+IMAGE_FORMATS = {"png": "PNG", "jpeg": "JPEG", "webp": "WEBP"}  # By the format's name, to PIL's
+IMAGE_EXTENSIONS = {"png": "png", "jpeg": "jpg", "webp": "webp"}
+
+
+@dataclass
+class RenderOptions:
+    dpi: int = PAGE_IMAGE_DPI
+    image_format: str = "png"  # png, jpeg or webp, the encoding of files written
+    grayscale: bool = False
+    quality: int = 85  # Of JPEG and WebP, 1 to 100
+
+    def validate(self) -> None:
+        """Raises ValueError for options pages cannot be rendered with"""
+        if isinstance(self.dpi, bool) or not isinstance(self.dpi, int) or not MIN_RENDER_DPI <= self.dpi <= MAX_RENDER_DPI:
+            raise ValueError(f"dpi must be an integer from {MIN_RENDER_DPI} to {MAX_RENDER_DPI}")
+        if self.image_format not in IMAGE_FORMATS:
+            raise ValueError(f"Unknown image format {self.image_format}, expected one of {', '.join(IMAGE_FORMATS)}")
+        if self.image_format == "webp" and not features.check("webp"):
+            raise ValueError("This Pillow build cannot write WebP, render PNG or JPEG")
+        if isinstance(self.quality, bool) or not isinstance(self.quality, int) or not 1 <= self.quality <= 100:
+            raise ValueError("quality must be an integer from 1 to 100")
+
+
+def _render_page(document: pymupdf.Document, page_number: int, options: RenderOptions) -> Image.Image:
+    colorspace = pymupdf.csGRAY if options.grayscale else pymupdf.csRGB
+    pixmap = document[page_number].get_pixmap(matrix=pymupdf.Matrix(options.dpi / 72, options.dpi / 72), colorspace=colorspace, alpha=False)
+    # From the pixels, without encoding the page to decode it again
+    return Image.frombytes("L" if options.grayscale else "RGB", (pixmap.width, pixmap.height), pixmap.samples)
+
+
+def pdf_to_images(pdf_path: Path, page_numbers: List[int], options: Optional[RenderOptions] = None) -> List[Image.Image]:
+    """
+    Render pages of a PDF to images, page numbers start at 0 and those out of range are skipped
+
+    Raises:
+        ValueError: If the options are invalid
+    """
+    options = options or RenderOptions()
+    options.validate()
+    images = []
+    with pymupdf.open(pdf_path) as document:
+        for page_number in page_numbers:
+            if 0 <= page_number < len(document):
+                images.append(_render_page(document, page_number, options))
+    return images
+
+
+def pdf_to_image_files(pdf_path: Path, page_numbers: List[int], output_dir: Path, options: Optional[RenderOptions] = None) -> List[Path]:
+    """
+    Render pages of a PDF to files named page_0001.png and so on, one page in memory at a time
+
+    Page numbers start at 0 and those out of range are skipped, file names count from 1 like printed pages.
+
+    Returns:
+        List[Path]: The files written, in the order of the pages
+
+    Raises:
+        ValueError: If the options are invalid
+    """
+    options = options or RenderOptions()
+    options.validate()
+    output_dir.mkdir(parents=True, exist_ok=True)
+    paths = []
+    with pymupdf.open(pdf_path) as document:
+        for page_number in page_numbers:
+            if not 0 <= page_number < len(document):
+                continue
+            path = output_dir / f"page_{page_number + 1:04d}.{IMAGE_EXTENSIONS[options.image_format]}"
+            image = _render_page(document, page_number, options)
+            if options.image_format == "png":
+                image.save(path, format="PNG")
+            else:
+                image.save(path, format=IMAGE_FORMATS[options.image_format], quality=options.quality)
+            image.close()
+            paths.append(path)
+    return paths
@@ -0,0 +1,198 @@
+# Disk and memory pressure
+# Uploads, OCR output and page images all land on the disk of the uploads directory, and a full disk fails writes
+# halfway, leaving truncated markdown or databases behind. The resource monitor checks the free space of that disk
+# and the memory (RSS) of the server process, on a timer and before every upload. While the disk is low, uploads
+# fail right away with a "server out of space" error and OCR jobs fail before writing their output. While either
+# threshold is crossed, the OCR jobs that ingest documents stay paused in their queue, and every crossing is counted
+# in the metrics and posted to the webhook, if one is set. The jobs resume when the pressure is gone. The thresholds
+# are set in the [resources] section of config.toml:
+#   [resources]
+#   min_free_disk_mb = 1024       # 0 turns the disk check off
+#   max_rss_mb = 0                # 0 turns the memory check off
+#   check_interval_seconds = 30
+#   webhook_url = "https://..."   # gets {"event": "disk_low" or "memory_high", "message": ..., "free_disk_mb", "rss_mb"}
+
+import os
+import shutil
+import sys
+import threading
+from dataclasses import dataclass
+from datetime import datetime, timezone
+from typing import Callable, Dict, List, Optional
+
+import requests
+
+DEFAULT_MIN_FREE_DISK_MB = 1024
+DEFAULT_CHECK_INTERVAL_SECONDS = 30
+WEBHOOK_TIMEOUT_SECONDS = 10
+
+EVENT_DISK_LOW = "disk_low"
+EVENT_MEMORY_HIGH = "memory_high"
+
+MB = 1024 * 1024
+
+
+class OutOfSpaceError(Exception):
+    """Raised instead of writing when the disk has less free space than the threshold"""
+
+    def __init__(self, free_disk_mb: int, min_free_disk_mb: int):
+        self.free_disk_mb = free_disk_mb
+        self.min_free_disk_mb = min_free_disk_mb
+        super().__init__(f"Server out of space: {free_disk_mb} MB free, uploads need at least {min_free_disk_mb} MB")
+
+
+@dataclass
+class ResourceLimits:
+    min_free_disk_mb: int = DEFAULT_MIN_FREE_DISK_MB  # 0 turns the disk check off
+    max_rss_mb: int = 0  # 0 turns the memory check off
+    check_interval_seconds: int = DEFAULT_CHECK_INTERVAL_SECONDS
+    webhook_url: Optional[str] = None
+
+
+@dataclass
+class ResourceStatus:
+    free_disk_mb: int
+    rss_mb: int
+    disk_low: bool
+    memory_high: bool
+    checked_at: datetime
+
+    @property
+    def under_pressure(self) -> bool:
+        return self.disk_low or self.memory_high
+
+
+def resource_limits_from_config(config: dict) -> ResourceLimits:
+    """The thresholds of the [resources] section of config.toml, raises ValueError for negative ones"""
+    section = config.get("resources", {})
+    limits = ResourceLimits(
+        min_free_disk_mb=section.get("min_free_disk_mb", DEFAULT_MIN_FREE_DISK_MB),
+        max_rss_mb=section.get("max_rss_mb", 0),
+        check_interval_seconds=section.get("check_interval_seconds", DEFAULT_CHECK_INTERVAL_SECONDS),
+        webhook_url=section.get("webhook_url") or None,
+    )
+    if limits.min_free_disk_mb < 0 or limits.max_rss_mb < 0:
+        raise ValueError("min_free_disk_mb and max_rss_mb cannot be negative")
+    if limits.check_interval_seconds < 1:
+        raise ValueError("check_interval_seconds must be at least 1")
+    return limits
+
+
+def free_disk_mb(path: str) -> int:
+    """Free space of the disk a path is on, the nearest existing parent for paths not created yet"""
+    path = os.path.abspath(path)
+    while not os.path.exists(path) and os.path.dirname(path) != path:
+        path = os.path.dirname(path)
+    return shutil.disk_usage(path).free // MB
+
+
+def process_rss_mb() -> int:
+    """Resident memory of the server process, its peak where the current one cannot be read"""
+    try:
+        with open("/proc/self/statm") as statm:
+            return int(statm.read().split()[1]) * os.sysconf("SC_PAGE_SIZE") // MB
+    except (OSError, ValueError, IndexError):
+        import resource
+        peak = resource.getrusage(resource.RUSAGE_SELF).ru_maxrss
+        # Bytes on macOS, kilobytes elsewhere
+        return peak // MB if sys.platform == "darwin" else peak // 1024
+
+
+def post_webhook(url: str, payload: dict) -> None:
+    """Post a warning to the webhook, failures are printed, a warning never fails what raised it"""
+    try:
+        requests.post(url, json=payload, timeout=WEBHOOK_TIMEOUT_SECONDS).raise_for_status()
+    except requests.RequestException as e:
+        print(f"Error posting {payload.get('event')} to the resource webhook: {e}")
+
+
+class ResourceMonitor:
+    def __init__(
+        self,
+        path: str,
+        limits: Optional[ResourceLimits] = None,
+        on_pressure: Optional[Callable[[bool], None]] = None,
+        measure_disk: Callable[[str], int] = free_disk_mb,
+        measure_rss: Callable[[], int] = process_rss_mb,
+        send_webhook: Callable[[str, dict], None] = post_webhook,
+    ):
+        """
+        Args:
+            path: A path on the disk to watch, the uploads directory
+            on_pressure: Called with True when a threshold is crossed and False when the pressure is gone, e.g. to
+                pause and resume jobs
+        """
+        self.path = path
+        self.limits = limits or ResourceLimits()
+        self.on_pressure = on_pressure
+        self.measure_disk = measure_disk
+        self.measure_rss = measure_rss
+        self.send_webhook = send_webhook
+        self.warnings: Dict[str, int] = {EVENT_DISK_LOW: 0, EVENT_MEMORY_HIGH: 0}  # Crossings since the server started
+        self.last_status: Optional[ResourceStatus] = None
+        self._lock = threading.Lock()
+        self._stop = threading.Event()
+        self._thread: Optional[threading.Thread] = None
+
+    def check(self) -> ResourceStatus:
+        """Measure the disk and memory, warning about thresholds crossed since the last check"""
+        free = self.measure_disk(self.path)
+        rss = self.measure_rss()
+        status = ResourceStatus(
+            free_disk_mb=free,
+            rss_mb=rss,
+            disk_low=self.limits.min_free_disk_mb > 0 and free < self.limits.min_free_disk_mb,
+            memory_high=self.limits.max_rss_mb > 0 and rss > self.limits.max_rss_mb,
+            checked_at=datetime.now(timezone.utc),
+        )
+        with self._lock:
+            previous = self.last_status
+            self.last_status = status
+            events: List[str] = []
+            if status.disk_low and not (previous and previous.disk_low):
+                events.append(EVENT_DISK_LOW)
+            if status.memory_high and not (previous and previous.memory_high):
+                events.append(EVENT_MEMORY_HIGH)
+            for event in events:
+                self.warnings[event] += 1
+            pressure_changed = status.under_pressure != (previous.under_pressure if previous else False)
+
+        for event in events:
+            self._warn(event, status)
+        if pressure_changed and self.on_pressure is not None:
+            self.on_pressure(status.under_pressure)
+        return status
+
+    def _warn(self, event: str, status: ResourceStatus) -> None:
+        if event == EVENT_DISK_LOW:
+            message = f"{status.free_disk_mb} MB free on the uploads disk, below {self.limits.min_free_disk_mb} MB: uploads are refused and OCR jobs paused"
+        else:
+            message = f"The server uses {status.rss_mb} MB of memory, above {self.limits.max_rss_mb} MB: OCR jobs are paused"
+        print(f"Resource warning: {message}")
+        if self.limits.webhook_url:
+            payload = {"event": event, "message": message, "free_disk_mb": status.free_disk_mb, "rss_mb": status.rss_mb}
+            threading.Thread(target=self.send_webhook, args=(self.limits.webhook_url, payload), daemon=True).start()
+
+    def require_space(self) -> ResourceStatus:
+        """Check the resources before a write, raises OutOfSpaceError while the disk is low"""
+        status = self.check()
+        if status.disk_low:
+            raise OutOfSpaceError(status.free_disk_mb, self.limits.min_free_disk_mb)
+        return status
+
+    def start(self) -> None:
+        """Check on a timer in the background until stopped"""
+        def run():
+            while not self._stop.wait(self.limits.check_interval_seconds):
+                try:
+                    self.check()
+                except Exception as e:
+                    print(f"Error checking disk and memory: {e}")
+
+        self._thread = threading.Thread(target=run, name="resource-monitor", daemon=True)
+        self._thread.start()
+
+    def stop(self) -> None:
+        self._stop.set()
+        if self._thread is not None:
+            self._thread.join(timeout=5)
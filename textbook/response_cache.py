@@ -0,0 +1,127 @@
+# Response cache
+# Running a pipeline again on the same chapter sends the same prompts, so the validated responses of structured
+# prompts are kept in the database by the SHA-256 of the model, prompt, schema, prompt options and attachments, and
+# an identical request is answered from there without calling (or billing) the provider. Free text answers, which are
+# streamed to a reader, are not cached. Responses expire after ttl_hours and the least recently used go first beyond
+# max_entries. A job submitted with force_refresh prompts the provider again, replacing what its prompts had cached:
+#   [response_cache]
+#   enabled = true
+#   ttl_hours = 168
+#   max_entries = 10000
+
+import hashlib
+import json
+import threading
+from dataclasses import dataclass
+from datetime import datetime, timedelta, timezone
+from typing import Any, Dict, List, Optional, TypeVar
+
+from pydantic import BaseModel, ValidationError
+
+from textbook.database import ResponseCacheInfo, TextBookDatabase
+from textbook.json_repair import json_schema
+from textbook.jobs import current_job_id
+
+DEFAULT_TTL_HOURS = 168
+DEFAULT_MAX_ENTRIES = 10000
+
+T = TypeVar("T", bound=BaseModel)
+
+
+@dataclass
+class ResponseCacheSettings:
+    enabled: bool = True
+    ttl_hours: float = DEFAULT_TTL_HOURS
+    max_entries: int = DEFAULT_MAX_ENTRIES
+
+
+def response_cache_settings_from_config(config: dict) -> ResponseCacheSettings:
+    """The [response_cache] section of config.toml, raises ValueError for invalid settings"""
+    section = config.get("response_cache", {})
+    unknown = set(section) - {"enabled", "ttl_hours", "max_entries"}
+    if unknown:
+        raise ValueError(f"Unknown settings of response_cache: {', '.join(sorted(unknown))}")
+    settings = ResponseCacheSettings(
+        enabled=bool(section.get("enabled", True)),
+        ttl_hours=section.get("ttl_hours", DEFAULT_TTL_HOURS),
+        max_entries=section.get("max_entries", DEFAULT_MAX_ENTRIES),
+    )
+    if isinstance(settings.ttl_hours, bool) or not isinstance(settings.ttl_hours, (int, float)) or settings.ttl_hours <= 0:
+        raise ValueError("response_cache.ttl_hours must be a positive number")
+    if isinstance(settings.max_entries, bool) or not isinstance(settings.max_entries, int) or settings.max_entries < 1:
+        raise ValueError("response_cache.max_entries must be a positive integer")
+    return settings
+
+
+def cache_key(model_name: str, prompt: str, schema: type[BaseModel], options: Dict[str, Any], attachments: List[Any]) -> str:
+    """The SHA-256 identifying a structured request, attachments by their content"""
+    request = {
+        "model": model_name,
+        "prompt": prompt,
+        "schema": json_schema(schema),
+        "options": options,
+        "attachments": [hashlib.sha256(attachment.content_bytes()).hexdigest() for attachment in attachments],
+    }
+    return hashlib.sha256(json.dumps(request, sort_keys=True, default=str).encode("utf-8")).hexdigest()
+
+
+class ResponseCache:
+    """Keeps validated responses in the database according to the settings"""
+
+    def __init__(self, database: TextBookDatabase, settings: ResponseCacheSettings):
+        self.database = database
+        self.settings = settings
+        self._refreshing: Dict[str, bool] = {}  # Whether a job was submitted with force_refresh, by job
+        self._lock = threading.Lock()
+
+    def key(self, model_name: str, prompt: str, schema: type[BaseModel], options: Dict[str, Any], attachments: List[Any]) -> str:
+        return cache_key(model_name, prompt, schema, options, attachments)
+
+    def get(self, key: str, schema: type[T]) -> Optional[T]:
+        """The cached response of a request, None when there is none, it expired or the current job refreshes"""
+        if not self.settings.enabled or self._force_refresh():
+            return None
+        not_before = datetime.now(timezone.utc) - timedelta(hours=self.settings.ttl_hours)
+        entry = self.database.use_cached_response(key, not_before)
+        if entry is None:
+            return None
+        try:
+            return schema.model_validate_json(entry.response)
+        except ValidationError:
+            # Validators changed since, what they rejected is prompted again
+            return None
+
+    def put(self, key: str, model_name: str, result: BaseModel) -> None:
+        """Cache the validated response of a request, evicting the least recently used beyond max_entries"""
+        if not self.settings.enabled:
+            return
+        now = datetime.now(timezone.utc)
+        self.database.save_cached_response(ResponseCacheInfo(
+            cache_key=key,
+            model_name=model_name,
+            schema_name=type(result).__name__,
+            response=result.model_dump_json(),
+            hits=0,
+            created_at=now,
+            last_used_at=now,
+        ))
+        self.database.trim_cached_responses(self.settings.max_entries)
+
+    def purge(self, now: Optional[datetime] = None) -> int:
+        """Delete the expired responses, returns how many were deleted"""
+        now = now or datetime.now(timezone.utc)
+        return self.database.delete_cached_responses_before(now - timedelta(hours=self.settings.ttl_hours))
+
+    def _force_refresh(self) -> bool:
+        # A job prompts many times, its parameters are looked up once
+        job_id = current_job_id()
+        if job_id is None:
+            return False
+        with self._lock:
+            if job_id in self._refreshing:
+                return self._refreshing[job_id]
+        job = self.database.get_job(job_id)
+        refreshing = bool(json.loads(job.params or "{}").get("force_refresh")) if job is not None else False
+        with self._lock:
+            self._refreshing[job_id] = refreshing
+        return refreshing
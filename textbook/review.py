@@ -0,0 +1,196 @@
+# Spaced repetition of flashcards
+# Every user keeps their own schedule per card in card_state_info; cards without a state are new to them. Reviews
+# are graded with the four buttons of common flashcard apps and scheduled with SM-2: the interval grows by the
+# card's ease factor after every successful review, and failing a card starts it over at a short relearning step.
+# Each review is logged in review_info together with the schedule it produced and the one it replaced, so the
+# last review of a card can be undone for a short while after it was given.
+# With load balancing, longer intervals are fuzzed by a few days towards the day with the fewest reviews due, so
+# cards learned together do not keep coming due on the same day.
+
+from dataclasses import dataclass
+from datetime import date, datetime, timedelta, timezone
+from typing import Dict, List, Optional, Tuple
+
+from textbook.database import CardStateInfo, FlashcardInfo, ReviewInfo, TextBookDatabase
+from textbook.timestamps import as_utc
+
+# Grades of a review, stored on review_info.grade
+GRADE_AGAIN = 1
+GRADE_HARD = 2
+GRADE_GOOD = 3
+GRADE_EASY = 4
+GRADE_LABELS = {GRADE_AGAIN: "again", GRADE_HARD: "hard", GRADE_GOOD: "good", GRADE_EASY: "easy"}
+
+INITIAL_EASE = 2.5
+MIN_EASE = 1.3
+RELEARN_INTERVAL_DAYS = 10 / (24 * 60)  # Failed cards come back after ten minutes
+FIRST_INTERVAL_DAYS = 1.0
+SECOND_INTERVAL_DAYS = 6.0
+HARD_INTERVAL_FACTOR = 1.2
+EASY_BONUS = 1.3
+MAX_REVIEW_BATCH = 100
+UNDO_WINDOW_MINUTES = 30  # Reviews older than this can no longer be undone
+LOAD_BALANCE_MIN_INTERVAL_DAYS = 2.5  # Shorter intervals are kept exact
+LOAD_BALANCE_FUZZ = 0.05  # Share of the interval a due date may move by, at least a day
+
+
+@dataclass
+class CardSchedule:
+    interval_days: float
+    ease: float
+    repetitions: int  # Successful reviews since the card was last failed
+    lapses: int
+    due_at: datetime
+
+
+@dataclass
+class ReviewGrade:
+    card_id: int
+    grade: int
+    reviewed_at: Optional[datetime] = None
+    duration_ms: Optional[int] = None
+
+
+def schedule_review(state: Optional[CardSchedule], grade: int, now: datetime) -> CardSchedule:
+    """The schedule of a card after a review, state is None for a new card"""
+    if grade not in GRADE_LABELS:
+        raise ValueError(f"Invalid grade: {grade}, expected one of {', '.join(map(str, GRADE_LABELS))}")
+    interval, ease, repetitions, lapses = (state.interval_days, state.ease, state.repetitions, state.lapses) if state else (0.0, INITIAL_EASE, 0, 0)
+
+    if grade == GRADE_AGAIN:
+        return CardSchedule(RELEARN_INTERVAL_DAYS, max(ease - 0.2, MIN_EASE), 0, lapses + (1 if repetitions else 0), now + timedelta(days=RELEARN_INTERVAL_DAYS))
+
+    if grade == GRADE_HARD:
+        interval = FIRST_INTERVAL_DAYS if repetitions == 0 else max(interval * HARD_INTERVAL_FACTOR, FIRST_INTERVAL_DAYS)
+        ease = max(ease - 0.15, MIN_EASE)
+    else:
+        if repetitions == 0:
+            interval = FIRST_INTERVAL_DAYS
+        elif repetitions == 1:
+            interval = SECOND_INTERVAL_DAYS
+        else:
+            interval = interval * ease
+        if grade == GRADE_EASY:
+            interval *= EASY_BONUS
+            ease += 0.15
+    return CardSchedule(interval, ease, repetitions + 1, lapses, now + timedelta(days=interval))
+
+
+def preview_intervals(state: Optional[CardSchedule], now: datetime) -> Dict[int, float]:
+    """The interval in days each grade would give, to label the review buttons"""
+    return {grade: schedule_review(state, grade, now).interval_days for grade in GRADE_LABELS}
+
+
+def fuzz_days(interval_days: float) -> int:
+    """How many whole days a due date may move either way to balance the load"""
+    if interval_days < LOAD_BALANCE_MIN_INTERVAL_DAYS:
+        return 0
+    return max(1, round(interval_days * LOAD_BALANCE_FUZZ))
+
+
+def balance_schedule(schedule: CardSchedule, due_per_day: Dict[date, int]) -> CardSchedule:
+    """Move the due date of a schedule within its fuzz to the day with the fewest reviews due, the nearest on ties"""
+    fuzz = fuzz_days(schedule.interval_days)
+    if fuzz == 0:
+        return schedule
+    shift = min(range(-fuzz, fuzz + 1), key=lambda days: (due_per_day.get((schedule.due_at + timedelta(days=days)).date(), 0), abs(days), days))
+    return CardSchedule(schedule.interval_days + shift, schedule.ease, schedule.repetitions, schedule.lapses, schedule.due_at + timedelta(days=shift))
+
+
+def due_per_day(due_dates: List[datetime]) -> Dict[date, int]:
+    """The number of reviews due on each (UTC) day"""
+    counts: Dict[date, int] = {}
+    for due_at in due_dates:
+        day = as_utc(due_at).date()
+        counts[day] = counts.get(day, 0) + 1
+    return counts
+
+
+def card_schedule(state: Optional[CardStateInfo]) -> Optional[CardSchedule]:
+    if state is None:
+        return None
+    return CardSchedule(state.interval_days, state.ease, state.repetitions, state.lapses, state.due_at)
+
+
+def new_card_allowance(database: TextBookDatabase, user_id: str, daily_limit: int, now: datetime) -> int:
+    """How many new cards the user can still be shown today (UTC)"""
+    start_of_day = datetime(now.year, now.month, now.day, tzinfo=timezone.utc)
+    return max(daily_limit - database.count_cards_introduced(user_id, start_of_day), 0)
+
+
+def review_batch(database: TextBookDatabase, user_id: str, limit: int, daily_new_limit: int, book_id: Optional[int] = None, now: Optional[datetime] = None) -> List[Tuple[FlashcardInfo, Optional[CardStateInfo]]]:
+    """The next cards to review: due cards, most overdue first, then new cards within the daily limit"""
+    now = now or datetime.now(timezone.utc)
+    due = database.get_due_card_states(user_id, now, limit, book_id)
+    batch: List[Tuple[FlashcardInfo, Optional[CardStateInfo]]] = [(state.card, state) for state in due]
+    new_count = min(limit - len(batch), new_card_allowance(database, user_id, daily_new_limit, now))
+    if new_count > 0:
+        batch.extend((card, None) for card in database.get_new_flashcards(user_id, new_count, book_id))
+    return batch
+
+
+def submit_reviews(database: TextBookDatabase, user_id: str, grades: List[ReviewGrade], now: Optional[datetime] = None, load_balancing: bool = False) -> List[ReviewInfo]:
+    """
+    Schedule and store a batch of reviews at once
+
+    The batch is validated as a whole and stored in one transaction, so either every review is recorded or none.
+    A card graded twice in one batch is scheduled from its first review. With load_balancing, due dates are moved
+    to the least loaded day within their fuzz.
+    """
+    now = now or datetime.now(timezone.utc)
+    if not grades:
+        return []
+    if len(grades) > MAX_REVIEW_BATCH:
+        raise ValueError(f"At most {MAX_REVIEW_BATCH} reviews can be submitted at once")
+    card_ids = {grade.card_id for grade in grades}
+    cards = {card.card_id: card for card in database.get_flashcards_by_ids(list(card_ids))}
+    missing = sorted(card_ids - cards.keys())
+    if missing:
+        raise ValueError(f"Flashcards not found: {missing}")
+
+    schedules = {state.card_id: card_schedule(state) for state in database.get_card_states(user_id, list(card_ids))}
+    load = due_per_day([state.due_at for state in database.get_reviewable_card_states(user_id)]) if load_balancing else {}
+    reviews = []
+    for grade in sorted(grades, key=lambda grade: as_utc(grade.reviewed_at or now)):
+        reviewed_at = as_utc(grade.reviewed_at or now)
+        previous = schedules.get(grade.card_id)
+        schedule = schedule_review(previous, grade.grade, reviewed_at)
+        if load_balancing:
+            # The card leaves the day it was due on and adds to the day it is moved to
+            if previous is not None and load.get(as_utc(previous.due_at).date(), 0) > 0:
+                load[as_utc(previous.due_at).date()] -= 1
+            schedule = balance_schedule(schedule, load)
+            load[as_utc(schedule.due_at).date()] = load.get(as_utc(schedule.due_at).date(), 0) + 1
+        schedules[grade.card_id] = schedule
+        reviews.append(ReviewInfo(
+            card_id=grade.card_id,
+            user_id=user_id,
+            grade=grade.grade,
+            duration_ms=grade.duration_ms,
+            interval_days=schedule.interval_days,
+            ease=schedule.ease,
+            repetitions=schedule.repetitions,
+            lapses=schedule.lapses,
+            due_at=schedule.due_at,
+            reviewed_at=reviewed_at,
+            book_id=cards[grade.card_id].book_id,
+        ))
+    database.save_reviews(user_id, reviews)
+    return reviews
+
+
+def undo_last_review(database: TextBookDatabase, user_id: str, card_id: int, now: Optional[datetime] = None) -> Optional[Tuple[ReviewInfo, Optional[CardStateInfo]]]:
+    """
+    Undo the last review of a card, restoring its schedule from before the review
+
+    Returns:
+        Optional[Tuple[ReviewInfo, Optional[CardStateInfo]]]: The undone review and the restored state (None if the
+        card is new again), or None if there is no review to undo
+    """
+    now = now or datetime.now(timezone.utc)
+    review = database.get_last_review(user_id, card_id)
+    if review is None:
+        return None
+    if as_utc(review.reviewed_at) < now - timedelta(minutes=UNDO_WINDOW_MINUTES):
+        raise ValueError(f"The last review of card {card_id} is older than {UNDO_WINDOW_MINUTES} minutes and can no longer be undone")
+    return review, database.undo_review(review.review_id)
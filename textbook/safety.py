@@ -0,0 +1,65 @@
+# Provider safety settings
+# Providers filter prompts and answers by harm category, and their default thresholds block pages of medical and
+# biology textbooks (anatomy, drugs, diseases), which fails the jobs generating from them with a content_filter error.
+# The thresholds can be set per provider in config.toml, e.g.
+#   [safety_settings.gemini]
+#   dangerous_content = "block_only_high"
+#   sexually_explicit = "block_none"
+# Categories left out keep the provider's default. The settings are passed with every prompt as an option of the
+# model, so the model's plugin has to support them; when it does not they are ignored with a warning.
+
+from dataclasses import dataclass, field
+from typing import Any, Dict
+
+SAFETY_OPTION = "safety_settings"  # Name of the prompt option the settings are passed in
+
+# Categories and thresholds per provider, by their names in config.toml
+SAFETY_CATEGORIES = {
+    "gemini": {
+        "harassment": "HARM_CATEGORY_HARASSMENT",
+        "hate_speech": "HARM_CATEGORY_HATE_SPEECH",
+        "sexually_explicit": "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+        "dangerous_content": "HARM_CATEGORY_DANGEROUS_CONTENT",
+        "civic_integrity": "HARM_CATEGORY_CIVIC_INTEGRITY",
+    },
+}
+SAFETY_THRESHOLDS = {
+    "gemini": {
+        "block_none": "BLOCK_NONE",
+        "block_only_high": "BLOCK_ONLY_HIGH",
+        "block_medium_and_above": "BLOCK_MEDIUM_AND_ABOVE",
+        "block_low_and_above": "BLOCK_LOW_AND_ABOVE",
+        "off": "OFF",
+    },
+}
+
+
+@dataclass
+class SafetySettings:
+    provider: str
+    thresholds: Dict[str, str] = field(default_factory=dict)  # Threshold per category, by their names in config.toml
+
+    def prompt_options(self) -> Dict[str, Any]:
+        """The prompt options applying the thresholds, none when nothing is configured"""
+        if not self.thresholds:
+            return {}
+        categories, thresholds = SAFETY_CATEGORIES[self.provider], SAFETY_THRESHOLDS[self.provider]
+        return {SAFETY_OPTION: [
+            {"category": categories[category], "threshold": thresholds[threshold]}
+            for category, threshold in self.thresholds.items()
+        ]}
+
+
+def safety_settings_from_config(config: dict, provider: str) -> SafetySettings:
+    """The safety settings of a provider in config.toml, raises ValueError for invalid ones"""
+    configured = config.get("safety_settings", {})
+    for name in configured:
+        if name not in SAFETY_CATEGORIES:
+            raise ValueError(f"Unsupported provider in safety_settings: {name}, expected one of {', '.join(SAFETY_CATEGORIES)}")
+    thresholds = {category: str(threshold).lower() for category, threshold in configured.get(provider, {}).items()}
+    for category, threshold in thresholds.items():
+        if category not in SAFETY_CATEGORIES[provider]:
+            raise ValueError(f"Unsupported {provider} safety category: {category}, expected one of {', '.join(SAFETY_CATEGORIES[provider])}")
+        if threshold not in SAFETY_THRESHOLDS[provider]:
+            raise ValueError(f"Unsupported {provider} safety threshold: {threshold}, expected one of {', '.join(SAFETY_THRESHOLDS[provider])}")
+    return SafetySettings(provider, thresholds)
@@ -0,0 +1,207 @@
+# Chapter segmentation
+# MinerU marks headings but rarely their level: "1 Groups" and "1.2 Cosets" both come out as "#", and chapter titles
+# printed in bold or as a "Chapter 3" line come out as plain text. Segmentation rebuilds the structure of a document's
+# markdown. Every markdown heading and heading-like line is a candidate, whose level is inferred from its numbering
+# ("Chapter 2" and "2" are chapters, "2.3" a section of one), then from the document's table of contents when its
+# title is an entry there, then from the numbered heading before it, and otherwise from the markdown. An optional LLM
+# pass reviews the candidates, fixing levels and dropping lines that are not headings.
+# The headings kept replace the document's section chunks, so problem extraction, summaries and the reader follow the
+# chapters found, and their tree is stored with the document. Problems stay with their chapter: they move to the new
+# chapter in which their old one started.
+
+import json
+import re
+from dataclasses import dataclass
+from typing import Callable, Dict, List, Optional
+
+from pydantic import BaseModel
+
+from textbook.content import HEADING, ContentChunk, CHUNK_SECTION, get_pages, heading_chunks, store_sections
+from textbook.database import ContentChunkInfo, TextBookDatabase
+from textbook.jobs import CancellationToken
+from textbook.model import LLM
+from textbook.toc import document_toc
+from textbook.utils.toc_detection import SECTION_NUMBER
+
+MAX_HEADING_CHARS = 100
+SNIPPET_CHARS = 100  # Of the text after each heading, shown to the LLM
+# Headings before the first chapter or after the last one, which stay at the top level in numbered documents
+MATTER_TITLES = {
+    "preface", "foreword", "introduction", "contents", "table of contents", "notation", "acknowledgements",
+    "acknowledgments", "appendix", "bibliography", "references", "index", "glossary", "solutions", "answers",
+}
+# Titles of heading-like lines end in neither punctuation, like sentences, nor a number, like table of contents entries
+CHAPTER_LINE = re.compile(r"^[ \t]*(?:\*\*)?((?:chapter|part|appendix)[ \t]+[\w.]+(?:[ \t]*[:.\-–—]?[ \t]+[^\n*]*?[^\n*\d.,;:])?)(?:\*\*)?[ \t]*$", re.IGNORECASE | re.MULTILINE)
+BOLD_NUMBERED_LINE = re.compile(r"^[ \t]*\*\*(\d+(?:\.\d+)*\.?[ \t]+[^\n*]*?[^\n*\d.,;:])\*\*[ \t]*$", re.MULTILINE)
+
+
+@dataclass
+class Heading:
+    char_start: int
+    title: str
+    markdown_level: int  # The level of "#" headings, 1 for heading-like lines
+    numbered_level: Optional[int]  # From the numbering of the title, None for unnumbered headings
+
+
+class HeadingLevelSchema(BaseModel):
+    index: int
+    level: int
+
+
+class OutlineSchema(BaseModel):
+    headings: List[HeadingLevelSchema]
+
+
+def _title_key(title: str) -> str:
+    return " ".join(re.sub(r"[^\w\s]", " ", title.lower()).split())
+
+
+def _numbered_level(title: str) -> Optional[int]:
+    match = SECTION_NUMBER.match(title + " ")  # A title may be its number alone, "Chapter 2"
+    if match is None:
+        return None
+    return len(match.group("number").split(".")) if match.group("number") else 1
+
+
+def find_headings(markdown: str) -> List[Heading]:
+    """The markdown headings and heading-like lines of a document, in order"""
+    candidates = [(match.start(), len(match.group(1)), match.group(2)) for match in HEADING.finditer(markdown)]
+    candidates += [(match.start(), 1, match.group(1)) for pattern in (CHAPTER_LINE, BOLD_NUMBERED_LINE) for match in pattern.finditer(markdown)]
+    headings = []
+    for char_start, level, title in sorted(candidates):
+        title = title.replace("*", "").strip()
+        if title and len(title) <= MAX_HEADING_CHARS:
+            headings.append(Heading(char_start, title, level, _numbered_level(title)))
+    return headings
+
+
+def infer_levels(headings: List[Heading], toc_entries: Optional[List[dict]] = None) -> List[int]:
+    """The level of every heading from its numbering, the table of contents, the heading before it or the markdown"""
+    toc_levels = {_title_key(entry["title"]): entry["level"] for entry in toc_entries or []}
+    levels = []
+    numbered_level = None  # Of the last numbered heading
+    for heading in headings:
+        key = _title_key(heading.title)
+        if heading.numbered_level is not None:
+            level = numbered_level = heading.numbered_level
+        elif key in toc_levels:
+            level = toc_levels[key]
+        elif numbered_level is not None and key not in MATTER_TITLES:
+            level = numbered_level + 1
+        else:
+            level = heading.markdown_level
+        levels.append(min(level, 6))
+    return levels
+
+
+def segmentation_prompt(title: str, markdown: str, headings: List[Heading], levels: List[int]) -> str:
+    lines = "\n".join(
+        f"{index}. [level {level}] {heading.title} | {' '.join(markdown[heading.char_start:heading.char_start + SNIPPET_CHARS + len(heading.title)].split())}"
+        for index, (heading, level) in enumerate(zip(headings, levels))
+    )
+    return f"""
+    Review the headings found in the OCR output of "{title}" with rules:
+    - each line is the index of a heading, the level proposed for it, its title and the start of its text
+    - level is 1 for chapters (or parts of a book without chapters), 2 for their sections, 3 for subsections and so on
+    - level is 0 for lines that are not headings, e.g. running heads, figure captions or problem statements
+    - keep the proposed level unless it is wrong, and answer with every index
+
+    Headings:
+     {lines}
+    """
+
+
+def review_levels(llm: LLM, title: str, markdown: str, headings: List[Heading], levels: List[int]) -> List[int]:
+    """The levels of the headings as the LLM reviewed them, 0 for those that are not headings"""
+    reviewed = llm.prompt_with_schema(segmentation_prompt(title, markdown, headings, levels), schema=OutlineSchema)
+    levels = list(levels)
+    for heading in reviewed.headings:
+        # Indexes and levels out of range are the model's mistakes, the heuristic level stays
+        if 0 <= heading.index < len(levels) and 0 <= heading.level <= 6:
+            levels[heading.index] = heading.level
+    return levels
+
+
+def segment_markdown(
+    markdown: str,
+    pages: Optional[List[ContentChunk]] = None,
+    toc_entries: Optional[List[dict]] = None,
+    llm: Optional[LLM] = None,
+    title: str = "",
+) -> List[ContentChunk]:
+    """The section chunks of a document's markdown, with the levels inferred and, given an LLM, reviewed"""
+    headings = find_headings(markdown)
+    levels = infer_levels(headings, toc_entries)
+    if llm is not None and headings:
+        levels = review_levels(llm, title, markdown, headings, levels)
+    return heading_chunks(markdown, [(heading.char_start, level, heading.title) for heading, level in zip(headings, levels) if level > 0], pages)
+
+
+def outline_tree(sections: List[ContentChunk]) -> List[dict]:
+    """The sections nested by level, each with its section_index, title, level, pages and children"""
+    roots: List[dict] = []
+    ancestors: List[dict] = []
+    for section in sections:
+        node = {"section_index": section.index, "title": section.title, "level": section.level, "page_start": section.page_start, "page_end": section.page_end, "children": []}
+        while ancestors and ancestors[-1]["level"] >= section.level:
+            ancestors.pop()
+        (ancestors[-1]["children"] if ancestors else roots).append(node)
+        ancestors.append(node)
+    return roots
+
+
+def _chapter_moves(old_sections: List[ContentChunkInfo], new_sections: List[ContentChunk]) -> Dict[int, Optional[int]]:
+    # The new chapter in which each old section started, None for those before the first chapter
+    top_level = min((section.level for section in new_sections), default=1)
+    chapters = [section for section in new_sections if section.level == top_level]
+    moves = {}
+    for section in old_sections:
+        chapter = next((chapter for chapter in reversed(chapters) if chapter.char_start <= section.char_start), None)
+        moves[section.chunk_index] = chapter.index if chapter is not None else None
+    return moves
+
+
+def segment_document(
+    database: TextBookDatabase,
+    document_id: int,
+    markdown: str,
+    llm: Optional[LLM] = None,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+) -> List[dict]:
+    """
+    Segment a document's markdown into chapters and sections, replacing its section chunks and storing the tree
+
+    Args:
+        markdown: The markdown the document's chunks were made from
+        llm: Reviews the headings found, by default they are kept as inferred
+
+    Returns:
+        List[dict]: The outline of the document, see outline_tree
+
+    Raises:
+        ValueError: If the document does not exist
+    """
+    document = database.get_document(document_id)
+    if document is None:
+        raise ValueError(f"Document not found: {document_id}")
+    if report_progress is not None:
+        report_progress("segment", 0, f"Looking for the chapters of {document.title}")
+    old_sections = database.get_content_chunks(document_id, CHUNK_SECTION)
+    sections = segment_markdown(markdown, get_pages(database, document_id), document_toc(database, document_id), llm, document.title)
+    if cancellation_token is not None:
+        cancellation_token.raise_if_cancelled()
+
+    store_sections(database, document_id, sections)
+    database.move_problems(document_id, _chapter_moves(old_sections, sections))
+    outline = outline_tree(sections)
+    database.update_document(document_id, outline=json.dumps(outline))
+    return outline
+
+
+def document_outline(database: TextBookDatabase, document_id: int) -> Optional[List[dict]]:
+    """The stored outline of a document, None if it was not segmented"""
+    document = database.get_document(document_id)
+    if document is None or document.outline is None:
+        return None
+    return json.loads(document.outline)
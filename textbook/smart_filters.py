@@ -0,0 +1,173 @@
+# Smart filters
+# Saved searches of a book's exercises, like "unsolved hard problems in chapters 5 to 7", stored per user under a name
+# and evaluated again whenever they are used, so they follow the attempts made since. A filter combines criteria:
+# a range of chapters, numbered from 1 in the order of the book, or a list of chapters; the learner's progress, solved
+# meaning an attempt scored at least CORRECT_SCORE; a difficulty range; the exercise type; and words the description
+# contains. Quizzes drawn with a filter only pick its exercises, and a study plan with a filter only plans refreshing
+# the chapters it matches exercises of. Exercises of archived chapters never match.
+
+import json
+from dataclasses import asdict, dataclass
+from typing import Any, Dict, List, Optional, Set
+
+from textbook.database import SmartFilterInfo, TextBookDatabase
+from textbook.quiz import CORRECT_SCORE, ExerciseCandidate, load_exercise_candidates
+from textbook.reader import EXERCISE_TYPES
+
+PROGRESS_UNSOLVED = "unsolved"  # No attempt scored CORRECT_SCORE, attempted or not
+PROGRESS_SOLVED = "solved"
+PROGRESS_UNATTEMPTED = "unattempted"
+PROGRESS_ATTEMPTED = "attempted"
+PROGRESS_FILTERS = (PROGRESS_UNSOLVED, PROGRESS_SOLVED, PROGRESS_UNATTEMPTED, PROGRESS_ATTEMPTED)
+
+MIN_DIFFICULTY = 1
+MAX_DIFFICULTY = 5
+MAX_FILTER_NAME_LENGTH = 100
+
+
+@dataclass
+class FilterCriteria:
+    chapter_from: Optional[int] = None  # Chapter numbers, from 1 in the order of the book, inclusive
+    chapter_to: Optional[int] = None
+    chapter_ids: Optional[List[int]] = None
+    progress: Optional[str] = None  # One of PROGRESS_FILTERS
+    min_difficulty: Optional[int] = None  # Exercises without a difficulty only match without a difficulty range
+    max_difficulty: Optional[int] = None
+    exercise_type: Optional[str] = None  # text or code
+    text: Optional[str] = None  # Words the description contains, in any order and case
+
+
+def _check_difficulty(name: str, value: Optional[int]):
+    if value is not None and (isinstance(value, bool) or not isinstance(value, int) or not MIN_DIFFICULTY <= value <= MAX_DIFFICULTY):
+        raise ValueError(f"{name} must be an integer between {MIN_DIFFICULTY} and {MAX_DIFFICULTY}")
+
+
+def validate_criteria(criteria: FilterCriteria):
+    """Raises ValueError for criteria no exercise could be matched against"""
+    for name in ("chapter_from", "chapter_to"):
+        value = getattr(criteria, name)
+        if value is not None and (isinstance(value, bool) or not isinstance(value, int) or value < 1):
+            raise ValueError(f"{name} must be a chapter number from 1")
+    if criteria.chapter_from is not None and criteria.chapter_to is not None and criteria.chapter_from > criteria.chapter_to:
+        raise ValueError("chapter_from must not be after chapter_to")
+    if criteria.chapter_ids is not None and (not isinstance(criteria.chapter_ids, list) or not all(isinstance(chapter_id, int) for chapter_id in criteria.chapter_ids)):
+        raise ValueError("chapter_ids must be a list of chapter IDs")
+    if criteria.progress is not None and criteria.progress not in PROGRESS_FILTERS:
+        raise ValueError(f"Invalid progress: {criteria.progress}, expected one of {', '.join(PROGRESS_FILTERS)}")
+    _check_difficulty("min_difficulty", criteria.min_difficulty)
+    _check_difficulty("max_difficulty", criteria.max_difficulty)
+    if criteria.min_difficulty is not None and criteria.max_difficulty is not None and criteria.min_difficulty > criteria.max_difficulty:
+        raise ValueError("min_difficulty must not be above max_difficulty")
+    if criteria.exercise_type is not None and criteria.exercise_type not in EXERCISE_TYPES:
+        raise ValueError(f"Invalid exercise_type: {criteria.exercise_type}, expected one of {', '.join(EXERCISE_TYPES)}")
+    if criteria.text is not None and not isinstance(criteria.text, str):
+        raise ValueError("text must be a string")
+
+
+def criteria_from_dict(data: Dict[str, Any]) -> FilterCriteria:
+    """The criteria of a filter, raises ValueError for unknown or invalid ones"""
+    unknown = set(data) - set(FilterCriteria.__dataclass_fields__)
+    if unknown:
+        raise ValueError(f"Unknown filter criteria: {', '.join(sorted(unknown))}")
+    criteria = FilterCriteria(**data)
+    validate_criteria(criteria)
+    return criteria
+
+
+def filter_criteria(smart_filter: SmartFilterInfo) -> FilterCriteria:
+    return FilterCriteria(**json.loads(smart_filter.criteria or "{}"))
+
+
+def _criteria_json(criteria: FilterCriteria) -> str:
+    # Criteria left unset are not stored, so a filter reads back as it was saved
+    return json.dumps({name: value for name, value in asdict(criteria).items() if value is not None})
+
+
+def _check_filter(database: TextBookDatabase, user_id: str, name: str, book_id: int, filter_id: Optional[int] = None) -> str:
+    name = name.strip()
+    if not name or len(name) > MAX_FILTER_NAME_LENGTH:
+        raise ValueError(f"A filter needs a name of at most {MAX_FILTER_NAME_LENGTH} characters")
+    if database.get_book_by_id(book_id) is None:
+        raise LookupError(f"Book not found: {book_id}")
+    existing = database.get_smart_filter_by_name(user_id, name)
+    if existing is not None and existing.filter_id != filter_id:
+        raise ValueError(f"{user_id} already has a filter named {name}")
+    return name
+
+
+def save_filter(database: TextBookDatabase, user_id: str, name: str, book_id: int, criteria: FilterCriteria) -> SmartFilterInfo:
+    """Save a new filter, raises ValueError for invalid criteria or a name in use and LookupError for unknown books"""
+    validate_criteria(criteria)
+    name = _check_filter(database, user_id, name, book_id)
+    return database.create_smart_filter(SmartFilterInfo(user_id=user_id, name=name, criteria=_criteria_json(criteria), book_id=book_id))
+
+
+def update_filter(database: TextBookDatabase, smart_filter: SmartFilterInfo, name: str, book_id: int, criteria: FilterCriteria) -> SmartFilterInfo:
+    """Replace the name, book and criteria of a filter, raising as save_filter does"""
+    validate_criteria(criteria)
+    name = _check_filter(database, smart_filter.user_id, name, book_id, smart_filter.filter_id)
+    return database.update_smart_filter(smart_filter.filter_id, name, _criteria_json(criteria), book_id)
+
+
+def _progress_matches(candidate: ExerciseCandidate, progress: Optional[str]) -> bool:
+    solved = any(attempt.score >= CORRECT_SCORE for attempt in candidate.attempts)
+    return {
+        None: True,
+        PROGRESS_UNSOLVED: not solved,
+        PROGRESS_SOLVED: solved,
+        PROGRESS_UNATTEMPTED: not candidate.attempts,
+        PROGRESS_ATTEMPTED: bool(candidate.attempts),
+    }[progress]
+
+
+def _difficulty_matches(candidate: ExerciseCandidate, criteria: FilterCriteria) -> bool:
+    if criteria.min_difficulty is None and criteria.max_difficulty is None:
+        return True
+    if candidate.difficulty_level is None:
+        return False
+    return (criteria.min_difficulty or MIN_DIFFICULTY) <= candidate.difficulty_level <= (criteria.max_difficulty or MAX_DIFFICULTY)
+
+
+def matching_exercises(database: TextBookDatabase, book_id: int, criteria: FilterCriteria) -> List[ExerciseCandidate]:
+    """The exercises of a book matching the criteria, with their chapter and attempts, in the order of the book"""
+    chapters = sorted(database.get_chapters_by_book_id(book_id), key=lambda chapter: (chapter.start_page_number, chapter.chapter_id))
+    allowed: Set[int] = {chapter.chapter_id for chapter in chapters if chapter.archived_at is None}
+    if criteria.chapter_from is not None or criteria.chapter_to is not None:
+        numbered = chapters[(criteria.chapter_from or 1) - 1:criteria.chapter_to]
+        allowed &= {chapter.chapter_id for chapter in numbered}
+    if criteria.chapter_ids is not None:
+        allowed &= set(criteria.chapter_ids)
+    chapter_filtered = criteria.chapter_from is not None or criteria.chapter_to is not None or criteria.chapter_ids is not None
+
+    words = (criteria.text or "").casefold().split()
+    exercises = {exercise.exercise_id: exercise for exercise in database.get_exercises_by_book_id(book_id, criteria.exercise_type)}
+    matches = []
+    for candidate in load_exercise_candidates(database, book_id):
+        exercise = exercises.get(candidate.exercise_id)
+        if exercise is None:
+            continue
+        # Exercises outside every chapter only match filters without chapters
+        if candidate.chapter_id is None and chapter_filtered:
+            continue
+        if candidate.chapter_id is not None and candidate.chapter_id not in allowed:
+            continue
+        if not _progress_matches(candidate, criteria.progress) or not _difficulty_matches(candidate, criteria):
+            continue
+        description = exercise.exercise_description.casefold()
+        if not all(word in description for word in words):
+            continue
+        matches.append(candidate)
+    return matches
+
+
+def evaluate_filter(database: TextBookDatabase, smart_filter: SmartFilterInfo) -> List[ExerciseCandidate]:
+    """The exercises a saved filter matches now"""
+    return matching_exercises(database, smart_filter.book_id, filter_criteria(smart_filter))
+
+
+def require_filter(database: TextBookDatabase, filter_id: int, user_id: str) -> SmartFilterInfo:
+    """A filter of the user, raises LookupError for filters that do not exist or are another user's"""
+    smart_filter = database.get_smart_filter(filter_id)
+    if smart_filter is None or smart_filter.user_id != user_id:
+        raise LookupError(f"Filter not found: {filter_id}")
+    return smart_filter
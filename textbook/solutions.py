@@ -0,0 +1,106 @@
+# Solution generation
+# Worked solutions of extracted problems, generated by the LLM in a job. The answer has to match a stepwise schema: the
+# steps of the solution in order, the final answer, and hints to give before showing any step. It is validated
+# against the schema, and rejected when it has no steps or no final answer, before it replaces the solution stored
+# for the problem. The section the problem was extracted from is sent along, so the solution uses the document's
+# definitions and notation, and models that can see images get the pages of a problem's figures.
+
+import json
+from typing import Callable, List, Optional
+
+from pydantic import BaseModel
+
+from textbook.content import get_section
+from textbook.database import ProblemInfo, SolutionInfo, TextBookDatabase
+from textbook.jobs import CancellationToken
+from textbook.model import LLM
+from textbook.problems import problem_figures, problem_page_images
+
+SOLUTION_CONTEXT_CHARS = 6000  # Of the problem's section sent with the prompt
+
+
+class SolutionSchema(BaseModel):
+    steps: List[str]
+    final_answer: str
+    hints: List[str]
+
+
+def figures_rule(figures: List[str], attached: bool) -> str:
+    if attached:
+        return f"the problem refers to these figures, on the attached images of its pages: {', '.join(figures)}"
+    return f"the problem refers to these figures, which you cannot see: {', '.join(figures) or 'none'}"
+
+
+def solution_prompt(problem: ProblemInfo, context: str, figures_attached: bool = False) -> str:
+    return f"""
+    Solve the following problem step by step with rules:
+    - steps are the steps of a complete solution in order, each one a single idea a student can check, in LaTeX for mathematics
+    - final_answer is the result or the statement proven, without the steps
+    - hints are 1 to 3 hints, from the most general to the most specific, that help without giving the solution away
+    - use the definitions and notation of the context when it has them
+    - {figures_rule(problem_figures(problem), figures_attached)}
+
+    Problem {problem.problem_number or ""}:
+     {problem.statement}
+
+    Context:
+     {context}
+    """
+
+
+def problem_context(database: TextBookDatabase, problem: ProblemInfo) -> str:
+    """The start of the section a problem was extracted from, empty when it has none"""
+    if problem.section_index is None:
+        return ""
+    section = get_section(database, problem.document_id, problem.section_index)
+    return section.text[:SOLUTION_CONTEXT_CHARS] if section is not None else ""
+
+
+def generate_solution(
+    database: TextBookDatabase,
+    llm: LLM,
+    problem_id: int,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+    uploads_dir: Optional[str] = None,
+) -> SolutionInfo:
+    """
+    Generate and store a stepwise solution of a problem, replacing the one generated before
+
+    Args:
+        uploads_dir: Where the problem's PDF is, to attach the pages of its figures for models that can see them
+
+    Raises:
+        ValueError: If the problem does not exist or the LLM answered without steps or final answer
+    """
+    problem = database.get_problem(problem_id)
+    if problem is None:
+        raise ValueError(f"Problem not found: {problem_id}")
+    if report_progress is not None:
+        report_progress("solve", 0, f"Solving problem {problem.problem_number or problem_id}")
+
+    images = problem_page_images(database, problem, uploads_dir) if uploads_dir is not None and llm.supports_images else []
+    prompt = solution_prompt(problem, problem_context(database, problem), figures_attached=bool(images))
+    solution = llm.prompt_with_schema_and_images(prompt, SolutionSchema, images) if images else llm.prompt_with_schema(prompt, schema=SolutionSchema)
+    steps = [step.strip() for step in solution.steps if step.strip()]
+    hints = [hint.strip() for hint in solution.hints if hint.strip()]
+    if not steps or not solution.final_answer.strip():
+        raise ValueError(f"The solution of problem {problem_id} has no steps or no final answer")
+
+    if cancellation_token is not None:
+        cancellation_token.raise_if_cancelled()
+    return database.save_solution(SolutionInfo(
+        problem_id=problem_id,
+        steps=json.dumps(steps),
+        final_answer=solution.final_answer.strip(),
+        hints=json.dumps(hints),
+        model_name=llm.model_name,
+    ))
+
+
+def solution_steps(solution: SolutionInfo) -> List[str]:
+    return json.loads(solution.steps)
+
+
+def solution_hints(solution: SolutionInfo) -> List[str]:
+    return json.loads(solution.hints)
@@ -0,0 +1,62 @@
+# Spaced repetition of problems
+# Extracted problems are scheduled like flashcards: every user keeps their own schedule per problem in
+# problem_state_info, and problems without a state are new to them. A review grades how well the problem went with
+# the four flashcard grades and is scheduled with the same SM-2 steps as cards (see review.py), so a problem solved
+# easily comes back after weeks and a failed one the same day. Every review is logged in problem_review_info with
+# the schedule it produced.
+
+from datetime import datetime, timezone
+from typing import List, Optional, Tuple
+
+from textbook.database import ProblemInfo, ProblemReviewInfo, ProblemStateInfo, TextBookDatabase
+from textbook.review import CardSchedule, schedule_review
+
+
+def problem_schedule(state: Optional[ProblemStateInfo]) -> Optional[CardSchedule]:
+    if state is None:
+        return None
+    return CardSchedule(state.interval_days, state.ease, state.repetitions, state.lapses, state.due_at)
+
+
+def problem_review_queue(
+    database: TextBookDatabase,
+    user_id: str,
+    limit: int,
+    new_limit: int,
+    document_id: Optional[int] = None,
+    now: Optional[datetime] = None,
+) -> List[Tuple[ProblemInfo, Optional[ProblemStateInfo]]]:
+    """The next problems to review: due problems, most overdue first, then up to new_limit problems never reviewed"""
+    now = now or datetime.now(timezone.utc)
+    due = database.get_due_problem_states(user_id, now, limit, document_id)
+    queue: List[Tuple[ProblemInfo, Optional[ProblemStateInfo]]] = [(state.problem, state) for state in due]
+    new_count = min(limit - len(queue), new_limit)
+    if new_count > 0:
+        queue.extend((problem, None) for problem in database.get_new_problems(user_id, new_count, document_id))
+    return queue
+
+
+def grade_problem(database: TextBookDatabase, user_id: str, problem_id: int, grade: int, now: Optional[datetime] = None) -> ProblemStateInfo:
+    """
+    Record a review of a problem and schedule its next one
+
+    Raises:
+        ValueError: If the problem does not exist or the grade is invalid
+    """
+    now = now or datetime.now(timezone.utc)
+    problem = database.get_problem(problem_id)
+    if problem is None:
+        raise ValueError(f"Problem not found: {problem_id}")
+    schedule = schedule_review(problem_schedule(database.get_problem_state(user_id, problem_id)), grade, now)
+    return database.save_problem_review(ProblemReviewInfo(
+        problem_id=problem_id,
+        user_id=user_id,
+        grade=grade,
+        interval_days=schedule.interval_days,
+        ease=schedule.ease,
+        repetitions=schedule.repetitions,
+        lapses=schedule.lapses,
+        due_at=schedule.due_at,
+        reviewed_at=now,
+        document_id=problem.document_id,
+    ))
@@ -0,0 +1,40 @@
+# Archive and suspend states of documents, decks and cards
+# A deck is the set of flashcards and exercises of one chapter, so its state is kept on the chapter. Archived items
+# are retained but hidden from quizzes, review queues and listings unless asked for; suspended items stay listed
+# and in quizzes but are left out of the review queues until they are unsuspended. A state applies to everything
+# below it: suspending a document suspends all of its cards.
+
+from datetime import datetime, timezone
+from typing import Optional
+
+STATUS_ACTIVE = "active"
+STATUS_SUSPENDED = "suspended"
+STATUS_ARCHIVED = "archived"
+STATUS_ALL = "all"
+# Values of the status filter of listing endpoints, which default to everything not archived
+STATUS_FILTERS = (STATUS_ACTIVE, STATUS_SUSPENDED, STATUS_ARCHIVED, STATUS_ALL)
+
+
+def item_status(*levels) -> str:
+    """The effective status of an item from its own state and its parents' (objects with archived_at and suspended_at, or None)"""
+    levels = tuple(level for level in levels if level is not None)
+    if any(level.archived_at is not None for level in levels):
+        return STATUS_ARCHIVED
+    if any(level.suspended_at is not None for level in levels):
+        return STATUS_SUSPENDED
+    return STATUS_ACTIVE
+
+
+def matches_status(status: str, status_filter: Optional[str]) -> bool:
+    if status_filter is None:
+        return status != STATUS_ARCHIVED
+    return status_filter == STATUS_ALL or status == status_filter
+
+
+def apply_status(item, archived: Optional[bool], suspended: Optional[bool]) -> None:
+    """Set or clear the archived and suspended state of an item, None leaves it unchanged"""
+    now = datetime.now(timezone.utc)
+    if archived is not None:
+        item.archived_at = (item.archived_at or now) if archived else None
+    if suspended is not None:
+        item.suspended_at = (item.suspended_at or now) if suspended else None
@@ -0,0 +1,32 @@
+# Study events
+# Every study-relevant action is appended to study_event_info as it is stored, in the same transaction: an exercise
+# attempted, an attempt graded or voided, a card or problem reviewed or a review undone, a reading item read. The
+# stream is never changed afterwards, corrections are events of their own, and it is kept when books and documents
+# are deleted. Analytics, mastery and streaks are projections of the stream (see projections), so they can be
+# rebuilt from the whole history whenever the way they are computed changes. Attempts are not made per user, their
+# events have no user and count towards DEFAULT_USER_ID like in the analytics export.
+
+import json
+from typing import Any, Dict, Optional
+
+EVENT_ATTEMPTED = "attempted"
+EVENT_GRADED = "graded"  # Every judgment of an attempt, the last one is the grade that counts
+EVENT_VOIDED = "voided"
+EVENT_REVIEWED = "reviewed"
+EVENT_REVIEW_UNDONE = "review_undone"
+EVENT_READ = "read"
+EVENT_KINDS = (EVENT_ATTEMPTED, EVENT_GRADED, EVENT_VOIDED, EVENT_REVIEWED, EVENT_REVIEW_UNDONE, EVENT_READ)
+
+# What an event is about, subject_id being its ID
+SUBJECT_EXERCISE = "exercise"
+SUBJECT_CARD = "card"
+SUBJECT_PROBLEM = "problem"
+SUBJECT_READING = "reading"
+
+
+def encode_payload(payload: Optional[Dict[str, Any]]) -> str:
+    return json.dumps(payload or {}, sort_keys=True, default=str)
+
+
+def decode_payload(payload: Optional[str]) -> Dict[str, Any]:
+    return json.loads(payload or "{}")
@@ -0,0 +1,155 @@
+# Search suggestions
+# Completions of what a user types in the search box, answered on every keystroke: the titles of the library's
+# documents and books, the headings of their chapters and sections, and the names of their theorems, definitions and
+# other entities. They come from a prefix index in memory rather than the database, so a lookup takes well under a
+# millisecond for a library of hundreds of books. Every term is indexed from the start of each of its words, so "fin"
+# completes "Introduction to Finite Groups", and matching ignores case and accents. Suggestions matching from the start
+# of the term come first, then titles before headings before entity names, then the shortest. The index is built on
+# first use, or by startup priming, and rebuilt in the background once it is older than REFRESH_SECONDS while the
+# previous one keeps answering, so what was added to the library shows up within that delay.
+
+import re
+import threading
+import time
+import unicodedata
+from bisect import bisect_left
+from dataclasses import dataclass
+from typing import Dict, List, Optional, Sequence, Tuple
+
+from textbook.database import TextBookDatabase
+
+SUGGESTION_TITLE = "title"
+SUGGESTION_HEADING = "heading"
+SUGGESTION_ENTITY = "entity"
+SUGGESTION_KINDS = (SUGGESTION_TITLE, SUGGESTION_HEADING, SUGGESTION_ENTITY)  # In the order they are ranked
+
+REFRESH_SECONDS = 60
+MAX_SCANNED = 2000  # Index entries looked at for a prefix, short prefixes match thousands
+MAX_SUGGESTIONS = 50
+
+_SEPARATORS = re.compile(r"[^\w]+")
+
+
+@dataclass
+class Suggestion:
+    text: str
+    kind: str  # title, heading or entity
+    book_id: Optional[int] = None
+    document_id: Optional[int] = None
+    chapter_id: Optional[int] = None  # Of chapter headings
+    section_index: Optional[int] = None  # Of section headings in a document's OCR output
+    entity_id: Optional[int] = None
+    entity_kind: Optional[str] = None  # e.g. theorem or definition
+    page_number: Optional[int] = None
+
+
+def normalize(text: str) -> str:
+    """Lower case words separated by single spaces, without accents"""
+    decomposed = unicodedata.normalize("NFKD", text)
+    stripped = "".join(character for character in decomposed if not unicodedata.combining(character))
+    return " ".join(_SEPARATORS.sub(" ", stripped.casefold()).split())
+
+
+def collect_suggestions(database: TextBookDatabase) -> List[Suggestion]:
+    """Every title, heading and entity name of the library"""
+    sources = database.get_suggestion_sources()
+    suggestions = [Suggestion(title, SUGGESTION_TITLE, book_id=book_id, document_id=document_id) for document_id, title, book_id in sources["documents"]]
+    # Books ingested from a document already have its title
+    titled = {suggestion.book_id for suggestion in suggestions if suggestion.book_id is not None}
+    suggestions += [Suggestion(name, SUGGESTION_TITLE, book_id=book_id) for book_id, name in sources["books"] if book_id not in titled]
+    suggestions += [
+        Suggestion(title, SUGGESTION_HEADING, book_id=book_id, chapter_id=chapter_id, page_number=page)
+        for chapter_id, title, page, book_id in sources["chapters"]
+    ]
+    suggestions += [Suggestion(title, SUGGESTION_HEADING, book_id=book_id, page_number=page) for _, title, page, book_id in sources["sections"]]
+    suggestions += [
+        Suggestion(title, SUGGESTION_HEADING, document_id=document_id, section_index=section_index, page_number=page)
+        for section_index, title, page, document_id in sources["document_sections"]
+    ]
+    suggestions += [
+        Suggestion(name, SUGGESTION_ENTITY, book_id=book_id, entity_id=entity_id, entity_kind=entity_kind, page_number=page)
+        for entity_id, name, entity_kind, page, book_id in sources["entities"]
+    ]
+    return [suggestion for suggestion in suggestions if suggestion.text and suggestion.text.strip()]
+
+
+class PrefixIndex:
+    """Sorted keys of every term from the start of each of its words, searched by binary search"""
+
+    def __init__(self, suggestions: Sequence[Suggestion]):
+        self.suggestions = list(suggestions)
+        self._terms = [normalize(suggestion.text) for suggestion in self.suggestions]
+        entries: List[Tuple[str, int, int]] = []  # Key, its offset in the term, index of the suggestion
+        for index, term in enumerate(self._terms):
+            words = term.split(" ")
+            for start in range(len(words)):
+                entries.append((" ".join(words[start:]), start, index))
+        entries.sort()
+        self._keys = [key for key, _, _ in entries]
+        self._entries = [(start, index) for _, start, index in entries]
+
+    def __len__(self) -> int:
+        return len(self.suggestions)
+
+    def lookup(self, prefix: str, limit: int = 10, kinds: Optional[Sequence[str]] = None) -> List[Suggestion]:
+        query = normalize(prefix)
+        if not query:
+            return []
+        ranked: Dict[Tuple, Tuple[Tuple, Suggestion]] = {}
+        position = bisect_left(self._keys, query)
+        end = min(len(self._keys), position + MAX_SCANNED)
+        while position < end and self._keys[position].startswith(query):
+            start, index = self._entries[position]
+            position += 1
+            suggestion = self.suggestions[index]
+            if kinds is not None and suggestion.kind not in kinds:
+                continue
+            rank = (start > 0, SUGGESTION_KINDS.index(suggestion.kind), len(suggestion.text), suggestion.text)
+            # The same heading in one book, like every "Exercises" section, is suggested once
+            identity = (suggestion.kind, self._terms[index], suggestion.book_id, suggestion.document_id)
+            if identity not in ranked or rank < ranked[identity][0]:
+                ranked[identity] = (rank, suggestion)
+        return [suggestion for _, suggestion in sorted(ranked.values(), key=lambda item: item[0])[:limit]]
+
+
+class SuggestionIndex:
+    """The prefix index of a library's titles, headings and entity names, refreshed in the background"""
+
+    def __init__(self, database: TextBookDatabase, refresh_seconds: float = REFRESH_SECONDS):
+        self.database = database
+        self.refresh_seconds = refresh_seconds
+        self._index: Optional[PrefixIndex] = None
+        self._built_at = 0.0
+        self._lock = threading.Lock()
+        self._refreshing = False
+
+    def build(self) -> int:
+        """Build the index from the library now, returns the number of suggestions in it"""
+        index = PrefixIndex(collect_suggestions(self.database))
+        with self._lock:
+            self._index = index
+            self._built_at = time.monotonic()
+            self._refreshing = False
+        return len(index)
+
+    def suggest(self, prefix: str, limit: int = 10, kinds: Optional[Sequence[str]] = None) -> List[Suggestion]:
+        with self._lock:
+            index = self._index
+            stale = index is not None and not self._refreshing and time.monotonic() - self._built_at > self.refresh_seconds
+            if stale:
+                self._refreshing = True
+        if index is None:
+            self.build()
+            with self._lock:
+                index = self._index
+        elif stale:
+            threading.Thread(target=self._refresh, name="suggestion-index", daemon=True).start()
+        return index.lookup(prefix, limit, kinds) if index is not None else []
+
+    def _refresh(self):
+        try:
+            self.build()
+        except Exception as e:
+            print(f"Error rebuilding the search suggestions: {e}")
+            with self._lock:
+                self._refreshing = False
@@ -0,0 +1,141 @@
+# Course syllabi
+# A learner following a course has a syllabus saying what is covered each week. Imported as pasted text or a file,
+# it is parsed by the LLM in a job into topics by week, each aligned to the chapter of the book covering it, so the
+# planner and the digest can put first what the course covers next. The weeks are dated from the day the course
+# starts. Importing a syllabus again replaces the user's syllabus of the book.
+
+from dataclasses import dataclass
+from datetime import date, datetime, time, timedelta, timezone
+from typing import Callable, Dict, List, Optional
+
+import pymupdf
+from pydantic import BaseModel
+
+from textbook.database import ChapterInfo, SyllabusTopicInfo, TextBookDatabase
+from textbook.jobs import CancellationToken
+from textbook.model import LLM
+from textbook.quiz import book_mastery
+
+MAX_SYLLABUS_CHARS = 50000  # Of the text sent to the LLM, syllabi are a few pages
+MAX_WEEKS = 52
+UPCOMING_DAYS = 7  # Topics of weeks starting within this many days are coming up
+SYLLABUS_TEXT_EXTENSIONS = (".txt", ".md")
+
+
+class SyllabusTopicSchema(BaseModel):
+    week: int
+    title: str
+    description: str
+    chapter: Optional[int]  # The number of the chapter in the list given, None if no chapter covers the topic
+
+
+class SyllabusSchema(BaseModel):
+    topics: List[SyllabusTopicSchema]
+
+
+@dataclass
+class SyllabusFocus:
+    topic: SyllabusTopicInfo
+    mastery: Optional[float]  # Of the topic's chapter, None without one
+
+
+def syllabus_text(filename: str, content: bytes) -> str:
+    """The text of an uploaded syllabus, a PDF or a text file; raises ValueError for other files"""
+    extension = filename.lower().rsplit(".", 1)[-1] if "." in filename else ""
+    if f".{extension}" in SYLLABUS_TEXT_EXTENSIONS:
+        return content.decode("utf-8", errors="replace")
+    if extension == "pdf":
+        with pymupdf.open(stream=content, filetype="pdf") as document:
+            return "\n".join(page.get_text() for page in document)
+    raise ValueError(f"Unsupported syllabus file: {filename}, expected a PDF or one of {', '.join(SYLLABUS_TEXT_EXTENSIONS)}")
+
+
+def syllabus_prompt(text: str, chapters: List[ChapterInfo]) -> str:
+    listing = "\n".join(f"{number}. {chapter.title}" for number, chapter in enumerate(chapters, start=1))
+    return f"""
+    List the topics of the following course syllabus with rules:
+    - week is the week of the course the topic is covered in, starting from 1; topics of several weeks are listed once per week
+    - title is the topic as the syllabus names it and description what the syllabus says about it, in one or two sentences
+    - chapter is the number of the chapter of the textbook below covering the topic, or null if none does
+    - leave out exams, holidays and administrative items
+
+    Textbook chapters:
+     {listing}
+
+    Syllabus:
+     {text[:MAX_SYLLABUS_CHARS]}
+    """
+
+
+def import_syllabus(
+    database: TextBookDatabase,
+    llm: LLM,
+    user_id: str,
+    book_id: int,
+    text: str,
+    starts_on: date,
+    cancellation_token: Optional[CancellationToken] = None,
+    report_progress: Optional[Callable[[str, float, Optional[str]], None]] = None,
+) -> List[SyllabusTopicInfo]:
+    """
+    Parse a syllabus into topics aligned to the chapters of a book and store it, replacing the user's syllabus of the book
+
+    Args:
+        starts_on: The first day of week 1
+
+    Raises:
+        ValueError: If the book does not exist or the syllabus has no topics
+    """
+    if database.get_book_by_id(book_id) is None:
+        raise ValueError(f"Book not found: {book_id}")
+    if not text.strip():
+        raise ValueError("The syllabus is empty")
+    if report_progress is not None:
+        report_progress("syllabus", 0, "Reading the syllabus")
+
+    chapters = [chapter for chapter in database.get_chapters_by_book_id(book_id) if chapter.archived_at is None]
+    syllabus = llm.prompt_with_schema(syllabus_prompt(text, chapters), schema=SyllabusSchema)
+    week_one = datetime.combine(starts_on, time(), tzinfo=timezone.utc)
+    topics = [
+        SyllabusTopicInfo(
+            week=topic.week,
+            week_starts_at=week_one + timedelta(weeks=topic.week - 1),
+            title=topic.title.strip(),
+            description=topic.description.strip() or None,
+            # Numbers the LLM made up align to no chapter
+            chapter_id=chapters[topic.chapter - 1].chapter_id if topic.chapter is not None and 1 <= topic.chapter <= len(chapters) else None,
+        )
+        for topic in syllabus.topics
+        if topic.title.strip() and 1 <= topic.week <= MAX_WEEKS
+    ]
+    if not topics:
+        raise ValueError("No topics found in the syllabus")
+
+    if cancellation_token is not None:
+        cancellation_token.raise_if_cancelled()
+    return database.replace_syllabus(user_id, book_id, topics)
+
+
+def upcoming_topics(
+    database: TextBookDatabase,
+    user_id: str,
+    book_id: Optional[int] = None,
+    now: Optional[datetime] = None,
+    days: int = UPCOMING_DAYS,
+) -> List[SyllabusFocus]:
+    """The syllabus topics of the week under way and the weeks starting within days, by week and weakest chapter first"""
+    now = now or datetime.now(timezone.utc)
+    start, end = now.date() - timedelta(days=6), now.date() + timedelta(days=days)
+    topics = [
+        topic for topic in database.get_syllabus_topics(user_id, book_id)
+        if start <= topic.week_starts_at.date() <= end
+    ]
+    masteries: Dict[int, Dict[int, float]] = {}
+    focus = []
+    for topic in topics:
+        if topic.book_id not in masteries:
+            masteries[topic.book_id] = {chapter_id: mastery.mastery for chapter_id, mastery in book_mastery(database, topic.book_id).items()}
+        mastery = masteries[topic.book_id].get(topic.chapter_id) if topic.chapter_id is not None else None
+        focus.append(SyllabusFocus(topic, mastery))
+    focus.sort(key=lambda item: (item.topic.week_starts_at, item.mastery if item.mastery is not None else 1.0))
+    return focus
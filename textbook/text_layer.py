@@ -0,0 +1,122 @@
+# Text layer extraction
+# Most textbooks are born digital: their PDF carries the text of every page, and OCRing them only to get that text back
+# takes minutes per book. Uploads are read from their text layer first, with the headings found from font sizes, set
+# larger than the body text, so sections, the table of contents and chapters are found as in OCR output. Only the
+# scanned pages, those without a text layer but with an image, go to the OCR backend, copied into a PDF of their own,
+# and its blocks are put back on their pages. A document with a text layer on every page never reaches the backend.
+# parse_method "ocr" OCRs every page as before, "txt" reads the text layer only. Set in config.toml to OCR everything:
+#   [ocr]
+#   text_layer = false
+
+from collections import Counter
+from dataclasses import dataclass, field
+from typing import Any, Dict, List, Optional
+
+import pymupdf
+
+from textbook.content import block_markdown
+from textbook.ocr import MIN_TEXT_LAYER_CHARS, OcrBackend, OcrResult
+
+HEADING_SIZE_RATIO = 1.15  # Of the body text, a block set at least this much larger is a heading
+MAX_HEADING_CHARS = 200
+MAX_HEADING_LEVELS = 3
+
+
+@dataclass
+class TextBlock:
+    text: str
+    size: float  # The font size most of its characters are set in
+
+
+@dataclass
+class TextLayer:
+    page_count: int
+    pages: Dict[int, List[TextBlock]] = field(default_factory=dict)  # The blocks of the pages with a text layer
+    scanned: List[int] = field(default_factory=list)  # The pages without a text layer but with an image, to OCR
+
+
+def _page_blocks(page: pymupdf.Page) -> List[TextBlock]:
+    blocks = []
+    for block in page.get_text("dict", sort=True)["blocks"]:
+        if block.get("type") != 0:
+            continue
+        sizes: Counter = Counter()
+        lines = []
+        for line in block.get("lines", []):
+            for span in line["spans"]:
+                sizes[round(span["size"] * 2) / 2] += len(span["text"].strip())
+            text = "".join(span["text"] for span in line["spans"]).strip()
+            if text:
+                lines.append(text)
+        if lines:
+            blocks.append(TextBlock("\n".join(lines), sizes.most_common(1)[0][0]))
+    return blocks
+
+
+def read_text_layer(pdf_path: str) -> TextLayer:
+    """The text blocks of every page with a text layer, and the pages to OCR"""
+    with pymupdf.open(pdf_path) as document:
+        layer = TextLayer(len(document))
+        for page in document:
+            blocks = _page_blocks(page)
+            if sum(len(block.text) for block in blocks) >= MIN_TEXT_LAYER_CHARS:
+                layer.pages[page.number] = blocks
+            elif page.get_images():
+                layer.scanned.append(page.number)
+            # Blank pages are neither read nor OCRed
+    return layer
+
+
+def text_layer_blocks(layer: TextLayer) -> List[Dict[str, Any]]:
+    """The content list of the pages read from the text layer, headings leveled by their font size"""
+    weights: Counter = Counter()
+    for blocks in layer.pages.values():
+        for block in blocks:
+            weights[block.size] += len(block.text)
+    if not weights:
+        return []
+    body_size = weights.most_common(1)[0][0]
+
+    def is_heading(block: TextBlock) -> bool:
+        return block.size >= body_size * HEADING_SIZE_RATIO and len(block.text) <= MAX_HEADING_CHARS
+
+    heading_sizes = sorted({block.size for blocks in layer.pages.values() for block in blocks if is_heading(block)}, reverse=True)
+    content_list = []
+    for page_number in sorted(layer.pages):
+        for block in layer.pages[page_number]:
+            entry: Dict[str, Any] = {"type": "text", "text": block.text, "page_idx": page_number}
+            if is_heading(block):
+                entry["text"] = " ".join(block.text.split())
+                entry["text_level"] = min(heading_sizes.index(block.size) + 1, MAX_HEADING_LEVELS)
+            content_list.append(entry)
+    return content_list
+
+
+def extract_with_text_layer(backend: OcrBackend, pdf_path: str, language: str, parse_method: str, job_id: Optional[str] = None, layer: Optional[TextLayer] = None) -> OcrResult:
+    """
+    The content of a PDF from its text layer, OCRing only the pages without one
+
+    Args:
+        backend: OCRs the scanned pages, or the whole PDF with parse_method "ocr" or when no page has a text layer
+        language: MinerU's language code of the document
+        parse_method: auto, txt to read the text layer only, or ocr to OCR every page
+        layer: The text layer when it was read already
+
+    Returns:
+        OcrResult: The markdown and content list of the whole document, with the images the backend extracted
+    """
+    if parse_method == "ocr":
+        return backend.ocr_pdf(pdf_path, language, parse_method, job_id=job_id)
+    layer = layer or read_text_layer(pdf_path)
+    if not layer.pages and parse_method != "txt":
+        return backend.ocr_pdf(pdf_path, language, parse_method, job_id=job_id)
+
+    content_list = text_layer_blocks(layer)
+    images: Dict[str, bytes] = {}
+    if layer.scanned and parse_method != "txt":
+        scanned = backend.ocr_pages(pdf_path, layer.scanned, language, parse_method, job_id)
+        images = scanned.images
+        # Stable, so the blocks of each page keep their reading order
+        content_list = sorted(content_list + scanned.content_list, key=lambda block: int(block["page_idx"]))
+    markdown = "\n\n".join(text for text in (block_markdown(block) for block in content_list) if text)
+    return OcrResult(markdown, content_list, images)
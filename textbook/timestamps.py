@@ -0,0 +1,9 @@
+# Timestamps of the database
+# SQLite returns naive datetimes, which are stored in UTC, while clients and the scheduler pass aware ones.
+
+from datetime import datetime, timezone
+
+
+def as_utc(moment: datetime) -> datetime:
+    """The moment as an aware datetime in UTC, naive datetimes are taken to be in UTC"""
+    return moment.astimezone(timezone.utc) if moment.tzinfo is not None else moment.replace(tzinfo=timezone.utc)
@@ -0,0 +1,63 @@
+# Table of contents
+# The OCR output of an uploaded document is scanned for a table of contents without the LLM: each of its first pages
+# is scored by the Bayes detector of utils/toc_detection on whether it has a contents heading and how many of its lines
+# name a chapter or end in a dotted leader and a page number. The table of contents runs from the first page scoring
+# above the threshold until the next page that does not, and its entries are parsed with their page numbers and
+# levels. Whether the document has one and the entries are stored with the document, analyzing it again replaces them.
+
+import json
+from dataclasses import dataclass, field
+from typing import List, Optional
+
+from textbook.content import CHUNK_PAGE, CHUNK_SECTION
+from textbook.database import TextBookDatabase
+from textbook.utils.toc_detection import MARKDOWN_TOC_THRESHOLD, markdown_toc_probability, parse_toc_entries
+
+TOC_SCAN_PAGES = 15  # Number of pages scanned for a table of contents
+
+
+@dataclass
+class TocAnalysis:
+    has_toc: bool
+    entries: List[dict] = field(default_factory=list)  # Title, page_number and level of each entry
+
+
+def find_toc(pages: List[str]) -> List[int]:
+    """The indexes of the pages forming the table of contents, empty without one"""
+    toc_pages = []
+    for index, page in enumerate(pages):
+        if markdown_toc_probability(page) > MARKDOWN_TOC_THRESHOLD:
+            toc_pages.append(index)
+        elif toc_pages:
+            break
+    return toc_pages
+
+
+def analyze_toc(database: TextBookDatabase, document_id: int, max_pages: int = TOC_SCAN_PAGES) -> Optional[TocAnalysis]:
+    """
+    Detect and parse the table of contents of a document's OCR output and store it with the document
+
+    Documents without pages are scanned a section at a time, their sections standing in for the pages.
+
+    Returns:
+        Optional[TocAnalysis]: The table of contents found, None if the document does not exist or has no OCR output
+    """
+    if database.get_document(document_id) is None:
+        return None
+    chunks = database.get_content_chunks(document_id, CHUNK_PAGE) or database.get_content_chunks(document_id, CHUNK_SECTION)
+    if not chunks:
+        return None
+    chunks = chunks[:max_pages]
+    toc_pages = find_toc([chunk.text for chunk in chunks])
+    entries = parse_toc_entries("\n".join(chunks[index].text for index in toc_pages))
+    analysis = TocAnalysis(has_toc=bool(toc_pages) and bool(entries), entries=entries)
+    database.update_document(document_id, has_toc=analysis.has_toc, toc=json.dumps(analysis.entries) if analysis.has_toc else None)
+    return analysis
+
+
+def document_toc(database: TextBookDatabase, document_id: int) -> List[dict]:
+    """The stored table of contents entries of a document, empty if it has none or was not analyzed"""
+    document = database.get_document(document_id)
+    if document is None or not document.toc:
+        return []
+    return json.loads(document.toc)
@@ -0,0 +1,300 @@
+# Ingestion of lecture and video transcripts (SRT, WebVTT or timestamped plain text)
+# Cues are cleaned up (markup removed, the rolling duplicates of auto-generated captions dropped), merged into
+# timestamped paragraphs and grouped into fixed-length sections. The result is rendered to a PDF like a web article,
+# and every section, chapter and page keeps the time range it covers so the UI can deep-link into the video.
+
+import re
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import List, Optional, Tuple
+from urllib.parse import parse_qs, urlencode, urlparse, urlunparse
+
+import pymupdf
+import requests
+
+from textbook.web_article import find_heading_pages, headings_to_toc, render_markdown_to_pdf
+
+SECTION_SECONDS = 300  # Target length of a section
+PARAGRAPH_SECONDS = 45  # Paragraphs are closed at the first sentence end after this long
+MAX_PARAGRAPH_SECONDS = 90  # and unconditionally after this long
+TITLE_WORDS = 8  # Words of the section text used in its heading
+MIN_OVERLAP_WORDS = 3  # Shortest repeated caption text treated as a rolling duplicate
+CAPTION_FETCH_TIMEOUT_SECONDS = 20
+TRANSCRIPT_EXTENSIONS = (".srt", ".vtt", ".txt")
+
+TIMESTAMP_MARKER = re.compile(r"\[(\d{1,2}):(\d{2}):(\d{2})\]")
+_CUE_TIMING = re.compile(r"((?:\d+:)?\d{1,2}:\d{2}[.,]\d{1,3})\s*-->\s*((?:\d+:)?\d{1,2}:\d{2}[.,]\d{1,3})")
+_TEXT_TIMESTAMP = re.compile(r"^\s*\[?((?:\d+:)?\d{1,2}:\d{2})\]?\s*[-–]?\s*(.*)$")
+_MARKUP = re.compile(r"<[^>]+>|\{\\[^}]*\}")
+
+
+class TranscriptError(ValueError):
+    """Raised when a transcript cannot be read or fetched"""
+    pass
+
+
+@dataclass
+class Cue:
+    start: float
+    end: float
+    text: str
+
+
+@dataclass
+class TranscriptSection:
+    start: float
+    end: float
+    paragraphs: List[Tuple[float, str]] = field(default_factory=list)  # (start, text)
+
+    @property
+    def title(self) -> str:
+        words = " ".join(text for _, text in self.paragraphs).split()
+        preview = " ".join(words[:TITLE_WORDS]).rstrip(",.;:")
+        return f"[{format_timestamp(self.start)}] {preview}{'...' if len(words) > TITLE_WORDS else ''}"
+
+
+# ------------------------------------------------------------
+# Timestamps and links
+# ------------------------------------------------------------
+
+def parse_timestamp(value: str) -> float:
+    """Parse hh:mm:ss(.mmm) or mm:ss(.mmm) into seconds"""
+    parts = value.replace(",", ".").split(":")
+    seconds = 0.0
+    for part in parts:
+        seconds = seconds * 60 + float(part)
+    return seconds
+
+
+def format_timestamp(seconds: float) -> str:
+    total = int(seconds)
+    return f"{total // 3600:02d}:{total % 3600 // 60:02d}:{total % 60:02d}"
+
+
+def youtube_video_id(url: str) -> Optional[str]:
+    parsed = urlparse(url)
+    host = (parsed.hostname or "").removeprefix("www.").removeprefix("m.")
+    if host == "youtu.be":
+        return parsed.path.lstrip("/").split("/")[0] or None
+    if host in ("youtube.com", "youtube-nocookie.com"):
+        if parsed.path == "/watch":
+            return parse_qs(parsed.query).get("v", [None])[0]
+        match = re.match(r"^/(?:embed|shorts|live|v)/([^/?#]+)", parsed.path)
+        if match:
+            return match.group(1)
+    return None
+
+
+def video_deep_link(video_url: Optional[str], seconds: Optional[float]) -> Optional[str]:
+    """Link to a position in the video, using YouTube's t parameter or a media fragment elsewhere"""
+    if not video_url or seconds is None:
+        return None
+    position = int(seconds)
+    video_id = youtube_video_id(video_url)
+    if video_id is not None:
+        return f"https://www.youtube.com/watch?{urlencode({'v': video_id, 't': f'{position}s'})}"
+    parsed = urlparse(video_url)
+    return urlunparse(parsed._replace(fragment=f"t={position}"))
+
+
+# ------------------------------------------------------------
+# Parsing
+# ------------------------------------------------------------
+
+def _clean_line(line: str) -> str:
+    return re.sub(r"\s+", " ", _MARKUP.sub("", line)).strip()
+
+
+def parse_subtitles(content: str) -> List[Cue]:
+    """Parse SRT or WebVTT cues, the timing line format is the same for both"""
+    cues: List[Cue] = []
+    for block in re.split(r"\n\s*\n", content.replace("\r\n", "\n").replace("\r", "\n")):
+        lines = block.strip().split("\n")
+        for index, line in enumerate(lines):
+            timing = _CUE_TIMING.search(line)
+            if timing:
+                text = " ".join(filter(None, (_clean_line(text_line) for text_line in lines[index + 1:])))
+                if text:
+                    cues.append(Cue(parse_timestamp(timing.group(1)), parse_timestamp(timing.group(2)), text))
+                break
+    return cues
+
+
+def parse_timestamped_text(content: str) -> List[Cue]:
+    """Parse plain text where lines start with a timestamp, like transcripts copied from YouTube"""
+    cues: List[Cue] = []
+    pending_start: Optional[float] = None
+    for line in content.splitlines():
+        match = _TEXT_TIMESTAMP.match(line)
+        if match and re.fullmatch(r"(?:\d+:)?\d{1,2}:\d{2}", match.group(1)):
+            start = parse_timestamp(match.group(1))
+            text = _clean_line(match.group(2))
+            if text:
+                cues.append(Cue(start, start, text))
+                pending_start = None
+            else:
+                # Timestamp on its own line, the text follows on the next line
+                pending_start = start
+        elif line.strip():
+            if pending_start is not None:
+                cues.append(Cue(pending_start, pending_start, _clean_line(line)))
+                pending_start = None
+            elif cues:
+                cues[-1].text = f"{cues[-1].text} {_clean_line(line)}"
+
+    # Plain text has no end times, each cue lasts until the next one starts
+    for cue, following in zip(cues, cues[1:]):
+        cue.end = following.start
+    return cues
+
+
+def parse_transcript(content: str, file_name: str = "") -> List[Cue]:
+    """Parse a transcript file, choosing the format by extension or content"""
+    if file_name.lower().endswith((".srt", ".vtt")) or _CUE_TIMING.search(content):
+        cues = parse_subtitles(content)
+    else:
+        cues = parse_timestamped_text(content)
+    if not cues:
+        raise TranscriptError("No timestamped text found in the transcript")
+    return normalize_cues(cues)
+
+
+def normalize_cues(cues: List[Cue]) -> List[Cue]:
+    """Sort cues and drop the text auto-generated captions repeat from the previous cue"""
+    normalized: List[Cue] = []
+    previous_text = ""
+    for cue in sorted(cues, key=lambda cue: cue.start):
+        # Rolling captions repeat the previous line before adding new words
+        if len(cue.text.split()) >= MIN_OVERLAP_WORDS and previous_text.endswith(cue.text):
+            text = ""
+        else:
+            text = cue.text[_suffix_prefix_overlap(previous_text, cue.text):].strip()
+        if text:
+            normalized.append(Cue(cue.start, max(cue.end, cue.start), text))
+        previous_text = cue.text
+    return normalized
+
+
+def _suffix_prefix_overlap(previous: str, current: str) -> int:
+    # Longest word-aligned suffix of previous that starts current, short overlaps are ordinary repeated words
+    words = previous.split()
+    for index in range(len(words) - MIN_OVERLAP_WORDS + 1):
+        suffix = " ".join(words[index:])
+        if current.startswith(suffix) and (len(current) == len(suffix) or current[len(suffix)] == " "):
+            return len(suffix)
+    return 0
+
+
+# ------------------------------------------------------------
+# Segmentation
+# ------------------------------------------------------------
+
+def group_sections(cues: List[Cue], section_seconds: int = SECTION_SECONDS) -> List[TranscriptSection]:
+    """Merge cues into paragraphs and paragraphs into sections of roughly section_seconds"""
+    sections: List[TranscriptSection] = []
+    paragraph_start: Optional[float] = None
+    paragraph: List[str] = []
+
+    def close_paragraph(end: float):
+        nonlocal paragraph_start
+        if paragraph_start is None:
+            return
+        if not sections or paragraph_start - sections[-1].start >= section_seconds:
+            sections.append(TranscriptSection(start=paragraph_start, end=end))
+        sections[-1].paragraphs.append((paragraph_start, " ".join(paragraph)))
+        sections[-1].end = end
+        paragraph.clear()
+        paragraph_start = None
+
+    for cue in cues:
+        if paragraph_start is None:
+            paragraph_start = cue.start
+        paragraph.append(cue.text)
+        elapsed = cue.end - paragraph_start
+        if elapsed >= MAX_PARAGRAPH_SECONDS or (elapsed >= PARAGRAPH_SECONDS and cue.text.endswith((".", "?", "!"))):
+            close_paragraph(cue.end)
+    if cues:
+        close_paragraph(cues[-1].end)
+    return sections
+
+
+def transcript_to_markdown(title: str, sections: List[TranscriptSection], video_url: Optional[str] = None) -> str:
+    lines = [f"# {title}"]
+    if video_url:
+        lines.append(f"Video: [{video_url}]({video_url})")
+    for section in sections:
+        lines.append(f"## {section.title}")
+        lines.extend(f"[{format_timestamp(start)}] {text}" for start, text in section.paragraphs)
+    return "\n\n".join(lines) + "\n"
+
+
+def page_time_ranges(pdf_path: Path, duration: float) -> List[Tuple[Optional[float], Optional[float]]]:
+    """
+    Find the time range covered by each page from the timestamp markers in its text
+
+    A page without markers continues the paragraph of the previous page.
+    """
+    with pymupdf.open(pdf_path) as document:
+        markers = [[parse_timestamp(":".join(match)) for match in TIMESTAMP_MARKER.findall(page.get_text())] for page in document]
+
+    starts: List[Optional[float]] = []
+    last_seen: Optional[float] = None
+    for page_markers in markers:
+        starts.append(page_markers[0] if page_markers else last_seen)
+        if page_markers:
+            last_seen = page_markers[-1]
+
+    ranges: List[Tuple[Optional[float], Optional[float]]] = []
+    for index, start in enumerate(starts):
+        following = next((value for value in starts[index + 1:] if value is not None and value != start), None)
+        ranges.append((start, following if following is not None else (duration if start is not None else None)))
+    return ranges
+
+
+# ------------------------------------------------------------
+# Fetching and ingestion
+# ------------------------------------------------------------
+
+def fetch_youtube_captions(video_url: str, language: str = "en") -> List[Cue]:
+    """Fetch the public captions of a YouTube video, when the video has any"""
+    video_id = youtube_video_id(video_url)
+    if video_id is None:
+        raise TranscriptError("Captions can only be fetched for YouTube videos, upload a subtitle file instead")
+    try:
+        response = requests.get(
+            "https://www.youtube.com/api/timedtext",
+            params={"v": video_id, "lang": language, "fmt": "vtt"},
+            timeout=CAPTION_FETCH_TIMEOUT_SECONDS,
+        )
+        response.raise_for_status()
+    except requests.RequestException as e:
+        raise TranscriptError(f"Could not fetch captions for {video_url}: {e}")
+
+    cues = parse_subtitles(response.text)
+    if not cues:
+        raise TranscriptError(f"No {language} captions are available for {video_url}, upload a subtitle file instead")
+    return normalize_cues(cues)
+
+
+@dataclass
+class IngestedTranscript:
+    sections: List[TranscriptSection]
+    toc: dict  # For LazyTextbookReader.save_toc
+    chapter_ranges: dict  # Chapter title -> (start, end) in seconds
+    page_ranges: List[Tuple[Optional[float], Optional[float]]]
+
+
+def ingest_transcript(title: str, cues: List[Cue], pdf_path: Path, markdown_path: Path, video_url: Optional[str] = None) -> IngestedTranscript:
+    """Store a transcript as markdown and PDF, and derive its TOC and time ranges"""
+    sections = group_sections(cues)
+    markdown = transcript_to_markdown(title, sections, video_url)
+
+    with open(markdown_path, "w", encoding="utf-8") as f:
+        f.write(markdown)
+    render_markdown_to_pdf(markdown, pdf_path)
+
+    headings = [(2, section.title) for section in sections]
+    toc = headings_to_toc(title, headings, find_heading_pages(pdf_path, headings))
+    chapter_ranges = {section.title: (section.start, section.end) for section in sections}
+    duration = sections[-1].end if sections else 0.0
+    return IngestedTranscript(sections, toc, chapter_ranges, page_time_ranges(pdf_path, duration))
@@ -0,0 +1,270 @@
+# Numeric answers with units
+# Physics and chemistry problems mostly ask for a quantity, "9.81 m/s^2" or "2.4 kJ", and such an answer is right or
+# wrong by arithmetic alone. A problem can be given a numeric answer, a value with its unit and a tolerance; answers
+# to it are parsed, converted to SI and compared within the tolerance, without a job or the LLM. The answer may use
+# any unit of the same dimension with an SI prefix ("2400 J", "0.0024 MJ" for "2.4 kJ"), and a number written as
+# "3.0e8", "3.0 × 10^8" or in LaTeX ("3.0 \times 10^{8}\,\mathrm{m/s}"). Units are a product of symbols with integer
+# powers, "/" dividing by everything after it up to the closing parenthesis or the end, as "J/mol K" and "J/mol·K"
+# are written for J/(mol K). Temperatures are in kelvin, scales with an offset (°C, °F) are not converted. An answer that is not a number with a unit is left to the LLM.
+
+import math
+import re
+from dataclasses import dataclass
+from typing import Dict, Optional, Tuple
+
+DIMENSION_SYMBOLS = ("m", "kg", "s", "A", "K", "mol", "cd")
+Dimensions = Tuple[int, int, int, int, int, int, int]  # Powers of DIMENSION_SYMBOLS
+DIMENSIONLESS: Dimensions = (0, 0, 0, 0, 0, 0, 0)
+DEFAULT_RELATIVE_TOLERANCE = 0.01  # When a numeric answer sets no tolerance
+MAX_UNIT_CHARS = 100
+
+PREFIXES = {
+    "Y": 1e24, "Z": 1e21, "E": 1e18, "P": 1e15, "T": 1e12, "G": 1e9, "M": 1e6, "k": 1e3, "h": 1e2, "da": 1e1,
+    "d": 1e-1, "c": 1e-2, "m": 1e-3, "µ": 1e-6, "μ": 1e-6, "u": 1e-6, "n": 1e-9, "p": 1e-12, "f": 1e-15, "a": 1e-18,
+    "z": 1e-21, "y": 1e-24,
+}
+
+
+def _dimensions(m=0, kg=0, s=0, A=0, K=0, mol=0, cd=0) -> Dimensions:
+    return (m, kg, s, A, K, mol, cd)
+
+
+# Symbol: (factor to SI, dimensions, whether it takes SI prefixes)
+UNITS: Dict[str, Tuple[float, Dimensions, bool]] = {
+    "m": (1.0, _dimensions(m=1), True),
+    "g": (1e-3, _dimensions(kg=1), True),
+    "s": (1.0, _dimensions(s=1), True),
+    "A": (1.0, _dimensions(A=1), True),
+    "K": (1.0, _dimensions(K=1), True),
+    "mol": (1.0, _dimensions(mol=1), True),
+    "cd": (1.0, _dimensions(cd=1), True),
+    "Hz": (1.0, _dimensions(s=-1), True),
+    "N": (1.0, _dimensions(m=1, kg=1, s=-2), True),
+    "Pa": (1.0, _dimensions(m=-1, kg=1, s=-2), True),
+    "J": (1.0, _dimensions(m=2, kg=1, s=-2), True),
+    "W": (1.0, _dimensions(m=2, kg=1, s=-3), True),
+    "C": (1.0, _dimensions(s=1, A=1), True),
+    "V": (1.0, _dimensions(m=2, kg=1, s=-3, A=-1), True),
+    "F": (1.0, _dimensions(m=-2, kg=-1, s=4, A=2), True),
+    "Ω": (1.0, _dimensions(m=2, kg=1, s=-3, A=-2), True),
+    "ohm": (1.0, _dimensions(m=2, kg=1, s=-3, A=-2), True),
+    "S": (1.0, _dimensions(m=-2, kg=-1, s=3, A=2), True),
+    "Wb": (1.0, _dimensions(m=2, kg=1, s=-2, A=-1), True),
+    "T": (1.0, _dimensions(kg=1, s=-2, A=-1), True),
+    "H": (1.0, _dimensions(m=2, kg=1, s=-2, A=-2), True),
+    "L": (1e-3, _dimensions(m=3), True),
+    "l": (1e-3, _dimensions(m=3), True),
+    "M": (1e3, _dimensions(m=-3, mol=1), True),  # Molar, mol/L
+    "eV": (1.602176634e-19, _dimensions(m=2, kg=1, s=-2), True),
+    "bar": (1e5, _dimensions(m=-1, kg=1, s=-2), True),
+    "rad": (1.0, DIMENSIONLESS, True),
+    "sr": (1.0, DIMENSIONLESS, False),
+    "min": (60.0, _dimensions(s=1), False),
+    "h": (3600.0, _dimensions(s=1), False),
+    "d": (86400.0, _dimensions(s=1), False),
+    "t": (1e3, _dimensions(kg=1), False),
+    "atm": (101325.0, _dimensions(m=-1, kg=1, s=-2), False),
+    "Da": (1.66053906660e-27, _dimensions(kg=1), True),
+    "u": (1.66053906660e-27, _dimensions(kg=1), False),
+    "cal": (4.184, _dimensions(m=2, kg=1, s=-2), True),
+    "deg": (math.pi / 180, DIMENSIONLESS, False),
+    "°": (math.pi / 180, DIMENSIONLESS, False),
+    "%": (1e-2, DIMENSIONLESS, False),
+}
+
+_NUMBER = re.compile(r"\s*(?P<mantissa>[-+]?(?:\d+(?:\.\d*)?|\.\d+))(?:[eE](?P<exponent>[-+]?\d+)|\s*[*×x]\s*10\s*(?:\^|\*\*)\s*\{?\s*(?P<power>[-+]?\d+)\s*\}?)?")
+_SYMBOL = re.compile(r"[A-Za-zµμΩ°%]+")
+_EXPONENT = re.compile(r"\s*(?:\^|\*\*)\s*(?:\{\s*(?P<braced>[-+]?\d+)\s*\}|\(\s*(?P<parenthesized>[-+]?\d+)\s*\)|(?P<plain>[-+]?\d+))|(?P<superscript>⁻?[¹²³⁴⁵⁶⁷⁸⁹⁰]+)|(?P<digits>[-]?\d+)")
+_SUPERSCRIPTS = str.maketrans("⁻¹²³⁴⁵⁶⁷⁸⁹⁰", "-1234567890")
+# Written forms in LaTeX or typeset, to plain text
+_REPLACEMENTS = (
+    ("\\times", "×"), ("\\cdot", "·"), ("\\,", " "), ("\\;", " "), ("\\ ", " "), ("~", " "), ("\\%", "%"),
+    ("\\mu", "µ"), ("\\Omega", "Ω"), ("^\\circ", "°"), ("\\circ", "°"), ("−", "-"), ("⋅", "·"),
+)
+_WRAPPERS = re.compile(r"\\(?:mathrm|text|operatorname|unit|si)\s*\{([^{}]*)\}")
+
+
+@dataclass
+class Quantity:
+    value: float  # In SI
+    dimensions: Dimensions
+
+
+@dataclass
+class NumericAnswer:
+    value: float
+    unit: str = ""
+    abs_tolerance: Optional[float] = None  # In the unit of the answer
+    rel_tolerance: Optional[float] = None  # Of the value, DEFAULT_RELATIVE_TOLERANCE when neither is set
+
+
+def dimension_text(dimensions: Dimensions) -> str:
+    """The SI unit of dimensions, e.g. "m s^-2", empty for dimensionless"""
+    return " ".join(symbol if power == 1 else f"{symbol}^{power}" for symbol, power in zip(DIMENSION_SYMBOLS, dimensions) if power)
+
+
+def _normalize(text: str) -> str:
+    text = text.strip().strip("$").strip().rstrip(".")
+    text = _WRAPPERS.sub(lambda match: match.group(1), text)
+    for written, plain in _REPLACEMENTS:
+        text = text.replace(written, plain)
+    return text.strip()
+
+
+def _symbol(symbol: str) -> Optional[Tuple[float, Dimensions]]:
+    # A unit as written first, so "min", "Pa" or "cd" are not read as a prefixed unit
+    if symbol in UNITS:
+        factor, dimensions, _ = UNITS[symbol]
+        return factor, dimensions
+    for prefix, scale in PREFIXES.items():
+        if symbol.startswith(prefix) and symbol[len(prefix):] in UNITS:
+            factor, dimensions, prefixable = UNITS[symbol[len(prefix):]]
+            if prefixable:
+                return scale * factor, dimensions
+    return None
+
+
+class _UnitParser:
+    def __init__(self, text: str):
+        self.text = text
+        self.position = 0
+
+    def _skip_spaces(self) -> None:
+        while self.position < len(self.text) and self.text[self.position].isspace():
+            self.position += 1
+
+    def peek(self) -> str:
+        self._skip_spaces()
+        return self.text[self.position] if self.position < len(self.text) else ""
+
+    def _exponent(self) -> int:
+        match = _EXPONENT.match(self.text, self.position)
+        if match is None:
+            return 1
+        self.position = match.end()
+        if match.group("superscript"):
+            return int(match.group("superscript").translate(_SUPERSCRIPTS))
+        return int(match.group("braced") or match.group("parenthesized") or match.group("plain") or match.group("digits"))
+
+    def _term(self) -> Tuple[float, Dimensions]:
+        if self.peek() == "(":
+            self.position += 1
+            factor, dimensions = self.expression()
+            if self.peek() != ")":
+                raise ValueError("unbalanced parentheses")
+            self.position += 1
+        else:
+            match = _SYMBOL.match(self.text, self.position)
+            if match is None:
+                raise ValueError(f"expected a unit at '{self.text[self.position:]}'")
+            unit = _symbol(match.group())
+            if unit is None:
+                raise ValueError(f"unknown unit '{match.group()}'")
+            self.position = match.end()
+            factor, dimensions = unit
+        power = self._exponent()
+        return factor ** power, tuple(power * dimension for dimension in dimensions)
+
+    def expression(self) -> Tuple[float, Dimensions]:
+        factor, dimensions = self._term()
+        denominator = False  # Every term after a "/" divides, up to the closing parenthesis
+        while self.peek() not in ("", ")"):
+            operator = self.peek()
+            if operator in "*·/":
+                self.position += 1
+            denominator = denominator or operator == "/"
+            term_factor, term_dimensions = self._term()
+            if denominator:
+                term_factor, term_dimensions = 1 / term_factor, tuple(-dimension for dimension in term_dimensions)
+            factor *= term_factor
+            dimensions = tuple(a + b for a, b in zip(dimensions, term_dimensions))
+        return factor, dimensions
+
+
+def parse_unit(text: str) -> Tuple[float, Dimensions]:
+    """
+    The factor to SI and the dimensions of a unit, e.g. (1000.0, kg m^2 s^-2) for "kJ"
+
+    Raises:
+        ValueError: If the text is not a product of known units with integer powers
+    """
+    text = _normalize(text)
+    if len(text) > MAX_UNIT_CHARS:
+        raise ValueError(f"The unit is longer than {MAX_UNIT_CHARS} characters")
+    if not text or text == "1":
+        return 1.0, DIMENSIONLESS
+    parser = _UnitParser(text)
+    try:
+        factor, dimensions = parser.expression()
+        if parser.peek():
+            raise ValueError(f"unexpected '{parser.text[parser.position:]}'")
+    except (ValueError, OverflowError, ZeroDivisionError) as e:
+        raise ValueError(f"Invalid unit '{text}': {e}") from e
+    return factor, dimensions
+
+
+def parse_quantity(text: str) -> Optional[Quantity]:
+    """A number with an optional unit in SI, None when the text is not one; "v = 3 m/s" is read by its last side"""
+    text = _normalize(re.split(r"=|≈|\\approx", text)[-1])
+    match = _NUMBER.match(text)
+    if match is None:
+        return None
+    exponent = match.group("exponent") or match.group("power") or "0"
+    try:
+        value = float(match.group("mantissa")) * 10.0 ** int(exponent)
+        factor, dimensions = parse_unit(text[match.end():])
+    except (ValueError, OverflowError):
+        return None
+    if not math.isfinite(value * factor):
+        return None
+    return Quantity(value * factor, dimensions)
+
+
+def validate_numeric_answer(answer: NumericAnswer) -> Quantity:
+    """
+    The expected quantity of a numeric answer in SI
+
+    Raises:
+        ValueError: If the value is not finite, the unit is unknown or a tolerance is negative
+    """
+    if not math.isfinite(answer.value):
+        raise ValueError("The value of a numeric answer must be a finite number")
+    factor, dimensions = parse_unit(answer.unit)
+    if answer.abs_tolerance is not None and not (math.isfinite(answer.abs_tolerance) and answer.abs_tolerance >= 0):
+        raise ValueError("abs_tolerance must be a number of at least 0")
+    if answer.rel_tolerance is not None and not (math.isfinite(answer.rel_tolerance) and 0 <= answer.rel_tolerance < 1):
+        raise ValueError("rel_tolerance must be a number of at least 0 and below 1")
+    return Quantity(answer.value * factor, dimensions)
+
+
+def within_tolerance(value: float, expected: Quantity, answer: NumericAnswer) -> bool:
+    """Whether a value in SI is the expected one within the absolute or the relative tolerance of the answer"""
+    factor, _ = parse_unit(answer.unit)
+    if answer.abs_tolerance is None and answer.rel_tolerance is None:
+        tolerance = DEFAULT_RELATIVE_TOLERANCE * abs(expected.value)
+    else:
+        tolerance = max((answer.abs_tolerance or 0.0) * factor, (answer.rel_tolerance or 0.0) * abs(expected.value))
+    # A little slack for the rounding of unit conversions
+    return abs(value - expected.value) <= tolerance + 1e-12 * max(abs(value), abs(expected.value))
+
+
+def check_numeric(text: str, answer: NumericAnswer) -> Optional[Tuple[bool, str]]:
+    """
+    Check an answer against a numeric answer
+
+    Returns:
+        Optional[Tuple[bool, str]]: Whether it is correct and the feedback, None when the text is not a number with a
+        unit, the LLM judges it then
+    """
+    quantity = parse_quantity(text)
+    if quantity is None:
+        return None
+    expected = validate_numeric_answer(answer)
+    unit = answer.unit.strip() or "a pure number"
+    if quantity.dimensions != expected.dimensions:
+        if quantity.dimensions == DIMENSIONLESS:
+            return False, f"Your answer has no unit, give it in {unit} or an equivalent unit."
+        return False, f"Your answer is in {dimension_text(quantity.dimensions)}, which does not measure the same as {unit}."
+    if within_tolerance(quantity.value, expected, answer):
+        return True, f"Correct, {text.strip()} is the expected value."
+    return False, "The unit is right but the value is not, check your calculation."
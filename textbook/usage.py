@@ -0,0 +1,160 @@
+# LLM usage
+# Processing a whole book sends hundreds of prompts, so every call's tokens are recorded with the job it was made
+# for and the document that job worked on, as the provider reports them in its response; providers that do not
+# report them get an estimate from the characters, flagged as such. The cost is estimated from the prices per million
+# tokens set per model in config.toml, calls of models without a price count their tokens only:
+#   [pricing."gemini-2.5-flash"]
+#   input_per_million = 0.30
+#   output_per_million = 2.50
+# The records are summed up per day, document, model and job.
+
+import json
+import threading
+from collections import defaultdict
+from dataclasses import dataclass, field
+from datetime import date, datetime
+from typing import Dict, List, Optional, Tuple
+
+from textbook.database import JobInfo, TextBookDatabase, UsageInfo
+from textbook.jobs import current_job_id
+from textbook.timestamps import as_utc
+
+
+@dataclass(frozen=True)
+class ModelPrice:
+    input_per_million: float  # USD per million prompt tokens
+    output_per_million: float
+
+    def cost(self, input_tokens: int, output_tokens: int) -> float:
+        return (input_tokens * self.input_per_million + output_tokens * self.output_per_million) / 1_000_000
+
+
+@dataclass
+class UsageTotals:
+    calls: int = 0
+    input_tokens: int = 0
+    output_tokens: int = 0
+    cost: float = 0.0  # Of the calls with a price
+    unpriced_calls: int = 0  # Of models without a configured price, not in the cost
+    estimated_calls: int = 0  # Whose tokens the provider did not report
+
+    def add(self, usage: UsageInfo):
+        self.calls += 1
+        self.input_tokens += usage.input_tokens
+        self.output_tokens += usage.output_tokens
+        if usage.cost is None:
+            self.unpriced_calls += 1
+        else:
+            self.cost += usage.cost
+        if usage.estimated:
+            self.estimated_calls += 1
+
+
+@dataclass
+class JobUsage:
+    job_id: str
+    job_kind: Optional[str]
+    document_id: Optional[int]
+    totals: UsageTotals = field(default_factory=UsageTotals)
+
+
+@dataclass
+class UsageSummary:
+    since: datetime
+    totals: UsageTotals
+    days: Dict[date, UsageTotals]  # UTC
+    documents: Dict[int, UsageTotals]  # Calls of jobs without a document are in the totals only
+    models: Dict[str, UsageTotals]
+    jobs: List[JobUsage]  # Most expensive first
+
+
+def pricing_from_config(config: dict) -> Dict[str, ModelPrice]:
+    """The [pricing] section of config.toml by model name, raises ValueError for invalid prices"""
+    prices = {}
+    for model_name, section in config.get("pricing", {}).items():
+        if not isinstance(section, dict):
+            raise ValueError(f"pricing.{model_name} must be a table with input_per_million and output_per_million")
+        unknown = set(section) - {"input_per_million", "output_per_million"}
+        if unknown:
+            raise ValueError(f"Unknown settings of pricing.{model_name}: {', '.join(sorted(unknown))}")
+        values = []
+        for name in ("input_per_million", "output_per_million"):
+            value = section.get(name, 0)
+            if isinstance(value, bool) or not isinstance(value, (int, float)) or value < 0:
+                raise ValueError(f"pricing.{model_name}.{name} must be a non-negative number, got {value!r}")
+            values.append(float(value))
+        prices[model_name] = ModelPrice(*values)
+    return prices
+
+
+def job_document_id(database: TextBookDatabase, job: JobInfo) -> Optional[int]:
+    """The document a job works on, from its parameters: the document itself or the document of its problem"""
+    params = json.loads(job.params or "{}")
+    if params.get("document_id") is not None:
+        return params["document_id"]
+    if params.get("problem_id") is not None:
+        problem = database.get_problem(params["problem_id"])
+        return problem.document_id if problem is not None else None
+    return None
+
+
+class UsageTracker:
+    """Records the tokens and cost of the LLM calls, with the job and document they were made for"""
+
+    def __init__(self, database: TextBookDatabase, prices: Dict[str, ModelPrice]):
+        self.database = database
+        self.prices = prices
+        self._jobs: Dict[str, Tuple[Optional[str], Optional[int]]] = {}  # Kind and document by job
+        self._lock = threading.Lock()
+
+    def record(self, model_name: str, input_tokens: int, output_tokens: int, estimated: bool = False) -> UsageInfo:
+        """Record a call for the current job, if any"""
+        job_id = current_job_id()
+        job_kind, document_id = self._job(job_id) if job_id is not None else (None, None)
+        price = self.prices.get(model_name)
+        return self.database.create_usage(UsageInfo(
+            job_id=job_id,
+            job_kind=job_kind,
+            document_id=document_id,
+            model_name=model_name,
+            input_tokens=input_tokens,
+            output_tokens=output_tokens,
+            estimated=estimated,
+            cost=price.cost(input_tokens, output_tokens) if price is not None else None,
+        ))
+
+    def _job(self, job_id: str) -> Tuple[Optional[str], Optional[int]]:
+        # A job prompts many times, its document is looked up once
+        with self._lock:
+            if job_id in self._jobs:
+                return self._jobs[job_id]
+        job = self.database.get_job(job_id)
+        resolved = (job.job_kind, job_document_id(self.database, job)) if job is not None else (None, None)
+        with self._lock:
+            self._jobs[job_id] = resolved
+        return resolved
+
+
+def usage_summary(database: TextBookDatabase, since: datetime, document_id: Optional[int] = None, job_id: Optional[str] = None) -> UsageSummary:
+    """The usage since a moment summed up per day, document, model and job, only of a document or job if given"""
+    totals = UsageTotals()
+    days: Dict[date, UsageTotals] = defaultdict(UsageTotals)
+    documents: Dict[int, UsageTotals] = defaultdict(UsageTotals)
+    models: Dict[str, UsageTotals] = defaultdict(UsageTotals)
+    jobs: Dict[str, JobUsage] = {}
+    for usage in database.get_usage(since, document_id, job_id):
+        totals.add(usage)
+        days[as_utc(usage.created_at).date()].add(usage)
+        models[usage.model_name].add(usage)
+        if usage.document_id is not None:
+            documents[usage.document_id].add(usage)
+        if usage.job_id is not None:
+            jobs.setdefault(usage.job_id, JobUsage(usage.job_id, usage.job_kind, usage.document_id)).totals.add(usage)
+    return UsageSummary(
+        since=since,
+        totals=totals,
+        days=dict(sorted(days.items())),
+        documents=dict(documents),
+        models=dict(models),
+        jobs=sorted(jobs.values(), key=lambda job: (-job.totals.cost, -(job.totals.input_tokens + job.totals.output_tokens))),
+    )
@@ -15,6 +15,57 @@ NUMERICAL_DISTRIBUTIONS = create_distribution_dict([
     ("number_of_page_numbers", 20, 5),
 ])
 
+# Features of a page of OCR markdown, which keeps headings and the dotted leaders between titles and page numbers
+MARKDOWN_TOC_THRESHOLD = 0.5
+
+MARKDOWN_BINARY_LIKELIHOODS = create_binary_likelihood_dict([
+    ("has_contents_heading", 0.85, 0.005),
+])
+
+MARKDOWN_NUMERICAL_DISTRIBUTIONS = create_distribution_dict([
+    ("chapter_line_count", 12, 1),
+    ("dotted_leader_count", 8, 1),
+])
+
+CONTENTS_HEADING = re.compile(r"^\s*(?:#+\s*)?(?:\*\*)?(?:table of )?contents\b", re.IGNORECASE | re.MULTILINE)
+DOTTED_LEADER = re.compile(r"(?:\.\s?){3,}\s*\d+\s*$|…+\s*\d+\s*$")
+# A line naming a chapter, part or numbered section and ending with its page number
+CHAPTER_LINE = re.compile(r"^\s*(?:[-*]\s+)?(?:(?:chapter|part|appendix)\s+\w+|\d+(?:\.\d+)*\.?\s)\D.*?\b\d+\s*$", re.IGNORECASE)
+# A table of contents entry: an optional number, the title, then a leader or a gap and the page number
+TOC_ENTRY = re.compile(r"^\s*(?:[-*]\s+)?(?P<title>\S.*?)\s*(?:(?:\.\s?){2,}|…+|\s{2,}|\t|\s(?=\d+\s*$))\s*(?P<page>\d+)\s*$")
+SECTION_NUMBER = re.compile(r"^(?:(?:chapter|part|appendix)\s+\w+|(?P<number>\d+(?:\.\d+)*))\.?\s", re.IGNORECASE)
+
+
+def markdown_toc_features(page_markdown: str) -> tuple[dict[str, bool], dict[str, int]]:
+    """The binary and count features of a page of OCR markdown"""
+    lines = [line for line in page_markdown.split("\n") if line.strip()]
+    binary_features = {"has_contents_heading": CONTENTS_HEADING.search(page_markdown) is not None}
+    numerical_features = {
+        "chapter_line_count": sum(1 for line in lines if CHAPTER_LINE.search(line)),
+        "dotted_leader_count": sum(1 for line in lines if DOTTED_LEADER.search(line)),
+    }
+    return binary_features, numerical_features
+
+
+def markdown_toc_probability(page_markdown: str) -> float:
+    """The probability that a page of OCR markdown is part of a table of contents"""
+    binary_features, numerical_features = markdown_toc_features(page_markdown)
+    return float(predict(binary_features, numerical_features, PRIOR_TOC, MARKDOWN_BINARY_LIKELIHOODS, MARKDOWN_NUMERICAL_DISTRIBUTIONS))
+
+
+def parse_toc_entries(toc_markdown: str) -> list[dict]:
+    """The entries of a table of contents: title, page number and level, 1 for chapters and deeper for numbered sections"""
+    entries = []
+    for line in toc_markdown.split("\n"):
+        match = TOC_ENTRY.match(line.replace("*", "").replace("|", " ").strip())
+        if match is None or CONTENTS_HEADING.match(line):
+            continue
+        title = match.group("title").strip(" .")
+        number = SECTION_NUMBER.match(title)
+        level = len(number.group("number").split(".")) if number and number.group("number") else 1
+        entries.append({"title": title, "page_number": int(match.group("page")), "level": level})
+    return entries
+
 
 def detect_toc(page_text: str) -> bool:
     has_keyword_contents = "contents" in page_text.lower()
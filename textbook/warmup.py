@@ -0,0 +1,123 @@
+# Startup priming
+# Some work is done once per process, and the first requests after a restart paid for it: generating the JSON schemas
+# that prompts and cache keys embed, creating the models of the tasks with their own generation parameters, and SQLite
+# reading the library's tables from disk while SQLAlchemy compiles its first queries, and building the index of search
+# suggestions. The server primes all of it in a background thread on startup, and pings the OCR backend so /ready
+# shows when MinerU was down. GET /ready answers 503 until priming finished, so a load balancer or a deployment script
+# only sends traffic to a warm server, while GET /health answers as soon as the server is up. A step that fails is
+# logged and skipped rather than keep the server from becoming ready. The provider clients are created, and checked
+# with a prompt, before the server starts. Priming is on by default, set in config.toml:
+#   warm_startup = false
+
+import threading
+import time
+from dataclasses import dataclass
+from functools import partial
+from typing import Callable, Iterator, List, Optional, Tuple
+
+import structlog
+from pydantic import BaseModel
+
+from textbook.database import TextBookDatabase
+from textbook.health import DEPENDENCY_DOWN, HealthProbe
+from textbook.json_repair import json_schema
+from textbook.profiles import ModelRouter
+from textbook.suggest import SuggestionIndex
+
+PrimingStep = Tuple[str, Callable[[], int]]  # Name, and the work returning how many items it primed
+
+
+@dataclass
+class StepReport:
+    name: str
+    primed: int = 0
+    seconds: float = 0.0
+    error: Optional[str] = None  # Why the step failed, the server became ready without it
+
+
+def _subclasses(cls: type) -> Iterator[type]:
+    for subclass in cls.__subclasses__():
+        yield subclass
+        yield from _subclasses(subclass)
+
+
+def prime_schemas() -> int:
+    """Generate the JSON schema of every response schema of the package imported so far"""
+    schemas = {schema for schema in _subclasses(BaseModel) if schema.__module__.startswith("textbook.")}
+    for schema in schemas:
+        json_schema(schema)
+    return len(schemas)
+
+
+def prime_models(router: ModelRouter) -> int:
+    """Create the model of every profile and task with its own generation parameters"""
+    for profile in router.names:
+        for task in router.generation.tasks:
+            router.model_for(task, profile)
+    return len(router.task_models)
+
+
+def prime_database(database: TextBookDatabase) -> int:
+    """Read the library, loading its tables into SQLite's cache and compiling the queries of the first requests"""
+    return len(database.get_documents()) + len(database.get_all_books())
+
+
+def prime_ocr(health_probe: HealthProbe) -> int:
+    """Ping the OCR backend, keeping the answer for the first health checks; raises with the reason it is down"""
+    health = health_probe.ocr()
+    if health.status == DEPENDENCY_DOWN:
+        raise RuntimeError(health.detail)
+    return 1
+
+
+def priming_steps(database: TextBookDatabase, router: Optional[ModelRouter], suggestions: Optional[SuggestionIndex] = None, health_probe: Optional[HealthProbe] = None) -> List[PrimingStep]:
+    steps: List[PrimingStep] = [("schemas", prime_schemas), ("database", partial(prime_database, database))]
+    if suggestions is not None:
+        steps.append(("suggestions", suggestions.build))
+    if health_probe is not None:
+        steps.append(("ocr", partial(prime_ocr, health_probe)))
+    if router is not None:
+        steps.append(("models", partial(prime_models, router)))
+    return steps
+
+
+class Warmup:
+    """Runs the priming steps once, in the background, and tells whether the server is ready"""
+
+    def __init__(self, steps: List[PrimingStep]):
+        self.steps = steps
+        self.logger = structlog.get_logger("Warmup")
+        self._reports: List[StepReport] = []
+        self._lock = threading.Lock()
+        self._done = threading.Event()
+
+    @property
+    def ready(self) -> bool:
+        return self._done.is_set()
+
+    def reports(self) -> List[StepReport]:
+        with self._lock:
+            return list(self._reports)
+
+    def run(self) -> None:
+        started = time.monotonic()
+        for name, step in self.steps:
+            report = StepReport(name)
+            step_started = time.monotonic()
+            try:
+                report.primed = step()
+            except Exception as e:
+                report.error = str(e)
+                self.logger.warning("Priming step failed", step=name, error=str(e))
+            report.seconds = round(time.monotonic() - step_started, 3)
+            with self._lock:
+                self._reports.append(report)
+        self._done.set()
+        self.logger.info("Server ready", seconds=round(time.monotonic() - started, 3))
+
+    def start(self) -> None:
+        threading.Thread(target=self.run, name="warmup", daemon=True).start()
+
+    def wait(self, timeout: Optional[float] = None) -> bool:
+        """Block until priming finished or the timeout passed, True if it finished"""
+        return self._done.wait(timeout)
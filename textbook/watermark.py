@@ -0,0 +1,114 @@
+# Export watermarks
+# On an instance shared by a class, exported assessment material can leak. Each export can carry a fingerprint, a
+# random identifier recorded with the instance, the user the export was made for, what was exported and when, so an
+# admin can trace a leaked copy back to its export. Two modes, set in config.toml:
+# - metadata: the fingerprint is written where the format keeps metadata, a comment heading a markdown export or a
+#   field of a bundle's manifest; it is lost when only the text is copied
+# - zero-width: the fingerprint is also appended to every paragraph as invisible zero-width characters, which copying
+#   the text keeps along, so an excerpt pasted elsewhere can still be traced
+#   [watermark]
+#   mode = "zero-width"
+#   instance_id = "physics-101"  # by default the host name
+# The watermark is off by default.
+
+import re
+import secrets
+import socket
+from dataclasses import dataclass
+from typing import List, Optional
+
+from textbook.database import ExportFingerprintInfo, TextBookDatabase
+
+WATERMARK_DISABLED = "disabled"
+WATERMARK_METADATA = "metadata"
+WATERMARK_ZERO_WIDTH = "zero-width"
+WATERMARK_MODES = (WATERMARK_DISABLED, WATERMARK_METADATA, WATERMARK_ZERO_WIDTH)
+
+EXPORT_BOOK_BUNDLE = "book_bundle"
+EXPORT_PROBLEM_SET = "problem_set"
+
+FINGERPRINT_BYTES = 8
+ZERO = "\u200b"  # Zero-width space, a 0 bit
+ONE = "\u200c"  # Zero-width non-joiner, a 1 bit
+MARK = "\u2060"  # Word joiner, around the bits of a fingerprint
+ZERO_WIDTH_FINGERPRINT = re.compile(f"{MARK}([{ZERO}{ONE}]{{{FINGERPRINT_BYTES * 8}}}){MARK}")
+METADATA_FINGERPRINT = re.compile(rf"fingerprint\"?\s*[:=]\s*\"?([0-9a-f]{{{FINGERPRINT_BYTES * 2}}})\b")
+
+
+@dataclass
+class WatermarkSettings:
+    mode: str = WATERMARK_DISABLED
+    instance_id: str = ""  # The host name when empty
+
+
+@dataclass
+class Watermark:
+    fingerprint: str
+    instance_id: str
+    mode: str
+
+    def header(self) -> str:
+        """The metadata comment heading a markdown export"""
+        return f"<!-- instance: {self.instance_id}, fingerprint: {self.fingerprint} -->\n\n"
+
+    def text(self, text: str) -> str:
+        """A text of the export, with the fingerprint appended to each paragraph in zero-width mode"""
+        if self.mode != WATERMARK_ZERO_WIDTH or not text:
+            return text
+        mark = zero_width(self.fingerprint)
+        return "\n\n".join(paragraph + mark if paragraph.strip() else paragraph for paragraph in text.split("\n\n"))
+
+
+def watermark_settings_from_config(config: dict) -> WatermarkSettings:
+    """The [watermark] section of config.toml, raises ValueError for invalid settings"""
+    section = config.get("watermark", {})
+    unknown = set(section) - {"mode", "instance_id"}
+    if unknown:
+        raise ValueError(f"Unknown settings of watermark: {', '.join(sorted(unknown))}")
+    settings = WatermarkSettings(mode=section.get("mode", WATERMARK_DISABLED), instance_id=str(section.get("instance_id", "")))
+    if settings.mode not in WATERMARK_MODES:
+        raise ValueError(f"Unsupported watermark mode: {settings.mode}, expected one of {', '.join(WATERMARK_MODES)}")
+    return settings
+
+
+def zero_width(fingerprint: str) -> str:
+    """The fingerprint as invisible characters, a bit each between two marks"""
+    bits = "".join(f"{byte:08b}" for byte in bytes.fromhex(fingerprint))
+    return MARK + "".join(ONE if bit == "1" else ZERO for bit in bits) + MARK
+
+
+def find_fingerprints(text: str) -> List[str]:
+    """The distinct fingerprints in a text, invisible or in metadata, in the order found"""
+    found = []
+    for match in ZERO_WIDTH_FINGERPRINT.finditer(text):
+        bits = "".join("1" if char == ONE else "0" for char in match.group(1))
+        found.append(int(bits, 2).to_bytes(FINGERPRINT_BYTES, "big").hex())
+    found.extend(match.group(1) for match in METADATA_FINGERPRINT.finditer(text))
+    return list(dict.fromkeys(found))
+
+
+def issue_watermark(
+    database: TextBookDatabase,
+    settings: WatermarkSettings,
+    user_id: str,
+    export_kind: str,
+    subject_id: Optional[int] = None,
+) -> Optional[Watermark]:
+    """Record a new fingerprint for an export, None when watermarks are disabled"""
+    if settings.mode == WATERMARK_DISABLED:
+        return None
+    instance_id = settings.instance_id or socket.gethostname()
+    entry = database.create_export_fingerprint(ExportFingerprintInfo(
+        fingerprint=secrets.token_hex(FINGERPRINT_BYTES),
+        instance_id=instance_id,
+        user_id=user_id,
+        export_kind=export_kind,
+        subject_id=subject_id,
+    ))
+    return Watermark(entry.fingerprint, instance_id, settings.mode)
+
+
+def trace_export(database: TextBookDatabase, text: str) -> List[ExportFingerprintInfo]:
+    """The exports whose fingerprints are found in a text, e.g. a leaked copy"""
+    fingerprints = find_fingerprints(text)
+    return database.get_export_fingerprints(fingerprints) if fingerprints else []
@@ -0,0 +1,557 @@
+# Ingestion of web articles (blog posts, lecture pages) into the study library
+# The page is fetched, its main content is picked readability-style (paragraph text scores propagate to
+# their containers, link-heavy and boilerplate containers lose), converted to markdown and rendered to a PDF,
+# so the rest of the pipeline reads it like any uploaded book. Headings become the chapters and sections.
+
+import ipaddress
+import re
+import socket
+from dataclasses import dataclass, field
+from html import escape
+from html.parser import HTMLParser
+from pathlib import Path
+from typing import Callable, Dict, List, Optional, Tuple, Union
+from urllib.parse import urljoin, urlparse
+
+import pymupdf
+import requests
+from requests.adapters import HTTPAdapter
+
+FETCH_TIMEOUT_SECONDS = 20
+MAX_REDIRECTS = 5
+REDIRECT_STATUSES = {301, 302, 303, 307, 308}
+MAX_ARTICLE_BYTES = 5 * 1024 * 1024  # Pages larger than this are rejected before parsing
+MIN_PARAGRAPH_LENGTH = 25  # Shorter paragraphs (captions, buttons) do not contribute to scores
+USER_AGENT = "Mozilla/5.0 (compatible; ProblemBasedSelfStudy article reader)"
+
+# Elements that never hold article content
+REMOVED_TAGS = {"script", "style", "noscript", "template", "svg", "canvas", "iframe", "form", "nav", "aside", "footer", "button", "select", "input", "textarea"}
+VOID_TAGS = {"area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr"}
+BLOCK_TAGS = {"address", "article", "blockquote", "dd", "div", "dl", "dt", "figcaption", "figure", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li", "main", "ol", "p", "pre", "section", "table", "tbody", "thead", "tfoot", "tr", "td", "th", "ul"}
+HEADING_TAGS = {"h1": 1, "h2": 2, "h3": 3, "h4": 4, "h5": 5, "h6": 6}
+
+POSITIVE_HINTS = re.compile(r"article|body|content|entry|main|post|story|text|blog", re.IGNORECASE)
+NEGATIVE_HINTS = re.compile(r"comment|sidebar|footer|footnote-back|nav|menu|share|social|advert|promo|related|cookie|newsletter|subscribe|breadcrumb|popup|modal|banner|sponsor", re.IGNORECASE)
+
+ARTICLE_CSS = """
+* {font-family: sans-serif; font-size: 11pt; line-height: 1.4;}
+h1 {font-size: 20pt;} h2 {font-size: 16pt;} h3 {font-size: 13pt;} h4, h5, h6 {font-size: 11pt;}
+pre, code {font-family: monospace; font-size: 9pt;}
+blockquote {margin-left: 18pt; color: #444444;}
+"""
+
+
+class WebArticleError(ValueError):
+    """Raised when a URL cannot be fetched or holds no readable article"""
+    pass
+
+
+@dataclass
+class ArticleContent:
+    title: str
+    markdown: str
+    byline: Optional[str] = None
+    keywords: Optional[str] = None
+    headings: List[Tuple[int, str]] = field(default_factory=list)  # (level, text) in document order
+
+
+# ------------------------------------------------------------
+# Fetching
+# ------------------------------------------------------------
+
+def _check_public_host(url: str) -> str:
+    """The address a URL's host resolves to, raising WebArticleError unless all of its addresses are public"""
+    parsed = urlparse(url)
+    if parsed.scheme not in ("http", "https") or not parsed.hostname:
+        raise WebArticleError(f"Only http and https URLs are supported: {url}")
+    try:
+        addresses = {info[4][0] for info in socket.getaddrinfo(parsed.hostname, None)}
+    except socket.gaierror as e:
+        raise WebArticleError(f"Could not resolve {parsed.hostname}: {e}")
+    for address in addresses:
+        ip = ipaddress.ip_address(address.split("%")[0])
+        if ip.is_private or ip.is_loopback or ip.is_link_local or ip.is_reserved or ip.is_multicast:
+            raise WebArticleError(f"Refusing to fetch a non-public address: {parsed.hostname}")
+    return sorted(addresses)[0].split("%")[0]
+
+
+class _PinnedHostAdapter(HTTPAdapter):
+    """Connects to an address checked beforehand, sending and verifying the certificate of the URL's host name"""
+
+    def __init__(self, hostname: str):
+        self.hostname = hostname
+        super().__init__()
+
+    def init_poolmanager(self, *args, **kwargs):
+        kwargs["server_hostname"] = self.hostname
+        kwargs["assert_hostname"] = self.hostname
+        super().init_poolmanager(*args, **kwargs)
+
+
+def _get_pinned(session: requests.Session, url: str) -> requests.Response:
+    # The host is resolved once, checked, and connected to by that address, so a second lookup cannot rebind it to
+    # a private one. Redirects are left to fetch_html, which checks every hop before requesting it.
+    address = _check_public_host(url)
+    parsed = urlparse(url)
+    host = f"[{address}]" if ":" in address else address
+    netloc = f"{host}:{parsed.port}" if parsed.port else host
+    if parsed.scheme == "https":
+        session.mount(f"https://{netloc}", _PinnedHostAdapter(parsed.hostname))
+    headers = {"User-Agent": USER_AGENT, "Host": parsed.netloc.rsplit("@", 1)[-1]}
+    return session.get(parsed._replace(netloc=netloc).geturl(), timeout=FETCH_TIMEOUT_SECONDS, headers=headers, stream=True, allow_redirects=False)
+
+
+def _read_html(response: requests.Response) -> str:
+    response.raise_for_status()
+    content_type = response.headers.get("Content-Type", "")
+    if "html" not in content_type:
+        raise WebArticleError(f"URL does not point to an HTML page (Content-Type: {content_type or 'unknown'})")
+
+    content = b""
+    for chunk in response.iter_content(chunk_size=64 * 1024):
+        content += chunk
+        if len(content) > MAX_ARTICLE_BYTES:
+            raise WebArticleError(f"Page is larger than {MAX_ARTICLE_BYTES} bytes")
+
+    encoding = response.encoding or response.apparent_encoding or "utf-8"
+    return content.decode(encoding, errors="replace")
+
+
+def fetch_html(url: str) -> Tuple[str, str]:
+    """
+    Download an HTML page, following at most MAX_REDIRECTS redirects, each to a public address
+
+    Returns:
+        Tuple[str, str]: The decoded HTML and the final URL after redirects
+    """
+    try:
+        for _ in range(MAX_REDIRECTS + 1):
+            with requests.Session() as session, _get_pinned(session, url) as response:
+                if response.status_code in REDIRECT_STATUSES and response.headers.get("Location"):
+                    url = urljoin(url, response.headers["Location"])
+                    continue
+                return _read_html(response), url
+        raise WebArticleError(f"More than {MAX_REDIRECTS} redirects, stopped at {url}")
+    except requests.RequestException as e:
+        raise WebArticleError(f"Could not fetch {url}: {e}")
+
+
+# ------------------------------------------------------------
+# Parsing
+# ------------------------------------------------------------
+
+@dataclass(eq=False)
+class _Node:
+    tag: str
+    attrs: Dict[str, str] = field(default_factory=dict)
+    children: List[Union["_Node", str]] = field(default_factory=list)
+    parent: Optional["_Node"] = None
+
+    def hints(self) -> str:
+        return f"{self.attrs.get('class', '')} {self.attrs.get('id', '')}"
+
+    def elements(self) -> List["_Node"]:
+        return [child for child in self.children if isinstance(child, _Node)]
+
+    def iter(self):
+        yield self
+        for child in self.elements():
+            yield from child.iter()
+
+    def text(self) -> str:
+        return "".join(child if isinstance(child, str) else child.text() for child in self.children)
+
+    def find(self, tag: str) -> Optional["_Node"]:
+        return next((node for node in self.iter() if node.tag == tag), None)
+
+
+class _TreeBuilder(HTMLParser):
+    def __init__(self):
+        super().__init__(convert_charrefs=True)
+        self.root = _Node("document")
+        self.current = self.root
+
+    def handle_starttag(self, tag, attrs):
+        node = _Node(tag, {name: value or "" for name, value in attrs}, parent=self.current)
+        self.current.children.append(node)
+        if tag not in VOID_TAGS:
+            self.current = node
+
+    def handle_startendtag(self, tag, attrs):
+        self.current.children.append(_Node(tag, {name: value or "" for name, value in attrs}, parent=self.current))
+
+    def handle_endtag(self, tag):
+        # Close up to the matching element, ignoring stray end tags
+        node = self.current
+        while node is not self.root and node.tag != tag:
+            node = node.parent  # type: ignore[assignment]
+        if node is not self.root:
+            self.current = node.parent  # type: ignore[assignment]
+
+    def handle_data(self, data):
+        self.current.children.append(data)
+
+
+def _parse(html: str) -> _Node:
+    builder = _TreeBuilder()
+    builder.feed(html)
+    builder.close()
+    return builder.root
+
+
+def _meta(root: _Node, *names: str) -> Optional[str]:
+    for node in root.iter():
+        if node.tag == "meta" and (node.attrs.get("name") in names or node.attrs.get("property") in names):
+            content = node.attrs.get("content", "").strip()
+            if content:
+                return content
+    return None
+
+
+def _remove_boilerplate(node: _Node):
+    kept: List[Union[_Node, str]] = []
+    for child in node.children:
+        if isinstance(child, _Node):
+            hints = child.hints()
+            if child.tag in REMOVED_TAGS:
+                continue
+            if child.tag not in ("body", "html", "article", "main") and NEGATIVE_HINTS.search(hints) and not POSITIVE_HINTS.search(hints):
+                continue
+            _remove_boilerplate(child)
+        kept.append(child)
+    node.children = kept
+
+
+def _link_density(node: _Node) -> float:
+    text_length = len(_collapse(node.text()))
+    if text_length == 0:
+        return 1.0
+    link_length = sum(len(_collapse(link.text())) for link in node.iter() if link.tag == "a")
+    return link_length / text_length
+
+
+def _initial_score(node: _Node) -> float:
+    score = {"article": 10.0, "main": 8.0, "section": 5.0, "div": 5.0, "pre": 3.0, "td": 3.0, "blockquote": 3.0}.get(node.tag, 0.0)
+    hints = node.hints()
+    if POSITIVE_HINTS.search(hints):
+        score += 25.0
+    if NEGATIVE_HINTS.search(hints):
+        score -= 25.0
+    return score
+
+
+def _find_main_content(root: _Node) -> _Node:
+    scores: Dict[_Node, float] = {}
+    for paragraph in root.iter():
+        if paragraph.tag not in ("p", "pre", "td"):
+            continue
+        text = _collapse(paragraph.text())
+        if len(text) < MIN_PARAGRAPH_LENGTH:
+            continue
+
+        content_score = 1.0 + text.count(",") + min(len(text) / 100, 3.0)
+        ancestors = [paragraph.parent, paragraph.parent.parent if paragraph.parent else None]
+        for weight, ancestor in zip((1.0, 0.5), ancestors):
+            if ancestor is None or ancestor is root:
+                continue
+            if ancestor not in scores:
+                scores[ancestor] = _initial_score(ancestor)
+            scores[ancestor] += content_score * weight
+
+    if not scores:
+        return root.find("body") or root
+
+    return max(scores, key=lambda node: scores[node] * (1 - _link_density(node)))
+
+
+# ------------------------------------------------------------
+# Markdown conversion
+# ------------------------------------------------------------
+
+def _collapse(text: str) -> str:
+    return re.sub(r"\s+", " ", text).strip()
+
+
+def _inline(node: Union[_Node, str], base_url: str) -> str:
+    if isinstance(node, str):
+        return re.sub(r"\s+", " ", node)
+
+    inner = "".join(_inline(child, base_url) for child in node.children)
+    if node.tag == "br":
+        return "\n"
+    if node.tag == "a" and node.attrs.get("href") and _collapse(inner):
+        href = urljoin(base_url, node.attrs["href"])
+        return f"[{_collapse(inner)}]({href})" if not href.startswith(("javascript:", "#")) else inner
+    if node.tag in ("strong", "b") and _collapse(inner):
+        return f"**{_collapse(inner)}**"
+    if node.tag in ("em", "i") and _collapse(inner):
+        return f"*{_collapse(inner)}*"
+    if node.tag == "code":
+        return f"`{node.text()}`"
+    if node.tag == "img":
+        alt = _collapse(node.attrs.get("alt", ""))
+        return f"[image: {alt}]" if alt else ""
+    return inner
+
+
+def _blocks(node: _Node, base_url: str, headings: List[Tuple[int, str]]) -> List[str]:
+    """Convert the children of a node to markdown blocks"""
+    blocks: List[str] = []
+    run: List[Union[_Node, str]] = []
+
+    def flush():
+        text = _collapse("".join(_inline(item, base_url) for item in run))
+        if text:
+            blocks.append(text)
+        run.clear()
+
+    for child in node.children:
+        if isinstance(child, str) or child.tag not in BLOCK_TAGS:
+            run.append(child)
+            continue
+        flush()
+        blocks.extend(_block(child, base_url, headings))
+    flush()
+    return blocks
+
+
+def _block(node: _Node, base_url: str, headings: List[Tuple[int, str]]) -> List[str]:
+    if node.tag in HEADING_TAGS:
+        text = _collapse(node.text())
+        if not text:
+            return []
+        headings.append((HEADING_TAGS[node.tag], text))
+        return [f"{'#' * HEADING_TAGS[node.tag]} {text}"]
+    if node.tag == "p":
+        text = "\n".join(_collapse(line) for line in "".join(_inline(child, base_url) for child in node.children).split("\n"))
+        return [text.strip()] if text.strip() else []
+    if node.tag == "pre":
+        return [f"```\n{node.text().strip(chr(10))}\n```"]
+    if node.tag == "hr":
+        return ["---"]
+    if node.tag in ("ul", "ol"):
+        items = []
+        for index, item in enumerate((child for child in node.elements() if child.tag == "li"), start=1):
+            marker = f"{index}." if node.tag == "ol" else "-"
+            content = _blocks(item, base_url, headings)
+            if content:
+                items.append(f"{marker} " + "\n".join(content).replace("\n", "\n   "))
+        return ["\n".join(items)] if items else []
+    if node.tag == "blockquote":
+        content = "\n\n".join(_blocks(node, base_url, headings))
+        return ["\n".join(f"> {line}" if line else ">" for line in content.split("\n"))] if content else []
+    if node.tag == "table":
+        rows = []
+        for row in (candidate for candidate in node.iter() if candidate.tag == "tr"):
+            cells = [_collapse(_inline(cell, base_url)) for cell in row.elements() if cell.tag in ("td", "th")]
+            if cells:
+                rows.append("| " + " | ".join(cells) + " |")
+        if not rows:
+            return []
+        separator = "|" + " --- |" * rows[0].count(" | ") + " --- |"
+        return ["\n".join([rows[0], separator, *rows[1:]])]
+    return _blocks(node, base_url, headings)
+
+
+def extract_article(html: str, url: str) -> ArticleContent:
+    """
+    Pick the main content of an HTML page and convert it to markdown
+
+    Args:
+        html: The page source
+        url: The URL the page was fetched from, used to resolve relative links
+    """
+    root = _parse(html)
+
+    title_node = root.find("title")
+    title = _meta(root, "og:title", "twitter:title") or (_collapse(title_node.text()) if title_node else "")
+    byline = _meta(root, "author", "article:author")
+    keywords = _meta(root, "keywords", "news_keywords")
+
+    _remove_boilerplate(root)
+    content = _find_main_content(root)
+
+    headings: List[Tuple[int, str]] = []
+    blocks = _blocks(content, url, headings)
+    if not blocks:
+        raise WebArticleError("No readable content found on the page")
+
+    # An article that opens with a top level heading carries its own title, which is cleaner than <title>
+    if headings and headings[0][0] == 1 and blocks[0] == f"# {headings[0][1]}":
+        title = headings[0][1]
+        blocks = blocks[1:]
+        headings = headings[1:]
+    if not title:
+        title = urlparse(url).netloc
+
+    header = [f"# {title}"]
+    if byline:
+        header.append(f"*{byline}*")
+    header.append(f"Source: [{url}]({url})")
+    return ArticleContent(title=title, markdown="\n\n".join(header + blocks) + "\n", byline=byline, keywords=keywords, headings=headings)
+
+
+# ------------------------------------------------------------
+# Rendering
+# ------------------------------------------------------------
+
+def _inline_markdown_to_html(text: str) -> str:
+    html = escape(text, quote=False)
+    html = re.sub(r"`([^`]+)`", r"<code>\1</code>", html)
+    html = re.sub(r"\*\*([^*]+)\*\*", r"<b>\1</b>", html)
+    html = re.sub(r"\*([^*]+)\*", r"<i>\1</i>", html)
+    html = re.sub(r"\[([^\]]+)\]\(([^)\s]+)\)", r'<a href="\2">\1</a>', html)
+    return html.replace("\n", "<br/>")
+
+
+def _split_blocks(markdown: str) -> List[str]:
+    # Blank lines separate blocks, except inside fenced code
+    blocks: List[str] = []
+    current: List[str] = []
+    fence: Optional[str] = None
+    for line in markdown.strip().split("\n"):
+        marker = re.match(r"`{3,}", line)
+        if marker and fence is None:
+            fence = marker.group(0)
+        elif marker and line.rstrip() == fence:
+            fence = None
+        if not line.strip() and fence is None:
+            if current:
+                blocks.append("\n".join(current))
+                current = []
+            continue
+        current.append(line)
+    if current:
+        blocks.append("\n".join(current))
+    return blocks
+
+
+def markdown_to_html(markdown: str) -> str:
+    """Render the markdown produced by extract_article as HTML for pymupdf.Story"""
+    html: List[str] = []
+    for block in _split_blocks(markdown):
+        if block.startswith("```"):
+            # Drop the fence lines, including a language info string like ```python
+            code = "\n".join(block.split("\n")[1:-1])
+            html.append(f"<pre>{escape(code, quote=False)}</pre>")
+        elif re.match(r"#{1,6} ", block):
+            level = len(block) - len(block.lstrip("#"))
+            html.append(f"<h{level}>{_inline_markdown_to_html(block[level + 1:])}</h{level}>")
+        elif block == "---":
+            html.append("<hr/>")
+        elif block.startswith(">"):
+            quote = "\n".join(line[1:].lstrip() for line in block.split("\n"))
+            html.append(f"<blockquote>{_inline_markdown_to_html(quote)}</blockquote>")
+        elif re.match(r"(-|\d+\.) ", block):
+            tag = "ul" if block.startswith("-") else "ol"
+            items = re.split(r"\n(?=-|\d+\. )", block)
+            rendered = "".join(f"<li>{_inline_markdown_to_html(re.sub(r'^(-|\d+\.) ', '', item).replace(chr(10) + '   ', chr(10)))}</li>" for item in items)
+            html.append(f"<{tag}>{rendered}</{tag}>")
+        elif block.startswith("|"):
+            rows = [row for row in block.split("\n") if not re.fullmatch(r"\|( --- \|)+", row)]
+            rendered = "".join("<tr>" + "".join(f"<td>{_inline_markdown_to_html(cell.strip())}</td>" for cell in row.strip("|").split(" | ")) + "</tr>" for row in rows)
+            html.append(f"<table>{rendered}</table>")
+        else:
+            html.append(f"<p>{_inline_markdown_to_html(block)}</p>")
+    return "\n".join(html)
+
+
+def render_markdown_to_pdf(markdown: str, pdf_path: Path) -> int:
+    """
+    Lay out markdown on A4 pages
+
+    Returns:
+        int: The number of pages written
+    """
+    story = pymupdf.Story(html=markdown_to_html(markdown), user_css=ARTICLE_CSS)
+    mediabox = pymupdf.paper_rect("a4")
+    where = mediabox + (54, 54, -54, -54)
+
+    writer = pymupdf.DocumentWriter(str(pdf_path))
+    page_count = 0
+    more = True
+    while more:
+        device = writer.begin_page(mediabox)
+        more, _ = story.place(where)
+        story.draw(device)
+        writer.end_page()
+        page_count += 1
+    writer.close()
+    return page_count
+
+
+# ------------------------------------------------------------
+# Segmentation
+# ------------------------------------------------------------
+
+def _normalize(text: str) -> str:
+    return _collapse(text).lower()
+
+
+def find_heading_pages(pdf_path: Path, headings: List[Tuple[int, str]]) -> List[int]:
+    """Locate each heading in the rendered PDF, searching forward from the previous heading"""
+    with pymupdf.open(pdf_path) as document:
+        page_texts = [_normalize(page.get_text()) for page in document]
+
+    pages: List[int] = []
+    current = 0
+    for _, text in headings:
+        needle = _normalize(text)
+        found = next((number for number in range(current, len(page_texts)) if needle in page_texts[number]), current)
+        pages.append(found)
+        current = found
+    return pages
+
+
+def headings_to_toc(title: str, headings: List[Tuple[int, str]], page_numbers: List[int]) -> dict:
+    """
+    Build the TOC structure expected by LazyTextbookReader.save_toc from article headings
+
+    The shallowest heading level becomes chapters and the next level sections, an article without
+    headings becomes a single chapter.
+    """
+    levels = sorted({level for level, _ in headings})
+    if not levels:
+        return {"chapters": [{"index_string": "1", "title": title, "page_number": 0, "sections": []}]}
+
+    chapter_level = levels[0]
+    section_level = levels[1] if len(levels) > 1 else None
+    chapters: List[dict] = []
+    used_titles: Dict[str, int] = {}
+
+    def unique(text: str) -> str:
+        count = used_titles.get(text, 0) + 1
+        used_titles[text] = count
+        return text if count == 1 else f"{text} ({count})"
+
+    for (level, text), page_number in zip(headings, page_numbers):
+        if level == chapter_level:
+            chapters.append({"index_string": str(len(chapters) + 1), "title": unique(text), "page_number": page_number, "sections": []})
+        elif level == section_level:
+            if not chapters:
+                chapters.append({"index_string": "0", "title": unique(title), "page_number": 0, "sections": []})
+            sections = chapters[-1]["sections"]
+            sections.append({"index_string": f"{chapters[-1]['index_string']}.{len(sections) + 1}", "title": unique(text), "page_number": page_number})
+
+    # Content before the first chapter heading belongs to an introduction chapter
+    if chapters[0]["page_number"] > 0:
+        chapters.insert(0, {"index_string": "0", "title": unique(title), "page_number": 0, "sections": []})
+    return {"chapters": chapters}
+
+
+def ingest_article(url: str, pdf_path: Path, markdown_path: Path, fetch: Callable[[str], Tuple[str, str]] = fetch_html) -> Tuple[ArticleContent, dict]:
+    """
+    Fetch an article, store it as markdown and PDF, and derive its TOC
+
+    Returns:
+        Tuple[ArticleContent, dict]: The extracted article and its TOC for LazyTextbookReader.save_toc
+    """
+    html, final_url = fetch(url)
+    article = extract_article(html, final_url)
+
+    with open(markdown_path, "w", encoding="utf-8") as f:
+        f.write(article.markdown)
+    render_markdown_to_pdf(article.markdown, pdf_path)
+
+    page_numbers = find_heading_pages(pdf_path, article.headings)
+    return article, headings_to_toc(article.title, article.headings, page_numbers)
@@ -0,0 +1,196 @@
+# Instructor workspaces
+# An instructor teaching a class creates a workspace and adds the students to its roster. Readings (the sections of
+# a chapter or of a whole book, enqueued in each student's reading queue) and quizzes (the diagnostic quiz of a
+# chapter, see chapter_quiz) are assigned to the workspace and delivered to every student on the roster, including
+# students added later. Instructors see how the class does per topic, a chapter of the books assigned: how many
+# students read it and took its quiz, the mean quiz score and how well its flashcards are recalled. The aggregates
+# are anonymous, rates over fewer than MIN_GROUP_SIZE students are withheld so no single student can be singled out,
+# and a student's own readings, quizzes and reviews are only shown to instructors when the student shares them.
+
+from collections import defaultdict
+from dataclasses import dataclass
+from datetime import datetime
+from statistics import mean
+from typing import Dict, List, Optional
+
+from textbook.chapter_quiz import DEFAULT_QUIZ_SIZE, ChapterQuizProgress, chapter_quiz_progress, enqueue_chapter_quiz
+from textbook.database import AssignmentInfo, ReadingItemInfo, TextBookDatabase, WorkspaceInfo, WorkspaceMemberInfo
+from textbook.reading_queue import enqueue_section
+from textbook.review import GRADE_GOOD
+
+ROLE_INSTRUCTOR = "instructor"
+ROLE_STUDENT = "student"
+ROLES = (ROLE_INSTRUCTOR, ROLE_STUDENT)
+
+ASSIGNMENT_READING = "reading"
+ASSIGNMENT_QUIZ = "quiz"
+ASSIGNMENT_KINDS = (ASSIGNMENT_READING, ASSIGNMENT_QUIZ)
+
+MIN_GROUP_SIZE = 3  # Students a rate is computed over at least, fewer could identify them
+
+
+@dataclass
+class TopicAnalytics:
+    book_id: int
+    chapter_id: int
+    title: str
+    students: int  # On the roster
+    read: int  # Students done with every section of the chapter
+    quizzes_completed: int
+    quiz_score: Optional[float]  # Mean score of the completed quizzes, None under MIN_GROUP_SIZE of them
+    reviewers: int  # Students who reviewed the chapter's flashcards
+    retention: Optional[float]  # Share of those reviews recalled (good or easy), None under MIN_GROUP_SIZE reviewers
+
+
+@dataclass
+class StudentActivity:
+    user_id: str
+    readings: List[ReadingItemInfo]  # Of the books assigned
+    quizzes: List[ChapterQuizProgress]  # Of the books assigned
+    review_grades: Dict[int, List[int]]  # By chapter
+
+
+def require_member(database: TextBookDatabase, workspace_id: int, user_id: str, role: Optional[str] = None) -> WorkspaceMemberInfo:
+    """
+    The membership of a user in a workspace
+
+    Raises:
+        ValueError: If the workspace does not exist
+        PermissionError: If the user is not on the roster, or has another role than the one required
+    """
+    if database.get_workspace(workspace_id) is None:
+        raise ValueError(f"Workspace not found: {workspace_id}")
+    member = database.get_workspace_member(workspace_id, user_id)
+    if member is None:
+        raise PermissionError(f"{user_id} is not on the roster of workspace {workspace_id}")
+    if role is not None and member.role != role:
+        raise PermissionError(f"{user_id} is not a {role} of workspace {workspace_id}")
+    return member
+
+
+def create_workspace(database: TextBookDatabase, name: str, instructor_id: str) -> WorkspaceInfo:
+    if not name.strip():
+        raise ValueError("A workspace needs a name")
+    return database.create_workspace(WorkspaceInfo(name=name.strip()), WorkspaceMemberInfo(user_id=instructor_id, role=ROLE_INSTRUCTOR))
+
+
+def deliver(database: TextBookDatabase, assignment: AssignmentInfo, user_id: str, quiz_size: int = DEFAULT_QUIZ_SIZE, now: Optional[datetime] = None) -> int:
+    """Enqueue an assignment for a student, returns how many reading items and quizzes were enqueued"""
+    if assignment.assignment_kind == ASSIGNMENT_QUIZ:
+        return 1 if enqueue_chapter_quiz(database, user_id, assignment.book_id, assignment.chapter_id, quiz_size) is not None else 0
+    # Sections already in the student's queue, read or not, are not enqueued again
+    queued = {item.section_id for item in database.get_reading_items(user_id, assignment.book_id, include_done=True)}
+    sections = [
+        section for section in database.get_sections_by_book_id(assignment.book_id)
+        if (assignment.chapter_id is None or section.chapter_id == assignment.chapter_id) and section.section_id not in queued
+    ]
+    for section in sections:
+        enqueue_section(database, user_id, assignment.book_id, section.section_id, now)
+    return len(sections)
+
+
+def add_member(database: TextBookDatabase, workspace_id: int, user_id: str, role: str = ROLE_STUDENT) -> WorkspaceMemberInfo:
+    """Add a user to the roster, delivering the workspace's assignments to students; raises ValueError for members already on it"""
+    if role not in ROLES:
+        raise ValueError(f"Unsupported role: {role}, expected one of {', '.join(ROLES)}")
+    if database.get_workspace_member(workspace_id, user_id) is not None:
+        raise ValueError(f"{user_id} is already on the roster of workspace {workspace_id}")
+    member = database.add_workspace_member(WorkspaceMemberInfo(workspace_id=workspace_id, user_id=user_id, role=role))
+    if role == ROLE_STUDENT:
+        for assignment in database.get_assignments(workspace_id):
+            deliver(database, assignment, user_id)
+    return member
+
+
+def assign(
+    database: TextBookDatabase,
+    workspace_id: int,
+    kind: str,
+    book_id: int,
+    chapter_id: Optional[int] = None,
+    due_at: Optional[datetime] = None,
+    quiz_size: int = DEFAULT_QUIZ_SIZE,
+) -> AssignmentInfo:
+    """Assign a reading or a quiz to a workspace and deliver it to its students; raises ValueError for invalid ones"""
+    if kind not in ASSIGNMENT_KINDS:
+        raise ValueError(f"Unsupported assignment kind: {kind}, expected one of {', '.join(ASSIGNMENT_KINDS)}")
+    if database.get_book_by_id(book_id) is None:
+        raise ValueError(f"Book not found: {book_id}")
+    if chapter_id is None and kind == ASSIGNMENT_QUIZ:
+        raise ValueError("A quiz is assigned on a chapter")
+    if chapter_id is not None and chapter_id not in {chapter.chapter_id for chapter in database.get_chapters_by_book_id(book_id)}:
+        raise ValueError(f"Chapter {chapter_id} not found in book {book_id}")
+    assignment = database.create_assignment(AssignmentInfo(workspace_id=workspace_id, assignment_kind=kind, book_id=book_id, chapter_id=chapter_id, due_at=due_at))
+    for member in database.get_workspace_members(workspace_id, ROLE_STUDENT):
+        deliver(database, assignment, member.user_id, quiz_size)
+    return assignment
+
+
+def _rate(values: List[float], contributors: int) -> Optional[float]:
+    return mean(values) if values and contributors >= MIN_GROUP_SIZE else None
+
+
+def topic_analytics(database: TextBookDatabase, workspace_id: int) -> List[TopicAnalytics]:
+    """How the students of a workspace do on every chapter assigned, in aggregate"""
+    students = [member.user_id for member in database.get_workspace_members(workspace_id, ROLE_STUDENT)]
+    assigned: Dict[int, set] = defaultdict(set)
+    for assignment in database.get_assignments(workspace_id):
+        chapters = database.get_chapters_by_book_id(assignment.book_id)
+        assigned[assignment.book_id].update(chapter.chapter_id for chapter in chapters if assignment.chapter_id in (None, chapter.chapter_id))
+
+    analytics = []
+    for book_id, chapter_ids in assigned.items():
+        sections = database.get_sections_by_book_id(book_id)
+        done = {
+            user_id: {item.section_id for item in database.get_reading_items(user_id, book_id, include_done=True) if item.done_at is not None}
+            for user_id in students
+        }
+        grades: Dict[int, Dict[str, List[int]]] = defaultdict(lambda: defaultdict(list))
+        for user_id, chapter_id, grade in database.get_chapter_review_grades(students, book_id):
+            grades[chapter_id][user_id].append(grade)
+        for chapter in database.get_chapters_by_book_id(book_id):
+            if chapter.chapter_id not in chapter_ids:
+                continue
+            chapter_sections = {section.section_id for section in sections if section.chapter_id == chapter.chapter_id}
+            quizzes = [database.get_chapter_quiz_by_chapter(user_id, chapter.chapter_id) for user_id in students]
+            scores = [chapter_quiz_progress(database, quiz).score for quiz in quizzes if quiz is not None and quiz.completed_at is not None]
+            reviews = grades.get(chapter.chapter_id, {})
+            analytics.append(TopicAnalytics(
+                book_id=book_id,
+                chapter_id=chapter.chapter_id,
+                title=chapter.title,
+                students=len(students),
+                read=sum(1 for user_id in students if chapter_sections and chapter_sections <= done[user_id]),
+                quizzes_completed=len(scores),
+                quiz_score=_rate([score for score in scores if score is not None], len(scores)),
+                reviewers=len(reviews),
+                retention=_rate([1.0 if grade >= GRADE_GOOD else 0.0 for user_grades in reviews.values() for grade in user_grades], len(reviews)),
+            ))
+    return analytics
+
+
+def student_activity(database: TextBookDatabase, workspace_id: int, user_id: str) -> StudentActivity:
+    """
+    A student's readings, quizzes and reviews of what the workspace assigned
+
+    Raises:
+        PermissionError: If the user is not a student of the workspace or does not share their attempts
+    """
+    member = require_member(database, workspace_id, user_id, ROLE_STUDENT)
+    if not member.share_attempts:
+        raise PermissionError(f"{user_id} does not share their attempts with the instructors of workspace {workspace_id}")
+    assignments = database.get_assignments(workspace_id)
+    book_ids = sorted({assignment.book_id for assignment in assignments})
+    chapter_ids = {chapter.chapter_id for book_id in book_ids for chapter in database.get_chapters_by_book_id(book_id)}
+    readings = [item for book_id in book_ids for item in database.get_reading_items(user_id, book_id, include_done=True)]
+    quizzes = [
+        chapter_quiz_progress(database, quiz)
+        for quiz in database.get_chapter_quizzes(user_id, include_completed=True)
+        if quiz.chapter_id in chapter_ids
+    ]
+    review_grades: Dict[int, List[int]] = defaultdict(list)
+    for book_id in book_ids:
+        for _, chapter_id, grade in database.get_chapter_review_grades([user_id], book_id):
+            if chapter_id is not None:
+                review_grades[chapter_id].append(grade)
+    return StudentActivity(user_id, readings, quizzes, dict(review_grades))